@@ -0,0 +1,1477 @@
+use crate::{portfolio_weights, user_portfolio_assets, PORTFOLIO_ASSETS};
+use serde_json::{json, Map, Value};
+use solana_sdk::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use spl_token::{
+    instruction::TokenInstruction,
+    state::{
+        Account, AssetEntry, DcaConfig, DcaPosition, DepositLimits, DepositRecord, HedgeConfig,
+        HedgeEpoch, Mint, Multisig, Portfolio, Position, ProgramConfig, StateAccount, StopLoss,
+        TakeProfit, UserPortfolio, WithdrawTicket,
+    },
+};
+use std::{
+    fs,
+    io::{self, Read},
+    str::FromStr,
+};
+
+/// Account layouts `decode-account` knows about.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AccountType {
+    Mint,
+    Account,
+    Multisig,
+    Portfolio,
+    UserPortfolio,
+    HedgeConfig,
+    Position,
+    ProgramConfig,
+    AssetEntry,
+    DepositLimits,
+    DepositRecord,
+    StopLoss,
+    TakeProfit,
+    DcaConfig,
+    DcaPosition,
+    WithdrawTicket,
+    HedgeEpoch,
+}
+
+impl AccountType {
+    pub const NAMES: &'static [&'static str] = &[
+        "mint",
+        "account",
+        "multisig",
+        "portfolio",
+        "user-portfolio",
+        "hedge-config",
+        "position",
+        "program-config",
+        "asset-entry",
+        "deposit-limits",
+        "deposit-record",
+        "stop-loss",
+        "take-profit",
+        "dca-config",
+        "dca-position",
+        "withdraw-ticket",
+        "hedge-epoch",
+    ];
+
+    const ALL: &'static [AccountType] = &[
+        AccountType::Mint,
+        AccountType::Account,
+        AccountType::Multisig,
+        AccountType::Portfolio,
+        AccountType::UserPortfolio,
+        AccountType::HedgeConfig,
+        AccountType::Position,
+        AccountType::ProgramConfig,
+        AccountType::AssetEntry,
+        AccountType::DepositLimits,
+        AccountType::DepositRecord,
+        AccountType::StopLoss,
+        AccountType::TakeProfit,
+        AccountType::DcaConfig,
+        AccountType::DcaPosition,
+        AccountType::WithdrawTicket,
+        AccountType::HedgeEpoch,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|account_type| account_type.name() == name)
+    }
+
+    /// The layout of an account of the token program. State accounts are told
+    /// apart by their type and version header; mints, token accounts and
+    /// multisigs, which have none, by their size.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if let Ok(state) = StateAccount::unpack(data) {
+            return Some(match state {
+                StateAccount::HedgeConfig(_) => AccountType::HedgeConfig,
+                StateAccount::Position(_) => AccountType::Position,
+                StateAccount::Portfolio(_) => AccountType::Portfolio,
+                StateAccount::UserPortfolio(_) => AccountType::UserPortfolio,
+                StateAccount::ProgramConfig(_) => AccountType::ProgramConfig,
+                StateAccount::AssetEntry(_) => AccountType::AssetEntry,
+                StateAccount::DepositLimits(_) => AccountType::DepositLimits,
+                StateAccount::DepositRecord(_) => AccountType::DepositRecord,
+                StateAccount::StopLoss(_) => AccountType::StopLoss,
+                StateAccount::TakeProfit(_) => AccountType::TakeProfit,
+                StateAccount::DcaConfig(_) => AccountType::DcaConfig,
+                StateAccount::DcaPosition(_) => AccountType::DcaPosition,
+                StateAccount::WithdrawTicket(_) => AccountType::WithdrawTicket,
+                StateAccount::HedgeEpoch(_) => AccountType::HedgeEpoch,
+            });
+        }
+        match data.len() {
+            Mint::LEN => Some(AccountType::Mint),
+            Account::LEN => Some(AccountType::Account),
+            Multisig::LEN => Some(AccountType::Multisig),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AccountType::Mint => "mint",
+            AccountType::Account => "account",
+            AccountType::Multisig => "multisig",
+            AccountType::Portfolio => "portfolio",
+            AccountType::UserPortfolio => "user-portfolio",
+            AccountType::HedgeConfig => "hedge-config",
+            AccountType::Position => "position",
+            AccountType::ProgramConfig => "program-config",
+            AccountType::AssetEntry => "asset-entry",
+            AccountType::DepositLimits => "deposit-limits",
+            AccountType::DepositRecord => "deposit-record",
+            AccountType::StopLoss => "stop-loss",
+            AccountType::TakeProfit => "take-profit",
+            AccountType::DcaConfig => "dca-config",
+            AccountType::DcaPosition => "dca-position",
+            AccountType::WithdrawTicket => "withdraw-ticket",
+            AccountType::HedgeEpoch => "hedge-epoch",
+        }
+    }
+}
+
+/// Account data read by `decode-account`, with the address and owner when the
+/// input carried them.
+pub struct EncodedAccount {
+    pub pubkey: Option<Pubkey>,
+    pub owner: Option<Pubkey>,
+    pub data: Vec<u8>,
+}
+
+/// Instruction read by `decode-instruction`.
+pub struct EncodedInstruction {
+    pub data: Vec<u8>,
+    pub accounts: Vec<Pubkey>,
+}
+
+/// Read the file at `path`, or standard input if `path` is `-`.
+pub fn read_input(path: &str) -> Result<Vec<u8>, String> {
+    if path == "-" {
+        let mut input = vec![];
+        io::stdin()
+            .read_to_end(&mut input)
+            .map_err(|e| format!("Could not read standard input: {}", e))?;
+        Ok(input)
+    } else {
+        fs::read(path).map_err(|e| format!("Could not read {}: {}", path, e))
+    }
+}
+
+/// Decode `text` from `encoding`, one of `base58`, `base64` or `hex`.
+pub fn decode_data(text: &str, encoding: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    match encoding {
+        "base58" => bs58::decode(text)
+            .into_vec()
+            .map_err(|e| format!("Invalid base58 data: {}", e)),
+        "base64" => base64::decode(text).map_err(|e| format!("Invalid base64 data: {}", e)),
+        "hex" => {
+            let text = text.trim_start_matches("0x");
+            if text.len() % 2 == 1 {
+                return Err("Invalid hex data: odd number of digits".to_string());
+            }
+            (0..text.len())
+                .step_by(2)
+                .map(|i| {
+                    u8::from_str_radix(&text[i..i + 2], 16)
+                        .map_err(|e| format!("Invalid hex data: {}", e))
+                })
+                .collect()
+        }
+        _ => Err(format!("Unsupported encoding {}", encoding)),
+    }
+}
+
+fn parse_pubkey(value: &Value, what: &str) -> Result<Pubkey, String> {
+    value
+        .as_str()
+        .and_then(|pubkey| Pubkey::from_str(pubkey).ok())
+        .ok_or_else(|| format!("Invalid {}: {}", what, value))
+}
+
+/// The data of a JSON account, either a string in `encoding` or an RPC style
+/// `[data, encoding]` pair.
+fn json_data(value: &Value, encoding: &str) -> Result<Vec<u8>, String> {
+    match value {
+        Value::String(text) => decode_data(text, encoding),
+        Value::Array(pair) => match (pair.first(), pair.get(1)) {
+            (Some(Value::String(text)), Some(Value::String(encoding))) => {
+                decode_data(text, encoding)
+            }
+            _ => Err(format!("Invalid account data: {}", value)),
+        },
+        _ => Err(format!("Invalid account data: {}", value)),
+    }
+}
+
+/// Parse the input of `decode-account`: the output of `solana account --output
+/// json`, an RPC account object, raw account bytes, or the data as text in
+/// `encoding`.
+pub fn parse_account_input(input: &[u8], encoding: &str) -> Result<EncodedAccount, String> {
+    let text = match std::str::from_utf8(input) {
+        Ok(text) => text.trim(),
+        Err(_) => {
+            return Ok(EncodedAccount {
+                pubkey: None,
+                owner: None,
+                data: input.to_vec(),
+            })
+        }
+    };
+    if !text.starts_with('{') {
+        return Ok(EncodedAccount {
+            pubkey: None,
+            owner: None,
+            data: decode_data(text, encoding)?,
+        });
+    }
+
+    let value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let account = value.get("account").unwrap_or(&value);
+    let data = account
+        .get("data")
+        .ok_or_else(|| "The account has no data".to_string())?;
+    Ok(EncodedAccount {
+        pubkey: value
+            .get("pubkey")
+            .map(|pubkey| parse_pubkey(pubkey, "account address"))
+            .transpose()?,
+        owner: account
+            .get("owner")
+            .map(|owner| parse_pubkey(owner, "account owner"))
+            .transpose()?,
+        data: json_data(data, encoding)?,
+    })
+}
+
+/// Parse the input of `decode-instruction`: the data as text in `encoding`, or
+/// a JSON object with the `data` and the `accounts` (or web3.js `keys`) of the
+/// instruction.
+pub fn parse_instruction_input(input: &[u8], encoding: &str) -> Result<EncodedInstruction, String> {
+    let text = std::str::from_utf8(input)
+        .map_err(|_| "The instruction must be given as text".to_string())?
+        .trim();
+    if !text.starts_with('{') {
+        return Ok(EncodedInstruction {
+            data: decode_data(text, encoding)?,
+            accounts: vec![],
+        });
+    }
+
+    let value: Value = serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let data = value
+        .get("data")
+        .ok_or_else(|| "The instruction has no data".to_string())?;
+    let accounts = match value.get("accounts").or_else(|| value.get("keys")) {
+        Some(Value::Array(accounts)) => accounts
+            .iter()
+            .map(|account| parse_pubkey(account.get("pubkey").unwrap_or(account), "account"))
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(accounts) => return Err(format!("Invalid accounts: {}", accounts)),
+        None => vec![],
+    };
+    Ok(EncodedInstruction {
+        data: json_data(data, encoding)?,
+        accounts,
+    })
+}
+
+fn pubkey_json(pubkey: &Pubkey) -> Value {
+    json!(pubkey.to_string())
+}
+
+fn coption_json(option: &COption<u64>) -> Value {
+    match option {
+        COption::Some(value) => json!(value),
+        COption::None => Value::Null,
+    }
+}
+
+fn coption_pubkey_json(option: &COption<Pubkey>) -> Value {
+    match option {
+        COption::Some(pubkey) => pubkey_json(pubkey),
+        COption::None => Value::Null,
+    }
+}
+
+/// Decode account `data` with the `account_type` layout.
+pub fn decode_account(account_type: AccountType, data: &[u8]) -> Result<Value, String> {
+    let invalid = |e| format!("Invalid {} data: {}", account_type.name(), e);
+    let fields = match account_type {
+        AccountType::Mint => {
+            let mint = Mint::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "mintAuthority": coption_pubkey_json(&mint.mint_authority),
+                "supply": mint.supply,
+                "decimals": mint.decimals,
+                "isInitialized": mint.is_initialized,
+                "freezeAuthority": coption_pubkey_json(&mint.freeze_authority),
+            })
+        }
+        AccountType::Account => {
+            let account = Account::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "mint": pubkey_json(&account.mint),
+                "owner": pubkey_json(&account.owner),
+                "amount": account.amount,
+                "delegate": coption_pubkey_json(&account.delegate),
+                "state": format!("{:?}", account.state).to_lowercase(),
+                "rentExemptReserve": coption_json(&account.is_native),
+                "delegatedAmount": account.delegated_amount,
+                "closeAuthority": coption_pubkey_json(&account.close_authority),
+            })
+        }
+        AccountType::Multisig => {
+            let multisig = Multisig::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "m": multisig.m,
+                "n": multisig.n,
+                "isInitialized": multisig.is_initialized,
+                "signers": multisig
+                    .signers
+                    .iter()
+                    .take(multisig.n as usize)
+                    .map(pubkey_json)
+                    .collect::<Vec<_>>(),
+            })
+        }
+        AccountType::Portfolio => {
+            let portfolio = Portfolio::unpack_unchecked(data).map_err(invalid)?;
+            let assets = (1..=PORTFOLIO_ASSETS as u8)
+                .zip(portfolio_weights(&portfolio).iter())
+                .filter_map(|(index, weight)| {
+                    portfolio
+                        .asset_schedule(index)
+                        .map(|(mint, sell_into, period)| {
+                            json!({
+                                "index": index,
+                                "mint": pubkey_json(&mint),
+                                "weight": weight,
+                                "period": period,
+                                "sellInto": pubkey_json(&sell_into),
+                            })
+                        })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "portfolioAccount": pubkey_json(&portfolio.portfolio_account),
+                "creator": pubkey_json(&portfolio.creator_portfolio),
+                "initialized": portfolio.is_initialize == 1,
+                "metadataUrl": String::from_utf8_lossy(&portfolio.metadataUrl)
+                    .trim_end_matches('\0'),
+                "metadataHash": portfolio.metadataHash,
+                "assets": assets,
+            })
+        }
+        AccountType::UserPortfolio => {
+            let user_portfolio = UserPortfolio::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "userPortfolioAccount": pubkey_json(&user_portfolio.user_portfolio_account),
+                "owner": pubkey_json(&user_portfolio.owner),
+                "portfolio": pubkey_json(&user_portfolio.portfolio_address),
+                "delegate": pubkey_json(&user_portfolio.delegate),
+                "delegatedAmount": user_portfolio.delegated_amount,
+                "assetAccounts": user_portfolio_assets(&user_portfolio)
+                    .iter()
+                    .map(pubkey_json)
+                    .collect::<Vec<_>>(),
+            })
+        }
+        AccountType::HedgeConfig => {
+            let hedge_config = HedgeConfig::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "mint": pubkey_json(&hedge_config.mint),
+                "authority": pubkey_json(&hedge_config.authority),
+                "assetMint": pubkey_json(&hedge_config.asset_mint),
+                "stableMint": pubkey_json(&hedge_config.stable_mint),
+                "swapProgramId": pubkey_json(&hedge_config.swap_program_id),
+                "swapPool": pubkey_json(&hedge_config.swap_pool),
+                "assetVault": pubkey_json(&hedge_config.asset_vault),
+                "stableVault": pubkey_json(&hedge_config.stable_vault),
+                "feeAccount": pubkey_json(&hedge_config.fee_account),
+                "targetRatioBps": hedge_config.target_ratio_bps,
+                "depositFeeBps": hedge_config.deposit_fee_bps,
+                "withdrawFeeBps": hedge_config.withdraw_fee_bps,
+                "assetScale": hedge_config.asset_scale.to_string(),
+                "stablePerBase": hedge_config.stable_per_base.to_string(),
+                "epoch": hedge_config.epoch,
+                "previousStablePerBase": hedge_config.previous_stable_per_base.to_string(),
+                "isInitialized": hedge_config.is_initialized,
+            })
+        }
+        AccountType::Position => {
+            let position = Position::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "account": pubkey_json(&position.account),
+                "asset": position.asset,
+                "usdc": position.usdc,
+                "rewardPerSharePaid": position.reward_per_share_paid.to_string(),
+                "rewards": position.rewards,
+                "tracksCostBasis": position.tracks_cost_basis,
+                "costBasis": position.cost_basis,
+                "deposited": position.deposited,
+                "withdrawn": position.withdrawn,
+                "realizedPnl": position.realized_pnl,
+                "lastDepositSlot": position.last_deposit_slot,
+                "assetScalePaid": position.asset_scale_paid.to_string(),
+                "stablePerBasePaid": position.stable_per_base_paid.to_string(),
+                "hedgeEpoch": position.hedge_epoch,
+                "isInitialized": position.is_initialized,
+            })
+        }
+        AccountType::ProgramConfig => {
+            let program_config = ProgramConfig::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "admin": pubkey_json(&program_config.admin),
+                "guardian": pubkey_json(&program_config.guardian),
+                "paused": program_config.paused,
+                "protocolFeeBps": program_config.protocol_fee_bps,
+                "protocolFeeOwner": pubkey_json(&program_config.protocol_fee_owner),
+                "withdrawDelaySlots": program_config.withdraw_delay_slots,
+                "bypassWithdrawDelay": program_config.bypass_withdraw_delay,
+                "isInitialized": program_config.is_initialized,
+            })
+        }
+        AccountType::AssetEntry => {
+            let asset_entry = AssetEntry::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "mint": pubkey_json(&asset_entry.mint),
+                "kind": format!("{:?}", asset_entry.kind).to_lowercase(),
+                "decimals": asset_entry.decimals,
+                "oracle": pubkey_json(&asset_entry.oracle),
+                "swapProgramId": pubkey_json(&asset_entry.swap_program_id),
+                "swapPool": pubkey_json(&asset_entry.swap_pool),
+                "isInitialized": asset_entry.is_initialized,
+            })
+        }
+        AccountType::DepositLimits => {
+            let deposit_limits = DepositLimits::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "target": pubkey_json(&deposit_limits.target),
+                "maxSupply": deposit_limits.max_supply,
+                "maxDeposit": deposit_limits.max_deposit,
+                "maxOwnerDeposit": deposit_limits.max_owner_deposit,
+                "supply": deposit_limits.supply,
+                "isInitialized": deposit_limits.is_initialized,
+            })
+        }
+        AccountType::DepositRecord => {
+            let deposit_record = DepositRecord::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "target": pubkey_json(&deposit_record.target),
+                "owner": pubkey_json(&deposit_record.owner),
+                "deposited": deposit_record.deposited,
+                "isInitialized": deposit_record.is_initialized,
+            })
+        }
+        AccountType::StopLoss => {
+            let stop_loss = StopLoss::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "mint": pubkey_json(&stop_loss.mint),
+                "oracle": pubkey_json(&stop_loss.oracle),
+                "stopPrice": stop_loss.stop_price,
+                "trailingBps": stop_loss.trailing_bps,
+                "maxSlippageBps": stop_loss.max_slippage_bps,
+                "assetDecimals": stop_loss.asset_decimals,
+                "stableDecimals": stop_loss.stable_decimals,
+                "highPrice": stop_loss.high_price,
+                "triggered": stop_loss.triggered,
+                "triggeredSlot": stop_loss.triggered_slot,
+                "triggeredPrice": stop_loss.triggered_price,
+                "assetSold": stop_loss.asset_sold,
+                "stableBought": stop_loss.stable_bought,
+                "isInitialized": stop_loss.is_initialized,
+            })
+        }
+        AccountType::TakeProfit => {
+            let take_profit = TakeProfit::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "mint": pubkey_json(&take_profit.mint),
+                "oracle": pubkey_json(&take_profit.oracle),
+                "triggerBps": take_profit.trigger_bps,
+                "sellBps": take_profit.sell_bps,
+                "maxSlippageBps": take_profit.max_slippage_bps,
+                "distribute": take_profit.distribute,
+                "assetDecimals": take_profit.asset_decimals,
+                "stableDecimals": take_profit.stable_decimals,
+                "entryPrice": take_profit.entry_price,
+                "entryExpo": take_profit.entry_expo,
+                "rewardPerShare": take_profit.reward_per_share.to_string(),
+                "reservedRewards": take_profit.reserved_rewards,
+                "realizedProfit": take_profit.realized_profit,
+                "lastHarvestSlot": take_profit.last_harvest_slot,
+                "isInitialized": take_profit.is_initialized,
+            })
+        }
+        AccountType::DcaConfig => {
+            let dca_config = DcaConfig::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "portfolio": pubkey_json(&dca_config.portfolio),
+                "assetIndex": dca_config.asset_index,
+                "assetMint": pubkey_json(&dca_config.asset_mint),
+                "stableMint": pubkey_json(&dca_config.stable_mint),
+                "swapProgramId": pubkey_json(&dca_config.swap_program_id),
+                "swapPool": pubkey_json(&dca_config.swap_pool),
+                "assetVault": pubkey_json(&dca_config.asset_vault),
+                "stableVault": pubkey_json(&dca_config.stable_vault),
+                "oracle": pubkey_json(&dca_config.oracle),
+                "maxSlippageBps": dca_config.max_slippage_bps,
+                "assetDecimals": dca_config.asset_decimals,
+                "stableDecimals": dca_config.stable_decimals,
+                "periodSlots": dca_config.period_slots,
+                "tranches": dca_config.tranches,
+                "totalUsdc": dca_config.total_usdc,
+                "totalAsset": dca_config.total_asset,
+                "isInitialized": dca_config.is_initialized,
+            })
+        }
+        AccountType::DcaPosition => {
+            let dca_position = DcaPosition::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "dcaConfig": pubkey_json(&dca_position.dca_config),
+                "owner": pubkey_json(&dca_position.owner),
+                "usdc": dca_position.usdc,
+                "trancheAmount": dca_position.tranche_amount,
+                "asset": dca_position.asset,
+                "nextExecutionSlot": dca_position.next_execution_slot,
+                "isInitialized": dca_position.is_initialized,
+            })
+        }
+        AccountType::WithdrawTicket => {
+            let withdraw_ticket = WithdrawTicket::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "account": pubkey_json(&withdraw_ticket.account),
+                "amount": withdraw_ticket.amount,
+                "requestSlot": withdraw_ticket.request_slot,
+                "claimableSlot": withdraw_ticket.claimable_slot,
+                "isInitialized": withdraw_ticket.is_initialized,
+            })
+        }
+        AccountType::HedgeEpoch => {
+            let hedge_epoch = HedgeEpoch::unpack_unchecked(data).map_err(invalid)?;
+            json!({
+                "mint": pubkey_json(&hedge_epoch.mint),
+                "epoch": hedge_epoch.epoch,
+                "stablePerBase": hedge_epoch.stable_per_base.to_string(),
+                "isInitialized": hedge_epoch.is_initialized,
+            })
+        }
+    };
+    Ok(json!({
+        "type": account_type.name(),
+        "fields": fields,
+    }))
+}
+
+/// Names of the accounts an instruction expects, in order, and whether
+/// multisignature signers may follow them.
+fn instruction_accounts(instruction: &TokenInstruction) -> (Vec<String>, bool) {
+    use TokenInstruction::*;
+
+    let names: Vec<&str> = match instruction {
+        InitializeMint { .. } => vec!["mint", "rent sysvar"],
+        InitializeAccount => vec!["account", "mint", "owner", "rent sysvar"],
+        InitializeMultisig { .. } => vec!["multisig", "rent sysvar"],
+        Transfer { .. } => vec!["source", "destination", "owner"],
+        Approve { .. } | ApproveUserPortfolio { .. } => vec!["source", "delegate", "owner"],
+        Revoke => vec!["source", "owner"],
+        SetAuthority { .. } => vec!["mint or account", "current authority"],
+        MintTo { .. } | MintToChecked { .. } => vec!["mint", "destination", "mint authority"],
+        Burn { .. } | BurnChecked { .. } => vec!["account", "mint", "owner"],
+        CloseAccount => vec!["account", "destination", "owner"],
+        FreezeAccount | ThawAccount => vec!["account", "mint", "freeze authority"],
+        TransferChecked { .. } => vec!["source", "mint", "destination", "owner"],
+        ApproveChecked { .. } => vec!["source", "mint", "delegate", "owner"],
+        InitializeAccount2 { .. } => vec!["account", "mint", "rent sysvar"],
+        Deposit { .. } => vec![
+            "program config",
+            "hedge config",
+            "mint",
+            "account",
+            "position",
+            "source",
+            "source owner",
+            "asset vault",
+            "stablecoin vault",
+            "fee account",
+            "protocol fee account",
+            "vault authority",
+            "swap pool",
+            "swap pool authority",
+            "pool asset reserve",
+            "pool stablecoin reserve",
+            "pool mint",
+            "pool fee account",
+            "pool token program",
+            "swap program",
+            "deposit limits",
+            "deposit record",
+            "stop loss",
+            "take profit",
+            "clock sysvar",
+            "host fee account",
+        ],
+        Withdraw { .. } => vec![
+            "program config",
+            "account",
+            "position",
+            "take profit",
+            "hedge config",
+            "swap pool",
+            "pool asset reserve",
+            "pool stablecoin reserve",
+            "withdraw ticket",
+            "mint",
+            "asset vault",
+            "stablecoin vault",
+            "asset destination",
+            "stablecoin destination",
+            "fee account",
+            "stablecoin fee account",
+            "protocol fee account",
+            "protocol stablecoin fee account",
+            "vault authority",
+            "token program",
+            "clock sysvar",
+            "owner",
+        ],
+        InitializePortfolio { .. } => {
+            let mut names = vec!["portfolio".to_string(), "creator".to_string()];
+            for i in 1..=PORTFOLIO_ASSETS {
+                names.push(format!("asset {} mint", i));
+                names.push(format!("asset {} sold into", i));
+            }
+            names.push("owner".to_string());
+            for i in 1..=PORTFOLIO_ASSETS {
+                names.push(format!("asset {} registry entry", i));
+            }
+            return (names, false);
+        }
+        createInitUserPortfolio { .. } => {
+            let mut names = vec![
+                "user portfolio".to_string(),
+                "portfolio".to_string(),
+                "owner".to_string(),
+                "delegate".to_string(),
+            ];
+            for i in 1..=PORTFOLIO_ASSETS {
+                names.push(format!("asset {} account", i));
+            }
+            names.push("deposit limits".to_string());
+            names.push("deposit record".to_string());
+            names.push("program config".to_string());
+            return (names, false);
+        }
+        InitializeHedgedMint { .. } => vec![
+            "hedge config",
+            "mint",
+            "payer",
+            "asset vault",
+            "stablecoin vault",
+            "fee account",
+            "swap pool",
+            "asset registry entry",
+            "stablecoin registry entry",
+            "system program",
+            "rent sysvar",
+            "mint authority",
+        ],
+        InitializePosition => vec![
+            "position",
+            "account",
+            "payer",
+            "system program",
+            "rent sysvar",
+        ],
+        MigrateAccount => vec![
+            "account",
+            "position or payer",
+            "payer or system program",
+            "system program or rent sysvar",
+            "rent sysvar",
+        ],
+        InitializeProgramConfig { .. } => vec![
+            "program config",
+            "admin",
+            "guardian",
+            "protocol fee owner",
+            "payer",
+            "program data",
+            "upgrade authority",
+            "system program",
+            "rent sysvar",
+        ],
+        SetProgramConfig { .. } => vec![
+            "program config",
+            "new admin",
+            "new guardian",
+            "new protocol fee owner",
+            "admin",
+        ],
+        SetPause { .. } | SetWithdrawDelay { .. } => vec!["program config", "admin"],
+        RegisterAsset { .. } => vec![
+            "program config",
+            "registry entry",
+            "mint",
+            "price oracle",
+            "swap pool",
+            "payer",
+            "system program",
+            "rent sysvar",
+            "admin",
+        ],
+        UpdateAsset { .. } => vec![
+            "program config",
+            "registry entry",
+            "price oracle",
+            "swap pool",
+            "admin",
+        ],
+        DeregisterAsset => vec!["program config", "registry entry", "destination", "admin"],
+        SetDepositLimits { .. } => vec![
+            "deposit limits",
+            "mint or portfolio",
+            "payer",
+            "system program",
+            "rent sysvar",
+            "authority",
+        ],
+        InitializeDepositRecord => vec![
+            "deposit record",
+            "mint or portfolio",
+            "owner",
+            "payer",
+            "system program",
+            "rent sysvar",
+        ],
+        SetStopLoss { .. } => vec![
+            "stop loss",
+            "hedge config",
+            "mint",
+            "asset registry entry",
+            "stablecoin registry entry",
+            "payer",
+            "system program",
+            "rent sysvar",
+            "closed hedge epoch",
+            "authority",
+        ],
+        TriggerConditions { .. } | HarvestProfit { .. } => vec![
+            "program config",
+            match instruction {
+                TriggerConditions { .. } => "stop loss",
+                _ => "take profit",
+            },
+            "hedge config",
+            "mint",
+            "price oracle",
+            "clock sysvar",
+            "asset vault",
+            "stablecoin vault",
+            "vault authority",
+            "swap pool",
+            "swap pool authority",
+            "pool asset reserve",
+            "pool stablecoin reserve",
+            "pool mint",
+            "pool fee account",
+            "pool token program",
+            "swap program",
+            "host fee account",
+        ],
+        SetTakeProfit { .. } => vec![
+            "take profit",
+            "hedge config",
+            "mint",
+            "asset registry entry",
+            "stablecoin registry entry",
+            "price oracle",
+            "clock sysvar",
+            "payer",
+            "system program",
+            "rent sysvar",
+            "authority",
+        ],
+        ClaimRewards => vec![
+            "take profit",
+            "hedge config",
+            "mint",
+            "account",
+            "position",
+            "stablecoin vault",
+            "destination",
+            "vault authority",
+            "stablecoin token program",
+            "owner",
+        ],
+        InitializeDca { .. } => vec![
+            "dca config",
+            "portfolio",
+            "payer",
+            "asset vault",
+            "stablecoin vault",
+            "swap pool",
+            "asset registry entry",
+            "stablecoin registry entry",
+            "system program",
+            "rent sysvar",
+            "creator",
+        ],
+        DepositDca { .. } => vec![
+            "program config",
+            "dca config",
+            "dca position",
+            "source",
+            "stablecoin vault",
+            "stablecoin token program",
+            "payer",
+            "system program",
+            "rent sysvar",
+            "owner",
+        ],
+        ExecuteDca { .. } => vec![
+            "program config",
+            "dca config",
+            "dca position",
+            "price oracle",
+            "clock sysvar",
+            "stablecoin vault",
+            "asset vault",
+            "vault authority",
+            "swap pool",
+            "swap pool authority",
+            "pool stablecoin reserve",
+            "pool asset reserve",
+            "pool mint",
+            "pool fee account",
+            "pool token program",
+            "swap program",
+            "host fee account",
+        ],
+        WithdrawDca { .. } => vec![
+            "program config",
+            "dca config",
+            "dca position",
+            "stablecoin vault",
+            "asset vault",
+            "stablecoin destination",
+            "asset destination",
+            "vault authority",
+            "vault token program",
+            "owner",
+        ],
+        RequestWithdraw { .. } => vec![
+            "program config",
+            "account",
+            "withdraw ticket",
+            "clock sysvar",
+            "payer",
+            "system program",
+            "rent sysvar",
+            "owner",
+        ],
+    };
+    let multisig = !matches!(
+        instruction,
+        InitializeMint { .. }
+            | InitializeAccount
+            | InitializeAccount2 { .. }
+            | Deposit { .. }
+            | InitializePosition
+            | MigrateAccount
+            | InitializeProgramConfig { .. }
+            | InitializeDepositRecord
+            | TriggerConditions { .. }
+            | HarvestProfit { .. }
+            | DepositDca { .. }
+            | ExecuteDca { .. }
+            | WithdrawDca { .. }
+    );
+    (names.into_iter().map(str::to_string).collect(), multisig)
+}
+
+/// The name and arguments of an instruction.
+fn instruction_fields(instruction: &TokenInstruction) -> (&'static str, Value) {
+    use TokenInstruction::*;
+
+    match instruction {
+        InitializeMint {
+            decimals,
+            mint_authority,
+            freeze_authority,
+        } => (
+            "InitializeMint",
+            json!({
+                "decimals": decimals,
+                "mintAuthority": pubkey_json(mint_authority),
+                "freezeAuthority": coption_pubkey_json(freeze_authority),
+            }),
+        ),
+        InitializeAccount => ("InitializeAccount", json!({})),
+        InitializeMultisig { m } => ("InitializeMultisig", json!({ "m": m })),
+        Transfer { amount } => ("Transfer", json!({ "amount": amount })),
+        Approve { amount } => ("Approve", json!({ "amount": amount })),
+        ApproveUserPortfolio { amount } => ("ApproveUserPortfolio", json!({ "amount": amount })),
+        Revoke => ("Revoke", json!({})),
+        SetAuthority {
+            authority_type,
+            new_authority,
+        } => (
+            "SetAuthority",
+            json!({
+                "authorityType": format!("{:?}", authority_type),
+                "newAuthority": coption_pubkey_json(new_authority),
+            }),
+        ),
+        MintTo { amount } => ("MintTo", json!({ "amount": amount })),
+        Burn { amount } => ("Burn", json!({ "amount": amount })),
+        CloseAccount => ("CloseAccount", json!({})),
+        FreezeAccount => ("FreezeAccount", json!({})),
+        ThawAccount => ("ThawAccount", json!({})),
+        TransferChecked { amount, decimals } => (
+            "TransferChecked",
+            json!({ "amount": amount, "decimals": decimals }),
+        ),
+        ApproveChecked { amount, decimals } => (
+            "ApproveChecked",
+            json!({ "amount": amount, "decimals": decimals }),
+        ),
+        MintToChecked { amount, decimals } => (
+            "MintToChecked",
+            json!({ "amount": amount, "decimals": decimals }),
+        ),
+        BurnChecked { amount, decimals } => (
+            "BurnChecked",
+            json!({ "amount": amount, "decimals": decimals }),
+        ),
+        InitializeAccount2 { owner } => {
+            ("InitializeAccount2", json!({ "owner": pubkey_json(owner) }))
+        }
+        Deposit {
+            amount,
+            minimum_amount_out,
+        } => (
+            "Deposit",
+            json!({ "amount": amount, "minimumAmountOut": minimum_amount_out }),
+        ),
+        Withdraw { amount } => ("Withdraw", json!({ "amount": amount })),
+        InitializePortfolio {
+            metaDataUrl: metadata_url,
+            metaDataHash: metadata_hash,
+            amountAsset1: amount_asset1,
+            periodAsset1: period_asset1,
+            amountAsset2: amount_asset2,
+            periodAsset2: period_asset2,
+            amountAsset3: amount_asset3,
+            periodAsset3: period_asset3,
+            amountAsset4: amount_asset4,
+            periodAsset4: period_asset4,
+            amountAsset5: amount_asset5,
+            periodAsset5: period_asset5,
+            amountAsset6: amount_asset6,
+            periodAsset6: period_asset6,
+            amountAsset7: amount_asset7,
+            periodAsset7: period_asset7,
+            amountAsset8: amount_asset8,
+            periodAsset8: period_asset8,
+            amountAsset9: amount_asset9,
+            periodAsset9: period_asset9,
+        } => {
+            let weights = [
+                amount_asset1,
+                amount_asset2,
+                amount_asset3,
+                amount_asset4,
+                amount_asset5,
+                amount_asset6,
+                amount_asset7,
+                amount_asset8,
+                amount_asset9,
+            ];
+            let periods = [
+                period_asset1,
+                period_asset2,
+                period_asset3,
+                period_asset4,
+                period_asset5,
+                period_asset6,
+                period_asset7,
+                period_asset8,
+                period_asset9,
+            ];
+            (
+                "InitializePortfolio",
+                json!({
+                    "metadataUrl": String::from_utf8_lossy(metadata_url).trim_end_matches('\0'),
+                    "metadataHash": metadata_hash,
+                    "assets": weights
+                        .iter()
+                        .zip(periods.iter())
+                        .map(|(weight, period)| json!({ "weight": weight, "period": period }))
+                        .collect::<Vec<_>>(),
+                }),
+            )
+        }
+        createInitUserPortfolio { delegated_amount } => (
+            "createInitUserPortfolio",
+            json!({ "delegatedAmount": delegated_amount }),
+        ),
+        InitializeHedgedMint {
+            target_ratio_bps,
+            deposit_fee_bps,
+            withdraw_fee_bps,
+        } => (
+            "InitializeHedgedMint",
+            json!({
+                "targetRatioBps": target_ratio_bps,
+                "depositFeeBps": deposit_fee_bps,
+                "withdrawFeeBps": withdraw_fee_bps,
+            }),
+        ),
+        InitializePosition => ("InitializePosition", json!({})),
+        MigrateAccount => ("MigrateAccount", json!({})),
+        InitializeProgramConfig { protocol_fee_bps } => (
+            "InitializeProgramConfig",
+            json!({ "protocolFeeBps": protocol_fee_bps }),
+        ),
+        SetProgramConfig { protocol_fee_bps } => (
+            "SetProgramConfig",
+            json!({ "protocolFeeBps": protocol_fee_bps }),
+        ),
+        SetPause { paused } => ("SetPause", json!({ "paused": paused })),
+        RegisterAsset { kind } => ("RegisterAsset", json!({ "kind": format!("{:?}", kind) })),
+        UpdateAsset { kind } => ("UpdateAsset", json!({ "kind": format!("{:?}", kind) })),
+        DeregisterAsset => ("DeregisterAsset", json!({})),
+        SetDepositLimits {
+            max_supply,
+            max_deposit,
+            max_owner_deposit,
+        } => (
+            "SetDepositLimits",
+            json!({
+                "maxSupply": max_supply,
+                "maxDeposit": max_deposit,
+                "maxOwnerDeposit": max_owner_deposit,
+            }),
+        ),
+        InitializeDepositRecord => ("InitializeDepositRecord", json!({})),
+        SetStopLoss {
+            stop_price,
+            trailing_bps,
+            max_slippage_bps,
+        } => (
+            "SetStopLoss",
+            json!({
+                "stopPrice": stop_price,
+                "trailingBps": trailing_bps,
+                "maxSlippageBps": max_slippage_bps,
+            }),
+        ),
+        TriggerConditions { minimum_amount_out } => (
+            "TriggerConditions",
+            json!({ "minimumAmountOut": minimum_amount_out }),
+        ),
+        SetTakeProfit {
+            trigger_bps,
+            sell_bps,
+            max_slippage_bps,
+            distribute,
+        } => (
+            "SetTakeProfit",
+            json!({
+                "triggerBps": trigger_bps,
+                "sellBps": sell_bps,
+                "maxSlippageBps": max_slippage_bps,
+                "distribute": distribute,
+            }),
+        ),
+        HarvestProfit { minimum_amount_out } => (
+            "HarvestProfit",
+            json!({ "minimumAmountOut": minimum_amount_out }),
+        ),
+        ClaimRewards => ("ClaimRewards", json!({})),
+        InitializeDca {
+            asset_index,
+            tranches,
+            max_slippage_bps,
+        } => (
+            "InitializeDca",
+            json!({
+                "assetIndex": asset_index,
+                "tranches": tranches,
+                "maxSlippageBps": max_slippage_bps,
+            }),
+        ),
+        DepositDca { amount } => ("DepositDca", json!({ "amount": amount })),
+        ExecuteDca { minimum_amount_out } => (
+            "ExecuteDca",
+            json!({ "minimumAmountOut": minimum_amount_out }),
+        ),
+        WithdrawDca { usdc, asset } => ("WithdrawDca", json!({ "usdc": usdc, "asset": asset })),
+        RequestWithdraw { amount } => ("RequestWithdraw", json!({ "amount": amount })),
+        SetWithdrawDelay {
+            delay_slots,
+            bypass,
+        } => (
+            "SetWithdrawDelay",
+            json!({ "delaySlots": delay_slots, "bypass": bypass }),
+        ),
+    }
+}
+
+/// Decode instruction `data`, naming `accounts` after the roles the
+/// instruction gives them.
+pub fn decode_instruction(data: &[u8], accounts: &[Pubkey]) -> Result<Value, String> {
+    let instruction = TokenInstruction::unpack(data)
+        .map_err(|e| format!("Invalid token instruction data: {}", e))?;
+    let (name, fields) = instruction_fields(&instruction);
+    let (names, multisig) = instruction_accounts(&instruction);
+    let signers = if let TokenInstruction::InitializeMultisig { .. } = instruction {
+        "signer"
+    } else if multisig {
+        "multisig signer"
+    } else {
+        "extra account"
+    };
+    let accounts = accounts
+        .iter()
+        .enumerate()
+        .map(|(index, pubkey)| {
+            let name = names
+                .get(index)
+                .cloned()
+                .unwrap_or_else(|| format!("{} {}", signers, index + 1 - names.len()));
+            json!({ "index": index, "name": name, "pubkey": pubkey_json(pubkey) })
+        })
+        .collect::<Vec<_>>();
+    let mut value = Map::new();
+    value.insert("instruction".to_string(), json!(name));
+    value.insert("fields".to_string(), fields);
+    if !accounts.is_empty() {
+        value.insert("accounts".to_string(), json!(accounts));
+    }
+    Ok(Value::Object(value))
+}
+
+fn json_to_display(value: &Value) -> String {
+    match value {
+        Value::Null => "(none)".to_string(),
+        Value::String(string) => string.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Print a decoded account or instruction for humans, one field per line.
+pub fn println_decoded(value: &Value, indent: usize) {
+    let object = match value.as_object() {
+        Some(object) => object,
+        None => return,
+    };
+    for (key, value) in object {
+        match value {
+            Value::Object(_) => {
+                println!("{:indent$}{}:", "", key, indent = indent);
+                println_decoded(value, indent + 2);
+            }
+            Value::Array(items) => {
+                println!("{:indent$}{}:", "", key, indent = indent);
+                for (index, item) in items.iter().enumerate() {
+                    if item.is_object() {
+                        println!("{:indent$}[{}]", "", index, indent = indent + 2);
+                        println_decoded(item, indent + 4);
+                    } else {
+                        println!(
+                            "{:indent$}{}",
+                            "",
+                            json_to_display(item),
+                            indent = indent + 2
+                        );
+                    }
+                }
+            }
+            value => println!(
+                "{:indent$}{}: {}",
+                "",
+                key,
+                json_to_display(value),
+                indent = indent
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_token::{
+        instruction::{initialize_multisig, transfer},
+        state::AccountState,
+    };
+
+    #[test]
+    fn test_decode_data() {
+        let bytes = vec![0, 1, 0xab, 0xff];
+        assert_eq!(decode_data("0001abff", "hex").unwrap(), bytes);
+        assert_eq!(decode_data(" 0x0001ABFF\n", "hex").unwrap(), bytes);
+        assert_eq!(
+            decode_data(&bs58::encode(&bytes).into_string(), "base58").unwrap(),
+            bytes
+        );
+        assert_eq!(
+            decode_data(&base64::encode(&bytes), "base64").unwrap(),
+            bytes
+        );
+        assert!(decode_data("abc", "hex").is_err());
+        assert!(decode_data("zz", "hex").is_err());
+        assert!(decode_data("0OIl", "base58").is_err());
+        assert!(decode_data("00", "base32").is_err());
+    }
+
+    #[test]
+    fn test_parse_account_input() {
+        let data = vec![0xff, 0xfe, 0];
+        let pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+
+        // raw bytes
+        let account = parse_account_input(&data, "base64").unwrap();
+        assert_eq!(account.data, data);
+        assert_eq!((account.pubkey, account.owner), (None, None));
+
+        // the data as text
+        let account = parse_account_input(base64::encode(&data).as_bytes(), "base64").unwrap();
+        assert_eq!(account.data, data);
+
+        // `solana account --output json`
+        let input = json!({
+            "pubkey": pubkey.to_string(),
+            "account": {
+                "lamports": 1,
+                "data": [base64::encode(&data), "base64"],
+                "owner": owner.to_string(),
+            },
+        });
+        let account = parse_account_input(input.to_string().as_bytes(), "base58").unwrap();
+        assert_eq!(account.data, data);
+        assert_eq!((account.pubkey, account.owner), (Some(pubkey), Some(owner)));
+
+        // an RPC account with the data in the given encoding
+        let input = json!({ "data": bs58::encode(&data).into_string() });
+        let account = parse_account_input(input.to_string().as_bytes(), "base58").unwrap();
+        assert_eq!(account.data, data);
+        assert_eq!((account.pubkey, account.owner), (None, None));
+
+        assert!(parse_account_input(b"{\"owner\": \"x\"}", "base64").is_err());
+        assert!(parse_account_input(b"{\"data\": 1}", "base64").is_err());
+        assert!(parse_account_input(b"{\"data\": \"\", \"owner\": 1}", "base64").is_err());
+    }
+
+    #[test]
+    fn test_parse_instruction_input() {
+        let data = vec![3, 42, 0, 0, 0, 0, 0, 0, 0];
+        let accounts = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+
+        let instruction = parse_instruction_input(b"032a00000000000000", "hex").unwrap();
+        assert_eq!(instruction.data, data);
+        assert!(instruction.accounts.is_empty());
+
+        // web3.js `keys`
+        let input = json!({
+            "data": bs58::encode(&data).into_string(),
+            "keys": accounts
+                .iter()
+                .map(|pubkey| json!({ "pubkey": pubkey.to_string(), "isSigner": false }))
+                .collect::<Vec<_>>(),
+        });
+        let instruction = parse_instruction_input(input.to_string().as_bytes(), "base58").unwrap();
+        assert_eq!(instruction.data, data);
+        assert_eq!(instruction.accounts, accounts);
+
+        // `accounts` as plain addresses
+        let input = json!({
+            "data": base64::encode(&data),
+            "accounts": accounts.iter().map(Pubkey::to_string).collect::<Vec<_>>(),
+        });
+        let instruction = parse_instruction_input(input.to_string().as_bytes(), "base64").unwrap();
+        assert_eq!(instruction.accounts, accounts);
+
+        assert!(parse_instruction_input(&[0xff, 0xfe], "hex").is_err());
+        assert!(parse_instruction_input(b"{\"keys\": []}", "hex").is_err());
+        assert!(parse_instruction_input(b"{\"data\": \"03\", \"keys\": 1}", "hex").is_err());
+        assert!(parse_instruction_input(b"{\"data\": \"03\", \"keys\": [\"x\"]}", "hex").is_err());
+    }
+
+    #[test]
+    fn test_decode_account() {
+        let mint_authority = Pubkey::new_unique();
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                mint_authority: COption::Some(mint_authority),
+                supply: 1_000,
+                decimals: 6,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            },
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(AccountType::detect(&data), Some(AccountType::Mint));
+        assert_eq!(
+            decode_account(AccountType::Mint, &data).unwrap(),
+            json!({
+                "type": "mint",
+                "fields": {
+                    "mintAuthority": mint_authority.to_string(),
+                    "supply": 1_000,
+                    "decimals": 6,
+                    "isInitialized": true,
+                    "freezeAuthority": null,
+                },
+            })
+        );
+
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut data = vec![0; Account::LEN];
+        Account::pack(
+            Account {
+                mint,
+                owner,
+                amount: 42,
+                state: AccountState::Frozen,
+                is_native: COption::Some(2_039_280),
+                ..Account::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(AccountType::detect(&data), Some(AccountType::Account));
+        assert_eq!(
+            decode_account(AccountType::Account, &data).unwrap()["fields"],
+            json!({
+                "mint": mint.to_string(),
+                "owner": owner.to_string(),
+                "amount": 42,
+                "delegate": null,
+                "state": "frozen",
+                "rentExemptReserve": 2_039_280,
+                "delegatedAmount": 0,
+                "closeAuthority": null,
+            })
+        );
+
+        // state accounts are told apart by their header
+        let account = Pubkey::new_unique();
+        let mut data = vec![0; Position::LEN];
+        Position::pack(
+            Position {
+                is_initialized: true,
+                account,
+                asset: 400,
+                usdc: 1_200,
+                ..Position::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(AccountType::detect(&data), Some(AccountType::Position));
+        let decoded = decode_account(AccountType::Position, &data).unwrap();
+        assert_eq!(decoded["type"], "position");
+        assert_eq!(decoded["fields"]["account"], account.to_string());
+        assert_eq!(decoded["fields"]["usdc"], 1_200);
+
+        let mut data = vec![0; HedgeEpoch::LEN];
+        HedgeEpoch::pack(
+            HedgeEpoch {
+                is_initialized: true,
+                epoch: 3,
+                stable_per_base: u128::MAX,
+                ..HedgeEpoch::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(AccountType::detect(&data), Some(AccountType::HedgeEpoch));
+        assert_eq!(
+            decode_account(AccountType::HedgeEpoch, &data).unwrap()["fields"]["stablePerBase"],
+            u128::MAX.to_string()
+        );
+
+        // a portfolio is only detected by its header, not by its size
+        assert_eq!(AccountType::detect(&vec![0; Portfolio::LEN]), None);
+        assert_eq!(AccountType::detect(&[0; 1]), None);
+        assert!(decode_account(AccountType::Mint, &data).is_err());
+        for name in AccountType::NAMES {
+            assert_eq!(AccountType::from_name(name).unwrap().name(), *name);
+        }
+        assert_eq!(AccountType::NAMES.len(), AccountType::ALL.len());
+    }
+
+    #[test]
+    fn test_decode_instruction() {
+        let source = Pubkey::new_unique();
+        let destination = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let signers = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let instruction = transfer(
+            &spl_token::id(),
+            &source,
+            &destination,
+            &owner,
+            &[&signers[0], &signers[1]],
+            42,
+        )
+        .unwrap();
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|meta| meta.pubkey)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decode_instruction(&instruction.data, &accounts).unwrap(),
+            json!({
+                "instruction": "Transfer",
+                "fields": { "amount": 42 },
+                "accounts": [
+                    { "index": 0, "name": "source", "pubkey": source.to_string() },
+                    { "index": 1, "name": "destination", "pubkey": destination.to_string() },
+                    { "index": 2, "name": "owner", "pubkey": owner.to_string() },
+                    { "index": 3, "name": "multisig signer 1", "pubkey": signers[0].to_string() },
+                    { "index": 4, "name": "multisig signer 2", "pubkey": signers[1].to_string() },
+                ],
+            })
+        );
+
+        // without accounts, only the data is decoded
+        assert_eq!(
+            decode_instruction(&instruction.data, &[]).unwrap(),
+            json!({ "instruction": "Transfer", "fields": { "amount": 42 } })
+        );
+
+        let multisig = Pubkey::new_unique();
+        let instruction =
+            initialize_multisig(&spl_token::id(), &multisig, &[&signers[0], &signers[1]], 2)
+                .unwrap();
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|meta| meta.pubkey)
+            .collect::<Vec<_>>();
+        let decoded = decode_instruction(&instruction.data, &accounts).unwrap();
+        assert_eq!(decoded["fields"], json!({ "m": 2 }));
+        assert_eq!(decoded["accounts"][2]["name"], "signer 1");
+        assert_eq!(decoded["accounts"][3]["name"], "signer 2");
+
+        assert!(decode_instruction(&[], &[]).is_err());
+        assert!(decode_instruction(&[255], &[]).is_err());
+    }
+}
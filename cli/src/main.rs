@@ -3,15 +3,21 @@ use clap::{
     ArgMatches, SubCommand,
 };
 use console::Emoji;
+use serde::Deserialize;
 use solana_account_decoder::{
-    parse_token::{TokenAccountType, UiAccountState},
-    UiAccountData,
+    parse_token::{
+        parse_token, token_amount_to_ui_amount, TokenAccountType, UiAccountState, UiTokenAccount,
+        UiTokenAmount,
+    },
+    UiAccountEncoding,
 };
 use solana_clap_utils::{
     fee_payer::fee_payer_arg,
-    input_parsers::{pubkey_of_signer, pubkeys_of_multiple_signers, signer_of, value_of},
+    input_parsers::{
+        pubkey_of_signer, pubkeys_of, pubkeys_of_multiple_signers, signer_of, value_of,
+    },
     input_validators::{
-        is_amount, is_amount_or_all, is_parsable, is_url_or_moniker, is_valid_pubkey,
+        is_amount, is_amount_or_all, is_parsable, is_pubkey, is_url_or_moniker, is_valid_pubkey,
         is_valid_signer, normalize_to_url_if_moniker,
     },
     keypair::{signer_from_path, DefaultSigner, SignerFromPathConfig},
@@ -21,32 +27,43 @@ use solana_clap_utils::{
 };
 use solana_cli_output::{display::println_name_value, return_signers, OutputFormat};
 use solana_client::{
-    blockhash_query::BlockhashQuery, rpc_client::RpcClient, rpc_request::TokenAccountsFilter,
+    blockhash_query::BlockhashQuery,
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
 };
 use solana_remote_wallet::remote_wallet::RemoteWalletManager;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
-    instruction::Instruction,
+    instruction::{AccountMeta, Instruction},
     message::Message,
     native_token::*,
     program_option::COption,
     program_pack::Pack,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    system_instruction, system_program,
+    system_instruction, system_program, sysvar,
     transaction::Transaction,
 };
-use spl_associated_token_account::*;
 use spl_token::{
     self,
     instruction::*,
     native_mint,
-    state::{Account, Mint, Multisig},
+    state::{
+        Account, AssetEntry, DepositRecord, HedgeConfig, HedgeEpoch, Mint, Multisig, Portfolio,
+        Position, ProgramConfig, UserPortfolio, WithdrawTicket,
+    },
+    swap::{unpack_token_account, SwapQuote, SwapState},
 };
-use std::{collections::HashMap, process::exit, str::FromStr, sync::Arc};
+use std::{collections::HashMap, fs, path::Path, process::exit, rc::Rc, str::FromStr};
 
+mod decode;
+use decode::AccountType;
+mod portfolio;
+use portfolio::{redeem_amounts, Holding, PortfolioSpec, PORTFOLIO_ASSETS};
+mod simulate;
 mod sort;
-use sort::sort_and_parse_token_accounts;
+use sort::{sort_and_parse_token_accounts, TokenAccounts};
 
 static WARNING: Emoji = Emoji("⚠️", "!");
 
@@ -74,6 +91,12 @@ pub const MULTISIG_SIGNER_ARG: ArgConstant<'static> = ArgConstant {
     help: "Member signer of a multisig account",
 };
 
+pub const OUTPUT_FORMAT_ARG: ArgConstant<'static> = ArgConstant {
+    name: "output_format",
+    long: "output",
+    help: "Return information in specified output format",
+};
+
 pub fn mint_address_arg<'a, 'b>() -> Arg<'a, 'b> {
     Arg::with_name(MINT_ADDRESS_ARG.name)
         .long(MINT_ADDRESS_ARG.long)
@@ -134,6 +157,31 @@ pub fn multisig_signer_arg<'a, 'b>() -> Arg<'a, 'b> {
         .help(MULTISIG_SIGNER_ARG.help)
 }
 
+pub const ENCODING_ARG: ArgConstant<'static> = ArgConstant {
+    name: "encoding",
+    long: "encoding",
+    help: "Encoding of data given as text",
+};
+
+pub fn decode_encoding_arg<'a, 'b>(default: &'a str) -> Arg<'a, 'b> {
+    Arg::with_name(ENCODING_ARG.name)
+        .long(ENCODING_ARG.long)
+        .value_name("ENCODING")
+        .takes_value(true)
+        .possible_values(&["base58", "base64", "hex"])
+        .default_value(default)
+        .help(ENCODING_ARG.help)
+}
+
+pub fn output_format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name(OUTPUT_FORMAT_ARG.name)
+        .long(OUTPUT_FORMAT_ARG.long)
+        .value_name("FORMAT")
+        .takes_value(true)
+        .possible_values(&["json", "json-compact"])
+        .help(OUTPUT_FORMAT_ARG.help)
+}
+
 fn is_multisig_minimum_signers(string: String) -> Result<(), String> {
     let v = u8::from_str(&string).map_err(|e| e.to_string())? as usize;
     if v < MIN_SIGNERS {
@@ -156,6 +204,62 @@ struct Config<'a> {
     blockhash_query: BlockhashQuery,
     sign_only: bool,
     multisigner_pubkeys: Vec<&'a Pubkey>,
+    program_id: Pubkey,
+    simulate: bool,
+}
+
+impl Config<'_> {
+    /// The associated token address of `wallet` for `mint` under the configured program.
+    fn associated_token_address(&self, wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(wallet, mint, &self.program_id)
+    }
+
+    /// Create the associated token account of `wallet` for `mint`, funded by the fee payer.
+    fn create_associated_token_account(&self, wallet: &Pubkey, mint: &Pubkey) -> Instruction {
+        create_associated_token_account_with_program_id(
+            &self.fee_payer,
+            wallet,
+            mint,
+            &self.program_id,
+        )
+    }
+}
+
+/// Derive the associated token address of `wallet` for `mint`, seeded with the
+/// token program that owns the account instead of the canonical one.
+pub fn get_associated_token_address_with_program_id(
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[&wallet.to_bytes(), &program_id.to_bytes(), &mint.to_bytes()],
+        &spl_associated_token_account::id(),
+    )
+    .0
+}
+
+fn create_associated_token_account_with_program_id(
+    funding_address: &Pubkey,
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> Instruction {
+    let associated_account_address =
+        get_associated_token_address_with_program_id(wallet, mint, program_id);
+    Instruction {
+        program_id: spl_associated_token_account::id(),
+        accounts: vec![
+            AccountMeta::new(*funding_address, true),
+            AccountMeta::new(associated_account_address, false),
+            AccountMeta::new_readonly(*wallet, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*program_id, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: vec![],
+    }
 }
 
 type Error = Box<dyn std::error::Error>;
@@ -182,6 +286,60 @@ fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(),
     }
 }
 
+/// Entries of the configuration file that only this CLI reads.
+#[derive(Default, Deserialize)]
+struct TokenCliConfig {
+    token_program_id: Option<String>,
+}
+
+/// Read the `token_program_id` entry of `config_file`, if any.
+fn program_id_from_config_file(config_file: &str) -> Result<Option<Pubkey>, Error> {
+    if !Path::new(config_file).exists() {
+        return Ok(None);
+    }
+    let contents = fs::read_to_string(config_file)?;
+    let token_cli_config: TokenCliConfig = serde_yaml::from_str(&contents)?;
+    token_cli_config
+        .token_program_id
+        .map(|program_id| {
+            Pubkey::from_str(&program_id).map_err(|_| {
+                Error::from(format!(
+                    "Invalid token_program_id {} in {}",
+                    program_id, config_file
+                ))
+            })
+        })
+        .transpose()
+}
+
+/// Make sure the token program is deployed and that its accounts have the
+/// layouts this CLI packs and unpacks.
+fn check_program(config: &Config) -> Result<(), Error> {
+    let program = config
+        .rpc_client
+        .get_account_with_commitment(&config.program_id, config.rpc_client.commitment())?
+        .value
+        .ok_or_else(|| format!("Token program {} does not exist", config.program_id))?;
+    if !program.executable {
+        return Err(format!("Token program {} is not executable", config.program_id).into());
+    }
+    let (address, _) = find_program_config_with_program_id(&config.program_id);
+    if let Some(data) = get_account_data(config, &address)? {
+        if data.len() != ProgramConfig::LEN {
+            return Err(format!(
+                "Program config {} of {} is {} bytes long, expected {}; \
+                 the program does not use the account layouts of this CLI",
+                address,
+                config.program_id,
+                data.len(),
+                ProgramConfig::LEN
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
 fn check_owner_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
     let balance = config.rpc_client.get_balance(&config.owner)?;
     if balance < required_balance {
@@ -201,7 +359,7 @@ type SignersOf = Vec<(Box<dyn Signer>, Pubkey)>;
 pub fn signers_of(
     matches: &ArgMatches<'_>,
     name: &str,
-    wallet_manager: &mut Option<Arc<RemoteWalletManager>>,
+    wallet_manager: &mut Option<Rc<RemoteWalletManager>>,
 ) -> Result<Option<SignersOf>, Box<dyn std::error::Error>> {
     if let Some(values) = matches.values_of(name) {
         let mut results = Vec::new();
@@ -244,10 +402,10 @@ fn command_create_token(
             &token,
             minimum_balance_for_rent_exemption,
             Mint::LEN as u64,
-            &spl_token::id(),
+            &config.program_id,
         ),
         initialize_mint(
-            &spl_token::id(),
+            &config.program_id,
             &token,
             &config.owner,
             freeze_authority_pubkey.as_ref(),
@@ -284,22 +442,18 @@ fn command_create_account(
                     &account,
                     minimum_balance_for_rent_exemption,
                     Account::LEN as u64,
-                    &spl_token::id(),
+                    &config.program_id,
                 ),
-                initialize_account(&spl_token::id(), &account, &token, &config.owner)?,
+                initialize_account(&config.program_id, &account, &token, &config.owner)?,
             ],
         )
     } else {
-        let account = get_associated_token_address(&config.owner, &token);
+        let account = config.associated_token_address(&config.owner, &token);
         println!("Creating account {}", account);
         (
             account,
             true,
-            vec![create_associated_token_account(
-                &config.fee_payer,
-                &config.owner,
-                &token,
-            )],
+            vec![config.create_associated_token_account(&config.owner, &token)],
         )
     };
 
@@ -348,10 +502,10 @@ fn command_create_multisig(
             &multisig,
             minimum_balance_for_rent_exemption,
             Multisig::LEN as u64,
-            &spl_token::id(),
+            &config.program_id,
         ),
         initialize_multisig(
-            &spl_token::id(),
+            &config.program_id,
             &multisig,
             multisig_members.iter().collect::<Vec<_>>().as_slice(),
             minimum_signers,
@@ -390,7 +544,7 @@ fn command_authorize(
         } else if let Ok(token_account) = Account::unpack(&target_account.data) {
             let check_associated_token_account = || -> Result<(), Error> {
                 let maybe_associated_token_account =
-                    get_associated_token_address(&config.owner, &token_account.mint);
+                    config.associated_token_address(&config.owner, &token_account.mint);
                 if account == maybe_associated_token_account
                     && !force_authorize
                     && Some(config.owner) != new_owner
@@ -440,7 +594,7 @@ fn command_authorize(
     );
 
     let instructions = vec![set_authority(
-        &spl_token::id(),
+        &config.program_id,
         &account,
         new_owner.as_ref(),
         authority_type,
@@ -457,9 +611,7 @@ fn resolve_mint_info(
     mint_decimals: Option<u8>,
 ) -> Result<(Pubkey, u8), Error> {
     if !config.sign_only {
-        let source_account = config
-            .rpc_client
-            .get_token_account(&token_account)?
+        let source_account = get_token_account(config, token_account)?
             .ok_or_else(|| format!("Could not find token account {}", token_account))?;
         let source_mint = Pubkey::from_str(&source_account.mint)?;
         if let Some(mint) = mint_address {
@@ -482,7 +634,18 @@ fn resolve_mint_info(
 
 fn validate_mint(config: &Config, token: Pubkey) -> Result<(), Error> {
     let mint = config.rpc_client.get_account(&token);
-    if mint.is_err() || Mint::unpack(&mint.unwrap().data).is_err() {
+    if mint.is_err() {
+        return Err(format!("Invalid mint account {:?}", token).into());
+    }
+    let mint = mint.unwrap();
+    if mint.owner != config.program_id {
+        return Err(format!(
+            "Mint {:?} is owned by {}, not by the token program {}",
+            token, mint.owner, config.program_id
+        )
+        .into());
+    }
+    if Mint::unpack(&mint.data).is_err() {
         return Err(format!("Invalid mint account {:?}", token).into());
     }
     Ok(())
@@ -503,21 +666,18 @@ fn command_transfer(
     let sender = if let Some(sender) = sender {
         sender
     } else {
-        get_associated_token_address(&config.owner, &token)
+        config.associated_token_address(&config.owner, &token)
     };
     let (mint_pubkey, decimals) = resolve_mint_info(config, &sender, Some(token), mint_decimals)?;
     let maybe_transfer_balance =
         ui_amount.map(|ui_amount| spl_token::ui_amount_to_amount(ui_amount, decimals));
     let transfer_balance = if !config.sign_only {
-        let sender_token_amount = config
-            .rpc_client
-            .get_token_account_balance(&sender)
-            .map_err(|err| {
-                format!(
-                    "Error: Failed to get token balance of sender address {}: {}",
-                    sender, err
-                )
-            })?;
+        let sender_token_amount = get_token_account_balance(config, &sender).map_err(|err| {
+            format!(
+                "Error: Failed to get token balance of sender address {}: {}",
+                sender, err
+            )
+        })?;
         let sender_balance = sender_token_amount.amount.parse::<u64>().map_err(|err| {
             format!(
                 "Token account {} balance could not be parsed: {}",
@@ -555,7 +715,9 @@ fn command_transfer(
             .rpc_client
             .get_account_with_commitment(&recipient, config.rpc_client.commitment())?
             .value
-            .map(|account| account.owner == spl_token::id() && account.data.len() == Account::LEN);
+            .map(|account| {
+                account.owner == config.program_id && account.data.len() == Account::LEN
+            });
 
         if recipient_account_info.is_none() && !allow_unfunded_recipient {
             return Err("Error: The recipient address is not funded. \
@@ -570,7 +732,7 @@ fn command_transfer(
     };
 
     if !recipient_is_token_account {
-        recipient_token_account = get_associated_token_address(&recipient, &mint_pubkey);
+        recipient_token_account = config.associated_token_address(&recipient, &mint_pubkey);
         println!(
             "  Recipient associated token account: {}",
             recipient_token_account
@@ -587,7 +749,7 @@ fn command_transfer(
             {
                 if recipient_token_account_data.owner == system_program::id() {
                     true
-                } else if recipient_token_account_data.owner == spl_token::id() {
+                } else if recipient_token_account_data.owner == config.program_id {
                     false
                 } else {
                     return Err(
@@ -613,11 +775,7 @@ fn command_transfer(
                         lamports_to_sol(minimum_balance_for_rent_exemption)
                     );
                 }
-                instructions.push(create_associated_token_account(
-                    &config.fee_payer,
-                    &recipient,
-                    &mint_pubkey,
-                ));
+                instructions.push(config.create_associated_token_account(&recipient, &mint_pubkey));
             } else {
                 return Err(
                     "Error: Recipient's associated token account does not exist. \
@@ -628,13 +786,11 @@ fn command_transfer(
         }
     }
 
-    instructions.push(transfer_checked(
-        &spl_token::id(),
+    instructions.push(transfer_checked_settling_positions(
+        config,
         &sender,
         &mint_pubkey,
         &recipient_token_account,
-        &config.owner,
-        &config.multisigner_pubkeys,
         transfer_balance,
         decimals,
     )?);
@@ -657,7 +813,7 @@ fn command_burn(
     let amount = spl_token::ui_amount_to_amount(ui_amount, decimals);
 
     let instructions = vec![burn_checked(
-        &spl_token::id(),
+        &config.program_id,
         &source,
         &mint_pubkey,
         &config.owner,
@@ -684,7 +840,7 @@ fn command_mint(
     let amount = spl_token::ui_amount_to_amount(ui_amount, decimals);
 
     let instructions = vec![mint_to_checked(
-        &spl_token::id(),
+        &config.program_id,
         &token,
         &recipient,
         &config.owner,
@@ -701,7 +857,7 @@ fn command_freeze(config: &Config, account: Pubkey, mint_address: Option<Pubkey>
     println!("Freezing account: {}\n  Token: {}", account, token);
 
     let instructions = vec![freeze_account(
-        &spl_token::id(),
+        &config.program_id,
         &account,
         &token,
         &config.owner,
@@ -716,7 +872,7 @@ fn command_thaw(config: &Config, account: Pubkey, mint_address: Option<Pubkey>)
     println!("Freezing account: {}\n  Token: {}", account, token);
 
     let instructions = vec![thaw_account(
-        &spl_token::id(),
+        &config.program_id,
         &account,
         &token,
         &config.owner,
@@ -736,17 +892,17 @@ fn command_wrap(config: &Config, sol: f64, account: Option<Pubkey>) -> CommandRe
                 &account,
                 lamports,
                 Account::LEN as u64,
-                &spl_token::id(),
+                &config.program_id,
             ),
             initialize_account(
-                &spl_token::id(),
+                &config.program_id,
                 &account,
                 &native_mint::id(),
                 &config.owner,
             )?,
         ]
     } else {
-        let account = get_associated_token_address(&config.owner, &native_mint::id());
+        let account = config.associated_token_address(&config.owner, &native_mint::id());
 
         if !config.sign_only {
             if let Some(account_data) = config
@@ -763,7 +919,7 @@ fn command_wrap(config: &Config, sol: f64, account: Option<Pubkey>) -> CommandRe
         println!("Wrapping {} SOL into {}", sol, account);
         vec![
             system_instruction::transfer(&config.owner, &account, lamports),
-            create_associated_token_account(&config.fee_payer, &config.owner, &native_mint::id()),
+            config.create_associated_token_account(&config.owner, &native_mint::id()),
         ]
     };
     if !config.sign_only {
@@ -774,8 +930,8 @@ fn command_wrap(config: &Config, sol: f64, account: Option<Pubkey>) -> CommandRe
 
 fn command_unwrap(config: &Config, address: Option<Pubkey>) -> CommandResult {
     let use_associated_account = address.is_none();
-    let address =
-        address.unwrap_or_else(|| get_associated_token_address(&config.owner, &native_mint::id()));
+    let address = address
+        .unwrap_or_else(|| config.associated_token_address(&config.owner, &native_mint::id()));
     println!("Unwrapping {}", address);
     if !config.sign_only {
         let lamports = config.rpc_client.get_balance(&address)?;
@@ -790,8 +946,8 @@ fn command_unwrap(config: &Config, address: Option<Pubkey>) -> CommandResult {
     }
     println!("  Recipient: {}", &config.owner);
 
-    let instructions = vec![close_account(
-        &spl_token::id(),
+    let instructions = vec![close_account_with_position(
+        &config.program_id,
         &address,
         &config.owner,
         &config.owner,
@@ -817,7 +973,7 @@ fn command_approve(
     let amount = spl_token::ui_amount_to_amount(ui_amount, decimals);
 
     let instructions = vec![approve_checked(
-        &spl_token::id(),
+        &config.program_id,
         &account,
         &mint_pubkey,
         &delegate,
@@ -831,9 +987,7 @@ fn command_approve(
 
 fn command_revoke(config: &Config, account: Pubkey, delegate: Option<Pubkey>) -> CommandResult {
     let delegate = if !config.sign_only {
-        let source_account = config
-            .rpc_client
-            .get_token_account(&account)?
+        let source_account = get_token_account(config, &account)?
             .ok_or_else(|| format!("Could not find token account {}", account))?;
 
         if let Some(string) = source_account.delegate {
@@ -855,7 +1009,7 @@ fn command_revoke(config: &Config, account: Pubkey, delegate: Option<Pubkey>) ->
     }
 
     let instructions = vec![revoke(
-        &spl_token::id(),
+        &config.program_id,
         &account,
         &config.owner,
         &config.multisigner_pubkeys,
@@ -872,12 +1026,10 @@ fn command_close(
     let account = if let Some(account) = account {
         account
     } else {
-        get_associated_token_address(&config.owner, &token.unwrap())
+        config.associated_token_address(&config.owner, &token.unwrap())
     };
     if !config.sign_only {
-        let source_account = config
-            .rpc_client
-            .get_token_account(&account)?
+        let source_account = get_token_account(config, &account)?
             .ok_or_else(|| format!("Could not find token account {}", account))?;
         let source_amount = source_account
             .token_amount
@@ -900,8 +1052,8 @@ fn command_close(
         }
     }
 
-    let instructions = vec![close_account(
-        &spl_token::id(),
+    let instructions = vec![close_account_with_position(
+        &config.program_id,
         &account,
         &recipient,
         &config.owner,
@@ -914,21 +1066,41 @@ fn command_balance(
     config: &Config,
     token: Option<Pubkey>,
     address: Option<Pubkey>,
+    output: Option<&str>,
 ) -> CommandResult {
     let address = if let Some(address) = address {
         address
     } else {
-        get_associated_token_address(&config.owner, &token.unwrap())
+        config.associated_token_address(&config.owner, &token.unwrap())
     };
-    let balance = config
-        .rpc_client
-        .get_token_account_balance(&address)
-        .map_err(|_| format!("Could not find token account {}", address))?;
-
+    let account = get_token_account(config, &address)
+        .ok()
+        .flatten()
+        .ok_or_else(|| format!("Could not find token account {}", address))?;
+    let balance = account.token_amount;
+    let breakdown = get_account_breakdown(config, &Pubkey::from_str(&account.mint)?, &address)?;
+
+    if let Some(output) = output {
+        return print_json(
+            &serde_json::json!({
+                "address": address.to_string(),
+                "uiAmount": balance.real_number_string_trimmed(),
+                "decimals": balance.decimals,
+                "amount": balance.amount,
+                "hedged": breakdown.as_ref().map(HedgedBreakdown::to_json),
+            }),
+            output,
+        );
+    }
     if config.verbose {
         println!("ui amount: {}", balance.real_number_string_trimmed());
         println!("decimals: {}", balance.decimals);
         println!("amount: {}", balance.amount);
+        if let Some(breakdown) = breakdown {
+            println!("asset: {}", breakdown.asset);
+            println!("usdc: {}", breakdown.usdc);
+            println!("estimated value: {}", breakdown.value_string());
+        }
     } else {
         println!("{}", balance.real_number_string_trimmed());
     }
@@ -936,48 +1108,101 @@ fn command_balance(
 }
 
 fn command_supply(config: &Config, address: Pubkey) -> CommandResult {
-    let supply = config.rpc_client.get_token_supply(&address)?;
+    let mint = get_mint(config, &address)?;
+    let supply = token_amount_to_ui_amount(mint.supply, mint.decimals);
 
     println!("{}", supply.real_number_string_trimmed());
     Ok(None)
 }
 
-fn command_accounts(config: &Config, token: Option<Pubkey>) -> CommandResult {
+fn command_accounts(config: &Config, token: Option<Pubkey>, output: Option<&str>) -> CommandResult {
     if let Some(token) = token {
         validate_mint(config, token)?;
     }
-    let accounts = config.rpc_client.get_token_accounts_by_owner(
-        &config.owner,
-        match token {
-            Some(token) => TokenAccountsFilter::Mint(token),
-            None => TokenAccountsFilter::ProgramId(spl_token::id()),
-        },
-    )?;
-    if accounts.is_empty() {
+    let accounts = get_token_accounts_by_owner(config, &config.owner, token)?;
+    if accounts.is_empty() && output.is_none() {
         println!("None");
     }
 
     let (mint_accounts, unsupported_accounts, max_len_balance, includes_aux) =
-        sort_and_parse_token_accounts(&config.owner, accounts);
+        sort_and_parse_token_accounts(&config.owner, &config.program_id, accounts);
     let aux_len = if includes_aux { 10 } else { 0 };
     let mut gc_alert = false;
 
+    let mut breakdowns = HashMap::new();
+    for (mint, accounts_list) in mint_accounts.iter() {
+        if let Some(pricing) = get_hedge_pricing(config, &Pubkey::from_str(mint)?)? {
+            for account in accounts_list {
+                let address = Pubkey::from_str(&account.address)?;
+                if let Some(breakdown) = get_hedged_breakdown(config, &pricing, &address)? {
+                    breakdowns.insert(account.address.clone(), breakdown);
+                }
+            }
+        }
+    }
+
+    if let Some(output) = output {
+        let accounts: Vec<_> = mint_accounts
+            .values()
+            .flatten()
+            .map(|account| {
+                serde_json::json!({
+                    "address": account.address,
+                    "mint": account.ui_token_account.mint,
+                    "uiAmount": account.ui_token_account.token_amount.real_number_string_trimmed(),
+                    "decimals": account.ui_token_account.token_amount.decimals,
+                    "amount": account.ui_token_account.token_amount.amount,
+                    "isAssociated": account.is_associated,
+                    "isFrozen": account.ui_token_account.state == UiAccountState::Frozen,
+                    "hedged": breakdowns.get(&account.address).map(HedgedBreakdown::to_json),
+                })
+            })
+            .collect();
+        return print_json(&serde_json::json!({ "accounts": accounts }), output);
+    }
+
+    let hedged_header = if breakdowns.is_empty() {
+        "".to_string()
+    } else {
+        format!("  {:<14}{:<14}{:<14}", "Asset", "Usdc", "Value")
+    };
     if config.verbose {
         if token.is_some() {
-            println!("{:<44}  {:<2$}", "Account", "Balance", max_len_balance);
+            println!(
+                "{:<44}  {:<2$}{hedged}",
+                "Account",
+                "Balance",
+                max_len_balance,
+                hedged = hedged_header,
+            );
             println!("-------------------------------------------------------------");
         } else {
             println!(
-                "{:<44}  {:<44}  {:<3$}",
-                "Token", "Account", "Balance", max_len_balance
+                "{:<44}  {:<44}  {:<3$}{hedged}",
+                "Token",
+                "Account",
+                "Balance",
+                max_len_balance,
+                hedged = hedged_header,
             );
             println!("----------------------------------------------------------------------------------------------------------");
         }
     } else if token.is_some() {
-        println!("{:<1$}", "Balance", max_len_balance);
+        println!(
+            "{:<1$}{hedged}",
+            "Balance",
+            max_len_balance,
+            hedged = hedged_header,
+        );
         println!("-------------");
     } else {
-        println!("{:<44}  {:<2$}", "Token", "Balance", max_len_balance);
+        println!(
+            "{:<44}  {:<2$}{hedged}",
+            "Token",
+            "Balance",
+            max_len_balance,
+            hedged = hedged_header,
+        );
         println!("---------------------------------------------------------------");
     }
     for (_mint, accounts_list) in mint_accounts.iter() {
@@ -996,10 +1221,20 @@ fn command_accounts(config: &Config, token: Option<Pubkey>) -> CommandResult {
             } else {
                 "".to_string()
             };
+            let hedged = match breakdowns.get(&account.address) {
+                Some(breakdown) => format!(
+                    "  {:<14}{:<14}{:<14}",
+                    breakdown.asset,
+                    breakdown.usdc,
+                    breakdown.value_string()
+                ),
+                None if breakdowns.is_empty() => "".to_string(),
+                None => format!("  {:<42}", ""),
+            };
             if config.verbose {
                 if token.is_some() {
                     println!(
-                        "{:<44}  {:<4$}{:<5$}{}",
+                        "{:<44}  {:<4$}{:<5$}{hedged}{}",
                         account.address,
                         account
                             .ui_token_account
@@ -1009,10 +1244,11 @@ fn command_accounts(config: &Config, token: Option<Pubkey>) -> CommandResult {
                         maybe_frozen,
                         max_len_balance,
                         aux_len,
+                        hedged = hedged,
                     )
                 } else {
                     println!(
-                        "{:<44}  {:<44}  {:<5$}{:<6$}{}",
+                        "{:<44}  {:<44}  {:<5$}{:<6$}{hedged}{}",
                         account.ui_token_account.mint,
                         account.address,
                         account
@@ -1023,11 +1259,12 @@ fn command_accounts(config: &Config, token: Option<Pubkey>) -> CommandResult {
                         maybe_frozen,
                         max_len_balance,
                         aux_len,
+                        hedged = hedged,
                     )
                 }
             } else if token.is_some() {
                 println!(
-                    "{:<3$}{:<4$}{}",
+                    "{:<3$}{:<4$}{hedged}{}",
                     account
                         .ui_token_account
                         .token_amount
@@ -1036,10 +1273,11 @@ fn command_accounts(config: &Config, token: Option<Pubkey>) -> CommandResult {
                     maybe_frozen,
                     max_len_balance,
                     aux_len,
+                    hedged = hedged,
                 )
             } else {
                 println!(
-                    "{:<44}  {:<4$}{:<5$}{}",
+                    "{:<44}  {:<4$}{:<5$}{hedged}{}",
                     account.ui_token_account.mint,
                     account
                         .ui_token_account
@@ -1049,6 +1287,7 @@ fn command_accounts(config: &Config, token: Option<Pubkey>) -> CommandResult {
                     maybe_frozen,
                     max_len_balance,
                     aux_len,
+                    hedged = hedged,
                 )
             }
         }
@@ -1069,7 +1308,7 @@ fn command_accounts(config: &Config, token: Option<Pubkey>) -> CommandResult {
 fn command_address(config: &Config, token: Option<Pubkey>) -> CommandResult {
     if let Some(token) = token {
         validate_mint(config, token)?;
-        let associated_token_address = get_associated_token_address(&config.owner, &token);
+        let associated_token_address = config.associated_token_address(&config.owner, &token);
         println!("Wallet address: {:?}", config.owner);
         println!("Associated token address: {:?}", associated_token_address);
     } else {
@@ -1082,24 +1321,36 @@ fn command_account_info(
     config: &Config,
     token: Option<Pubkey>,
     address: Option<Pubkey>,
+    output: Option<&str>,
 ) -> CommandResult {
     let mut is_associated = false;
     let address = if let Some(address) = address {
         address
     } else {
         is_associated = true;
-        get_associated_token_address(&config.owner, &token.unwrap())
+        config.associated_token_address(&config.owner, &token.unwrap())
     };
-    let account = config
-        .rpc_client
-        .get_token_account(&address)
-        .map_err(|_| format!("Could not find token account {}", address))?
-        .unwrap();
+    let account = get_token_account(config, &address)
+        .ok()
+        .flatten()
+        .ok_or_else(|| format!("Could not find token account {}", address))?;
     if !is_associated {
         if let Ok(mint) = Pubkey::from_str(&account.mint) {
-            is_associated = get_associated_token_address(&config.owner, &mint) == address;
+            is_associated = config.associated_token_address(&config.owner, &mint) == address;
         }
     }
+    let breakdown = get_account_breakdown(config, &Pubkey::from_str(&account.mint)?, &address)?;
+    if let Some(output) = output {
+        return print_json(
+            &serde_json::json!({
+                "address": address.to_string(),
+                "isAssociated": is_associated,
+                "account": account,
+                "hedged": breakdown.as_ref().map(HedgedBreakdown::to_json),
+            }),
+            output,
+        );
+    }
     let address_message = if is_associated {
         address.to_string()
     } else {
@@ -1127,45 +1378,1549 @@ fn command_account_info(
     } else {
         println_name_value("Delegation:", "");
     }
-    println_name_value(
-        "Close authority:",
-        &account.close_authority.as_ref().unwrap_or(&String::new()),
-    );
-    if !is_associated {
-        println!();
-        println!("* Please run `spl-token gc` to clean up Aux accounts");
+    println_name_value(
+        "Close authority:",
+        account.close_authority.as_ref().unwrap_or(&String::new()),
+    );
+    if let Some(breakdown) = breakdown {
+        println!("Hedged:");
+        println_name_value("  Asset:", &breakdown.asset.to_string());
+        println_name_value("  Usdc:", &breakdown.usdc.to_string());
+        println_name_value("  Estimated value:", &breakdown.value_string());
+    }
+    if !is_associated {
+        println!();
+        println!("* Please run `spl-token gc` to clean up Aux accounts");
+    }
+    Ok(None)
+}
+
+fn print_json(value: &serde_json::Value, output: &str) -> CommandResult {
+    let json = if output == "json-compact" {
+        serde_json::to_string(value)?
+    } else {
+        serde_json::to_string_pretty(value)?
+    };
+    println!("{}", json);
+    Ok(None)
+}
+
+/// Decimals and spot price of a hedged mint, shared by the accounts holding it.
+struct HedgePricing {
+    hedge_config: HedgeConfig,
+    asset_decimals: u8,
+    stable_decimals: u8,
+    asset_reserve: u64,
+    stable_reserve: u64,
+}
+
+/// Hedged components backing the balance of a token account.
+struct HedgedBreakdown {
+    asset: f64,
+    usdc: f64,
+    /// `usdc` plus `asset` at the spot price of the pool, in stablecoin.
+    value: Option<f64>,
+}
+
+impl HedgedBreakdown {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "asset": self.asset,
+            "usdc": self.usdc,
+            "estimatedValue": self.value,
+        })
+    }
+
+    fn value_string(&self) -> String {
+        self.value
+            .map_or_else(|| "-".to_string(), |value| value.to_string())
+    }
+}
+
+/// Fetch the pricing of `mint`, or `None` if it is not a hedged mint.
+fn get_hedge_pricing(config: &Config, mint: &Pubkey) -> Result<Option<HedgePricing>, Error> {
+    let (address, _) = find_hedge_config_with_program_id(&config.program_id, mint);
+    if get_account_data(config, &address)?.is_none() {
+        return Ok(None);
+    }
+    let (hedge_config, swap) = get_hedge_config(config, mint)?;
+    let reserve_balance = |reserve: Option<Pubkey>| -> Result<u64, Error> {
+        let reserve = reserve.ok_or_else(|| {
+            format!(
+                "Swap pool {} does not trade the hedged mint",
+                hedge_config.swap_pool
+            )
+        })?;
+        let data = get_account_data(config, &reserve)?
+            .ok_or_else(|| format!("Could not find swap reserve {}", reserve))?;
+        Ok(Account::unpack(&data)?.amount)
+    };
+    Ok(Some(HedgePricing {
+        hedge_config,
+        asset_decimals: get_mint(config, &hedge_config.asset_mint)?.decimals,
+        stable_decimals: get_mint(config, &hedge_config.stable_mint)?.decimals,
+        asset_reserve: reserve_balance(swap.reserve_for_mint(&hedge_config.asset_mint))?,
+        stable_reserve: reserve_balance(swap.reserve_for_mint(&hedge_config.stable_mint))?,
+    }))
+}
+
+/// Fetch the position of `account`, or `None` if it has none.
+fn get_hedged_breakdown(
+    config: &Config,
+    pricing: &HedgePricing,
+    account: &Pubkey,
+) -> Result<Option<HedgedBreakdown>, Error> {
+    let (address, _) = find_position_with_program_id(&config.program_id, account);
+    let mut position = match get_account_data(config, &address)? {
+        Some(data) => Position::unpack(&data)?,
+        None => return Ok(None),
+    };
+    settle_position(config, &pricing.hedge_config, &mut position)?;
+    let value = if pricing.asset_reserve > 0 {
+        let asset_value = u128::from(position.asset) * u128::from(pricing.stable_reserve)
+            / u128::from(pricing.asset_reserve);
+        Some(spl_token::amount_to_ui_amount(
+            (asset_value as u64).saturating_add(position.usdc),
+            pricing.stable_decimals,
+        ))
+    } else {
+        None
+    };
+    Ok(Some(HedgedBreakdown {
+        asset: spl_token::amount_to_ui_amount(position.asset, pricing.asset_decimals),
+        usdc: spl_token::amount_to_ui_amount(position.usdc, pricing.stable_decimals),
+        value,
+    }))
+}
+
+/// Fetch the breakdown of `account` holding `mint`, or `None` if the mint is
+/// not hedged or the account has no position.
+fn get_account_breakdown(
+    config: &Config,
+    mint: &Pubkey,
+    account: &Pubkey,
+) -> Result<Option<HedgedBreakdown>, Error> {
+    match get_hedge_pricing(config, mint)? {
+        Some(pricing) => get_hedged_breakdown(config, &pricing, account),
+        None => Ok(None),
+    }
+}
+
+fn get_multisig(config: &Config, address: &Pubkey) -> Result<Multisig, Error> {
+    let account = config.rpc_client.get_account(address)?;
+    Multisig::unpack(&account.data).map_err(|e| e.into())
+}
+
+fn command_multisig(config: &Config, address: Pubkey) -> CommandResult {
+    let multisig = get_multisig(config, &address)?;
+    let n = multisig.n as usize;
+    assert!(n <= multisig.signers.len());
+    println!();
+    println_name_value("Address:", &address.to_string());
+    println_name_value("M/N:", &format!("{}/{}", multisig.m, n));
+    println_name_value("Signers:", " ");
+    let width = if n >= 9 { 4 } else { 3 };
+    for i in 0..n {
+        let title = format!("{1:>0$}:", width, i + 1);
+        let pubkey = &multisig.signers[i];
+        println_name_value(&title, &pubkey.to_string())
+    }
+    Ok(None)
+}
+
+/// Fetch every initialized entry of the asset registry, sorted by mint.
+fn get_asset_entries(config: &Config) -> Result<Vec<(Pubkey, AssetEntry)>, Error> {
+    let accounts = config.rpc_client.get_program_accounts_with_config(
+        &config.program_id,
+        RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::DataSize(AssetEntry::LEN as u64)]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        },
+    )?;
+    let mut entries: Vec<_> = accounts
+        .into_iter()
+        .filter_map(|(address, account)| {
+            AssetEntry::unpack(&account.data)
+                .ok()
+                .map(|entry| (address, entry))
+        })
+        .collect();
+    entries.sort_by_key(|(_, entry)| entry.mint.to_string());
+    Ok(entries)
+}
+
+fn command_registry(config: &Config) -> CommandResult {
+    let entries = get_asset_entries(config)?;
+    if entries.is_empty() {
+        println!("No registered assets");
+        return Ok(None);
+    }
+    for (address, entry) in entries {
+        println!();
+        println_name_value("Address:", &address.to_string());
+        println_name_value("Mint:", &entry.mint.to_string());
+        println_name_value("Kind:", &format!("{:?}", entry.kind));
+        println_name_value("Decimals:", &entry.decimals.to_string());
+        println_name_value("Oracle:", &entry.oracle.to_string());
+        println_name_value("Swap program:", &entry.swap_program_id.to_string());
+        println_name_value("Swap pool:", &entry.swap_pool.to_string());
+    }
+    Ok(None)
+}
+
+/// Fetch the data of `address`, or `None` if the account does not exist.
+fn get_account_data(config: &Config, address: &Pubkey) -> Result<Option<Vec<u8>>, Error> {
+    Ok(config
+        .rpc_client
+        .get_account_with_commitment(address, config.rpc_client.commitment())?
+        .value
+        .map(|account| account.data))
+}
+
+/// Fetch the token account at `address`, or `None` if it is not an account of the
+/// configured token program.
+fn get_token_account(config: &Config, address: &Pubkey) -> Result<Option<UiTokenAccount>, Error> {
+    let account = match config
+        .rpc_client
+        .get_account_with_commitment(address, config.rpc_client.commitment())?
+        .value
+    {
+        Some(account) if account.owner == config.program_id => account,
+        _ => return Ok(None),
+    };
+    parse_token_account(config, &account.data, &mut HashMap::new()).map(Some)
+}
+
+fn get_token_account_balance(config: &Config, address: &Pubkey) -> Result<UiTokenAmount, Error> {
+    get_token_account(config, address)?
+        .map(|account| account.token_amount)
+        .ok_or_else(|| format!("Could not find token account {}", address).into())
+}
+
+/// Fetch the token accounts of `owner` under the configured token program,
+/// optionally restricted to `mint`. Accounts that cannot be decoded come back
+/// with the reason.
+fn get_token_accounts_by_owner(
+    config: &Config,
+    owner: &Pubkey,
+    mint: Option<Pubkey>,
+) -> Result<TokenAccounts, Error> {
+    let mut filters = vec![
+        RpcFilterType::DataSize(Account::LEN as u64),
+        // the owner follows the mint
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(32, owner.as_ref())),
+    ];
+    if let Some(mint) = mint {
+        filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            0,
+            mint.as_ref(),
+        )));
+    }
+    let accounts = config.rpc_client.get_program_accounts_with_config(
+        &config.program_id,
+        RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        },
+    )?;
+    let mut decimals = HashMap::new();
+    Ok(accounts
+        .into_iter()
+        .map(|(address, account)| {
+            let ui_token_account = parse_token_account(config, &account.data, &mut decimals)
+                .map_err(|err| format!("Account parse failure: {}", err));
+            (address, ui_token_account)
+        })
+        .collect())
+}
+
+/// Decode a token account, caching the decimals of the mints it looks up.
+fn parse_token_account(
+    config: &Config,
+    data: &[u8],
+    decimals: &mut HashMap<Pubkey, u8>,
+) -> Result<UiTokenAccount, Error> {
+    let mint = Account::unpack(data)?.mint;
+    let mint_decimals = match decimals.get(&mint) {
+        Some(mint_decimals) => *mint_decimals,
+        None => {
+            let mint_decimals = get_mint(config, &mint)?.decimals;
+            decimals.insert(mint, mint_decimals);
+            mint_decimals
+        }
+    };
+    match parse_token(data, Some(mint_decimals))? {
+        TokenAccountType::Account(ui_token_account) => Ok(ui_token_account),
+        _ => Err("Not a token account".into()),
+    }
+}
+
+fn get_mint(config: &Config, address: &Pubkey) -> Result<Mint, Error> {
+    let account = config.rpc_client.get_account(address)?;
+    Mint::unpack(&account.data).map_err(|_| format!("Invalid mint account {}", address).into())
+}
+
+fn get_program_config(config: &Config) -> Result<ProgramConfig, Error> {
+    let (address, _) = find_program_config_with_program_id(&config.program_id);
+    let account = config.rpc_client.get_account(&address)?;
+    ProgramConfig::unpack(&account.data).map_err(|e| e.into())
+}
+
+/// Fetch the hedge configuration of `token` and the swap pool it trades through.
+fn get_hedge_config(config: &Config, token: &Pubkey) -> Result<(HedgeConfig, SwapState), Error> {
+    let (address, _) = find_hedge_config_with_program_id(&config.program_id, token);
+    let hedge_config = get_account_data(config, &address)?
+        .and_then(|data| HedgeConfig::unpack(&data).ok())
+        .ok_or_else(|| format!("Token {} is not a hedged mint", token))?;
+    let swap = get_account_data(config, &hedge_config.swap_pool)?
+        .and_then(|data| SwapState::unpack(&data).ok())
+        .ok_or_else(|| format!("Invalid swap pool {}", hedge_config.swap_pool))?;
+    Ok((hedge_config, swap))
+}
+
+/// Settles the swaps out of the asset vault recorded in `hedge_config` into
+/// `position` like the program does, returning the address of the
+/// `HedgeEpoch` the program needs for it, if any.
+fn settle_position(
+    config: &Config,
+    hedge_config: &HedgeConfig,
+    position: &mut Position,
+) -> Result<Option<Pubkey>, Error> {
+    if !position.needs_hedge_epoch(hedge_config) {
+        position.settle_vault_swaps(hedge_config, None)?;
+        return Ok(None);
+    }
+    let (address, _) = find_hedge_epoch_with_program_id(
+        &config.program_id,
+        &hedge_config.mint,
+        position.hedge_epoch,
+    );
+    let hedge_epoch = get_account_data(config, &address)?
+        .and_then(|data| HedgeEpoch::unpack(&data).ok())
+        .ok_or_else(|| {
+            format!(
+                "Could not find the record of epoch {} of token {}",
+                position.hedge_epoch, hedge_config.mint
+            )
+        })?;
+    position.settle_vault_swaps(hedge_config, Some(hedge_epoch.stable_per_base))?;
+    Ok(Some(address))
+}
+
+/// The `HedgeEpoch` accounts the program needs to settle the positions of
+/// `accounts`, which hold `mint`.
+fn hedge_epoch_metas(
+    config: &Config,
+    mint: &Pubkey,
+    accounts: &[&Pubkey],
+) -> Result<Vec<AccountMeta>, Error> {
+    let (hedge_config_address, _) = find_hedge_config_with_program_id(&config.program_id, mint);
+    let hedge_config = match get_account_data(config, &hedge_config_address)?
+        .and_then(|data| HedgeConfig::unpack(&data).ok())
+    {
+        Some(hedge_config) => hedge_config,
+        None => return Ok(vec![]),
+    };
+    let mut metas = vec![];
+    for account in accounts {
+        let (position_address, _) = find_position_with_program_id(&config.program_id, account);
+        let mut position = match get_account_data(config, &position_address)?
+            .and_then(|data| Position::unpack(&data).ok())
+        {
+            Some(position) => position,
+            None => continue,
+        };
+        if let Some(address) = settle_position(config, &hedge_config, &mut position)? {
+            if !metas
+                .iter()
+                .any(|meta: &AccountMeta| meta.pubkey == address)
+            {
+                metas.push(AccountMeta::new_readonly(address, false));
+            }
+        }
+    }
+    Ok(metas)
+}
+
+/// Creates a `TransferChecked` instruction between token accounts of this
+/// program, naming the positions and records the program settles.
+fn transfer_checked_settling_positions(
+    config: &Config,
+    source: &Pubkey,
+    mint: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, Error> {
+    let mut instruction = transfer_checked_with_positions(
+        &config.program_id,
+        source,
+        mint,
+        destination,
+        &config.owner,
+        &config.multisigner_pubkeys,
+        amount,
+        decimals,
+    )?;
+    instruction
+        .accounts
+        .extend(hedge_epoch_metas(config, mint, &[source, destination])?);
+    Ok(instruction)
+}
+
+/// Returns `basis_points` of `amount`, rounded down like the program does.
+fn basis_points_of(amount: u64, basis_points: u16) -> u64 {
+    (u128::from(amount) * u128::from(basis_points) / 10_000) as u64
+}
+
+/// Returns the share of `component` backing `amount` out of a balance of `total`.
+fn proportional_share(component: u64, amount: u64, total: u64) -> u64 {
+    if total == 0 {
+        return 0;
+    }
+    (u128::from(component) * u128::from(amount) / u128::from(total)) as u64
+}
+
+#[allow(clippy::too_many_arguments)]
+fn command_hedge_deposit(
+    config: &Config,
+    token: Pubkey,
+    ui_amount: f64,
+    source: Option<Pubkey>,
+    account: Option<Pubkey>,
+    minimum_ui_amount_out: f64,
+    host_fee_account: Option<Pubkey>,
+) -> CommandResult {
+    let (hedge_config, swap) = get_hedge_config(config, &token)?;
+    let program_config = get_program_config(config)?;
+    let asset_decimals = get_mint(config, &hedge_config.asset_mint)?.decimals;
+    let stable_decimals = get_mint(config, &hedge_config.stable_mint)?.decimals;
+    let amount = spl_token::ui_amount_to_amount(ui_amount, asset_decimals);
+    let minimum_amount_out = spl_token::ui_amount_to_amount(minimum_ui_amount_out, stable_decimals);
+
+    // The asset lives under the token program of the swap pool
+    let source = source.unwrap_or_else(|| {
+        get_associated_token_address_with_program_id(
+            &config.owner,
+            &hedge_config.asset_mint,
+            &swap.token_program_id,
+        )
+    });
+    let account = account.unwrap_or_else(|| config.associated_token_address(&config.owner, &token));
+    let account_owner = get_account_data(config, &account)?
+        .and_then(|data| Account::unpack(&data).ok())
+        .ok_or_else(|| format!("Could not find token account {}", account))?
+        .owner;
+
+    println!(
+        "Deposit {} tokens\n  Token: {}\n  Source: {}\n  Account: {}",
+        ui_amount, token, source, account
+    );
+
+    let mut instructions = vec![];
+    let mut lamports_needed = 0;
+    let (position, _) = find_position_with_program_id(&config.program_id, &account);
+    if get_account_data(config, &position)?.is_none() {
+        println!("  Creating position {}", position);
+        instructions.push(initialize_position(
+            &config.program_id,
+            &account,
+            &config.fee_payer,
+        )?);
+        lamports_needed += config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(Position::LEN)?;
+    }
+    let (deposit_record, _) =
+        find_deposit_record_with_program_id(&config.program_id, &token, &account_owner);
+    if get_account_data(config, &deposit_record)?.is_none() {
+        instructions.push(initialize_deposit_record(
+            &config.program_id,
+            &token,
+            &account_owner,
+            &config.fee_payer,
+        )?);
+        lamports_needed += config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(DepositRecord::LEN)?;
+    }
+
+    let swap_authority = swap.authority(&hedge_config.swap_program_id, &hedge_config.swap_pool)?;
+    let swap_source = swap
+        .reserve_for_mint(&hedge_config.asset_mint)
+        .ok_or_else(|| {
+            format!(
+                "Swap pool {} does not trade the asset",
+                hedge_config.swap_pool
+            )
+        })?;
+    let swap_destination = swap
+        .reserve_for_mint(&hedge_config.stable_mint)
+        .ok_or_else(|| {
+            format!(
+                "Swap pool {} does not trade the stablecoin",
+                hedge_config.swap_pool
+            )
+        })?;
+    let protocol_fee_account = get_associated_token_address_with_program_id(
+        &program_config.protocol_fee_owner,
+        &hedge_config.asset_mint,
+        &swap.token_program_id,
+    );
+    instructions.push(deposit(
+        &config.program_id,
+        &token,
+        &account,
+        &account_owner,
+        &source,
+        &config.owner,
+        &hedge_config.asset_vault,
+        &hedge_config.stable_vault,
+        &hedge_config.fee_account,
+        &protocol_fee_account,
+        &hedge_config.swap_program_id,
+        &hedge_config.swap_pool,
+        &swap_authority,
+        &swap_source,
+        &swap_destination,
+        &swap.pool_mint,
+        &swap.pool_fee_account,
+        &swap.token_program_id,
+        host_fee_account.as_ref(),
+        amount,
+        minimum_amount_out,
+    )?);
+    let hedge_epochs = hedge_epoch_metas(config, &token, &[&account])?;
+    if let Some(instruction) = instructions.last_mut() {
+        instruction.accounts.extend(hedge_epochs);
+    }
+
+    let fee = basis_points_of(amount, hedge_config.deposit_fee_bps);
+    let net_amount = amount - fee;
+    let hedged = basis_points_of(net_amount, hedge_config.target_ratio_bps);
+    println_name_value(
+        "  Fee:",
+        &spl_token::amount_to_ui_amount(fee, asset_decimals).to_string(),
+    );
+    println_name_value(
+        "  Kept as asset:",
+        &spl_token::amount_to_ui_amount(net_amount - hedged, asset_decimals).to_string(),
+    );
+    println_name_value(
+        "  Swapped to usdc:",
+        &format!(
+            "{} (at least {} usdc)",
+            spl_token::amount_to_ui_amount(hedged, asset_decimals),
+            minimum_ui_amount_out
+        ),
+    );
+    Ok(Some((lamports_needed, vec![instructions])))
+}
+
+fn command_hedge_withdraw(
+    config: &Config,
+    token: Pubkey,
+    ui_amount: Option<f64>,
+    account: Option<Pubkey>,
+    request: bool,
+) -> CommandResult {
+    let (hedge_config, swap) = get_hedge_config(config, &token)?;
+    let program_config = get_program_config(config)?;
+    let account = account.unwrap_or_else(|| config.associated_token_address(&config.owner, &token));
+    let token_account = get_account_data(config, &account)?
+        .and_then(|data| Account::unpack(&data).ok())
+        .ok_or_else(|| format!("Could not find token account {}", account))?;
+    if token_account.mint != token {
+        return Err(format!("Account {} does not contain {} tokens", account, token).into());
+    }
+    let decimals = get_mint(config, &token)?.decimals;
+    let amount = ui_amount.map_or(token_account.amount, |ui_amount| {
+        spl_token::ui_amount_to_amount(ui_amount, decimals)
+    });
+    if amount > token_account.amount {
+        return Err(format!(
+            "Error: Account {} has insufficient funds, current balance is {}",
+            account,
+            spl_token::amount_to_ui_amount(token_account.amount, decimals)
+        )
+        .into());
+    }
+
+    if request {
+        println!(
+            "Request withdrawal of {} tokens\n  Token: {}\n  Account: {}",
+            spl_token::amount_to_ui_amount(amount, decimals),
+            token,
+            account
+        );
+        let (withdraw_ticket, _) =
+            find_withdraw_ticket_with_program_id(&config.program_id, &account);
+        let lamports_needed = if get_account_data(config, &withdraw_ticket)?.is_none() {
+            config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(WithdrawTicket::LEN)?
+        } else {
+            0
+        };
+        let instructions = vec![request_withdraw(
+            &config.program_id,
+            &account,
+            &config.fee_payer,
+            &config.owner,
+            &config.multisigner_pubkeys,
+            amount,
+        )?];
+        return Ok(Some((lamports_needed, vec![instructions])));
+    }
+
+    if program_config.withdraw_delay_slots > 0 && !program_config.bypass_withdraw_delay {
+        let (withdraw_ticket, _) =
+            find_withdraw_ticket_with_program_id(&config.program_id, &account);
+        let ticket = get_account_data(config, &withdraw_ticket)?
+            .and_then(|data| WithdrawTicket::unpack(&data).ok());
+        match ticket {
+            Some(ticket) if ticket.amount >= amount => {
+                let slot = config.rpc_client.get_slot()?;
+                if slot < ticket.claimable_slot {
+                    return Err(format!(
+                        "Withdrawal is claimable from slot {}, current slot is {}",
+                        ticket.claimable_slot, slot
+                    )
+                    .into());
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "Withdrawals are delayed by {} slots, request one first with `--request`",
+                    program_config.withdraw_delay_slots
+                )
+                .into())
+            }
+        }
+    }
+
+    let asset_reserve = swap
+        .reserve_for_mint(&hedge_config.asset_mint)
+        .ok_or_else(|| {
+            format!(
+                "Swap pool {} does not trade the asset",
+                hedge_config.swap_pool
+            )
+        })?;
+    let stable_reserve = swap
+        .reserve_for_mint(&hedge_config.stable_mint)
+        .ok_or_else(|| {
+            format!(
+                "Swap pool {} does not trade the stablecoin",
+                hedge_config.swap_pool
+            )
+        })?;
+    let (position_address, _) = find_position_with_program_id(&config.program_id, &account);
+    let mut position = get_account_data(config, &position_address)?
+        .and_then(|data| Position::unpack(&data).ok())
+        .ok_or_else(|| format!("Account {} has no position", account))?;
+    let hedge_epoch = settle_position(config, &hedge_config, &mut position)?;
+
+    println!(
+        "Withdraw {} tokens\n  Token: {}\n  Account: {}",
+        spl_token::amount_to_ui_amount(amount, decimals),
+        token,
+        account
+    );
+    let asset_decimals = get_mint(config, &hedge_config.asset_mint)?.decimals;
+    let stable_decimals = get_mint(config, &hedge_config.stable_mint)?.decimals;
+    let asset = proportional_share(position.asset, amount, token_account.amount);
+    let usdc = proportional_share(position.usdc, amount, token_account.amount);
+    let asset_fee = basis_points_of(asset, hedge_config.withdraw_fee_bps);
+    let usdc_fee = basis_points_of(usdc, hedge_config.withdraw_fee_bps);
+    println_name_value(
+        "  Asset:",
+        &spl_token::amount_to_ui_amount(asset - asset_fee, asset_decimals).to_string(),
+    );
+    println_name_value(
+        "  Usdc:",
+        &spl_token::amount_to_ui_amount(usdc - usdc_fee, stable_decimals).to_string(),
+    );
+    println_name_value(
+        "  Fee:",
+        &format!(
+            "{} asset, {} usdc",
+            spl_token::amount_to_ui_amount(asset_fee, asset_decimals),
+            spl_token::amount_to_ui_amount(usdc_fee, stable_decimals)
+        ),
+    );
+
+    // The asset and stablecoin are paid out under the token program of the swap pool
+    let mut instructions = vec![];
+    let mut lamports_needed = 0;
+    let mut destinations = vec![];
+    for mint in &[hedge_config.asset_mint, hedge_config.stable_mint] {
+        let destination = get_associated_token_address_with_program_id(
+            &config.owner,
+            mint,
+            &swap.token_program_id,
+        );
+        if get_account_data(config, &destination)?.is_none() {
+            instructions.push(create_associated_token_account_with_program_id(
+                &config.fee_payer,
+                &config.owner,
+                mint,
+                &swap.token_program_id,
+            ));
+            lamports_needed += config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(Account::LEN)?;
+        }
+        destinations.push(destination);
+    }
+    // The stablecoin share of the fee goes to the owner of the fee account
+    let fee_owner = get_account_data(config, &hedge_config.fee_account)?
+        .and_then(|data| Account::unpack(&data).ok())
+        .ok_or_else(|| format!("Could not find fee account {}", hedge_config.fee_account))?
+        .owner;
+    let fee_account_for = |owner: &Pubkey, mint: &Pubkey| {
+        get_associated_token_address_with_program_id(owner, mint, &swap.token_program_id)
+    };
+    instructions.push(withdraw(
+        &config.program_id,
+        &token,
+        &account,
+        &hedge_config.swap_pool,
+        &asset_reserve,
+        &stable_reserve,
+        &hedge_config.asset_vault,
+        &hedge_config.stable_vault,
+        &destinations[0],
+        &destinations[1],
+        &hedge_config.fee_account,
+        &fee_account_for(&fee_owner, &hedge_config.stable_mint),
+        &fee_account_for(&program_config.protocol_fee_owner, &hedge_config.asset_mint),
+        &fee_account_for(&program_config.protocol_fee_owner, &hedge_config.stable_mint),
+        &swap.token_program_id,
+        &config.owner,
+        &config.multisigner_pubkeys,
+        amount,
+    )?);
+    if let (Some(hedge_epoch), Some(instruction)) = (hedge_epoch, instructions.last_mut()) {
+        instruction
+            .accounts
+            .push(AccountMeta::new_readonly(hedge_epoch, false));
+    }
+    Ok(Some((lamports_needed, vec![instructions])))
+}
+
+fn command_hedge_info(config: &Config, token: Pubkey, address: Option<Pubkey>) -> CommandResult {
+    let (hedge_config, _) = get_hedge_config(config, &token)?;
+    let asset_decimals = get_mint(config, &hedge_config.asset_mint)?.decimals;
+    let stable_decimals = get_mint(config, &hedge_config.stable_mint)?.decimals;
+    let bps = |basis_points: u16| format!("{}%", f64::from(basis_points) / 100.0);
+
+    println!();
+    println_name_value("Mint:", &token.to_string());
+    println_name_value("Authority:", &hedge_config.authority.to_string());
+    println_name_value("Asset mint:", &hedge_config.asset_mint.to_string());
+    println_name_value("Stable mint:", &hedge_config.stable_mint.to_string());
+    println_name_value("Swap program:", &hedge_config.swap_program_id.to_string());
+    println_name_value("Swap pool:", &hedge_config.swap_pool.to_string());
+    println_name_value("Asset vault:", &hedge_config.asset_vault.to_string());
+    println_name_value("Stable vault:", &hedge_config.stable_vault.to_string());
+    println_name_value("Fee account:", &hedge_config.fee_account.to_string());
+    println_name_value("Target ratio:", &bps(hedge_config.target_ratio_bps));
+    println_name_value("Deposit fee:", &bps(hedge_config.deposit_fee_bps));
+    println_name_value("Withdraw fee:", &bps(hedge_config.withdraw_fee_bps));
+
+    let address = address.unwrap_or_else(|| config.associated_token_address(&config.owner, &token));
+    let (position_address, _) = find_position_with_program_id(&config.program_id, &address);
+    let position = match get_account_data(config, &position_address)? {
+        Some(data) => Position::unpack(&data)?,
+        None => {
+            println!();
+            println!("No position for account {}", address);
+            return Ok(None);
+        }
+    };
+    println!();
+    println_name_value("Account:", &address.to_string());
+    println_name_value("Position:", &position_address.to_string());
+    println_name_value(
+        "  Asset:",
+        &spl_token::amount_to_ui_amount(position.asset, asset_decimals).to_string(),
+    );
+    println_name_value(
+        "  Usdc:",
+        &spl_token::amount_to_ui_amount(position.usdc, stable_decimals).to_string(),
+    );
+    println_name_value(
+        "  Rewards:",
+        &spl_token::amount_to_ui_amount(position.rewards, stable_decimals).to_string(),
+    );
+    if position.tracks_cost_basis {
+        println_name_value(
+            "  Cost basis:",
+            &spl_token::amount_to_ui_amount(position.cost_basis, stable_decimals).to_string(),
+        );
+    }
+    println_name_value(
+        "  Realized PnL:",
+        &format!(
+            "{}{}",
+            if position.realized_pnl < 0 { "-" } else { "" },
+            spl_token::amount_to_ui_amount(
+                i128::from(position.realized_pnl).unsigned_abs() as u64,
+                stable_decimals
+            )
+        ),
+    );
+    Ok(None)
+}
+
+fn get_portfolio(config: &Config, address: &Pubkey) -> Result<Portfolio, Error> {
+    let account = config.rpc_client.get_account(address)?;
+    if account.owner != config.program_id {
+        return Err(format!("{} is not a portfolio account", address).into());
+    }
+    Portfolio::unpack(&account.data)
+        .map_err(|_| format!("{} is not a portfolio account", address).into())
+}
+
+fn portfolio_weights(portfolio: &Portfolio) -> [u8; PORTFOLIO_ASSETS] {
+    [
+        portfolio.amountAsset1,
+        portfolio.amountAsset2,
+        portfolio.amountAsset3,
+        portfolio.amountAsset4,
+        portfolio.amountAsset5,
+        portfolio.amountAsset6,
+        portfolio.amountAsset7,
+        portfolio.amountAsset8,
+        portfolio.amountAsset9,
+    ]
+}
+
+fn command_portfolio_create(config: &Config, spec_path: &str, portfolio: Pubkey) -> CommandResult {
+    let spec = PortfolioSpec::from_file(spec_path)?;
+    let assets = spec.validate()?;
+    let metadata_hash = spec.metadata_hash();
+    if !config.sign_only {
+        for asset in spec.assets.iter() {
+            let mint = Pubkey::from_str(&asset.mint)?;
+            let (asset_entry, _) = find_asset_entry_with_program_id(&config.program_id, &mint);
+            if get_account_data(config, &asset_entry)?
+                .and_then(|data| AssetEntry::unpack(&data).ok())
+                .is_none()
+            {
+                return Err(format!("Asset {} is not registered", mint).into());
+            }
+        }
+    }
+
+    println!("Creating portfolio {}", portfolio);
+    println_name_value("  Metadata URL:", &spec.metadata_url);
+    println_name_value("  Metadata hash:", &metadata_hash.to_string());
+    for (i, asset) in assets.iter().take(spec.assets.len()).enumerate() {
+        println_name_value(
+            &format!("  Asset {}:", i + 1),
+            &format!(
+                "{}  weight {}  period {}  sold into {}",
+                asset.mint, asset.weight, asset.period, asset.sell_into
+            ),
+        );
+    }
+
+    let minimum_balance_for_rent_exemption = if !config.sign_only {
+        config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(Portfolio::LEN)?
+    } else {
+        0
+    };
+    let [a1, a2, a3, a4, a5, a6, a7, a8, a9] = assets;
+    let instructions = vec![
+        system_instruction::create_account(
+            &config.fee_payer,
+            &portfolio,
+            minimum_balance_for_rent_exemption,
+            Portfolio::LEN as u64,
+            &config.program_id,
+        ),
+        initialize_portfolio(
+            &config.program_id,
+            &portfolio,
+            &config.owner,
+            &config.owner,
+            &spec.metadata_url.as_bytes().to_vec(),
+            &metadata_hash,
+            &a1.weight,
+            &a1.mint,
+            &a1.period,
+            &a1.sell_into,
+            &a2.weight,
+            &a2.mint,
+            &a2.period,
+            &a2.sell_into,
+            &a3.weight,
+            &a3.mint,
+            &a3.period,
+            &a3.sell_into,
+            &a4.weight,
+            &a4.mint,
+            &a4.period,
+            &a4.sell_into,
+            &a5.weight,
+            &a5.mint,
+            &a5.period,
+            &a5.sell_into,
+            &a6.weight,
+            &a6.mint,
+            &a6.period,
+            &a6.sell_into,
+            &a7.weight,
+            &a7.mint,
+            &a7.period,
+            &a7.sell_into,
+            &a8.weight,
+            &a8.mint,
+            &a8.period,
+            &a8.sell_into,
+            &a9.weight,
+            &a9.mint,
+            &a9.period,
+            &a9.sell_into,
+        )?,
+    ];
+    Ok(Some((
+        minimum_balance_for_rent_exemption,
+        vec![instructions],
+    )))
+}
+
+fn command_portfolio_show(config: &Config, address: Pubkey, output: Option<&str>) -> CommandResult {
+    let portfolio = get_portfolio(config, &address)?;
+    let metadata_url = String::from_utf8_lossy(&portfolio.metadataUrl)
+        .trim_end_matches('\0')
+        .to_string();
+    let assets: Vec<_> = (1..=PORTFOLIO_ASSETS as u8)
+        .zip(portfolio_weights(&portfolio).iter())
+        .filter(|(_, weight)| **weight > 0)
+        .filter_map(|(index, weight)| {
+            portfolio
+                .asset_schedule(index)
+                .map(|(mint, sell_into, period)| (index, *weight, mint, period, sell_into))
+        })
+        .collect();
+
+    if let Some(output) = output {
+        let value = serde_json::json!({
+            "address": address.to_string(),
+            "creator": portfolio.creator_portfolio.to_string(),
+            "initialized": portfolio.is_initialize == 1,
+            "metadataUrl": metadata_url,
+            "metadataHash": portfolio.metadataHash,
+            "assets": assets
+                .iter()
+                .map(|(index, weight, mint, period, sell_into)| {
+                    serde_json::json!({
+                        "index": index,
+                        "mint": mint.to_string(),
+                        "weight": weight,
+                        "period": period,
+                        "sellInto": sell_into.to_string(),
+                    })
+                })
+                .collect::<Vec<_>>(),
+        });
+        return print_json(&value, output);
+    }
+
+    println!();
+    println_name_value("Address:", &address.to_string());
+    println_name_value("Creator:", &portfolio.creator_portfolio.to_string());
+    println_name_value("Initialized:", &(portfolio.is_initialize == 1).to_string());
+    println_name_value("Metadata URL:", &metadata_url);
+    println_name_value("Metadata hash:", &portfolio.metadataHash.to_string());
+    println!();
+    println!(
+        "{:<5} {:<44} {:>6} {:>6}  Sold into",
+        "Slot", "Mint", "Weight", "Period"
+    );
+    for (index, weight, mint, period, sell_into) in assets {
+        println!(
+            "{:<5} {:<44} {:>6} {:>6}  {}",
+            index,
+            mint.to_string(),
+            weight,
+            period,
+            sell_into
+        );
+    }
+    Ok(None)
+}
+
+/// One swap of a portfolio quote, between the quote mint and an asset.
+struct QuoteLeg {
+    index: u8,
+    asset: Pubkey,
+    /// The pool the swap goes through, `None` when the asset is the quote mint.
+    pool: Option<Pubkey>,
+    source_decimals: u8,
+    destination_decimals: u8,
+    quote: SwapQuote,
+}
+
+impl QuoteLeg {
+    fn to_json(&self) -> serde_json::Value {
+        let source = |amount| spl_token::amount_to_ui_amount(amount, self.source_decimals);
+        serde_json::json!({
+            "index": self.index,
+            "asset": self.asset.to_string(),
+            "pool": self.pool.map(|pool| pool.to_string()),
+            "amountIn": source(self.quote.amount_in),
+            "amountOut": spl_token::amount_to_ui_amount(
+                self.quote.amount_out,
+                self.destination_decimals
+            ),
+            "tradeFee": source(self.quote.trade_fee),
+            "ownerFee": source(self.quote.owner_fee),
+            "priceImpactBps": self.quote.price_impact_bps,
+        })
+    }
+}
+
+/// Quote the swap of `amount_in` between `quote_mint` and `asset` through the
+/// preferred pool of the asset in the registry.
+fn quote_asset_swap(
+    config: &Config,
+    asset: &Pubkey,
+    quote_mint: &Pubkey,
+    amount_in: u64,
+    redeem: bool,
+) -> Result<(Pubkey, SwapQuote), Error> {
+    let (asset_entry, _) = find_asset_entry_with_program_id(&config.program_id, asset);
+    let asset_entry = get_account_data(config, &asset_entry)?
+        .and_then(|data| AssetEntry::unpack(&data).ok())
+        .ok_or_else(|| format!("Asset {} is not registered", asset))?;
+    let pool = asset_entry.swap_pool;
+    let swap = get_account_data(config, &pool)?
+        .and_then(|data| SwapState::unpack(&data).ok())
+        .ok_or_else(|| format!("Invalid swap pool {}", pool))?;
+    let (source_mint, destination_mint) = if redeem {
+        (asset, quote_mint)
+    } else {
+        (quote_mint, asset)
+    };
+    let reserve_balance = |mint: &Pubkey| -> Result<u64, Error> {
+        let reserve = swap.reserve_for_mint(mint).ok_or_else(|| {
+            format!(
+                "Swap pool {} of asset {} does not trade {}",
+                pool, asset, mint
+            )
+        })?;
+        let account = config.rpc_client.get_account(&reserve)?;
+        Ok(unpack_token_account(&swap.token_program_id, &account.owner, &account.data)?.amount)
+    };
+    let source_reserve = reserve_balance(source_mint)?;
+    let destination_reserve = reserve_balance(destination_mint)?;
+    let quote = swap
+        .quote(source_mint, amount_in, source_reserve, destination_reserve)
+        .map_err(|err| format!("Could not quote swap pool {}: {}", pool, err))?;
+    Ok((pool, quote))
+}
+
+/// Preview a deposit of `ui_amount` of `quote_mint` into the portfolio
+/// `address`, bought into each asset by weight, or with `redeem` the sale of
+/// `ui_amount` portfolio units of the user portfolio `address` back into
+/// `quote_mint`.
+fn command_portfolio_quote(
+    config: &Config,
+    address: Pubkey,
+    quote_mint: Pubkey,
+    ui_amount: f64,
+    redeem: bool,
+    output: Option<&str>,
+) -> CommandResult {
+    let user_portfolio = if redeem {
+        Some(get_user_portfolio(config, &address)?)
+    } else {
+        None
+    };
+    let portfolio_address = user_portfolio
+        .as_ref()
+        .map_or(address, |user_portfolio| user_portfolio.portfolio_address);
+    let portfolio = get_portfolio(config, &portfolio_address)?;
+    let quote_decimals = get_mint(config, &quote_mint)?.decimals;
+
+    let mut slots = vec![];
+    for (index, weight) in (1..=PORTFOLIO_ASSETS as u8).zip(portfolio_weights(&portfolio).iter()) {
+        if *weight == 0 {
+            continue;
+        }
+        let (asset, _, _) = portfolio.asset_schedule(index).unwrap();
+        slots.push((index, *weight, asset, get_mint(config, &asset)?.decimals));
+    }
+    // A deposit splits the amount by weight, a redemption sells the same
+    // share of every asset the user portfolio actually holds.
+    let amounts_in = match &user_portfolio {
+        Some(user_portfolio) => {
+            let asset_accounts = user_portfolio_assets(user_portfolio);
+            let mut holdings = vec![];
+            for (index, weight, _, decimals) in slots.iter().copied() {
+                let asset_account = &asset_accounts[usize::from(index) - 1];
+                holdings.push(Holding {
+                    weight,
+                    balance: get_token_account_balance(config, asset_account)?
+                        .amount
+                        .parse::<u64>()?,
+                    decimals,
+                });
+            }
+            redeem_amounts(&holdings, ui_amount)?
+        }
+        None => slots
+            .iter()
+            .map(|(_, weight, _, _)| {
+                let ui_amount = ui_amount * f64::from(*weight) / 100.0;
+                spl_token::ui_amount_to_amount(ui_amount, quote_decimals)
+            })
+            .collect(),
+    };
+
+    let mut legs = vec![];
+    for ((index, _, asset, asset_decimals), amount_in) in slots.into_iter().zip(amounts_in) {
+        let (source_decimals, destination_decimals) = if redeem {
+            (asset_decimals, quote_decimals)
+        } else {
+            (quote_decimals, asset_decimals)
+        };
+        let (pool, quote) = if asset == quote_mint {
+            let quote = SwapQuote {
+                amount_in,
+                amount_out: amount_in,
+                ..SwapQuote::default()
+            };
+            (None, quote)
+        } else {
+            let (pool, quote) = quote_asset_swap(config, &asset, &quote_mint, amount_in, redeem)?;
+            (Some(pool), quote)
+        };
+        legs.push(QuoteLeg {
+            index,
+            asset,
+            pool,
+            source_decimals,
+            destination_decimals,
+            quote,
+        });
+    }
+
+    // Only the quote mint side of the legs adds up.
+    let total: u64 = legs
+        .iter()
+        .map(|leg| {
+            if redeem {
+                leg.quote.amount_out
+            } else {
+                leg.quote.amount_in
+            }
+        })
+        .sum();
+    let total = spl_token::amount_to_ui_amount(total, quote_decimals);
+
+    if let Some(output) = output {
+        let direction = if redeem { "redeem" } else { "deposit" };
+        let value = serde_json::json!({
+            "portfolio": portfolio_address.to_string(),
+            "userPortfolio": user_portfolio.map(|_| address.to_string()),
+            "mint": quote_mint.to_string(),
+            "direction": direction,
+            "amount": ui_amount,
+            "total": total,
+            "legs": legs.iter().map(QuoteLeg::to_json).collect::<Vec<_>>(),
+        });
+        return print_json(&value, output);
+    }
+
+    println!();
+    println_name_value("Portfolio:", &portfolio_address.to_string());
+    if redeem {
+        println_name_value("User portfolio:", &address.to_string());
+        println_name_value("Redeem:", &format!("{} portfolio units", ui_amount));
+        println_name_value("Into:", &quote_mint.to_string());
+        println_name_value("Expected total:", &total.to_string());
+    } else {
+        println_name_value("Deposit:", &format!("{} {}", ui_amount, quote_mint));
+        println_name_value("Total paid:", &total.to_string());
+    }
+    println!();
+    println!(
+        "{:<5} {:<44} {:>14} {:>14} {:>12} {:>7}",
+        "Index", "Asset", "In", "Expected out", "Fees", "Impact"
+    );
+    for leg in legs {
+        let source = |amount| spl_token::amount_to_ui_amount(amount, leg.source_decimals);
+        println!(
+            "{:<5} {:<44} {:>14} {:>14} {:>12} {:>6.2}%",
+            leg.index,
+            leg.asset.to_string(),
+            source(leg.quote.amount_in),
+            spl_token::amount_to_ui_amount(leg.quote.amount_out, leg.destination_decimals),
+            source(leg.quote.trade_fee + leg.quote.owner_fee),
+            leg.quote.price_impact_bps as f64 / 100.0,
+        );
+    }
+    Ok(None)
+}
+
+fn get_user_portfolio(config: &Config, address: &Pubkey) -> Result<UserPortfolio, Error> {
+    let account = config.rpc_client.get_account(address)?;
+    if account.owner != config.program_id {
+        return Err(format!("{} is not a user portfolio account", address).into());
+    }
+    UserPortfolio::unpack(&account.data)
+        .map_err(|_| format!("{} is not a user portfolio account", address).into())
+}
+
+fn user_portfolio_assets(user_portfolio: &UserPortfolio) -> [Pubkey; PORTFOLIO_ASSETS] {
+    [
+        user_portfolio.splu_asset1,
+        user_portfolio.splu_asset2,
+        user_portfolio.splu_asset3,
+        user_portfolio.splu_asset4,
+        user_portfolio.splu_asset5,
+        user_portfolio.splu_asset6,
+        user_portfolio.splu_asset7,
+        user_portfolio.splu_asset8,
+        user_portfolio.splu_asset9,
+    ]
+}
+
+/// The weighted slots of the portfolio behind `user_portfolio`, as
+/// `(weight, mint, decimals, asset account)`.
+fn user_portfolio_slots(
+    config: &Config,
+    user_portfolio: &UserPortfolio,
+) -> Result<Vec<(u8, Pubkey, u8, Pubkey)>, Error> {
+    let portfolio = get_portfolio(config, &user_portfolio.portfolio_address)?;
+    let mut slots = vec![];
+    for ((index, weight), asset_account) in (1..=PORTFOLIO_ASSETS as u8)
+        .zip(portfolio_weights(&portfolio).iter())
+        .zip(user_portfolio_assets(user_portfolio).iter())
+    {
+        if *weight == 0 {
+            continue;
+        }
+        let (mint, _, _) = portfolio.asset_schedule(index).unwrap();
+        let decimals = get_mint(config, &mint)?.decimals;
+        slots.push((*weight, mint, decimals, *asset_account));
+    }
+    Ok(slots)
+}
+
+/// Creates a user portfolio of `portfolio` owned by the configured owner.
+/// Each asset is held in the associated token account of the user portfolio
+/// address, which approves the owner so that it can redeem the assets. An
+/// initial deposit of `ui_amount` portfolio units is made before joining, so
+/// that it counts against the deposit caps of the portfolio.
+fn command_portfolio_join(
+    config: &Config,
+    portfolio_address: Pubkey,
+    user_portfolio: Pubkey,
+    delegated_amount: u64,
+    ui_amount: Option<f64>,
+) -> CommandResult {
+    let portfolio = get_portfolio(config, &portfolio_address)?;
+    let (deposit_limits, _) =
+        find_deposit_limits_with_program_id(&config.program_id, &portfolio_address);
+    let has_deposit_limits = get_account_data(config, &deposit_limits)?.is_some();
+
+    println!(
+        "Joining portfolio {}\n  User portfolio: {}",
+        portfolio_address, user_portfolio
+    );
+
+    let minimum_balance_for_rent_exemption = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(UserPortfolio::LEN)?;
+    let mut lamports_needed = minimum_balance_for_rent_exemption;
+    let mut asset_accounts = [Pubkey::default(); PORTFOLIO_ASSETS];
+    let mut create_instructions = vec![system_instruction::create_account(
+        &config.fee_payer,
+        &user_portfolio,
+        minimum_balance_for_rent_exemption,
+        UserPortfolio::LEN as u64,
+        &config.program_id,
+    )];
+    let mut approve_instructions = vec![];
+    let mut created = vec![];
+    for (i, asset_account) in asset_accounts.iter_mut().enumerate() {
+        let (mint, _, _) = portfolio.asset_schedule(i as u8 + 1).unwrap();
+        *asset_account = config.associated_token_address(&user_portfolio, &mint);
+        if created.contains(asset_account) {
+            continue;
+        }
+        println_name_value(&format!("  Asset {}:", i + 1), &asset_account.to_string());
+        create_instructions.push(config.create_associated_token_account(&user_portfolio, &mint));
+        approve_instructions.push(approve(
+            &config.program_id,
+            asset_account,
+            &config.owner,
+            &user_portfolio,
+            &[],
+            u64::MAX,
+        )?);
+        lamports_needed += config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(Account::LEN)?;
+        created.push(*asset_account);
+    }
+
+    let mut join_instructions = vec![];
+    if let Some(ui_amount) = ui_amount {
+        let weights = portfolio_weights(&portfolio);
+        for (i, asset_account) in asset_accounts.iter().enumerate() {
+            if weights[i] == 0 {
+                continue;
+            }
+            let (mint, _, _) = portfolio.asset_schedule(i as u8 + 1).unwrap();
+            let decimals = get_mint(config, &mint)?.decimals;
+            let asset_ui_amount = ui_amount * f64::from(weights[i]) / 100.0;
+            let amount = spl_token::ui_amount_to_amount(asset_ui_amount, decimals);
+            let source = config.associated_token_address(&config.owner, &mint);
+            println_name_value(&format!("  Deposit {}:", mint), &asset_ui_amount.to_string());
+            join_instructions.push(transfer_checked_settling_positions(
+                config,
+                &source,
+                &mint,
+                asset_account,
+                amount,
+                decimals,
+            )?);
+        }
+    }
+    let (deposit_record, _) =
+        find_deposit_record_with_program_id(&config.program_id, &portfolio_address, &config.owner);
+    if has_deposit_limits && get_account_data(config, &deposit_record)?.is_none() {
+        join_instructions.push(initialize_deposit_record(
+            &config.program_id,
+            &portfolio_address,
+            &config.owner,
+            &config.fee_payer,
+        )?);
+        lamports_needed += config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(DepositRecord::LEN)?;
+    }
+    join_instructions.push(create_Init_User_Portfolio(
+        &config.program_id,
+        &user_portfolio,
+        &portfolio_address,
+        &config.owner,
+        &config.owner,
+        &asset_accounts[0],
+        &asset_accounts[1],
+        &asset_accounts[2],
+        &asset_accounts[3],
+        &asset_accounts[4],
+        &asset_accounts[5],
+        &asset_accounts[6],
+        &asset_accounts[7],
+        &asset_accounts[8],
+        &delegated_amount,
+    )?);
+    Ok(Some((
+        lamports_needed,
+        vec![create_instructions, approve_instructions, join_instructions],
+    )))
+}
+
+/// Moves `ui_amount` portfolio units from the owner's associated token
+/// accounts into the user portfolio, split across the assets by weight.
+fn command_portfolio_deposit(config: &Config, address: Pubkey, ui_amount: f64) -> CommandResult {
+    let user_portfolio = get_user_portfolio(config, &address)?;
+    println!("Deposit {} into user portfolio {}", ui_amount, address);
+
+    let mut instructions = vec![];
+    for (weight, mint, decimals, asset_account) in user_portfolio_slots(config, &user_portfolio)? {
+        let asset_ui_amount = ui_amount * f64::from(weight) / 100.0;
+        let amount = spl_token::ui_amount_to_amount(asset_ui_amount, decimals);
+        let source = config.associated_token_address(&config.owner, &mint);
+        println_name_value(&format!("  {}:", mint), &asset_ui_amount.to_string());
+        instructions.push(transfer_checked_settling_positions(
+            config,
+            &source,
+            &mint,
+            &asset_account,
+            amount,
+            decimals,
+        )?);
+    }
+    Ok(Some((0, vec![instructions])))
+}
+
+/// Moves `ui_amount` portfolio units, or everything, out of the user
+/// portfolio into the owner's associated token accounts.
+fn command_portfolio_redeem(
+    config: &Config,
+    address: Pubkey,
+    ui_amount: Option<f64>,
+) -> CommandResult {
+    let user_portfolio = get_user_portfolio(config, &address)?;
+    match ui_amount {
+        Some(ui_amount) => println!("Redeem {} from user portfolio {}", ui_amount, address),
+        None => println!("Redeem all assets from user portfolio {}", address),
+    }
+
+    let mut instructions = vec![];
+    let mut lamports_needed = 0;
+    let mut destinations = vec![];
+    let mut redeemed = vec![];
+    for (weight, mint, decimals, asset_account) in user_portfolio_slots(config, &user_portfolio)? {
+        let amount = match ui_amount {
+            Some(ui_amount) => {
+                spl_token::ui_amount_to_amount(ui_amount * f64::from(weight) / 100.0, decimals)
+            }
+            None if redeemed.contains(&asset_account) => continue,
+            None => {
+                redeemed.push(asset_account);
+                get_token_account_balance(config, &asset_account)?
+                    .amount
+                    .parse::<u64>()?
+            }
+        };
+        if amount == 0 {
+            continue;
+        }
+
+        let destination = config.associated_token_address(&config.owner, &mint);
+        if !destinations.contains(&destination) && get_account_data(config, &destination)?.is_none()
+        {
+            instructions.push(config.create_associated_token_account(&config.owner, &mint));
+            lamports_needed += config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(Account::LEN)?;
+        }
+        destinations.push(destination);
+        println_name_value(
+            &format!("  {}:", mint),
+            &spl_token::amount_to_ui_amount(amount, decimals).to_string(),
+        );
+        instructions.push(transfer_checked_settling_positions(
+            config,
+            &asset_account,
+            &mint,
+            &destination,
+            amount,
+            decimals,
+        )?);
+    }
+    if instructions.is_empty() {
+        println!("Nothing to redeem");
+        return Ok(None);
     }
-    Ok(None)
+    Ok(Some((lamports_needed, vec![instructions])))
 }
 
-fn get_multisig(config: &Config, address: &Pubkey) -> Result<Multisig, Error> {
-    let account = config.rpc_client.get_account(&address)?;
-    Multisig::unpack(&account.data).map_err(|e| e.into())
+fn command_portfolio_delegate(
+    config: &Config,
+    address: Pubkey,
+    delegate: Pubkey,
+    amount: u64,
+) -> CommandResult {
+    println!(
+        "Approve {} of user portfolio {}\n  Delegate: {}",
+        amount, address, delegate
+    );
+    let instructions = vec![approveUserPortfolio(
+        &config.program_id,
+        &address,
+        &delegate,
+        &config.owner,
+        &config.multisigner_pubkeys,
+        amount,
+    )?];
+    Ok(Some((0, vec![instructions])))
 }
 
-fn command_multisig(config: &Config, address: Pubkey) -> CommandResult {
-    let multisig = get_multisig(config, &address)?;
-    let n = multisig.n as usize;
-    assert!(n <= multisig.signers.len());
-    println!();
-    println_name_value("Address:", &address.to_string());
-    println_name_value("M/N:", &format!("{}/{}", multisig.m, n));
-    println_name_value("Signers:", " ");
-    let width = if n >= 9 { 4 } else { 3 };
-    for i in 0..n {
-        let title = format!("{1:>0$}:", width, i + 1);
-        let pubkey = &multisig.signers[i];
-        println_name_value(&title, &pubkey.to_string())
+fn command_portfolio_revoke(config: &Config, address: Pubkey) -> CommandResult {
+    println!("Revoking user portfolio {}", address);
+    let instructions = vec![approveUserPortfolio(
+        &config.program_id,
+        &address,
+        &Pubkey::default(),
+        &config.owner,
+        &config.multisigner_pubkeys,
+        0,
+    )?];
+    Ok(Some((0, vec![instructions])))
+}
+
+fn command_portfolio_positions(config: &Config) -> CommandResult {
+    let accounts = config.rpc_client.get_program_accounts_with_config(
+        &config.program_id,
+        RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(UserPortfolio::LEN as u64),
+                // the owner follows the account header and the user portfolio address
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(34, config.owner.as_ref())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        },
+    )?;
+    let mut user_portfolios: Vec<_> = accounts
+        .into_iter()
+        .filter_map(|(address, account)| {
+            UserPortfolio::unpack(&account.data)
+                .ok()
+                .map(|user_portfolio| (address, user_portfolio))
+        })
+        .collect();
+    if user_portfolios.is_empty() {
+        println!("No user portfolios");
+        return Ok(None);
+    }
+    user_portfolios.sort_by_key(|(address, _)| address.to_string());
+
+    for (address, user_portfolio) in user_portfolios {
+        println!();
+        println_name_value("Address:", &address.to_string());
+        println_name_value("Portfolio:", &user_portfolio.portfolio_address.to_string());
+        if user_portfolio.delegate == Pubkey::default() {
+            println_name_value("Delegation:", "");
+        } else {
+            println!("Delegation:");
+            println_name_value("  Delegate:", &user_portfolio.delegate.to_string());
+            println_name_value("  Allowance:", &user_portfolio.delegated_amount.to_string());
+        }
+        println_name_value("Assets:", " ");
+        match user_portfolio_slots(config, &user_portfolio) {
+            Ok(slots) => {
+                for (weight, mint, _, asset_account) in slots {
+                    let balance = get_token_account_balance(config, &asset_account)
+                        .map(|balance| balance.real_number_string_trimmed())
+                        .unwrap_or_else(|_| "-".to_string());
+                    println!("  {:<44} {:>3}%  {}", mint.to_string(), weight, balance);
+                }
+            }
+            Err(err) => println!("  {}", err),
+        }
     }
     Ok(None)
 }
 
 fn command_gc(config: &Config) -> CommandResult {
     println!("Fetching token accounts");
-    let accounts = config.rpc_client.get_token_accounts_by_owner(
-        &config.owner,
-        TokenAccountsFilter::ProgramId(spl_token::id()),
-    )?;
+    let accounts = get_token_accounts_by_owner(config, &config.owner, None)?;
     if accounts.is_empty() {
         println!("Nothing to do");
         return Ok(None);
@@ -1181,47 +2936,38 @@ fn command_gc(config: &Config) -> CommandResult {
 
     let mut accounts_by_token = HashMap::new();
 
-    for keyed_account in accounts {
-        if let UiAccountData::Json(parsed_account) = keyed_account.account.data {
-            if parsed_account.program == "spl-token" {
-                if let Ok(TokenAccountType::Account(ui_token_account)) =
-                    serde_json::from_value(parsed_account.parsed)
-                {
-                    let frozen = ui_token_account.state == UiAccountState::Frozen;
-
-                    let token = ui_token_account
-                        .mint
-                        .parse::<Pubkey>()
-                        .unwrap_or_else(|err| panic!("Invalid mint: {}", err));
-                    let token_account = keyed_account
-                        .pubkey
-                        .parse::<Pubkey>()
-                        .unwrap_or_else(|err| panic!("Invalid token account: {}", err));
-                    let token_amount = ui_token_account
-                        .token_amount
-                        .amount
-                        .parse::<u64>()
-                        .unwrap_or_else(|err| panic!("Invalid token amount: {}", err));
-
-                    let close_authority =
-                        ui_token_account.close_authority.map_or(config.owner, |s| {
-                            s.parse::<Pubkey>()
-                                .unwrap_or_else(|err| panic!("Invalid close authority: {}", err))
-                        });
-
-                    let entry = accounts_by_token.entry(token).or_insert_with(HashMap::new);
-                    entry.insert(
-                        token_account,
-                        (
-                            token_amount,
-                            ui_token_account.token_amount.decimals,
-                            frozen,
-                            close_authority,
-                        ),
-                    );
-                }
-            }
-        }
+    for (token_account, ui_token_account) in accounts {
+        let ui_token_account = match ui_token_account {
+            Ok(ui_token_account) => ui_token_account,
+            Err(_) => continue,
+        };
+        let frozen = ui_token_account.state == UiAccountState::Frozen;
+
+        let token = ui_token_account
+            .mint
+            .parse::<Pubkey>()
+            .unwrap_or_else(|err| panic!("Invalid mint: {}", err));
+        let token_amount = ui_token_account
+            .token_amount
+            .amount
+            .parse::<u64>()
+            .unwrap_or_else(|err| panic!("Invalid token amount: {}", err));
+
+        let close_authority = ui_token_account.close_authority.map_or(config.owner, |s| {
+            s.parse::<Pubkey>()
+                .unwrap_or_else(|err| panic!("Invalid close authority: {}", err))
+        });
+
+        let entry = accounts_by_token.entry(token).or_insert_with(HashMap::new);
+        entry.insert(
+            token_account,
+            (
+                token_amount,
+                ui_token_account.token_amount.decimals,
+                frozen,
+                close_authority,
+            ),
+        );
     }
 
     let mut instructions = vec![];
@@ -1229,16 +2975,14 @@ fn command_gc(config: &Config) -> CommandResult {
 
     for (token, accounts) in accounts_by_token.into_iter() {
         println!("Processing token: {}", token);
-        let associated_token_account = get_associated_token_address(&config.owner, &token);
+        let associated_token_account = config.associated_token_address(&config.owner, &token);
         let total_balance: u64 = accounts.values().map(|account| account.0).sum();
 
         if total_balance > 0 && !accounts.contains_key(&associated_token_account) {
             // Create the associated token account
-            instructions.push(vec![create_associated_token_account(
-                &config.fee_payer,
-                &config.owner,
-                &token,
-            )]);
+            instructions.push(vec![
+                config.create_associated_token_account(&config.owner, &token)
+            ]);
             lamports_needed += minimum_balance_for_rent_exemption;
         }
 
@@ -1257,21 +3001,19 @@ fn command_gc(config: &Config) -> CommandResult {
 
             // Transfer the account balance into the associated token account
             if amount > 0 {
-                account_instructions.push(transfer_checked(
-                    &spl_token::id(),
+                account_instructions.push(transfer_checked_settling_positions(
+                    config,
                     &address,
                     &token,
                     &associated_token_account,
-                    &config.owner,
-                    &config.multisigner_pubkeys,
                     amount,
                     decimals,
                 )?);
             }
             // Close the account if config.owner is able to
             if close_authority == config.owner {
-                account_instructions.push(close_account(
-                    &spl_token::id(),
+                account_instructions.push(close_account_with_position(
+                    &config.program_id,
                     &address,
                     &config.owner,
                     &config.owner,
@@ -1288,6 +3030,82 @@ fn command_gc(config: &Config) -> CommandResult {
     Ok(Some((lamports_needed, instructions)))
 }
 
+fn command_decode_account(matches: &ArgMatches<'_>, program_id: &Pubkey) -> CommandResult {
+    let input = decode::read_input(matches.value_of("input").unwrap())?;
+    let account =
+        decode::parse_account_input(&input, matches.value_of(ENCODING_ARG.name).unwrap())?;
+    let account_type = match matches.value_of("type") {
+        Some(name) => AccountType::from_name(name).unwrap(),
+        None => {
+            if let Some(owner) = account.owner {
+                if owner != *program_id {
+                    return Err(format!(
+                        "The account is owned by {}, not by the token program {}. \
+                         Use `--type` to decode it anyway",
+                        owner, program_id
+                    )
+                    .into());
+                }
+            }
+            AccountType::detect(&account.data).ok_or_else(|| {
+                format!(
+                    "No token program account has this header or is {} bytes long. \
+                     Use `--type` to pick a layout",
+                    account.data.len()
+                )
+            })?
+        }
+    };
+    let mut value = decode::decode_account(account_type, &account.data)?;
+    if let Some(pubkey) = account.pubkey {
+        value["address"] = serde_json::json!(pubkey.to_string());
+    }
+
+    if let Some(output) = matches.value_of(OUTPUT_FORMAT_ARG.name) {
+        return print_json(&value, output);
+    }
+    println!();
+    if let Some(pubkey) = account.pubkey {
+        println_name_value("Address:", &pubkey.to_string());
+    }
+    println_name_value("Type:", account_type.name());
+    decode::println_decoded(&value["fields"], 0);
+    Ok(None)
+}
+
+fn command_decode_instruction(matches: &ArgMatches<'_>) -> CommandResult {
+    let input = decode::read_input(matches.value_of("input").unwrap())?;
+    let mut instruction =
+        decode::parse_instruction_input(&input, matches.value_of(ENCODING_ARG.name).unwrap())?;
+    if let Some(accounts) = pubkeys_of(matches, "account") {
+        instruction.accounts = accounts;
+    }
+    let value = decode::decode_instruction(&instruction.data, &instruction.accounts)?;
+
+    if let Some(output) = matches.value_of(OUTPUT_FORMAT_ARG.name) {
+        return print_json(&value, output);
+    }
+    println!();
+    println_name_value(
+        "Instruction:",
+        value["instruction"].as_str().unwrap_or_default(),
+    );
+    println!("Fields:");
+    decode::println_decoded(&value["fields"], 2);
+    if let Some(accounts) = value["accounts"].as_array() {
+        println!("Accounts:");
+        for account in accounts {
+            println!(
+                "  {:<3} {:<44}  {}",
+                account["index"],
+                account["pubkey"].as_str().unwrap_or_default(),
+                account["name"].as_str().unwrap_or_default()
+            );
+        }
+    }
+    Ok(None)
+}
+
 struct SignOnlyNeedsFullMintSpec {}
 impl offline::ArgsConfig for SignOnlyNeedsFullMintSpec {
     fn sign_only_arg<'a, 'b>(&self, arg: Arg<'a, 'b>) -> Arg<'a, 'b> {
@@ -1332,7 +3150,7 @@ fn main() {
                 .global(true)
                 .help("Configuration file to use");
             if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
-                arg.default_value(&config_file)
+                arg.default_value(config_file)
             } else {
                 arg
             }
@@ -1373,6 +3191,31 @@ fn main() {
                 ),
         )
         .arg(fee_payer_arg().global(true))
+        .arg(
+            Arg::with_name("simulate")
+                .long("simulate")
+                .takes_value(false)
+                .global(true)
+                .help(
+                    "Simulate the transactions instead of sending them, \
+                     printing their logs, events and compute units. \
+                     Each transaction is simulated against the current state, \
+                     without the effects of the ones before it.",
+                ),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program-id")
+                .value_name("ADDRESS")
+                .validator(is_valid_pubkey)
+                .takes_value(true)
+                .global(true)
+                .help(
+                    "Address of the token program to use. \
+                     Defaults to the token_program_id of the configuration file, \
+                     then to the SPL Token program.",
+                ),
+        )
         .subcommand(SubCommand::with_name("create-token").about("Create a new token")
                 .arg(
                     Arg::with_name("decimals")
@@ -1380,7 +3223,7 @@ fn main() {
                         .validator(is_mint_decimals)
                         .value_name("DECIMALS")
                         .takes_value(true)
-                        .default_value(&default_decimals)
+                        .default_value(default_decimals)
                         .help("Number of base 10 digits to the right of the decimal place"),
                 )
                 .arg(
@@ -1841,7 +3684,8 @@ fn main() {
                         .conflicts_with("token")
                         .help("Specify the token account to query \
                             [default: owner's associated token account]"),
-                ),
+                )
+                .arg(output_format_arg()),
         )
         .subcommand(
             SubCommand::with_name("supply")
@@ -1866,7 +3710,8 @@ fn main() {
                         .takes_value(true)
                         .index(1)
                         .help("Limit results to the given token. [Default: list accounts for all tokens]"),
-                ),
+                )
+                .arg(output_format_arg()),
         )
         .subcommand(
             SubCommand::with_name("address")
@@ -1901,7 +3746,8 @@ fn main() {
                         .long("address")
                         .conflicts_with("token")
                         .help("Specify the token account to query"),
-                ),
+                )
+                .arg(output_format_arg()),
         )
         .subcommand(
             SubCommand::with_name("multisig-info")
@@ -1916,10 +3762,439 @@ fn main() {
                     .help("The address of the SPL Token multisig account to query"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("registry")
+                .about("List the assets approved for hedged mints and portfolios")
+        )
+        .subcommand(
+            SubCommand::with_name("hedge")
+                .about("Deposit into and withdraw from hedged mints")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("deposit")
+                        .about("Deposit the asset of a hedged mint. \
+                            The hedge configuration and swap pool are read from the cluster, \
+                            also with --sign-only")
+                        .arg(
+                            Arg::with_name("token")
+                                .validator(is_valid_pubkey)
+                                .value_name("TOKEN_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The hedged mint to deposit into"),
+                        )
+                        .arg(
+                            Arg::with_name("amount")
+                                .validator(is_amount)
+                                .value_name("ASSET_AMOUNT")
+                                .takes_value(true)
+                                .index(2)
+                                .required(true)
+                                .help("Amount of asset to deposit"),
+                        )
+                        .arg(
+                            Arg::with_name("from")
+                                .long("from")
+                                .validator(is_valid_pubkey)
+                                .value_name("ASSET_ACCOUNT_ADDRESS")
+                                .takes_value(true)
+                                .help("Specify the asset account to deposit from \
+                                    [default: owner's associated asset account]"),
+                        )
+                        .arg(
+                            Arg::with_name("address")
+                                .long("address")
+                                .validator(is_valid_pubkey)
+                                .value_name("TOKEN_ACCOUNT_ADDRESS")
+                                .takes_value(true)
+                                .help("Specify the token account to deposit into \
+                                    [default: owner's associated token account]"),
+                        )
+                        .arg(
+                            Arg::with_name("minimum_amount_out")
+                                .long("minimum-amount-out")
+                                .validator(is_amount)
+                                .value_name("USDC_AMOUNT")
+                                .takes_value(true)
+                                .default_value("0")
+                                .help("Minimum amount of stablecoin the hedged share must swap into"),
+                        )
+                        .arg(
+                            Arg::with_name("host_fee_account")
+                                .long("host-fee-account")
+                                .validator(is_valid_pubkey)
+                                .value_name("POOL_TOKEN_ACCOUNT_ADDRESS")
+                                .takes_value(true)
+                                .help("Pool token account receiving the host fee of the swap"),
+                        )
+                        .nonce_args(true)
+                        .offline_args(),
+                )
+                .subcommand(
+                    SubCommand::with_name("withdraw")
+                        .about("Withdraw from a hedged mint. \
+                            The hedge configuration and swap pool are read from the cluster, \
+                            also with --sign-only")
+                        .arg(
+                            Arg::with_name("token")
+                                .validator(is_valid_pubkey)
+                                .value_name("TOKEN_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The hedged mint to withdraw from"),
+                        )
+                        .arg(
+                            Arg::with_name("amount")
+                                .validator(is_amount_or_all)
+                                .value_name("TOKEN_AMOUNT")
+                                .takes_value(true)
+                                .index(2)
+                                .required(true)
+                                .help("Amount to withdraw, in tokens; accepts keyword ALL"),
+                        )
+                        .arg(
+                            Arg::with_name("address")
+                                .long("address")
+                                .validator(is_valid_pubkey)
+                                .value_name("TOKEN_ACCOUNT_ADDRESS")
+                                .takes_value(true)
+                                .help("Specify the token account to withdraw from \
+                                    [default: owner's associated token account]"),
+                        )
+                        .arg(
+                            Arg::with_name("request")
+                                .long("request")
+                                .takes_value(false)
+                                .help("Request the withdrawal instead of executing it, \
+                                    for programs enforcing a withdrawal delay"),
+                        )
+                        .arg(multisig_signer_arg())
+                        .nonce_args(true)
+                        .offline_args(),
+                )
+                .subcommand(
+                    SubCommand::with_name("info")
+                        .about("Query the hedge configuration of a mint and the position of an account")
+                        .arg(
+                            Arg::with_name("token")
+                                .validator(is_valid_pubkey)
+                                .value_name("TOKEN_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The hedged mint to query"),
+                        )
+                        .arg(
+                            Arg::with_name("address")
+                                .long("address")
+                                .validator(is_valid_pubkey)
+                                .value_name("TOKEN_ACCOUNT_ADDRESS")
+                                .takes_value(true)
+                                .help("Specify the token account whose position to show \
+                                    [default: owner's associated token account]"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("portfolio")
+                .about("Create, join and inspect portfolios")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .about("Create a portfolio from a spec file")
+                        .arg(
+                            Arg::with_name("spec")
+                                .long("spec")
+                                .value_name("PATH")
+                                .takes_value(true)
+                                .required(true)
+                                .help(
+                                    "TOML file listing the metadata URL and between 1 and 9 assets, \
+                                     each with its mint, weight, period and the mint it is sold into. \
+                                     Weights must add up to 100."
+                                ),
+                        )
+                        .arg(
+                            Arg::with_name("portfolio_keypair")
+                                .value_name("KEYPAIR")
+                                .validator(is_valid_signer)
+                                .takes_value(true)
+                                .index(1)
+                                .help(
+                                    "Specify the portfolio keypair. \
+                                     This may be a keypair file or the ASK keyword. \
+                                     [default: randomly generated keypair]"
+                                ),
+                        )
+                        .nonce_args(true)
+                        .offline_args(),
+                )
+                .subcommand(
+                    SubCommand::with_name("join")
+                        .about("Create a user portfolio holding the assets of a portfolio")
+                        .arg(
+                            Arg::with_name("portfolio")
+                                .validator(is_valid_pubkey)
+                                .value_name("PORTFOLIO_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The portfolio to join"),
+                        )
+                        .arg(
+                            Arg::with_name("user_portfolio_keypair")
+                                .value_name("KEYPAIR")
+                                .validator(is_valid_signer)
+                                .takes_value(true)
+                                .index(2)
+                                .help(
+                                    "Specify the user portfolio keypair. \
+                                     This may be a keypair file or the ASK keyword. \
+                                     [default: randomly generated keypair]"
+                                ),
+                        )
+                        .arg(
+                            Arg::with_name("delegated_amount")
+                                .long("delegated-amount")
+                                .validator(is_parsable::<u64>)
+                                .value_name("AMOUNT")
+                                .takes_value(true)
+                                .default_value("0")
+                                .help("Amount delegated, recorded in the user portfolio"),
+                        )
+                        .arg(
+                            Arg::with_name("deposit")
+                                .long("deposit")
+                                .validator(is_amount)
+                                .value_name("AMOUNT")
+                                .takes_value(true)
+                                .help(
+                                    "Deposit this many portfolio units from the owner's \
+                                     associated token accounts when joining, counted \
+                                     against the deposit caps of the portfolio"
+                                ),
+                        )
+                        .nonce_args(true)
+                        .offline_args(),
+                )
+                .subcommand(
+                    SubCommand::with_name("deposit")
+                        .about("Deposit into a user portfolio from the owner's associated token accounts")
+                        .arg(
+                            Arg::with_name("address")
+                                .validator(is_valid_pubkey)
+                                .value_name("USER_PORTFOLIO_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The user portfolio to deposit into"),
+                        )
+                        .arg(
+                            Arg::with_name("amount")
+                                .validator(is_amount)
+                                .value_name("AMOUNT")
+                                .takes_value(true)
+                                .index(2)
+                                .required(true)
+                                .help("Amount to deposit, split across the assets by weight"),
+                        )
+                        .arg(multisig_signer_arg())
+                        .nonce_args(true)
+                        .offline_args(),
+                )
+                .subcommand(
+                    SubCommand::with_name("redeem")
+                        .about("Redeem from a user portfolio into the owner's associated token accounts")
+                        .arg(
+                            Arg::with_name("address")
+                                .validator(is_valid_pubkey)
+                                .value_name("USER_PORTFOLIO_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The user portfolio to redeem from"),
+                        )
+                        .arg(
+                            Arg::with_name("amount")
+                                .validator(is_amount_or_all)
+                                .value_name("AMOUNT")
+                                .takes_value(true)
+                                .index(2)
+                                .required(true)
+                                .help("Amount to redeem, split across the assets by weight; \
+                                    accepts keyword ALL"),
+                        )
+                        .arg(multisig_signer_arg())
+                        .nonce_args(true)
+                        .offline_args(),
+                )
+                .subcommand(
+                    SubCommand::with_name("delegate")
+                        .about("Approve a delegate for a user portfolio")
+                        .arg(
+                            Arg::with_name("address")
+                                .validator(is_valid_pubkey)
+                                .value_name("USER_PORTFOLIO_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The user portfolio"),
+                        )
+                        .arg(
+                            Arg::with_name("delegate")
+                                .validator(is_valid_pubkey)
+                                .value_name("DELEGATE_ADDRESS")
+                                .takes_value(true)
+                                .index(2)
+                                .required(true)
+                                .help("The delegate"),
+                        )
+                        .arg(
+                            Arg::with_name("amount")
+                                .validator(is_parsable::<u64>)
+                                .value_name("AMOUNT")
+                                .takes_value(true)
+                                .index(3)
+                                .required(true)
+                                .help("Amount the delegate is approved for"),
+                        )
+                        .arg(multisig_signer_arg())
+                        .nonce_args(true)
+                        .offline_args(),
+                )
+                .subcommand(
+                    SubCommand::with_name("revoke")
+                        .about("Revoke the delegate of a user portfolio")
+                        .arg(
+                            Arg::with_name("address")
+                                .validator(is_valid_pubkey)
+                                .value_name("USER_PORTFOLIO_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The user portfolio"),
+                        )
+                        .arg(multisig_signer_arg())
+                        .nonce_args(true)
+                        .offline_args(),
+                )
+                .subcommand(
+                    SubCommand::with_name("positions")
+                        .about("List the user portfolios of the owner")
+                )
+                .subcommand(
+                    SubCommand::with_name("quote")
+                        .about("Preview the swaps of a deposit into or a redemption from a portfolio")
+                        .arg(
+                            Arg::with_name("address")
+                                .validator(is_valid_pubkey)
+                                .value_name("PORTFOLIO_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The portfolio to quote, \
+                                    or with --redeem the user portfolio to redeem from"),
+                        )
+                        .arg(
+                            Arg::with_name("amount")
+                                .validator(is_amount)
+                                .value_name("AMOUNT")
+                                .takes_value(true)
+                                .index(2)
+                                .required(true)
+                                .help("Amount of the quote mint to deposit, \
+                                    or with --redeem the portfolio units to redeem"),
+                        )
+                        .arg(
+                            Arg::with_name("mint")
+                                .long("mint")
+                                .validator(is_valid_pubkey)
+                                .value_name("MINT_ADDRESS")
+                                .takes_value(true)
+                                .required(true)
+                                .help("The token paid in, or received with --redeem, \
+                                    swapped through the preferred pool of each asset"),
+                        )
+                        .arg(
+                            Arg::with_name("redeem")
+                                .long("redeem")
+                                .takes_value(false)
+                                .help("Quote a redemption instead of a deposit"),
+                        )
+                        .arg(output_format_arg()),
+                )
+                .subcommand(
+                    SubCommand::with_name("show")
+                        .about("Query details of a portfolio by address")
+                        .arg(
+                            Arg::with_name("address")
+                                .validator(is_valid_pubkey)
+                                .value_name("PORTFOLIO_ADDRESS")
+                                .takes_value(true)
+                                .index(1)
+                                .required(true)
+                                .help("The address of the portfolio to query"),
+                        )
+                        .arg(output_format_arg()),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("gc")
                 .about("Cleanup unnecessary token accounts")
         )
+        .subcommand(
+            SubCommand::with_name("decode-account")
+                .about("Decode the data of a token program account, offline")
+                .arg(
+                    Arg::with_name("input")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .index(1)
+                        .default_value("-")
+                        .help("File holding the output of `solana account --output json`, \
+                            the raw account data, or the data as text. \
+                            Reads standard input by default"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .long("type")
+                        .value_name("TYPE")
+                        .takes_value(true)
+                        .possible_values(AccountType::NAMES)
+                        .help("Decode the data as this account type instead of \
+                            detecting it from the owner, header and size of the account"),
+                )
+                .arg(decode_encoding_arg("base64"))
+                .arg(output_format_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("decode-instruction")
+                .about("Decode the data of a token program instruction, offline")
+                .arg(
+                    Arg::with_name("input")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .index(1)
+                        .default_value("-")
+                        .help("File holding the instruction data as text, or a JSON object \
+                            with its `data` and its `accounts` or `keys`. \
+                            Reads standard input by default"),
+                )
+                .arg(
+                    Arg::with_name("account")
+                        .long("account")
+                        .value_name("ADDRESS")
+                        .validator(is_pubkey)
+                        .takes_value(true)
+                        .multiple(true)
+                        .help("Account of the instruction, in order. \
+                            Overrides the accounts of a JSON input"),
+                )
+                .arg(decode_encoding_arg("base58"))
+                .arg(output_format_arg()),
+        )
         .get_matches();
 
     let mut wallet_manager = None;
@@ -1928,6 +4203,38 @@ fn main() {
 
     let (sub_command, sub_matches) = app_matches.subcommand();
     let matches = sub_matches.unwrap();
+    // Grouped commands such as `hedge deposit` take their arguments from the nested subcommand
+    let matches = match matches.subcommand() {
+        (_, Some(nested_matches)) => nested_matches,
+        _ => matches,
+    };
+
+    let program_id = pubkey_of_signer(matches, "program_id", &mut wallet_manager)
+        .and_then(|program_id| match program_id {
+            Some(program_id) => Ok(Some(program_id)),
+            None => matches
+                .value_of("config_file")
+                .map_or(Ok(None), program_id_from_config_file),
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        })
+        .unwrap_or_else(spl_token::id);
+
+    // Decoding needs neither a keypair nor a cluster
+    let decoded = match sub_command {
+        "decode-account" => Some(command_decode_account(matches, &program_id)),
+        "decode-instruction" => Some(command_decode_instruction(matches)),
+        _ => None,
+    };
+    if let Some(decoded) = decoded {
+        if let Err(err) = decoded {
+            eprintln!("{}", err);
+            exit(1);
+        }
+        return;
+    }
 
     let config = {
         let cli_config = if let Some(config_file) = matches.value_of("config_file") {
@@ -1946,17 +4253,14 @@ fn main() {
             .value_of(&default_signer_arg_name)
             .map(|s| s.to_string())
             .unwrap_or_else(|| cli_config.keypair_path.clone());
-        let default_signer = DefaultSigner {
-            path: default_signer_path,
-            arg_name: default_signer_arg_name,
-        };
+        let default_signer = DefaultSigner::new(default_signer_arg_name, default_signer_path);
 
         let (owner, signer) = {
             let config = SignerFromPathConfig {
                 allow_null_signer: true,
             };
             let owner = default_signer
-                .signer_from_path_with_config(&matches, &mut wallet_manager, &config)
+                .signer_from_path_with_config(matches, &mut wallet_manager, &config)
                 .unwrap_or_else(|e| {
                     eprintln!("error: {}", e);
                     exit(1);
@@ -1966,7 +4270,7 @@ fn main() {
         bulk_signers.push(signer);
 
         let (signer, fee_payer) = signer_from_path(
-            &matches,
+            matches,
             matches
                 .value_of("fee_payer")
                 .unwrap_or(&cli_config.keypair_path),
@@ -1985,13 +4289,13 @@ fn main() {
 
         let verbose = matches.is_present("verbose");
 
-        let nonce_account = pubkey_of_signer(&matches, NONCE_ARG.name, &mut wallet_manager)
+        let nonce_account = pubkey_of_signer(matches, NONCE_ARG.name, &mut wallet_manager)
             .unwrap_or_else(|e| {
                 eprintln!("error: {}", e);
                 exit(1);
             });
         let (signer, nonce_authority) = signer_from_path(
-            &matches,
+            matches,
             matches
                 .value_of(NONCE_AUTHORITY_ARG.name)
                 .unwrap_or(&cli_config.keypair_path),
@@ -2010,14 +4314,19 @@ fn main() {
 
         let blockhash_query = BlockhashQuery::new_from_matches(matches);
         let sign_only = matches.is_present(SIGN_ONLY_ARG.name);
+        let simulate = matches.is_present("simulate");
+        if sign_only && simulate {
+            eprintln!("error: --simulate cannot be used with --sign-only");
+            exit(1);
+        }
 
-        let multisig_signers = signers_of(&matches, MULTISIG_SIGNER_ARG.name, &mut wallet_manager)
+        let multisig_signers = signers_of(matches, MULTISIG_SIGNER_ARG.name, &mut wallet_manager)
             .unwrap_or_else(|e| {
                 eprintln!("error: {}", e);
                 exit(1);
             });
         if let Some(mut multisig_signers) = multisig_signers {
-            multisig_signers.sort_by(|(_, lp), (_, rp)| lp.cmp(rp));
+            multisig_signers.sort_by_key(|(_, lp)| *lp);
             let (signers, pubkeys): (Vec<_>, Vec<_>) = multisig_signers.into_iter().unzip();
             bulk_signers.extend(signers.into_iter().map(Some));
             multisigner_ids = pubkeys;
@@ -2035,9 +4344,18 @@ fn main() {
             blockhash_query,
             sign_only,
             multisigner_pubkeys,
+            program_id,
+            simulate,
         }
     };
 
+    if !config.sign_only {
+        if let Err(e) = check_program(&config) {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+    }
+
     if matches.is_present(MULTISIG_SIGNER_ARG.name)
         && !config.sign_only
         && get_multisig(&config, &config.owner).is_err()
@@ -2052,7 +4370,7 @@ fn main() {
         ("create-token", Some(arg_matches)) => {
             let decimals = value_t_or_exit!(arg_matches, "decimals", u8);
             let (signer, token) = if arg_matches.is_present("token_keypair") {
-                signer_of(&arg_matches, "token_keypair", &mut wallet_manager).unwrap_or_else(|e| {
+                signer_of(arg_matches, "token_keypair", &mut wallet_manager).unwrap_or_else(|e| {
                     eprintln!("error: {}", e);
                     exit(1);
                 })
@@ -2076,7 +4394,7 @@ fn main() {
 
             let account = if arg_matches.is_present("account_keypair") {
                 let (signer, account) =
-                    signer_of(&arg_matches, "account_keypair", &mut wallet_manager).unwrap_or_else(
+                    signer_of(arg_matches, "account_keypair", &mut wallet_manager).unwrap_or_else(
                         |e| {
                             eprintln!("error: {}", e);
                             exit(1);
@@ -2092,9 +4410,9 @@ fn main() {
             command_create_account(&config, token, account)
         }
         ("create-multisig", Some(arg_matches)) => {
-            let minimum_signers = value_of::<u8>(&arg_matches, "minimum_signers").unwrap();
+            let minimum_signers = value_of::<u8>(arg_matches, "minimum_signers").unwrap();
             let multisig_members =
-                pubkeys_of_multiple_signers(&arg_matches, "multisig_member", &mut wallet_manager)
+                pubkeys_of_multiple_signers(arg_matches, "multisig_member", &mut wallet_manager)
                     .unwrap_or_else(|e| {
                         eprintln!("error: {}", e);
                         exit(1);
@@ -2109,12 +4427,10 @@ fn main() {
             }
 
             let (signer, account) = if arg_matches.is_present("address_keypair") {
-                signer_of(&arg_matches, "address_keypair", &mut wallet_manager).unwrap_or_else(
-                    |e| {
-                        eprintln!("error: {}", e);
-                        exit(1);
-                    },
-                )
+                signer_of(arg_matches, "address_keypair", &mut wallet_manager).unwrap_or_else(|e| {
+                    eprintln!("error: {}", e);
+                    exit(1);
+                })
             } else {
                 new_throwaway_signer()
             };
@@ -2158,7 +4474,7 @@ fn main() {
                 .unwrap()
                 .unwrap();
             let sender = pubkey_of_signer(arg_matches, "from", &mut wallet_manager).unwrap();
-            let mint_decimals = value_of::<u8>(&arg_matches, MINT_DECIMALS_ARG.name);
+            let mint_decimals = value_of::<u8>(arg_matches, MINT_DECIMALS_ARG.name);
             let fund_recipient = matches.is_present("fund_recipient");
             let allow_unfunded_recipient = matches.is_present("allow_empty_recipient")
                 || matches.is_present("allow_unfunded_recipient");
@@ -2184,7 +4500,7 @@ fn main() {
             let amount = value_t_or_exit!(arg_matches, "amount", f64);
             let mint_address =
                 pubkey_of_signer(arg_matches, MINT_ADDRESS_ARG.name, &mut wallet_manager).unwrap();
-            let mint_decimals = value_of::<u8>(&arg_matches, MINT_DECIMALS_ARG.name);
+            let mint_decimals = value_of::<u8>(arg_matches, MINT_DECIMALS_ARG.name);
             command_burn(&config, source, amount, mint_address, mint_decimals)
         }
         ("mint", Some(arg_matches)) => {
@@ -2194,8 +4510,8 @@ fn main() {
             let amount = value_t_or_exit!(arg_matches, "amount", f64);
             let recipient = pubkey_of_signer(arg_matches, "recipient", &mut wallet_manager)
                 .unwrap()
-                .unwrap_or_else(|| get_associated_token_address(&config.owner, &token));
-            let mint_decimals = value_of::<u8>(&arg_matches, MINT_DECIMALS_ARG.name);
+                .unwrap_or_else(|| config.associated_token_address(&config.owner, &token));
+            let mint_decimals = value_of::<u8>(arg_matches, MINT_DECIMALS_ARG.name);
             command_mint(&config, token, amount, recipient, mint_decimals)
         }
         ("freeze", Some(arg_matches)) => {
@@ -2240,7 +4556,7 @@ fn main() {
                 .unwrap();
             let mint_address =
                 pubkey_of_signer(arg_matches, MINT_ADDRESS_ARG.name, &mut wallet_manager).unwrap();
-            let mint_decimals = value_of::<u8>(&arg_matches, MINT_DECIMALS_ARG.name);
+            let mint_decimals = value_of::<u8>(arg_matches, MINT_DECIMALS_ARG.name);
             command_approve(
                 &config,
                 account,
@@ -2270,7 +4586,12 @@ fn main() {
         ("balance", Some(arg_matches)) => {
             let token = pubkey_of_signer(arg_matches, "token", &mut wallet_manager).unwrap();
             let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager).unwrap();
-            command_balance(&config, token, address)
+            command_balance(
+                &config,
+                token,
+                address,
+                arg_matches.value_of(OUTPUT_FORMAT_ARG.name),
+            )
         }
         ("supply", Some(arg_matches)) => {
             let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager)
@@ -2280,7 +4601,7 @@ fn main() {
         }
         ("accounts", Some(arg_matches)) => {
             let token = pubkey_of_signer(arg_matches, "token", &mut wallet_manager).unwrap();
-            command_accounts(&config, token)
+            command_accounts(&config, token, arg_matches.value_of(OUTPUT_FORMAT_ARG.name))
         }
         ("address", Some(arg_matches)) => {
             let token = pubkey_of_signer(arg_matches, "token", &mut wallet_manager).unwrap();
@@ -2289,7 +4610,12 @@ fn main() {
         ("account-info", Some(arg_matches)) => {
             let token = pubkey_of_signer(arg_matches, "token", &mut wallet_manager).unwrap();
             let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager).unwrap();
-            command_account_info(&config, token, address)
+            command_account_info(
+                &config,
+                token,
+                address,
+                arg_matches.value_of(OUTPUT_FORMAT_ARG.name),
+            )
         }
         ("multisig-info", Some(arg_matches)) => {
             let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager)
@@ -2297,7 +4623,159 @@ fn main() {
                 .unwrap();
             command_multisig(&config, address)
         }
+        ("registry", Some(_arg_matches)) => command_registry(&config),
         ("gc", Some(_arg_matches)) => command_gc(&config),
+        ("hedge", Some(hedge_matches)) => match hedge_matches.subcommand() {
+            ("deposit", Some(arg_matches)) => {
+                let token = pubkey_of_signer(arg_matches, "token", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let amount = value_t_or_exit!(arg_matches, "amount", f64);
+                let source = pubkey_of_signer(arg_matches, "from", &mut wallet_manager).unwrap();
+                let account =
+                    pubkey_of_signer(arg_matches, "address", &mut wallet_manager).unwrap();
+                let minimum_amount_out = value_t_or_exit!(arg_matches, "minimum_amount_out", f64);
+                let host_fee_account =
+                    pubkey_of_signer(arg_matches, "host_fee_account", &mut wallet_manager).unwrap();
+                command_hedge_deposit(
+                    &config,
+                    token,
+                    amount,
+                    source,
+                    account,
+                    minimum_amount_out,
+                    host_fee_account,
+                )
+            }
+            ("withdraw", Some(arg_matches)) => {
+                let token = pubkey_of_signer(arg_matches, "token", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let amount = match arg_matches.value_of("amount").unwrap() {
+                    "ALL" => None,
+                    amount => Some(amount.parse::<f64>().unwrap()),
+                };
+                let account =
+                    pubkey_of_signer(arg_matches, "address", &mut wallet_manager).unwrap();
+                let request = arg_matches.is_present("request");
+                command_hedge_withdraw(&config, token, amount, account, request)
+            }
+            ("info", Some(arg_matches)) => {
+                let token = pubkey_of_signer(arg_matches, "token", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let address =
+                    pubkey_of_signer(arg_matches, "address", &mut wallet_manager).unwrap();
+                command_hedge_info(&config, token, address)
+            }
+            _ => unreachable!(),
+        },
+        ("portfolio", Some(portfolio_matches)) => match portfolio_matches.subcommand() {
+            ("create", Some(arg_matches)) => {
+                let (signer, portfolio) = if arg_matches.is_present("portfolio_keypair") {
+                    signer_of(arg_matches, "portfolio_keypair", &mut wallet_manager).unwrap_or_else(
+                        |e| {
+                            eprintln!("error: {}", e);
+                            exit(1);
+                        },
+                    )
+                } else {
+                    new_throwaway_signer()
+                };
+                let portfolio = portfolio.unwrap();
+                bulk_signers.push(signer);
+
+                command_portfolio_create(&config, arg_matches.value_of("spec").unwrap(), portfolio)
+            }
+            ("show", Some(arg_matches)) => {
+                let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                command_portfolio_show(
+                    &config,
+                    address,
+                    arg_matches.value_of(OUTPUT_FORMAT_ARG.name),
+                )
+            }
+            ("quote", Some(arg_matches)) => {
+                let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let mint = pubkey_of_signer(arg_matches, "mint", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let amount = value_t_or_exit!(arg_matches, "amount", f64);
+                command_portfolio_quote(
+                    &config,
+                    address,
+                    mint,
+                    amount,
+                    arg_matches.is_present("redeem"),
+                    arg_matches.value_of(OUTPUT_FORMAT_ARG.name),
+                )
+            }
+            ("join", Some(arg_matches)) => {
+                let portfolio = pubkey_of_signer(arg_matches, "portfolio", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let (signer, user_portfolio) = if arg_matches.is_present("user_portfolio_keypair") {
+                    signer_of(arg_matches, "user_portfolio_keypair", &mut wallet_manager)
+                        .unwrap_or_else(|e| {
+                            eprintln!("error: {}", e);
+                            exit(1);
+                        })
+                } else {
+                    new_throwaway_signer()
+                };
+                let user_portfolio = user_portfolio.unwrap();
+                bulk_signers.push(signer);
+                let delegated_amount = value_t_or_exit!(arg_matches, "delegated_amount", u64);
+                let deposit = value_of::<f64>(arg_matches, "deposit");
+
+                command_portfolio_join(
+                    &config,
+                    portfolio,
+                    user_portfolio,
+                    delegated_amount,
+                    deposit,
+                )
+            }
+            ("deposit", Some(arg_matches)) => {
+                let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let amount = value_t_or_exit!(arg_matches, "amount", f64);
+                command_portfolio_deposit(&config, address, amount)
+            }
+            ("redeem", Some(arg_matches)) => {
+                let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let amount = match arg_matches.value_of("amount").unwrap() {
+                    "ALL" => None,
+                    amount => Some(amount.parse::<f64>().unwrap()),
+                };
+                command_portfolio_redeem(&config, address, amount)
+            }
+            ("delegate", Some(arg_matches)) => {
+                let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let delegate = pubkey_of_signer(arg_matches, "delegate", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                let amount = value_t_or_exit!(arg_matches, "amount", u64);
+                command_portfolio_delegate(&config, address, delegate, amount)
+            }
+            ("revoke", Some(arg_matches)) => {
+                let address = pubkey_of_signer(arg_matches, "address", &mut wallet_manager)
+                    .unwrap()
+                    .unwrap();
+                command_portfolio_revoke(&config, address)
+            }
+            ("positions", Some(_arg_matches)) => command_portfolio_positions(&config),
+            _ => unreachable!(),
+        },
         _ => unreachable!(),
     }
     .and_then(|transaction_info| {
@@ -2305,14 +4783,16 @@ fn main() {
             let fee_payer = Some(&config.fee_payer);
             let signer_info = config
                 .default_signer
-                .generate_unique_signers(bulk_signers, &matches, &mut wallet_manager)
+                .generate_unique_signers(bulk_signers, matches, &mut wallet_manager)
                 .unwrap_or_else(|e| {
                     eprintln!("error: {}", e);
                     exit(1);
                 });
 
-            for instructions in instruction_batches {
-                let message = if let Some(nonce_account) = config.nonce_account.as_ref() {
+            let transaction_count = instruction_batches.len();
+            let mut simulation_failed = false;
+            for (transaction_index, instructions) in instruction_batches.into_iter().enumerate() {
+                let mut message = if let Some(nonce_account) = config.nonce_account.as_ref() {
                     Message::new_with_nonce(
                         instructions,
                         fee_payer,
@@ -2322,22 +4802,18 @@ fn main() {
                 } else {
                     Message::new(&instructions, fee_payer)
                 };
-                let (recent_blockhash, fee_calculator) = config
+                let recent_blockhash = config
                     .blockhash_query
-                    .get_blockhash_and_fee_calculator(
-                        &config.rpc_client,
-                        config.rpc_client.commitment(),
-                    )
+                    .get_blockhash(&config.rpc_client, config.rpc_client.commitment())
                     .unwrap_or_else(|e| {
                         eprintln!("error: {}", e);
                         exit(1);
                     });
+                message.recent_blockhash = recent_blockhash;
 
                 if !config.sign_only {
-                    check_fee_payer_balance(
-                        &config,
-                        minimum_balance_for_rent_exemption + fee_calculator.calculate_fee(&message),
-                    )?;
+                    let fee = config.rpc_client.get_fee_for_message(&message)?;
+                    check_fee_payer_balance(&config, minimum_balance_for_rent_exemption + fee)?;
                 }
 
                 let signers = signer_info.signers_for_message(&message);
@@ -2346,6 +4822,20 @@ fn main() {
                 if config.sign_only {
                     transaction.try_partial_sign(&signers, recent_blockhash)?;
                     println!("{}", return_signers(&transaction, &OutputFormat::Display)?);
+                } else if config.simulate {
+                    transaction.try_sign(&signers, recent_blockhash)?;
+                    let result = config.rpc_client.simulate_transaction(&transaction)?.value;
+                    println!();
+                    println!(
+                        "Transaction {} of {}",
+                        transaction_index + 1,
+                        transaction_count
+                    );
+                    simulation_failed |= !simulate::print_simulation(
+                        &config.program_id,
+                        &transaction.message,
+                        &result,
+                    );
                 } else {
                     transaction.try_sign(&signers, recent_blockhash)?;
                     let signature = if no_wait {
@@ -2358,6 +4848,9 @@ fn main() {
                     println!("Signature: {}", signature);
                 }
             }
+            if simulation_failed {
+                return Err("Simulation failed".into());
+            }
         }
         Ok(())
     })
@@ -0,0 +1,279 @@
+use serde::Deserialize;
+use solana_sdk::{hash::hash, pubkey::Pubkey};
+use spl_token::instruction::METADATA_URL_LEN;
+use std::{fs, str::FromStr};
+
+/// Number of asset slots of a portfolio.
+pub const PORTFOLIO_ASSETS: usize = 9;
+
+/// Portfolio description read from a `portfolio create --spec` file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PortfolioSpec {
+    pub metadata_url: String,
+    pub assets: Vec<AssetSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AssetSpec {
+    pub mint: String,
+    pub weight: u8,
+    pub period: u8,
+    pub sell_into: String,
+}
+
+/// What a user portfolio holds of one asset of its portfolio.
+#[derive(Clone, Copy, Debug)]
+pub struct Holding {
+    pub weight: u8,
+    pub balance: u64,
+    pub decimals: u8,
+}
+
+/// A slot of `InitializePortfolio`, with its addresses parsed.
+#[derive(Clone, Copy, Debug)]
+pub struct PortfolioAsset {
+    pub mint: Pubkey,
+    pub weight: u8,
+    pub period: u8,
+    pub sell_into: Pubkey,
+}
+
+impl PortfolioSpec {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        toml::from_str(&contents).map_err(|e| format!("Invalid portfolio spec {}: {}", path, e))
+    }
+
+    /// Checks the spec and returns the assets filling every slot of the
+    /// portfolio. Unused slots repeat the first asset with a zero weight, as the
+    /// program expects a registered mint in each of them.
+    pub fn validate(&self) -> Result<[PortfolioAsset; PORTFOLIO_ASSETS], String> {
+        if self.metadata_url.is_empty() {
+            return Err("metadata_url must not be empty".to_string());
+        }
+        if self.metadata_url.len() > METADATA_URL_LEN {
+            return Err(format!(
+                "metadata_url is {} bytes long, at most {} are stored",
+                self.metadata_url.len(),
+                METADATA_URL_LEN
+            ));
+        }
+        if self.assets.is_empty() || self.assets.len() > PORTFOLIO_ASSETS {
+            return Err(format!(
+                "A portfolio holds between 1 and {} assets, found {}",
+                PORTFOLIO_ASSETS,
+                self.assets.len()
+            ));
+        }
+
+        let mut assets = Vec::with_capacity(PORTFOLIO_ASSETS);
+        let mut total_weight = 0u32;
+        for (i, asset) in self.assets.iter().enumerate() {
+            let parse = |address: &str| {
+                Pubkey::from_str(address)
+                    .map_err(|_| format!("Asset {}: invalid address {}", i + 1, address))
+            };
+            let mint = parse(&asset.mint)?;
+            let sell_into = parse(&asset.sell_into)?;
+            if asset.weight == 0 {
+                return Err(format!("Asset {}: weight must be positive", i + 1));
+            }
+            if asset.period == 0 {
+                return Err(format!("Asset {}: period must be positive", i + 1));
+            }
+            if assets
+                .iter()
+                .any(|other: &PortfolioAsset| other.mint == mint)
+            {
+                return Err(format!("Asset {}: {} is listed twice", i + 1, mint));
+            }
+            total_weight += u32::from(asset.weight);
+            assets.push(PortfolioAsset {
+                mint,
+                weight: asset.weight,
+                period: asset.period,
+                sell_into,
+            });
+        }
+        if total_weight != 100 {
+            return Err(format!(
+                "Asset weights must add up to 100, found {}",
+                total_weight
+            ));
+        }
+
+        let padding = PortfolioAsset {
+            weight: 0,
+            period: 0,
+            ..assets[0]
+        };
+        assets.resize(PORTFOLIO_ASSETS, padding);
+        let mut slots = [padding; PORTFOLIO_ASSETS];
+        slots.copy_from_slice(&assets);
+        Ok(slots)
+    }
+
+    /// The metadata hash stored with the portfolio: the first two bytes of
+    /// the SHA-256 of the metadata URL, little endian.
+    pub fn metadata_hash(&self) -> u16 {
+        let digest = hash(self.metadata_url.as_bytes());
+        u16::from_le_bytes([digest.as_ref()[0], digest.as_ref()[1]])
+    }
+}
+
+/// Portfolio units backed by `holdings`. A unit deposits `weight / 100` of
+/// each asset, so the units are those of the scarcest holding.
+pub fn units_held(holdings: &[Holding]) -> f64 {
+    holdings
+        .iter()
+        .map(|holding| {
+            spl_token::amount_to_ui_amount(holding.balance, holding.decimals) * 100.0
+                / f64::from(holding.weight)
+        })
+        .fold(None, |units: Option<f64>, held| {
+            Some(units.map_or(held, |units| units.min(held)))
+        })
+        .unwrap_or(0.0)
+}
+
+/// Amounts of each of `holdings` paid out by the redemption of `ui_units`
+/// portfolio units: the same share of every holding.
+pub fn redeem_amounts(holdings: &[Holding], ui_units: f64) -> Result<Vec<u64>, String> {
+    let units = units_held(holdings);
+    if ui_units > units {
+        return Err(format!(
+            "The user portfolio holds {} portfolio units, {} requested",
+            units, ui_units
+        ));
+    }
+    Ok(holdings
+        .iter()
+        .map(|holding| ((holding.balance as f64 * ui_units / units) as u64).min(holding.balance))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(weights: &[u8]) -> PortfolioSpec {
+        PortfolioSpec {
+            metadata_url: "https://example.com/portfolio.json".to_string(),
+            assets: weights
+                .iter()
+                .map(|&weight| AssetSpec {
+                    mint: Pubkey::new_unique().to_string(),
+                    weight,
+                    period: 7,
+                    sell_into: Pubkey::new_unique().to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let two_assets = spec(&[60, 40]);
+        let slots = two_assets.validate().unwrap();
+        assert_eq!(slots[0].mint.to_string(), two_assets.assets[0].mint);
+        assert_eq!(
+            slots[0].sell_into.to_string(),
+            two_assets.assets[0].sell_into
+        );
+        assert_eq!((slots[0].weight, slots[0].period), (60, 7));
+        assert_eq!(slots[1].mint.to_string(), two_assets.assets[1].mint);
+        assert_eq!((slots[1].weight, slots[1].period), (40, 7));
+
+        // unused slots repeat the first asset with nothing allocated to it
+        for slot in &slots[2..] {
+            assert_eq!(slot.mint, slots[0].mint);
+            assert_eq!(slot.sell_into, slots[0].sell_into);
+            assert_eq!((slot.weight, slot.period), (0, 0));
+        }
+
+        // a full portfolio needs no padding
+        let slots = spec(&[10, 10, 10, 10, 10, 10, 10, 10, 20])
+            .validate()
+            .unwrap();
+        assert!(slots.iter().all(|slot| slot.weight != 0));
+    }
+
+    #[test]
+    fn test_validate_errors() {
+        let error = spec(&[60, 30]).validate().unwrap_err();
+        assert_eq!(error, "Asset weights must add up to 100, found 90");
+        assert!(spec(&[60, 50]).validate().is_err());
+        assert!(spec(&[100, 0]).validate().is_err());
+        assert!(spec(&[]).validate().is_err());
+        assert!(spec(&[10; PORTFOLIO_ASSETS + 1]).validate().is_err());
+
+        let mut duplicate = spec(&[50, 50]);
+        duplicate.assets[1].mint = duplicate.assets[0].mint.clone();
+        assert!(duplicate.validate().is_err());
+
+        let mut zero_period = spec(&[100]);
+        zero_period.assets[0].period = 0;
+        assert!(zero_period.validate().is_err());
+
+        let mut bad_address = spec(&[100]);
+        bad_address.assets[0].sell_into = "not an address".to_string();
+        assert!(bad_address.validate().is_err());
+
+        let mut no_url = spec(&[100]);
+        no_url.metadata_url.clear();
+        assert!(no_url.validate().is_err());
+
+        let mut long_url = spec(&[100]);
+        long_url.metadata_url = "a".repeat(METADATA_URL_LEN + 1);
+        assert!(long_url.validate().is_err());
+    }
+
+    #[test]
+    fn test_metadata_hash() {
+        let one_asset = spec(&[100]);
+        let digest = hash(one_asset.metadata_url.as_bytes());
+        let digest = digest.as_ref();
+        assert_eq!(
+            one_asset.metadata_hash(),
+            u16::from(digest[0]) | u16::from(digest[1]) << 8
+        );
+        // SHA-256("abc") starts with ba 78
+        let abc = PortfolioSpec {
+            metadata_url: "abc".to_string(),
+            assets: vec![],
+        };
+        assert_eq!(abc.metadata_hash(), 0x78ba);
+    }
+
+    #[test]
+    fn test_redeem_amounts() {
+        // a unit deposits 0.6 of the first asset and 0.4 of the second, which
+        // has since grown by half
+        let holdings = [
+            Holding {
+                weight: 60,
+                balance: 6_000_000,
+                decimals: 6,
+            },
+            Holding {
+                weight: 40,
+                balance: 600_000_000,
+                decimals: 8,
+            },
+        ];
+        assert_eq!(units_held(&holdings), 10.0);
+        assert_eq!(
+            redeem_amounts(&holdings, 2.5).unwrap(),
+            vec![1_500_000, 150_000_000]
+        );
+        assert_eq!(
+            redeem_amounts(&holdings, 10.0).unwrap(),
+            vec![6_000_000, 600_000_000]
+        );
+        assert!(redeem_amounts(&holdings, 10.5).is_err());
+        assert_eq!(units_held(&[]), 0.0);
+    }
+}
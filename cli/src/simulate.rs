@@ -0,0 +1,113 @@
+use num_traits::FromPrimitive;
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::{
+    instruction::InstructionError, message::Message, pubkey::Pubkey, transaction::TransactionError,
+};
+use spl_token::{error::TokenError, events::parse_logs};
+
+/// Compute units consumed by each instruction of a transaction, in order,
+/// read from the `Program <id> consumed <n> of <m> compute units` lines the
+/// runtime logs when a top-level instruction completes.
+pub fn compute_units_by_instruction<S: AsRef<str>>(logs: &[S]) -> Vec<(String, u64)> {
+    let mut depth = 0;
+    let mut consumed = vec![];
+    for log in logs {
+        let mut words = match log.as_ref().strip_prefix("Program ") {
+            Some(rest) => rest.split(' '),
+            None => continue,
+        };
+        let (program, action) = match (words.next(), words.next()) {
+            (Some(program), Some(action)) => (program, action),
+            _ => continue,
+        };
+        match action {
+            "invoke" => depth += 1,
+            "success" | "failed:" => depth -= 1,
+            "consumed" if depth == 1 => {
+                if let Some(units) = words.next().and_then(|units| units.parse().ok()) {
+                    consumed.push((program.to_string(), units));
+                }
+            }
+            _ => {}
+        }
+    }
+    consumed
+}
+
+/// Describe a failed transaction, decoding custom errors of the token program
+/// into their `TokenError` message.
+pub fn describe_error(program_id: &Pubkey, message: &Message, err: &TransactionError) -> String {
+    if let TransactionError::InstructionError(index, InstructionError::Custom(code)) = err {
+        let failed_program = message
+            .instructions
+            .get(*index as usize)
+            .map(|instruction| instruction.program_id(&message.account_keys));
+        if failed_program == Some(program_id) {
+            if let Some(token_error) = TokenError::from_u32(*code) {
+                return format!(
+                    "Instruction {} failed: {} (TokenError::{:?}, custom program error {:#x})",
+                    index, token_error, token_error, code
+                );
+            }
+        }
+    }
+    err.to_string()
+}
+
+/// Print the outcome of simulating `message`: its program logs, the events
+/// logged by the token program, the compute units consumed per instruction and
+/// the error, if any. Returns whether the simulation succeeded.
+pub fn print_simulation(
+    program_id: &Pubkey,
+    message: &Message,
+    result: &RpcSimulateTransactionResult,
+) -> bool {
+    let logs = result.logs.clone().unwrap_or_default();
+
+    println!("Logs:");
+    let mut depth = 0;
+    for log in &logs {
+        if log.ends_with("success") || log.contains(" failed: ") {
+            depth -= 1;
+        }
+        println!(
+            "  {:indent$}{}",
+            "",
+            log,
+            indent = depth.max(0) as usize * 2
+        );
+        if log.starts_with("Program ") && log.contains(" invoke [") {
+            depth += 1;
+        }
+    }
+
+    match parse_logs(program_id, &logs) {
+        Ok(events) if events.is_empty() => {}
+        Ok(events) => {
+            println!("Events:");
+            for event in events {
+                println!("  {:?}", event);
+            }
+        }
+        Err(err) => println!("Events: could not be decoded: {}", err),
+    }
+
+    println!("Compute units:");
+    for (index, (program, units)) in compute_units_by_instruction(&logs).iter().enumerate() {
+        println!("  Instruction {}: {} ({})", index, units, program);
+    }
+
+    match &result.err {
+        Some(err) => {
+            println!(
+                "Simulation failed: {}",
+                describe_error(program_id, message, err)
+            );
+            false
+        }
+        None => {
+            println!("Simulation succeeded");
+            true
+        }
+    }
+}
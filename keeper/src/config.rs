@@ -0,0 +1,63 @@
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::{fs, path::Path, str::FromStr, time::Duration};
+
+/// Default seconds between two scans of the program accounts.
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 10;
+
+/// Default address of the metrics endpoint.
+pub const DEFAULT_METRICS_ADDRESS: &str = "127.0.0.1:9184";
+
+/// Default number of times a crank transaction is sent before giving up.
+pub const DEFAULT_MAX_RETRIES: usize = 3;
+
+/// Default slippage tolerated on the pool quote of a DCA tranche, in basis
+/// points.
+pub const DEFAULT_DCA_SLIPPAGE_BPS: u16 = 100;
+
+/// Keeper settings read from a `--config` file. Every entry is optional and
+/// the command line takes precedence over the file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeeperConfigFile {
+    pub json_rpc_url: Option<String>,
+    pub keypair_path: Option<String>,
+    pub program_id: Option<String>,
+    pub poll_interval_secs: Option<u64>,
+    pub metrics_address: Option<String>,
+    pub max_retries: Option<usize>,
+    pub dca_slippage_bps: Option<u16>,
+}
+
+impl KeeperConfigFile {
+    pub fn load(path: &str) -> Result<Self, String> {
+        if !Path::new(path).exists() {
+            return Err(format!("Config file {} does not exist", path));
+        }
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        serde_yaml::from_str(&contents).map_err(|e| format!("Invalid config {}: {}", path, e))
+    }
+
+    pub fn program_id(&self) -> Result<Option<Pubkey>, String> {
+        self.program_id
+            .as_ref()
+            .map(|program_id| {
+                Pubkey::from_str(program_id)
+                    .map_err(|_| format!("Invalid program_id {}", program_id))
+            })
+            .transpose()
+    }
+}
+
+/// Resolved keeper settings.
+#[derive(Debug)]
+pub struct KeeperConfig {
+    pub json_rpc_url: String,
+    pub keypair_path: String,
+    pub program_id: Pubkey,
+    pub poll_interval: Duration,
+    pub metrics_address: String,
+    pub max_retries: usize,
+    pub dca_slippage_bps: u16,
+}
@@ -0,0 +1,431 @@
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::RpcFilterType,
+};
+use solana_sdk::{
+    account::Account as RpcAccount,
+    instruction::Instruction,
+    program_pack::{IsInitialized, Pack},
+    pubkey::Pubkey,
+};
+use spl_token::{
+    instruction::{
+        execute_dca, find_hedge_config_with_program_id, harvest_profit, trigger_conditions,
+    },
+    oracle::unpack_price,
+    state::{DcaConfig, DcaPosition, HedgeConfig, StopLoss, TakeProfit},
+    swap::{unpack_token_account, SwapState},
+};
+use std::{collections::HashMap, fmt};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Targets left out of a scan, with the reason.
+pub type Skipped = Vec<(Pubkey, String)>;
+
+/// The kinds of crank, in the order they are sent: protecting holders from a
+/// falling price comes before realizing profits, which comes before buying
+/// the next portfolio tranche.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CrankKind {
+    StopLoss,
+    TakeProfit,
+    Dca,
+}
+
+impl fmt::Display for CrankKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrankKind::StopLoss => write!(f, "stop-loss"),
+            CrankKind::TakeProfit => write!(f, "take-profit"),
+            CrankKind::Dca => write!(f, "dca"),
+        }
+    }
+}
+
+/// A crank instruction whose condition is met.
+#[derive(Debug)]
+pub struct Crank {
+    pub kind: CrankKind,
+    /// The rule or position the crank executes.
+    pub target: Pubkey,
+    /// How far past its condition the target is: the price distance for
+    /// stop-loss and take-profit rules, the slots overdue for DCA positions.
+    /// Within a kind, the largest goes first.
+    pub urgency: u64,
+    pub instruction: Instruction,
+}
+
+/// Scans the program accounts for rules and positions that can be cranked.
+pub struct Scanner<'a> {
+    rpc_client: &'a RpcClient,
+    program_id: Pubkey,
+    dca_slippage_bps: u16,
+    slot: u64,
+    accounts: HashMap<Pubkey, Option<RpcAccount>>,
+}
+
+impl<'a> Scanner<'a> {
+    pub fn new(rpc_client: &'a RpcClient, program_id: Pubkey, dca_slippage_bps: u16) -> Self {
+        Self {
+            rpc_client,
+            program_id,
+            dca_slippage_bps,
+            slot: 0,
+            accounts: HashMap::new(),
+        }
+    }
+
+    pub fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    /// Returns every crank that is due, highest priority first. Targets whose
+    /// accounts cannot be read are reported in the second list and skipped.
+    pub fn scan(&mut self) -> Result<(Vec<Crank>, Skipped), Error> {
+        self.slot = self.rpc_client.get_slot()?;
+        self.accounts.clear();
+
+        let mut cranks = vec![];
+        let mut skipped = vec![];
+        let mut collect = |target, result: Result<Option<Crank>, Error>| match result {
+            Ok(Some(crank)) => cranks.push(crank),
+            Ok(None) => {}
+            Err(err) => skipped.push((target, err.to_string())),
+        };
+
+        for (address, stop_loss) in self.program_accounts::<StopLoss>()? {
+            collect(address, self.stop_loss_crank(&address, &stop_loss));
+        }
+        for (address, take_profit) in self.program_accounts::<TakeProfit>()? {
+            collect(address, self.take_profit_crank(&address, &take_profit));
+        }
+        for (address, dca_position) in self.program_accounts::<DcaPosition>()? {
+            collect(address, self.dca_crank(&address, &dca_position));
+        }
+
+        sort_cranks(&mut cranks);
+        Ok((cranks, skipped))
+    }
+
+    fn program_accounts<T: Pack + IsInitialized>(&self) -> Result<Vec<(Pubkey, T)>, Error> {
+        let accounts = self
+            .rpc_client
+            .get_program_accounts_with_config(&self.program_id, program_accounts_config::<T>())?;
+        Ok(unpack_program_accounts(accounts))
+    }
+
+    /// Fetch an account, once per scan.
+    fn account(&mut self, address: &Pubkey) -> Result<RpcAccount, Error> {
+        if !self.accounts.contains_key(address) {
+            let account = self
+                .rpc_client
+                .get_multiple_accounts(&[*address])?
+                .pop()
+                .flatten();
+            self.accounts.insert(*address, account);
+        }
+        self.accounts[address]
+            .clone()
+            .ok_or_else(|| format!("Account {} not found", address).into())
+    }
+
+    fn hedge_config(&mut self, mint: &Pubkey) -> Result<HedgeConfig, Error> {
+        let (address, _) = find_hedge_config_with_program_id(&self.program_id, mint);
+        Ok(HedgeConfig::unpack(&self.account(&address)?.data)?)
+    }
+
+    fn swap(&mut self, address: &Pubkey) -> Result<SwapState, Error> {
+        Ok(SwapState::unpack(&self.account(address)?.data)?)
+    }
+
+    fn stop_loss_crank(
+        &mut self,
+        address: &Pubkey,
+        stop_loss: &StopLoss,
+    ) -> Result<Option<Crank>, Error> {
+        if stop_loss.triggered {
+            return Ok(None);
+        }
+        let price = unpack_price(&self.account(&stop_loss.oracle)?.data, self.slot)?;
+        // The program raises the high price before checking the trailing stop.
+        let stop = StopLoss {
+            high_price: stop_loss.high_price.max(price.price),
+            ..*stop_loss
+        }
+        .effective_stop();
+        if price.price > stop {
+            return Ok(None);
+        }
+
+        let hedge_config = self.hedge_config(&stop_loss.mint)?;
+        let swap = self.swap(&hedge_config.swap_pool)?;
+        let (source, destination) =
+            swap_reserves(&swap, &hedge_config.asset_mint, &hedge_config.stable_mint)?;
+        // The program holds the swap to the oracle price less the slippage of
+        // the rule, so no minimum is added here.
+        let instruction = trigger_conditions(
+            &self.program_id,
+            &stop_loss.mint,
+            &stop_loss.oracle,
+            &hedge_config.asset_vault,
+            &hedge_config.stable_vault,
+            &hedge_config.swap_program_id,
+            &hedge_config.swap_pool,
+            &swap.authority(&hedge_config.swap_program_id, &hedge_config.swap_pool)?,
+            &source,
+            &destination,
+            &swap.pool_mint,
+            &swap.pool_fee_account,
+            &swap.token_program_id,
+            None,
+            0,
+        )?;
+        Ok(Some(Crank {
+            kind: CrankKind::StopLoss,
+            target: *address,
+            urgency: stop - price.price,
+            instruction,
+        }))
+    }
+
+    fn take_profit_crank(
+        &mut self,
+        address: &Pubkey,
+        take_profit: &TakeProfit,
+    ) -> Result<Option<Crank>, Error> {
+        if take_profit.sell_bps == 0 {
+            return Ok(None);
+        }
+        let price = unpack_price(&self.account(&take_profit.oracle)?.data, self.slot)?;
+        let target = take_profit.target_price();
+        if price.expo != take_profit.entry_expo || price.price < target {
+            return Ok(None);
+        }
+
+        let hedge_config = self.hedge_config(&take_profit.mint)?;
+        let swap = self.swap(&hedge_config.swap_pool)?;
+        let (source, destination) =
+            swap_reserves(&swap, &hedge_config.asset_mint, &hedge_config.stable_mint)?;
+        let instruction = harvest_profit(
+            &self.program_id,
+            &take_profit.mint,
+            &take_profit.oracle,
+            &hedge_config.asset_vault,
+            &hedge_config.stable_vault,
+            &hedge_config.swap_program_id,
+            &hedge_config.swap_pool,
+            &swap.authority(&hedge_config.swap_program_id, &hedge_config.swap_pool)?,
+            &source,
+            &destination,
+            &swap.pool_mint,
+            &swap.pool_fee_account,
+            &swap.token_program_id,
+            None,
+            0,
+        )?;
+        Ok(Some(Crank {
+            kind: CrankKind::TakeProfit,
+            target: *address,
+            urgency: price.price - target,
+            instruction,
+        }))
+    }
+
+    fn dca_crank(
+        &mut self,
+        address: &Pubkey,
+        dca_position: &DcaPosition,
+    ) -> Result<Option<Crank>, Error> {
+        if dca_position.usdc == 0 || self.slot < dca_position.next_execution_slot {
+            return Ok(None);
+        }
+
+        let dca_config = DcaConfig::unpack(&self.account(&dca_position.dca_config)?.data)?;
+        let swap = self.swap(&dca_config.swap_pool)?;
+        let (source, destination) =
+            swap_reserves(&swap, &dca_config.stable_mint, &dca_config.asset_mint)?;
+        // The program holds the tranche to the oracle price less the slippage
+        // of the configuration; the pool quote bounds it further.
+        let amount_in = dca_position.tranche_amount.min(dca_position.usdc);
+        let quote = swap.quote(
+            &dca_config.stable_mint,
+            amount_in,
+            self.reserve_amount(&swap, &source)?,
+            self.reserve_amount(&swap, &destination)?,
+        )?;
+        let minimum_amount_out = (u128::from(quote.amount_out)
+            * u128::from(10_000 - self.dca_slippage_bps.min(10_000))
+            / 10_000) as u64;
+        let instruction = execute_dca(
+            &self.program_id,
+            &dca_position.dca_config,
+            &dca_position.owner,
+            &dca_config.oracle,
+            &dca_config.stable_vault,
+            &dca_config.asset_vault,
+            &dca_config.swap_program_id,
+            &dca_config.swap_pool,
+            &swap.authority(&dca_config.swap_program_id, &dca_config.swap_pool)?,
+            &source,
+            &destination,
+            &swap.pool_mint,
+            &swap.pool_fee_account,
+            &swap.token_program_id,
+            None,
+            minimum_amount_out,
+        )?;
+        Ok(Some(Crank {
+            kind: CrankKind::Dca,
+            target: *address,
+            urgency: self.slot - dca_position.next_execution_slot,
+            instruction,
+        }))
+    }
+
+    fn reserve_amount(&mut self, swap: &SwapState, reserve: &Pubkey) -> Result<u64, Error> {
+        let account = self.account(reserve)?;
+        Ok(unpack_token_account(&swap.token_program_id, &account.owner, &account.data)?.amount)
+    }
+}
+
+/// Orders `cranks` by kind, then by decreasing urgency within a kind.
+fn sort_cranks(cranks: &mut [Crank]) {
+    cranks.sort_by(|a, b| a.kind.cmp(&b.kind).then(b.urgency.cmp(&a.urgency)));
+}
+
+/// Requests the program accounts of the size of a `T`.
+fn program_accounts_config<T: Pack>() -> RpcProgramAccountsConfig {
+    RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::DataSize(T::LEN as u64)]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    }
+}
+
+/// Keeps the initialized `T` among `accounts`.
+fn unpack_program_accounts<T: Pack + IsInitialized>(
+    accounts: Vec<(Pubkey, RpcAccount)>,
+) -> Vec<(Pubkey, T)> {
+    accounts
+        .into_iter()
+        .filter_map(|(address, account)| T::unpack(&account.data).ok().map(|t| (address, t)))
+        .collect()
+}
+
+/// Returns the pool reserves a swap of `source_mint` into `destination_mint`
+/// goes through.
+fn swap_reserves(
+    swap: &SwapState,
+    source_mint: &Pubkey,
+    destination_mint: &Pubkey,
+) -> Result<(Pubkey, Pubkey), Error> {
+    match (
+        swap.reserve_for_mint(source_mint),
+        swap.reserve_for_mint(destination_mint),
+    ) {
+        (Some(source), Some(destination)) => Ok((source, destination)),
+        _ => Err(format!(
+            "Pool does not trade {} for {}",
+            source_mint, destination_mint
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crank(kind: CrankKind, urgency: u64) -> Crank {
+        Crank {
+            kind,
+            target: Pubkey::new_unique(),
+            urgency,
+            instruction: Instruction {
+                program_id: spl_token::id(),
+                accounts: vec![],
+                data: vec![],
+            },
+        }
+    }
+
+    #[test]
+    fn test_sort_cranks() {
+        let mut cranks = vec![
+            crank(CrankKind::Dca, 50),
+            crank(CrankKind::TakeProfit, 1),
+            crank(CrankKind::StopLoss, 2),
+            crank(CrankKind::TakeProfit, 30),
+            crank(CrankKind::StopLoss, 10),
+            crank(CrankKind::Dca, 0),
+        ];
+        sort_cranks(&mut cranks);
+        assert_eq!(
+            cranks
+                .iter()
+                .map(|crank| (crank.kind, crank.urgency))
+                .collect::<Vec<_>>(),
+            vec![
+                (CrankKind::StopLoss, 10),
+                (CrankKind::StopLoss, 2),
+                (CrankKind::TakeProfit, 30),
+                (CrankKind::TakeProfit, 1),
+                (CrankKind::Dca, 50),
+                (CrankKind::Dca, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_program_accounts_config() {
+        let check = |config: RpcProgramAccountsConfig, len: usize| {
+            match config.filters.as_deref() {
+                Some([RpcFilterType::DataSize(size)]) => assert_eq!(*size, len as u64),
+                filters => panic!("unexpected filters {:?}", filters),
+            }
+            assert_eq!(
+                config.account_config.encoding,
+                Some(UiAccountEncoding::Base64)
+            );
+        };
+        check(program_accounts_config::<StopLoss>(), StopLoss::LEN);
+        check(program_accounts_config::<TakeProfit>(), TakeProfit::LEN);
+        check(program_accounts_config::<DcaPosition>(), DcaPosition::LEN);
+    }
+
+    #[test]
+    fn test_unpack_program_accounts() {
+        let account = |data: Vec<u8>| RpcAccount {
+            lamports: 1,
+            data,
+            owner: spl_token::id(),
+            executable: false,
+            rent_epoch: 0,
+        };
+        let stop_loss = StopLoss {
+            is_initialized: true,
+            mint: Pubkey::new_unique(),
+            ..StopLoss::default()
+        };
+        let mut data = vec![0; StopLoss::LEN];
+        StopLoss::pack(stop_loss, &mut data).unwrap();
+        let address = Pubkey::new_unique();
+
+        // uninitialized and foreign accounts of the same size are skipped
+        let accounts = vec![
+            (Pubkey::new_unique(), account(vec![0; StopLoss::LEN])),
+            (address, account(data)),
+            (Pubkey::new_unique(), account(vec![0xff; StopLoss::LEN])),
+        ];
+        assert_eq!(
+            unpack_program_accounts::<StopLoss>(accounts),
+            vec![(address, stop_loss)]
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! Crank daemon of the Nova token program.
+//!
+//! Stop-loss rules, take-profit rules and DCA positions only move when someone
+//! sends the instruction executing them. The keeper scans the program
+//! accounts every poll interval, simulates the crank of every rule or position
+//! whose condition is met and submits the ones that succeed, stop-losses
+//! first.
+//!
+//! Portfolio periods and rebalancing are not cranked yet: the program has no
+//! instruction that advances a portfolio period or rebalances its assets, so
+//! there is nothing for the keeper to send. The crank follows once that
+//! instruction exists.
+
+use clap::{crate_description, crate_name, crate_version, value_t, App, Arg};
+use log::*;
+use num_traits::FromPrimitive;
+use solana_clap_utils::input_validators::{
+    is_parsable, is_pubkey, is_url_or_moniker, normalize_to_url_if_moniker,
+};
+use solana_client::{client_error::ClientError, rpc_client::RpcClient};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::error::TokenError;
+use std::{process::exit, slice, str::FromStr, sync::Arc, thread::sleep, time::Duration};
+
+mod config;
+use config::*;
+mod crank;
+use crank::{Crank, CrankKind, Scanner};
+mod metrics;
+use metrics::Metrics;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Delay before the first retry of a crank transaction, doubled on every
+/// further retry.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+struct Keeper {
+    rpc_client: RpcClient,
+    payer: Keypair,
+    config: KeeperConfig,
+    metrics: Arc<Metrics>,
+}
+
+impl Keeper {
+    /// Scan the program once and send every crank that is due.
+    fn run_once(&self) -> Result<(), Error> {
+        let mut scanner = Scanner::new(
+            &self.rpc_client,
+            self.config.program_id,
+            self.config.dca_slippage_bps,
+        );
+        let (cranks, skipped) = scanner.scan()?;
+        Metrics::increment(&self.metrics.scans);
+        Metrics::set(&self.metrics.last_scan_slot, scanner.slot());
+        let due = |kind| cranks.iter().filter(|crank| crank.kind == kind).count() as u64;
+        Metrics::set(&self.metrics.due_stop_loss, due(CrankKind::StopLoss));
+        Metrics::set(&self.metrics.due_take_profit, due(CrankKind::TakeProfit));
+        Metrics::set(&self.metrics.due_dca, due(CrankKind::Dca));
+        for (target, err) in skipped {
+            warn!("Skipping {}: {}", target, err);
+        }
+        info!("Slot {}: {} cranks due", scanner.slot(), cranks.len());
+
+        for crank in cranks {
+            self.send_crank(&crank);
+        }
+        Metrics::set(
+            &self.metrics.fee_payer_lamports,
+            self.rpc_client.get_balance(&self.payer.pubkey())?,
+        );
+        Ok(())
+    }
+
+    /// Simulate then send `crank`, retrying with a fresh blockhash when the
+    /// transaction does not land. A failed simulation is not retried: the
+    /// condition no longer holds or the crank cannot succeed until the next
+    /// scan.
+    fn send_crank(&self, crank: &Crank) {
+        let done = with_retries(self.config.max_retries, sleep, |attempt| {
+            let retry = |err: ClientError| {
+                warn!(
+                    "{} {}: attempt {}: {}",
+                    crank.kind, crank.target, attempt, err
+                );
+                Attempt::Retry
+            };
+            let blockhash = match self.rpc_client.get_latest_blockhash() {
+                Ok(blockhash) => blockhash,
+                Err(err) => return retry(err),
+            };
+            let transaction = Transaction::new_signed_with_payer(
+                slice::from_ref(&crank.instruction),
+                Some(&self.payer.pubkey()),
+                &[&self.payer],
+                blockhash,
+            );
+            match self.rpc_client.simulate_transaction(&transaction) {
+                Ok(simulation) => {
+                    if let Some(err) = simulation.value.err {
+                        Metrics::increment(&self.metrics.simulation_failures);
+                        warn!(
+                            "{} {}: simulation failed: {}",
+                            crank.kind,
+                            crank.target,
+                            describe_error(&err)
+                        );
+                        return Attempt::Done;
+                    }
+                }
+                Err(err) => return retry(err),
+            }
+
+            Metrics::increment(&self.metrics.transactions_sent);
+            match self.rpc_client.send_and_confirm_transaction(&transaction) {
+                Ok(signature) => {
+                    Metrics::increment(&self.metrics.transactions_confirmed);
+                    info!("{} {}: {}", crank.kind, crank.target, signature);
+                    Attempt::Done
+                }
+                Err(err) => retry(err),
+            }
+        });
+        if !done {
+            Metrics::increment(&self.metrics.transactions_failed);
+            error!(
+                "{} {}: giving up after {} attempts",
+                crank.kind, crank.target, self.config.max_retries
+            );
+        }
+    }
+}
+
+/// Outcome of one attempt at sending a crank.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Attempt {
+    /// The crank landed, or cannot succeed: no further attempt is made.
+    Done,
+    /// The transaction did not land and may be sent again.
+    Retry,
+}
+
+/// Calls `attempt` with the number of the attempt, from 1, until it is done
+/// or `max_attempts` were made, passing the delay to wait between two
+/// attempts to `sleep`. Returns whether an attempt was done.
+fn with_retries(
+    max_attempts: usize,
+    mut sleep: impl FnMut(Duration),
+    mut attempt: impl FnMut(usize) -> Attempt,
+) -> bool {
+    let mut delay = RETRY_DELAY;
+    for number in 1..=max_attempts {
+        if attempt(number) == Attempt::Done {
+            return true;
+        }
+        if number < max_attempts {
+            sleep(delay);
+            delay *= 2;
+        }
+    }
+    false
+}
+
+/// Describe a failed crank, decoding custom errors of the token program into
+/// their `TokenError` message. Crank transactions hold a single instruction.
+fn describe_error(err: &TransactionError) -> String {
+    if let TransactionError::InstructionError(0, InstructionError::Custom(code)) = err {
+        if let Some(token_error) = TokenError::from_u32(*code) {
+            return format!(
+                "{} (TokenError::{:?}, custom program error {:#x})",
+                token_error, token_error, code
+            );
+        }
+    }
+    err.to_string()
+}
+
+fn main() {
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .arg(
+            Arg::with_name("config_file")
+                .short("c")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("Keeper configuration file"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .short("u")
+                .long("url")
+                .value_name("URL_OR_MONIKER")
+                .takes_value(true)
+                .validator(is_url_or_moniker)
+                .help(
+                    "URL for Solana's JSON RPC or moniker (or their first letter): \
+                     [mainnet-beta, testnet, devnet, localhost] \
+                     [default: the Solana CLI configuration]",
+                ),
+        )
+        .arg(
+            Arg::with_name("keypair")
+                .short("k")
+                .long("keypair")
+                .value_name("KEYPAIR")
+                .takes_value(true)
+                .help(
+                    "Keypair paying for the crank transactions \
+                     [default: the Solana CLI configuration]",
+                ),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program-id")
+                .value_name("ADDRESS")
+                .takes_value(true)
+                .validator(is_pubkey)
+                .help("Token program to crank [default: spl_token::id()]"),
+        )
+        .arg(
+            Arg::with_name("poll_interval")
+                .long("poll-interval")
+                .value_name("SECONDS")
+                .takes_value(true)
+                .validator(is_parsable::<u64>)
+                .help("Seconds between two scans of the program accounts"),
+        )
+        .arg(
+            Arg::with_name("metrics_address")
+                .long("metrics-address")
+                .value_name("HOST:PORT")
+                .takes_value(true)
+                .help("Address of the metrics endpoint"),
+        )
+        .arg(
+            Arg::with_name("max_retries")
+                .long("max-retries")
+                .value_name("COUNT")
+                .takes_value(true)
+                .validator(is_parsable::<usize>)
+                .help("Number of times a crank transaction is sent before giving up"),
+        )
+        .arg(
+            Arg::with_name("dca_slippage_bps")
+                .long("dca-slippage-bps")
+                .value_name("BASIS_POINTS")
+                .takes_value(true)
+                .validator(is_parsable::<u16>)
+                .help("Slippage tolerated on the pool quote of a DCA tranche"),
+        )
+        .arg(
+            Arg::with_name("once")
+                .long("once")
+                .takes_value(false)
+                .help("Scan and crank once, then exit"),
+        )
+        .get_matches();
+
+    solana_logger::setup_with_default("solana=info,nova_keeper=info");
+
+    let _ = (|| -> Result<(), Error> {
+        let config_file = match matches.value_of("config_file") {
+            Some(path) => KeeperConfigFile::load(path)?,
+            None => KeeperConfigFile::default(),
+        };
+        let cli_config = solana_cli_config::CONFIG_FILE
+            .as_ref()
+            .and_then(|path| solana_cli_config::Config::load(path).ok())
+            .unwrap_or_default();
+
+        let json_rpc_url = normalize_to_url_if_moniker(
+            matches
+                .value_of("json_rpc_url")
+                .map(str::to_string)
+                .or_else(|| config_file.json_rpc_url.clone())
+                .unwrap_or(cli_config.json_rpc_url),
+        );
+        let keypair_path = matches
+            .value_of("keypair")
+            .map(str::to_string)
+            .or_else(|| config_file.keypair_path.clone())
+            .unwrap_or(cli_config.keypair_path);
+        let program_id = match matches.value_of("program_id") {
+            Some(program_id) => Pubkey::from_str(program_id)?,
+            None => config_file.program_id()?.unwrap_or_else(spl_token::id),
+        };
+        let config = KeeperConfig {
+            json_rpc_url,
+            keypair_path,
+            program_id,
+            poll_interval: Duration::from_secs(
+                value_t!(matches, "poll_interval", u64)
+                    .ok()
+                    .or(config_file.poll_interval_secs)
+                    .unwrap_or(DEFAULT_POLL_INTERVAL_SECS),
+            ),
+            metrics_address: matches
+                .value_of("metrics_address")
+                .map(str::to_string)
+                .or(config_file.metrics_address)
+                .unwrap_or_else(|| DEFAULT_METRICS_ADDRESS.to_string()),
+            max_retries: value_t!(matches, "max_retries", usize)
+                .ok()
+                .or(config_file.max_retries)
+                .unwrap_or(DEFAULT_MAX_RETRIES)
+                .max(1),
+            dca_slippage_bps: value_t!(matches, "dca_slippage_bps", u16)
+                .ok()
+                .or(config_file.dca_slippage_bps)
+                .unwrap_or(DEFAULT_DCA_SLIPPAGE_BPS),
+        };
+        if config.dca_slippage_bps > 10_000 {
+            return Err("dca_slippage_bps must be at most 10000".into());
+        }
+
+        let payer = read_keypair_file(&config.keypair_path)
+            .map_err(|e| format!("Could not read keypair {}: {}", config.keypair_path, e))?;
+        info!(
+            "Cranking {} on {} as {}",
+            config.program_id,
+            config.json_rpc_url,
+            payer.pubkey()
+        );
+        let keeper = Keeper {
+            rpc_client: RpcClient::new_with_commitment(
+                config.json_rpc_url.clone(),
+                CommitmentConfig::confirmed(),
+            ),
+            payer,
+            metrics: Arc::new(Metrics::default()),
+            config,
+        };
+
+        let once = matches.is_present("once");
+        if !once {
+            metrics::serve(&keeper.config.metrics_address, keeper.metrics.clone())?;
+        }
+        loop {
+            if let Err(err) = keeper.run_once() {
+                Metrics::increment(&keeper.metrics.scan_errors);
+                error!("Scan failed: {}", err);
+                if once {
+                    return Err(err);
+                }
+            }
+            if once {
+                return Ok(());
+            }
+            sleep(keeper.config.poll_interval);
+        }
+    })()
+    .map_err(|err| {
+        eprintln!("{}", err);
+        exit(1);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_retries() {
+        // the delay doubles between attempts, and none follows the last one
+        let mut delays = vec![];
+        let mut attempts = vec![];
+        let done = with_retries(
+            4,
+            |delay| delays.push(delay),
+            |attempt| {
+                attempts.push(attempt);
+                Attempt::Retry
+            },
+        );
+        assert!(!done);
+        assert_eq!(attempts, vec![1, 2, 3, 4]);
+        assert_eq!(delays, vec![RETRY_DELAY, RETRY_DELAY * 2, RETRY_DELAY * 4]);
+
+        // a done attempt stops the retries
+        let mut delays = vec![];
+        let mut attempts = vec![];
+        let done = with_retries(
+            4,
+            |delay| delays.push(delay),
+            |attempt| {
+                attempts.push(attempt);
+                if attempt == 2 {
+                    Attempt::Done
+                } else {
+                    Attempt::Retry
+                }
+            },
+        );
+        assert!(done);
+        assert_eq!(attempts, vec![1, 2]);
+        assert_eq!(delays, vec![RETRY_DELAY]);
+
+        // no attempt allowed
+        let done = with_retries(0, |_| panic!("no delay"), |_| panic!("no attempt"));
+        assert!(!done);
+    }
+}
@@ -0,0 +1,128 @@
+use log::*;
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+/// Counters exposed by the metrics endpoint.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub scans: AtomicU64,
+    pub scan_errors: AtomicU64,
+    pub last_scan_slot: AtomicU64,
+    pub due_stop_loss: AtomicU64,
+    pub due_take_profit: AtomicU64,
+    pub due_dca: AtomicU64,
+    pub simulation_failures: AtomicU64,
+    pub transactions_sent: AtomicU64,
+    pub transactions_confirmed: AtomicU64,
+    pub transactions_failed: AtomicU64,
+    pub fee_payer_lamports: AtomicU64,
+}
+
+impl Metrics {
+    pub fn increment(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set(gauge: &AtomicU64, value: u64) {
+        gauge.store(value, Ordering::Relaxed);
+    }
+
+    /// Render the metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let metrics = [
+            ("nova_keeper_scans_total", "counter", &self.scans),
+            (
+                "nova_keeper_scan_errors_total",
+                "counter",
+                &self.scan_errors,
+            ),
+            ("nova_keeper_last_scan_slot", "gauge", &self.last_scan_slot),
+            ("nova_keeper_due_stop_loss", "gauge", &self.due_stop_loss),
+            (
+                "nova_keeper_due_take_profit",
+                "gauge",
+                &self.due_take_profit,
+            ),
+            ("nova_keeper_due_dca", "gauge", &self.due_dca),
+            (
+                "nova_keeper_simulation_failures_total",
+                "counter",
+                &self.simulation_failures,
+            ),
+            (
+                "nova_keeper_transactions_sent_total",
+                "counter",
+                &self.transactions_sent,
+            ),
+            (
+                "nova_keeper_transactions_confirmed_total",
+                "counter",
+                &self.transactions_confirmed,
+            ),
+            (
+                "nova_keeper_transactions_failed_total",
+                "counter",
+                &self.transactions_failed,
+            ),
+            (
+                "nova_keeper_fee_payer_lamports",
+                "gauge",
+                &self.fee_payer_lamports,
+            ),
+        ];
+        let mut body = String::new();
+        for (name, kind, value) in metrics.iter() {
+            body.push_str(&format!(
+                "# TYPE {} {}\n{} {}\n",
+                name,
+                kind,
+                name,
+                value.load(Ordering::Relaxed)
+            ));
+        }
+        body
+    }
+}
+
+/// Serve `metrics` over HTTP on `address` from a background thread. Every
+/// request, whatever its path, is answered with the current metrics.
+pub fn serve(address: &str, metrics: Arc<Metrics>) -> Result<(), String> {
+    let listener = TcpListener::bind(address)
+        .map_err(|e| format!("Could not bind the metrics endpoint to {}: {}", address, e))?;
+    info!("Serving metrics on http://{}/metrics", address);
+    thread::Builder::new()
+        .name("metrics".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => {
+                        if let Err(err) = respond(stream, &metrics) {
+                            debug!("Metrics request failed: {}", err);
+                        }
+                    }
+                    Err(err) => debug!("Metrics connection failed: {}", err),
+                }
+            }
+        })
+        .map_err(|e| format!("Could not start the metrics endpoint: {}", e))?;
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    let mut request = [0u8; 1024];
+    let _ = stream.read(&mut request)?;
+    let body = metrics.render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use spl_token::instruction::TokenInstruction;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(instruction) = TokenInstruction::unpack(data) {
+        let packed = instruction.pack();
+        assert_eq!(TokenInstruction::unpack(&packed), Ok(instruction));
+    }
+});
@@ -0,0 +1,39 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use solana_program::program_pack::Pack;
+use spl_token::state::{
+    Account, AssetEntry, DcaConfig, DcaPosition, DepositLimits, DepositRecord, HedgeConfig, Mint,
+    Multisig, Portfolio, Position, ProgramConfig, StateAccount, StopLoss, TakeProfit,
+    UserPortfolio, WithdrawTicket,
+};
+
+fn roundtrip<T: Pack + Clone + PartialEq + std::fmt::Debug>(data: &[u8]) {
+    if data.len() != T::LEN {
+        return;
+    }
+    if let Ok(state) = T::unpack_unchecked(data) {
+        let mut packed = vec![0u8; T::LEN];
+        T::pack(state.clone(), &mut packed).unwrap();
+        assert_eq!(T::unpack_unchecked(&packed).unwrap(), state);
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    roundtrip::<Mint>(data);
+    roundtrip::<Account>(data);
+    roundtrip::<Multisig>(data);
+    roundtrip::<HedgeConfig>(data);
+    roundtrip::<Position>(data);
+    roundtrip::<ProgramConfig>(data);
+    roundtrip::<AssetEntry>(data);
+    roundtrip::<DepositLimits>(data);
+    roundtrip::<DepositRecord>(data);
+    roundtrip::<StopLoss>(data);
+    roundtrip::<TakeProfit>(data);
+    roundtrip::<DcaConfig>(data);
+    roundtrip::<DcaPosition>(data);
+    roundtrip::<WithdrawTicket>(data);
+    roundtrip::<Portfolio>(data);
+    roundtrip::<UserPortfolio>(data);
+    let _ = StateAccount::unpack(data);
+});
@@ -64,6 +64,141 @@ pub enum TokenError {
     /// Mint decimals mismatch between the client and mint
     #[error("The provided decimals value different from the Mint decimals")]
     MintDecimalsMismatch,
+    /// Mint is not configured to hedge an asset
+    #[error("Mint is not configured for hedging")]
+    MintNotHedged,
+    /// Swap pool does not match the pool configured on the mint
+    #[error("Swap pool does not match the mint's configured pool")]
+    SwapPoolMismatch,
+    /// Swap pool is not owned by the provided swap program
+    #[error("Swap pool is not owned by the provided swap program")]
+    IncorrectSwapProgramId,
+    /// Swap pool state could not be read or is not initialized
+    #[error("Swap pool state is invalid")]
+    InvalidSwapState,
+    /// Swap authority is not derived from the swap pool
+    #[error("Swap authority does not match the swap pool")]
+    InvalidSwapAuthority,
+    /// Account is not one of the swap pool reserves
+    #[error("Account is not a reserve of the swap pool")]
+    IncorrectSwapAccount,
+    /// Pool mint does not match the swap pool
+    #[error("Pool mint does not match the swap pool")]
+    IncorrectPoolMint,
+    /// Pool fee account does not match the swap pool
+    #[error("Pool fee account does not match the swap pool")]
+    IncorrectPoolFeeAccount,
+    /// Token program does not match the swap pool
+    #[error("Token program does not match the swap pool")]
+    IncorrectTokenProgramId,
+    /// Swap pool does not trade the asset hedged by the mint
+    #[error("Swap pool does not trade the mint's asset")]
+    AssetMintMismatch,
+    /// Vault authority is not the one derived from the mint
+    #[error("Vault authority is not derived from the mint")]
+    InvalidVaultAuthority,
+    /// Vault token account is not owned by the vault authority
+    #[error("Vault is not owned by the vault authority")]
+    IncorrectVaultOwner,
+    /// Ratio or fee exceeds 100%
+    #[error("Ratio or fee exceeds 10000 basis points")]
+    InvalidBasisPoints,
+    /// Swap pool does not trade the stablecoin
+    #[error("Swap pool does not trade the stablecoin")]
+    StableMintMismatch,
+    /// Vault does not match the hedge configuration
+    #[error("Vault does not match the hedge configuration")]
+    IncorrectVaultAccount,
+    /// Fee account does not match the hedge configuration
+    #[error("Fee account does not match the hedge configuration")]
+    IncorrectFeeAccount,
+    /// Position belongs to another token account
+    #[error("Position does not belong to the token account")]
+    PositionMismatch,
+    /// Source position supplied without the destination position
+    #[error("Destination position is required to move the source position")]
+    PositionRequired,
+    /// Account does not use a legacy layout
+    #[error("Account does not use a legacy layout")]
+    NotLegacyAccount,
+    /// Operation is paused by the program configuration
+    #[error("Operation is paused")]
+    Paused,
+    /// Signer is not the upgrade authority of the program
+    #[error("Signer is not the upgrade authority of the program")]
+    IncorrectUpgradeAuthority,
+    /// Guardian attempted to resume a paused operation
+    #[error("Guardian can only pause operations")]
+    GuardianCannotUnpause,
+    /// Protocol fee account is not owned by the protocol fee owner
+    #[error("Fee account is not owned by the protocol fee owner")]
+    IncorrectProtocolFeeAccount,
+    /// Mint is not approved in the asset registry
+    #[error("Mint is not approved in the asset registry")]
+    AssetNotRegistered,
+    /// Mint is registered with another role
+    #[error("Mint is registered with another role")]
+    IncorrectAssetKind,
+    /// Deposit exceeds the cap on a single deposit
+    #[error("Deposit exceeds the cap on a single deposit")]
+    DepositCapExceeded,
+    /// Supply would exceed its cap
+    #[error("Supply would exceed its cap")]
+    SupplyCapExceeded,
+    /// Cumulative deposits of the owner would exceed their cap
+    #[error("Cumulative deposits of the owner would exceed their cap")]
+    OwnerDepositCapExceeded,
+    /// Oracle account is not a valid price feed
+    #[error("Oracle account is not a valid price feed")]
+    InvalidOracle,
+    /// Oracle price is stale
+    #[error("Oracle price is stale")]
+    StaleOraclePrice,
+    /// Stop-loss rule is not set for the mint
+    #[error("Stop-loss rule is not set for the mint")]
+    StopLossNotSet,
+    /// Stop-loss rule already triggered
+    #[error("Stop-loss rule already triggered")]
+    StopLossTriggered,
+    /// Take-profit rule is not set for the mint
+    #[error("Take-profit rule is not set for the mint")]
+    TakeProfitNotSet,
+    /// Oracle price is below the take-profit target
+    #[error("Oracle price is below the take-profit target")]
+    TakeProfitNotReached,
+    /// Take-profit rule of the mint must be provided
+    #[error("Take-profit rule of the mint must be provided")]
+    TakeProfitRequired,
+    /// Portfolio asset has no dollar-cost-averaging schedule
+    #[error("Portfolio asset has no dollar-cost-averaging schedule")]
+    InvalidDcaSchedule,
+    /// Next dollar-cost-averaging tranche is not due
+    #[error("Next dollar-cost-averaging tranche is not due")]
+    DcaNotDue,
+    /// No withdrawal was requested for this amount
+    #[error("No withdrawal was requested for this amount")]
+    WithdrawNotRequested,
+    /// Requested withdrawal is still in its delay
+    #[error("Requested withdrawal is still in its delay")]
+    WithdrawNotClaimable,
+    /// Cannot withdraw in the slot of the last deposit
+    #[error("Cannot withdraw in the slot of the last deposit")]
+    WithdrawInDepositSlot,
+    /// Position still holds hedged components or unclaimed rewards
+    #[error("Position still holds hedged components or unclaimed rewards")]
+    PositionNotEmpty,
+    /// Hedge configuration of the mint must be provided
+    #[error("Hedge configuration of the mint must be provided")]
+    HedgeConfigRequired,
+    /// Record of the epoch the position was last settled in must be provided
+    #[error("Record of the epoch the position was last settled in must be provided")]
+    HedgeEpochRequired,
+    /// Legacy mint still names a hedged asset or swap pool
+    #[error("Legacy mint still names a hedged asset or swap pool")]
+    LegacyMintHedged,
+    /// Deposit limits of the mint must be provided
+    #[error("Deposit limits of the mint must be provided")]
+    DepositLimitsRequired,
 }
 impl From<TokenError> for ProgramError {
     fn from(e: TokenError) -> Self {
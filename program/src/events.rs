@@ -0,0 +1,604 @@
+//! Events logged by the token program
+
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+use std::convert::TryInto;
+
+/// Prefix of the program log lines carrying an event
+pub const EVENT_LOG_PREFIX: &str = "event: ";
+/// Version of the binary encoding of events
+pub const EVENT_VERSION: u8 = 1;
+
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+
+/// Tokens were deposited into a hedged token account.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DepositEvent {
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The token account credited.
+    pub account: Pubkey,
+    /// The tokens minted into the account, net of fees.
+    pub amount: u64,
+    /// The asset credited to the position.
+    pub asset: u64,
+    /// The stablecoin credited to the position.
+    pub usdc: u64,
+}
+
+/// Tokens were withdrawn from a hedged token account.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WithdrawEvent {
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The token account debited.
+    pub account: Pubkey,
+    /// The tokens withdrawn from the account.
+    pub amount: u64,
+    /// The asset released from the position.
+    pub asset: u64,
+    /// The stablecoin released from the position.
+    pub usdc: u64,
+}
+
+/// Part of a deposit was swapped into the stablecoin to keep the target ratio.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RebalanceEvent {
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The asset sold through the swap pool.
+    pub asset_sold: u64,
+    /// The stablecoin received from the swap pool.
+    pub usdc_bought: u64,
+}
+
+/// A portfolio was initialized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortfolioCreated {
+    /// The portfolio account.
+    pub portfolio: Pubkey,
+    /// The creator of the portfolio.
+    pub creator: Pubkey,
+    /// The hash of the portfolio metadata.
+    pub metadata_hash: u16,
+}
+
+/// A user joined a portfolio.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UserPortfolioCreated {
+    /// The user portfolio account.
+    pub user_portfolio: Pubkey,
+    /// The portfolio joined.
+    pub portfolio: Pubkey,
+    /// The owner of the user portfolio.
+    pub owner: Pubkey,
+    /// The amount delegated to the portfolio.
+    pub delegated_amount: u64,
+}
+
+/// A fee was collected into the fee account of a hedged mint.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FeeCollected {
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The account receiving the fee.
+    pub fee_account: Pubkey,
+    /// The fee collected.
+    pub amount: u64,
+}
+
+/// The stop-loss rule of a hedged mint was triggered and its asset vault
+/// swapped into the stablecoin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StopLossTriggered {
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The oracle price that met the rule.
+    pub price: u64,
+    /// The asset sold through the swap pool.
+    pub asset_sold: u64,
+    /// The stablecoin received from the swap pool.
+    pub usdc_bought: u64,
+}
+
+/// Part of the asset vault of a hedged mint was sold by its take-profit rule.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProfitHarvested {
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The oracle price the asset was sold at.
+    pub price: u64,
+    /// The asset sold through the swap pool.
+    pub asset_sold: u64,
+    /// The stablecoin received from the swap pool.
+    pub usdc_bought: u64,
+    /// The stablecoin received over the entry price of the asset sold.
+    pub profit: u64,
+    /// The share of the profit distributed to the holders of the mint.
+    pub distributed: u64,
+}
+
+/// Rewards distributed by a take-profit rule were claimed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RewardsClaimed {
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The token account the rewards accrued to.
+    pub account: Pubkey,
+    /// The stablecoin paid out.
+    pub amount: u64,
+}
+
+/// A dollar-cost-averaging tranche was bought for a depositor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DcaExecuted {
+    /// The dollar-cost-averaging configuration.
+    pub dca_config: Pubkey,
+    /// The depositor.
+    pub owner: Pubkey,
+    /// The stablecoin sold through the swap pool.
+    pub usdc_sold: u64,
+    /// The asset received from the swap pool.
+    pub asset_bought: u64,
+}
+
+/// Events logged by the token program.
+///
+/// Each event is logged as `EVENT_LOG_PREFIX` followed by the base64 encoding
+/// of `[EVENT_VERSION, tag, payload...]`, integers being little endian.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenEvent {
+    /// See `DepositEvent`
+    Deposit(DepositEvent),
+    /// See `WithdrawEvent`
+    Withdraw(WithdrawEvent),
+    /// See `RebalanceEvent`
+    Rebalance(RebalanceEvent),
+    /// See `PortfolioCreated`
+    PortfolioCreated(PortfolioCreated),
+    /// See `UserPortfolioCreated`
+    UserPortfolioCreated(UserPortfolioCreated),
+    /// See `FeeCollected`
+    FeeCollected(FeeCollected),
+    /// See `StopLossTriggered`
+    StopLossTriggered(StopLossTriggered),
+    /// See `ProfitHarvested`
+    ProfitHarvested(ProfitHarvested),
+    /// See `RewardsClaimed`
+    RewardsClaimed(RewardsClaimed),
+    /// See `DcaExecuted`
+    DcaExecuted(DcaExecuted),
+}
+
+impl TokenEvent {
+    /// Unpacks a byte buffer into a [TokenEvent](enum.TokenEvent.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&version, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidArgument)?;
+        if version != EVENT_VERSION {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (&tag, rest) = rest.split_first().ok_or(ProgramError::InvalidArgument)?;
+        let (event, rest) = match tag {
+            0 => {
+                let (mint, rest) = unpack_pubkey(rest)?;
+                let (account, rest) = unpack_pubkey(rest)?;
+                let (amount, rest) = unpack_u64(rest)?;
+                let (asset, rest) = unpack_u64(rest)?;
+                let (usdc, rest) = unpack_u64(rest)?;
+                let event = DepositEvent {
+                    mint,
+                    account,
+                    amount,
+                    asset,
+                    usdc,
+                };
+                (Self::Deposit(event), rest)
+            }
+            1 => {
+                let (mint, rest) = unpack_pubkey(rest)?;
+                let (account, rest) = unpack_pubkey(rest)?;
+                let (amount, rest) = unpack_u64(rest)?;
+                let (asset, rest) = unpack_u64(rest)?;
+                let (usdc, rest) = unpack_u64(rest)?;
+                let event = WithdrawEvent {
+                    mint,
+                    account,
+                    amount,
+                    asset,
+                    usdc,
+                };
+                (Self::Withdraw(event), rest)
+            }
+            2 => {
+                let (mint, rest) = unpack_pubkey(rest)?;
+                let (asset_sold, rest) = unpack_u64(rest)?;
+                let (usdc_bought, rest) = unpack_u64(rest)?;
+                let event = RebalanceEvent {
+                    mint,
+                    asset_sold,
+                    usdc_bought,
+                };
+                (Self::Rebalance(event), rest)
+            }
+            3 => {
+                let (portfolio, rest) = unpack_pubkey(rest)?;
+                let (creator, rest) = unpack_pubkey(rest)?;
+                let metadata_hash = rest
+                    .get(..2)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u16::from_le_bytes)
+                    .ok_or(ProgramError::InvalidArgument)?;
+                let event = PortfolioCreated {
+                    portfolio,
+                    creator,
+                    metadata_hash,
+                };
+                (Self::PortfolioCreated(event), &rest[2..])
+            }
+            4 => {
+                let (user_portfolio, rest) = unpack_pubkey(rest)?;
+                let (portfolio, rest) = unpack_pubkey(rest)?;
+                let (owner, rest) = unpack_pubkey(rest)?;
+                let (delegated_amount, rest) = unpack_u64(rest)?;
+                let event = UserPortfolioCreated {
+                    user_portfolio,
+                    portfolio,
+                    owner,
+                    delegated_amount,
+                };
+                (Self::UserPortfolioCreated(event), rest)
+            }
+            5 => {
+                let (mint, rest) = unpack_pubkey(rest)?;
+                let (fee_account, rest) = unpack_pubkey(rest)?;
+                let (amount, rest) = unpack_u64(rest)?;
+                let event = FeeCollected {
+                    mint,
+                    fee_account,
+                    amount,
+                };
+                (Self::FeeCollected(event), rest)
+            }
+            6 => {
+                let (mint, rest) = unpack_pubkey(rest)?;
+                let (price, rest) = unpack_u64(rest)?;
+                let (asset_sold, rest) = unpack_u64(rest)?;
+                let (usdc_bought, rest) = unpack_u64(rest)?;
+                let event = StopLossTriggered {
+                    mint,
+                    price,
+                    asset_sold,
+                    usdc_bought,
+                };
+                (Self::StopLossTriggered(event), rest)
+            }
+            7 => {
+                let (mint, rest) = unpack_pubkey(rest)?;
+                let (price, rest) = unpack_u64(rest)?;
+                let (asset_sold, rest) = unpack_u64(rest)?;
+                let (usdc_bought, rest) = unpack_u64(rest)?;
+                let (profit, rest) = unpack_u64(rest)?;
+                let (distributed, rest) = unpack_u64(rest)?;
+                let event = ProfitHarvested {
+                    mint,
+                    price,
+                    asset_sold,
+                    usdc_bought,
+                    profit,
+                    distributed,
+                };
+                (Self::ProfitHarvested(event), rest)
+            }
+            8 => {
+                let (mint, rest) = unpack_pubkey(rest)?;
+                let (account, rest) = unpack_pubkey(rest)?;
+                let (amount, rest) = unpack_u64(rest)?;
+                let event = RewardsClaimed {
+                    mint,
+                    account,
+                    amount,
+                };
+                (Self::RewardsClaimed(event), rest)
+            }
+            9 => {
+                let (dca_config, rest) = unpack_pubkey(rest)?;
+                let (owner, rest) = unpack_pubkey(rest)?;
+                let (usdc_sold, rest) = unpack_u64(rest)?;
+                let (asset_bought, rest) = unpack_u64(rest)?;
+                let event = DcaExecuted {
+                    dca_config,
+                    owner,
+                    usdc_sold,
+                    asset_bought,
+                };
+                (Self::DcaExecuted(event), rest)
+            }
+            _ => return Err(ProgramError::InvalidArgument),
+        };
+        if !rest.is_empty() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(event)
+    }
+
+    /// Packs a [TokenEvent](enum.TokenEvent.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = vec![EVENT_VERSION];
+        match self {
+            Self::Deposit(event) => {
+                buf.push(0);
+                buf.extend_from_slice(event.mint.as_ref());
+                buf.extend_from_slice(event.account.as_ref());
+                buf.extend_from_slice(&event.amount.to_le_bytes());
+                buf.extend_from_slice(&event.asset.to_le_bytes());
+                buf.extend_from_slice(&event.usdc.to_le_bytes());
+            }
+            Self::Withdraw(event) => {
+                buf.push(1);
+                buf.extend_from_slice(event.mint.as_ref());
+                buf.extend_from_slice(event.account.as_ref());
+                buf.extend_from_slice(&event.amount.to_le_bytes());
+                buf.extend_from_slice(&event.asset.to_le_bytes());
+                buf.extend_from_slice(&event.usdc.to_le_bytes());
+            }
+            Self::Rebalance(event) => {
+                buf.push(2);
+                buf.extend_from_slice(event.mint.as_ref());
+                buf.extend_from_slice(&event.asset_sold.to_le_bytes());
+                buf.extend_from_slice(&event.usdc_bought.to_le_bytes());
+            }
+            Self::PortfolioCreated(event) => {
+                buf.push(3);
+                buf.extend_from_slice(event.portfolio.as_ref());
+                buf.extend_from_slice(event.creator.as_ref());
+                buf.extend_from_slice(&event.metadata_hash.to_le_bytes());
+            }
+            Self::UserPortfolioCreated(event) => {
+                buf.push(4);
+                buf.extend_from_slice(event.user_portfolio.as_ref());
+                buf.extend_from_slice(event.portfolio.as_ref());
+                buf.extend_from_slice(event.owner.as_ref());
+                buf.extend_from_slice(&event.delegated_amount.to_le_bytes());
+            }
+            Self::FeeCollected(event) => {
+                buf.push(5);
+                buf.extend_from_slice(event.mint.as_ref());
+                buf.extend_from_slice(event.fee_account.as_ref());
+                buf.extend_from_slice(&event.amount.to_le_bytes());
+            }
+            Self::StopLossTriggered(event) => {
+                buf.push(6);
+                buf.extend_from_slice(event.mint.as_ref());
+                buf.extend_from_slice(&event.price.to_le_bytes());
+                buf.extend_from_slice(&event.asset_sold.to_le_bytes());
+                buf.extend_from_slice(&event.usdc_bought.to_le_bytes());
+            }
+            Self::ProfitHarvested(event) => {
+                buf.push(7);
+                buf.extend_from_slice(event.mint.as_ref());
+                buf.extend_from_slice(&event.price.to_le_bytes());
+                buf.extend_from_slice(&event.asset_sold.to_le_bytes());
+                buf.extend_from_slice(&event.usdc_bought.to_le_bytes());
+                buf.extend_from_slice(&event.profit.to_le_bytes());
+                buf.extend_from_slice(&event.distributed.to_le_bytes());
+            }
+            Self::RewardsClaimed(event) => {
+                buf.push(8);
+                buf.extend_from_slice(event.mint.as_ref());
+                buf.extend_from_slice(event.account.as_ref());
+                buf.extend_from_slice(&event.amount.to_le_bytes());
+            }
+            Self::DcaExecuted(event) => {
+                buf.push(9);
+                buf.extend_from_slice(event.dca_config.as_ref());
+                buf.extend_from_slice(event.owner.as_ref());
+                buf.extend_from_slice(&event.usdc_sold.to_le_bytes());
+                buf.extend_from_slice(&event.asset_bought.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    /// Writes the event to the program log.
+    pub fn emit(&self) {
+        msg!("{}{}", EVENT_LOG_PREFIX, base64::encode(self.pack()));
+    }
+}
+
+/// Decodes the events logged by `program_id` from the log messages of a
+/// transaction, in order.
+///
+/// Only events logged while `program_id` is the innermost program executing
+/// are returned, so other programs cannot forge events by logging the same
+/// prefix.
+pub fn parse_logs<S: AsRef<str>>(
+    program_id: &Pubkey,
+    logs: &[S],
+) -> Result<Vec<TokenEvent>, ProgramError> {
+    let program_id = program_id.to_string();
+    let mut invoke_stack: Vec<&str> = vec![];
+    let mut events = vec![];
+    for log in logs {
+        let log = log.as_ref();
+        if let Some(data) = log
+            .strip_prefix(PROGRAM_LOG_PREFIX)
+            .and_then(|message| message.strip_prefix(EVENT_LOG_PREFIX))
+        {
+            if invoke_stack.last() == Some(&program_id.as_str()) {
+                let data = base64::decode(data).map_err(|_| ProgramError::InvalidArgument)?;
+                events.push(TokenEvent::unpack(&data)?);
+            }
+        } else if let Some(rest) = log.strip_prefix("Program ") {
+            let mut words = rest.split(' ');
+            if let (Some(program), Some(action)) = (words.next(), words.next()) {
+                if action == "invoke" {
+                    invoke_stack.push(program);
+                } else if action == "success" || action == "failed:" {
+                    invoke_stack.pop();
+                }
+            }
+        }
+    }
+    Ok(events)
+}
+
+fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+    let key = input
+        .get(..32)
+        .and_then(|slice| slice.try_into().ok())
+        .map(Pubkey::new_from_array)
+        .ok_or(ProgramError::InvalidArgument)?;
+    Ok((key, &input[32..]))
+}
+
+fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+    let value = input
+        .get(..8)
+        .and_then(|slice| slice.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(ProgramError::InvalidArgument)?;
+    Ok((value, &input[8..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deposit_event() -> TokenEvent {
+        TokenEvent::Deposit(DepositEvent {
+            mint: Pubkey::new(&[1u8; 32]),
+            account: Pubkey::new(&[2u8; 32]),
+            amount: 3,
+            asset: 4,
+            usdc: 5,
+        })
+    }
+
+    #[test]
+    fn test_event_packing() {
+        let check = deposit_event();
+        let packed = check.pack();
+        let mut expect = vec![EVENT_VERSION, 0];
+        expect.extend_from_slice(&[1u8; 32]);
+        expect.extend_from_slice(&[2u8; 32]);
+        expect.extend_from_slice(&[3, 0, 0, 0, 0, 0, 0, 0]);
+        expect.extend_from_slice(&[4, 0, 0, 0, 0, 0, 0, 0]);
+        expect.extend_from_slice(&[5, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        assert_eq!(TokenEvent::unpack(&expect), Ok(check));
+
+        let checks = vec![
+            TokenEvent::Withdraw(WithdrawEvent {
+                mint: Pubkey::new(&[1u8; 32]),
+                account: Pubkey::new(&[2u8; 32]),
+                amount: 3,
+                asset: 4,
+                usdc: 5,
+            }),
+            TokenEvent::Rebalance(RebalanceEvent {
+                mint: Pubkey::new(&[1u8; 32]),
+                asset_sold: 2,
+                usdc_bought: 3,
+            }),
+            TokenEvent::PortfolioCreated(PortfolioCreated {
+                portfolio: Pubkey::new(&[1u8; 32]),
+                creator: Pubkey::new(&[2u8; 32]),
+                metadata_hash: 3,
+            }),
+            TokenEvent::UserPortfolioCreated(UserPortfolioCreated {
+                user_portfolio: Pubkey::new(&[1u8; 32]),
+                portfolio: Pubkey::new(&[2u8; 32]),
+                owner: Pubkey::new(&[3u8; 32]),
+                delegated_amount: 4,
+            }),
+            TokenEvent::FeeCollected(FeeCollected {
+                mint: Pubkey::new(&[1u8; 32]),
+                fee_account: Pubkey::new(&[2u8; 32]),
+                amount: 3,
+            }),
+            TokenEvent::StopLossTriggered(StopLossTriggered {
+                mint: Pubkey::new(&[1u8; 32]),
+                price: 2,
+                asset_sold: 3,
+                usdc_bought: 4,
+            }),
+            TokenEvent::ProfitHarvested(ProfitHarvested {
+                mint: Pubkey::new(&[1u8; 32]),
+                price: 2,
+                asset_sold: 3,
+                usdc_bought: 4,
+                profit: 5,
+                distributed: 6,
+            }),
+            TokenEvent::RewardsClaimed(RewardsClaimed {
+                mint: Pubkey::new(&[1u8; 32]),
+                account: Pubkey::new(&[2u8; 32]),
+                amount: 3,
+            }),
+            TokenEvent::DcaExecuted(DcaExecuted {
+                dca_config: Pubkey::new(&[1u8; 32]),
+                owner: Pubkey::new(&[2u8; 32]),
+                usdc_sold: 3,
+                asset_bought: 4,
+            }),
+        ];
+        for check in checks {
+            let mut packed = check.pack();
+            assert_eq!(TokenEvent::unpack(&packed), Ok(check));
+            for len in 0..packed.len() {
+                assert_eq!(
+                    TokenEvent::unpack(&packed[..len]),
+                    Err(ProgramError::InvalidArgument)
+                );
+            }
+            packed.push(0);
+            assert_eq!(
+                TokenEvent::unpack(&packed),
+                Err(ProgramError::InvalidArgument)
+            );
+        }
+
+        let mut packed = deposit_event().pack();
+        packed[0] = EVENT_VERSION + 1;
+        assert_eq!(
+            TokenEvent::unpack(&packed),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_parse_logs() {
+        let program_id = crate::id();
+        let other_program_id = Pubkey::new_unique();
+        let event = deposit_event();
+        let event_log = format!(
+            "{}{}{}",
+            PROGRAM_LOG_PREFIX,
+            EVENT_LOG_PREFIX,
+            base64::encode(event.pack())
+        );
+        let logs = vec![
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Instruction: Deposit".to_string(),
+            format!("Program {} invoke [2]", other_program_id),
+            event_log.clone(),
+            format!("Program {} success", other_program_id),
+            event_log.clone(),
+            format!("Program {} consumed 100 of 200000 compute units", program_id),
+            format!("Program {} success", program_id),
+            event_log.clone(),
+        ];
+        assert_eq!(parse_logs(&program_id, &logs), Ok(vec![event]));
+
+        let logs = vec![
+            format!("Program {} invoke [1]", program_id),
+            format!("{}{}not base64", PROGRAM_LOG_PREFIX, EVENT_LOG_PREFIX),
+            format!("Program {} success", program_id),
+        ];
+        assert_eq!(
+            parse_logs(&program_id, &logs),
+            Err(ProgramError::InvalidArgument)
+        );
+    }
+}
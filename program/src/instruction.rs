@@ -1,13 +1,15 @@
 //! Instruction types
 
-use crate::error::TokenError;
+use crate::{error::TokenError, state::AssetKind};
+use num_enum::TryFromPrimitive;
 use solana_program::{
+    bpf_loader_upgradeable,
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
     program_option::COption,
     pubkey::Pubkey,
+    system_program,
     sysvar,
-    msg
 };
 use std::convert::TryInto;
 use std::mem::size_of;
@@ -16,6 +18,39 @@ use std::mem::size_of;
 pub const MIN_SIGNERS: usize = 1;
 /// Maximum number of multisignature signers (max N)
 pub const MAX_SIGNERS: usize = 11;
+/// Seed prefix of the vault authority derived for each mint
+pub const VAULT_AUTHORITY_SEED: &[u8] = b"vault_authority";
+/// Seed prefix of the hedge configuration derived for each mint
+pub const HEDGE_CONFIG_SEED: &[u8] = b"hedge_config";
+/// Seed of the position holding the hedged components of a token account.
+pub const POSITION_SEED: &[u8] = b"position";
+/// Seed of the program configuration
+pub const PROGRAM_CONFIG_SEED: &[u8] = b"program_config";
+/// Seed prefix of the asset registry entry derived for each mint
+pub const ASSET_ENTRY_SEED: &[u8] = b"asset_entry";
+/// Seed prefix of the deposit limits derived for each hedged mint or portfolio
+pub const DEPOSIT_LIMITS_SEED: &[u8] = b"deposit_limits";
+/// Seed prefix of the deposit record derived for each owner of a hedged mint
+/// or portfolio
+pub const DEPOSIT_RECORD_SEED: &[u8] = b"deposit_record";
+/// Seed prefix of the stop-loss rule derived for each hedged mint
+pub const STOP_LOSS_SEED: &[u8] = b"stop_loss";
+/// Seed prefix of the take-profit rule derived for each hedged mint
+pub const TAKE_PROFIT_SEED: &[u8] = b"take_profit";
+/// Seed prefix of the dollar-cost-averaging configuration derived for each
+/// portfolio asset
+pub const DCA_CONFIG_SEED: &[u8] = b"dca_config";
+/// Seed prefix of the dollar-cost-averaging position derived for each
+/// configuration and owner
+pub const DCA_POSITION_SEED: &[u8] = b"dca_position";
+/// Seed prefix of the withdraw ticket derived for each token account
+pub const WITHDRAW_TICKET_SEED: &[u8] = b"withdraw_ticket";
+/// Seed prefix of the record derived for each closed epoch of a hedged mint
+pub const HEDGE_EPOCH_SEED: &[u8] = b"hedge_epoch";
+/// Denominator of ratios and fees expressed in basis points
+pub const BASIS_POINTS: u16 = 10_000;
+/// Length of the metadata URL of a portfolio
+pub const METADATA_URL_LEN: usize = 128;
 
 /// Instructions supported by the token program.
 #[repr(C)]
@@ -35,18 +70,14 @@ pub enum TokenInstruction {
     ///   0. `[writable]` The mint to initialize.
     ///   1. `[]` Rent sysvar
     ///
-    InitializeMint { 
+    InitializeMint {
         /// Number of base 10 digits to the right of the decimal place.
         decimals: u8,
         /// The authority/multisignature to mint tokens.
         mint_authority: Pubkey,
         /// The freeze authority/multisignature of the mint.
         freeze_authority: COption<Pubkey>,
-        /// program id asset .
-        mint_id_asset: COption<Pubkey>,
-        /// program id swap.
-        pubkey_swap: COption<Pubkey>
-        },
+    },
     /// Initializes a new account to hold tokens.  If this account is associated
     /// with the native mint then the token balance of the initialized account
     /// will be equal to the amount of SOL in the account. If this account is
@@ -107,6 +138,18 @@ pub enum TokenInstruction {
     ///   1. `[writable]` The destination account.
     ///   2. `[]` The source account's multisignature owner/delegate.
     ///   3. ..3+M `[signer]` M signer accounts.
+    ///
+    /// Tokens of a hedged mint are transferred with the hedge configuration
+    /// of the mint, see `find_hedge_config`, the positions of the source and
+    /// destination accounts, see `find_position`, and the take-profit rule
+    /// of the mint, see `find_take_profit`, following the accounts above,
+    /// see `transfer_with_positions`. The positions and the take-profit rule
+    /// must be passed even where they do not exist. The hedged components
+    /// move with the tokens in proportion to the amount, a source position
+    /// requiring the destination position. The rewards and the swaps out of
+    /// the asset vault of both positions are settled before the transfer, a
+    /// position last settled two or more epochs of the hedge configuration
+    /// ago also requiring its `HedgeEpoch`, see `find_hedge_epoch`.
     Transfer {
         /// The amount of tokens to transfer.
         amount: u64,
@@ -196,6 +239,15 @@ pub enum TokenInstruction {
     ///   1. `[writable]` The account to mint tokens to.
     ///   2. `[]` The mint's multisignature mint-tokens authority.
     ///   3. ..3+M `[signer]` M signer accounts.
+    ///
+    /// The deposit limits of the mint, see `find_deposit_limits`, may follow
+    /// the accounts above, in which case the new supply must not exceed the
+    /// supply cap. They must be passed for a hedged mint, even where the mint
+    /// has none. Tokens of a hedged mint are minted with the hedge
+    /// configuration of the mint, the position of the account and the
+    /// take-profit rule of the mint following them as for `Transfer`, the
+    /// rewards and the swaps out of the asset vault of the position being
+    /// settled first.
     MintTo {
         /// The amount of new tokens to mint.
         amount: u64,
@@ -215,6 +267,13 @@ pub enum TokenInstruction {
     ///   1. `[writable]` The token mint.
     ///   2. `[]` The account's multisignature owner/delegate.
     ///   3. ..3+M `[signer]` M signer accounts.
+    ///
+    /// Tokens of a hedged mint are burned with the hedge configuration of the
+    /// mint, the position of the account and the take-profit rule of the mint
+    /// following the accounts above as for `Transfer`, the rewards and the
+    /// swaps out of the asset vault of the position being settled first. The
+    /// position gives up the hedged components backing the burned tokens in
+    /// proportion to the amount, which are left in the vaults.
     Burn {
         /// The amount of tokens to burn.
         amount: u64,
@@ -234,6 +293,12 @@ pub enum TokenInstruction {
     ///   1. `[writable]` The destination account.
     ///   2. `[]` The account's multisignature owner.
     ///   3. ..3+M `[signer]` M signer accounts.
+    ///
+    /// The position of the account, see `find_position`, may follow the
+    /// accounts above, see `close_account_with_position`, and must do so,
+    /// even where it does not exist, when the hedge configuration of the
+    /// mint, see `find_hedge_config`, is passed. It must not hold hedged
+    /// components or unclaimed rewards and is closed along with the account.
     CloseAccount,
     /// Freeze an Initialized account using the Mint's freeze_authority (if
     /// set).
@@ -290,6 +355,10 @@ pub enum TokenInstruction {
     ///   2. `[writable]` The destination account.
     ///   3. `[]` The source account's multisignature owner/delegate.
     ///   4. ..4+M `[signer]` M signer accounts.
+    ///
+    /// Tokens of a hedged mint are transferred with the hedge configuration,
+    /// the positions and the take-profit rule following the accounts above as
+    /// for `Transfer`, see `transfer_checked_with_positions`.
     TransferChecked {
         /// The amount of tokens to transfer.
         amount: u64,
@@ -342,6 +411,10 @@ pub enum TokenInstruction {
     ///   1. `[writable]` The account to mint tokens to.
     ///   2. `[]` The mint's multisignature mint-tokens authority.
     ///   3. ..3+M `[signer]` M signer accounts.
+    ///
+    /// The deposit limits and, for a hedged mint, the hedge configuration,
+    /// the position and the take-profit rule follow the accounts above as
+    /// for `MintTo`.
     MintToChecked {
         /// The amount of new tokens to mint.
         amount: u64,
@@ -368,6 +441,9 @@ pub enum TokenInstruction {
     ///   1. `[writable]` The token mint.
     ///   2. `[]` The account's multisignature owner/delegate.
     ///   3. ..3+M `[signer]` M signer accounts.
+    ///
+    /// For a hedged mint, the hedge configuration, the position and the
+    /// take-profit rule follow the accounts above as for `Burn`.
     BurnChecked {
         /// The amount of tokens to burn.
         amount: u64,
@@ -391,17 +467,145 @@ pub enum TokenInstruction {
 
     /// 17
     /// Deposit a an amount to hedge token
+    ///
+    /// Moves `amount` of the asset from the source account into the asset
+    /// vault, less the deposit fee, swaps the `target_ratio_bps` share of it
+    /// into the stablecoin vault and credits the net amount to the account,
+    /// recording the asset and stablecoin components in its position.
+    /// The swap is signed by the vault authority of the mint, see
+    /// `find_vault_authority`, and every vault and pool account is checked
+    /// against the mint's `HedgeConfig`. The `protocol_fee_bps` share of the
+    /// deposit fee is paid to the protocol fee account.
+    ///
+    /// Fails if deposits, or swaps when part of the deposit is hedged, are
+    /// paused in the `ProgramConfig`, or if the deposit exceeds one of the
+    /// caps in the `DepositLimits` of the mint. The amount is added to the
+    /// deposit record of the owner of the account. Once the `StopLoss` of the
+    /// mint has triggered, the whole net amount is swapped into the stablecoin
+    /// until the rule is re-armed. The rewards of the position and the swaps
+    /// out of the asset vault since its last deposit, withdrawal or transfer
+    /// are settled before it is credited, and the deposited amount is added
+    /// to its cost basis at the spot price of the pool. A position last
+    /// settled two or more epochs of the hedge configuration ago requires its
+    /// `HedgeEpoch`, see `find_hedge_epoch`, after the accounts below.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[]` The hedge configuration of the mint.
+    ///   2. `[writable]` The mint.
+    ///   3. `[writable]` The account to deposit into.
+    ///   4. `[writable]` The position of the account, see `find_position`.
+    ///   5. `[writable]` The source asset token account.
+    ///   6. `[signer]` The owner or delegate of the source account.
+    ///   7. `[writable]` The asset vault.
+    ///   8. `[writable]` The stablecoin vault.
+    ///   9. `[writable]` The fee account.
+    ///   10. `[writable]` The asset token account of the protocol fee owner.
+    ///   11. `[]` The vault authority of the mint.
+    ///   12. `[]` The swap pool.
+    ///   13. `[]` The swap pool authority.
+    ///   14. `[writable]` The pool reserve receiving the asset.
+    ///   15. `[writable]` The pool reserve sending the stablecoin.
+    ///   16. `[writable]` The pool token mint.
+    ///   17. `[writable]` The pool fee account.
+    ///   18. `[]` The token program used by the pool.
+    ///   19. `[]` The swap program.
+    ///   20. `[]` The deposit limits of the mint, see `find_deposit_limits`.
+    ///   21. `[writable]` The deposit record of the owner of the account, see
+    ///       `find_deposit_record`.
+    ///   22. `[]` The stop-loss rule of the mint, see `find_stop_loss`, which
+    ///       may not exist.
+    ///   23. `[]` The take-profit rule of the mint, see `find_take_profit`,
+    ///       which may not exist.
+    ///   24. `[]` Clock sysvar
+    ///   25. `[writable]` Optional host fee account.
     Deposit {
         /// amount to deposit
        amount: u64,
-       /// volatility
-       volatility: u64,
-        /// nonce used to create valid program address
-        nonce: u8 
+       /// Minimum amount of stablecoin the hedged share must swap into.
+       minimum_amount_out: u64,
     },
 
     // 18
     /// withdraw funds after conversion
+    ///
+    /// Fails if withdrawals are paused in the `ProgramConfig`, in the slot of
+    /// the last deposit into the account, or, when the `ProgramConfig` has a
+    /// withdrawal delay, for more than the amount requested with
+    /// `RequestWithdraw` or before the delay has passed. The admin may bypass
+    /// both checks with `SetWithdrawDelay`. The rewards of the position and
+    /// the swaps out of the asset vault are settled before it is debited, a
+    /// position last settled two or more epochs of the hedge configuration
+    /// ago requiring its `HedgeEpoch`, see `find_hedge_epoch`, after the
+    /// accounts below. The withdrawn asset is
+    /// valued at the spot price of the pool of the mint to realize the profit
+    /// or loss against the cost basis of the position. The burned tokens are
+    /// removed from the supply of the mint and the asset and stablecoin they
+    /// held are paid out of the vaults of the mint, less the
+    /// `withdraw_fee_bps` of the `HedgeConfig` on each. The `protocol_fee_bps`
+    /// share of the withdrawal fee is paid to the protocol fee accounts, the
+    /// rest to the fee account of the mint and the stablecoin account of its
+    /// owner.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single owner
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[writable]` The account to withdraw from.
+    ///   2. `[writable]` The position of the account.
+    ///   3. `[]` The take-profit rule of the mint, see `find_take_profit`,
+    ///      which may not exist.
+    ///   4. `[]` The hedge configuration of the mint.
+    ///   5. `[]` The swap pool.
+    ///   6. `[]` The pool reserve holding the asset.
+    ///   7. `[]` The pool reserve holding the stablecoin.
+    ///   8. `[writable]` The withdraw ticket of the account, see
+    ///      `find_withdraw_ticket`, which may not exist.
+    ///   9. `[writable]` The mint.
+    ///   10. `[writable]` The asset vault of the mint.
+    ///   11. `[writable]` The stablecoin vault of the mint.
+    ///   12. `[writable]` The account receiving the asset.
+    ///   13. `[writable]` The account receiving the stablecoin.
+    ///   14. `[writable]` The fee account.
+    ///   15. `[writable]` The stablecoin token account of the owner of the fee
+    ///      account.
+    ///   16. `[writable]` The asset token account of the protocol fee owner.
+    ///   17. `[writable]` The stablecoin token account of the protocol fee
+    ///      owner.
+    ///   18. `[]` The vault authority, see `find_vault_authority`.
+    ///   19. `[]` The token program of the vaults.
+    ///   20. `[]` Clock sysvar
+    ///   21. `[signer]` The account's owner.
+    ///
+    ///   * Multisignature owner
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[writable]` The account to withdraw from.
+    ///   2. `[writable]` The position of the account.
+    ///   3. `[]` The take-profit rule of the mint, see `find_take_profit`,
+    ///      which may not exist.
+    ///   4. `[]` The hedge configuration of the mint.
+    ///   5. `[]` The swap pool.
+    ///   6. `[]` The pool reserve holding the asset.
+    ///   7. `[]` The pool reserve holding the stablecoin.
+    ///   8. `[writable]` The withdraw ticket of the account, see
+    ///      `find_withdraw_ticket`, which may not exist.
+    ///   9. `[writable]` The mint.
+    ///   10. `[writable]` The asset vault of the mint.
+    ///   11. `[writable]` The stablecoin vault of the mint.
+    ///   12. `[writable]` The account receiving the asset.
+    ///   13. `[writable]` The account receiving the stablecoin.
+    ///   14. `[writable]` The fee account.
+    ///   15. `[writable]` The stablecoin token account of the owner of the fee
+    ///      account.
+    ///   16. `[writable]` The asset token account of the protocol fee owner.
+    ///   17. `[writable]` The stablecoin token account of the protocol fee
+    ///      owner.
+    ///   18. `[]` The vault authority, see `find_vault_authority`.
+    ///   19. `[]` The token program of the vaults.
+    ///   20. `[]` Clock sysvar
+    ///   21. `[]` The account's multisignature owner.
+    ///   22. ..22+M `[signer]` M signer accounts.
     Withdraw {
         /// amount to withdraw
         amount: u64,
@@ -409,8 +613,18 @@ pub enum TokenInstruction {
 
     //19
     /// Initialize Portfolio 
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable, signer]` The portfolio.
+    ///   1. `[writable, signer]` The creator of the portfolio.
+    ///   2. ..20 Nine pairs of `[writable]` asset mint and `[writable]` account
+    ///      the asset is sold into.
+    ///   20. `[]` The owner of the portfolio.
+    ///   21. ..30 `[]` The registry entries of the nine asset mints, see
+    ///       `find_asset_entry`.
     InitializePortfolio {
-        ///the data of the new portfolio
+        ///the data of the new portfolio, zero padded or truncated to `METADATA_URL_LEN` bytes
         metaDataUrl : Vec<u8>,
         ///Hash of dataUrl to insure the immuability of data
         metaDataHash : u16,
@@ -458,33 +672,639 @@ pub enum TokenInstruction {
 
     //20
     /// create Init User Portfolio 
+    ///
+    /// Where the portfolio has `DepositLimits`, the tokens held by the asset
+    /// accounts of the user portfolio are checked against its caps and added
+    /// to its supply and to the deposit record of the owner. The delegated
+    /// amount is only recorded.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The user portfolio.
+    ///   1. `[]` The portfolio.
+    ///   2. `[signer]` The owner of the user portfolio.
+    ///   3. `[]` The delegate.
+    ///   4. ..13 `[]` The nine asset accounts of the user portfolio, recorded
+    ///      in the user portfolio.
+    ///   13. `[writable]` The deposit limits of the portfolio, see
+    ///       `find_deposit_limits`, which need not exist.
+    ///   14. `[writable]` The deposit record of the owner, see
+    ///       `find_deposit_record`, only read if the deposit limits exist.
+    ///   15. `[]` The program configuration, see `find_program_config`.
     createInitUserPortfolio {
         /// amount delegated
         delegated_amount: u64,
-    }
+    },
 
-    
+    // 22
+    /// Configures an initialized mint to hedge deposits of an asset through a
+    /// swap pool. The hedge configuration is created at the address derived
+    /// from the mint, see `find_hedge_config`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The hedge configuration to create.
+    ///   1. `[]` The mint.
+    ///   2. `[writable, signer]` The payer of the hedge configuration.
+    ///   3. `[]` The asset vault, owned by the vault authority of the mint.
+    ///   4. `[]` The stablecoin vault, owned by the vault authority of the mint.
+    ///   5. `[]` The asset token account receiving the fees.
+    ///   6. `[]` The swap pool trading the asset against the stablecoin, which
+    ///      must be the preferred pool of the asset in the registry.
+    ///   7. `[]` The registry entry of the asset, see `find_asset_entry`.
+    ///   8. `[]` The registry entry of the stablecoin.
+    ///   9. `[]` System program
+    ///   10. `[]` Rent sysvar
+    ///   11. `[signer]` The mint authority, or its multisignature account.
+    ///   12. ..12+M `[signer]` M signer accounts.
+    InitializeHedgedMint {
+        /// Share of each deposit swapped into the stablecoin, in basis points.
+        target_ratio_bps: u16,
+        /// Fee charged on deposits, in basis points.
+        deposit_fee_bps: u16,
+        /// Fee charged on withdrawals, in basis points.
+        withdraw_fee_bps: u16,
+    },
+
+    // 23
+    /// Creates the position holding the hedged components of a token
+    /// account at the address derived from it, see `find_position`. Anyone
+    /// may pay for the position of any account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The position to create.
+    ///   1. `[]` The token account.
+    ///   2. `[writable, signer]` The payer of the position.
+    ///   3. `[]` System program
+    ///   4. `[]` Rent sysvar
+    InitializePosition,
+
+    // 24
+    /// Shrinks a mint or token account created with the legacy 154 or 181
+    /// byte layout to the canonical SPL Token layout. A legacy token account
+    /// gets an empty position, which is created if needed; its legacy hedge
+    /// components were never backed by the vaults and are dropped. A legacy
+    /// mint naming a hedged asset or swap pool is refused; others are
+    /// shrunk and may be configured with `InitializeHedgedMint`. Lamports
+    /// freed by the smaller size stay in the account.
+    ///
+    /// Portfolios and user portfolios written without the type and version
+    /// header are grown in place to hold it, the payer topping up rent
+    /// exemption.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Legacy mint
+    ///   0. `[writable]` The mint to migrate.
+    ///
+    ///   * Legacy token account
+    ///   0. `[writable]` The token account to migrate.
+    ///   1. `[writable]` The position of the account.
+    ///   2. `[writable, signer]` The payer of the position.
+    ///   3. `[]` System program
+    ///   4. `[]` Rent sysvar
+    ///
+    ///   * Portfolio or user portfolio
+    ///   0. `[writable]` The portfolio or user portfolio to migrate.
+    ///   1. `[writable, signer]` The payer of the additional rent.
+    ///   2. `[]` System program
+    ///   3. `[]` Rent sysvar
+    MigrateAccount,
+
+    // 25
+    /// Creates the program configuration at the address derived with
+    /// `PROGRAM_CONFIG_SEED`. Only the upgrade authority of the program may
+    /// initialize it, once.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The program configuration, see `find_program_config`.
+    ///   1. `[]` The admin, optionally a multisignature account.
+    ///   2. `[]` The guardian.
+    ///   3. `[]` The owner of the token accounts receiving the protocol fees.
+    ///   4. `[writable, signer]` The payer of the program configuration.
+    ///   5. `[]` The program data account of the program.
+    ///   6. `[signer]` The upgrade authority of the program.
+    ///   7. `[]` System program
+    ///   8. `[]` Rent sysvar
+    InitializeProgramConfig {
+        /// Share of the fees of hedged mints paid to the protocol, in basis points.
+        protocol_fee_bps: u16,
+    },
+
+    // 26
+    /// Replaces the admin, guardian and protocol fee settings of the program
+    /// configuration.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single admin
+    ///   0. `[writable]` The program configuration.
+    ///   1. `[]` The new admin.
+    ///   2. `[]` The new guardian.
+    ///   3. `[]` The new owner of the token accounts receiving the protocol fees.
+    ///   4. `[signer]` The current admin.
+    ///
+    ///   * Multisignature admin
+    ///   0. `[writable]` The program configuration.
+    ///   1. `[]` The new admin.
+    ///   2. `[]` The new guardian.
+    ///   3. `[]` The new owner of the token accounts receiving the protocol fees.
+    ///   4. `[]` The current multisignature admin.
+    ///   5. ..5+M `[signer]` M signer accounts.
+    SetProgramConfig {
+        /// Share of the fees of hedged mints paid to the protocol, in basis points.
+        protocol_fee_bps: u16,
+    },
+
+    // 27
+    /// Sets the paused operations of the program. The admin may set any
+    /// flags; the guardian may only add flags to the ones already set.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single authority
+    ///   0. `[writable]` The program configuration.
+    ///   1. `[signer]` The admin or the guardian.
+    ///
+    ///   * Multisignature admin
+    ///   0. `[writable]` The program configuration.
+    ///   1. `[]` The multisignature admin.
+    ///   2. ..2+M `[signer]` M signer accounts.
+    SetPause {
+        /// The paused operations, a combination of the `PAUSE_*` flags.
+        paused: u32,
+    },
+
+    // 28
+    /// Approves a mint in the asset registry, creating its entry at the
+    /// address derived from the mint. The decimals are read from the mint,
+    /// which must be traded by the preferred swap pool.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The program configuration.
+    ///   1. `[writable]` The registry entry to create, see `find_asset_entry`.
+    ///   2. `[]` The mint to approve.
+    ///   3. `[]` The price oracle of the mint.
+    ///   4. `[]` The preferred swap pool trading the mint.
+    ///   5. `[writable, signer]` The payer of the registry entry.
+    ///   6. `[]` System program
+    ///   7. `[]` Rent sysvar
+    ///   8. `[signer]` The admin, or its multisignature account.
+    ///   9. ..9+M `[signer]` M signer accounts.
+    RegisterAsset {
+        /// The role of the mint.
+        kind: AssetKind,
+    },
+
+    // 29
+    /// Replaces the role, oracle and preferred swap pool of a registry entry.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The program configuration.
+    ///   1. `[writable]` The registry entry.
+    ///   2. `[]` The price oracle of the mint.
+    ///   3. `[]` The preferred swap pool trading the mint.
+    ///   4. `[signer]` The admin, or its multisignature account.
+    ///   5. ..5+M `[signer]` M signer accounts.
+    UpdateAsset {
+        /// The role of the mint.
+        kind: AssetKind,
+    },
+
+    // 30
+    /// Removes a mint from the asset registry, closing its entry. Hedged
+    /// mints and portfolios already referencing it are not affected.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The program configuration.
+    ///   1. `[writable]` The registry entry to close.
+    ///   2. `[writable]` The destination of the entry's lamports.
+    ///   3. `[signer]` The admin, or its multisignature account.
+    ///   4. ..4+M `[signer]` M signer accounts.
+    DeregisterAsset,
+
+    // 31
+    /// Sets the deposit caps of a hedged mint or a portfolio, creating its
+    /// `DepositLimits` at the address derived from it when needed. A cap of
+    /// zero is unlimited.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The deposit limits, see `find_deposit_limits`.
+    ///   1. `[]` The mint or portfolio.
+    ///   2. `[writable, signer]` The payer of the deposit limits.
+    ///   3. `[]` System program
+    ///   4. `[]` Rent sysvar
+    ///   5. `[signer]` The mint authority of the mint or the creator of the
+    ///      portfolio, or its multisignature account.
+    ///   6. ..6+M `[signer]` M signer accounts.
+    SetDepositLimits {
+        /// Cap on the supply of the mint, or on the total delegated to the
+        /// portfolio.
+        max_supply: u64,
+        /// Cap on the amount of a single deposit.
+        max_deposit: u64,
+        /// Cap on the cumulative amount deposited by a single owner.
+        max_owner_deposit: u64,
+    },
+
+    // 32
+    /// Creates the deposit record of an owner of a hedged mint or a
+    /// portfolio at the address derived from both, see `find_deposit_record`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The deposit record to create.
+    ///   1. `[]` The mint or portfolio.
+    ///   2. `[]` The owner.
+    ///   3. `[writable, signer]` The payer of the deposit record.
+    ///   4. `[]` System program
+    ///   5. `[]` Rent sysvar
+    InitializeDepositRecord,
+
+    // 33
+    /// Sets the stop-loss rule of a hedged mint, creating its `StopLoss` at
+    /// the address derived from the mint when needed. Setting the rule again
+    /// re-arms it after it triggered, keeping the record of the last trigger.
+    /// The oracle and decimals are taken from the registry entries of the
+    /// asset and the stablecoin. Once a trigger closed an epoch of the hedge
+    /// configuration, the `HedgeEpoch` of the closed epoch is created unless
+    /// it exists.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The stop-loss rule, see `find_stop_loss`.
+    ///   1. `[]` The hedge configuration of the mint.
+    ///   2. `[]` The mint.
+    ///   3. `[]` The registry entry of the asset, see `find_asset_entry`.
+    ///   4. `[]` The registry entry of the stablecoin.
+    ///   5. `[writable, signer]` The payer of the stop-loss rule.
+    ///   6. `[]` System program
+    ///   7. `[]` Rent sysvar
+    ///   8. `[writable]` The `HedgeEpoch` of the epoch before the current
+    ///      epoch of the hedge configuration, see `find_hedge_epoch`, ignored
+    ///      while no epoch is closed.
+    ///   9. `[signer]` The authority of the hedge configuration, or its
+    ///      multisignature account.
+    ///   10. ..10+M `[signer]` M signer accounts.
+    SetStopLoss {
+        /// Price at or below which the rule triggers, in the units of the
+        /// oracle.
+        stop_price: u64,
+        /// Distance of the trailing stop below the highest observed price, in
+        /// basis points. Zero disables the trailing stop.
+        trailing_bps: u16,
+        /// Slippage tolerated on the oracle price when the rule triggers, in
+        /// basis points.
+        max_slippage_bps: u16,
+    },
+
+    // 34
+    /// Checks the stop-loss rule of a hedged mint against its oracle and,
+    /// once the price is at or below the stop, swaps the whole asset vault
+    /// into the stablecoin vault and records the trigger. Anyone may send
+    /// this instruction; when the rule is not met it only tracks the highest
+    /// price for the trailing stop.
+    ///
+    /// The swap closes the current epoch of the hedge configuration: the
+    /// positions of the mint have their asset component moved to the
+    /// stablecoin component, at the rate `stable_bought / asset_sold`
+    /// recorded in the `StopLoss`, when next settled.
+    ///
+    /// Fails if the rule already triggered, if the oracle price is stale, or
+    /// if swaps are paused in the `ProgramConfig` when the rule is met.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[writable]` The stop-loss rule, see `find_stop_loss`.
+    ///   2. `[writable]` The hedge configuration of the mint.
+    ///   3. `[]` The mint.
+    ///   4. `[]` The price oracle of the asset.
+    ///   5. `[]` Clock sysvar
+    ///   6. `[writable]` The asset vault.
+    ///   7. `[writable]` The stablecoin vault.
+    ///   8. `[]` The vault authority of the mint.
+    ///   9. `[]` The swap pool.
+    ///   10. `[]` The swap pool authority.
+    ///   11. `[writable]` The pool reserve receiving the asset.
+    ///   12. `[writable]` The pool reserve sending the stablecoin.
+    ///   13. `[writable]` The pool token mint.
+    ///   14. `[writable]` The pool fee account.
+    ///   15. `[]` The token program used by the pool.
+    ///   16. `[]` The swap program.
+    ///   17. `[writable]` Optional host fee account.
+    TriggerConditions {
+        /// Minimum amount of stablecoin the asset vault must swap into, on top
+        /// of the slippage bound derived from the oracle price.
+        minimum_amount_out: u64,
+    },
+
+    // 35
+    /// Sets the take-profit rule of a hedged mint, creating its `TakeProfit`
+    /// at the address derived from the mint when needed. The entry price is
+    /// set to the current oracle price; rewards already distributed are kept.
+    /// The oracle and decimals are taken from the registry entries of the
+    /// asset and the stablecoin.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The take-profit rule, see `find_take_profit`.
+    ///   1. `[]` The hedge configuration of the mint.
+    ///   2. `[]` The mint.
+    ///   3. `[]` The registry entry of the asset, see `find_asset_entry`.
+    ///   4. `[]` The registry entry of the stablecoin.
+    ///   5. `[]` The price oracle of the asset.
+    ///   6. `[]` Clock sysvar
+    ///   7. `[writable, signer]` The payer of the take-profit rule.
+    ///   8. `[]` System program
+    ///   9. `[]` Rent sysvar
+    ///   10. `[signer]` The authority of the hedge configuration, or its
+    ///       multisignature account.
+    ///   11. ..11+M `[signer]` M signer accounts.
+    SetTakeProfit {
+        /// Gain of the oracle price over the entry price that triggers a
+        /// harvest, in basis points.
+        trigger_bps: u16,
+        /// Share of the asset vault sold by a harvest, in basis points, below
+        /// the whole vault. Zero disables the rule.
+        sell_bps: u16,
+        /// Slippage tolerated on the oracle price when harvesting, in basis
+        /// points.
+        max_slippage_bps: u16,
+        /// Whether the profit is distributed to the holders of the mint
+        /// rather than retained in the vaults.
+        distribute: bool,
+    },
+
+    // 36
+    /// Harvests the profit of a hedged mint once the oracle price exceeds the
+    /// entry price of its take-profit rule by `trigger_bps`: the `sell_bps`
+    /// share of the asset vault is swapped into the stablecoin vault and the
+    /// entry price reset to the oracle price. Anyone may send this
+    /// instruction.
+    ///
+    /// The profit is the stablecoin received over the value of the asset sold
+    /// at the entry price. If the rule distributes it, it is reserved for the
+    /// holders in proportion to their balance, see `ClaimRewards`. The rest
+    /// of the stablecoin received is credited to the positions of the mint in
+    /// proportion to their asset component, which shrinks by the share sold,
+    /// when next settled.
+    ///
+    /// Fails if the price is below the target, if the oracle price is stale,
+    /// or if swaps are paused in the `ProgramConfig`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[writable]` The take-profit rule, see `find_take_profit`.
+    ///   2. `[writable]` The hedge configuration of the mint.
+    ///   3. `[]` The mint.
+    ///   4. `[]` The price oracle of the asset.
+    ///   5. `[]` Clock sysvar
+    ///   6. `[writable]` The asset vault.
+    ///   7. `[writable]` The stablecoin vault.
+    ///   8. `[]` The vault authority of the mint.
+    ///   9. `[]` The swap pool.
+    ///   10. `[]` The swap pool authority.
+    ///   11. `[writable]` The pool reserve receiving the asset.
+    ///   12. `[writable]` The pool reserve sending the stablecoin.
+    ///   13. `[writable]` The pool token mint.
+    ///   14. `[writable]` The pool fee account.
+    ///   15. `[]` The token program used by the pool.
+    ///   16. `[]` The swap program.
+    ///   17. `[writable]` Optional host fee account.
+    HarvestProfit {
+        /// Minimum amount of stablecoin the sold asset must swap into, on top
+        /// of the slippage bound derived from the oracle price.
+        minimum_amount_out: u64,
+    },
+
+    // 37
+    /// Settles the rewards of a hedged token account and pays them out of the
+    /// stablecoin vault.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single owner
+    ///   0. `[writable]` The take-profit rule, see `find_take_profit`.
+    ///   1. `[]` The hedge configuration of the mint.
+    ///   2. `[]` The mint.
+    ///   3. `[]` The token account the rewards accrued to.
+    ///   4. `[writable]` The position of the account.
+    ///   5. `[writable]` The stablecoin vault.
+    ///   6. `[writable]` The stablecoin account receiving the rewards.
+    ///   7. `[]` The vault authority of the mint.
+    ///   8. `[]` The token program of the stablecoin.
+    ///   9. `[signer]` The account's owner.
+    ///
+    ///   * Multisignature owner
+    ///   0. ..8 As above.
+    ///   9. `[]` The account's multisignature owner.
+    ///   10. ..10+M `[signer]` M signer accounts.
+    ClaimRewards,
+
+    // 38
+    /// Sets up dollar-cost averaging into an asset of a portfolio, creating
+    /// its `DcaConfig` at the address derived from the portfolio and the asset
+    /// index. Deposits of the asset the portfolio sells into it are bought
+    /// into the asset in `tranches` equal tranches, one every period of the
+    /// asset, counted in `DCA_PERIOD_SLOTS`.
+    ///
+    /// The vaults must be owned by the vault authority derived from the
+    /// `DcaConfig`, see `find_vault_authority`, and the pool must be the one
+    /// registered for the asset. The oracle registered for the asset prices
+    /// the tranches.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` The dollar-cost-averaging configuration, see
+    ///      `find_dca_config`.
+    ///   1. `[]` The portfolio.
+    ///   2. `[writable, signer]` The payer of the configuration.
+    ///   3. `[]` The asset vault.
+    ///   4. `[]` The stablecoin vault.
+    ///   5. `[]` The swap pool.
+    ///   6. `[]` The registry entry of the asset, see `find_asset_entry`.
+    ///   7. `[]` The registry entry of the stablecoin.
+    ///   8. `[]` System program
+    ///   9. `[]` Rent sysvar
+    ///   10. `[signer]` The creator of the portfolio, or its multisignature
+    ///       account.
+    ///   11. ..11+M `[signer]` M signer accounts.
+    InitializeDca {
+        /// Index of the asset in the portfolio, from 1.
+        asset_index: u8,
+        /// Number of tranches a deposit is split into.
+        tranches: u16,
+        /// Slippage tolerated on the oracle price when buying a tranche, in
+        /// basis points.
+        max_slippage_bps: u16,
+    },
+
+    // 39
+    /// Deposits stablecoin into a dollar-cost-averaging schedule, creating the
+    /// `DcaPosition` of the owner when needed. The stablecoin not yet sold is
+    /// split again into equal tranches.
+    ///
+    /// Fails if deposits are paused in the `ProgramConfig`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[writable]` The dollar-cost-averaging configuration.
+    ///   2. `[writable]` The position of the owner, see `find_dca_position`.
+    ///   3. `[writable]` The stablecoin account to deposit from.
+    ///   4. `[writable]` The stablecoin vault.
+    ///   5. `[]` The token program of the stablecoin.
+    ///   6. `[writable, signer]` The payer of the position.
+    ///   7. `[]` System program
+    ///   8. `[]` Rent sysvar
+    ///   9. `[signer]` The owner, also the authority of the source account.
+    DepositDca {
+        /// The amount of stablecoin to deposit.
+        amount: u64,
+    },
+
+    // 40
+    /// Buys the next tranche of a dollar-cost-averaging position, swapping
+    /// it from the stablecoin vault into the asset vault. Anyone may send this
+    /// instruction once the period since the last tranche has elapsed.
+    ///
+    /// Fails if swaps are paused in the `ProgramConfig`, if the oracle price
+    /// is stale, or if the tranche swaps into less asset than its value at
+    /// the oracle price less the slippage of the `DcaConfig`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[writable]` The dollar-cost-averaging configuration.
+    ///   2. `[writable]` The position.
+    ///   3. `[]` The price oracle of the asset.
+    ///   4. `[]` Clock sysvar
+    ///   5. `[writable]` The stablecoin vault.
+    ///   6. `[writable]` The asset vault.
+    ///   7. `[]` The vault authority of the configuration.
+    ///   8. `[]` The swap pool.
+    ///   9. `[]` The swap pool authority.
+    ///   10. `[writable]` The pool reserve receiving the stablecoin.
+    ///   11. `[writable]` The pool reserve sending the asset.
+    ///   12. `[writable]` The pool token mint.
+    ///   13. `[writable]` The pool fee account.
+    ///   14. `[]` The token program used by the pool.
+    ///   15. `[]` The swap program.
+    ///   16. `[writable]` Optional host fee account.
+    ExecuteDca {
+        /// Minimum amount of asset the tranche must swap into, on top of the
+        /// slippage bound derived from the oracle price.
+        minimum_amount_out: u64,
+    },
+
+    // 41
+    /// Withdraws stablecoin not yet sold and asset already bought from a
+    /// dollar-cost-averaging position. The tranche amount is kept, so
+    /// withdrawing stablecoin shortens the schedule.
+    ///
+    /// Fails if withdrawals are paused in the `ProgramConfig`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[writable]` The dollar-cost-averaging configuration.
+    ///   2. `[writable]` The position of the owner.
+    ///   3. `[writable]` The stablecoin vault.
+    ///   4. `[writable]` The asset vault.
+    ///   5. `[writable]` The stablecoin account receiving the withdrawal.
+    ///   6. `[writable]` The asset account receiving the withdrawal.
+    ///   7. `[]` The vault authority of the configuration.
+    ///   8. `[]` The token program of the vaults.
+    ///   9. `[signer]` The owner of the position.
+    WithdrawDca {
+        /// The amount of stablecoin to withdraw.
+        usdc: u64,
+        /// The amount of asset to withdraw.
+        asset: u64,
+    },
+
+    // 42
+    /// Requests the withdrawal of `amount` from a token account, claimable
+    /// with `Withdraw` once the withdrawal delay of the `ProgramConfig` has
+    /// passed. A new request replaces the pending one. The payer may be any
+    /// account.
+    ///
+    /// Fails if withdrawals are paused in the `ProgramConfig`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single owner
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[]` The token account.
+    ///   2. `[writable]` The withdraw ticket of the account, see
+    ///      `find_withdraw_ticket`.
+    ///   3. `[]` Clock sysvar
+    ///   4. `[writable, signer]` The payer of the ticket.
+    ///   5. `[]` System program
+    ///   6. `[]` Rent sysvar
+    ///   7. `[signer]` The account's owner.
+    ///
+    ///   * Multisignature owner
+    ///   0. `[]` The program configuration, see `find_program_config`.
+    ///   1. `[]` The token account.
+    ///   2. `[writable]` The withdraw ticket of the account, see
+    ///      `find_withdraw_ticket`.
+    ///   3. `[]` Clock sysvar
+    ///   4. `[writable, signer]` The payer of the ticket.
+    ///   5. `[]` System program
+    ///   6. `[]` Rent sysvar
+    ///   7. `[]` The account's multisignature owner.
+    ///   8. ..8+M `[signer]` M signer accounts.
+    RequestWithdraw {
+        /// The amount to withdraw.
+        amount: u64,
+    },
+
+    // 43
+    /// Sets the withdrawal delay of the program configuration, and whether
+    /// withdrawals bypass it and the deposit slot check for an emergency
+    /// unwind.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   * Single admin
+    ///   0. `[writable]` The program configuration.
+    ///   1. `[signer]` The admin.
+    ///
+    ///   * Multisignature admin
+    ///   0. `[writable]` The program configuration.
+    ///   1. `[]` The multisignature admin.
+    ///   2. ..2+M `[signer]` M signer accounts.
+    SetWithdrawDelay {
+        /// Slots between a withdrawal request and its claim.
+        delay_slots: u64,
+        /// Whether withdrawals skip the delay and the deposit slot check.
+        bypass: bool,
+    },
 }
 impl TokenInstruction {
     /// Unpacks a byte buffer into a [TokenInstruction](enum.TokenInstruction.html).
     pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
         use TokenError::InvalidInstruction;
-        
+
         let (&tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
-        msg!("1 ,{}",&tag);
         Ok(match tag {
             0 => {
                 let (&decimals, rest) = rest.split_first().ok_or(InvalidInstruction)?;
                 let (mint_authority, rest) = Self::unpack_pubkey(rest)?;
                 let (freeze_authority, _rest) = Self::unpack_pubkey_option(rest)?;
-                let (mint_id_asset, _rest2) = Self::unpack_pubkey_option(_rest)?;
-                let (pubkey_swap, _rest3) = Self::unpack_pubkey_option(_rest2)?;
                 Self::InitializeMint {
                     decimals,
                     mint_authority,
                     freeze_authority,
-                    mint_id_asset,
-                    pubkey_swap
                 }
             }
             1 => Self::InitializeAccount,
@@ -493,11 +1313,7 @@ impl TokenInstruction {
                 Self::InitializeMultisig { m }
             }
             3 | 4 | 21 |7 | 8 | 18 => {
-                let amount = rest
-                    .get(..8)
-                    .and_then(|slice| slice.try_into().ok())
-                    .map(u64::from_le_bytes)
-                    .ok_or(InvalidInstruction)?;
+                let (amount, _rest) = Self::unpack_u64(rest)?;
                 match tag {
                     3 => Self::Transfer { amount },
                     4 => Self::Approve { amount },
@@ -509,23 +1325,9 @@ impl TokenInstruction {
                 }
             }
             17 => {
-                  
-                let (amount, rest) = rest.split_at(8);
-               
-                let amount = amount
-                    .try_into()
-                    .ok()
-                    .map(u64::from_le_bytes)
-                    .ok_or(InvalidInstruction)?;
-                let (volatility, rest) = rest.split_at(8);
-                let volatility = volatility.try_into()
-                    .ok()
-                    .map(u64::from_le_bytes)
-                    .ok_or(InvalidInstruction)?;
-                
-                    let (&nonce, _rest) = rest.split_first().ok_or(InvalidInstruction)?;
-
-                Self::Deposit { amount, volatility, nonce }
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::Deposit { amount, minimum_amount_out }
             }
             5 => Self::Revoke,
             6 => {
@@ -544,47 +1346,19 @@ impl TokenInstruction {
             10 => Self::FreezeAccount,
             11 => Self::ThawAccount,
             12 => {
-                let (amount, rest) = rest.split_at(8);
-                let amount = amount
-                    .try_into()
-                    .ok()
-                    .map(u64::from_le_bytes)
-                    .ok_or(InvalidInstruction)?;
-                let (&decimals, _rest) = rest.split_first().ok_or(InvalidInstruction)?;
-
+                let (amount, decimals, _rest) = Self::unpack_amount_decimals(rest)?;
                 Self::TransferChecked { amount, decimals }
             }
             13 => {
-                let (amount, rest) = rest.split_at(8);
-                let amount = amount
-                    .try_into()
-                    .ok()
-                    .map(u64::from_le_bytes)
-                    .ok_or(InvalidInstruction)?;
-                let (&decimals, _rest) = rest.split_first().ok_or(InvalidInstruction)?;
-
+                let (amount, decimals, _rest) = Self::unpack_amount_decimals(rest)?;
                 Self::ApproveChecked { amount, decimals }
             }
             14 => {
-                let (amount, rest) = rest.split_at(8);
-                let amount = amount
-                    .try_into()
-                    .ok()
-                    .map(u64::from_le_bytes)
-                    .ok_or(InvalidInstruction)?;
-                let (&decimals, _rest) = rest.split_first().ok_or(InvalidInstruction)?;
-
+                let (amount, decimals, _rest) = Self::unpack_amount_decimals(rest)?;
                 Self::MintToChecked { amount, decimals }
             }
             15 => {
-                let (amount, rest) = rest.split_at(8);
-                let amount = amount
-                    .try_into()
-                    .ok()
-                    .map(u64::from_le_bytes)
-                    .ok_or(InvalidInstruction)?;
-                let (&decimals, _rest) = rest.split_first().ok_or(InvalidInstruction)?;
-
+                let (amount, decimals, _rest) = Self::unpack_amount_decimals(rest)?;
                 Self::BurnChecked { amount, decimals }
             }
             16 => {
@@ -593,192 +1367,163 @@ impl TokenInstruction {
             }
 
             19 => {
-                msg!("initial lecture {:?}",rest);
-                let (metaDataUrl, rest) = rest.split_at(128);
-                msg!("second error1 {:?}",rest);
-                let metaDataUrl = metaDataUrl
-                .try_into()
-                .ok()
-                .ok_or(InvalidInstruction)?;
-                
-                let (metaDataHash, rest) = rest.split_at(2);
-                msg!("second error2 metadataHash {:?}", metaDataHash);
-                msg!("second error2 rest {:?}", rest);
-                let metaDataHash = metaDataHash
-                .try_into()
-                .ok()
-                .map(u16::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                
-                let (amountAsset1, _rest) = rest.split_at(1);
-                msg!("second error3 amountAsset1 {:?}", amountAsset1);
-                msg!("second error3 rest {:?}", _rest);
-                let amountAsset1 = amountAsset1
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                
-                let (periodAsset1, _rest2) = _rest.split_at(1);
-                msg!("second error4 periodAsset1 {:?}", periodAsset1);
-                msg!("second error4 rest {:?}", _rest2);
-                let periodAsset1 = periodAsset1
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (amountAsset2, _rest3) = _rest2.split_at(1);
-                msg!("second error5 amountAsset2 {:?}", amountAsset2);
-                msg!("second error5 rest {:?}", _rest3);
-                let amountAsset2 = amountAsset2
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (periodAsset2, _rest4) = _rest3.split_at(1);
-                msg!("second error6");
-                let periodAsset2 = periodAsset2
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (amountAsset3, _rest5) = _rest4.split_at(1);
-                msg!("second error7");
-                let amountAsset3 = amountAsset3
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (periodAsset3, _rest6) = _rest5.split_at(1);
-                let periodAsset3 = periodAsset3
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (amountAsset4, _rest7) = _rest6.split_at(1);
-                let amountAsset4 = amountAsset4
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (periodAsset4, _rest8) = _rest7.split_at(1);
-                let periodAsset4 = periodAsset4
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (amountAsset5, _rest9) = _rest8.split_at(1);
-                let amountAsset5 = amountAsset5
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (periodAsset5, _rest10) = _rest9.split_at(1);
-                let periodAsset5 = periodAsset5
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (amountAsset6, _rest11) = _rest10.split_at(1);
-                let amountAsset6 = amountAsset6
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (periodAsset6, _rest12) = _rest11.split_at(1);
-                let periodAsset6 = periodAsset6
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (amountAsset7, _rest13) = _rest12.split_at(1);
-                let amountAsset7 = amountAsset7
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (periodAsset7, _rest14) = _rest13.split_at(1);
-                let periodAsset7 = periodAsset7
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (amountAsset8, _rest15) = _rest14.split_at(1);
-                let amountAsset8 = amountAsset8
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (periodAsset8, _rest16) = _rest15.split_at(1);
-                let periodAsset8 = periodAsset8
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (amountAsset9, _rest17) = _rest16.split_at(1);
-                msg!("second error777 {:?}", amountAsset9);
-                msg!("second _rest17 {:?}", _rest17);
-                let amountAsset9 = amountAsset9
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                let (periodAsset9, _rest18) = _rest17.split_at(1);
-                msg!("second error888 {:?}", periodAsset9);
-                msg!("second _rest18 {:?}", _rest18);
-                let periodAsset9 = periodAsset9
-                .try_into()
-                .ok()
-                .map(u8::from_le_bytes)
-                .ok_or(InvalidInstruction)?;
-                // let (amountAsset10, _rest19) = _rest18.split_at(1);
-                // let amountAsset10 = amountAsset10
-                // .try_into()
-                // .ok()
-                // .map(u8::from_le_bytes)
-                // .ok_or(InvalidInstruction)?;
-                // let (periodAsset10, _rest20) = _rest19.split_at(32);
-                // let periodAsset10 = periodAsset10
-                // .try_into()
-                // .ok()
-                // .map(u64::from_le_bytes)
-                // .ok_or(InvalidInstruction)?;
+                let url = rest.get(..METADATA_URL_LEN).ok_or(InvalidInstruction)?;
+                let (hash, rest) = Self::unpack_u16(&rest[METADATA_URL_LEN..])?;
+                let assets = rest.get(..18).ok_or(InvalidInstruction)?;
                 Self::InitializePortfolio {
-                    metaDataUrl,
-                    metaDataHash,
-                    amountAsset1,
-                    periodAsset1,
-                    amountAsset2,
-                    periodAsset2,
-                    amountAsset3,
-                    periodAsset3,
-                    amountAsset4,
-                    periodAsset4,
-                    amountAsset5,
-                    periodAsset5,
-                    amountAsset6,
-                    periodAsset6,
-                    amountAsset7,
-                    periodAsset7,
-                    amountAsset8,
-                    periodAsset8,
-                    amountAsset9,
-                    periodAsset9,
+                    metaDataUrl: url.to_vec(),
+                    metaDataHash: hash,
+                    amountAsset1: assets[0],
+                    periodAsset1: assets[1],
+                    amountAsset2: assets[2],
+                    periodAsset2: assets[3],
+                    amountAsset3: assets[4],
+                    periodAsset3: assets[5],
+                    amountAsset4: assets[6],
+                    periodAsset4: assets[7],
+                    amountAsset5: assets[8],
+                    periodAsset5: assets[9],
+                    amountAsset6: assets[10],
+                    periodAsset6: assets[11],
+                    amountAsset7: assets[12],
+                    periodAsset7: assets[13],
+                    amountAsset8: assets[14],
+                    periodAsset8: assets[15],
+                    amountAsset9: assets[16],
+                    periodAsset9: assets[17],
                     // amountAsset10,
                     // periodAsset10,
                 }
             }
             20 => {
-                let (delegated_amount, _rest) = rest.split_at(8);
-                msg!("delegated_amount : {:?}" , delegated_amount);
-                let delegated_amount = delegated_amount
-                    .try_into()
-                    .ok()
-                    .map(u64::from_le_bytes)
-                    .ok_or(InvalidInstruction)?;
-              
+                let (delegated_amount, _rest) = Self::unpack_u64(rest)?;
                 Self::createInitUserPortfolio { delegated_amount}
             }
+            22 => {
+                let (target_ratio_bps, rest) = Self::unpack_u16(rest)?;
+                let (deposit_fee_bps, rest) = Self::unpack_u16(rest)?;
+                let (withdraw_fee_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::InitializeHedgedMint {
+                    target_ratio_bps,
+                    deposit_fee_bps,
+                    withdraw_fee_bps,
+                }
+            }
+            23 => Self::InitializePosition,
+            24 => Self::MigrateAccount,
+            25 => {
+                let (protocol_fee_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::InitializeProgramConfig { protocol_fee_bps }
+            }
+            26 => {
+                let (protocol_fee_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::SetProgramConfig { protocol_fee_bps }
+            }
+            27 => {
+                let paused = rest
+                    .get(..4)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(u32::from_le_bytes)
+                    .ok_or(InvalidInstruction)?;
+                Self::SetPause { paused }
+            }
+            28 | 29 => {
+                let kind = rest
+                    .first()
+                    .and_then(|&kind| AssetKind::try_from_primitive(kind).ok())
+                    .ok_or(InvalidInstruction)?;
+                if tag == 28 {
+                    Self::RegisterAsset { kind }
+                } else {
+                    Self::UpdateAsset { kind }
+                }
+            }
+            30 => Self::DeregisterAsset,
+            31 => {
+                let (max_supply, rest) = Self::unpack_u64(rest)?;
+                let (max_deposit, rest) = Self::unpack_u64(rest)?;
+                let (max_owner_deposit, _rest) = Self::unpack_u64(rest)?;
+                Self::SetDepositLimits {
+                    max_supply,
+                    max_deposit,
+                    max_owner_deposit,
+                }
+            }
+            32 => Self::InitializeDepositRecord,
+            33 => {
+                let (stop_price, rest) = Self::unpack_u64(rest)?;
+                let (trailing_bps, rest) = Self::unpack_u16(rest)?;
+                let (max_slippage_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::SetStopLoss {
+                    stop_price,
+                    trailing_bps,
+                    max_slippage_bps,
+                }
+            }
+            34 => {
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::TriggerConditions { minimum_amount_out }
+            }
+            35 => {
+                let (trigger_bps, rest) = Self::unpack_u16(rest)?;
+                let (sell_bps, rest) = Self::unpack_u16(rest)?;
+                let (max_slippage_bps, rest) = Self::unpack_u16(rest)?;
+                let distribute = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetTakeProfit {
+                    trigger_bps,
+                    sell_bps,
+                    max_slippage_bps,
+                    distribute,
+                }
+            }
+            36 => {
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::HarvestProfit { minimum_amount_out }
+            }
+            37 => Self::ClaimRewards,
+            38 => {
+                let (&asset_index, rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let (tranches, rest) = Self::unpack_u16(rest)?;
+                let (max_slippage_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::InitializeDca {
+                    asset_index,
+                    tranches,
+                    max_slippage_bps,
+                }
+            }
+            39 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositDca { amount }
+            }
+            40 => {
+                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
+                Self::ExecuteDca { minimum_amount_out }
+            }
+            41 => {
+                let (usdc, rest) = Self::unpack_u64(rest)?;
+                let (asset, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawDca { usdc, asset }
+            }
+            42 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::RequestWithdraw { amount }
+            }
+            43 => {
+                let (delay_slots, rest) = Self::unpack_u64(rest)?;
+                let bypass = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(InvalidInstruction.into()),
+                };
+                Self::SetWithdrawDelay {
+                    delay_slots,
+                    bypass,
+                }
+            }
 
 
             _ => return Err(TokenError::InvalidInstruction.into()),
@@ -793,15 +1538,11 @@ impl TokenInstruction {
                 ref mint_authority,
                 ref freeze_authority,
                 decimals,
-                ref mint_id_asset,
-                ref pubkey_swap
             } => {
                 buf.push(0);
                 buf.push(decimals);
                 buf.extend_from_slice(mint_authority.as_ref());
                 Self::pack_pubkey_option(freeze_authority, &mut buf);
-                Self::pack_pubkey_option(mint_id_asset, &mut buf);
-                Self::pack_pubkey_option(pubkey_swap, &mut buf);
             }
             Self::InitializeAccount => buf.push(1),
             &Self::InitializeMultisig { m } => {
@@ -817,7 +1558,7 @@ impl TokenInstruction {
                 buf.extend_from_slice(&amount.to_le_bytes());
             }
             &Self::ApproveUserPortfolio { amount } => {
-                buf.push(4);
+                buf.push(21);
                 buf.extend_from_slice(&amount.to_le_bytes());
             }
             &Self::MintTo { amount } => {
@@ -864,11 +1605,10 @@ impl TokenInstruction {
                 buf.push(16);
                 buf.extend_from_slice(owner.as_ref());
             }
-            &Self::Deposit {amount , volatility, nonce} => {
+            &Self::Deposit {amount , minimum_amount_out} => {
                 buf.push(17);
                 buf.extend_from_slice(&amount.to_le_bytes());
-                buf.extend_from_slice(&volatility.to_le_bytes());
-                buf.push(nonce);
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
             },
             
             &Self::Withdraw {amount } => {
@@ -901,7 +1641,10 @@ impl TokenInstruction {
                 // periodAsset10,
             } => {
                 buf.push(19);
-                buf.extend_from_slice(&metaDataUrl);
+                let mut url = [0; METADATA_URL_LEN];
+                let url_len = metaDataUrl.len().min(METADATA_URL_LEN);
+                url[..url_len].copy_from_slice(&metaDataUrl[..url_len]);
+                buf.extend_from_slice(&url);
                 buf.extend_from_slice(&metaDataHash.to_le_bytes());
                 buf.extend_from_slice(&amountAsset1.to_le_bytes());
                 buf.extend_from_slice(&periodAsset1.to_le_bytes());
@@ -929,11 +1672,145 @@ impl TokenInstruction {
                 buf.push(20);
                 buf.extend_from_slice(&delegated_amount.to_le_bytes());
             },
+            &Self::InitializeHedgedMint {
+                target_ratio_bps,
+                deposit_fee_bps,
+                withdraw_fee_bps,
+            } => {
+                buf.push(22);
+                buf.extend_from_slice(&target_ratio_bps.to_le_bytes());
+                buf.extend_from_slice(&deposit_fee_bps.to_le_bytes());
+                buf.extend_from_slice(&withdraw_fee_bps.to_le_bytes());
+            }
+            Self::InitializePosition => buf.push(23),
+            Self::MigrateAccount => buf.push(24),
+            &Self::InitializeProgramConfig { protocol_fee_bps } => {
+                buf.push(25);
+                buf.extend_from_slice(&protocol_fee_bps.to_le_bytes());
+            }
+            &Self::SetProgramConfig { protocol_fee_bps } => {
+                buf.push(26);
+                buf.extend_from_slice(&protocol_fee_bps.to_le_bytes());
+            }
+            &Self::SetPause { paused } => {
+                buf.push(27);
+                buf.extend_from_slice(&paused.to_le_bytes());
+            }
+            &Self::RegisterAsset { kind } => {
+                buf.push(28);
+                buf.push(kind as u8);
+            }
+            &Self::UpdateAsset { kind } => {
+                buf.push(29);
+                buf.push(kind as u8);
+            }
+            Self::DeregisterAsset => buf.push(30),
+            &Self::SetDepositLimits {
+                max_supply,
+                max_deposit,
+                max_owner_deposit,
+            } => {
+                buf.push(31);
+                buf.extend_from_slice(&max_supply.to_le_bytes());
+                buf.extend_from_slice(&max_deposit.to_le_bytes());
+                buf.extend_from_slice(&max_owner_deposit.to_le_bytes());
+            }
+            Self::InitializeDepositRecord => buf.push(32),
+            &Self::SetStopLoss {
+                stop_price,
+                trailing_bps,
+                max_slippage_bps,
+            } => {
+                buf.push(33);
+                buf.extend_from_slice(&stop_price.to_le_bytes());
+                buf.extend_from_slice(&trailing_bps.to_le_bytes());
+                buf.extend_from_slice(&max_slippage_bps.to_le_bytes());
+            }
+            &Self::TriggerConditions { minimum_amount_out } => {
+                buf.push(34);
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            &Self::SetTakeProfit {
+                trigger_bps,
+                sell_bps,
+                max_slippage_bps,
+                distribute,
+            } => {
+                buf.push(35);
+                buf.extend_from_slice(&trigger_bps.to_le_bytes());
+                buf.extend_from_slice(&sell_bps.to_le_bytes());
+                buf.extend_from_slice(&max_slippage_bps.to_le_bytes());
+                buf.push(distribute as u8);
+            }
+            &Self::HarvestProfit { minimum_amount_out } => {
+                buf.push(36);
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            Self::ClaimRewards => buf.push(37),
+            &Self::InitializeDca {
+                asset_index,
+                tranches,
+                max_slippage_bps,
+            } => {
+                buf.push(38);
+                buf.push(asset_index);
+                buf.extend_from_slice(&tranches.to_le_bytes());
+                buf.extend_from_slice(&max_slippage_bps.to_le_bytes());
+            }
+            &Self::DepositDca { amount } => {
+                buf.push(39);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            &Self::ExecuteDca { minimum_amount_out } => {
+                buf.push(40);
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            }
+            &Self::WithdrawDca { usdc, asset } => {
+                buf.push(41);
+                buf.extend_from_slice(&usdc.to_le_bytes());
+                buf.extend_from_slice(&asset.to_le_bytes());
+            }
+            &Self::RequestWithdraw { amount } => {
+                buf.push(42);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            &Self::SetWithdrawDelay {
+                delay_slots,
+                bypass,
+            } => {
+                buf.push(43);
+                buf.extend_from_slice(&delay_slots.to_le_bytes());
+                buf.push(bypass as u8);
+            }
 
         };
         buf
     }
 
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        let value = input
+            .get(..2)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u16::from_le_bytes)
+            .ok_or(TokenError::InvalidInstruction)?;
+        Ok((value, &input[2..]))
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        let value = input
+            .get(..8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(TokenError::InvalidInstruction)?;
+        Ok((value, &input[8..]))
+    }
+
+    fn unpack_amount_decimals(input: &[u8]) -> Result<(u64, u8, &[u8]), ProgramError> {
+        let (amount, rest) = Self::unpack_u64(input)?;
+        let (&decimals, rest) = rest.split_first().ok_or(TokenError::InvalidInstruction)?;
+        Ok((amount, decimals, rest))
+    }
+
     fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
         if input.len() >= 32 {
             let (key, rest) = input.split_at(32);
@@ -1006,51 +1883,76 @@ impl AuthorityType {
 
 
 /// Creates a `Deposit` instruction.
+#[allow(clippy::too_many_arguments)]
 pub fn deposit(
     program_id: &Pubkey,
-    swap_info: &Pubkey,
-    owner_key: &Pubkey,
-    account_key: &Pubkey,
-    source_info: &Pubkey,
-    swap_source_info: &Pubkey,
-    swap_destination_info: &Pubkey,
-    destination_info: &Pubkey,
-    pool_mint_info: &Pubkey,
-    pool_fee_account_info: &Pubkey,
-    token_program_info: &Pubkey,
-    host_fee_account: &Pubkey,
-    prog_address: &Pubkey,
-    pubkey_swap: &Pubkey,
+    mint_pubkey: &Pubkey,
+    account_pubkey: &Pubkey,
+    account_owner_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    source_authority_pubkey: &Pubkey,
+    asset_vault_pubkey: &Pubkey,
+    stable_vault_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    protocol_fee_pubkey: &Pubkey,
+    swap_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    swap_authority_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    token_program_id: &Pubkey,
+    host_fee_pubkey: Option<&Pubkey>,
     amount: u64,
-    volatility: u64,
-    nonce: u8,
-
+    minimum_amount_out: u64,
 ) -> Result<Instruction, ProgramError> {
     let data = TokenInstruction::Deposit {
         amount,
-        volatility,
-        nonce,
-     }.pack();
-
+        minimum_amount_out,
+    }
+    .pack();
 
-    let  accounts = vec![
-    AccountMeta::new(*swap_info, false),
-    AccountMeta::new(*owner_key, true),
-    AccountMeta::new(*account_key, false),
-    AccountMeta::new(*source_info, false),
-    AccountMeta::new(*swap_source_info, false),
-    AccountMeta::new(*swap_destination_info, false),
-    AccountMeta::new(*destination_info, false),
-    AccountMeta::new(*pool_mint_info, false),
-    AccountMeta::new(*pool_fee_account_info, false),
-    AccountMeta::new(*token_program_info, false),
-    AccountMeta::new(*host_fee_account, false),
-    AccountMeta::new(*prog_address, false),
-    AccountMeta::new(*pubkey_swap, false),
-    
+    let (hedge_config, _) = find_hedge_config_with_program_id(program_id, mint_pubkey);
+    let (vault_authority, _) = find_vault_authority_with_program_id(program_id, mint_pubkey);
+    let (position, _) = find_position_with_program_id(program_id, account_pubkey);
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (deposit_limits, _) = find_deposit_limits_with_program_id(program_id, mint_pubkey);
+    let (deposit_record, _) =
+        find_deposit_record_with_program_id(program_id, mint_pubkey, account_owner_pubkey);
+    let (stop_loss, _) = find_stop_loss_with_program_id(program_id, mint_pubkey);
+    let (take_profit, _) = find_take_profit_with_program_id(program_id, mint_pubkey);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(program_config, false),
+        AccountMeta::new_readonly(hedge_config, false),
+        AccountMeta::new(*mint_pubkey, false),
+        AccountMeta::new(*account_pubkey, false),
+        AccountMeta::new(position, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new_readonly(*source_authority_pubkey, true),
+        AccountMeta::new(*asset_vault_pubkey, false),
+        AccountMeta::new(*stable_vault_pubkey, false),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new(*protocol_fee_pubkey, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*swap_authority_pubkey, false),
+        AccountMeta::new(*swap_source_pubkey, false),
+        AccountMeta::new(*swap_destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*swap_program_id, false),
+        AccountMeta::new_readonly(deposit_limits, false),
+        AccountMeta::new(deposit_record, false),
+        AccountMeta::new_readonly(stop_loss, false),
+        AccountMeta::new_readonly(take_profit, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
 
-       ];
-  
     Ok(Instruction {
         program_id: *program_id,
         accounts,
@@ -1058,13 +1960,945 @@ pub fn deposit(
     })
 }
 
-
-
-/// Creates a `Withdraw` instruction.
-pub fn withdraw(
+/// Creates an `InitializeHedgedMint` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_hedged_mint(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    asset_vault_pubkey: &Pubkey,
+    stable_vault_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    swap_pubkey: &Pubkey,
+    asset_mint_pubkey: &Pubkey,
+    stable_mint_pubkey: &Pubkey,
+    mint_authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    target_ratio_bps: u16,
+    deposit_fee_bps: u16,
+    withdraw_fee_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::InitializeHedgedMint {
+        target_ratio_bps,
+        deposit_fee_bps,
+        withdraw_fee_bps,
+    }
+    .pack();
+
+    let (hedge_config, _) = find_hedge_config_with_program_id(program_id, mint_pubkey);
+    let (asset_entry, _) = find_asset_entry_with_program_id(program_id, asset_mint_pubkey);
+    let (stable_entry, _) = find_asset_entry_with_program_id(program_id, stable_mint_pubkey);
+    let mut accounts = Vec::with_capacity(12 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(hedge_config, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(*asset_vault_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*stable_vault_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*fee_account_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*swap_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(asset_entry, false));
+    accounts.push(AccountMeta::new_readonly(stable_entry, false));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts.push(AccountMeta::new_readonly(
+        *mint_authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitializePosition` instruction.
+pub fn initialize_position(
+    program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::InitializePosition.pack();
+
+    let (position, _) = find_position_with_program_id(program_id, account_pubkey);
+    let accounts = vec![
+        AccountMeta::new(position, false),
+        AccountMeta::new_readonly(*account_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `MigrateAccount` instruction for a legacy mint.
+pub fn migrate_mint(program_id: &Pubkey, mint_pubkey: &Pubkey) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::MigrateAccount.pack();
+
+    let accounts = vec![AccountMeta::new(*mint_pubkey, false)];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `MigrateAccount` instruction for a legacy token account.
+pub fn migrate_account(
+    program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::MigrateAccount.pack();
+
+    let (position, _) = find_position_with_program_id(program_id, account_pubkey);
+    let accounts = vec![
+        AccountMeta::new(*account_pubkey, false),
+        AccountMeta::new(position, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `MigrateAccount` instruction for a portfolio or user portfolio
+/// written without a header.
+pub fn migrate_state_account(
+    program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::MigrateAccount.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*account_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitializeProgramConfig` instruction.
+pub fn initialize_program_config(
+    program_id: &Pubkey,
+    admin_pubkey: &Pubkey,
+    guardian_pubkey: &Pubkey,
+    protocol_fee_owner_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    upgrade_authority_pubkey: &Pubkey,
+    protocol_fee_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::InitializeProgramConfig { protocol_fee_bps }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (program_data, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    let accounts = vec![
+        AccountMeta::new(program_config, false),
+        AccountMeta::new_readonly(*admin_pubkey, false),
+        AccountMeta::new_readonly(*guardian_pubkey, false),
+        AccountMeta::new_readonly(*protocol_fee_owner_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(program_data, false),
+        AccountMeta::new_readonly(*upgrade_authority_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SetProgramConfig` instruction.
+pub fn set_program_config(
+    program_id: &Pubkey,
+    new_admin_pubkey: &Pubkey,
+    new_guardian_pubkey: &Pubkey,
+    new_protocol_fee_owner_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    protocol_fee_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::SetProgramConfig { protocol_fee_bps }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let mut accounts = Vec::with_capacity(5 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(program_config, false));
+    accounts.push(AccountMeta::new_readonly(*new_admin_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*new_guardian_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*new_protocol_fee_owner_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *admin_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SetPause` instruction.
+pub fn set_pause(
+    program_id: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    paused: u32,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::SetPause { paused }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(program_config, false));
+    accounts.push(AccountMeta::new_readonly(
+        *authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `RegisterAsset` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn register_asset(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    swap_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    kind: AssetKind,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::RegisterAsset { kind }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (asset_entry, _) = find_asset_entry_with_program_id(program_id, mint_pubkey);
+    let mut accounts = Vec::with_capacity(9 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new_readonly(program_config, false));
+    accounts.push(AccountMeta::new(asset_entry, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*oracle_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*swap_pubkey, false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts.push(AccountMeta::new_readonly(
+        *admin_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `UpdateAsset` instruction.
+pub fn update_asset(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    kind: AssetKind,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::UpdateAsset { kind }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (asset_entry, _) = find_asset_entry_with_program_id(program_id, mint_pubkey);
+    let mut accounts = Vec::with_capacity(5 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new_readonly(program_config, false));
+    accounts.push(AccountMeta::new(asset_entry, false));
+    accounts.push(AccountMeta::new_readonly(*oracle_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*swap_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *admin_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `DeregisterAsset` instruction.
+pub fn deregister_asset(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::DeregisterAsset.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (asset_entry, _) = find_asset_entry_with_program_id(program_id, mint_pubkey);
+    let mut accounts = Vec::with_capacity(4 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new_readonly(program_config, false));
+    accounts.push(AccountMeta::new(asset_entry, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *admin_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SetDepositLimits` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn set_deposit_limits(
+    program_id: &Pubkey,
+    target_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    max_supply: u64,
+    max_deposit: u64,
+    max_owner_deposit: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::SetDepositLimits {
+        max_supply,
+        max_deposit,
+        max_owner_deposit,
+    }
+    .pack();
+
+    let (deposit_limits, _) = find_deposit_limits_with_program_id(program_id, target_pubkey);
+    let mut accounts = Vec::with_capacity(6 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(deposit_limits, false));
+    accounts.push(AccountMeta::new_readonly(*target_pubkey, false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts.push(AccountMeta::new_readonly(
+        *authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SetStopLoss` instruction, `closed_epoch` being the epoch before
+/// the current epoch of the hedge configuration of the mint.
+#[allow(clippy::too_many_arguments)]
+pub fn set_stop_loss(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    asset_mint_pubkey: &Pubkey,
+    stable_mint_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    closed_epoch: u32,
+    stop_price: u64,
+    trailing_bps: u16,
+    max_slippage_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::SetStopLoss {
+        stop_price,
+        trailing_bps,
+        max_slippage_bps,
+    }
+    .pack();
+
+    let (stop_loss, _) = find_stop_loss_with_program_id(program_id, mint_pubkey);
+    let (hedge_config, _) = find_hedge_config_with_program_id(program_id, mint_pubkey);
+    let (asset_entry, _) = find_asset_entry_with_program_id(program_id, asset_mint_pubkey);
+    let (stable_entry, _) = find_asset_entry_with_program_id(program_id, stable_mint_pubkey);
+    let (hedge_epoch, _) = find_hedge_epoch_with_program_id(program_id, mint_pubkey, closed_epoch);
+    let mut accounts = Vec::with_capacity(10 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(stop_loss, false));
+    accounts.push(AccountMeta::new_readonly(hedge_config, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(asset_entry, false));
+    accounts.push(AccountMeta::new_readonly(stable_entry, false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts.push(AccountMeta::new(hedge_epoch, false));
+    accounts.push(AccountMeta::new_readonly(
+        *authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `TriggerConditions` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn trigger_conditions(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    asset_vault_pubkey: &Pubkey,
+    stable_vault_pubkey: &Pubkey,
+    swap_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    swap_authority_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    token_program_id: &Pubkey,
+    host_fee_pubkey: Option<&Pubkey>,
+    minimum_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::TriggerConditions { minimum_amount_out }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (stop_loss, _) = find_stop_loss_with_program_id(program_id, mint_pubkey);
+    let (hedge_config, _) = find_hedge_config_with_program_id(program_id, mint_pubkey);
+    let (vault_authority, _) = find_vault_authority_with_program_id(program_id, mint_pubkey);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(program_config, false),
+        AccountMeta::new(stop_loss, false),
+        AccountMeta::new(hedge_config, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*oracle_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*asset_vault_pubkey, false),
+        AccountMeta::new(*stable_vault_pubkey, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*swap_authority_pubkey, false),
+        AccountMeta::new(*swap_source_pubkey, false),
+        AccountMeta::new(*swap_destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*swap_program_id, false),
+    ];
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SetTakeProfit` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn set_take_profit(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    asset_mint_pubkey: &Pubkey,
+    stable_mint_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    trigger_bps: u16,
+    sell_bps: u16,
+    max_slippage_bps: u16,
+    distribute: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::SetTakeProfit {
+        trigger_bps,
+        sell_bps,
+        max_slippage_bps,
+        distribute,
+    }
+    .pack();
+
+    let (take_profit, _) = find_take_profit_with_program_id(program_id, mint_pubkey);
+    let (hedge_config, _) = find_hedge_config_with_program_id(program_id, mint_pubkey);
+    let (asset_entry, _) = find_asset_entry_with_program_id(program_id, asset_mint_pubkey);
+    let (stable_entry, _) = find_asset_entry_with_program_id(program_id, stable_mint_pubkey);
+    let mut accounts = Vec::with_capacity(11 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(take_profit, false));
+    accounts.push(AccountMeta::new_readonly(hedge_config, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(asset_entry, false));
+    accounts.push(AccountMeta::new_readonly(stable_entry, false));
+    accounts.push(AccountMeta::new_readonly(*oracle_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts.push(AccountMeta::new_readonly(
+        *authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `HarvestProfit` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn harvest_profit(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    asset_vault_pubkey: &Pubkey,
+    stable_vault_pubkey: &Pubkey,
+    swap_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    swap_authority_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    token_program_id: &Pubkey,
+    host_fee_pubkey: Option<&Pubkey>,
+    minimum_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::HarvestProfit { minimum_amount_out }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (take_profit, _) = find_take_profit_with_program_id(program_id, mint_pubkey);
+    let (hedge_config, _) = find_hedge_config_with_program_id(program_id, mint_pubkey);
+    let (vault_authority, _) = find_vault_authority_with_program_id(program_id, mint_pubkey);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(program_config, false),
+        AccountMeta::new(take_profit, false),
+        AccountMeta::new(hedge_config, false),
+        AccountMeta::new_readonly(*mint_pubkey, false),
+        AccountMeta::new_readonly(*oracle_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*asset_vault_pubkey, false),
+        AccountMeta::new(*stable_vault_pubkey, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*swap_authority_pubkey, false),
+        AccountMeta::new(*swap_source_pubkey, false),
+        AccountMeta::new(*swap_destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*swap_program_id, false),
+    ];
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `ClaimRewards` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn claim_rewards(
+    program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    account_pubkey: &Pubkey,
+    stable_vault_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    token_program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::ClaimRewards.pack();
+
+    let (take_profit, _) = find_take_profit_with_program_id(program_id, mint_pubkey);
+    let (hedge_config, _) = find_hedge_config_with_program_id(program_id, mint_pubkey);
+    let (position, _) = find_position_with_program_id(program_id, account_pubkey);
+    let (vault_authority, _) = find_vault_authority_with_program_id(program_id, mint_pubkey);
+    let mut accounts = Vec::with_capacity(10 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(take_profit, false));
+    accounts.push(AccountMeta::new_readonly(hedge_config, false));
+    accounts.push(AccountMeta::new_readonly(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*account_pubkey, false));
+    accounts.push(AccountMeta::new(position, false));
+    accounts.push(AccountMeta::new(*stable_vault_pubkey, false));
+    accounts.push(AccountMeta::new(*destination_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(vault_authority, false));
+    accounts.push(AccountMeta::new_readonly(*token_program_id, false));
+    accounts.push(AccountMeta::new_readonly(
+        *owner_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitializeDca` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_dca(
+    program_id: &Pubkey,
+    portfolio_pubkey: &Pubkey,
+    asset_mint_pubkey: &Pubkey,
+    stable_mint_pubkey: &Pubkey,
+    asset_vault_pubkey: &Pubkey,
+    stable_vault_pubkey: &Pubkey,
+    swap_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    creator_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    asset_index: u8,
+    tranches: u16,
+    max_slippage_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::InitializeDca {
+        asset_index,
+        tranches,
+        max_slippage_bps,
+    }
+    .pack();
+
+    let (dca_config, _) = find_dca_config_with_program_id(program_id, portfolio_pubkey, asset_index);
+    let (asset_entry, _) = find_asset_entry_with_program_id(program_id, asset_mint_pubkey);
+    let (stable_entry, _) = find_asset_entry_with_program_id(program_id, stable_mint_pubkey);
+    let mut accounts = Vec::with_capacity(11 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(dca_config, false));
+    accounts.push(AccountMeta::new_readonly(*portfolio_pubkey, false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(*asset_vault_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*stable_vault_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(*swap_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(asset_entry, false));
+    accounts.push(AccountMeta::new_readonly(stable_entry, false));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts.push(AccountMeta::new_readonly(
+        *creator_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `DepositDca` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_dca(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    dca_config_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    stable_vault_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::DepositDca { amount }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (dca_position, _) =
+        find_dca_position_with_program_id(program_id, dca_config_pubkey, owner_pubkey);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_config, false),
+        AccountMeta::new(*dca_config_pubkey, false),
+        AccountMeta::new(dca_position, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*stable_vault_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `ExecuteDca` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_dca(
+    program_id: &Pubkey,
+    dca_config_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    stable_vault_pubkey: &Pubkey,
+    asset_vault_pubkey: &Pubkey,
+    swap_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    swap_authority_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    token_program_id: &Pubkey,
+    host_fee_pubkey: Option<&Pubkey>,
+    minimum_amount_out: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::ExecuteDca { minimum_amount_out }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (dca_position, _) =
+        find_dca_position_with_program_id(program_id, dca_config_pubkey, owner_pubkey);
+    let (vault_authority, _) = find_vault_authority_with_program_id(program_id, dca_config_pubkey);
+    let mut accounts = vec![
+        AccountMeta::new_readonly(program_config, false),
+        AccountMeta::new(*dca_config_pubkey, false),
+        AccountMeta::new(dca_position, false),
+        AccountMeta::new_readonly(*oracle_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*stable_vault_pubkey, false),
+        AccountMeta::new(*asset_vault_pubkey, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*swap_authority_pubkey, false),
+        AccountMeta::new(*swap_source_pubkey, false),
+        AccountMeta::new(*swap_destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*swap_program_id, false),
+    ];
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `WithdrawDca` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_dca(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    dca_config_pubkey: &Pubkey,
+    stable_vault_pubkey: &Pubkey,
+    asset_vault_pubkey: &Pubkey,
+    usdc_destination_pubkey: &Pubkey,
+    asset_destination_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    usdc: u64,
+    asset: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::WithdrawDca { usdc, asset }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (dca_position, _) =
+        find_dca_position_with_program_id(program_id, dca_config_pubkey, owner_pubkey);
+    let (vault_authority, _) = find_vault_authority_with_program_id(program_id, dca_config_pubkey);
+    let accounts = vec![
+        AccountMeta::new_readonly(program_config, false),
+        AccountMeta::new(*dca_config_pubkey, false),
+        AccountMeta::new(dca_position, false),
+        AccountMeta::new(*stable_vault_pubkey, false),
+        AccountMeta::new(*asset_vault_pubkey, false),
+        AccountMeta::new(*usdc_destination_pubkey, false),
+        AccountMeta::new(*asset_destination_pubkey, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `RequestWithdraw` instruction.
+pub fn request_withdraw(
+    program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::RequestWithdraw { amount }.pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (withdraw_ticket, _) = find_withdraw_ticket_with_program_id(program_id, account_pubkey);
+    let mut accounts = Vec::with_capacity(8 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new_readonly(program_config, false));
+    accounts.push(AccountMeta::new_readonly(*account_pubkey, false));
+    accounts.push(AccountMeta::new(withdraw_ticket, false));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    accounts.push(AccountMeta::new(*payer_pubkey, true));
+    accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+    accounts.push(AccountMeta::new_readonly(
+        *owner_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `SetWithdrawDelay` instruction.
+pub fn set_withdraw_delay(
+    program_id: &Pubkey,
+    admin_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    delay_slots: u64,
+    bypass: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::SetWithdrawDelay {
+        delay_slots,
+        bypass,
+    }
+    .pack();
+
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let mut accounts = Vec::with_capacity(2 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(program_config, false));
+    accounts.push(AccountMeta::new_readonly(
+        *admin_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an `InitializeDepositRecord` instruction.
+pub fn initialize_deposit_record(
+    program_id: &Pubkey,
+    target_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::InitializeDepositRecord.pack();
+
+    let (deposit_record, _) =
+        find_deposit_record_with_program_id(program_id, target_pubkey, owner_pubkey);
+    let accounts = vec![
+        AccountMeta::new(deposit_record, false),
+        AccountMeta::new_readonly(*target_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `Withdraw` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw(
     program_id: &Pubkey,
+    mint: &Pubkey,
     account: &Pubkey,
+    swap: &Pubkey,
+    asset_reserve: &Pubkey,
+    stable_reserve: &Pubkey,
+    asset_vault: &Pubkey,
+    stable_vault: &Pubkey,
+    asset_destination: &Pubkey,
+    stable_destination: &Pubkey,
+    fee_account: &Pubkey,
+    stable_fee_account: &Pubkey,
+    protocol_fee_account: &Pubkey,
+    protocol_stable_fee_account: &Pubkey,
+    token_program_id: &Pubkey,
     owner: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
     amount: u64,
    
 
@@ -1074,10 +2908,39 @@ pub fn withdraw(
      }.pack();
 
 
-    let  accounts = vec![
+    let (position, _) = find_position_with_program_id(program_id, account);
+    let (program_config, _) = find_program_config_with_program_id(program_id);
+    let (take_profit, _) = find_take_profit_with_program_id(program_id, mint);
+    let (hedge_config, _) = find_hedge_config_with_program_id(program_id, mint);
+    let (withdraw_ticket, _) = find_withdraw_ticket_with_program_id(program_id, account);
+    let (vault_authority, _) = find_vault_authority_with_program_id(program_id, mint);
+    let mut accounts = vec![
+    AccountMeta::new_readonly(program_config, false),
     AccountMeta::new(*account, false),
-    AccountMeta::new(*owner, true),
+    AccountMeta::new(position, false),
+    AccountMeta::new_readonly(take_profit, false),
+    AccountMeta::new_readonly(hedge_config, false),
+    AccountMeta::new_readonly(*swap, false),
+    AccountMeta::new_readonly(*asset_reserve, false),
+    AccountMeta::new_readonly(*stable_reserve, false),
+    AccountMeta::new(withdraw_ticket, false),
+    AccountMeta::new(*mint, false),
+    AccountMeta::new(*asset_vault, false),
+    AccountMeta::new(*stable_vault, false),
+    AccountMeta::new(*asset_destination, false),
+    AccountMeta::new(*stable_destination, false),
+    AccountMeta::new(*fee_account, false),
+    AccountMeta::new(*stable_fee_account, false),
+    AccountMeta::new(*protocol_fee_account, false),
+    AccountMeta::new(*protocol_stable_fee_account, false),
+    AccountMeta::new_readonly(vault_authority, false),
+    AccountMeta::new_readonly(*token_program_id, false),
+    AccountMeta::new_readonly(sysvar::clock::id(), false),
+    AccountMeta::new_readonly(*owner, signer_pubkeys.is_empty()),
        ];
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
   
     Ok(Instruction {
         program_id: *program_id,
@@ -1097,6 +2960,7 @@ pub fn initialize_portfolio(
     program_id: &Pubkey,
     portfolio_account: &Pubkey ,
     creator_portfolio: &Pubkey ,
+    owner: &Pubkey ,
     metaDataUrl : &Vec<u8>,
     metaDataHash : &u16,
     amountAsset1 : &u8,
@@ -1175,7 +3039,7 @@ pub fn initialize_portfolio(
      }.pack();
 
 
-    let  accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(*portfolio_account, true),
         AccountMeta::new(*creator_portfolio, true),
         AccountMeta::new(*addressAsset1, false),
@@ -1198,8 +3062,26 @@ pub fn initialize_portfolio(
         AccountMeta::new(*assetToSoldIntoAsset9, false),
         // AccountMeta::new(*addressAsset10, false),
         // AccountMeta::new(*assetToSoldIntoAsset10, false),
-     
+        AccountMeta::new_readonly(*owner, false),
        ];
+    accounts.extend(
+        [
+            addressAsset1,
+            addressAsset2,
+            addressAsset3,
+            addressAsset4,
+            addressAsset5,
+            addressAsset6,
+            addressAsset7,
+            addressAsset8,
+            addressAsset9,
+        ]
+        .iter()
+        .map(|mint| {
+            let (asset_entry, _) = find_asset_entry_with_program_id(program_id, mint);
+            AccountMeta::new_readonly(asset_entry, false)
+        }),
+    );
   
     Ok(Instruction {
         program_id: *program_id,
@@ -1234,7 +3116,10 @@ pub fn create_Init_User_Portfolio
         delegated_amount:*delegated_amount,
      }.pack();
 
-
+    let (deposit_limits, _) = find_deposit_limits_with_program_id(program_id, portfolio_address);
+    let (deposit_record, _) =
+        find_deposit_record_with_program_id(program_id, portfolio_address, owner);
+    let (program_config, _) = find_program_config_with_program_id(program_id);
     let  accounts = vec![
         AccountMeta::new(*user_portfolio_account, false),
         AccountMeta::new(*portfolio_address, false),
@@ -1249,6 +3134,9 @@ pub fn create_Init_User_Portfolio
         AccountMeta::new(*splu_asset7, false),
         AccountMeta::new(*splu_asset8, false),
         AccountMeta::new(*splu_asset9, false),
+        AccountMeta::new(deposit_limits, false),
+        AccountMeta::new(deposit_record, false),
+        AccountMeta::new_readonly(program_config, false),
        ];
   
     Ok(Instruction {
@@ -1264,21 +3152,15 @@ pub fn create_Init_User_Portfolio
 pub fn initialize_mint(
     token_program_id: &Pubkey,
     mint_pubkey: &Pubkey,
-    decimals: u8,
     mint_authority_pubkey: &Pubkey,
     freeze_authority_pubkey: Option<&Pubkey>,
-    cmint_id_asset: Option<&Pubkey>,
-    cpubkey_swap: Option<&Pubkey>
+    decimals: u8,
 ) -> Result<Instruction, ProgramError> {
     let freeze_authority = freeze_authority_pubkey.cloned().into();
-    let mint_id_asset = cmint_id_asset.cloned().into();
-    let pubkey_swap = cpubkey_swap.cloned().into();
     let data = TokenInstruction::InitializeMint {
         mint_authority: *mint_authority_pubkey,
         freeze_authority,
         decimals,
-        mint_id_asset,
-        pubkey_swap
     }
     .pack();
 
@@ -1400,6 +3282,47 @@ pub fn transfer(
     })
 }
 
+/// Creates a `Transfer` instruction that also moves the hedged components
+/// between the positions of the source and destination accounts. Transfers
+/// of a hedged mint must name both positions; `transfer` alone suits any
+/// other mint.
+pub fn transfer_with_positions(
+    token_program_id: &Pubkey,
+    mint_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = transfer(
+        token_program_id,
+        source_pubkey,
+        destination_pubkey,
+        authority_pubkey,
+        signer_pubkeys,
+        amount,
+    )?;
+    let (source_position, _) = find_position_with_program_id(token_program_id, source_pubkey);
+    let (destination_position, _) =
+        find_position_with_program_id(token_program_id, destination_pubkey);
+    instruction
+        .accounts
+        .push(AccountMeta::new(source_position, false));
+    instruction
+        .accounts
+        .push(AccountMeta::new(destination_position, false));
+    let (take_profit, _) = find_take_profit_with_program_id(token_program_id, mint_pubkey);
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(take_profit, false));
+    let (hedge_config, _) = find_hedge_config_with_program_id(token_program_id, mint_pubkey);
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(hedge_config, false));
+    Ok(instruction)
+}
+
 /// Creates an `Approve` instruction.
 pub fn approve(
     token_program_id: &Pubkey,
@@ -1602,6 +3525,28 @@ pub fn close_account(
     })
 }
 
+/// Creates a `CloseAccount` instruction that also closes the position of the
+/// account. Accounts of a hedged mint must name their position;
+/// `close_account` alone suits any other mint.
+pub fn close_account_with_position(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = close_account(
+        token_program_id,
+        account_pubkey,
+        destination_pubkey,
+        owner_pubkey,
+        signer_pubkeys,
+    )?;
+    let (position, _) = find_position_with_program_id(token_program_id, account_pubkey);
+    instruction.accounts.push(AccountMeta::new(position, false));
+    Ok(instruction)
+}
+
 /// Creates a `FreezeAccount` instruction.
 pub fn freeze_account(
     token_program_id: &Pubkey,
@@ -1691,6 +3636,43 @@ pub fn transfer_checked(
     })
 }
 
+/// Creates a `TransferChecked` instruction that also moves the hedged
+/// components between the positions of the source and destination accounts.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer_checked_with_positions(
+    token_program_id: &Pubkey,
+    source_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    let mut instruction = transfer_checked(
+        token_program_id,
+        source_pubkey,
+        mint_pubkey,
+        destination_pubkey,
+        authority_pubkey,
+        signer_pubkeys,
+        amount,
+        decimals,
+    )?;
+    let (source_position, _) = find_position_with_program_id(token_program_id, source_pubkey);
+    let (destination_position, _) =
+        find_position_with_program_id(token_program_id, destination_pubkey);
+    let (take_profit, _) = find_take_profit_with_program_id(token_program_id, mint_pubkey);
+    let (hedge_config, _) = find_hedge_config_with_program_id(token_program_id, mint_pubkey);
+    instruction.accounts.extend(vec![
+        AccountMeta::new(source_position, false),
+        AccountMeta::new(destination_position, false),
+        AccountMeta::new_readonly(take_profit, false),
+        AccountMeta::new_readonly(hedge_config, false),
+    ]);
+    Ok(instruction)
+}
+
 /// Creates an `ApproveChecked` instruction.
 #[allow(clippy::too_many_arguments)]
 pub fn approve_checked(
@@ -1747,41 +3729,207 @@ pub fn mint_to_checked(
         accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
     }
 
-    Ok(Instruction {
-        program_id: *token_program_id,
-        accounts,
-        data,
-    })
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a `BurnChecked` instruction.
+pub fn burn_checked(
+    token_program_id: &Pubkey,
+    account_pubkey: &Pubkey,
+    mint_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+    amount: u64,
+    decimals: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = TokenInstruction::BurnChecked { amount, decimals }.pack();
+
+    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
+    accounts.push(AccountMeta::new(*account_pubkey, false));
+    accounts.push(AccountMeta::new(*mint_pubkey, false));
+    accounts.push(AccountMeta::new_readonly(
+        *authority_pubkey,
+        signer_pubkeys.is_empty(),
+    ));
+    for signer_pubkey in signer_pubkeys.iter() {
+        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *token_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Derives the vault authority of `mint` and its bump seed.
+pub fn find_vault_authority(mint: &Pubkey) -> (Pubkey, u8) {
+    find_vault_authority_with_program_id(&crate::id(), mint)
+}
+
+/// Derives the vault authority of `mint` under `program_id` and its bump seed.
+pub fn find_vault_authority_with_program_id(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VAULT_AUTHORITY_SEED, mint.as_ref()], program_id)
+}
+
+/// Derives the hedge configuration of `mint` and its bump seed.
+pub fn find_hedge_config(mint: &Pubkey) -> (Pubkey, u8) {
+    find_hedge_config_with_program_id(&crate::id(), mint)
+}
+
+/// Derives the hedge configuration of `mint` under `program_id` and its bump seed.
+pub fn find_hedge_config_with_program_id(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HEDGE_CONFIG_SEED, mint.as_ref()], program_id)
+}
+
+/// Derives the position of the token account `account` and its bump seed.
+pub fn find_position(account: &Pubkey) -> (Pubkey, u8) {
+    find_position_with_program_id(&crate::id(), account)
+}
+
+/// Derives the position of the token account `account` under `program_id` and its bump seed.
+pub fn find_position_with_program_id(program_id: &Pubkey, account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POSITION_SEED, account.as_ref()], program_id)
+}
+
+/// Derives the program configuration and its bump seed.
+pub fn find_program_config() -> (Pubkey, u8) {
+    find_program_config_with_program_id(&crate::id())
+}
+
+/// Derives the program configuration under `program_id` and its bump seed.
+pub fn find_program_config_with_program_id(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PROGRAM_CONFIG_SEED], program_id)
+}
+
+/// Derives the registry entry of `mint` and its bump seed.
+pub fn find_asset_entry(mint: &Pubkey) -> (Pubkey, u8) {
+    find_asset_entry_with_program_id(&crate::id(), mint)
+}
+
+/// Derives the registry entry of `mint` under `program_id` and its bump seed.
+pub fn find_asset_entry_with_program_id(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ASSET_ENTRY_SEED, mint.as_ref()], program_id)
+}
+
+/// Derives the deposit limits of the mint or portfolio `target` and its bump seed.
+pub fn find_deposit_limits(target: &Pubkey) -> (Pubkey, u8) {
+    find_deposit_limits_with_program_id(&crate::id(), target)
+}
+
+/// Derives the deposit limits of `target` under `program_id` and its bump seed.
+pub fn find_deposit_limits_with_program_id(program_id: &Pubkey, target: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DEPOSIT_LIMITS_SEED, target.as_ref()], program_id)
+}
+
+/// Derives the deposit record of `owner` into the mint or portfolio `target`
+/// and its bump seed.
+pub fn find_deposit_record(target: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    find_deposit_record_with_program_id(&crate::id(), target, owner)
+}
+
+/// Derives the deposit record of `owner` into `target` under `program_id` and
+/// its bump seed.
+pub fn find_deposit_record_with_program_id(
+    program_id: &Pubkey,
+    target: &Pubkey,
+    owner: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[DEPOSIT_RECORD_SEED, target.as_ref(), owner.as_ref()],
+        program_id,
+    )
+}
+
+/// Derives the stop-loss rule of `mint` and its bump seed.
+pub fn find_stop_loss(mint: &Pubkey) -> (Pubkey, u8) {
+    find_stop_loss_with_program_id(&crate::id(), mint)
+}
+
+/// Derives the stop-loss rule of `mint` under `program_id` and its bump seed.
+pub fn find_stop_loss_with_program_id(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STOP_LOSS_SEED, mint.as_ref()], program_id)
+}
+
+/// Derives the take-profit rule of `mint` and its bump seed.
+pub fn find_take_profit(mint: &Pubkey) -> (Pubkey, u8) {
+    find_take_profit_with_program_id(&crate::id(), mint)
+}
+
+/// Derives the take-profit rule of `mint` under `program_id` and its bump seed.
+pub fn find_take_profit_with_program_id(program_id: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[TAKE_PROFIT_SEED, mint.as_ref()], program_id)
+}
+
+/// Derives the dollar-cost-averaging configuration of the asset at
+/// `asset_index` in `portfolio` and its bump seed.
+pub fn find_dca_config(portfolio: &Pubkey, asset_index: u8) -> (Pubkey, u8) {
+    find_dca_config_with_program_id(&crate::id(), portfolio, asset_index)
+}
+
+/// Derives the dollar-cost-averaging configuration of the asset at
+/// `asset_index` in `portfolio` under `program_id` and its bump seed.
+pub fn find_dca_config_with_program_id(
+    program_id: &Pubkey,
+    portfolio: &Pubkey,
+    asset_index: u8,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[DCA_CONFIG_SEED, portfolio.as_ref(), &[asset_index]],
+        program_id,
+    )
+}
+
+/// Derives the dollar-cost-averaging position of `owner` in `dca_config` and
+/// its bump seed.
+pub fn find_dca_position(dca_config: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    find_dca_position_with_program_id(&crate::id(), dca_config, owner)
+}
+
+/// Derives the dollar-cost-averaging position of `owner` in `dca_config`
+/// under `program_id` and its bump seed.
+pub fn find_dca_position_with_program_id(
+    program_id: &Pubkey,
+    dca_config: &Pubkey,
+    owner: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[DCA_POSITION_SEED, dca_config.as_ref(), owner.as_ref()],
+        program_id,
+    )
 }
 
-/// Creates a `BurnChecked` instruction.
-pub fn burn_checked(
-    token_program_id: &Pubkey,
-    account_pubkey: &Pubkey,
-    mint_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    signer_pubkeys: &[&Pubkey],
-    amount: u64,
-    decimals: u8,
-) -> Result<Instruction, ProgramError> {
-    let data = TokenInstruction::BurnChecked { amount, decimals }.pack();
+/// Derives the withdraw ticket of `account` and its bump seed.
+pub fn find_withdraw_ticket(account: &Pubkey) -> (Pubkey, u8) {
+    find_withdraw_ticket_with_program_id(&crate::id(), account)
+}
 
-    let mut accounts = Vec::with_capacity(3 + signer_pubkeys.len());
-    accounts.push(AccountMeta::new(*account_pubkey, false));
-    accounts.push(AccountMeta::new(*mint_pubkey, false));
-    accounts.push(AccountMeta::new_readonly(
-        *authority_pubkey,
-        signer_pubkeys.is_empty(),
-    ));
-    for signer_pubkey in signer_pubkeys.iter() {
-        accounts.push(AccountMeta::new_readonly(**signer_pubkey, true));
-    }
+/// Derives the withdraw ticket of `account` under `program_id` and its bump
+/// seed.
+pub fn find_withdraw_ticket_with_program_id(program_id: &Pubkey, account: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[WITHDRAW_TICKET_SEED, account.as_ref()], program_id)
+}
 
-    Ok(Instruction {
-        program_id: *token_program_id,
-        accounts,
-        data,
-    })
+/// Derives the record of the closed `epoch` of `mint` and its bump seed.
+pub fn find_hedge_epoch(mint: &Pubkey, epoch: u32) -> (Pubkey, u8) {
+    find_hedge_epoch_with_program_id(&crate::id(), mint, epoch)
+}
+
+/// Derives the record of the closed `epoch` of `mint` under `program_id` and
+/// its bump seed.
+pub fn find_hedge_epoch_with_program_id(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    epoch: u32,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[HEDGE_EPOCH_SEED, mint.as_ref(), &epoch.to_le_bytes()],
+        program_id,
+    )
 }
 
 /// Utility function that checks index is between MIN_SIGNERS and MAX_SIGNERS
@@ -1796,15 +3944,53 @@ pub fn is_valid_signer_index(index: usize) -> bool {
             decimals: 2,
             mint_authority: Pubkey::new(&[1u8; 32]),
             freeze_authority: COption::None,
-            mint_id_asset:COption::None,
-            pubkey_swap:COption::None
         };
         let packed = check.pack();
         let mut expect = Vec::from([0u8, 2]);
         expect.extend_from_slice(&[1u8; 32]);
         expect.extend_from_slice(&[0]);
-        expect.extend_from_slice(&[0]);
-        expect.extend_from_slice(&[0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::InitializeMint {
+            decimals: 2,
+            mint_authority: Pubkey::new(&[2u8; 32]),
+            freeze_authority: COption::Some(Pubkey::new(&[3u8; 32])),
+        };
+        let packed = check.pack();
+        let mut expect = vec![0u8, 2];
+        expect.extend_from_slice(&[2u8; 32]);
+        expect.extend_from_slice(&[1]);
+        expect.extend_from_slice(&[3u8; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::InitializeAccount;
+        let packed = check.pack();
+        let expect = Vec::from([1u8]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::InitializeMultisig { m: 1 };
+        let packed = check.pack();
+        let expect = Vec::from([2u8, 1]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::Transfer { amount: 1 };
+        let packed = check.pack();
+        let expect = Vec::from([3u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::Approve { amount: 1 };
+        let packed = check.pack();
+        let expect = Vec::from([4u8, 1, 0, 0, 0, 0, 0, 0, 0]);
         assert_eq!(packed, expect);
         let unpacked = TokenInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
@@ -1913,5 +4099,422 @@ pub fn is_valid_signer_index(index: usize) -> bool {
         assert_eq!(packed, expect);
         let unpacked = TokenInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::Deposit {
+            amount: 1,
+            minimum_amount_out: 2,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([17u8, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::Withdraw { amount: 1 };
+        let packed = check.pack();
+        let expect = Vec::from([18u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::InitializePortfolio {
+            metaDataUrl: vec![7u8; METADATA_URL_LEN],
+            metaDataHash: 0x0102,
+            amountAsset1: 1,
+            periodAsset1: 2,
+            amountAsset2: 3,
+            periodAsset2: 4,
+            amountAsset3: 5,
+            periodAsset3: 6,
+            amountAsset4: 7,
+            periodAsset4: 8,
+            amountAsset5: 9,
+            periodAsset5: 10,
+            amountAsset6: 11,
+            periodAsset6: 12,
+            amountAsset7: 13,
+            periodAsset7: 14,
+            amountAsset8: 15,
+            periodAsset8: 16,
+            amountAsset9: 17,
+            periodAsset9: 18,
+        };
+        let packed = check.pack();
+        let mut expect = vec![19u8];
+        expect.extend_from_slice(&[7u8; METADATA_URL_LEN]);
+        expect.extend_from_slice(&[2, 1]);
+        expect.extend(1u8..=18);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::createInitUserPortfolio {
+            delegated_amount: 1,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([20u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::ApproveUserPortfolio { amount: 1 };
+        let packed = check.pack();
+        let expect = Vec::from([21u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::InitializeHedgedMint {
+            target_ratio_bps: 5_000,
+            deposit_fee_bps: 30,
+            withdraw_fee_bps: 1,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([22u8, 0x88, 0x13, 30, 0, 1, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+        assert_eq!(
+            TokenInstruction::unpack(&expect[..6]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let check = TokenInstruction::InitializePosition;
+        let packed = check.pack();
+        let expect = Vec::from([23u8]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::MigrateAccount;
+        let packed = check.pack();
+        let expect = Vec::from([24u8]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::InitializeProgramConfig {
+            protocol_fee_bps: 1,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([25u8, 1, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::SetProgramConfig {
+            protocol_fee_bps: 1,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([26u8, 1, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::SetPause { paused: 1 };
+        let packed = check.pack();
+        let expect = Vec::from([27u8, 1, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::RegisterAsset {
+            kind: AssetKind::Stablecoin,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([28u8, 1]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::UpdateAsset {
+            kind: AssetKind::Asset,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([29u8, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+        assert_eq!(
+            TokenInstruction::unpack(&[29u8, 2]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let check = TokenInstruction::DeregisterAsset;
+        let packed = check.pack();
+        let expect = Vec::from([30u8]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::SetDepositLimits {
+            max_supply: 1,
+            max_deposit: 2,
+            max_owner_deposit: 3,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([
+            31u8, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::InitializeDepositRecord;
+        let packed = check.pack();
+        let expect = Vec::from([32u8]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::SetStopLoss {
+            stop_price: 1,
+            trailing_bps: 2,
+            max_slippage_bps: 3,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([33u8, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 3, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::TriggerConditions {
+            minimum_amount_out: 1,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([34u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::SetTakeProfit {
+            trigger_bps: 1,
+            sell_bps: 2,
+            max_slippage_bps: 3,
+            distribute: true,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([35u8, 1, 0, 2, 0, 3, 0, 1]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+        assert_eq!(
+            TokenInstruction::unpack(&[35u8, 1, 0, 2, 0, 3, 0, 2]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+
+        let check = TokenInstruction::HarvestProfit {
+            minimum_amount_out: 1,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([36u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::ClaimRewards;
+        let packed = check.pack();
+        let expect = Vec::from([37u8]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::InitializeDca {
+            asset_index: 1,
+            tranches: 2,
+            max_slippage_bps: 3,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([38u8, 1, 2, 0, 3, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::DepositDca { amount: 1 };
+        let packed = check.pack();
+        let expect = Vec::from([39u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::ExecuteDca {
+            minimum_amount_out: 1,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([40u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::WithdrawDca { usdc: 1, asset: 2 };
+        let packed = check.pack();
+        let expect = Vec::from([41u8, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::RequestWithdraw { amount: 1 };
+        let packed = check.pack();
+        let expect = Vec::from([42u8, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = TokenInstruction::SetWithdrawDelay {
+            delay_slots: 1,
+            bypass: true,
+        };
+        let packed = check.pack();
+        let expect = Vec::from([43u8, 1, 0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(packed, expect);
+        let unpacked = TokenInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+        assert_eq!(
+            TokenInstruction::unpack(&[43u8, 1, 0, 0, 0, 0, 0, 0, 0, 2]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+    }
+
+    #[test]
+    fn test_instruction_unpack_truncated() {
+        let checks = vec![
+            TokenInstruction::InitializeMint {
+                decimals: 2,
+                mint_authority: Pubkey::new(&[1u8; 32]),
+                freeze_authority: COption::Some(Pubkey::new(&[2u8; 32])),
+            },
+            TokenInstruction::InitializeMultisig { m: 1 },
+            TokenInstruction::Transfer { amount: 1 },
+            TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::CloseAccount,
+                new_authority: COption::Some(Pubkey::new(&[3u8; 32])),
+            },
+            TokenInstruction::TransferChecked {
+                amount: 1,
+                decimals: 2,
+            },
+            TokenInstruction::ApproveChecked {
+                amount: 1,
+                decimals: 2,
+            },
+            TokenInstruction::MintToChecked {
+                amount: 1,
+                decimals: 2,
+            },
+            TokenInstruction::BurnChecked {
+                amount: 1,
+                decimals: 2,
+            },
+            TokenInstruction::InitializeAccount2 {
+                owner: Pubkey::new(&[4u8; 32]),
+            },
+            TokenInstruction::Deposit {
+                amount: 1,
+                minimum_amount_out: 2,
+            },
+            TokenInstruction::Withdraw { amount: 1 },
+            TokenInstruction::InitializePortfolio {
+                metaDataUrl: vec![1u8; METADATA_URL_LEN],
+                metaDataHash: 2,
+                amountAsset1: 3,
+                periodAsset1: 4,
+                amountAsset2: 5,
+                periodAsset2: 6,
+                amountAsset3: 7,
+                periodAsset3: 8,
+                amountAsset4: 9,
+                periodAsset4: 10,
+                amountAsset5: 11,
+                periodAsset5: 12,
+                amountAsset6: 13,
+                periodAsset6: 14,
+                amountAsset7: 15,
+                periodAsset7: 16,
+                amountAsset8: 17,
+                periodAsset8: 18,
+                amountAsset9: 19,
+                periodAsset9: 20,
+            },
+            TokenInstruction::createInitUserPortfolio {
+                delegated_amount: 1,
+            },
+            TokenInstruction::ApproveUserPortfolio { amount: 1 },
+            TokenInstruction::InitializeHedgedMint {
+                target_ratio_bps: 1,
+                deposit_fee_bps: 2,
+                withdraw_fee_bps: 3,
+            },
+            TokenInstruction::InitializeProgramConfig {
+                protocol_fee_bps: 1,
+            },
+            TokenInstruction::SetProgramConfig {
+                protocol_fee_bps: 1,
+            },
+            TokenInstruction::SetPause { paused: 1 },
+            TokenInstruction::RegisterAsset {
+                kind: AssetKind::Asset,
+            },
+            TokenInstruction::UpdateAsset {
+                kind: AssetKind::Stablecoin,
+            },
+            TokenInstruction::SetDepositLimits {
+                max_supply: 1,
+                max_deposit: 2,
+                max_owner_deposit: 3,
+            },
+            TokenInstruction::SetStopLoss {
+                stop_price: 1,
+                trailing_bps: 2,
+                max_slippage_bps: 3,
+            },
+            TokenInstruction::TriggerConditions {
+                minimum_amount_out: 1,
+            },
+            TokenInstruction::SetTakeProfit {
+                trigger_bps: 1,
+                sell_bps: 2,
+                max_slippage_bps: 3,
+                distribute: true,
+            },
+            TokenInstruction::HarvestProfit {
+                minimum_amount_out: 1,
+            },
+            TokenInstruction::InitializeDca {
+                asset_index: 1,
+                tranches: 2,
+                max_slippage_bps: 3,
+            },
+            TokenInstruction::DepositDca { amount: 1 },
+            TokenInstruction::ExecuteDca {
+                minimum_amount_out: 1,
+            },
+            TokenInstruction::WithdrawDca { usdc: 1, asset: 2 },
+            TokenInstruction::RequestWithdraw { amount: 1 },
+            TokenInstruction::SetWithdrawDelay {
+                delay_slots: 1,
+                bypass: false,
+            },
+        ];
+        for check in checks {
+            let packed = check.pack();
+            for len in 0..packed.len() {
+                assert_eq!(
+                    TokenInstruction::unpack(&packed[..len]),
+                    Err(TokenError::InvalidInstruction.into()),
+                    "{:?} truncated to {} bytes",
+                    check,
+                    len
+                );
+            }
+            assert_eq!(TokenInstruction::unpack(&packed), Ok(check));
+        }
+        assert_eq!(
+            TokenInstruction::unpack(&[]),
+            Err(TokenError::InvalidInstruction.into())
+        );
+        assert_eq!(
+            TokenInstruction::unpack(&[44]),
+            Err(TokenError::InvalidInstruction.into())
+        );
     }
 
@@ -4,10 +4,13 @@
 //! An ERC20-like Token program for the Solana blockchain
 
 pub mod error;
+pub mod events;
 pub mod instruction;
 pub mod native_mint;
+pub mod oracle;
 pub mod processor;
 pub mod state;
+pub mod swap;
 
 #[cfg(not(feature = "no-entrypoint"))]
 mod entrypoint;
@@ -26,4 +29,4 @@ pub fn amount_to_ui_amount(amount: u64, decimals: u8) -> f64 {
     amount as f64 / 10_usize.pow(decimals as u32) as f64
 }
 
-solana_program::declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+solana_program::declare_id!("8gxHdFmdbkwEW1rjvNiGtZBhkK8jQANQQ8XHiUhgmrRz");
@@ -0,0 +1,199 @@
+//! Price accounts of the Pyth oracle, as laid out by version 2 of its program
+
+use crate::error::TokenError;
+use arrayref::{array_ref, array_refs};
+use solana_program::program_error::ProgramError;
+use std::convert::TryFrom;
+
+/// Magic number starting every Pyth account.
+pub const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+
+/// Layout version of the Pyth accounts read by this program.
+pub const PYTH_VERSION_2: u32 = 2;
+
+/// Account type of a Pyth price account.
+pub const PYTH_PRICE_ACCOUNT_TYPE: u32 = 3;
+
+/// Status of an aggregate price that is currently trading.
+pub const PYTH_STATUS_TRADING: u32 = 1;
+
+/// Length of the leading part of a price account read by this program, up to
+/// and including the aggregate price.
+pub const PRICE_ACCOUNT_HEADER_LEN: usize = 240;
+
+/// Number of slots after which a published price is considered stale.
+pub const MAX_PRICE_AGE_SLOTS: u64 = 25;
+
+/// Aggregate price read from a price account.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OraclePrice {
+    /// The price, scaled by `10^expo`.
+    pub price: u64,
+    /// The exponent of the price.
+    pub expo: i32,
+    /// The slot the price was published at.
+    pub publish_slot: u64,
+}
+impl OraclePrice {
+    /// Returns the value of `amount` base units of a mint with `decimals`,
+    /// in base units of the quote mint with `quote_decimals`.
+    pub fn quote(&self, amount: u64, decimals: u8, quote_decimals: u8) -> Option<u64> {
+        let value = (amount as u128).checked_mul(self.price as u128)?;
+        let exponent = self.expo + quote_decimals as i32 - decimals as i32;
+        let scale = 10u128.checked_pow(exponent.unsigned_abs())?;
+        let value = if exponent >= 0 {
+            value.checked_mul(scale)?
+        } else {
+            value / scale
+        };
+        u64::try_from(value).ok()
+    }
+
+    /// Returns the amount in base units of a mint with `decimals` worth
+    /// `value` base units of the quote mint with `quote_decimals`, the inverse
+    /// of `quote`.
+    pub fn amount_for(&self, value: u64, decimals: u8, quote_decimals: u8) -> Option<u64> {
+        let exponent = decimals as i32 - quote_decimals as i32 - self.expo;
+        let scale = 10u128.checked_pow(exponent.unsigned_abs())?;
+        let amount = if exponent >= 0 {
+            (value as u128).checked_mul(scale)?.checked_div(self.price as u128)?
+        } else {
+            (value as u128).checked_div(scale.checked_mul(self.price as u128)?)?
+        };
+        u64::try_from(amount).ok()
+    }
+}
+
+/// Reads the aggregate price of a Pyth price account, failing if the price is
+/// not trading or was published more than `MAX_PRICE_AGE_SLOTS` before
+/// `current_slot`.
+pub fn unpack_price(data: &[u8], current_slot: u64) -> Result<OraclePrice, ProgramError> {
+    let data = data
+        .get(..PRICE_ACCOUNT_HEADER_LEN)
+        .ok_or(TokenError::InvalidOracle)?;
+    let data = array_ref![data, 0, PRICE_ACCOUNT_HEADER_LEN];
+    #[allow(clippy::ptr_offset_with_cast)]
+    let (magic, version, account_type, _, expo, _, price, _, status, _, publish_slot) =
+        array_refs![data, 4, 4, 4, 8, 4, 184, 8, 8, 4, 4, 8];
+    if u32::from_le_bytes(*magic) != PYTH_MAGIC
+        || u32::from_le_bytes(*version) != PYTH_VERSION_2
+        || u32::from_le_bytes(*account_type) != PYTH_PRICE_ACCOUNT_TYPE
+    {
+        return Err(TokenError::InvalidOracle.into());
+    }
+    let price = i64::from_le_bytes(*price);
+    let publish_slot = u64::from_le_bytes(*publish_slot);
+    if u32::from_le_bytes(*status) != PYTH_STATUS_TRADING || price <= 0 {
+        return Err(TokenError::InvalidOracle.into());
+    }
+    if current_slot.saturating_sub(publish_slot) > MAX_PRICE_AGE_SLOTS {
+        return Err(TokenError::StaleOraclePrice.into());
+    }
+    Ok(OraclePrice {
+        price: price as u64,
+        expo: i32::from_le_bytes(*expo),
+        publish_slot,
+    })
+}
+
+/// Builds the leading part of a price account.
+#[cfg(test)]
+pub(crate) fn price_account_data(
+    price: i64,
+    expo: i32,
+    status: u32,
+    publish_slot: u64,
+) -> Vec<u8> {
+    let mut data = vec![0; PRICE_ACCOUNT_HEADER_LEN];
+    data[0..4].copy_from_slice(&PYTH_MAGIC.to_le_bytes());
+    data[4..8].copy_from_slice(&PYTH_VERSION_2.to_le_bytes());
+    data[8..12].copy_from_slice(&PYTH_PRICE_ACCOUNT_TYPE.to_le_bytes());
+    data[20..24].copy_from_slice(&expo.to_le_bytes());
+    data[208..216].copy_from_slice(&price.to_le_bytes());
+    data[224..228].copy_from_slice(&status.to_le_bytes());
+    data[232..240].copy_from_slice(&publish_slot.to_le_bytes());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpack_price() {
+        let data = price_account_data(2_500_000_000, -8, PYTH_STATUS_TRADING, 100);
+        assert_eq!(
+            unpack_price(&data, 110).unwrap(),
+            OraclePrice {
+                price: 2_500_000_000,
+                expo: -8,
+                publish_slot: 100,
+            }
+        );
+        assert_eq!(
+            unpack_price(&data, 100 + MAX_PRICE_AGE_SLOTS + 1),
+            Err(TokenError::StaleOraclePrice.into())
+        );
+        assert_eq!(
+            unpack_price(&data[..PRICE_ACCOUNT_HEADER_LEN - 1], 100),
+            Err(TokenError::InvalidOracle.into())
+        );
+        let data = price_account_data(2_500_000_000, -8, 0, 100);
+        assert_eq!(unpack_price(&data, 100), Err(TokenError::InvalidOracle.into()));
+        let data = price_account_data(-1, -8, PYTH_STATUS_TRADING, 100);
+        assert_eq!(unpack_price(&data, 100), Err(TokenError::InvalidOracle.into()));
+        let mut data = price_account_data(1, -8, PYTH_STATUS_TRADING, 100);
+        data[8] = 2;
+        assert_eq!(unpack_price(&data, 100), Err(TokenError::InvalidOracle.into()));
+    }
+
+    #[test]
+    fn test_quote() {
+        // 25.00000000 per asset with 9 decimals, quoted with 6 decimals
+        let price = OraclePrice {
+            price: 2_500_000_000,
+            expo: -8,
+            publish_slot: 0,
+        };
+        assert_eq!(price.quote(2_000_000_000, 9, 6), Some(50_000_000));
+        assert_eq!(price.quote(1, 9, 6), Some(0));
+        let price = OraclePrice {
+            price: 3,
+            expo: 2,
+            publish_slot: 0,
+        };
+        assert_eq!(price.quote(5, 0, 1), Some(15_000));
+        assert_eq!(price.quote(u64::MAX, 0, 6), None);
+    }
+
+    #[test]
+    fn test_amount_for() {
+        // 25.00000000 per asset with 9 decimals, paid with 6 decimals
+        let price = OraclePrice {
+            price: 2_500_000_000,
+            expo: -8,
+            publish_slot: 0,
+        };
+        assert_eq!(price.amount_for(50_000_000, 9, 6), Some(2_000_000_000));
+        assert_eq!(price.amount_for(1, 9, 6), Some(40));
+        let price = OraclePrice {
+            price: 3,
+            expo: 2,
+            publish_slot: 0,
+        };
+        assert_eq!(price.amount_for(15_000, 0, 1), Some(5));
+        assert_eq!(price.amount_for(1, 0, 1), Some(0));
+        let price = OraclePrice {
+            price: 1,
+            expo: -12,
+            publish_slot: 0,
+        };
+        assert_eq!(price.amount_for(u64::MAX, 18, 0), None);
+        let price = OraclePrice {
+            price: 0,
+            expo: 0,
+            publish_slot: 0,
+        };
+        assert_eq!(price.amount_for(1, 0, 0), None);
+    }
+}
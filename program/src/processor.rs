@@ -2,24 +2,55 @@
 
 use crate::{
     error::TokenError,
-    instruction::{is_valid_signer_index, AuthorityType, TokenInstruction, MAX_SIGNERS},
-    state::{Account, AccountState, Mint, Multisig , Portfolio , UserPortfolio},
+    events::{
+        DcaExecuted, DepositEvent, FeeCollected, PortfolioCreated, ProfitHarvested, RebalanceEvent,
+        RewardsClaimed, StopLossTriggered, TokenEvent, UserPortfolioCreated, WithdrawEvent,
+    },
+    instruction::{
+        find_asset_entry_with_program_id, find_dca_config_with_program_id,
+        find_dca_position_with_program_id, find_deposit_limits_with_program_id,
+        find_deposit_record_with_program_id, find_hedge_config_with_program_id,
+        find_hedge_epoch_with_program_id, find_position_with_program_id,
+        find_program_config_with_program_id,
+        find_stop_loss_with_program_id, find_take_profit_with_program_id,
+        find_vault_authority_with_program_id, find_withdraw_ticket_with_program_id,
+        is_valid_signer_index, transfer, AuthorityType,
+        TokenInstruction, ASSET_ENTRY_SEED, BASIS_POINTS, DCA_CONFIG_SEED, DCA_POSITION_SEED,
+        DEPOSIT_LIMITS_SEED,
+        DEPOSIT_RECORD_SEED, HEDGE_CONFIG_SEED, HEDGE_EPOCH_SEED, MAX_SIGNERS, POSITION_SEED,
+        PROGRAM_CONFIG_SEED,
+        STOP_LOSS_SEED, TAKE_PROFIT_SEED, VAULT_AUTHORITY_SEED, WITHDRAW_TICKET_SEED,
+    },
+    oracle::{unpack_price, OraclePrice},
+    state::{
+        Account, AccountState, AccountType, AssetEntry, AssetKind, DcaConfig,
+        DcaPosition, DepositLimits, DepositRecord, HedgeConfig, HedgeEpoch, Mint, Multisig, Portfolio,
+        Position, ProgramConfig,
+        StateAccount, StopLoss, TakeProfit, UserPortfolio, WithdrawTicket, ACCOUNT_HEADER_LEN,
+        HEDGE_PRECISION, LEGACY_ACCOUNT_LEN, LEGACY_MINT_LEN,
+        PAUSE_DEPOSIT, PAUSE_SWAP, PAUSE_WITHDRAW, REWARD_PRECISION, STATE_VERSION, DCA_PERIOD_SLOTS,
+    },
+    swap::{
+        swap as swap_instruction, unpack_token_account, unpack_token_mint_decimals, SwapState,
+    },
 };
+use arrayref::{array_ref, array_refs};
 use num_traits::FromPrimitive;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable,
     decode_error::DecodeError,
     entrypoint::ProgramResult,
     msg,
-    instruction::{AccountMeta, Instruction},
-    program::invoke_signed,
+    program::{invoke, invoke_signed},
     program_error::{PrintProgramError, ProgramError},
     program_option::COption,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
-    sysvar::{rent::Rent, Sysvar},
-    //system_instruction,
+    system_instruction,
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
+use std::convert::TryFrom;
 
 /// Program state handler.
 pub struct Processor {}
@@ -30,22 +61,13 @@ impl Processor {
         decimals: u8,
         mint_authority: Pubkey,
         freeze_authority: COption<Pubkey>,
-        mint_id_asset: COption<Pubkey>,
-        pubkey_swap: COption<Pubkey>,
-        _program_id: &Pubkey
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let mint_info = next_account_info(account_info_iter)?;
         let mint_data_len = mint_info.data_len();
         let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
 
-        let mut mint = 
-        match Mint::unpack_unchecked(&mint_info.data.borrow()) {
-            Ok(_a) => _a ,
-            Err(_a) => {
-                panic!("exit")
-            }
-        };
+        let mut mint = Mint::unpack_unchecked(&mint_info.data.borrow())?;
         if mint.is_initialized {
             return Err(TokenError::AlreadyInUse.into());
         }
@@ -58,1422 +80,9022 @@ impl Processor {
         mint.decimals = decimals;
         mint.is_initialized = true;
         mint.freeze_authority = freeze_authority;
-        mint.mint_id_asset = mint_id_asset;
-        mint.pubkey_swap = pubkey_swap;
 
         Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
 
-      
         Ok(())
     }
 
-    fn _process_initialize_account(
+    /// Processes an [InitializeHedgedMint](enum.TokenInstruction.html) instruction.
+    pub fn process_initialize_hedged_mint(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
-        owner: Option<&Pubkey>,
+        target_ratio_bps: u16,
+        deposit_fee_bps: u16,
+        withdraw_fee_bps: u16,
     ) -> ProgramResult {
-
-       let account_info_iter = &mut accounts.iter();
-        let new_account_info = next_account_info(account_info_iter)?;
+        let account_info_iter = &mut accounts.iter();
+        let hedge_config_info = next_account_info(account_info_iter)?;
         let mint_info = next_account_info(account_info_iter)?;
-        let owner = if let Some(owner) = owner {
-            owner
-        } else {
-            next_account_info(account_info_iter)?.key
-        };
-        let new_account_info_data_len = new_account_info.data_len();
+        let payer_info = next_account_info(account_info_iter)?;
+        let asset_vault_info = next_account_info(account_info_iter)?;
+        let stable_vault_info = next_account_info(account_info_iter)?;
+        let fee_account_info = next_account_info(account_info_iter)?;
+        let swap_info = next_account_info(account_info_iter)?;
+        let asset_entry_info = next_account_info(account_info_iter)?;
+        let stable_entry_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
         let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let authority_info = next_account_info(account_info_iter)?;
 
-        let mut account = Account::unpack_unchecked(&new_account_info.data.borrow())?;
-        if account.is_initialized() {
-            return Err(TokenError::AlreadyInUse.into());
+        if target_ratio_bps > BASIS_POINTS
+            || deposit_fee_bps > BASIS_POINTS
+            || withdraw_fee_bps > BASIS_POINTS
+        {
+            return Err(TokenError::InvalidBasisPoints.into());
         }
 
-        if !rent.is_exempt(new_account_info.lamports(), new_account_info_data_len) {
-            return Err(TokenError::NotRentExempt.into());
+        if mint_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mint = Mint::unpack(&mint_info.data.borrow())?;
+        match mint.mint_authority {
+            COption::Some(mint_authority) => Self::validate_owner(
+                program_id,
+                &mint_authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::FixedSupply.into()),
         }
 
-        if *mint_info.key != crate::native_mint::id() {
-            let _ = Mint::unpack(&mint_info.data.borrow_mut())
-                .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+        let (hedge_config_key, bump) =
+            find_hedge_config_with_program_id(program_id, mint_info.key);
+        if *hedge_config_info.key != hedge_config_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let (vault_authority, vault_authority_bump) =
+            find_vault_authority_with_program_id(program_id, mint_info.key);
+
+        let swap = SwapState::unpack(&swap_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidSwapState))?;
+        let asset_vault = unpack_token_account(
+            &swap.token_program_id,
+            asset_vault_info.owner,
+            &asset_vault_info.data.borrow(),
+        )?;
+        let stable_vault = unpack_token_account(
+            &swap.token_program_id,
+            stable_vault_info.owner,
+            &stable_vault_info.data.borrow(),
+        )?;
+        if asset_vault.owner != vault_authority || stable_vault.owner != vault_authority {
+            return Err(TokenError::IncorrectVaultOwner.into());
+        }
+        if swap.reserve_for_mint(&asset_vault.mint).is_none() {
+            return Err(TokenError::AssetMintMismatch.into());
+        }
+        if stable_vault.mint == asset_vault.mint
+            || swap.reserve_for_mint(&stable_vault.mint).is_none()
+        {
+            return Err(TokenError::StableMintMismatch.into());
+        }
+        let asset_entry = Self::unpack_asset_entry(program_id, asset_entry_info, &asset_vault.mint)?;
+        if asset_entry.kind != AssetKind::Asset {
+            return Err(TokenError::IncorrectAssetKind.into());
+        }
+        let stable_entry =
+            Self::unpack_asset_entry(program_id, stable_entry_info, &stable_vault.mint)?;
+        if stable_entry.kind != AssetKind::Stablecoin {
+            return Err(TokenError::IncorrectAssetKind.into());
+        }
+        if *swap_info.key != asset_entry.swap_pool || *swap_info.owner != asset_entry.swap_program_id
+        {
+            return Err(TokenError::SwapPoolMismatch.into());
+        }
+        let fee_account = unpack_token_account(
+            &swap.token_program_id,
+            fee_account_info.owner,
+            &fee_account_info.data.borrow(),
+        )?;
+        if fee_account.mint != asset_vault.mint {
+            return Err(TokenError::MintMismatch.into());
         }
 
-        account.mint = *mint_info.key;
-        account.owner = *owner;
-        account.delegate = COption::None;
-        account.delegated_amount = 0;
-        account.state = AccountState::Initialized;
-        account.amount = 0;
-        account.usdc = 0;
-        account.asset = 0;   
-        if *mint_info.key == crate::native_mint::id() {
-            let rent_exempt_reserve = rent.minimum_balance(new_account_info_data_len);
-            account.is_native = COption::Some(rent_exempt_reserve);
-            account.amount = new_account_info
-                .lamports()
-                .checked_sub(rent_exempt_reserve)
-                .ok_or(TokenError::Overflow)?;
-        } else {
-            account.is_native = COption::None;
-            account.amount = 0;
-            account.usdc = 0;
-            account.asset = 0;   
-        };
+        if hedge_config_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                HedgeConfig::LEN,
+                program_id,
+                system_program_info,
+                hedge_config_info,
+                &[HEDGE_CONFIG_SEED, mint_info.key.as_ref(), &[bump]],
+            )?;
+        }
+        if hedge_config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let hedge_config = HedgeConfig::unpack_unchecked(&hedge_config_info.data.borrow())?;
+        if hedge_config.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        if !rent.is_exempt(hedge_config_info.lamports(), hedge_config_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
 
-        Account::pack(account, &mut new_account_info.data.borrow_mut())?;
+        let hedge_config = HedgeConfig {
+            is_initialized: true,
+            mint: *mint_info.key,
+            authority: *authority_info.key,
+            asset_mint: asset_vault.mint,
+            stable_mint: stable_vault.mint,
+            swap_program_id: *swap_info.owner,
+            swap_pool: *swap_info.key,
+            asset_vault: *asset_vault_info.key,
+            stable_vault: *stable_vault_info.key,
+            fee_account: *fee_account_info.key,
+            target_ratio_bps,
+            deposit_fee_bps,
+            withdraw_fee_bps,
+            vault_authority_bump,
+            bump,
+            asset_scale: HEDGE_PRECISION,
+            ..HedgeConfig::default()
+        };
+        HedgeConfig::pack(hedge_config, &mut hedge_config_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    /// Processes an [InitializeAccount](enum.TokenInstruction.html) instruction.
-    pub fn process_initialize_account(accounts: &[AccountInfo]) -> ProgramResult {
-        Self::_process_initialize_account(accounts, None)
-    }
-
-    /// Processes an [InitializeAccount2](enum.TokenInstruction.html) instruction.
-    pub fn process_initialize_account2(accounts: &[AccountInfo], owner: Pubkey) -> ProgramResult {
-        Self::_process_initialize_account(accounts, Some(&owner))
-    }
-
-    /// Processes a [InitializeMultisig](enum.TokenInstruction.html) instruction.
-    pub fn process_initialize_multisig(accounts: &[AccountInfo], m: u8) -> ProgramResult {
+    /// Processes an [InitializeProgramConfig](enum.TokenInstruction.html) instruction.
+    pub fn process_initialize_program_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        protocol_fee_bps: u16,
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let multisig_info = next_account_info(account_info_iter)?;
-        let multisig_info_data_len = multisig_info.data_len();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let guardian_info = next_account_info(account_info_iter)?;
+        let protocol_fee_owner_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+        let upgrade_authority_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
         let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
 
-        let mut multisig = Multisig::unpack_unchecked(&multisig_info.data.borrow())?;
-        if multisig.is_initialized {
-            return Err(TokenError::AlreadyInUse.into());
+        if protocol_fee_bps > BASIS_POINTS {
+            return Err(TokenError::InvalidBasisPoints.into());
         }
+        Self::validate_upgrade_authority(program_id, program_data_info, upgrade_authority_info)?;
 
-        if !rent.is_exempt(multisig_info.lamports(), multisig_info_data_len) {
-            return Err(TokenError::NotRentExempt.into());
+        let (program_config_key, bump) = find_program_config_with_program_id(program_id);
+        if *program_config_info.key != program_config_key {
+            return Err(ProgramError::InvalidSeeds);
         }
-
-        let signer_infos = account_info_iter.as_slice();
-        multisig.m = m;
-        multisig.n = signer_infos.len() as u8;
-        if !is_valid_signer_index(multisig.n as usize) {
-            return Err(TokenError::InvalidNumberOfProvidedSigners.into());
+        if program_config_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                ProgramConfig::LEN,
+                program_id,
+                system_program_info,
+                program_config_info,
+                &[PROGRAM_CONFIG_SEED, &[bump]],
+            )?;
         }
-        if !is_valid_signer_index(multisig.m as usize) {
-            return Err(TokenError::InvalidNumberOfRequiredSigners.into());
+        if program_config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
         }
-        for (i, signer_info) in signer_infos.iter().enumerate() {
-            multisig.signers[i] = *signer_info.key;
+        let program_config = ProgramConfig::unpack_unchecked(&program_config_info.data.borrow())?;
+        if program_config.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        if !rent.is_exempt(program_config_info.lamports(), program_config_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
         }
-        multisig.is_initialized = true;
 
-        Multisig::pack(multisig, &mut multisig_info.data.borrow_mut())?;
+        let program_config = ProgramConfig {
+            is_initialized: true,
+            admin: *admin_info.key,
+            guardian: *guardian_info.key,
+            paused: 0,
+            protocol_fee_bps,
+            protocol_fee_owner: *protocol_fee_owner_info.key,
+            bump,
+            withdraw_delay_slots: 0,
+            bypass_withdraw_delay: false,
+        };
+        ProgramConfig::pack(program_config, &mut program_config_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    /// Processes a [Transfer](enum.TokenInstruction.html) instruction.
-    pub fn process_transfer(
+    /// Processes a [SetProgramConfig](enum.TokenInstruction.html) instruction.
+    pub fn process_set_program_config(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        amount: u64,
-        expected_decimals: Option<u8>,
+        protocol_fee_bps: u16,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-
-        let source_account_info = next_account_info(account_info_iter)?;
-
-        let expected_mint_info = if let Some(expected_decimals) = expected_decimals {
-            Some((next_account_info(account_info_iter)?, expected_decimals))
-        } else {
-            None
-        };
-
-        let dest_account_info = next_account_info(account_info_iter)?;
-        let authority_info = next_account_info(account_info_iter)?;
-
-        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
-        let mut dest_account = Account::unpack(&dest_account_info.data.borrow())?;
-
-        if source_account.is_frozen() || dest_account.is_frozen() {
-            return Err(TokenError::AccountFrozen.into());
-        }
-        if source_account.amount < amount {
-            return Err(TokenError::InsufficientFunds.into());
-        }
-        if source_account.mint != dest_account.mint {
-            return Err(TokenError::MintMismatch.into());
+        let program_config_info = next_account_info(account_info_iter)?;
+        let new_admin_info = next_account_info(account_info_iter)?;
+        let new_guardian_info = next_account_info(account_info_iter)?;
+        let new_protocol_fee_owner_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        if protocol_fee_bps > BASIS_POINTS {
+            return Err(TokenError::InvalidBasisPoints.into());
         }
+        let mut program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        Self::validate_owner(
+            program_id,
+            &program_config.admin,
+            admin_info,
+            account_info_iter.as_slice(),
+        )?;
 
-        if let Some((mint_info, expected_decimals)) = expected_mint_info {
-            if source_account.mint != *mint_info.key {
-                return Err(TokenError::MintMismatch.into());
-            }
+        program_config.admin = *new_admin_info.key;
+        program_config.guardian = *new_guardian_info.key;
+        program_config.protocol_fee_bps = protocol_fee_bps;
+        program_config.protocol_fee_owner = *new_protocol_fee_owner_info.key;
+        ProgramConfig::pack(program_config, &mut program_config_info.data.borrow_mut())?;
 
-            let mint = Mint::unpack(&mint_info.data.borrow_mut())?;
-            if expected_decimals != mint.decimals {
-                return Err(TokenError::MintDecimalsMismatch.into());
-            }
-        }
+        Ok(())
+    }
 
-        let self_transfer = source_account_info.key == dest_account_info.key;
+    /// Processes a [SetPause](enum.TokenInstruction.html) instruction.
+    pub fn process_set_pause(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        paused: u32,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
 
-        match source_account.delegate {
-            COption::Some(ref delegate) if authority_info.key == delegate => {
-                Self::validate_owner(
-                    program_id,
-                    delegate,
-                    authority_info,
-                    account_info_iter.as_slice(),
-                )?;
-                if source_account.delegated_amount < amount {
-                    return Err(TokenError::InsufficientFunds.into());
-                }
-                if !self_transfer {
-                    source_account.delegated_amount = source_account
-                        .delegated_amount
-                        .checked_sub(amount)
-                        .ok_or(TokenError::Overflow)?;
-                    if source_account.delegated_amount == 0 {
-                        source_account.delegate = COption::None;
-                    }
-                }
-            }
-            _ => Self::validate_owner(
+        let mut program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        if *authority_info.key == program_config.admin {
+            Self::validate_owner(
                 program_id,
-                &source_account.owner,
+                &program_config.admin,
                 authority_info,
                 account_info_iter.as_slice(),
-            )?,
-        };
-
-        // This check MUST occur just before the amounts are manipulated
-        // to ensure self-transfers are fully validated
-        if self_transfer {
-            return Ok(());
+            )?;
+        } else if *authority_info.key == program_config.guardian {
+            if !authority_info.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if paused & program_config.paused != program_config.paused {
+                return Err(TokenError::GuardianCannotUnpause.into());
+            }
+        } else {
+            return Err(TokenError::OwnerMismatch.into());
         }
 
-        let  value :u64  =  (amount.checked_mul(100)).unwrap().checked_div(source_account.amount.into()).unwrap() ;
-        let  amount_usdc_transfered  = source_account.usdc.checked_mul(value).unwrap().checked_div(100).unwrap();
-        let  amount_asset_transfered = source_account.asset.checked_mul(value).unwrap().checked_div(100).unwrap();
-
-        source_account.amount = source_account
-            .amount
-            .checked_sub(amount)
-            .ok_or(TokenError::Overflow)?;
-        dest_account.amount = dest_account
-            .amount
-            .checked_add(amount)
-            .ok_or(TokenError::Overflow)?;
+        program_config.paused = paused;
+        ProgramConfig::pack(program_config, &mut program_config_info.data.borrow_mut())?;
 
-        msg!("source usdc before transfer =  {:?} ", source_account.usdc);
+        Ok(())
+    }
 
-        source_account.usdc = source_account
-            .usdc
-            .checked_sub(amount_usdc_transfered)
-            .ok_or(TokenError::Overflow)?;
-     
-        
-        dest_account.usdc = dest_account
-            .usdc
-            .checked_add(amount_usdc_transfered)
-            .ok_or(TokenError::Overflow)?;
+    /// Processes a [RegisterAsset](enum.TokenInstruction.html) instruction.
+    pub fn process_register_asset(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        kind: AssetKind,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let asset_entry_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
+        let swap_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let admin_info = next_account_info(account_info_iter)?;
 
-        source_account.asset = source_account
-            .asset
-            .checked_sub(amount_asset_transfered)
-            .ok_or(TokenError::Overflow)?;
-        dest_account.asset = dest_account
-            .asset
-            .checked_add(amount_asset_transfered)
-            .ok_or(TokenError::Overflow)?;
+        let program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        Self::validate_owner(
+            program_id,
+            &program_config.admin,
+            admin_info,
+            account_info_iter.as_slice(),
+        )?;
 
-        if source_account.is_native() {
-            let source_starting_lamports = source_account_info.lamports();
-            **source_account_info.lamports.borrow_mut() = source_starting_lamports
-                .checked_sub(amount)
-                .ok_or(TokenError::Overflow)?;
+        let (asset_entry_key, bump) = find_asset_entry_with_program_id(program_id, mint_info.key);
+        if *asset_entry_info.key != asset_entry_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let swap = SwapState::unpack(&swap_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidSwapState))?;
+        if swap.reserve_for_mint(mint_info.key).is_none() {
+            return Err(TokenError::AssetMintMismatch.into());
+        }
+        let decimals = unpack_token_mint_decimals(
+            &swap.token_program_id,
+            mint_info.owner,
+            &mint_info.data.borrow(),
+        )?;
 
-            let dest_starting_lamports = dest_account_info.lamports();
-            **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
-                .checked_add(amount)
-                .ok_or(TokenError::Overflow)?;
+        if asset_entry_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                AssetEntry::LEN,
+                program_id,
+                system_program_info,
+                asset_entry_info,
+                &[ASSET_ENTRY_SEED, mint_info.key.as_ref(), &[bump]],
+            )?;
+        }
+        if asset_entry_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let asset_entry = AssetEntry::unpack_unchecked(&asset_entry_info.data.borrow())?;
+        if asset_entry.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        if !rent.is_exempt(asset_entry_info.lamports(), asset_entry_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
         }
 
-        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
-        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+        let asset_entry = AssetEntry {
+            is_initialized: true,
+            mint: *mint_info.key,
+            kind,
+            decimals,
+            oracle: *oracle_info.key,
+            swap_program_id: *swap_info.owner,
+            swap_pool: *swap_info.key,
+            bump,
+        };
+        AssetEntry::pack(asset_entry, &mut asset_entry_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    /// Processes an [Approve](enum.TokenInstruction.html) instruction.
-    pub fn process_approve(
+    /// Processes an [UpdateAsset](enum.TokenInstruction.html) instruction.
+    pub fn process_update_asset(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        amount: u64,
-        expected_decimals: Option<u8>,
+        kind: AssetKind,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let asset_entry_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
 
-        let source_account_info = next_account_info(account_info_iter)?;
-
-        let expected_mint_info = if let Some(expected_decimals) = expected_decimals {
-            Some((next_account_info(account_info_iter)?, expected_decimals))
-        } else {
-            None
-        };
-        let delegate_info = next_account_info(account_info_iter)?;
-        let owner_info = next_account_info(account_info_iter)?;
-
-        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
+        let program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        Self::validate_owner(
+            program_id,
+            &program_config.admin,
+            admin_info,
+            account_info_iter.as_slice(),
+        )?;
 
-        if source_account.is_frozen() {
-            return Err(TokenError::AccountFrozen.into());
+        let mut asset_entry = Self::unpack_registry_entry(program_id, asset_entry_info)?;
+        let swap = SwapState::unpack(&swap_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidSwapState))?;
+        if swap.reserve_for_mint(&asset_entry.mint).is_none() {
+            return Err(TokenError::AssetMintMismatch.into());
         }
 
-        if let Some((mint_info, expected_decimals)) = expected_mint_info {
-            if source_account.mint != *mint_info.key {
-                return Err(TokenError::MintMismatch.into());
-            }
+        asset_entry.kind = kind;
+        asset_entry.oracle = *oracle_info.key;
+        asset_entry.swap_program_id = *swap_info.owner;
+        asset_entry.swap_pool = *swap_info.key;
+        AssetEntry::pack(asset_entry, &mut asset_entry_info.data.borrow_mut())?;
 
-            let mint = Mint::unpack(&mint_info.data.borrow_mut())?;
-            if expected_decimals != mint.decimals {
-                return Err(TokenError::MintDecimalsMismatch.into());
-            }
-        }
+        Ok(())
+    }
+
+    /// Processes a [DeregisterAsset](enum.TokenInstruction.html) instruction.
+    pub fn process_deregister_asset(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let asset_entry_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
 
+        let program_config = Self::unpack_program_config(program_id, program_config_info)?;
         Self::validate_owner(
             program_id,
-            &source_account.owner,
-            owner_info,
+            &program_config.admin,
+            admin_info,
             account_info_iter.as_slice(),
         )?;
+        let asset_entry = Self::unpack_registry_entry(program_id, asset_entry_info)?;
 
-        source_account.delegate = COption::Some(*delegate_info.key);
-        source_account.delegated_amount = amount;
-
-        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        let dest_starting_lamports = dest_account_info.lamports();
+        **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
+            .checked_add(asset_entry_info.lamports())
+            .ok_or(TokenError::Overflow)?;
+        **asset_entry_info.lamports.borrow_mut() = 0;
+        AssetEntry::pack(
+            AssetEntry {
+                is_initialized: false,
+                ..asset_entry
+            },
+            &mut asset_entry_info.data.borrow_mut(),
+        )?;
 
         Ok(())
     }
 
-
-
-    /// Processes an [Approve](enum.TokenInstruction.html) instruction.
-    pub fn process_approve_User_Portfolio(
+    /// Processes a [SetDepositLimits](enum.TokenInstruction.html) instruction.
+    pub fn process_set_deposit_limits(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        amount: u64,
-      //  expected_decimals: Option<u8>,
+        max_supply: u64,
+        max_deposit: u64,
+        max_owner_deposit: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
+        let deposit_limits_info = next_account_info(account_info_iter)?;
+        let target_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let authority_info = next_account_info(account_info_iter)?;
 
-        let source_account_info = next_account_info(account_info_iter)?;
-
-       /* let expected_mint_info = if let Some(expected_decimals) = expected_decimals {
-            Some((next_account_info(account_info_iter)?, expected_decimals))
-        } else {
-            None
-        };*/
-        let delegate_info = next_account_info(account_info_iter)?;
-        let owner_info = next_account_info(account_info_iter)?;
-
-        let mut source_account = UserPortfolio::unpack(&source_account_info.data.borrow())?;
-
-        /*if source_account.is_frozen() {
-            return Err(TokenError::AccountFrozen.into());
-        }*/
-
-        /*if let Some((mint_info, expected_decimals)) = expected_mint_info {
-            if source_account.mint != *mint_info.key {
-                return Err(TokenError::MintMismatch.into());
+        if target_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let authority = if target_info.data_len() == Mint::LEN {
+            match Mint::unpack(&target_info.data.borrow())?.mint_authority {
+                COption::Some(mint_authority) => mint_authority,
+                COption::None => return Err(TokenError::FixedSupply.into()),
             }
-
-            let mint = Mint::unpack(&mint_info.data.borrow_mut())?;
-            if expected_decimals != mint.decimals {
-                return Err(TokenError::MintDecimalsMismatch.into());
+        } else {
+            match StateAccount::unpack(&target_info.data.borrow())? {
+                StateAccount::Portfolio(portfolio) => portfolio.creator_portfolio,
+                _ => return Err(ProgramError::InvalidAccountData),
             }
-        }*/
-
+        };
         Self::validate_owner(
             program_id,
-            &source_account.owner,
-            owner_info,
+            &authority,
+            authority_info,
             account_info_iter.as_slice(),
         )?;
 
-        source_account.delegate = *delegate_info.key;
-        source_account.delegated_amount = amount;
+        let (deposit_limits_key, bump) =
+            find_deposit_limits_with_program_id(program_id, target_info.key);
+        if *deposit_limits_info.key != deposit_limits_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if deposit_limits_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                DepositLimits::LEN,
+                program_id,
+                system_program_info,
+                deposit_limits_info,
+                &[DEPOSIT_LIMITS_SEED, target_info.key.as_ref(), &[bump]],
+            )?;
+        }
+        if deposit_limits_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !rent.is_exempt(deposit_limits_info.lamports(), deposit_limits_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+        let deposit_limits = DepositLimits::unpack_unchecked(&deposit_limits_info.data.borrow())?;
 
-        UserPortfolio::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        let deposit_limits = DepositLimits {
+            is_initialized: true,
+            target: *target_info.key,
+            max_supply,
+            max_deposit,
+            max_owner_deposit,
+            supply: deposit_limits.supply,
+            bump,
+        };
+        DepositLimits::pack(deposit_limits, &mut deposit_limits_info.data.borrow_mut())?;
 
         Ok(())
     }
 
+    /// Processes an [InitializeDepositRecord](enum.TokenInstruction.html) instruction.
+    pub fn process_initialize_deposit_record(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let deposit_record_info = next_account_info(account_info_iter)?;
+        let target_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
 
+        if target_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let (deposit_record_key, bump) =
+            find_deposit_record_with_program_id(program_id, target_info.key, owner_info.key);
+        if *deposit_record_info.key != deposit_record_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if deposit_record_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                DepositRecord::LEN,
+                program_id,
+                system_program_info,
+                deposit_record_info,
+                &[
+                    DEPOSIT_RECORD_SEED,
+                    target_info.key.as_ref(),
+                    owner_info.key.as_ref(),
+                    &[bump],
+                ],
+            )?;
+        }
+        if deposit_record_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let deposit_record = DepositRecord::unpack_unchecked(&deposit_record_info.data.borrow())?;
+        if deposit_record.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        if !rent.is_exempt(deposit_record_info.lamports(), deposit_record_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
 
-    /// Processes an [Revoke](enum.TokenInstruction.html) instruction.
-    pub fn process_revoke(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let source_account_info = next_account_info(account_info_iter)?;
+        let deposit_record = DepositRecord {
+            is_initialized: true,
+            target: *target_info.key,
+            owner: *owner_info.key,
+            deposited: 0,
+            bump,
+        };
+        DepositRecord::pack(deposit_record, &mut deposit_record_info.data.borrow_mut())?;
 
-        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
+        Ok(())
+    }
 
-        let owner_info = next_account_info(account_info_iter)?;
+    /// Processes a [SetStopLoss](enum.TokenInstruction.html) instruction.
+    pub fn process_set_stop_loss(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        stop_price: u64,
+        trailing_bps: u16,
+        max_slippage_bps: u16,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let stop_loss_info = next_account_info(account_info_iter)?;
+        let hedge_config_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let asset_entry_info = next_account_info(account_info_iter)?;
+        let stable_entry_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let hedge_epoch_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
 
-        if source_account.is_frozen() {
-            return Err(TokenError::AccountFrozen.into());
+        if trailing_bps > BASIS_POINTS || max_slippage_bps > BASIS_POINTS {
+            return Err(TokenError::InvalidBasisPoints.into());
         }
 
+        let hedge_config = Self::unpack_hedge_config(program_id, hedge_config_info, mint_info.key)?;
         Self::validate_owner(
             program_id,
-            &source_account.owner,
-            owner_info,
+            &hedge_config.authority,
+            authority_info,
             account_info_iter.as_slice(),
         )?;
+        let asset_entry =
+            Self::unpack_asset_entry(program_id, asset_entry_info, &hedge_config.asset_mint)?;
+        let stable_entry =
+            Self::unpack_asset_entry(program_id, stable_entry_info, &hedge_config.stable_mint)?;
+
+        let (stop_loss_key, bump) = find_stop_loss_with_program_id(program_id, mint_info.key);
+        if *stop_loss_info.key != stop_loss_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if stop_loss_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                StopLoss::LEN,
+                program_id,
+                system_program_info,
+                stop_loss_info,
+                &[STOP_LOSS_SEED, mint_info.key.as_ref(), &[bump]],
+            )?;
+        }
+        if stop_loss_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !rent.is_exempt(stop_loss_info.lamports(), stop_loss_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+        let stop_loss = StopLoss::unpack_unchecked(&stop_loss_info.data.borrow())?;
+        if hedge_config.epoch > 0 {
+            Self::archive_hedge_epoch(
+                program_id,
+                &hedge_config,
+                hedge_epoch_info,
+                payer_info,
+                system_program_info,
+                rent,
+            )?;
+        }
 
-        source_account.delegate = COption::None;
-        source_account.delegated_amount = 0;
-
-        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        let stop_loss = StopLoss {
+            is_initialized: true,
+            mint: *mint_info.key,
+            oracle: asset_entry.oracle,
+            stop_price,
+            trailing_bps,
+            max_slippage_bps,
+            asset_decimals: asset_entry.decimals,
+            stable_decimals: stable_entry.decimals,
+            high_price: 0,
+            triggered: false,
+            bump,
+            ..stop_loss
+        };
+        StopLoss::pack(stop_loss, &mut stop_loss_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    /// Processes a [SetAuthority](enum.TokenInstruction.html) instruction.
-    pub fn process_set_authority(
+    /// Records the `stable_per_base` the last epoch of `hedge_config` closed
+    /// at in its `HedgeEpoch`, unless already recorded, for the positions
+    /// still settled in that epoch once the next one closes.
+    fn archive_hedge_epoch<'a>(
+        program_id: &Pubkey,
+        hedge_config: &HedgeConfig,
+        hedge_epoch_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        rent: &Rent,
+    ) -> ProgramResult {
+        let epoch = hedge_config.epoch - 1;
+        let (hedge_epoch_key, bump) =
+            find_hedge_epoch_with_program_id(program_id, &hedge_config.mint, epoch);
+        if *hedge_epoch_info.key != hedge_epoch_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if hedge_epoch_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                HedgeEpoch::LEN,
+                program_id,
+                system_program_info,
+                hedge_epoch_info,
+                &[
+                    HEDGE_EPOCH_SEED,
+                    hedge_config.mint.as_ref(),
+                    &epoch.to_le_bytes(),
+                    &[bump],
+                ],
+            )?;
+        }
+        if hedge_epoch_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if HedgeEpoch::unpack_unchecked(&hedge_epoch_info.data.borrow())?.is_initialized {
+            return Ok(());
+        }
+        HedgeEpoch::pack(
+            HedgeEpoch {
+                is_initialized: true,
+                mint: hedge_config.mint,
+                epoch,
+                stable_per_base: hedge_config.previous_stable_per_base,
+                bump,
+            },
+            &mut hedge_epoch_info.data.borrow_mut(),
+        )
+    }
+
+    /// Processes a [TriggerConditions](enum.TokenInstruction.html) instruction.
+    pub fn process_trigger_conditions(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        authority_type: AuthorityType,
-        new_authority: COption<Pubkey>,
+        minimum_amount_out: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let account_info = next_account_info(account_info_iter)?;
-        let authority_info = next_account_info(account_info_iter)?;
-
-        if account_info.data_len() == Account::get_packed_len() {
-            let mut account = Account::unpack(&account_info.data.borrow())?;
-
-            if account.is_frozen() {
-                return Err(TokenError::AccountFrozen.into());
-            }
+        let program_config_info = next_account_info(account_info_iter)?;
+        let stop_loss_info = next_account_info(account_info_iter)?;
+        let hedge_config_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let asset_vault_info = next_account_info(account_info_iter)?;
+        let stable_vault_info = next_account_info(account_info_iter)?;
+        let vault_authority_info = next_account_info(account_info_iter)?;
+        let swap_info = next_account_info(account_info_iter)?;
+        let swap_authority_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let swap_program_info = next_account_info(account_info_iter)?;
+        let host_fee_account_info = next_account_info(account_info_iter).ok();
+
+        let program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        let mut hedge_config =
+            Self::unpack_hedge_config(program_id, hedge_config_info, mint_info.key)?;
+        let mut stop_loss = Self::unpack_stop_loss(program_id, stop_loss_info, mint_info.key)?;
+        if stop_loss.triggered {
+            return Err(TokenError::StopLossTriggered.into());
+        }
+        if *oracle_info.key != stop_loss.oracle {
+            return Err(TokenError::InvalidOracle.into());
+        }
+        let price = unpack_price(&oracle_info.data.borrow(), clock.slot)?;
+        stop_loss.high_price = stop_loss.high_price.max(price.price);
+        if price.price > stop_loss.effective_stop() {
+            StopLoss::pack(stop_loss, &mut stop_loss_info.data.borrow_mut())?;
+            return Ok(());
+        }
 
-            match authority_type {
-                AuthorityType::AccountOwner => {
-                    Self::validate_owner(
-                        program_id,
-                        &account.owner,
-                        authority_info,
-                        account_info_iter.as_slice(),
-                    )?;
+        program_config.check_not_paused(PAUSE_SWAP)?;
+        if *asset_vault_info.key != hedge_config.asset_vault
+            || *stable_vault_info.key != hedge_config.stable_vault
+        {
+            return Err(TokenError::IncorrectVaultAccount.into());
+        }
+        let vault_authority_bump = [hedge_config.vault_authority_bump];
+        let vault_authority_seeds = [
+            VAULT_AUTHORITY_SEED,
+            mint_info.key.as_ref(),
+            &vault_authority_bump,
+        ];
+        let vault_authority = Pubkey::create_program_address(&vault_authority_seeds, program_id)
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidVaultAuthority))?;
+        if *vault_authority_info.key != vault_authority {
+            return Err(TokenError::InvalidVaultAuthority.into());
+        }
+        let swap = Self::validate_swap_accounts(
+            &hedge_config,
+            swap_program_info,
+            swap_info,
+            swap_authority_info,
+            swap_source_info,
+            swap_destination_info,
+            pool_mint_info,
+            pool_fee_account_info,
+            token_program_info,
+        )?;
 
-                    if let COption::Some(authority) = new_authority {
-                        account.owner = authority;
-                    } else {
-                        return Err(TokenError::InvalidInstruction.into());
-                    }
-                }
-                AuthorityType::CloseAccount => {
-                    let authority = account.close_authority.unwrap_or(account.owner);
-                    Self::validate_owner(
-                        program_id,
-                        &authority,
-                        authority_info,
-                        account_info_iter.as_slice(),
-                    )?;
-                    account.close_authority = new_authority;
-                }
-                _ => {
-                    return Err(TokenError::AuthorityTypeNotSupported.into());
-                }
-            }
-            Account::pack(account, &mut account_info.data.borrow_mut())?;
-        } else if account_info.data_len() == Mint::get_packed_len() {
-            let mut mint = Mint::unpack(&account_info.data.borrow())?;
-            match authority_type {
-                AuthorityType::MintTokens => {
-                    // Once a mint's supply is fixed, it cannot be undone by setting a new
-                    // mint_authority
-                    let mint_authority = mint
-                        .mint_authority
-                        .ok_or(Into::<ProgramError>::into(TokenError::FixedSupply))?;
-                    Self::validate_owner(
-                        program_id,
-                        &mint_authority,
-                        authority_info,
-                        account_info_iter.as_slice(),
-                    )?;
-                    mint.mint_authority = new_authority;
-                }
-                AuthorityType::FreezeAccount => {
-                    // Once a mint's freeze authority is disabled, it cannot be re-enabled by
-                    // setting a new freeze_authority
-                    let freeze_authority = mint
-                        .freeze_authority
-                        .ok_or(Into::<ProgramError>::into(TokenError::MintCannotFreeze))?;
-                    Self::validate_owner(
-                        program_id,
-                        &freeze_authority,
-                        authority_info,
-                        account_info_iter.as_slice(),
-                    )?;
-                    mint.freeze_authority = new_authority;
-                }
-                _ => {
-                    return Err(TokenError::AuthorityTypeNotSupported.into());
-                }
-            }
-            Mint::pack(mint, &mut account_info.data.borrow_mut())?;
-        } else {
-            return Err(ProgramError::InvalidArgument);
+        let asset_sold = unpack_token_account(
+            &swap.token_program_id,
+            asset_vault_info.owner,
+            &asset_vault_info.data.borrow(),
+        )?
+        .amount;
+        let mut stable_bought = 0;
+        if asset_sold > 0 {
+            let quote = price
+                .quote(
+                    asset_sold,
+                    stop_loss.asset_decimals,
+                    stop_loss.stable_decimals,
+                )
+                .ok_or(TokenError::Overflow)?;
+            let oracle_minimum_amount_out =
+                basis_points_of(quote, BASIS_POINTS - stop_loss.max_slippage_bps)?;
+            stable_bought = Self::swap_vault(
+                &swap,
+                &vault_authority_seeds,
+                asset_vault_info,
+                stable_vault_info,
+                vault_authority_info,
+                swap_info,
+                swap_authority_info,
+                swap_source_info,
+                swap_destination_info,
+                pool_mint_info,
+                pool_fee_account_info,
+                token_program_info,
+                swap_program_info,
+                host_fee_account_info,
+                asset_sold,
+                minimum_amount_out.max(oracle_minimum_amount_out),
+            )?;
+            hedge_config.record_vault_swap(asset_sold, asset_sold, stable_bought)?;
+            HedgeConfig::pack(hedge_config, &mut hedge_config_info.data.borrow_mut())?;
         }
 
+        stop_loss.triggered = true;
+        stop_loss.triggered_slot = clock.slot;
+        stop_loss.triggered_price = price.price;
+        stop_loss.asset_sold = asset_sold;
+        stop_loss.stable_bought = stable_bought;
+        StopLoss::pack(stop_loss, &mut stop_loss_info.data.borrow_mut())?;
+
+        TokenEvent::StopLossTriggered(StopLossTriggered {
+            mint: *mint_info.key,
+            price: price.price,
+            asset_sold,
+            usdc_bought: stable_bought,
+        })
+        .emit();
         Ok(())
     }
 
-    /// Processes a [MintTo](enum.TokenInstruction.html) instruction.
-    pub fn process_mint_to(
+    /// Processes a [SetTakeProfit](enum.TokenInstruction.html) instruction.
+    pub fn process_set_take_profit(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        amount: u64,
-        expected_decimals: Option<u8>,
+        trigger_bps: u16,
+        sell_bps: u16,
+        max_slippage_bps: u16,
+        distribute: bool,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
+        let take_profit_info = next_account_info(account_info_iter)?;
+        let hedge_config_info = next_account_info(account_info_iter)?;
         let mint_info = next_account_info(account_info_iter)?;
-        let dest_account_info = next_account_info(account_info_iter)?;
-        let owner_info = next_account_info(account_info_iter)?;
+        let asset_entry_info = next_account_info(account_info_iter)?;
+        let stable_entry_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let authority_info = next_account_info(account_info_iter)?;
 
-        let mut dest_account = Account::unpack(&dest_account_info.data.borrow())?;
-        if dest_account.is_frozen() {
-            return Err(TokenError::AccountFrozen.into());
+        // the asset vault is never emptied by harvests, which would leave the
+        // positions without asset to rescale
+        if trigger_bps == 0 || sell_bps >= BASIS_POINTS || max_slippage_bps > BASIS_POINTS {
+            return Err(TokenError::InvalidBasisPoints.into());
         }
 
-        if dest_account.is_native() {
-            return Err(TokenError::NativeNotSupported.into());
-        }
-        if mint_info.key != &dest_account.mint {
-            return Err(TokenError::MintMismatch.into());
+        let hedge_config = Self::unpack_hedge_config(program_id, hedge_config_info, mint_info.key)?;
+        Self::validate_owner(
+            program_id,
+            &hedge_config.authority,
+            authority_info,
+            account_info_iter.as_slice(),
+        )?;
+        let asset_entry =
+            Self::unpack_asset_entry(program_id, asset_entry_info, &hedge_config.asset_mint)?;
+        let stable_entry =
+            Self::unpack_asset_entry(program_id, stable_entry_info, &hedge_config.stable_mint)?;
+        if *oracle_info.key != asset_entry.oracle {
+            return Err(TokenError::InvalidOracle.into());
         }
+        let price = unpack_price(&oracle_info.data.borrow(), clock.slot)?;
 
-        let mut mint = Mint::unpack(&mint_info.data.borrow())?;
-        if let Some(expected_decimals) = expected_decimals {
-            if expected_decimals != mint.decimals {
-                return Err(TokenError::MintDecimalsMismatch.into());
-            }
+        let (take_profit_key, bump) = find_take_profit_with_program_id(program_id, mint_info.key);
+        if *take_profit_info.key != take_profit_key {
+            return Err(ProgramError::InvalidSeeds);
         }
-
-        match mint.mint_authority {
-            COption::Some(mint_authority) => Self::validate_owner(
+        if take_profit_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                TakeProfit::LEN,
                 program_id,
-                &mint_authority,
-                owner_info,
-                account_info_iter.as_slice(),
-            )?,
-            COption::None => return Err(TokenError::FixedSupply.into()),
+                system_program_info,
+                take_profit_info,
+                &[TAKE_PROFIT_SEED, mint_info.key.as_ref(), &[bump]],
+            )?;
         }
+        if take_profit_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !rent.is_exempt(take_profit_info.lamports(), take_profit_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+        let take_profit = TakeProfit::unpack_unchecked(&take_profit_info.data.borrow())?;
 
-        dest_account.amount = dest_account
-            .amount
-            .checked_add(amount)
-            .ok_or(TokenError::Overflow)?;
-
-       dest_account.usdc = amount * 2;
-       dest_account.asset = amount / 2;
-       
-
-        mint.supply = mint
-            .supply
-            .checked_add(amount)
-            .ok_or(TokenError::Overflow)?;
-
-        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
-        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+        let take_profit = TakeProfit {
+            is_initialized: true,
+            mint: *mint_info.key,
+            oracle: asset_entry.oracle,
+            trigger_bps,
+            sell_bps,
+            max_slippage_bps,
+            distribute,
+            asset_decimals: asset_entry.decimals,
+            stable_decimals: stable_entry.decimals,
+            entry_price: price.price,
+            entry_expo: price.expo,
+            bump,
+            ..take_profit
+        };
+        TakeProfit::pack(take_profit, &mut take_profit_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    /// Processes a [Burn](enum.TokenInstruction.html) instruction.
-    pub fn process_burn(
+    /// Processes a [HarvestProfit](enum.TokenInstruction.html) instruction.
+    pub fn process_harvest_profit(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        amount: u64,
-        expected_decimals: Option<u8>,
+        minimum_amount_out: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-
-        let source_account_info = next_account_info(account_info_iter)?;
+        let program_config_info = next_account_info(account_info_iter)?;
+        let take_profit_info = next_account_info(account_info_iter)?;
+        let hedge_config_info = next_account_info(account_info_iter)?;
         let mint_info = next_account_info(account_info_iter)?;
-        let authority_info = next_account_info(account_info_iter)?;
-
-        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
-        let mut mint = Mint::unpack(&mint_info.data.borrow())?;
-
-        if source_account.is_frozen() {
-            return Err(TokenError::AccountFrozen.into());
+        let oracle_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let asset_vault_info = next_account_info(account_info_iter)?;
+        let stable_vault_info = next_account_info(account_info_iter)?;
+        let vault_authority_info = next_account_info(account_info_iter)?;
+        let swap_info = next_account_info(account_info_iter)?;
+        let swap_authority_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let swap_program_info = next_account_info(account_info_iter)?;
+        let host_fee_account_info = next_account_info(account_info_iter).ok();
+
+        let program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        let mut hedge_config =
+            Self::unpack_hedge_config(program_id, hedge_config_info, mint_info.key)?;
+        let mut take_profit = Self::unpack_take_profit(program_id, take_profit_info, mint_info.key)?;
+        if *oracle_info.key != take_profit.oracle {
+            return Err(TokenError::InvalidOracle.into());
         }
-        if source_account.is_native() {
-            return Err(TokenError::NativeNotSupported.into());
-        }
-        if source_account.amount < amount {
-            return Err(TokenError::InsufficientFunds.into());
+        let price = unpack_price(&oracle_info.data.borrow(), clock.slot)?;
+        if price.expo != take_profit.entry_expo {
+            return Err(TokenError::InvalidOracle.into());
         }
-        if mint_info.key != &source_account.mint {
-            return Err(TokenError::MintMismatch.into());
+        if take_profit.sell_bps == 0 || price.price < take_profit.target_price() {
+            return Err(TokenError::TakeProfitNotReached.into());
         }
 
-        if let Some(expected_decimals) = expected_decimals {
-            if expected_decimals != mint.decimals {
-                return Err(TokenError::MintDecimalsMismatch.into());
-            }
+        program_config.check_not_paused(PAUSE_SWAP)?;
+        if *asset_vault_info.key != hedge_config.asset_vault
+            || *stable_vault_info.key != hedge_config.stable_vault
+        {
+            return Err(TokenError::IncorrectVaultAccount.into());
         }
+        let vault_authority_bump = [hedge_config.vault_authority_bump];
+        let vault_authority_seeds = [
+            VAULT_AUTHORITY_SEED,
+            mint_info.key.as_ref(),
+            &vault_authority_bump,
+        ];
+        let vault_authority = Pubkey::create_program_address(&vault_authority_seeds, program_id)
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidVaultAuthority))?;
+        if *vault_authority_info.key != vault_authority {
+            return Err(TokenError::InvalidVaultAuthority.into());
+        }
+        let swap = Self::validate_swap_accounts(
+            &hedge_config,
+            swap_program_info,
+            swap_info,
+            swap_authority_info,
+            swap_source_info,
+            swap_destination_info,
+            pool_mint_info,
+            pool_fee_account_info,
+            token_program_info,
+        )?;
 
-        match source_account.delegate {
-            COption::Some(ref delegate) if authority_info.key == delegate => {
-                Self::validate_owner(
-                    program_id,
-                    delegate,
-                    authority_info,
-                    account_info_iter.as_slice(),
-                )?;
-
-                if source_account.delegated_amount < amount {
-                    return Err(TokenError::InsufficientFunds.into());
-                }
-                source_account.delegated_amount = source_account
-                    .delegated_amount
-                    .checked_sub(amount)
-                    .ok_or(TokenError::Overflow)?;
-                if source_account.delegated_amount == 0 {
-                    source_account.delegate = COption::None;
-                }
-            }
-            _ => Self::validate_owner(
-                program_id,
-                &source_account.owner,
-                authority_info,
-                account_info_iter.as_slice(),
-            )?,
+        let asset_vault = unpack_token_account(
+            &swap.token_program_id,
+            asset_vault_info.owner,
+            &asset_vault_info.data.borrow(),
+        )?;
+        let asset_sold = basis_points_of(asset_vault.amount, take_profit.sell_bps)?;
+        let mut stable_bought = 0;
+        let mut profit = 0;
+        if asset_sold > 0 {
+            let quote = price
+                .quote(
+                    asset_sold,
+                    take_profit.asset_decimals,
+                    take_profit.stable_decimals,
+                )
+                .ok_or(TokenError::Overflow)?;
+            let oracle_minimum_amount_out =
+                basis_points_of(quote, BASIS_POINTS - take_profit.max_slippage_bps)?;
+            stable_bought = Self::swap_vault(
+                &swap,
+                &vault_authority_seeds,
+                asset_vault_info,
+                stable_vault_info,
+                vault_authority_info,
+                swap_info,
+                swap_authority_info,
+                swap_source_info,
+                swap_destination_info,
+                pool_mint_info,
+                pool_fee_account_info,
+                token_program_info,
+                swap_program_info,
+                host_fee_account_info,
+                asset_sold,
+                minimum_amount_out.max(oracle_minimum_amount_out),
+            )?;
+            let entry = OraclePrice {
+                price: take_profit.entry_price,
+                expo: take_profit.entry_expo,
+                publish_slot: 0,
+            };
+            let cost = entry
+                .quote(
+                    asset_sold,
+                    take_profit.asset_decimals,
+                    take_profit.stable_decimals,
+                )
+                .ok_or(TokenError::Overflow)?;
+            profit = stable_bought.saturating_sub(cost);
         }
 
-        source_account.amount = source_account
-            .amount
-            .checked_sub(amount)
-            .ok_or(TokenError::Overflow)?;
-        mint.supply = mint
-            .supply
-            .checked_sub(amount)
+        let supply = Mint::unpack(&mint_info.data.borrow())?.supply;
+        let distributed = if take_profit.distribute && supply > 0 {
+            profit
+        } else {
+            0
+        };
+        if distributed > 0 {
+            let per_share = (profit as u128)
+                .checked_mul(REWARD_PRECISION)
+                .ok_or(TokenError::Overflow)?
+                / supply as u128;
+            take_profit.reward_per_share = take_profit
+                .reward_per_share
+                .checked_add(per_share)
+                .ok_or(TokenError::Overflow)?;
+            take_profit.reserved_rewards = take_profit
+                .reserved_rewards
+                .checked_add(profit)
+                .ok_or(TokenError::Overflow)?;
+        }
+        if asset_sold > 0 {
+            hedge_config.record_vault_swap(
+                asset_vault.amount,
+                asset_sold,
+                stable_bought - distributed,
+            )?;
+            HedgeConfig::pack(hedge_config, &mut hedge_config_info.data.borrow_mut())?;
+        }
+        take_profit.entry_price = price.price;
+        take_profit.last_harvest_slot = clock.slot;
+        take_profit.realized_profit = take_profit
+            .realized_profit
+            .checked_add(profit)
             .ok_or(TokenError::Overflow)?;
+        TakeProfit::pack(take_profit, &mut take_profit_info.data.borrow_mut())?;
+
+        TokenEvent::ProfitHarvested(ProfitHarvested {
+            mint: *mint_info.key,
+            price: price.price,
+            asset_sold,
+            usdc_bought: stable_bought,
+            profit,
+            distributed,
+        })
+        .emit();
+        Ok(())
+    }
 
-        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
-        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+    /// Processes a [ClaimRewards](enum.TokenInstruction.html) instruction.
+    pub fn process_claim_rewards(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let take_profit_info = next_account_info(account_info_iter)?;
+        let hedge_config_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let account_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let stable_vault_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let vault_authority_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        let mut take_profit = Self::unpack_take_profit(program_id, take_profit_info, mint_info.key)?;
+        let hedge_config = Self::unpack_hedge_config(program_id, hedge_config_info, mint_info.key)?;
+        let account = Account::unpack(&account_info.data.borrow())?;
+        if account.mint != *mint_info.key {
+            return Err(TokenError::MintMismatch.into());
+        }
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+        let mut position = Self::unpack_position(program_id, position_info, account_info.key)?;
+        if *stable_vault_info.key != hedge_config.stable_vault {
+            return Err(TokenError::IncorrectVaultAccount.into());
+        }
+        unpack_token_account(
+            token_program_info.key,
+            stable_vault_info.owner,
+            &stable_vault_info.data.borrow(),
+        )?;
+        let vault_authority_bump = [hedge_config.vault_authority_bump];
+        let vault_authority_seeds = [
+            VAULT_AUTHORITY_SEED,
+            mint_info.key.as_ref(),
+            &vault_authority_bump,
+        ];
+        let vault_authority = Pubkey::create_program_address(&vault_authority_seeds, program_id)
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidVaultAuthority))?;
+        if *vault_authority_info.key != vault_authority {
+            return Err(TokenError::InvalidVaultAuthority.into());
+        }
 
+        position.settle_rewards(account.amount, take_profit.reward_per_share)?;
+        let amount = position.rewards;
+        if amount > 0 {
+            invoke_signed(
+                &transfer(
+                    token_program_info.key,
+                    stable_vault_info.key,
+                    destination_info.key,
+                    vault_authority_info.key,
+                    &[],
+                    amount,
+                )?,
+                &[
+                    stable_vault_info.clone(),
+                    destination_info.clone(),
+                    vault_authority_info.clone(),
+                    token_program_info.clone(),
+                ],
+                &[&vault_authority_seeds],
+            )?;
+        }
+        // Rewards are rounded down when settled, so the reserve covers every
+        // claim unless balances were minted outside of deposits.
+        take_profit.reserved_rewards = take_profit.reserved_rewards.saturating_sub(amount);
+        position.rewards = 0;
+        TakeProfit::pack(take_profit, &mut take_profit_info.data.borrow_mut())?;
+        Position::pack(position, &mut position_info.data.borrow_mut())?;
+
+        TokenEvent::RewardsClaimed(RewardsClaimed {
+            mint: *mint_info.key,
+            account: *account_info.key,
+            amount,
+        })
+        .emit();
         Ok(())
     }
 
-    /// Processes a [CloseAccount](enum.TokenInstruction.html) instruction.
-    pub fn process_close_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    /// Processes an [InitializeDca](enum.TokenInstruction.html) instruction.
+    pub fn process_initialize_dca(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        asset_index: u8,
+        tranches: u16,
+        max_slippage_bps: u16,
+    ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let source_account_info = next_account_info(account_info_iter)?;
-        let dest_account_info = next_account_info(account_info_iter)?;
-        let authority_info = next_account_info(account_info_iter)?;
+        let dca_config_info = next_account_info(account_info_iter)?;
+        let portfolio_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let asset_vault_info = next_account_info(account_info_iter)?;
+        let stable_vault_info = next_account_info(account_info_iter)?;
+        let swap_info = next_account_info(account_info_iter)?;
+        let asset_entry_info = next_account_info(account_info_iter)?;
+        let stable_entry_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let creator_info = next_account_info(account_info_iter)?;
 
-        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
-        if !source_account.is_native() && source_account.amount != 0 {
-            return Err(TokenError::NonNativeHasBalance.into());
+        if max_slippage_bps > BASIS_POINTS {
+            return Err(TokenError::InvalidBasisPoints.into());
+        }
+        if portfolio_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let portfolio = Portfolio::unpack(&portfolio_info.data.borrow())?;
+        if portfolio.is_initialize != 1 {
+            return Err(ProgramError::UninitializedAccount);
         }
-
-        let authority = source_account
-            .close_authority
-            .unwrap_or(source_account.owner);
         Self::validate_owner(
             program_id,
-            &authority,
-            authority_info,
+            &portfolio.creator_portfolio,
+            creator_info,
             account_info_iter.as_slice(),
         )?;
+        let (asset_mint, stable_mint, period) = portfolio
+            .asset_schedule(asset_index)
+            .ok_or(TokenError::InvalidDcaSchedule)?;
+        if tranches == 0 || period == 0 || asset_mint == stable_mint {
+            return Err(TokenError::InvalidDcaSchedule.into());
+        }
 
-        let dest_starting_lamports = dest_account_info.lamports();
-        **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
-            .checked_add(source_account_info.lamports())
-            .ok_or(TokenError::Overflow)?;
+        let (dca_config_key, bump) =
+            find_dca_config_with_program_id(program_id, portfolio_info.key, asset_index);
+        if *dca_config_info.key != dca_config_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let (vault_authority, vault_authority_bump) =
+            find_vault_authority_with_program_id(program_id, &dca_config_key);
+
+        let swap = SwapState::unpack(&swap_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidSwapState))?;
+        let asset_vault = unpack_token_account(
+            &swap.token_program_id,
+            asset_vault_info.owner,
+            &asset_vault_info.data.borrow(),
+        )?;
+        let stable_vault = unpack_token_account(
+            &swap.token_program_id,
+            stable_vault_info.owner,
+            &stable_vault_info.data.borrow(),
+        )?;
+        if asset_vault.owner != vault_authority || stable_vault.owner != vault_authority {
+            return Err(TokenError::IncorrectVaultOwner.into());
+        }
+        if asset_vault.mint != asset_mint || swap.reserve_for_mint(&asset_mint).is_none() {
+            return Err(TokenError::AssetMintMismatch.into());
+        }
+        if stable_vault.mint != stable_mint || swap.reserve_for_mint(&stable_mint).is_none() {
+            return Err(TokenError::StableMintMismatch.into());
+        }
+        let asset_entry = Self::unpack_asset_entry(program_id, asset_entry_info, &asset_mint)?;
+        let stable_entry = Self::unpack_asset_entry(program_id, stable_entry_info, &stable_mint)?;
+        if *swap_info.key != asset_entry.swap_pool || *swap_info.owner != asset_entry.swap_program_id
+        {
+            return Err(TokenError::SwapPoolMismatch.into());
+        }
 
-        **source_account_info.lamports.borrow_mut() = 0;
-        source_account.amount = 0;
+        if dca_config_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                DcaConfig::LEN,
+                program_id,
+                system_program_info,
+                dca_config_info,
+                &[DCA_CONFIG_SEED, portfolio_info.key.as_ref(), &[asset_index], &[bump]],
+            )?;
+        }
+        if dca_config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let dca_config = DcaConfig::unpack_unchecked(&dca_config_info.data.borrow())?;
+        if dca_config.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        if !rent.is_exempt(dca_config_info.lamports(), dca_config_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
 
-        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        let dca_config = DcaConfig {
+            is_initialized: true,
+            portfolio: *portfolio_info.key,
+            asset_index,
+            asset_mint,
+            stable_mint,
+            swap_program_id: *swap_info.owner,
+            swap_pool: *swap_info.key,
+            asset_vault: *asset_vault_info.key,
+            stable_vault: *stable_vault_info.key,
+            oracle: asset_entry.oracle,
+            max_slippage_bps,
+            asset_decimals: asset_entry.decimals,
+            stable_decimals: stable_entry.decimals,
+            period_slots: (period as u64).saturating_mul(DCA_PERIOD_SLOTS),
+            tranches,
+            total_usdc: 0,
+            total_asset: 0,
+            vault_authority_bump,
+            bump,
+        };
+        DcaConfig::pack(dca_config, &mut dca_config_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    /// Processes a [FreezeAccount](enum.TokenInstruction.html) or a
-    /// [ThawAccount](enum.TokenInstruction.html) instruction.
-    pub fn process_toggle_freeze_account(
+    /// Processes a [DepositDca](enum.TokenInstruction.html) instruction.
+    pub fn process_deposit_dca(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        freeze: bool,
+        amount: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
-        let source_account_info = next_account_info(account_info_iter)?;
-        let mint_info = next_account_info(account_info_iter)?;
-        let authority_info = next_account_info(account_info_iter)?;
+        let program_config_info = next_account_info(account_info_iter)?;
+        let dca_config_info = next_account_info(account_info_iter)?;
+        let dca_position_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let stable_vault_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let owner_info = next_account_info(account_info_iter)?;
 
-        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
-        if freeze && source_account.is_frozen() || !freeze && !source_account.is_frozen() {
-            return Err(TokenError::InvalidState.into());
-        }
-        if source_account.is_native() {
-            return Err(TokenError::NativeNotSupported.into());
+        Self::unpack_program_config(program_id, program_config_info)?
+            .check_not_paused(PAUSE_DEPOSIT)?;
+        let mut dca_config = Self::unpack_dca_config(program_id, dca_config_info)?;
+        if *stable_vault_info.key != dca_config.stable_vault {
+            return Err(TokenError::IncorrectVaultAccount.into());
         }
-        if mint_info.key != &source_account.mint {
-            return Err(TokenError::MintMismatch.into());
+        unpack_token_account(
+            token_program_info.key,
+            stable_vault_info.owner,
+            &stable_vault_info.data.borrow(),
+        )?;
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
         }
 
-        let mint = Mint::unpack(&mint_info.data.borrow_mut())?;
-        match mint.freeze_authority {
-            COption::Some(authority) => Self::validate_owner(
+        let (dca_position_key, bump) =
+            find_dca_position_with_program_id(program_id, dca_config_info.key, owner_info.key);
+        if *dca_position_info.key != dca_position_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if dca_position_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                DcaPosition::LEN,
                 program_id,
-                &authority,
-                authority_info,
-                account_info_iter.as_slice(),
-            ),
-            COption::None => Err(TokenError::MintCannotFreeze.into()),
-        }?;
+                system_program_info,
+                dca_position_info,
+                &[
+                    DCA_POSITION_SEED,
+                    dca_config_info.key.as_ref(),
+                    owner_info.key.as_ref(),
+                    &[bump],
+                ],
+            )?;
+        }
+        if dca_position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !rent.is_exempt(dca_position_info.lamports(), dca_position_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+        let mut dca_position = DcaPosition::unpack_unchecked(&dca_position_info.data.borrow())?;
+        if !dca_position.is_initialized {
+            dca_position = DcaPosition {
+                is_initialized: true,
+                dca_config: *dca_config_info.key,
+                owner: *owner_info.key,
+                bump,
+                ..DcaPosition::default()
+            };
+        }
 
-        source_account.state = if freeze {
-            AccountState::Frozen
-        } else {
-            AccountState::Initialized
-        };
+        invoke(
+            &transfer(
+                token_program_info.key,
+                source_info.key,
+                stable_vault_info.key,
+                owner_info.key,
+                &[],
+                amount,
+            )?,
+            &[
+                source_info.clone(),
+                stable_vault_info.clone(),
+                owner_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
 
-        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        dca_position.usdc = dca_position
+            .usdc
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+        let tranches = dca_config.tranches as u64;
+        // rounded up so that the tranches cover the whole deposit
+        dca_position.tranche_amount =
+            dca_position.usdc / tranches + u64::from(dca_position.usdc % tranches != 0);
+        dca_config.total_usdc = dca_config
+            .total_usdc
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+        DcaPosition::pack(dca_position, &mut dca_position_info.data.borrow_mut())?;
+        DcaConfig::pack(dca_config, &mut dca_config_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    /// Processes an [Instruction](enum.Instruction.html).
-    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
-        let instruction = TokenInstruction::unpack(input)?;
+    /// Processes an [ExecuteDca](enum.TokenInstruction.html) instruction.
+    pub fn process_execute_dca(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        minimum_amount_out: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let dca_config_info = next_account_info(account_info_iter)?;
+        let dca_position_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let stable_vault_info = next_account_info(account_info_iter)?;
+        let asset_vault_info = next_account_info(account_info_iter)?;
+        let vault_authority_info = next_account_info(account_info_iter)?;
+        let swap_info = next_account_info(account_info_iter)?;
+        let swap_authority_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let swap_program_info = next_account_info(account_info_iter)?;
+        let host_fee_account_info = next_account_info(account_info_iter).ok();
+
+        let program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        let mut dca_config = Self::unpack_dca_config(program_id, dca_config_info)?;
+        let mut dca_position =
+            Self::unpack_dca_position(program_id, dca_position_info, dca_config_info.key)?;
+        if dca_position.usdc == 0 || clock.slot < dca_position.next_execution_slot {
+            return Err(TokenError::DcaNotDue.into());
+        }
+        if *oracle_info.key != dca_config.oracle {
+            return Err(TokenError::InvalidOracle.into());
+        }
+        let price = unpack_price(&oracle_info.data.borrow(), clock.slot)?;
 
-        match instruction {
-            TokenInstruction::InitializeMint {
-                decimals,
-                mint_authority,
-                freeze_authority,
-                mint_id_asset,
-                pubkey_swap,
-               
-            } => {
-                msg!("Instruction: InitializeMint");
-                Self::process_initialize_mint(accounts, decimals, mint_authority, freeze_authority,
-                    mint_id_asset, pubkey_swap , program_id
-                )
-            }
-            TokenInstruction::InitializeAccount => {
-                msg!("Instruction: InitializeAccount");
-                Self::process_initialize_account(accounts)
-            }
-            TokenInstruction::InitializeAccount2 { owner } => {
-                msg!("Instruction: InitializeAccount2");
-                Self::process_initialize_account2(accounts, owner)
-            }
-            TokenInstruction::InitializeMultisig { m } => {
-                msg!("Instruction: InitializeMultisig");
-                Self::process_initialize_multisig(accounts, m)
-            }
-            TokenInstruction::Transfer { amount } => {
-                msg!("Instruction: Transfer");
-                Self::process_transfer(program_id, accounts, amount, None)
-            }
-            TokenInstruction::Approve { amount } => {
-                msg!("Instruction: Approve");
-                Self::process_approve(program_id, accounts, amount, None)
-            }
-            TokenInstruction::ApproveUserPortfolio { amount } => {
-                msg!("Instruction: Approve");
-                Self::process_approve_User_Portfolio(program_id, accounts, amount)
-            }
-            TokenInstruction::Revoke => {
-                msg!("Instruction: Revoke");
-                Self::process_revoke(program_id, accounts)
-            }
-            TokenInstruction::SetAuthority {
-                authority_type,
-                new_authority,
-            } => {
-                msg!("Instruction: SetAuthority");
-                Self::process_set_authority(program_id, accounts, authority_type, new_authority)
+        program_config.check_not_paused(PAUSE_SWAP)?;
+        if *asset_vault_info.key != dca_config.asset_vault
+            || *stable_vault_info.key != dca_config.stable_vault
+        {
+            return Err(TokenError::IncorrectVaultAccount.into());
+        }
+        let vault_authority_bump = [dca_config.vault_authority_bump];
+        let vault_authority_seeds = [
+            VAULT_AUTHORITY_SEED,
+            dca_config_info.key.as_ref(),
+            &vault_authority_bump,
+        ];
+        let vault_authority = Pubkey::create_program_address(&vault_authority_seeds, program_id)
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidVaultAuthority))?;
+        if *vault_authority_info.key != vault_authority {
+            return Err(TokenError::InvalidVaultAuthority.into());
+        }
+        let swap = Self::validate_swap_pool(
+            &dca_config.swap_program_id,
+            &dca_config.swap_pool,
+            swap_program_info,
+            swap_info,
+            swap_authority_info,
+            pool_mint_info,
+            pool_fee_account_info,
+            token_program_info,
+        )?;
+        let stable_reserve = swap
+            .reserve_for_mint(&dca_config.stable_mint)
+            .ok_or(TokenError::StableMintMismatch)?;
+        let asset_reserve = swap
+            .reserve_for_mint(&dca_config.asset_mint)
+            .ok_or(TokenError::AssetMintMismatch)?;
+        if *swap_source_info.key != stable_reserve || *swap_destination_info.key != asset_reserve {
+            return Err(TokenError::IncorrectSwapAccount.into());
+        }
+
+        let usdc_sold = dca_position.tranche_amount.min(dca_position.usdc);
+        let quote = price
+            .amount_for(
+                usdc_sold,
+                dca_config.asset_decimals,
+                dca_config.stable_decimals,
+            )
+            .ok_or(TokenError::Overflow)?;
+        let oracle_minimum_amount_out =
+            basis_points_of(quote, BASIS_POINTS - dca_config.max_slippage_bps)?;
+        let asset_bought = Self::swap_vault(
+            &swap,
+            &vault_authority_seeds,
+            stable_vault_info,
+            asset_vault_info,
+            vault_authority_info,
+            swap_info,
+            swap_authority_info,
+            swap_source_info,
+            swap_destination_info,
+            pool_mint_info,
+            pool_fee_account_info,
+            token_program_info,
+            swap_program_info,
+            host_fee_account_info,
+            usdc_sold,
+            minimum_amount_out.max(oracle_minimum_amount_out),
+        )?;
+
+        dca_position.usdc -= usdc_sold;
+        dca_position.asset = dca_position
+            .asset
+            .checked_add(asset_bought)
+            .ok_or(TokenError::Overflow)?;
+        dca_position.next_execution_slot = clock.slot.saturating_add(dca_config.period_slots);
+        dca_config.total_usdc = dca_config.total_usdc.saturating_sub(usdc_sold);
+        dca_config.total_asset = dca_config
+            .total_asset
+            .checked_add(asset_bought)
+            .ok_or(TokenError::Overflow)?;
+        DcaPosition::pack(dca_position, &mut dca_position_info.data.borrow_mut())?;
+        DcaConfig::pack(dca_config, &mut dca_config_info.data.borrow_mut())?;
+
+        TokenEvent::DcaExecuted(DcaExecuted {
+            dca_config: *dca_config_info.key,
+            owner: dca_position.owner,
+            usdc_sold,
+            asset_bought,
+        })
+        .emit();
+        Ok(())
+    }
+
+    /// Processes a [WithdrawDca](enum.TokenInstruction.html) instruction.
+    pub fn process_withdraw_dca(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        usdc: u64,
+        asset: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let dca_config_info = next_account_info(account_info_iter)?;
+        let dca_position_info = next_account_info(account_info_iter)?;
+        let stable_vault_info = next_account_info(account_info_iter)?;
+        let asset_vault_info = next_account_info(account_info_iter)?;
+        let usdc_destination_info = next_account_info(account_info_iter)?;
+        let asset_destination_info = next_account_info(account_info_iter)?;
+        let vault_authority_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        Self::unpack_program_config(program_id, program_config_info)?
+            .check_not_paused(PAUSE_WITHDRAW)?;
+        let mut dca_config = Self::unpack_dca_config(program_id, dca_config_info)?;
+        let mut dca_position =
+            Self::unpack_dca_position(program_id, dca_position_info, dca_config_info.key)?;
+        Self::validate_owner(
+            program_id,
+            &dca_position.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+        if usdc > dca_position.usdc || asset > dca_position.asset {
+            return Err(TokenError::InsufficientFunds.into());
+        }
+        if *asset_vault_info.key != dca_config.asset_vault
+            || *stable_vault_info.key != dca_config.stable_vault
+        {
+            return Err(TokenError::IncorrectVaultAccount.into());
+        }
+        unpack_token_account(
+            token_program_info.key,
+            stable_vault_info.owner,
+            &stable_vault_info.data.borrow(),
+        )?;
+        let vault_authority_bump = [dca_config.vault_authority_bump];
+        let vault_authority_seeds = [
+            VAULT_AUTHORITY_SEED,
+            dca_config_info.key.as_ref(),
+            &vault_authority_bump,
+        ];
+        let vault_authority = Pubkey::create_program_address(&vault_authority_seeds, program_id)
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidVaultAuthority))?;
+        if *vault_authority_info.key != vault_authority {
+            return Err(TokenError::InvalidVaultAuthority.into());
+        }
+
+        for (vault_info, destination_info, amount) in [
+            (stable_vault_info, usdc_destination_info, usdc),
+            (asset_vault_info, asset_destination_info, asset),
+        ] {
+            if amount == 0 {
+                continue;
             }
-            TokenInstruction::MintTo { amount } => {
-                msg!("Instruction: MintTo");
-                Self::process_mint_to(program_id, accounts, amount, None)
+            invoke_signed(
+                &transfer(
+                    token_program_info.key,
+                    vault_info.key,
+                    destination_info.key,
+                    vault_authority_info.key,
+                    &[],
+                    amount,
+                )?,
+                &[
+                    vault_info.clone(),
+                    destination_info.clone(),
+                    vault_authority_info.clone(),
+                    token_program_info.clone(),
+                ],
+                &[&vault_authority_seeds],
+            )?;
+        }
+
+        dca_position.usdc -= usdc;
+        dca_position.asset -= asset;
+        dca_config.total_usdc = dca_config.total_usdc.saturating_sub(usdc);
+        dca_config.total_asset = dca_config.total_asset.saturating_sub(asset);
+        DcaPosition::pack(dca_position, &mut dca_position_info.data.borrow_mut())?;
+        DcaConfig::pack(dca_config, &mut dca_config_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes a [RequestWithdraw](enum.TokenInstruction.html) instruction.
+    pub fn process_request_withdraw(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let account_info = next_account_info(account_info_iter)?;
+        let withdraw_ticket_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        let program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        program_config.check_not_paused(PAUSE_WITHDRAW)?;
+        let account = Account::unpack(&account_info.data.borrow())?;
+        Self::validate_owner(
+            program_id,
+            &account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+        if account.amount < amount {
+            return Err(TokenError::InsufficientFunds.into());
+        }
+
+        let (withdraw_ticket_key, bump) =
+            find_withdraw_ticket_with_program_id(program_id, account_info.key);
+        if *withdraw_ticket_info.key != withdraw_ticket_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if withdraw_ticket_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                WithdrawTicket::LEN,
+                program_id,
+                system_program_info,
+                withdraw_ticket_info,
+                &[WITHDRAW_TICKET_SEED, account_info.key.as_ref(), &[bump]],
+            )?;
+        }
+        if withdraw_ticket_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !rent.is_exempt(withdraw_ticket_info.lamports(), withdraw_ticket_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+        WithdrawTicket::unpack_unchecked(&withdraw_ticket_info.data.borrow())?;
+
+        let withdraw_ticket = WithdrawTicket {
+            is_initialized: true,
+            account: *account_info.key,
+            amount,
+            request_slot: clock.slot,
+            claimable_slot: clock.slot.saturating_add(program_config.withdraw_delay_slots),
+            bump,
+        };
+        WithdrawTicket::pack(withdraw_ticket, &mut withdraw_ticket_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes a [SetWithdrawDelay](enum.TokenInstruction.html) instruction.
+    pub fn process_set_withdraw_delay(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        delay_slots: u64,
+        bypass: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        let mut program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        Self::validate_owner(
+            program_id,
+            &program_config.admin,
+            admin_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        program_config.withdraw_delay_slots = delay_slots;
+        program_config.bypass_withdraw_delay = bypass;
+        ProgramConfig::pack(program_config, &mut program_config_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes an [InitializePosition](enum.TokenInstruction.html) instruction.
+    pub fn process_initialize_position(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let position_info = next_account_info(account_info_iter)?;
+        let account_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+        if account_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Account::unpack(&account_info.data.borrow())?;
+
+        Self::create_position(
+            program_id,
+            position_info,
+            account_info.key,
+            payer_info,
+            system_program_info,
+            rent,
+            0,
+            0,
+        )
+    }
+
+    /// Processes a [MigrateAccount](enum.TokenInstruction.html) instruction.
+    pub fn process_migrate_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let legacy_info = next_account_info(account_info_iter)?;
+
+        if legacy_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        match legacy_info.data_len() {
+            LEGACY_MINT_LEN => {
+                {
+                    let data = legacy_info.data.borrow();
+                    Mint::unpack_unchecked(&data[..Mint::LEN])?;
+                    // the legacy hedge fields are the optional asset mint and
+                    // swap pool, neither of which has a vault backing it
+                    let hedge = array_ref![data, Mint::LEN, 72];
+                    let (mint_id_asset, pubkey_swap) = array_refs![hedge, 36, 36];
+                    if mint_id_asset[..4] != [0; 4] || pubkey_swap[..4] != [0; 4] {
+                        return Err(TokenError::LegacyMintHedged.into());
+                    }
+                }
+                legacy_info.realloc(Mint::LEN, false)
             }
-            TokenInstruction::Burn { amount } => {
-                msg!("Instruction: Burn");
-                Self::process_burn(program_id, accounts, amount, None)
+            LEGACY_ACCOUNT_LEN => {
+                let position_info = next_account_info(account_info_iter)?;
+                let payer_info = next_account_info(account_info_iter)?;
+                let system_program_info = next_account_info(account_info_iter)?;
+                let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+                // the legacy components were never backed by the vaults, so
+                // the position starts empty
+                Account::unpack_unchecked(&legacy_info.data.borrow()[..Account::LEN])?;
+                Self::create_position(
+                    program_id,
+                    position_info,
+                    legacy_info.key,
+                    payer_info,
+                    system_program_info,
+                    rent,
+                    0,
+                    0,
+                )?;
+                legacy_info.realloc(Account::LEN, false)
             }
-            TokenInstruction::CloseAccount => {
-                msg!("Instruction: CloseAccount");
-                Self::process_close_account(program_id, accounts)
+            len => {
+                let account_type =
+                    AccountType::from_unversioned_len(len).ok_or(TokenError::NotLegacyAccount)?;
+                let payer_info = next_account_info(account_info_iter)?;
+                let system_program_info = next_account_info(account_info_iter)?;
+                let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+                Self::grow_account(
+                    legacy_info,
+                    payer_info,
+                    system_program_info,
+                    rent,
+                    len + ACCOUNT_HEADER_LEN,
+                )?;
+                {
+                    let mut data = legacy_info.data.borrow_mut();
+                    data.copy_within(..len, ACCOUNT_HEADER_LEN);
+                    data[0] = account_type as u8;
+                    data[1] = STATE_VERSION;
+                }
+                StateAccount::unpack(&legacy_info.data.borrow())?;
+                Ok(())
             }
-            TokenInstruction::FreezeAccount => {
-                msg!("Instruction: FreezeAccount");
-                Self::process_toggle_freeze_account(program_id, accounts, true)
+        }
+    }
+
+    /// Grows `account_info` to `new_len` zeroed bytes, the payer topping up its
+    /// rent exemption.
+    fn grow_account<'a>(
+        account_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        rent: &Rent,
+        new_len: usize,
+    ) -> ProgramResult {
+        let required_lamports = rent
+            .minimum_balance(new_len)
+            .saturating_sub(account_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, account_info.key, required_lamports),
+                &[
+                    payer_info.clone(),
+                    account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        account_info.realloc(new_len, true)
+    }
+
+    /// Creates the position of `account` holding `asset` and `usdc`.
+    #[allow(clippy::too_many_arguments)]
+    fn create_position<'a>(
+        program_id: &Pubkey,
+        position_info: &AccountInfo<'a>,
+        account: &Pubkey,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        rent: &Rent,
+        asset: u64,
+        usdc: u64,
+    ) -> ProgramResult {
+        let (position_key, bump) = find_position_with_program_id(program_id, account);
+        if *position_info.key != position_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if position_info.data_is_empty() {
+            Self::create_pda_account(
+                payer_info,
+                rent,
+                Position::LEN,
+                program_id,
+                system_program_info,
+                position_info,
+                &[POSITION_SEED, account.as_ref(), &[bump]],
+            )?;
+        }
+        if position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let position = Position::unpack_unchecked(&position_info.data.borrow())?;
+        if position.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        if !rent.is_exempt(position_info.lamports(), position_info.data_len()) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+
+        let position = Position {
+            is_initialized: true,
+            account: *account,
+            asset,
+            usdc,
+            bump,
+            tracks_cost_basis: asset == 0 && usdc == 0,
+            ..Position::default()
+        };
+        Position::pack(position, &mut position_info.data.borrow_mut())
+    }
+
+    fn _process_initialize_account(
+        accounts: &[AccountInfo],
+        owner: Option<&Pubkey>,
+    ) -> ProgramResult {
+
+       let account_info_iter = &mut accounts.iter();
+        let new_account_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let owner = if let Some(owner) = owner {
+            owner
+        } else {
+            next_account_info(account_info_iter)?.key
+        };
+        let new_account_info_data_len = new_account_info.data_len();
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut account = Account::unpack_unchecked(&new_account_info.data.borrow())?;
+        if account.is_initialized() {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        if !rent.is_exempt(new_account_info.lamports(), new_account_info_data_len) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+
+        if *mint_info.key != crate::native_mint::id() {
+            let _ = Mint::unpack(&mint_info.data.borrow_mut())
+                .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidMint))?;
+        }
+
+        account.mint = *mint_info.key;
+        account.owner = *owner;
+        account.delegate = COption::None;
+        account.delegated_amount = 0;
+        account.state = AccountState::Initialized;
+        if *mint_info.key == crate::native_mint::id() {
+            let rent_exempt_reserve = rent.minimum_balance(new_account_info_data_len);
+            account.is_native = COption::Some(rent_exempt_reserve);
+            account.amount = new_account_info
+                .lamports()
+                .checked_sub(rent_exempt_reserve)
+                .ok_or(TokenError::Overflow)?;
+        } else {
+            account.is_native = COption::None;
+            account.amount = 0;
+        };
+
+        Account::pack(account, &mut new_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes an [InitializeAccount](enum.TokenInstruction.html) instruction.
+    pub fn process_initialize_account(accounts: &[AccountInfo]) -> ProgramResult {
+        Self::_process_initialize_account(accounts, None)
+    }
+
+    /// Processes an [InitializeAccount2](enum.TokenInstruction.html) instruction.
+    pub fn process_initialize_account2(accounts: &[AccountInfo], owner: Pubkey) -> ProgramResult {
+        Self::_process_initialize_account(accounts, Some(&owner))
+    }
+
+    /// Processes a [InitializeMultisig](enum.TokenInstruction.html) instruction.
+    pub fn process_initialize_multisig(accounts: &[AccountInfo], m: u8) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let multisig_info = next_account_info(account_info_iter)?;
+        let multisig_info_data_len = multisig_info.data_len();
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut multisig = Multisig::unpack_unchecked(&multisig_info.data.borrow())?;
+        if multisig.is_initialized {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+
+        if !rent.is_exempt(multisig_info.lamports(), multisig_info_data_len) {
+            return Err(TokenError::NotRentExempt.into());
+        }
+
+        let signer_infos = account_info_iter.as_slice();
+        multisig.m = m;
+        multisig.n = signer_infos.len() as u8;
+        if !is_valid_signer_index(multisig.n as usize) {
+            return Err(TokenError::InvalidNumberOfProvidedSigners.into());
+        }
+        if !is_valid_signer_index(multisig.m as usize) {
+            return Err(TokenError::InvalidNumberOfRequiredSigners.into());
+        }
+        for (i, signer_info) in signer_infos.iter().enumerate() {
+            multisig.signers[i] = *signer_info.key;
+        }
+        multisig.is_initialized = true;
+
+        Multisig::pack(multisig, &mut multisig_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes a [Transfer](enum.TokenInstruction.html) instruction.
+    pub fn process_transfer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        expected_decimals: Option<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_account_info = next_account_info(account_info_iter)?;
+
+        let expected_mint_info = if let Some(expected_decimals) = expected_decimals {
+            Some((next_account_info(account_info_iter)?, expected_decimals))
+        } else {
+            None
+        };
+
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
+        let mut dest_account = Account::unpack(&dest_account_info.data.borrow())?;
+
+        if source_account.is_frozen() || dest_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        if source_account.amount < amount {
+            return Err(TokenError::InsufficientFunds.into());
+        }
+        if source_account.mint != dest_account.mint {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        if let Some((mint_info, expected_decimals)) = expected_mint_info {
+            if source_account.mint != *mint_info.key {
+                return Err(TokenError::MintMismatch.into());
+            }
+
+            let mint = Mint::unpack(&mint_info.data.borrow_mut())?;
+            if expected_decimals != mint.decimals {
+                return Err(TokenError::MintDecimalsMismatch.into());
+            }
+        }
+
+        let self_transfer = source_account_info.key == dest_account_info.key;
+
+        match source_account.delegate {
+            COption::Some(ref delegate) if authority_info.key == delegate => {
+                Self::validate_owner(
+                    program_id,
+                    delegate,
+                    authority_info,
+                    account_info_iter.as_slice(),
+                )?;
+                if source_account.delegated_amount < amount {
+                    return Err(TokenError::InsufficientFunds.into());
+                }
+                if !self_transfer {
+                    source_account.delegated_amount = source_account
+                        .delegated_amount
+                        .checked_sub(amount)
+                        .ok_or(TokenError::Overflow)?;
+                    if source_account.delegated_amount == 0 {
+                        source_account.delegate = COption::None;
+                    }
+                }
+            }
+            _ => Self::validate_owner(
+                program_id,
+                &source_account.owner,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+        };
+
+        // This check MUST occur just before the amounts are manipulated
+        // to ensure self-transfers are fully validated
+        if self_transfer {
+            return Ok(());
+        }
+
+        let signers = account_info_iter.as_slice();
+        let mut source_position = Self::settle_position(
+            program_id,
+            &source_account.mint,
+            source_account_info.key,
+            source_account.amount,
+            signers,
+        )?;
+        let mut dest_position = Self::settle_position(
+            program_id,
+            &dest_account.mint,
+            dest_account_info.key,
+            dest_account.amount,
+            signers,
+        )?;
+        if let Some((_, source_position)) = source_position.as_mut() {
+            let (_, dest_position) = dest_position.as_mut().ok_or(TokenError::PositionRequired)?;
+            source_position.transfer_cost_basis(
+                dest_position,
+                amount,
+                source_account.amount,
+                dest_account.amount,
+            )?;
+            dest_position.last_deposit_slot = dest_position
+                .last_deposit_slot
+                .max(source_position.last_deposit_slot);
+
+            let asset = proportional_share(source_position.asset, amount, source_account.amount)?;
+            let usdc = proportional_share(source_position.usdc, amount, source_account.amount)?;
+            source_position.asset = source_position
+                .asset
+                .checked_sub(asset)
+                .ok_or(TokenError::Overflow)?;
+            source_position.usdc = source_position
+                .usdc
+                .checked_sub(usdc)
+                .ok_or(TokenError::Overflow)?;
+            dest_position.asset = dest_position
+                .asset
+                .checked_add(asset)
+                .ok_or(TokenError::Overflow)?;
+            dest_position.usdc = dest_position
+                .usdc
+                .checked_add(usdc)
+                .ok_or(TokenError::Overflow)?;
+        }
+        for (position_info, position) in source_position.into_iter().chain(dest_position) {
+            Position::pack(position, &mut position_info.data.borrow_mut())?;
+        }
+
+        source_account.amount = source_account
+            .amount
+            .checked_sub(amount)
+            .ok_or(TokenError::Overflow)?;
+        dest_account.amount = dest_account
+            .amount
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+
+        if source_account.is_native() {
+            let source_starting_lamports = source_account_info.lamports();
+            **source_account_info.lamports.borrow_mut() = source_starting_lamports
+                .checked_sub(amount)
+                .ok_or(TokenError::Overflow)?;
+
+            let dest_starting_lamports = dest_account_info.lamports();
+            **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
+                .checked_add(amount)
+                .ok_or(TokenError::Overflow)?;
+        }
+
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes an [Approve](enum.TokenInstruction.html) instruction.
+    pub fn process_approve(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        expected_decimals: Option<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_account_info = next_account_info(account_info_iter)?;
+
+        let expected_mint_info = if let Some(expected_decimals) = expected_decimals {
+            Some((next_account_info(account_info_iter)?, expected_decimals))
+        } else {
+            None
+        };
+        let delegate_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
+
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        if let Some((mint_info, expected_decimals)) = expected_mint_info {
+            if source_account.mint != *mint_info.key {
+                return Err(TokenError::MintMismatch.into());
+            }
+
+            let mint = Mint::unpack(&mint_info.data.borrow_mut())?;
+            if expected_decimals != mint.decimals {
+                return Err(TokenError::MintDecimalsMismatch.into());
+            }
+        }
+
+        Self::validate_owner(
+            program_id,
+            &source_account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        source_account.delegate = COption::Some(*delegate_info.key);
+        source_account.delegated_amount = amount;
+
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+
+
+    /// Processes an [Approve](enum.TokenInstruction.html) instruction.
+    pub fn process_approve_User_Portfolio(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+      //  expected_decimals: Option<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_account_info = next_account_info(account_info_iter)?;
+
+       /* let expected_mint_info = if let Some(expected_decimals) = expected_decimals {
+            Some((next_account_info(account_info_iter)?, expected_decimals))
+        } else {
+            None
+        };*/
+        let delegate_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        let mut source_account = UserPortfolio::unpack(&source_account_info.data.borrow())?;
+
+        /*if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }*/
+
+        /*if let Some((mint_info, expected_decimals)) = expected_mint_info {
+            if source_account.mint != *mint_info.key {
+                return Err(TokenError::MintMismatch.into());
+            }
+
+            let mint = Mint::unpack(&mint_info.data.borrow_mut())?;
+            if expected_decimals != mint.decimals {
+                return Err(TokenError::MintDecimalsMismatch.into());
+            }
+        }*/
+
+        Self::validate_owner(
+            program_id,
+            &source_account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        source_account.delegate = *delegate_info.key;
+        source_account.delegated_amount = amount;
+
+        UserPortfolio::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+
+
+    /// Processes an [Revoke](enum.TokenInstruction.html) instruction.
+    pub fn process_revoke(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_account_info = next_account_info(account_info_iter)?;
+
+        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
+
+        let owner_info = next_account_info(account_info_iter)?;
+
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        Self::validate_owner(
+            program_id,
+            &source_account.owner,
+            owner_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        source_account.delegate = COption::None;
+        source_account.delegated_amount = 0;
+
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes a [SetAuthority](enum.TokenInstruction.html) instruction.
+    pub fn process_set_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        authority_type: AuthorityType,
+        new_authority: COption<Pubkey>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        if account_info.data_len() == Account::get_packed_len() {
+            let mut account = Account::unpack(&account_info.data.borrow())?;
+
+            if account.is_frozen() {
+                return Err(TokenError::AccountFrozen.into());
+            }
+
+            match authority_type {
+                AuthorityType::AccountOwner => {
+                    Self::validate_owner(
+                        program_id,
+                        &account.owner,
+                        authority_info,
+                        account_info_iter.as_slice(),
+                    )?;
+
+                    if let COption::Some(authority) = new_authority {
+                        account.owner = authority;
+                    } else {
+                        return Err(TokenError::InvalidInstruction.into());
+                    }
+                }
+                AuthorityType::CloseAccount => {
+                    let authority = account.close_authority.unwrap_or(account.owner);
+                    Self::validate_owner(
+                        program_id,
+                        &authority,
+                        authority_info,
+                        account_info_iter.as_slice(),
+                    )?;
+                    account.close_authority = new_authority;
+                }
+                _ => {
+                    return Err(TokenError::AuthorityTypeNotSupported.into());
+                }
+            }
+            Account::pack(account, &mut account_info.data.borrow_mut())?;
+        } else if account_info.data_len() == Mint::get_packed_len() {
+            let mut mint = Mint::unpack(&account_info.data.borrow())?;
+            match authority_type {
+                AuthorityType::MintTokens => {
+                    // Once a mint's supply is fixed, it cannot be undone by setting a new
+                    // mint_authority
+                    let mint_authority = mint
+                        .mint_authority
+                        .ok_or(Into::<ProgramError>::into(TokenError::FixedSupply))?;
+                    Self::validate_owner(
+                        program_id,
+                        &mint_authority,
+                        authority_info,
+                        account_info_iter.as_slice(),
+                    )?;
+                    mint.mint_authority = new_authority;
+                }
+                AuthorityType::FreezeAccount => {
+                    // Once a mint's freeze authority is disabled, it cannot be re-enabled by
+                    // setting a new freeze_authority
+                    let freeze_authority = mint
+                        .freeze_authority
+                        .ok_or(Into::<ProgramError>::into(TokenError::MintCannotFreeze))?;
+                    Self::validate_owner(
+                        program_id,
+                        &freeze_authority,
+                        authority_info,
+                        account_info_iter.as_slice(),
+                    )?;
+                    mint.freeze_authority = new_authority;
+                }
+                _ => {
+                    return Err(TokenError::AuthorityTypeNotSupported.into());
+                }
+            }
+            Mint::pack(mint, &mut account_info.data.borrow_mut())?;
+        } else {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(())
+    }
+
+    /// Processes a [MintTo](enum.TokenInstruction.html) instruction.
+    pub fn process_mint_to(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        expected_decimals: Option<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let mint_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+
+        let mut dest_account = Account::unpack(&dest_account_info.data.borrow())?;
+        if dest_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+
+        if dest_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+        if mint_info.key != &dest_account.mint {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        let mut mint = Mint::unpack(&mint_info.data.borrow())?;
+        if let Some(expected_decimals) = expected_decimals {
+            if expected_decimals != mint.decimals {
+                return Err(TokenError::MintDecimalsMismatch.into());
+            }
+        }
+
+        match mint.mint_authority {
+            COption::Some(mint_authority) => Self::validate_owner(
+                program_id,
+                &mint_authority,
+                owner_info,
+                account_info_iter.as_slice(),
+            )?,
+            COption::None => return Err(TokenError::FixedSupply.into()),
+        }
+
+        // the supply cap of a hedged mint cannot be left out
+        let hedged =
+            Self::find_hedge_config(program_id, mint_info.key, account_info_iter.as_slice())?
+                .is_some();
+        let deposit_limits = Self::find_deposit_limits(
+            program_id,
+            mint_info.key,
+            account_info_iter.as_slice(),
+            hedged,
+        )?;
+        let dest_position = Self::settle_position(
+            program_id,
+            mint_info.key,
+            dest_account_info.key,
+            dest_account.amount,
+            account_info_iter.as_slice(),
+        )?;
+
+        dest_account.amount = dest_account
+            .amount
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+
+        mint.supply = mint
+            .supply
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+        if let Some(deposit_limits) = deposit_limits {
+            deposit_limits.check_supply(mint.supply)?;
+        }
+
+        if let Some((position_info, position)) = dest_position {
+            Position::pack(position, &mut position_info.data.borrow_mut())?;
+        }
+        Account::pack(dest_account, &mut dest_account_info.data.borrow_mut())?;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes a [Burn](enum.TokenInstruction.html) instruction.
+    pub fn process_burn(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        expected_decimals: Option<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_account_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
+        let mut mint = Mint::unpack(&mint_info.data.borrow())?;
+
+        if source_account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        if source_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+        if source_account.amount < amount {
+            return Err(TokenError::InsufficientFunds.into());
+        }
+        if mint_info.key != &source_account.mint {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        if let Some(expected_decimals) = expected_decimals {
+            if expected_decimals != mint.decimals {
+                return Err(TokenError::MintDecimalsMismatch.into());
+            }
+        }
+
+        match source_account.delegate {
+            COption::Some(ref delegate) if authority_info.key == delegate => {
+                Self::validate_owner(
+                    program_id,
+                    delegate,
+                    authority_info,
+                    account_info_iter.as_slice(),
+                )?;
+
+                if source_account.delegated_amount < amount {
+                    return Err(TokenError::InsufficientFunds.into());
+                }
+                source_account.delegated_amount = source_account
+                    .delegated_amount
+                    .checked_sub(amount)
+                    .ok_or(TokenError::Overflow)?;
+                if source_account.delegated_amount == 0 {
+                    source_account.delegate = COption::None;
+                }
+            }
+            _ => Self::validate_owner(
+                program_id,
+                &source_account.owner,
+                authority_info,
+                account_info_iter.as_slice(),
+            )?,
+        }
+
+        let mut source_position = Self::settle_position(
+            program_id,
+            mint_info.key,
+            source_account_info.key,
+            source_account.amount,
+            account_info_iter.as_slice(),
+        )?;
+        // the components backing the burned tokens are left in the vaults,
+        // realizing their cost as a loss
+        if let Some((_, position)) = source_position.as_mut() {
+            position.record_withdrawal(amount, source_account.amount, 0)?;
+            position.asset = position
+                .asset
+                .checked_sub(proportional_share(position.asset, amount, source_account.amount)?)
+                .ok_or(TokenError::Overflow)?;
+            position.usdc = position
+                .usdc
+                .checked_sub(proportional_share(position.usdc, amount, source_account.amount)?)
+                .ok_or(TokenError::Overflow)?;
+        }
+
+        source_account.amount = source_account
+            .amount
+            .checked_sub(amount)
+            .ok_or(TokenError::Overflow)?;
+        mint.supply = mint
+            .supply
+            .checked_sub(amount)
+            .ok_or(TokenError::Overflow)?;
+
+        if let Some((position_info, position)) = source_position {
+            Position::pack(position, &mut position_info.data.borrow_mut())?;
+        }
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes a [CloseAccount](enum.TokenInstruction.html) instruction.
+    pub fn process_close_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_account_info = next_account_info(account_info_iter)?;
+        let dest_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
+        if !source_account.is_native() && source_account.amount != 0 {
+            return Err(TokenError::NonNativeHasBalance.into());
+        }
+
+        let authority = source_account
+            .close_authority
+            .unwrap_or(source_account.owner);
+        Self::validate_owner(
+            program_id,
+            &authority,
+            authority_info,
+            account_info_iter.as_slice(),
+        )?;
+
+        let signers = account_info_iter.as_slice();
+        let (position_key, _) = find_position_with_program_id(program_id, source_account_info.key);
+        let position_info = if signers
+            .iter()
+            .any(|account_info| *account_info.key == position_key)
+            || Self::find_hedge_config(program_id, &source_account.mint, signers)?.is_some()
+        {
+            Self::position_info(program_id, source_account_info.key, signers)?
+        } else {
+            None
+        };
+        if let Some(position_info) = position_info {
+            let position = Position::unpack(&position_info.data.borrow())?;
+            if position.asset != 0 || position.usdc != 0 || position.rewards != 0 {
+                return Err(TokenError::PositionNotEmpty.into());
+            }
+            let dest_starting_lamports = dest_account_info.lamports();
+            **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
+                .checked_add(position_info.lamports())
+                .ok_or(TokenError::Overflow)?;
+            **position_info.lamports.borrow_mut() = 0;
+            position_info.data.borrow_mut().fill(0);
+        }
+
+        let dest_starting_lamports = dest_account_info.lamports();
+        **dest_account_info.lamports.borrow_mut() = dest_starting_lamports
+            .checked_add(source_account_info.lamports())
+            .ok_or(TokenError::Overflow)?;
+
+        **source_account_info.lamports.borrow_mut() = 0;
+        source_account.amount = 0;
+
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes a [FreezeAccount](enum.TokenInstruction.html) or a
+    /// [ThawAccount](enum.TokenInstruction.html) instruction.
+    pub fn process_toggle_freeze_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        freeze: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let source_account_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        let mut source_account = Account::unpack(&source_account_info.data.borrow())?;
+        if freeze && source_account.is_frozen() || !freeze && !source_account.is_frozen() {
+            return Err(TokenError::InvalidState.into());
+        }
+        if source_account.is_native() {
+            return Err(TokenError::NativeNotSupported.into());
+        }
+        if mint_info.key != &source_account.mint {
+            return Err(TokenError::MintMismatch.into());
+        }
+
+        let mint = Mint::unpack(&mint_info.data.borrow_mut())?;
+        match mint.freeze_authority {
+            COption::Some(authority) => Self::validate_owner(
+                program_id,
+                &authority,
+                authority_info,
+                account_info_iter.as_slice(),
+            ),
+            COption::None => Err(TokenError::MintCannotFreeze.into()),
+        }?;
+
+        source_account.state = if freeze {
+            AccountState::Frozen
+        } else {
+            AccountState::Initialized
+        };
+
+        Account::pack(source_account, &mut source_account_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes an [Instruction](enum.Instruction.html).
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+        let instruction = TokenInstruction::unpack(input)?;
+
+        match instruction {
+            TokenInstruction::InitializeMint {
+                decimals,
+                mint_authority,
+                freeze_authority,
+            } => {
+                msg!("Instruction: InitializeMint");
+                Self::process_initialize_mint(accounts, decimals, mint_authority, freeze_authority)
+            }
+            TokenInstruction::InitializeAccount => {
+                msg!("Instruction: InitializeAccount");
+                Self::process_initialize_account(accounts)
+            }
+            TokenInstruction::InitializeAccount2 { owner } => {
+                msg!("Instruction: InitializeAccount2");
+                Self::process_initialize_account2(accounts, owner)
+            }
+            TokenInstruction::InitializeMultisig { m } => {
+                msg!("Instruction: InitializeMultisig");
+                Self::process_initialize_multisig(accounts, m)
+            }
+            TokenInstruction::Transfer { amount } => {
+                msg!("Instruction: Transfer");
+                Self::process_transfer(program_id, accounts, amount, None)
+            }
+            TokenInstruction::Approve { amount } => {
+                msg!("Instruction: Approve");
+                Self::process_approve(program_id, accounts, amount, None)
+            }
+            TokenInstruction::ApproveUserPortfolio { amount } => {
+                msg!("Instruction: Approve");
+                Self::process_approve_User_Portfolio(program_id, accounts, amount)
+            }
+            TokenInstruction::Revoke => {
+                msg!("Instruction: Revoke");
+                Self::process_revoke(program_id, accounts)
+            }
+            TokenInstruction::SetAuthority {
+                authority_type,
+                new_authority,
+            } => {
+                msg!("Instruction: SetAuthority");
+                Self::process_set_authority(program_id, accounts, authority_type, new_authority)
+            }
+            TokenInstruction::MintTo { amount } => {
+                msg!("Instruction: MintTo");
+                Self::process_mint_to(program_id, accounts, amount, None)
+            }
+            TokenInstruction::Burn { amount } => {
+                msg!("Instruction: Burn");
+                Self::process_burn(program_id, accounts, amount, None)
+            }
+            TokenInstruction::CloseAccount => {
+                msg!("Instruction: CloseAccount");
+                Self::process_close_account(program_id, accounts)
+            }
+            TokenInstruction::FreezeAccount => {
+                msg!("Instruction: FreezeAccount");
+                Self::process_toggle_freeze_account(program_id, accounts, true)
+            }
+            TokenInstruction::ThawAccount => {
+                msg!("Instruction: FreezeAccount");
+                Self::process_toggle_freeze_account(program_id, accounts, false)
+            }
+            TokenInstruction::TransferChecked { amount, decimals } => {
+                msg!("Instruction: TransferChecked");
+                Self::process_transfer(program_id, accounts, amount, Some(decimals))
+            }
+            TokenInstruction::ApproveChecked { amount, decimals } => {
+                msg!("Instruction: ApproveChecked");
+                Self::process_approve(program_id, accounts, amount, Some(decimals))
+            }
+            TokenInstruction::MintToChecked { amount, decimals } => {
+                msg!("Instruction: MintToChecked");
+                Self::process_mint_to(program_id, accounts, amount, Some(decimals))
+            }
+            TokenInstruction::BurnChecked { amount, decimals } => {
+                msg!("Instruction: BurnChecked");
+                Self::process_burn(program_id, accounts, amount, Some(decimals))
+            }
+            TokenInstruction::Deposit { amount , minimum_amount_out} => {
+                msg!("Instruction: Deposit");
+                Self::process_deposit(program_id , accounts , amount , minimum_amount_out)
+            }
+            TokenInstruction::Withdraw { amount } => {
+                msg!("Instruction: Withdraw");
+                Self::process_withdraw(program_id , accounts , amount)
+            },
+            TokenInstruction::InitializePortfolio {
+                metaDataUrl,
+                metaDataHash,
+                amountAsset1,
+                periodAsset1,
+                amountAsset2,
+                periodAsset2,
+                amountAsset3,
+                periodAsset3,
+                amountAsset4,
+                periodAsset4,
+                amountAsset5,
+                periodAsset5,
+                amountAsset6,
+                periodAsset6,
+                amountAsset7,
+                periodAsset7,
+                amountAsset8,
+                periodAsset8,
+                amountAsset9,
+                periodAsset9,
+                // amountAsset10,
+                // periodAsset10
+             } => {
+                msg!("Instruction: InitializePortfolio");
+                Self::process_initialize_portfolio(program_id , accounts , 
+                    metaDataUrl,
+                   metaDataHash,
+                    amountAsset1,
+                    periodAsset1,
+                    amountAsset2,
+                    periodAsset2,
+                    amountAsset3,
+                    periodAsset3,
+                    amountAsset4,
+                    periodAsset4,
+                    amountAsset5,
+                    periodAsset5,
+                    amountAsset6,
+                    periodAsset6,
+                    amountAsset7,
+                    periodAsset7,
+                    amountAsset8,
+                    periodAsset8,
+                    amountAsset9,
+                    periodAsset9,
+                    // amountAsset10,
+                    // periodAsset10
+                )
+            },
+            TokenInstruction::createInitUserPortfolio {
+                delegated_amount,
+             } => {
+                msg!("Instruction: createInitUserPortfolio");
+                Self::process_create_Init_User_Portfolio(program_id , accounts ,
+                    delegated_amount,
+                )
+            },
+            TokenInstruction::InitializeHedgedMint {
+                target_ratio_bps,
+                deposit_fee_bps,
+                withdraw_fee_bps,
+            } => {
+                msg!("Instruction: InitializeHedgedMint");
+                Self::process_initialize_hedged_mint(
+                    program_id,
+                    accounts,
+                    target_ratio_bps,
+                    deposit_fee_bps,
+                    withdraw_fee_bps,
+                )
+            }
+            TokenInstruction::InitializePosition => {
+                msg!("Instruction: InitializePosition");
+                Self::process_initialize_position(program_id, accounts)
+            }
+            TokenInstruction::MigrateAccount => {
+                msg!("Instruction: MigrateAccount");
+                Self::process_migrate_account(program_id, accounts)
+            }
+            TokenInstruction::InitializeProgramConfig { protocol_fee_bps } => {
+                msg!("Instruction: InitializeProgramConfig");
+                Self::process_initialize_program_config(program_id, accounts, protocol_fee_bps)
+            }
+            TokenInstruction::SetProgramConfig { protocol_fee_bps } => {
+                msg!("Instruction: SetProgramConfig");
+                Self::process_set_program_config(program_id, accounts, protocol_fee_bps)
+            }
+            TokenInstruction::SetPause { paused } => {
+                msg!("Instruction: SetPause");
+                Self::process_set_pause(program_id, accounts, paused)
+            }
+            TokenInstruction::RegisterAsset { kind } => {
+                msg!("Instruction: RegisterAsset");
+                Self::process_register_asset(program_id, accounts, kind)
+            }
+            TokenInstruction::UpdateAsset { kind } => {
+                msg!("Instruction: UpdateAsset");
+                Self::process_update_asset(program_id, accounts, kind)
+            }
+            TokenInstruction::DeregisterAsset => {
+                msg!("Instruction: DeregisterAsset");
+                Self::process_deregister_asset(program_id, accounts)
+            }
+            TokenInstruction::SetDepositLimits {
+                max_supply,
+                max_deposit,
+                max_owner_deposit,
+            } => {
+                msg!("Instruction: SetDepositLimits");
+                Self::process_set_deposit_limits(
+                    program_id,
+                    accounts,
+                    max_supply,
+                    max_deposit,
+                    max_owner_deposit,
+                )
+            }
+            TokenInstruction::InitializeDepositRecord => {
+                msg!("Instruction: InitializeDepositRecord");
+                Self::process_initialize_deposit_record(program_id, accounts)
+            }
+            TokenInstruction::SetStopLoss {
+                stop_price,
+                trailing_bps,
+                max_slippage_bps,
+            } => {
+                msg!("Instruction: SetStopLoss");
+                Self::process_set_stop_loss(
+                    program_id,
+                    accounts,
+                    stop_price,
+                    trailing_bps,
+                    max_slippage_bps,
+                )
+            }
+            TokenInstruction::TriggerConditions { minimum_amount_out } => {
+                msg!("Instruction: TriggerConditions");
+                Self::process_trigger_conditions(program_id, accounts, minimum_amount_out)
+            }
+            TokenInstruction::SetTakeProfit {
+                trigger_bps,
+                sell_bps,
+                max_slippage_bps,
+                distribute,
+            } => {
+                msg!("Instruction: SetTakeProfit");
+                Self::process_set_take_profit(
+                    program_id,
+                    accounts,
+                    trigger_bps,
+                    sell_bps,
+                    max_slippage_bps,
+                    distribute,
+                )
+            }
+            TokenInstruction::HarvestProfit { minimum_amount_out } => {
+                msg!("Instruction: HarvestProfit");
+                Self::process_harvest_profit(program_id, accounts, minimum_amount_out)
+            }
+            TokenInstruction::ClaimRewards => {
+                msg!("Instruction: ClaimRewards");
+                Self::process_claim_rewards(program_id, accounts)
+            }
+            TokenInstruction::InitializeDca {
+                asset_index,
+                tranches,
+                max_slippage_bps,
+            } => {
+                msg!("Instruction: InitializeDca");
+                Self::process_initialize_dca(
+                    program_id,
+                    accounts,
+                    asset_index,
+                    tranches,
+                    max_slippage_bps,
+                )
+            }
+            TokenInstruction::DepositDca { amount } => {
+                msg!("Instruction: DepositDca");
+                Self::process_deposit_dca(program_id, accounts, amount)
+            }
+            TokenInstruction::ExecuteDca { minimum_amount_out } => {
+                msg!("Instruction: ExecuteDca");
+                Self::process_execute_dca(program_id, accounts, minimum_amount_out)
+            }
+            TokenInstruction::WithdrawDca { usdc, asset } => {
+                msg!("Instruction: WithdrawDca");
+                Self::process_withdraw_dca(program_id, accounts, usdc, asset)
+            }
+            TokenInstruction::RequestWithdraw { amount } => {
+                msg!("Instruction: RequestWithdraw");
+                Self::process_request_withdraw(program_id, accounts, amount)
+            }
+            TokenInstruction::SetWithdrawDelay {
+                delay_slots,
+                bypass,
+            } => {
+                msg!("Instruction: SetWithdrawDelay");
+                Self::process_set_withdraw_delay(program_id, accounts, delay_slots, bypass)
+            }
+        }
+    }
+
+    /// Deposit nAsset
+    pub fn process_create_Init_User_Portfolio(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        delegated_amount:u64,
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let user_portfolio_account = next_account_info(accounts_iter)?;
+        let portfolio_address = next_account_info(accounts_iter)?;
+        let owner = next_account_info(accounts_iter)?;
+        let delegate = next_account_info(accounts_iter)?;
+        let mut splu_assets = [Pubkey::default(); 9];
+        let mut asset_infos = Vec::with_capacity(splu_assets.len());
+        for splu_asset in splu_assets.iter_mut() {
+            let asset_info = next_account_info(accounts_iter)?;
+            *splu_asset = *asset_info.key;
+            asset_infos.push(asset_info);
+        }
+        let deposit_limits_info = next_account_info(accounts_iter)?;
+        let deposit_record_info = next_account_info(accounts_iter)?;
+        let program_config_info = next_account_info(accounts_iter)?;
+        let mut user_portfolio = UserPortfolio::unpack(&mut user_portfolio_account.data.borrow())?;
+
+        if !owner.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Self::unpack_program_config(program_id, program_config_info)?
+            .check_not_paused(PAUSE_DEPOSIT)?;
+        // the portfolio may have no limits, in which case nothing is counted
+        if let Some(mut deposit_limits) = Self::find_deposit_limits(
+            program_id,
+            portfolio_address.key,
+            std::slice::from_ref(deposit_limits_info),
+            true,
+        )? {
+            // the caps count what the asset accounts of the user portfolio
+            // actually hold, each account once
+            let mut deposited = 0u64;
+            for (i, asset_info) in asset_infos.iter().enumerate() {
+                if splu_assets[..i].contains(asset_info.key) {
+                    continue;
+                }
+                if asset_info.owner != program_id {
+                    return Err(ProgramError::IncorrectProgramId);
+                }
+                let asset_account = Account::unpack(&asset_info.data.borrow())?;
+                if asset_account.owner != *user_portfolio_account.key {
+                    return Err(TokenError::OwnerMismatch.into());
+                }
+                deposited = deposited
+                    .checked_add(asset_account.amount)
+                    .ok_or(TokenError::Overflow)?;
+            }
+            let mut deposit_record = Self::unpack_deposit_record(
+                program_id,
+                deposit_record_info,
+                portfolio_address.key,
+                owner.key,
+            )?;
+            deposit_limits.supply = deposit_limits
+                .supply
+                .checked_add(deposited)
+                .ok_or(TokenError::Overflow)?;
+            deposit_limits.check_deposit(
+                deposited,
+                deposit_record.deposited,
+                deposit_limits.supply,
+            )?;
+            deposit_record.deposited = deposit_record
+                .deposited
+                .checked_add(deposited)
+                .ok_or(TokenError::Overflow)?;
+            DepositLimits::pack(deposit_limits, &mut deposit_limits_info.data.borrow_mut())?;
+            DepositRecord::pack(deposit_record, &mut deposit_record_info.data.borrow_mut())?;
+        }
+
+        user_portfolio.user_portfolio_account = *user_portfolio_account.key;
+        user_portfolio.portfolio_address = *portfolio_address.key;
+        user_portfolio.owner = *owner.key;
+        user_portfolio.delegated_amount = delegated_amount;
+        user_portfolio.splu_asset1 = splu_assets[0];
+        user_portfolio.splu_asset2 = splu_assets[1];
+        user_portfolio.splu_asset3 = splu_assets[2];
+        user_portfolio.splu_asset4 = splu_assets[3];
+        user_portfolio.splu_asset5 = splu_assets[4];
+        user_portfolio.splu_asset6 = splu_assets[5];
+        user_portfolio.splu_asset7 = splu_assets[6];
+        user_portfolio.splu_asset8 = splu_assets[7];
+        user_portfolio.splu_asset9 = splu_assets[8];
+     /*
+        //portfolio.delegate = COption::None;
+        portfolio.delegated_amount = 0;
+        portfolio.userAccount = *user_account.key;
+        portfolio.userPortfolioAccount = *userPortfolioAccount.key;
+        portfolio.portfolioAddress = *portfolioAddress.key;
+
+        UserPortfolio::pack(portfolio, &mut user_account.data.borrow_mut())?;
+*/
+        UserPortfolio::pack(user_portfolio, &mut user_portfolio_account.data.borrow_mut())?;
+        TokenEvent::UserPortfolioCreated(UserPortfolioCreated {
+            user_portfolio: *user_portfolio_account.key,
+            portfolio: *portfolio_address.key,
+            owner: *owner.key,
+            delegated_amount,
+        })
+        .emit();
+        Ok(())
+
+    }
+
+
+
+    ///  Create init portfolio
+    pub fn process_initialize_portfolio(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        metaDataUrl : Vec<u8>,
+        metaDataHash : u16,
+        amountAsset1 : u8,
+        periodAsset1 : u8,
+        amountAsset2 : u8,
+        periodAsset2 : u8,
+        amountAsset3 : u8,
+        periodAsset3 : u8,
+        amountAsset4 : u8,
+        periodAsset4 : u8,
+        amountAsset5 : u8,
+        periodAsset5 : u8,
+        amountAsset6 : u8,
+        periodAsset6 : u8,
+        amountAsset7 : u8,
+        periodAsset7 : u8,
+        amountAsset8 : u8,
+        periodAsset8 : u8,
+        amountAsset9 : u8,
+        periodAsset9 : u8
+        //,
+        // amountAsset10 : u8,
+        // periodAsset10 : u8
+    ) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+        let portfolioAccount = next_account_info(accounts_iter)?;
+        let creatorPortfolio = next_account_info(accounts_iter)?;
+        let addressAsset1 = next_account_info(accounts_iter)?;
+        let assetToSoldIntoAsset1 = next_account_info(accounts_iter)?;
+        let addressAsset2 = next_account_info(accounts_iter)?;
+        let assetToSoldIntoAsset2 = next_account_info(accounts_iter)?;
+        let addressAsset3 = next_account_info(accounts_iter)?;
+        let assetToSoldIntoAsset3 = next_account_info(accounts_iter)?;
+        let addressAsset4 = next_account_info(accounts_iter)?;
+        let assetToSoldIntoAsset4 = next_account_info(accounts_iter)?;
+        let addressAsset5 = next_account_info(accounts_iter)?;
+        let assetToSoldIntoAsset5 = next_account_info(accounts_iter)?;
+        let addressAsset6 = next_account_info(accounts_iter)?;
+        let assetToSoldIntoAsset6 = next_account_info(accounts_iter)?;
+        let addressAsset7 = next_account_info(accounts_iter)?;
+        let assetToSoldIntoAsset7 = next_account_info(accounts_iter)?;
+        let addressAsset8 = next_account_info(accounts_iter)?;
+        let assetToSoldIntoAsset8 = next_account_info(accounts_iter)?;
+        let addressAsset9 = next_account_info(accounts_iter)?;
+        let assetToSoldIntoAsset9 = next_account_info(accounts_iter)?;
+        let owner = next_account_info(accounts_iter)?;
+        if !creatorPortfolio.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        for address_asset in [
+            addressAsset1,
+            addressAsset2,
+            addressAsset3,
+            addressAsset4,
+            addressAsset5,
+            addressAsset6,
+            addressAsset7,
+            addressAsset8,
+            addressAsset9,
+        ] {
+            let asset_entry_info = next_account_info(accounts_iter)?;
+            Self::unpack_asset_entry(program_id, asset_entry_info, address_asset.key)?;
+        }
+
+
+        let mut new_portfolio = Portfolio::unpack(&mut portfolioAccount.data.borrow())?;
+
+        if new_portfolio.is_initialize == 1 {
+            return Err(TokenError::AlreadyInUse.into());
+        }
+        new_portfolio.is_initialize = 1 ;
+        new_portfolio.portfolio_account = *portfolioAccount.key;
+        new_portfolio.creator_portfolio = *creatorPortfolio.key;
+        new_portfolio.metadataUrl = metaDataUrl;
+        new_portfolio.metadataHash = metaDataHash;
+        new_portfolio.amountAsset1 = amountAsset1;
+        new_portfolio.addressAsset1 = *addressAsset1.key;
+        new_portfolio.periodAsset1 = periodAsset1;
+        new_portfolio.assetToSoldIntoAsset1 = *assetToSoldIntoAsset1.key; 
+        new_portfolio.amountAsset2 = amountAsset2;
+        new_portfolio.addressAsset2 = *addressAsset2.key;
+        new_portfolio.periodAsset2 = periodAsset2;
+        new_portfolio.assetToSoldIntoAsset2 = *assetToSoldIntoAsset2.key; 
+        new_portfolio.amountAsset3 = amountAsset3;
+        new_portfolio.addressAsset3 = *addressAsset3.key;
+        new_portfolio.periodAsset3 = periodAsset3;
+        new_portfolio.assetToSoldIntoAsset3 = *assetToSoldIntoAsset3.key; 
+        new_portfolio.amountAsset4 = amountAsset4;
+        new_portfolio.addressAsset4 = *addressAsset4.key;
+        new_portfolio.periodAsset4 = periodAsset4;
+        new_portfolio.assetToSoldIntoAsset4 = *assetToSoldIntoAsset4.key; 
+        new_portfolio.amountAsset5 = amountAsset5;
+        new_portfolio.addressAsset5 = *addressAsset5.key;
+        new_portfolio.periodAsset5 = periodAsset5;
+        new_portfolio.assetToSoldIntoAsset5 = *assetToSoldIntoAsset5.key;
+        new_portfolio.amountAsset6 = amountAsset6;
+        new_portfolio.addressAsset6 = *addressAsset6.key;
+        new_portfolio.periodAsset6 = periodAsset6;
+        new_portfolio.assetToSoldIntoAsset6 = *assetToSoldIntoAsset6.key ;
+        new_portfolio.amountAsset7 = amountAsset7;
+        new_portfolio.addressAsset7 = *addressAsset7.key;
+        new_portfolio.periodAsset7 = periodAsset7;
+        new_portfolio.assetToSoldIntoAsset7 = *assetToSoldIntoAsset7.key;
+        new_portfolio.amountAsset8 = amountAsset8;
+        new_portfolio.addressAsset8 = *addressAsset8.key;
+        new_portfolio.periodAsset8 = periodAsset8;
+        new_portfolio.assetToSoldIntoAsset8 = *assetToSoldIntoAsset8.key;
+        new_portfolio.amountAsset9 = amountAsset9;
+        new_portfolio.addressAsset9 = *addressAsset9.key;
+        new_portfolio.periodAsset9 = periodAsset9;
+        new_portfolio.assetToSoldIntoAsset9 = *assetToSoldIntoAsset9.key;
+
+        Portfolio::pack(new_portfolio, &mut portfolioAccount.data.borrow_mut())?;
+        TokenEvent::PortfolioCreated(PortfolioCreated {
+            portfolio: *portfolioAccount.key,
+            creator: *creatorPortfolio.key,
+            metadata_hash: metaDataHash,
+        })
+        .emit();
+        Ok(())
+
+    }
+    /// Deposit nAsset
+    pub fn process_deposit(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let hedge_config_info = next_account_info(account_info_iter)?;
+        let mint_info = next_account_info(account_info_iter)?;
+        let account_info = next_account_info(account_info_iter)?;
+        let position_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let source_authority_info = next_account_info(account_info_iter)?;
+        let asset_vault_info = next_account_info(account_info_iter)?;
+        let stable_vault_info = next_account_info(account_info_iter)?;
+        let fee_account_info = next_account_info(account_info_iter)?;
+        let protocol_fee_account_info = next_account_info(account_info_iter)?;
+        let vault_authority_info = next_account_info(account_info_iter)?;
+        let swap_info = next_account_info(account_info_iter)?;
+        let swap_authority_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let swap_program_info = next_account_info(account_info_iter)?;
+        let deposit_limits_info = next_account_info(account_info_iter)?;
+        let deposit_record_info = next_account_info(account_info_iter)?;
+        let stop_loss_info = next_account_info(account_info_iter)?;
+        let take_profit_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        // the `HedgeEpoch` of the position, owned by this program, may take
+        // the place of the host fee account
+        let host_fee_account_info = next_account_info(account_info_iter)
+            .ok()
+            .filter(|account_info| account_info.owner != program_id);
+
+        let program_config = Self::unpack_program_config(program_id, program_config_info)?;
+        program_config.check_not_paused(PAUSE_DEPOSIT)?;
+        let hedge_config = Self::unpack_hedge_config(program_id, hedge_config_info, mint_info.key)?;
+        if *asset_vault_info.key != hedge_config.asset_vault
+            || *stable_vault_info.key != hedge_config.stable_vault
+        {
+            return Err(TokenError::IncorrectVaultAccount.into());
+        }
+        if *fee_account_info.key != hedge_config.fee_account {
+            return Err(TokenError::IncorrectFeeAccount.into());
+        }
+        let vault_authority_bump = [hedge_config.vault_authority_bump];
+        let vault_authority_seeds = [
+            VAULT_AUTHORITY_SEED,
+            mint_info.key.as_ref(),
+            &vault_authority_bump,
+        ];
+        let vault_authority = Pubkey::create_program_address(&vault_authority_seeds, program_id)
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidVaultAuthority))?;
+        if *vault_authority_info.key != vault_authority {
+            return Err(TokenError::InvalidVaultAuthority.into());
+        }
+        let swap = Self::validate_swap_accounts(
+            &hedge_config,
+            swap_program_info,
+            swap_info,
+            swap_authority_info,
+            swap_source_info,
+            swap_destination_info,
+            pool_mint_info,
+            pool_fee_account_info,
+            token_program_info,
+        )?;
+
+        let mut mint = Mint::unpack(&mint_info.data.borrow())?;
+        let mut account = Account::unpack(&account_info.data.borrow())?;
+        if account.mint != *mint_info.key {
+            return Err(TokenError::MintMismatch.into());
+        }
+        if account.is_frozen() {
+            return Err(TokenError::AccountFrozen.into());
+        }
+        let mut position = Self::unpack_position(program_id, position_info, account_info.key)?;
+        Self::settle_vault_swaps(program_id, &hedge_config, &mut position, accounts)?;
+        let deposit_limits =
+            Self::unpack_deposit_limits(program_id, deposit_limits_info, mint_info.key)?;
+        let mut deposit_record = Self::unpack_deposit_record(
+            program_id,
+            deposit_record_info,
+            mint_info.key,
+            &account.owner,
+        )?;
+        let stop_loss_triggered =
+            Self::stop_loss_triggered(program_id, stop_loss_info, mint_info.key)?;
+        let reward_per_share = Self::reward_per_share(program_id, take_profit_info, mint_info.key)?;
+        position.settle_rewards(account.amount, reward_per_share)?;
+        position.start_cost_basis(account.amount);
+        position.record_deposit(Self::pool_value(
+            &swap.token_program_id,
+            swap_source_info,
+            swap_destination_info,
+            amount,
+        )?)?;
+
+        let fee = basis_points_of(amount, hedge_config.deposit_fee_bps)?;
+        let protocol_fee = basis_points_of(fee, program_config.protocol_fee_bps)?;
+        let net_amount = amount.checked_sub(fee).ok_or(TokenError::Overflow)?;
+        deposit_limits.check_deposit(
+            amount,
+            deposit_record.deposited,
+            mint.supply.checked_add(net_amount).ok_or(TokenError::Overflow)?,
+        )?;
+        deposit_record.deposited = deposit_record
+            .deposited
+            .checked_add(amount)
+            .ok_or(TokenError::Overflow)?;
+        let hedged_amount = if stop_loss_triggered {
+            net_amount
+        } else {
+            basis_points_of(net_amount, hedge_config.target_ratio_bps)?
+        };
+        if hedged_amount > 0 {
+            program_config.check_not_paused(PAUSE_SWAP)?;
+        }
+        if protocol_fee > 0 {
+            let protocol_fee_account = unpack_token_account(
+                &swap.token_program_id,
+                protocol_fee_account_info.owner,
+                &protocol_fee_account_info.data.borrow(),
+            )?;
+            if protocol_fee_account.owner != program_config.protocol_fee_owner {
+                return Err(TokenError::IncorrectProtocolFeeAccount.into());
+            }
+        }
+
+        Self::collect_fee(
+            mint_info.key,
+            source_info,
+            source_authority_info,
+            fee_account_info,
+            token_program_info,
+            fee - protocol_fee,
+            &[],
+        )?;
+        Self::collect_fee(
+            mint_info.key,
+            source_info,
+            source_authority_info,
+            protocol_fee_account_info,
+            token_program_info,
+            protocol_fee,
+            &[],
+        )?;
+        invoke(
+            &transfer(
+                token_program_info.key,
+                source_info.key,
+                asset_vault_info.key,
+                source_authority_info.key,
+                &[],
+                net_amount,
+            )?,
+            &[
+                source_info.clone(),
+                asset_vault_info.clone(),
+                source_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+
+        let mut stable_amount = 0;
+        if hedged_amount > 0 {
+            stable_amount = Self::swap_vault(
+                &swap,
+                &vault_authority_seeds,
+                asset_vault_info,
+                stable_vault_info,
+                vault_authority_info,
+                swap_info,
+                swap_authority_info,
+                swap_source_info,
+                swap_destination_info,
+                pool_mint_info,
+                pool_fee_account_info,
+                token_program_info,
+                swap_program_info,
+                host_fee_account_info,
+                hedged_amount,
+                minimum_amount_out,
+            )?;
+            TokenEvent::Rebalance(RebalanceEvent {
+                mint: *mint_info.key,
+                asset_sold: hedged_amount,
+                usdc_bought: stable_amount,
+            })
+            .emit();
+        }
+
+        account.amount = account
+            .amount
+            .checked_add(net_amount)
+            .ok_or(TokenError::Overflow)?;
+        position.asset = position
+            .asset
+            .checked_add(net_amount - hedged_amount)
+            .ok_or(TokenError::Overflow)?;
+        position.usdc = position
+            .usdc
+            .checked_add(stable_amount)
+            .ok_or(TokenError::Overflow)?;
+        position.last_deposit_slot = clock.slot;
+        mint.supply = mint
+            .supply
+            .checked_add(net_amount)
+            .ok_or(TokenError::Overflow)?;
+
+        Account::pack(account, &mut account_info.data.borrow_mut())?;
+        Position::pack(position, &mut position_info.data.borrow_mut())?;
+        Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+        DepositRecord::pack(deposit_record, &mut deposit_record_info.data.borrow_mut())?;
+
+        TokenEvent::Deposit(DepositEvent {
+            mint: *mint_info.key,
+            account: *account_info.key,
+            amount: net_amount,
+            asset: net_amount - hedged_amount,
+            usdc: stable_amount,
+        })
+        .emit();
+        Ok(())
+    }
+
+
+/*
+    pub fn process_deposit(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        volatility: u64,
+        nonce: u8,
+
+
+
+    ) -> ProgramResult {
+
+        let accounts_iter = &mut accounts.iter();
+
+        let swap_info = next_account_info(accounts_iter)?;
+        let owner = next_account_info(accounts_iter)?;
+        let account = next_account_info(accounts_iter)?;
+        let source_info = next_account_info(accounts_iter)?;
+        let swap_source_info = next_account_info(accounts_iter)?;
+        let swap_destination_info = next_account_info(accounts_iter)?;
+        let destination_info = next_account_info(accounts_iter)?;
+        let pool_mint_info = next_account_info(accounts_iter)?;
+        let pool_fee_account_info = next_account_info(accounts_iter)?;
+        let token_program_info = next_account_info(accounts_iter)?;
+        let host_fee_account=next_account_info(accounts_iter)?;
+	    let prog_address = next_account_info(accounts_iter)?;
+        msg!("prog_address issssss {}" , prog_address.key);
+        msg!("0");
+        let program = next_account_info(accounts_iter)?;
+        msg!("program is {}" , program.key);
+
+       // let expected_allocated_key =Pubkey::create_program_address(&[b"Zouaoui karimaaaaaaaaaaaaaaaaaaaaaaaa",b"Silvester Stalone"], program_id)?;
+
+       let mut buf = Vec::new();
+       let instruction:u8 = 1;
+       let amountIn:u64 = amount;
+       let minimumAmountOut:u64=0;
+
+       msg!("1");
+
+       let mut vacAccounts = Vec::new();
+       buf.push(instruction);
+       buf.extend_from_slice(&amountIn.to_le_bytes());
+       buf.extend_from_slice(&minimumAmountOut.to_le_bytes());
+       msg!("2");
+       vacAccounts.push(AccountMeta::new(*swap_info.key, false));
+       vacAccounts.push(AccountMeta::new(*owner.key, false));
+       vacAccounts.push(AccountMeta::new(*account.key, false));
+       vacAccounts.push(AccountMeta::new(*source_info.key, false));
+       vacAccounts.push(AccountMeta::new(*swap_source_info.key, false));
+       vacAccounts.push(AccountMeta::new(*swap_destination_info.key, false));
+       vacAccounts.push(AccountMeta::new(*destination_info.key, false));
+       vacAccounts.push(AccountMeta::new(*pool_mint_info.key, false));
+       vacAccounts.push(AccountMeta::new(*pool_fee_account_info.key, false));
+       vacAccounts.push(AccountMeta::new(*token_program_info.key, false));
+       vacAccounts.push(AccountMeta::new(*host_fee_account.key,false));
+       msg!("3");
+       let ix = Instruction {
+           accounts:vacAccounts,
+           program_id: *program.key,
+           data: buf,
+       };
+     /*  let result = invoke_signed(&ix, 
+       &[account.clone(), prog_address.clone() , program.clone()],
+       &[&[b"Mohamed zouaouii2",b"Silvester Stalone"]]
+       )?;*/
+       msg!("4 {}",account.key);
+
+       let mut source_account = Account::unpack(&mut account.data.borrow())?;
+
+       msg!("5");
+
+      Self::validate_owner(
+           program_id,
+           &source_account.owner,
+           owner,
+           accounts_iter.as_slice(),
+       )?;
+       
+   
+         source_account.amount = source_account
+           .amount
+           .checked_add(amount)
+           .ok_or(TokenError::Overflow)?;
+       source_account.usdc = source_account
+           .usdc
+           .checked_add(400)
+           .ok_or(TokenError::Overflow)?;
+
+
+           msg!("5");
+
+       source_account.asset = source_account
+           .asset
+           .checked_add(5000)
+           .ok_or(TokenError::Overflow)?;
+     Account::pack(source_account, &mut account.data.borrow_mut())?;
+       Ok(())
+   }
+*/
+
+   /// withdraw nAsset
+   pub fn process_withdraw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+  let account_info_iter = &mut accounts.iter();
+
+    let program_config_info = next_account_info(account_info_iter)?;
+
+    let account= next_account_info(account_info_iter)?;
+
+    let position_info = next_account_info(account_info_iter)?;
+
+    let take_profit_info = next_account_info(account_info_iter)?;
+
+    let hedge_config_info = next_account_info(account_info_iter)?;
+
+    let swap_info = next_account_info(account_info_iter)?;
+
+    let asset_reserve_info = next_account_info(account_info_iter)?;
+
+    let stable_reserve_info = next_account_info(account_info_iter)?;
+
+    let withdraw_ticket_info = next_account_info(account_info_iter)?;
+
+    let mint_info = next_account_info(account_info_iter)?;
+
+    let asset_vault_info = next_account_info(account_info_iter)?;
+
+    let stable_vault_info = next_account_info(account_info_iter)?;
+
+    let asset_destination_info = next_account_info(account_info_iter)?;
+
+    let stable_destination_info = next_account_info(account_info_iter)?;
+
+    let fee_account_info = next_account_info(account_info_iter)?;
+
+    let stable_fee_account_info = next_account_info(account_info_iter)?;
+
+    let protocol_fee_account_info = next_account_info(account_info_iter)?;
+
+    let protocol_stable_fee_account_info = next_account_info(account_info_iter)?;
+
+    let vault_authority_info = next_account_info(account_info_iter)?;
+
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let owner = next_account_info(account_info_iter)?;
+
+    let program_config = Self::unpack_program_config(program_id, program_config_info)?;
+    program_config.check_not_paused(PAUSE_WITHDRAW)?;
+    let mut source_account = Account::unpack(&account.data.borrow())?;
+    let mut position = Self::unpack_position(program_id, position_info, account.key)?;
+    let hedge_config =
+        Self::unpack_hedge_config(program_id, hedge_config_info, &source_account.mint)?;
+    if *swap_info.key != hedge_config.swap_pool {
+        return Err(TokenError::SwapPoolMismatch.into());
+    }
+    if *swap_info.owner != hedge_config.swap_program_id {
+        return Err(TokenError::IncorrectSwapProgramId.into());
+    }
+    let swap = SwapState::unpack(&swap_info.data.borrow())
+        .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidSwapState))?;
+    if swap.reserve_for_mint(&hedge_config.asset_mint) != Some(*asset_reserve_info.key)
+        || swap.reserve_for_mint(&hedge_config.stable_mint) != Some(*stable_reserve_info.key)
+    {
+        return Err(TokenError::IncorrectSwapAccount.into());
+    }
+    if *mint_info.key != source_account.mint {
+        return Err(TokenError::MintMismatch.into());
+    }
+    let mut mint = Mint::unpack(&mint_info.data.borrow())?;
+    if *asset_vault_info.key != hedge_config.asset_vault
+        || *stable_vault_info.key != hedge_config.stable_vault
+    {
+        return Err(TokenError::IncorrectVaultAccount.into());
+    }
+    if *fee_account_info.key != hedge_config.fee_account {
+        return Err(TokenError::IncorrectFeeAccount.into());
+    }
+    unpack_token_account(
+        token_program_info.key,
+        asset_vault_info.owner,
+        &asset_vault_info.data.borrow(),
+    )?;
+    unpack_token_account(
+        token_program_info.key,
+        stable_vault_info.owner,
+        &stable_vault_info.data.borrow(),
+    )?;
+    let vault_authority_bump = [hedge_config.vault_authority_bump];
+    let vault_authority_seeds = [
+        VAULT_AUTHORITY_SEED,
+        mint_info.key.as_ref(),
+        &vault_authority_bump,
+    ];
+    let vault_authority = Pubkey::create_program_address(&vault_authority_seeds, program_id)
+        .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidVaultAuthority))?;
+    if *vault_authority_info.key != vault_authority {
+        return Err(TokenError::InvalidVaultAuthority.into());
+    }
+
+
+   Self::validate_owner(
+        program_id,
+        &source_account.owner,
+        owner,
+        account_info_iter.as_slice(),
+    )?;
+
+    if source_account.amount < amount {
+        return Err(TokenError::InsufficientFunds.into());
+    }
+    if !program_config.bypass_withdraw_delay {
+        if position.last_deposit_slot == clock.slot {
+            return Err(TokenError::WithdrawInDepositSlot.into());
+        }
+        if program_config.withdraw_delay_slots > 0 {
+            let mut withdraw_ticket =
+                Self::unpack_withdraw_ticket(program_id, withdraw_ticket_info, account.key)?;
+            if clock.slot < withdraw_ticket.claimable_slot {
+                return Err(TokenError::WithdrawNotClaimable.into());
+            }
+            withdraw_ticket.amount = withdraw_ticket
+                .amount
+                .checked_sub(amount)
+                .ok_or(TokenError::WithdrawNotRequested)?;
+            WithdrawTicket::pack(withdraw_ticket, &mut withdraw_ticket_info.data.borrow_mut())?;
+        }
+    }
+    Self::settle_vault_swaps(program_id, &hedge_config, &mut position, accounts)?;
+    let reward_per_share =
+        Self::reward_per_share(program_id, take_profit_info, &source_account.mint)?;
+    position.settle_rewards(source_account.amount, reward_per_share)?;
+    let amount_usdc_burned = proportional_share(position.usdc, amount, source_account.amount)?;
+    let amount_asset_burned = proportional_share(position.asset, amount, source_account.amount)?;
+    let asset_fee = basis_points_of(amount_asset_burned, hedge_config.withdraw_fee_bps)?;
+    let usdc_fee = basis_points_of(amount_usdc_burned, hedge_config.withdraw_fee_bps)?;
+    let asset_protocol_fee = basis_points_of(asset_fee, program_config.protocol_fee_bps)?;
+    let usdc_protocol_fee = basis_points_of(usdc_fee, program_config.protocol_fee_bps)?;
+    let value = Self::pool_value(
+        &swap.token_program_id,
+        asset_reserve_info,
+        stable_reserve_info,
+        amount_asset_burned - asset_fee,
+    )?
+    .checked_add(amount_usdc_burned - usdc_fee)
+    .ok_or(TokenError::Overflow)?;
+    position.record_withdrawal(amount, source_account.amount, value)?;
+    if usdc_fee > usdc_protocol_fee {
+        let fee_account = unpack_token_account(
+            token_program_info.key,
+            fee_account_info.owner,
+            &fee_account_info.data.borrow(),
+        )?;
+        let stable_fee_account = unpack_token_account(
+            token_program_info.key,
+            stable_fee_account_info.owner,
+            &stable_fee_account_info.data.borrow(),
+        )?;
+        if stable_fee_account.owner != fee_account.owner {
+            return Err(TokenError::IncorrectFeeAccount.into());
+        }
+    }
+    for (protocol_fee_account_info, protocol_fee) in [
+        (protocol_fee_account_info, asset_protocol_fee),
+        (protocol_stable_fee_account_info, usdc_protocol_fee),
+    ] {
+        if protocol_fee > 0 {
+            let protocol_fee_account = unpack_token_account(
+                token_program_info.key,
+                protocol_fee_account_info.owner,
+                &protocol_fee_account_info.data.borrow(),
+            )?;
+            if protocol_fee_account.owner != program_config.protocol_fee_owner {
+                return Err(TokenError::IncorrectProtocolFeeAccount.into());
+            }
+        }
+    }
+
+    source_account.amount = source_account
+        .amount
+        .checked_sub(amount)
+        .ok_or(TokenError::Overflow)?;
+
+    position.usdc = position
+        .usdc
+        .checked_sub(amount_usdc_burned)
+        .ok_or(TokenError::Overflow)?;
+
+    position.asset = position
+        .asset
+        .checked_sub(amount_asset_burned)
+        .ok_or(TokenError::Overflow)?;
+
+    mint.supply = mint
+        .supply
+        .checked_sub(amount)
+        .ok_or(TokenError::Overflow)?;
+
+  Account::pack(source_account, &mut account.data.borrow_mut())?;
+  Position::pack(position, &mut position_info.data.borrow_mut())?;
+  Mint::pack(mint, &mut mint_info.data.borrow_mut())?;
+
+    for (vault_info, destination_info, amount) in [
+        (asset_vault_info, asset_destination_info, amount_asset_burned - asset_fee),
+        (stable_vault_info, stable_destination_info, amount_usdc_burned - usdc_fee),
+    ] {
+        if amount == 0 {
+            continue;
+        }
+        invoke_signed(
+            &transfer(
+                token_program_info.key,
+                vault_info.key,
+                destination_info.key,
+                vault_authority_info.key,
+                &[],
+                amount,
+            )?,
+            &[
+                vault_info.clone(),
+                destination_info.clone(),
+                vault_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[&vault_authority_seeds],
+        )?;
+    }
+    for (vault_info, fee_account_info, fee) in [
+        (asset_vault_info, fee_account_info, asset_fee - asset_protocol_fee),
+        (asset_vault_info, protocol_fee_account_info, asset_protocol_fee),
+        (stable_vault_info, stable_fee_account_info, usdc_fee - usdc_protocol_fee),
+        (stable_vault_info, protocol_stable_fee_account_info, usdc_protocol_fee),
+    ] {
+        Self::collect_fee(
+            mint_info.key,
+            vault_info,
+            vault_authority_info,
+            fee_account_info,
+            token_program_info,
+            fee,
+            &[&vault_authority_seeds],
+        )?;
+    }
+
+    TokenEvent::Withdraw(WithdrawEvent {
+        mint: source_account.mint,
+        account: *account.key,
+        amount,
+        asset: amount_asset_burned,
+        usdc: amount_usdc_burned,
+    })
+    .emit();
+    Ok(())
+}
+    /// Unpacks the program configuration.
+    pub fn unpack_program_config(
+        program_id: &Pubkey,
+        program_config_info: &AccountInfo,
+    ) -> Result<ProgramConfig, ProgramError> {
+        if program_config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let program_config = ProgramConfig::unpack(&program_config_info.data.borrow())?;
+        let program_config_key = Pubkey::create_program_address(
+            &[PROGRAM_CONFIG_SEED, &[program_config.bump]],
+            program_id,
+        )?;
+        if *program_config_info.key != program_config_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(program_config)
+    }
+
+    /// Unpacks the registry entry of `mint`, failing if the mint is not approved.
+    pub fn unpack_asset_entry(
+        program_id: &Pubkey,
+        asset_entry_info: &AccountInfo,
+        mint: &Pubkey,
+    ) -> Result<AssetEntry, ProgramError> {
+        let asset_entry = Self::unpack_registry_entry(program_id, asset_entry_info)?;
+        if asset_entry.mint != *mint {
+            return Err(TokenError::MintMismatch.into());
+        }
+        Ok(asset_entry)
+    }
+
+    /// Unpacks a registry entry stored at the address derived from its mint.
+    fn unpack_registry_entry(
+        program_id: &Pubkey,
+        asset_entry_info: &AccountInfo,
+    ) -> Result<AssetEntry, ProgramError> {
+        if asset_entry_info.owner != program_id {
+            return Err(TokenError::AssetNotRegistered.into());
+        }
+        let asset_entry = AssetEntry::unpack_unchecked(&asset_entry_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::AssetNotRegistered))?;
+        if !asset_entry.is_initialized {
+            return Err(TokenError::AssetNotRegistered.into());
+        }
+        let asset_entry_key = Pubkey::create_program_address(
+            &[ASSET_ENTRY_SEED, asset_entry.mint.as_ref(), &[asset_entry.bump]],
+            program_id,
+        )?;
+        if *asset_entry_info.key != asset_entry_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(asset_entry)
+    }
+
+    /// Checks that `upgrade_authority_info` signed and is the upgrade authority
+    /// recorded in the program data account of `program_id`.
+    fn validate_upgrade_authority(
+        program_id: &Pubkey,
+        program_data_info: &AccountInfo,
+        upgrade_authority_info: &AccountInfo,
+    ) -> ProgramResult {
+        let (program_data_key, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        if *program_data_info.key != program_data_key
+            || *program_data_info.owner != bpf_loader_upgradeable::id()
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        // `UpgradeableLoaderState::ProgramData`: variant 3, slot, optional authority
+        let program_data = program_data_info.data.borrow();
+        let program_data = program_data
+            .get(..45)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let program_data = array_ref![program_data, 0, 45];
+        let (state, _slot, has_authority, authority) = array_refs![program_data, 4, 8, 1, 32];
+        if u32::from_le_bytes(*state) != 3 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if *has_authority != [1] || authority != upgrade_authority_info.key.as_ref() {
+            return Err(TokenError::IncorrectUpgradeAuthority.into());
+        }
+        if !upgrade_authority_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+    /// Transfers `amount` from the depositor's source account, or from a vault
+    /// signed for with `signer_seeds`, to `fee_account_info`.
+    fn collect_fee<'a>(
+        mint: &Pubkey,
+        source_info: &AccountInfo<'a>,
+        source_authority_info: &AccountInfo<'a>,
+        fee_account_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        amount: u64,
+        signer_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if amount == 0 {
+            return Ok(());
+        }
+        invoke_signed(
+            &transfer(
+                token_program_info.key,
+                source_info.key,
+                fee_account_info.key,
+                source_authority_info.key,
+                &[],
+                amount,
+            )?,
+            &[
+                source_info.clone(),
+                fee_account_info.clone(),
+                source_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            signer_seeds,
+        )?;
+        TokenEvent::FeeCollected(FeeCollected {
+            mint: *mint,
+            fee_account: *fee_account_info.key,
+            amount,
+        })
+        .emit();
+        Ok(())
+    }
+
+    /// Unpacks the hedge configuration of `mint`, failing if the mint was never
+    /// configured for hedging.
+    pub fn unpack_hedge_config(
+        program_id: &Pubkey,
+        hedge_config_info: &AccountInfo,
+        mint: &Pubkey,
+    ) -> Result<HedgeConfig, ProgramError> {
+        if hedge_config_info.owner != program_id {
+            return Err(TokenError::MintNotHedged.into());
+        }
+        let hedge_config = HedgeConfig::unpack_unchecked(&hedge_config_info.data.borrow())?;
+        if !hedge_config.is_initialized {
+            return Err(TokenError::MintNotHedged.into());
+        }
+        if hedge_config.mint != *mint {
+            return Err(TokenError::MintMismatch.into());
+        }
+        let hedge_config_key = Pubkey::create_program_address(
+            &[HEDGE_CONFIG_SEED, mint.as_ref(), &[hedge_config.bump]],
+            program_id,
+        )?;
+        if *hedge_config_info.key != hedge_config_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(hedge_config)
+    }
+
+    /// Unpacks the position of the token account `account`.
+    pub fn unpack_position(
+        program_id: &Pubkey,
+        position_info: &AccountInfo,
+        account: &Pubkey,
+    ) -> Result<Position, ProgramError> {
+        if position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let position = Position::unpack(&position_info.data.borrow())?;
+        if position.account != *account {
+            return Err(TokenError::PositionMismatch.into());
+        }
+        let position_key = Pubkey::create_program_address(
+            &[POSITION_SEED, account.as_ref(), &[position.bump]],
+            program_id,
+        )?;
+        if *position_info.key != position_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(position)
+    }
+
+    /// Finds the hedge configuration of `mint` among `account_infos`. SPL
+    /// instructions only carry it for hedged mints, which then also require
+    /// the positions of the accounts they change.
+    fn find_hedge_config(
+        program_id: &Pubkey,
+        mint: &Pubkey,
+        account_infos: &[AccountInfo],
+    ) -> Result<Option<HedgeConfig>, ProgramError> {
+        let (hedge_config_key, _) = find_hedge_config_with_program_id(program_id, mint);
+        let hedge_config_info = match account_infos
+            .iter()
+            .find(|account_info| *account_info.key == hedge_config_key)
+        {
+            Some(hedge_config_info) if hedge_config_info.owner == program_id => hedge_config_info,
+            _ => return Ok(None),
+        };
+        if !HedgeConfig::unpack_unchecked(&hedge_config_info.data.borrow())?.is_initialized {
+            return Ok(None);
+        }
+        Self::unpack_hedge_config(program_id, hedge_config_info, mint).map(Some)
+    }
+
+    /// Settles the rewards and the swaps out of the asset vault of the
+    /// position of `account`, which holds `balance` tokens of `mint`, before
+    /// its balance changes. Only accounts of a hedged mint, whose hedge
+    /// configuration is among `account_infos`, have a position to settle; it
+    /// is returned along with its account to be packed once changed.
+    fn settle_position<'a, 'b>(
+        program_id: &Pubkey,
+        mint: &Pubkey,
+        account: &Pubkey,
+        balance: u64,
+        account_infos: &'a [AccountInfo<'b>],
+    ) -> Result<Option<(&'a AccountInfo<'b>, Position)>, ProgramError> {
+        let hedge_config = match Self::find_hedge_config(program_id, mint, account_infos)? {
+            Some(hedge_config) => hedge_config,
+            None => return Ok(None),
+        };
+        let position_info = match Self::position_info(program_id, account, account_infos)? {
+            Some(position_info) => position_info,
+            None => return Ok(None),
+        };
+        let (take_profit_key, _) = find_take_profit_with_program_id(program_id, mint);
+        let take_profit_info = account_infos
+            .iter()
+            .find(|account_info| *account_info.key == take_profit_key)
+            .ok_or(TokenError::TakeProfitRequired)?;
+
+        let mut position = Position::unpack(&position_info.data.borrow())?;
+        Self::settle_vault_swaps(program_id, &hedge_config, &mut position, account_infos)?;
+        position.settle_rewards(
+            balance,
+            Self::reward_per_share(program_id, take_profit_info, mint)?,
+        )?;
+        Ok(Some((position_info, position)))
+    }
+
+    /// Finds the position of the token account `account` among
+    /// `account_infos`, which must hold its derived address so that a
+    /// position cannot be left out. An empty or foreign account at that
+    /// address means the account has no position.
+    fn position_info<'a, 'b>(
+        program_id: &Pubkey,
+        account: &Pubkey,
+        account_infos: &'a [AccountInfo<'b>],
+    ) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+        let (position_key, _) = find_position_with_program_id(program_id, account);
+        let position_info = account_infos
+            .iter()
+            .find(|account_info| *account_info.key == position_key)
+            .ok_or(TokenError::PositionRequired)?;
+        if position_info.owner != program_id || position_info.data_is_empty() {
+            return Ok(None);
+        }
+        let position = Position::unpack_unchecked(&position_info.data.borrow())?;
+        if !position.is_initialized {
+            return Ok(None);
+        }
+        if position.account != *account {
+            return Err(TokenError::PositionMismatch.into());
+        }
+        Ok(Some(position_info))
+    }
+
+    /// Finds the deposit limits of `target` among `account_infos`, which must
+    /// hold their derived address when `required` so that the limits cannot
+    /// be left out. An empty or foreign account at that address means
+    /// `target` has no limits.
+    fn find_deposit_limits(
+        program_id: &Pubkey,
+        target: &Pubkey,
+        account_infos: &[AccountInfo],
+        required: bool,
+    ) -> Result<Option<DepositLimits>, ProgramError> {
+        let (deposit_limits_key, _) = find_deposit_limits_with_program_id(program_id, target);
+        let deposit_limits_info = match account_infos
+            .iter()
+            .find(|account_info| *account_info.key == deposit_limits_key)
+        {
+            Some(deposit_limits_info) => deposit_limits_info,
+            None if required => return Err(TokenError::DepositLimitsRequired.into()),
+            None => return Ok(None),
+        };
+        if deposit_limits_info.owner != program_id || deposit_limits_info.data_is_empty() {
+            return Ok(None);
+        }
+        let deposit_limits = DepositLimits::unpack_unchecked(&deposit_limits_info.data.borrow())?;
+        if !deposit_limits.is_initialized {
+            return Ok(None);
+        }
+        if deposit_limits.target != *target {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(Some(deposit_limits))
+    }
+
+    /// Unpacks the deposit limits of the mint or portfolio `target`.
+    pub fn unpack_deposit_limits(
+        program_id: &Pubkey,
+        deposit_limits_info: &AccountInfo,
+        target: &Pubkey,
+    ) -> Result<DepositLimits, ProgramError> {
+        if deposit_limits_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let deposit_limits = DepositLimits::unpack(&deposit_limits_info.data.borrow())?;
+        let deposit_limits_key = Pubkey::create_program_address(
+            &[DEPOSIT_LIMITS_SEED, target.as_ref(), &[deposit_limits.bump]],
+            program_id,
+        )?;
+        if deposit_limits.target != *target || *deposit_limits_info.key != deposit_limits_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(deposit_limits)
+    }
+
+    /// Unpacks the deposit record of `owner` into the mint or portfolio `target`.
+    pub fn unpack_deposit_record(
+        program_id: &Pubkey,
+        deposit_record_info: &AccountInfo,
+        target: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<DepositRecord, ProgramError> {
+        if deposit_record_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let deposit_record = DepositRecord::unpack(&deposit_record_info.data.borrow())?;
+        let deposit_record_key = Pubkey::create_program_address(
+            &[
+                DEPOSIT_RECORD_SEED,
+                target.as_ref(),
+                owner.as_ref(),
+                &[deposit_record.bump],
+            ],
+            program_id,
+        )?;
+        if deposit_record.target != *target
+            || deposit_record.owner != *owner
+            || *deposit_record_info.key != deposit_record_key
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(deposit_record)
+    }
+
+    /// Unpacks the stop-loss rule of `mint`, failing if none was set.
+    pub fn unpack_stop_loss(
+        program_id: &Pubkey,
+        stop_loss_info: &AccountInfo,
+        mint: &Pubkey,
+    ) -> Result<StopLoss, ProgramError> {
+        if stop_loss_info.owner != program_id {
+            return Err(TokenError::StopLossNotSet.into());
+        }
+        let stop_loss = StopLoss::unpack_unchecked(&stop_loss_info.data.borrow())?;
+        if !stop_loss.is_initialized {
+            return Err(TokenError::StopLossNotSet.into());
+        }
+        let stop_loss_key = Pubkey::create_program_address(
+            &[STOP_LOSS_SEED, mint.as_ref(), &[stop_loss.bump]],
+            program_id,
+        )?;
+        if stop_loss.mint != *mint || *stop_loss_info.key != stop_loss_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(stop_loss)
+    }
+
+    /// Returns whether the stop-loss rule of `mint` triggered. A rule that was
+    /// never set is not triggered, but its address must still be the derived
+    /// one so that a triggered rule cannot be left out.
+    fn stop_loss_triggered(
+        program_id: &Pubkey,
+        stop_loss_info: &AccountInfo,
+        mint: &Pubkey,
+    ) -> Result<bool, ProgramError> {
+        if stop_loss_info.owner != program_id {
+            let (stop_loss_key, _) = find_stop_loss_with_program_id(program_id, mint);
+            if *stop_loss_info.key != stop_loss_key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            return Ok(false);
+        }
+        Ok(Self::unpack_stop_loss(program_id, stop_loss_info, mint)?.triggered)
+    }
+
+    /// Unpacks the take-profit rule of `mint`, failing if none was set.
+    pub fn unpack_take_profit(
+        program_id: &Pubkey,
+        take_profit_info: &AccountInfo,
+        mint: &Pubkey,
+    ) -> Result<TakeProfit, ProgramError> {
+        if take_profit_info.owner != program_id {
+            return Err(TokenError::TakeProfitNotSet.into());
+        }
+        let take_profit = TakeProfit::unpack_unchecked(&take_profit_info.data.borrow())?;
+        if !take_profit.is_initialized {
+            return Err(TokenError::TakeProfitNotSet.into());
+        }
+        let take_profit_key = Pubkey::create_program_address(
+            &[TAKE_PROFIT_SEED, mint.as_ref(), &[take_profit.bump]],
+            program_id,
+        )?;
+        if take_profit.mint != *mint || *take_profit_info.key != take_profit_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(take_profit)
+    }
+
+    /// Returns the rewards distributed per token of `mint`. A take-profit rule
+    /// that was never set distributed nothing, but its address must still be
+    /// the derived one so that rewards cannot be skipped when settling.
+    fn reward_per_share(
+        program_id: &Pubkey,
+        take_profit_info: &AccountInfo,
+        mint: &Pubkey,
+    ) -> Result<u128, ProgramError> {
+        if take_profit_info.owner != program_id {
+            let (take_profit_key, _) = find_take_profit_with_program_id(program_id, mint);
+            if *take_profit_info.key != take_profit_key {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            return Ok(0);
+        }
+        Ok(Self::unpack_take_profit(program_id, take_profit_info, mint)?.reward_per_share)
+    }
+
+    /// Settles the swaps out of the asset vault of the mint of `hedge_config`
+    /// into `position`, looking the `HedgeEpoch` up among `account_infos` when
+    /// the position needs it.
+    fn settle_vault_swaps(
+        program_id: &Pubkey,
+        hedge_config: &HedgeConfig,
+        position: &mut Position,
+        account_infos: &[AccountInfo],
+    ) -> ProgramResult {
+        let closed_stable_per_base = if position.needs_hedge_epoch(hedge_config) {
+            let (hedge_epoch_key, _) = find_hedge_epoch_with_program_id(
+                program_id,
+                &hedge_config.mint,
+                position.hedge_epoch,
+            );
+            let hedge_epoch_info = account_infos
+                .iter()
+                .find(|account_info| *account_info.key == hedge_epoch_key)
+                .ok_or(TokenError::HedgeEpochRequired)?;
+            if hedge_epoch_info.owner != program_id {
+                return Err(TokenError::HedgeEpochRequired.into());
+            }
+            Some(HedgeEpoch::unpack(&hedge_epoch_info.data.borrow())?.stable_per_base)
+        } else {
+            None
+        };
+        position.settle_vault_swaps(hedge_config, closed_stable_per_base)
+    }
+
+    /// Unpacks the withdraw ticket of `account`, failing if no withdrawal was
+    /// requested.
+    pub fn unpack_withdraw_ticket(
+        program_id: &Pubkey,
+        withdraw_ticket_info: &AccountInfo,
+        account: &Pubkey,
+    ) -> Result<WithdrawTicket, ProgramError> {
+        if withdraw_ticket_info.owner != program_id {
+            return Err(TokenError::WithdrawNotRequested.into());
+        }
+        let withdraw_ticket = WithdrawTicket::unpack_unchecked(&withdraw_ticket_info.data.borrow())?;
+        if !withdraw_ticket.is_initialized {
+            return Err(TokenError::WithdrawNotRequested.into());
+        }
+        let withdraw_ticket_key = Pubkey::create_program_address(
+            &[WITHDRAW_TICKET_SEED, account.as_ref(), &[withdraw_ticket.bump]],
+            program_id,
+        )?;
+        if withdraw_ticket.account != *account || *withdraw_ticket_info.key != withdraw_ticket_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(withdraw_ticket)
+    }
+
+    /// Unpacks a dollar-cost-averaging configuration.
+    pub fn unpack_dca_config(
+        program_id: &Pubkey,
+        dca_config_info: &AccountInfo,
+    ) -> Result<DcaConfig, ProgramError> {
+        if dca_config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let dca_config = DcaConfig::unpack(&dca_config_info.data.borrow())?;
+        let dca_config_key = Pubkey::create_program_address(
+            &[
+                DCA_CONFIG_SEED,
+                dca_config.portfolio.as_ref(),
+                &[dca_config.asset_index],
+                &[dca_config.bump],
+            ],
+            program_id,
+        )?;
+        if *dca_config_info.key != dca_config_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(dca_config)
+    }
+
+    /// Unpacks a dollar-cost-averaging position of the configuration `dca_config`.
+    pub fn unpack_dca_position(
+        program_id: &Pubkey,
+        dca_position_info: &AccountInfo,
+        dca_config: &Pubkey,
+    ) -> Result<DcaPosition, ProgramError> {
+        if dca_position_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let dca_position = DcaPosition::unpack(&dca_position_info.data.borrow())?;
+        let dca_position_key = Pubkey::create_program_address(
+            &[
+                DCA_POSITION_SEED,
+                dca_config.as_ref(),
+                dca_position.owner.as_ref(),
+                &[dca_position.bump],
+            ],
+            program_id,
+        )?;
+        if dca_position.dca_config != *dca_config || *dca_position_info.key != dca_position_key {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(dca_position)
+    }
+
+    /// Swaps `amount` from the source vault into the destination vault through
+    /// the pool, signed by the vault authority, and returns the amount received.
+    #[allow(clippy::too_many_arguments)]
+    fn swap_vault<'a>(
+        swap: &SwapState,
+        vault_authority_seeds: &[&[u8]],
+        source_vault_info: &AccountInfo<'a>,
+        destination_vault_info: &AccountInfo<'a>,
+        vault_authority_info: &AccountInfo<'a>,
+        swap_info: &AccountInfo<'a>,
+        swap_authority_info: &AccountInfo<'a>,
+        swap_source_info: &AccountInfo<'a>,
+        swap_destination_info: &AccountInfo<'a>,
+        pool_mint_info: &AccountInfo<'a>,
+        pool_fee_account_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        swap_program_info: &AccountInfo<'a>,
+        host_fee_account_info: Option<&AccountInfo<'a>>,
+        amount: u64,
+        minimum_amount_out: u64,
+    ) -> Result<u64, ProgramError> {
+        let received_before = unpack_token_account(
+            &swap.token_program_id,
+            destination_vault_info.owner,
+            &destination_vault_info.data.borrow(),
+        )?
+        .amount;
+        let mut swap_accounts = vec![
+            swap_info.clone(),
+            swap_authority_info.clone(),
+            vault_authority_info.clone(),
+            source_vault_info.clone(),
+            swap_source_info.clone(),
+            swap_destination_info.clone(),
+            destination_vault_info.clone(),
+            pool_mint_info.clone(),
+            pool_fee_account_info.clone(),
+            token_program_info.clone(),
+            swap_program_info.clone(),
+        ];
+        if let Some(host_fee_account_info) = host_fee_account_info {
+            swap_accounts.push(host_fee_account_info.clone());
+        }
+        invoke_signed(
+            &swap_instruction(
+                swap_program_info.key,
+                token_program_info.key,
+                swap_info.key,
+                swap_authority_info.key,
+                vault_authority_info.key,
+                source_vault_info.key,
+                swap_source_info.key,
+                swap_destination_info.key,
+                destination_vault_info.key,
+                pool_mint_info.key,
+                pool_fee_account_info.key,
+                host_fee_account_info.map(|info| info.key),
+                amount,
+                minimum_amount_out,
+            ),
+            &swap_accounts,
+            &[vault_authority_seeds],
+        )?;
+        let received_after = unpack_token_account(
+            &swap.token_program_id,
+            destination_vault_info.owner,
+            &destination_vault_info.data.borrow(),
+        )?
+        .amount;
+        received_after
+            .checked_sub(received_before)
+            .ok_or_else(|| TokenError::Overflow.into())
+    }
+
+    /// Values `amount` of asset in stablecoin at the spot price of the pool
+    /// holding `asset_reserve_info` and `stable_reserve_info`.
+    fn pool_value(
+        token_program_id: &Pubkey,
+        asset_reserve_info: &AccountInfo,
+        stable_reserve_info: &AccountInfo,
+        amount: u64,
+    ) -> Result<u64, ProgramError> {
+        let asset_reserve = unpack_token_account(
+            token_program_id,
+            asset_reserve_info.owner,
+            &asset_reserve_info.data.borrow(),
+        )?
+        .amount;
+        let stable_reserve = unpack_token_account(
+            token_program_id,
+            stable_reserve_info.owner,
+            &stable_reserve_info.data.borrow(),
+        )?
+        .amount;
+        if asset_reserve == 0 {
+            return Ok(0);
+        }
+        u64::try_from(amount as u128 * stable_reserve as u128 / asset_reserve as u128)
+            .map_err(|_| TokenError::Overflow.into())
+    }
+
+    /// Checks the swap accounts of a deposit against the pool configured for the
+    /// mint, so that no CPI is made into a pool the mint did not opt into.
+    #[allow(clippy::too_many_arguments)]
+    pub fn validate_swap_accounts(
+        hedge_config: &HedgeConfig,
+        swap_program_info: &AccountInfo,
+        swap_info: &AccountInfo,
+        swap_authority_info: &AccountInfo,
+        swap_source_info: &AccountInfo,
+        swap_destination_info: &AccountInfo,
+        pool_mint_info: &AccountInfo,
+        pool_fee_account_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+    ) -> Result<SwapState, ProgramError> {
+        let swap = Self::validate_swap_pool(
+            &hedge_config.swap_program_id,
+            &hedge_config.swap_pool,
+            swap_program_info,
+            swap_info,
+            swap_authority_info,
+            pool_mint_info,
+            pool_fee_account_info,
+            token_program_info,
+        )?;
+        let asset_reserve = swap
+            .reserve_for_mint(&hedge_config.asset_mint)
+            .ok_or(TokenError::AssetMintMismatch)?;
+        let stable_reserve = swap
+            .reserve_for_mint(&hedge_config.stable_mint)
+            .ok_or(TokenError::StableMintMismatch)?;
+        if *swap_source_info.key != asset_reserve || *swap_destination_info.key != stable_reserve {
+            return Err(TokenError::IncorrectSwapAccount.into());
+        }
+
+        Ok(swap)
+    }
+
+    /// Checks the pool accounts of a swap against the configured pool. The
+    /// reserves depend on the direction of the swap and are left to the caller.
+    #[allow(clippy::too_many_arguments)]
+    fn validate_swap_pool(
+        swap_program_id: &Pubkey,
+        swap_pool: &Pubkey,
+        swap_program_info: &AccountInfo,
+        swap_info: &AccountInfo,
+        swap_authority_info: &AccountInfo,
+        pool_mint_info: &AccountInfo,
+        pool_fee_account_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+    ) -> Result<SwapState, ProgramError> {
+        if *swap_info.key != *swap_pool {
+            return Err(TokenError::SwapPoolMismatch.into());
+        }
+        if *swap_program_info.key != *swap_program_id || swap_info.owner != swap_program_info.key {
+            return Err(TokenError::IncorrectSwapProgramId.into());
+        }
+        let swap = SwapState::unpack(&swap_info.data.borrow())
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidSwapState))?;
+
+        let swap_authority = swap
+            .authority(swap_program_info.key, swap_info.key)
+            .map_err(|_| Into::<ProgramError>::into(TokenError::InvalidSwapAuthority))?;
+        if *swap_authority_info.key != swap_authority {
+            return Err(TokenError::InvalidSwapAuthority.into());
+        }
+        if *token_program_info.key != swap.token_program_id {
+            return Err(TokenError::IncorrectTokenProgramId.into());
+        }
+        if *pool_mint_info.key != swap.pool_mint {
+            return Err(TokenError::IncorrectPoolMint.into());
+        }
+        if *pool_fee_account_info.key != swap.pool_fee_account {
+            return Err(TokenError::IncorrectPoolFeeAccount.into());
+        }
+
+        Ok(swap)
+    }
+
+    /// Creates `new_account_info` at a program address, funded by `payer_info`.
+    /// Lamports already held by the address are kept, so pre-funding it cannot
+    /// block the creation.
+    pub fn create_pda_account<'a>(
+        payer_info: &AccountInfo<'a>,
+        rent: &Rent,
+        space: usize,
+        owner: &Pubkey,
+        system_program_info: &AccountInfo<'a>,
+        new_account_info: &AccountInfo<'a>,
+        new_account_signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let required_lamports = rent
+            .minimum_balance(space)
+            .saturating_sub(new_account_info.lamports());
+        if required_lamports > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, new_account_info.key, required_lamports),
+                &[
+                    payer_info.clone(),
+                    new_account_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(new_account_info.key, space as u64),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[new_account_signer_seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(new_account_info.key, owner),
+            &[new_account_info.clone(), system_program_info.clone()],
+            &[new_account_signer_seeds],
+        )
+    }
+
+    /// Validates owner(s) are present
+    pub fn validate_owner(
+        program_id: &Pubkey,
+        expected_owner: &Pubkey,
+        owner_account_info: &AccountInfo,
+        signers: &[AccountInfo],
+    ) -> ProgramResult {
+        if expected_owner != owner_account_info.key {
+            return Err(TokenError::OwnerMismatch.into());
+        }
+        if program_id == owner_account_info.owner
+            && owner_account_info.data_len() == Multisig::get_packed_len()
+        {
+            let multisig = Multisig::unpack(&owner_account_info.data.borrow())?;
+            let mut num_signers = 0;
+            let mut matched = [false; MAX_SIGNERS];
+            for signer in signers.iter() {
+                for (position, key) in multisig.signers[0..multisig.n as usize].iter().enumerate() {
+                    if key == signer.key && !matched[position] {
+                        if !signer.is_signer {
+                            return Err(ProgramError::MissingRequiredSignature);
+                        }
+                        matched[position] = true;
+                        num_signers += 1;
+                    }
+                }
+            }
+            if num_signers < multisig.m {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            return Ok(());
+        } else if !owner_account_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+}
+
+/// Returns `basis_points` of `amount`, rounded down.
+fn basis_points_of(amount: u64, basis_points: u16) -> Result<u64, ProgramError> {
+    u128::from(amount)
+        .checked_mul(u128::from(basis_points))
+        .map(|value| value / u128::from(BASIS_POINTS))
+        .and_then(|value| u64::try_from(value).ok())
+        .ok_or_else(|| TokenError::Overflow.into())
+}
+
+/// Returns the share of `component` backing `amount` out of a balance of `total`,
+/// rounded down.
+fn proportional_share(component: u64, amount: u64, total: u64) -> Result<u64, ProgramError> {
+    if total == 0 {
+        return Ok(0);
+    }
+    u128::from(component)
+        .checked_mul(u128::from(amount))
+        .map(|value| value / u128::from(total))
+        .and_then(|value| u64::try_from(value).ok())
+        .ok_or_else(|| TokenError::Overflow.into())
+}
+
+impl PrintProgramError for TokenError {
+    fn print<E>(&self)
+    where
+        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
+    {
+        match self {
+            TokenError::NotRentExempt => msg!("Error: Lamport balance below rent-exempt threshold"),
+            TokenError::InsufficientFunds => msg!("Error: insufficient funds"),
+            TokenError::InvalidMint => msg!("Error: Invalid Mint"),
+            TokenError::MintMismatch => msg!("Error: Account not associated with this Mint"),
+            TokenError::OwnerMismatch => msg!("Error: owner does not match"),
+            TokenError::FixedSupply => msg!("Error: the total supply of this token is fixed"),
+            TokenError::AlreadyInUse => msg!("Error: account or token already in use"),
+            TokenError::InvalidNumberOfProvidedSigners => {
+                msg!("Error: Invalid number of provided signers")
+            }
+            TokenError::InvalidNumberOfRequiredSigners => {
+                msg!("Error: Invalid number of required signers")
+            }
+            TokenError::UninitializedState => msg!("Error: State is uninitialized"),
+            TokenError::NativeNotSupported => {
+                msg!("Error: Instruction does not support native tokens")
+            }
+            TokenError::NonNativeHasBalance => {
+                msg!("Error: Non-native account can only be closed if its balance is zero")
+            }
+            TokenError::InvalidInstruction => msg!("Error: Invalid instruction"),
+            TokenError::InvalidState => msg!("Error: Invalid account state for operation"),
+            TokenError::Overflow => msg!("Error: Operation overflowed"),
+            TokenError::AuthorityTypeNotSupported => {
+                msg!("Error: Account does not support specified authority type")
+            }
+            TokenError::MintCannotFreeze => msg!("Error: This token mint cannot freeze accounts"),
+            TokenError::AccountFrozen => msg!("Error: Account is frozen"),
+            TokenError::MintDecimalsMismatch => {
+                msg!("Error: decimals different from the Mint decimals")
+            }
+            TokenError::MintNotHedged => msg!("Error: Mint is not configured for hedging"),
+            TokenError::SwapPoolMismatch => {
+                msg!("Error: Swap pool does not match the mint's configured pool")
+            }
+            TokenError::IncorrectSwapProgramId => {
+                msg!("Error: Swap pool is not owned by the provided swap program")
+            }
+            TokenError::InvalidSwapState => msg!("Error: Swap pool state is invalid"),
+            TokenError::InvalidSwapAuthority => {
+                msg!("Error: Swap authority does not match the swap pool")
+            }
+            TokenError::IncorrectSwapAccount => {
+                msg!("Error: Account is not a reserve of the swap pool")
+            }
+            TokenError::IncorrectPoolMint => msg!("Error: Pool mint does not match the swap pool"),
+            TokenError::IncorrectPoolFeeAccount => {
+                msg!("Error: Pool fee account does not match the swap pool")
+            }
+            TokenError::IncorrectTokenProgramId => {
+                msg!("Error: Token program does not match the swap pool")
+            }
+            TokenError::AssetMintMismatch => {
+                msg!("Error: Swap pool does not trade the mint's asset")
+            }
+            TokenError::InvalidVaultAuthority => {
+                msg!("Error: Vault authority is not derived from the mint")
+            }
+            TokenError::IncorrectVaultOwner => {
+                msg!("Error: Vault is not owned by the vault authority")
+            }
+            TokenError::InvalidBasisPoints => {
+                msg!("Error: Ratio or fee exceeds 10000 basis points")
+            }
+            TokenError::StableMintMismatch => {
+                msg!("Error: Swap pool does not trade the stablecoin")
+            }
+            TokenError::IncorrectVaultAccount => {
+                msg!("Error: Vault does not match the hedge configuration")
+            }
+            TokenError::IncorrectFeeAccount => {
+                msg!("Error: Fee account does not match the hedge configuration")
+            }
+            TokenError::PositionMismatch => {
+                msg!("Error: Position does not belong to the token account")
+            }
+            TokenError::PositionRequired => {
+                msg!("Error: Destination position is required to move the source position")
+            }
+            TokenError::NotLegacyAccount => msg!("Error: Account does not use a legacy layout"),
+            TokenError::Paused => msg!("Error: Operation is paused"),
+            TokenError::IncorrectUpgradeAuthority => {
+                msg!("Error: Signer is not the upgrade authority of the program")
+            }
+            TokenError::GuardianCannotUnpause => {
+                msg!("Error: Guardian can only pause operations")
+            }
+            TokenError::IncorrectProtocolFeeAccount => {
+                msg!("Error: Fee account is not owned by the protocol fee owner")
+            }
+            TokenError::AssetNotRegistered => {
+                msg!("Error: Mint is not approved in the asset registry")
+            }
+            TokenError::IncorrectAssetKind => msg!("Error: Mint is registered with another role"),
+            TokenError::DepositCapExceeded => {
+                msg!("Error: Deposit exceeds the cap on a single deposit")
+            }
+            TokenError::SupplyCapExceeded => msg!("Error: Supply would exceed its cap"),
+            TokenError::OwnerDepositCapExceeded => {
+                msg!("Error: Cumulative deposits of the owner would exceed their cap")
+            }
+            TokenError::InvalidOracle => msg!("Error: Oracle account is not a valid price feed"),
+            TokenError::StaleOraclePrice => msg!("Error: Oracle price is stale"),
+            TokenError::StopLossNotSet => msg!("Error: Stop-loss rule is not set for the mint"),
+            TokenError::StopLossTriggered => msg!("Error: Stop-loss rule already triggered"),
+            TokenError::TakeProfitNotSet => msg!("Error: Take-profit rule is not set for the mint"),
+            TokenError::TakeProfitNotReached => {
+                msg!("Error: Oracle price is below the take-profit target")
+            }
+            TokenError::TakeProfitRequired => {
+                msg!("Error: Take-profit rule of the mint must be provided")
+            }
+            TokenError::InvalidDcaSchedule => {
+                msg!("Error: Portfolio asset has no dollar-cost-averaging schedule")
+            }
+            TokenError::DcaNotDue => {
+                msg!("Error: Next dollar-cost-averaging tranche is not due")
+            }
+            TokenError::WithdrawNotRequested => {
+                msg!("Error: No withdrawal was requested for this amount")
+            }
+            TokenError::WithdrawNotClaimable => {
+                msg!("Error: Requested withdrawal is still in its delay")
+            }
+            TokenError::WithdrawInDepositSlot => {
+                msg!("Error: Cannot withdraw in the slot of the last deposit")
+            }
+            TokenError::PositionNotEmpty => {
+                msg!("Error: Position still holds hedged components or unclaimed rewards")
+            }
+            TokenError::HedgeConfigRequired => {
+                msg!("Error: Hedge configuration of the mint must be provided")
+            }
+            TokenError::HedgeEpochRequired => {
+                msg!("Error: Record of the epoch the position was last settled in must be provided")
+            }
+            TokenError::LegacyMintHedged => {
+                msg!("Error: Legacy mint still names a hedged asset or swap pool")
+            }
+            TokenError::DepositLimitsRequired => {
+                msg!("Error: Deposit limits of the mint must be provided")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{instruction::*, state::account_type};
+    use solana_program::{
+        account_info::IntoAccountInfo, 
+        clock::Epoch, 
+        entrypoint::MAX_PERMITTED_DATA_INCREASE,
+        instruction::{AccountMeta, Instruction},
+        program_stubs,
+        sysvar::rent,
+    };
+    use solana_sdk::account::{
+        create_account_for_test, create_is_signer_account_infos, Account as SolanaAccount,
+    };
+    use std::{
+        convert::TryInto,
+        sync::{Mutex, Once},
+    };
+
+    fn do_process_instruction(
+        instruction: Instruction,
+        accounts: Vec<&mut SolanaAccount>,
+    ) -> ProgramResult {
+        let mut meta = instruction
+            .accounts
+            .iter()
+            .zip(accounts)
+            .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
+            .collect::<Vec<_>>();
+
+        let account_infos = create_is_signer_account_infos(&mut meta);
+        Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+    }
+
+    fn do_process_instruction_dups(
+        instruction: Instruction,
+        account_infos: Vec<AccountInfo>,
+    ) -> ProgramResult {
+        Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+    }
+
+    /// Runs `instruction` on accounts laid out the way the runtime serializes
+    /// them, with the original data length in front of each key and the data
+    /// length in front of the data, which `AccountInfo::realloc` relies on.
+    fn do_process_instruction_serialized(
+        instruction: Instruction,
+        accounts: Vec<&mut SolanaAccount>,
+    ) -> ProgramResult {
+        #[repr(C)]
+        struct SerializedKey {
+            original_data_len: u32,
+            key: Pubkey,
+        }
+
+        let keys = instruction
+            .accounts
+            .iter()
+            .zip(accounts.iter())
+            .map(|(account_meta, account)| SerializedKey {
+                original_data_len: account.data.len() as u32,
+                key: account_meta.pubkey,
+            })
+            .collect::<Vec<_>>();
+        let mut buffers = accounts
+            .iter()
+            .map(|account| {
+                let data_len = account.data.len();
+                let mut buffer = vec![0; 16 + data_len + MAX_PERMITTED_DATA_INCREASE];
+                let start = buffer.as_ptr().align_offset(8) + 8;
+                buffer[start - 8..start].copy_from_slice(&(data_len as u64).to_le_bytes());
+                buffer[start..start + data_len].copy_from_slice(&account.data);
+                (buffer, start, data_len)
+            })
+            .collect::<Vec<_>>();
+        let mut lamports = accounts.iter().map(|account| account.lamports).collect::<Vec<_>>();
+        let owners = accounts.iter().map(|account| account.owner).collect::<Vec<_>>();
+
+        let account_infos = instruction
+            .accounts
+            .iter()
+            .zip(keys.iter())
+            .zip(buffers.iter_mut())
+            .zip(lamports.iter_mut())
+            .zip(owners.iter())
+            .map(|((((account_meta, key), (buffer, start, data_len)), lamports), owner)| {
+                AccountInfo::new(
+                    &key.key,
+                    account_meta.is_signer,
+                    account_meta.is_writable,
+                    lamports,
+                    &mut buffer[*start..*start + *data_len],
+                    owner,
+                    false,
+                    Epoch::default(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let result = Processor::process(&instruction.program_id, &account_infos, &instruction.data);
+        for (account_info, account) in account_infos.iter().zip(accounts) {
+            account.lamports = account_info.lamports();
+            account.data = account_info.data.borrow().to_vec();
+            account.owner = *account_info.owner;
+        }
+        result
+    }
+
+    fn return_token_error_as_program_error() -> ProgramError {
+        TokenError::MintMismatch.into()
+    }
+
+    fn rent_sysvar() -> SolanaAccount {
+        create_account_for_test(&Rent::default())
+    }
+
+    fn mint_minimum_balance() -> u64 {
+        Rent::default().minimum_balance(Mint::get_packed_len())
+    }
+
+    fn account_minimum_balance() -> u64 {
+        Rent::default().minimum_balance(Account::get_packed_len())
+    }
+
+    fn multisig_minimum_balance() -> u64 {
+        Rent::default().minimum_balance(Multisig::get_packed_len())
+    }
+
+    #[test]
+    fn test_print_error() {
+        let error = return_token_error_as_program_error();
+        error.print::<TokenError>();
+    }
+
+    #[test]
+    #[should_panic(expected = "Custom(3)")]
+    fn test_error_unwrap() {
+        Err::<(), ProgramError>(return_token_error_as_program_error()).unwrap();
+    }
+
+    #[test]
+    fn test_unique_account_sizes() {
+        assert_ne!(Mint::get_packed_len(), 0);
+        assert_ne!(Mint::get_packed_len(), Account::get_packed_len());
+        assert_ne!(Mint::get_packed_len(), Multisig::get_packed_len());
+        assert_ne!(Account::get_packed_len(), 0);
+        assert_ne!(Account::get_packed_len(), Multisig::get_packed_len());
+        assert_ne!(Multisig::get_packed_len(), 0);
+        for len in [Mint::LEN, Account::LEN, Multisig::LEN, LEGACY_MINT_LEN, LEGACY_ACCOUNT_LEN] {
+            assert_ne!(Position::get_packed_len(), len);
+            assert_ne!(HedgeConfig::get_packed_len(), len);
+            assert_ne!(ProgramConfig::get_packed_len(), len);
+            assert_ne!(AssetEntry::get_packed_len(), len);
+            assert_ne!(DepositLimits::get_packed_len(), len);
+            assert_ne!(DepositRecord::get_packed_len(), len);
+            assert_ne!(StopLoss::get_packed_len(), len);
+            assert_ne!(TakeProfit::get_packed_len(), len);
+            assert_ne!(DcaConfig::get_packed_len(), len);
+            assert_ne!(DcaPosition::get_packed_len(), len);
+            assert_ne!(WithdrawTicket::get_packed_len(), len);
+            assert_ne!(HedgeEpoch::get_packed_len(), len);
+        }
+        // a current state account is never mistaken for one without header
+        for len in [
+            HedgeConfig::LEN,
+            Position::LEN,
+            Portfolio::LEN,
+            UserPortfolio::LEN,
+            ProgramConfig::LEN,
+            AssetEntry::LEN,
+            DepositLimits::LEN,
+            DepositRecord::LEN,
+            StopLoss::LEN,
+            TakeProfit::LEN,
+            DcaConfig::LEN,
+            DcaPosition::LEN,
+            WithdrawTicket::LEN,
+            HedgeEpoch::LEN,
+        ] {
+            assert_eq!(AccountType::from_unversioned_len(len), None);
+        }
+    }
+
+    fn asset_entry_account(
+        program_id: &Pubkey,
+        mint: &Pubkey,
+        kind: AssetKind,
+        swap_program_id: &Pubkey,
+        swap_pool: &Pubkey,
+    ) -> SolanaAccount {
+        let (_, bump) = find_asset_entry_with_program_id(program_id, mint);
+        let mut account = SolanaAccount::new(
+            Rent::default().minimum_balance(AssetEntry::get_packed_len()),
+            AssetEntry::get_packed_len(),
+            program_id,
+        );
+        AssetEntry::pack(
+            AssetEntry {
+                is_initialized: true,
+                mint: *mint,
+                kind,
+                decimals: 6,
+                oracle: Pubkey::new_unique(),
+                swap_program_id: *swap_program_id,
+                swap_pool: *swap_pool,
+                bump,
+            },
+            &mut account.data,
+        )
+        .unwrap();
+        account
+    }
+
+    fn program_config_account(program_id: &Pubkey, program_config: ProgramConfig) -> SolanaAccount {
+        let (_, bump) = find_program_config_with_program_id(program_id);
+        let mut program_config_account = SolanaAccount::new(
+            Rent::default().minimum_balance(ProgramConfig::get_packed_len()),
+            ProgramConfig::get_packed_len(),
+            program_id,
+        );
+        ProgramConfig::pack(
+            ProgramConfig {
+                is_initialized: true,
+                bump,
+                ..program_config
+            },
+            &mut program_config_account.data,
+        )
+        .unwrap();
+        program_config_account
+    }
+
+    fn position_account(program_id: &Pubkey, account: &Pubkey, asset: u64, usdc: u64) -> SolanaAccount {
+        let (_, bump) = find_position_with_program_id(program_id, account);
+        let mut position_account = SolanaAccount::new(
+            Rent::default().minimum_balance(Position::get_packed_len()),
+            Position::get_packed_len(),
+            program_id,
+        );
+        Position::pack(
+            Position {
+                is_initialized: true,
+                account: *account,
+                asset,
+                usdc,
+                bump,
+                ..Position::default()
+            },
+            &mut position_account.data,
+        )
+        .unwrap();
+        position_account
+    }
+
+    fn deposit_limits_account(program_id: &Pubkey, deposit_limits: DepositLimits) -> SolanaAccount {
+        let (_, bump) = find_deposit_limits_with_program_id(program_id, &deposit_limits.target);
+        let mut account = SolanaAccount::new(
+            Rent::default().minimum_balance(DepositLimits::get_packed_len()),
+            DepositLimits::get_packed_len(),
+            program_id,
+        );
+        DepositLimits::pack(
+            DepositLimits {
+                is_initialized: true,
+                bump,
+                ..deposit_limits
+            },
+            &mut account.data,
+        )
+        .unwrap();
+        account
+    }
+
+    fn deposit_record_account(
+        program_id: &Pubkey,
+        target: &Pubkey,
+        owner: &Pubkey,
+        deposited: u64,
+    ) -> SolanaAccount {
+        let (_, bump) = find_deposit_record_with_program_id(program_id, target, owner);
+        let mut account = SolanaAccount::new(
+            Rent::default().minimum_balance(DepositRecord::get_packed_len()),
+            DepositRecord::get_packed_len(),
+            program_id,
+        );
+        DepositRecord::pack(
+            DepositRecord {
+                is_initialized: true,
+                target: *target,
+                owner: *owner,
+                deposited,
+                bump,
+            },
+            &mut account.data,
+        )
+        .unwrap();
+        account
+    }
+
+    fn stop_loss_account(program_id: &Pubkey, stop_loss: StopLoss) -> SolanaAccount {
+        let (_, bump) = find_stop_loss_with_program_id(program_id, &stop_loss.mint);
+        let mut account = SolanaAccount::new(
+            Rent::default().minimum_balance(StopLoss::get_packed_len()),
+            StopLoss::get_packed_len(),
+            program_id,
+        );
+        StopLoss::pack(
+            StopLoss {
+                is_initialized: true,
+                bump,
+                ..stop_loss
+            },
+            &mut account.data,
+        )
+        .unwrap();
+        account
+    }
+
+    fn take_profit_account(program_id: &Pubkey, take_profit: TakeProfit) -> SolanaAccount {
+        let (_, bump) = find_take_profit_with_program_id(program_id, &take_profit.mint);
+        let mut account = SolanaAccount::new(
+            Rent::default().minimum_balance(TakeProfit::get_packed_len()),
+            TakeProfit::get_packed_len(),
+            program_id,
+        );
+        TakeProfit::pack(
+            TakeProfit {
+                is_initialized: true,
+                bump,
+                ..take_profit
+            },
+            &mut account.data,
+        )
+        .unwrap();
+        account
+    }
+
+    fn hedge_config_account(program_id: &Pubkey, hedge_config: HedgeConfig) -> SolanaAccount {
+        let (_, bump) = find_hedge_config_with_program_id(program_id, &hedge_config.mint);
+        let mut account = SolanaAccount::new(
+            Rent::default().minimum_balance(HedgeConfig::get_packed_len()),
+            HedgeConfig::get_packed_len(),
+            program_id,
+        );
+        HedgeConfig::pack(
+            HedgeConfig {
+                is_initialized: true,
+                bump,
+                ..hedge_config
+            },
+            &mut account.data,
+        )
+        .unwrap();
+        account
+    }
+
+    fn token_account(token_program_id: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> SolanaAccount {
+        let mut account = SolanaAccount::new(42, crate::swap::TOKEN_ACCOUNT_LEN, token_program_id);
+        account.data[..32].copy_from_slice(mint.as_ref());
+        account.data[32..64].copy_from_slice(owner.as_ref());
+        account
+    }
+
+    /// Token programs whose `Transfer` invocations `TransferStubs` carries out.
+    static STUBBED_TOKEN_PROGRAMS: Mutex<Vec<Pubkey>> = Mutex::new(Vec::new());
+
+    /// Swap programs whose `Swap` invocations `TransferStubs` fills at exactly
+    /// the minimum amount out, or rejects like the slippage check of the pool
+    /// when its destination reserve is a token account holding less.
+    static STUBBED_SWAP_PROGRAMS: Mutex<Vec<Pubkey>> = Mutex::new(Vec::new());
+
+    /// Error code of the slippage check of the token swap program.
+    const SWAP_EXCEEDED_SLIPPAGE: u32 = 16;
+
+    /// Moves the amounts of the `Transfer` instructions sent to the token
+    /// programs of `STUBBED_TOKEN_PROGRAMS` between the token accounts, and
+    /// of the `Swap` instructions sent to the swap programs of
+    /// `STUBBED_SWAP_PROGRAMS` between the user accounts, every other
+    /// invocation succeeds without effect like the default stubs.
+    struct TransferStubs;
+
+    impl program_stubs::SyscallStubs for TransferStubs {
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            let stubbed = |programs: &Mutex<Vec<Pubkey>>| {
+                programs.lock().unwrap().contains(&instruction.program_id)
+            };
+            let amount = |offset: usize| {
+                u64::from_le_bytes(instruction.data[offset..offset + 8].try_into().unwrap())
+            };
+            let find = |index: usize| {
+                account_infos
+                    .iter()
+                    .find(|info| *info.key == instruction.accounts[index].pubkey)
+                    .unwrap()
+            };
+            let balance = |info: &AccountInfo| {
+                u64::from_le_bytes(info.data.borrow()[64..72].try_into().unwrap())
+            };
+            let ((source, debit), (destination, credit)) = match instruction.data.first() {
+                Some(&3) if stubbed(&STUBBED_TOKEN_PROGRAMS) => ((0, amount(1)), (1, amount(1))),
+                Some(&crate::swap::SWAP_INSTRUCTION_TAG) if stubbed(&STUBBED_SWAP_PROGRAMS) => {
+                    let reserve = find(5);
+                    if reserve.data_len() == crate::swap::TOKEN_ACCOUNT_LEN
+                        && balance(reserve) < amount(9)
+                    {
+                        return Err(ProgramError::Custom(SWAP_EXCEEDED_SLIPPAGE));
+                    }
+                    ((3, amount(1)), (6, amount(9)))
+                }
+                _ => return Ok(()),
+            };
+            let (source, destination) = (find(source), find(destination));
+            let source_balance = balance(source)
+                .checked_sub(debit)
+                .ok_or(TokenError::InsufficientFunds)?;
+            source.data.borrow_mut()[64..72].copy_from_slice(&source_balance.to_le_bytes());
+            let destination_balance = balance(destination) + credit;
+            destination.data.borrow_mut()[64..72]
+                .copy_from_slice(&destination_balance.to_le_bytes());
+            Ok(())
+        }
+    }
+
+    fn install_stubs() {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TransferStubs));
+        });
+    }
+
+    /// Carries out the transfers of `token_program_id` for the rest of the tests.
+    fn stub_token_transfers(token_program_id: &Pubkey) {
+        install_stubs();
+        STUBBED_TOKEN_PROGRAMS.lock().unwrap().push(*token_program_id);
+    }
+
+    /// Fills the swaps of `swap_program_id` for the rest of the tests.
+    fn stub_swaps(swap_program_id: &Pubkey) {
+        install_stubs();
+        STUBBED_SWAP_PROGRAMS.lock().unwrap().push(*swap_program_id);
+    }
+
+    fn token_balance(account: &SolanaAccount) -> u64 {
+        u64::from_le_bytes(account.data[64..72].try_into().unwrap())
+    }
+
+    struct HedgeFixture {
+        program_id: Pubkey,
+        swap_program_id: Pubkey,
+        token_program_id: Pubkey,
+        asset_mint: Pubkey,
+        stable_mint: Pubkey,
+        swap_key: Pubkey,
+        swap_authority: Pubkey,
+        swap_state: SwapState,
+        swap_account: SolanaAccount,
+        mint_key: Pubkey,
+        mint_account: SolanaAccount,
+        mint_authority: Pubkey,
+        vault_authority: Pubkey,
+        asset_vault: Pubkey,
+        stable_vault: Pubkey,
+        fee_account: Pubkey,
+    }
+
+    impl HedgeFixture {
+        fn new() -> Self {
+            Self::with_program_id(Pubkey::new_unique())
+        }
+
+        fn with_program_id(program_id: Pubkey) -> Self {
+            let swap_program_id = Pubkey::new_unique();
+            let token_program_id = Pubkey::new_unique();
+            let asset_mint = Pubkey::new_unique();
+            let stable_mint = Pubkey::new_unique();
+            let swap_key = Pubkey::new_unique();
+            let (swap_authority, nonce) =
+                Pubkey::find_program_address(&[swap_key.as_ref()], &swap_program_id);
+            let swap_state = SwapState {
+                version: crate::swap::SWAP_VERSION_1,
+                is_initialized: true,
+                nonce,
+                token_program_id,
+                token_a: Pubkey::new_unique(),
+                token_b: Pubkey::new_unique(),
+                pool_mint: Pubkey::new_unique(),
+                token_a_mint: asset_mint,
+                token_b_mint: stable_mint,
+                pool_fee_account: Pubkey::new_unique(),
+                ..SwapState::default()
+            };
+            let mut swap_account =
+                SolanaAccount::new(42, SwapState::get_packed_len(), &swap_program_id);
+            SwapState::pack(swap_state, &mut swap_account.data).unwrap();
+
+            let mint_key = Pubkey::new_unique();
+            let mint_authority = Pubkey::new_unique();
+            let mut mint_account =
+                SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+            do_process_instruction(
+                initialize_mint(&program_id, &mint_key, &mint_authority, None, 2).unwrap(),
+                vec![&mut mint_account, &mut rent_sysvar()],
+            )
+            .unwrap();
+            let (vault_authority, _) = find_vault_authority_with_program_id(&program_id, &mint_key);
+
+            HedgeFixture {
+                program_id,
+                swap_program_id,
+                token_program_id,
+                asset_mint,
+                stable_mint,
+                swap_key,
+                swap_authority,
+                swap_state,
+                swap_account,
+                mint_key,
+                mint_account,
+                mint_authority,
+                vault_authority,
+                asset_vault: Pubkey::new_unique(),
+                stable_vault: Pubkey::new_unique(),
+                fee_account: Pubkey::new_unique(),
+            }
+        }
+
+        fn mint_account_with_supply(&self, supply: u64) -> SolanaAccount {
+            let mut account = self.mint_account.clone();
+            let mint = Mint::unpack(&account.data).unwrap();
+            Mint::pack(Mint { supply, ..mint }, &mut account.data).unwrap();
+            account
+        }
+
+        /// The accounts of a `Withdraw` from the mint to the withdraw ticket.
+        fn withdraw_payout_accounts(&self, supply: u64) -> Vec<SolanaAccount> {
+            vec![
+                self.mint_account_with_supply(supply),
+                token_account(&self.token_program_id, &self.asset_mint, &self.vault_authority),
+                token_account(&self.token_program_id, &self.stable_mint, &self.vault_authority),
+                token_account(&self.token_program_id, &self.asset_mint, &Pubkey::new_unique()),
+                token_account(&self.token_program_id, &self.stable_mint, &Pubkey::new_unique()),
+                token_account(&self.token_program_id, &self.asset_mint, &self.mint_authority),
+                token_account(&self.token_program_id, &self.stable_mint, &self.mint_authority),
+                token_account(&self.token_program_id, &self.asset_mint, &Pubkey::default()),
+                token_account(&self.token_program_id, &self.stable_mint, &Pubkey::default()),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+            ]
+        }
+
+        fn withdraw_ix(
+            &self,
+            account: &Pubkey,
+            asset_reserve: &Pubkey,
+            owner: &Pubkey,
+            amount: u64,
+        ) -> Instruction {
+            withdraw(
+                &self.program_id,
+                &self.mint_key,
+                account,
+                &self.swap_key,
+                asset_reserve,
+                &self.swap_state.token_b,
+                &self.asset_vault,
+                &self.stable_vault,
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &self.fee_account,
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &self.token_program_id,
+                owner,
+                &[],
+                amount,
+            )
+            .unwrap()
+        }
+
+        fn initialize_ix(&self, target_ratio_bps: u16, deposit_fee_bps: u16) -> Instruction {
+            initialize_hedged_mint(
+                &self.program_id,
+                &self.mint_key,
+                &Pubkey::new_unique(),
+                &self.asset_vault,
+                &self.stable_vault,
+                &self.fee_account,
+                &self.swap_key,
+                &self.asset_mint,
+                &self.stable_mint,
+                &self.mint_authority,
+                &[],
+                target_ratio_bps,
+                deposit_fee_bps,
+                0,
+            )
+            .unwrap()
+        }
+
+        fn initialize_accounts(&self) -> Vec<SolanaAccount> {
+            vec![
+                SolanaAccount::new(
+                    Rent::default().minimum_balance(HedgeConfig::get_packed_len()),
+                    HedgeConfig::get_packed_len(),
+                    &self.program_id,
+                ),
+                self.mint_account.clone(),
+                SolanaAccount::default(),
+                token_account(&self.token_program_id, &self.asset_mint, &self.vault_authority),
+                token_account(&self.token_program_id, &self.stable_mint, &self.vault_authority),
+                token_account(&self.token_program_id, &self.asset_mint, &Pubkey::new_unique()),
+                self.swap_account.clone(),
+                self.asset_entry_account(&self.asset_mint, AssetKind::Asset),
+                self.asset_entry_account(&self.stable_mint, AssetKind::Stablecoin),
+                SolanaAccount::default(),
+                rent_sysvar(),
+                SolanaAccount::default(),
+            ]
+        }
+
+        fn asset_entry_account(&self, mint: &Pubkey, kind: AssetKind) -> SolanaAccount {
+            asset_entry_account(
+                &self.program_id,
+                mint,
+                kind,
+                &self.swap_program_id,
+                &self.swap_key,
+            )
+        }
+
+        fn hedge_config_account(&self, target_ratio_bps: u16, deposit_fee_bps: u16) -> SolanaAccount {
+            let mut accounts = self.initialize_accounts();
+            do_process_instruction(
+                self.initialize_ix(target_ratio_bps, deposit_fee_bps),
+                accounts.iter_mut().collect(),
+            )
+            .unwrap();
+            accounts.swap_remove(0)
+        }
+
+        fn deposit_ix(&self, account: &Pubkey, source: &Pubkey, owner: &Pubkey) -> Instruction {
+            deposit(
+                &self.program_id,
+                &self.mint_key,
+                account,
+                owner,
+                source,
+                owner,
+                &self.asset_vault,
+                &self.stable_vault,
+                &self.fee_account,
+                &Pubkey::new_unique(),
+                &self.swap_program_id,
+                &self.swap_key,
+                &self.swap_authority,
+                &self.swap_state.token_a,
+                &self.swap_state.token_b,
+                &self.swap_state.pool_mint,
+                &self.swap_state.pool_fee_account,
+                &self.token_program_id,
+                None,
+                1_000,
+                10,
+            )
+            .unwrap()
+        }
+    }
+
+    #[test]
+    fn test_initialize_hedged_mint() {
+        let fixture = HedgeFixture::new();
+
+        // success
+        let hedge_config_account = fixture.hedge_config_account(5_000, 30);
+        let (_, bump) = find_hedge_config_with_program_id(&fixture.program_id, &fixture.mint_key);
+        let (_, vault_authority_bump) =
+            find_vault_authority_with_program_id(&fixture.program_id, &fixture.mint_key);
+        assert_eq!(
+            HedgeConfig::unpack(&hedge_config_account.data).unwrap(),
+            HedgeConfig {
+                is_initialized: true,
+                mint: fixture.mint_key,
+                authority: fixture.mint_authority,
+                asset_mint: fixture.asset_mint,
+                stable_mint: fixture.stable_mint,
+                swap_program_id: fixture.swap_program_id,
+                swap_pool: fixture.swap_key,
+                asset_vault: fixture.asset_vault,
+                stable_vault: fixture.stable_vault,
+                fee_account: fixture.fee_account,
+                target_ratio_bps: 5_000,
+                deposit_fee_bps: 30,
+                withdraw_fee_bps: 0,
+                vault_authority_bump,
+                bump,
+                asset_scale: HEDGE_PRECISION,
+                ..HedgeConfig::default()
+            }
+        );
+
+        // already initialized
+        let mut accounts = fixture.initialize_accounts();
+        accounts[0] = hedge_config_account;
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+
+        // ratio above 100%
+        let mut accounts = fixture.initialize_accounts();
+        assert_eq!(
+            Err(TokenError::InvalidBasisPoints.into()),
+            do_process_instruction(fixture.initialize_ix(10_001, 30), accounts.iter_mut().collect())
+        );
+
+        // missing mint authority signature
+        let mut accounts = fixture.initialize_accounts();
+        let mut ix = fixture.initialize_ix(5_000, 30);
+        ix.accounts[11].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // wrong mint authority
+        let mut accounts = fixture.initialize_accounts();
+        let mut ix = fixture.initialize_ix(5_000, 30);
+        ix.accounts[11].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // hedge config at another address
+        let mut accounts = fixture.initialize_accounts();
+        let mut ix = fixture.initialize_ix(5_000, 30);
+        ix.accounts[0].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // vault not owned by the vault authority
+        let mut accounts = fixture.initialize_accounts();
+        accounts[4] = token_account(&fixture.token_program_id, &fixture.stable_mint, &Pubkey::new_unique());
+        assert_eq!(
+            Err(TokenError::IncorrectVaultOwner.into()),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+
+        // asset not traded by the pool
+        let mut accounts = fixture.initialize_accounts();
+        accounts[3] = token_account(&fixture.token_program_id, &Pubkey::new_unique(), &fixture.vault_authority);
+        assert_eq!(
+            Err(TokenError::AssetMintMismatch.into()),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+
+        // stablecoin vault holding the asset
+        let mut accounts = fixture.initialize_accounts();
+        accounts[4] = token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.vault_authority);
+        assert_eq!(
+            Err(TokenError::StableMintMismatch.into()),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+
+        // fee account of another mint
+        let mut accounts = fixture.initialize_accounts();
+        accounts[5] = token_account(&fixture.token_program_id, &fixture.stable_mint, &Pubkey::new_unique());
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+
+        // asset not approved
+        let mut accounts = fixture.initialize_accounts();
+        accounts[7] = SolanaAccount::default();
+        assert_eq!(
+            Err(TokenError::AssetNotRegistered.into()),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+
+        // registry entry of another mint
+        let mut accounts = fixture.initialize_accounts();
+        accounts.swap(7, 8);
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+
+        // stablecoin registered as an asset
+        let mut accounts = fixture.initialize_accounts();
+        accounts[8] = fixture.asset_entry_account(&fixture.stable_mint, AssetKind::Asset);
+        assert_eq!(
+            Err(TokenError::IncorrectAssetKind.into()),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+
+        // pool other than the preferred one
+        let mut accounts = fixture.initialize_accounts();
+        let mut asset_entry = AssetEntry::unpack(&accounts[7].data).unwrap();
+        asset_entry.swap_pool = Pubkey::new_unique();
+        AssetEntry::pack(asset_entry, &mut accounts[7].data).unwrap();
+        assert_eq!(
+            Err(TokenError::SwapPoolMismatch.into()),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+
+        // pool data that is not a swap
+        let mut accounts = fixture.initialize_accounts();
+        accounts[6].data[0] = 0;
+        assert_eq!(
+            Err(TokenError::InvalidSwapState.into()),
+            do_process_instruction(fixture.initialize_ix(5_000, 30), accounts.iter_mut().collect())
+        );
+    }
+
+    #[test]
+    fn test_deposit() {
+        let fixture = HedgeFixture::new();
+        let hedge_config_account = fixture.hedge_config_account(5_000, 100);
+
+        let owner_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let mut account_account =
+            SolanaAccount::new(account_minimum_balance(), Account::get_packed_len(), &fixture.program_id);
+        Account::pack(
+            Account {
+                mint: fixture.mint_key,
+                owner: owner_key,
+                state: AccountState::Initialized,
+                ..Account::default()
+            },
+            &mut account_account.data,
+        )
+        .unwrap();
+        let source_key = Pubkey::new_unique();
+        let protocol_fee_owner = Pubkey::new_unique();
+        let program_config = ProgramConfig {
+            protocol_fee_bps: 5_000,
+            protocol_fee_owner,
+            ..ProgramConfig::default()
+        };
+        let deposit_limits = DepositLimits {
+            target: fixture.mint_key,
+            max_supply: 990,
+            max_deposit: 1_000,
+            max_owner_deposit: 1_500,
+            ..DepositLimits::default()
+        };
+        let mut asset_reserve_account =
+            token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.swap_authority);
+        asset_reserve_account.data[64..72].copy_from_slice(&1_000_000u64.to_le_bytes());
+        let mut stable_reserve_account =
+            token_account(&fixture.token_program_id, &fixture.stable_mint, &fixture.swap_authority);
+        stable_reserve_account.data[64..72].copy_from_slice(&2_000_000u64.to_le_bytes());
+        let accounts = || {
+            vec![
+                program_config_account(&fixture.program_id, program_config),
+                hedge_config_account.clone(),
+                fixture.mint_account.clone(),
+                account_account.clone(),
+                position_account(&fixture.program_id, &account_key, 0, 0),
+                token_account(&fixture.token_program_id, &fixture.asset_mint, &owner_key),
+                SolanaAccount::default(),
+                token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.vault_authority),
+                token_account(&fixture.token_program_id, &fixture.stable_mint, &fixture.vault_authority),
+                token_account(&fixture.token_program_id, &fixture.asset_mint, &Pubkey::new_unique()),
+                token_account(&fixture.token_program_id, &fixture.asset_mint, &protocol_fee_owner),
+                SolanaAccount::default(),
+                fixture.swap_account.clone(),
+                SolanaAccount::default(),
+                asset_reserve_account.clone(),
+                stable_reserve_account.clone(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                deposit_limits_account(&fixture.program_id, deposit_limits),
+                deposit_record_account(&fixture.program_id, &fixture.mint_key, &owner_key, 500),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                create_account_for_test(&Clock {
+                    slot: 100,
+                    ..Clock::default()
+                }),
+            ]
+        };
+        let deposit_ix = || fixture.deposit_ix(&account_key, &source_key, &owner_key);
+
+        // success: 1% fee, half of the rest hedged
+        let mut deposit_accounts = accounts();
+        do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect()).unwrap();
+        let account = Account::unpack(&deposit_accounts[3].data).unwrap();
+        assert_eq!(account.amount, 990);
+        let position = Position::unpack(&deposit_accounts[4].data).unwrap();
+        assert_eq!(position.asset, 495);
+        assert_eq!(position.last_deposit_slot, 100);
+        assert!(position.tracks_cost_basis);
+        assert_eq!(position.cost_basis, 2_000);
+        assert_eq!(position.deposited, 2_000);
+        assert_eq!(Mint::unpack(&deposit_accounts[2].data).unwrap().supply, 990);
+        assert_eq!(
+            DepositRecord::unpack(&deposit_accounts[21].data).unwrap().deposited,
+            1_500
+        );
+
+        // stop-loss triggered: the whole net amount is hedged
+        let mut deposit_accounts = accounts();
+        deposit_accounts[22] = stop_loss_account(
+            &fixture.program_id,
+            StopLoss {
+                mint: fixture.mint_key,
+                triggered: true,
+                ..StopLoss::default()
+            },
+        );
+        do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect()).unwrap();
+        assert_eq!(Account::unpack(&deposit_accounts[3].data).unwrap().amount, 990);
+        assert_eq!(Position::unpack(&deposit_accounts[4].data).unwrap().asset, 0);
+
+        // stop-loss armed but not triggered
+        let mut deposit_accounts = accounts();
+        deposit_accounts[22] = stop_loss_account(
+            &fixture.program_id,
+            StopLoss {
+                mint: fixture.mint_key,
+                ..StopLoss::default()
+            },
+        );
+        do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect()).unwrap();
+        assert_eq!(Position::unpack(&deposit_accounts[4].data).unwrap().asset, 495);
+
+        // stop-loss rule left out
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[22].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // rewards accrued by the previous balance are settled first
+        let mut deposit_accounts = accounts();
+        let mut account = Account::unpack(&deposit_accounts[3].data).unwrap();
+        account.amount = 100;
+        Account::pack(account, &mut deposit_accounts[3].data).unwrap();
+        deposit_accounts[23] = take_profit_account(
+            &fixture.program_id,
+            TakeProfit {
+                mint: fixture.mint_key,
+                reward_per_share: 2 * REWARD_PRECISION,
+                ..TakeProfit::default()
+            },
+        );
+        do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect()).unwrap();
+        assert_eq!(Account::unpack(&deposit_accounts[3].data).unwrap().amount, 1_090);
+        let position = Position::unpack(&deposit_accounts[4].data).unwrap();
+        assert_eq!(position.rewards, 200);
+        assert_eq!(position.reward_per_share_paid, 2 * REWARD_PRECISION);
+
+        // take-profit rule left out
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[23].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // stop-loss rule of another mint
+        let mut deposit_accounts = accounts();
+        deposit_accounts[22] = stop_loss_account(
+            &fixture.program_id,
+            StopLoss {
+                mint: Pubkey::new_unique(),
+                triggered: true,
+                ..StopLoss::default()
+            },
+        );
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // deposit above the cap on a single deposit
+        let mut deposit_accounts = accounts();
+        deposit_accounts[20] = deposit_limits_account(
+            &fixture.program_id,
+            DepositLimits {
+                max_deposit: 999,
+                ..deposit_limits
+            },
+        );
+        assert_eq!(
+            Err(TokenError::DepositCapExceeded.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // supply cap reached, the fee is not minted
+        let mut deposit_accounts = accounts();
+        deposit_accounts[20] = deposit_limits_account(
+            &fixture.program_id,
+            DepositLimits {
+                max_supply: 989,
+                ..deposit_limits
+            },
+        );
+        assert_eq!(
+            Err(TokenError::SupplyCapExceeded.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // cap on the deposits of the owner reached
+        let mut deposit_accounts = accounts();
+        deposit_accounts[21] =
+            deposit_record_account(&fixture.program_id, &fixture.mint_key, &owner_key, 1_001);
+        assert_eq!(
+            Err(TokenError::OwnerDepositCapExceeded.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // deposit record of another owner
+        let mut deposit_accounts = accounts();
+        deposit_accounts[21] = deposit_record_account(
+            &fixture.program_id,
+            &fixture.mint_key,
+            &Pubkey::new_unique(),
+            0,
+        );
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // deposit limits of another mint
+        let mut deposit_accounts = accounts();
+        deposit_accounts[20] = deposit_limits_account(
+            &fixture.program_id,
+            DepositLimits {
+                target: Pubkey::new_unique(),
+                ..deposit_limits
+            },
+        );
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // deposits paused
+        let mut deposit_accounts = accounts();
+        deposit_accounts[0] = program_config_account(
+            &fixture.program_id,
+            ProgramConfig {
+                paused: PAUSE_DEPOSIT,
+                ..program_config
+            },
+        );
+        assert_eq!(
+            Err(TokenError::Paused.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // swaps paused
+        let mut deposit_accounts = accounts();
+        deposit_accounts[0] = program_config_account(
+            &fixture.program_id,
+            ProgramConfig {
+                paused: PAUSE_SWAP,
+                ..program_config
+            },
+        );
+        assert_eq!(
+            Err(TokenError::Paused.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // protocol fee paid to an account of someone else
+        let mut deposit_accounts = accounts();
+        deposit_accounts[10] =
+            token_account(&fixture.token_program_id, &fixture.asset_mint, &Pubkey::new_unique());
+        assert_eq!(
+            Err(TokenError::IncorrectProtocolFeeAccount.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // program configuration at another address
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[0].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // position of another account
+        let mut deposit_accounts = accounts();
+        deposit_accounts[4] = position_account(&fixture.program_id, &Pubkey::new_unique(), 0, 0);
+        assert_eq!(
+            Err(TokenError::PositionMismatch.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // mint without hedge configuration
+        let mut deposit_accounts = accounts();
+        deposit_accounts[1] = SolanaAccount::default();
+        assert_eq!(
+            Err(TokenError::MintNotHedged.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // hedge configuration of another mint
+        let mut deposit_accounts = accounts();
+        let mut hedge_config = HedgeConfig::unpack(&deposit_accounts[1].data).unwrap();
+        hedge_config.mint = Pubkey::new_unique();
+        HedgeConfig::pack(hedge_config, &mut deposit_accounts[1].data).unwrap();
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // account of another mint
+        let mut deposit_accounts = accounts();
+        Account::pack(
+            Account {
+                mint: Pubkey::new_unique(),
+                owner: owner_key,
+                state: AccountState::Initialized,
+                ..Account::default()
+            },
+            &mut deposit_accounts[3].data,
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // vault other than the configured one
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[8].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectVaultAccount.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // fee account other than the configured one
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[9].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectFeeAccount.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // vault authority not derived from the mint
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[11].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::InvalidVaultAuthority.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // pool other than the configured one
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[12].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::SwapPoolMismatch.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // pool not owned by the swap program
+        let mut deposit_accounts = accounts();
+        deposit_accounts[12].owner = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectSwapProgramId.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+
+        // wrong swap program
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[19].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectSwapProgramId.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // wrong pool authority
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[13].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::InvalidSwapAuthority.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // reserves swapped
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts.swap(14, 15);
+        assert_eq!(
+            Err(TokenError::IncorrectSwapAccount.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // wrong pool mint
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[16].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectPoolMint.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // wrong pool fee account
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[17].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectPoolFeeAccount.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // wrong token program
+        let mut deposit_accounts = accounts();
+        let mut ix = deposit_ix();
+        ix.accounts[18].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectTokenProgramId.into()),
+            do_process_instruction(ix, deposit_accounts.iter_mut().collect())
+        );
+
+        // frozen account
+        let mut deposit_accounts = accounts();
+        let mut account = Account::unpack(&deposit_accounts[3].data).unwrap();
+        account.state = AccountState::Frozen;
+        Account::pack(account, &mut deposit_accounts[3].data).unwrap();
+        assert_eq!(
+            Err(TokenError::AccountFrozen.into()),
+            do_process_instruction(deposit_ix(), deposit_accounts.iter_mut().collect())
+        );
+    }
+
+    #[test]
+    fn test_stop_loss() {
+        let fixture = HedgeFixture::new();
+        let hedge_config_account = fixture.hedge_config_account(5_000, 0);
+        let asset_entry_account = fixture.asset_entry_account(&fixture.asset_mint, AssetKind::Asset);
+        let stable_entry_account =
+            fixture.asset_entry_account(&fixture.stable_mint, AssetKind::Stablecoin);
+        let oracle_key = AssetEntry::unpack(&asset_entry_account.data).unwrap().oracle;
+        let set_accounts = |stop_loss_account: &SolanaAccount| {
+            vec![
+                stop_loss_account.clone(),
+                hedge_config_account.clone(),
+                fixture.mint_account.clone(),
+                asset_entry_account.clone(),
+                stable_entry_account.clone(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                rent_sysvar(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+            ]
+        };
+        let set_ix = |authority: &Pubkey, trailing_bps| {
+            set_stop_loss(
+                &fixture.program_id,
+                &fixture.mint_key,
+                &fixture.asset_mint,
+                &fixture.stable_mint,
+                &Pubkey::new_unique(),
+                authority,
+                &[],
+                0,
+                2_000_000_000,
+                trailing_bps,
+                100,
+            )
+            .unwrap()
+        };
+        let new_stop_loss_account = SolanaAccount::new(
+            Rent::default().minimum_balance(StopLoss::get_packed_len()),
+            StopLoss::get_packed_len(),
+            &fixture.program_id,
+        );
+
+        // not the authority of the hedge configuration
+        let mut accounts = set_accounts(&new_stop_loss_account);
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_ix(&Pubkey::new_unique(), 1_000),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // trailing stop above 100%
+        let mut accounts = set_accounts(&new_stop_loss_account);
+        assert_eq!(
+            Err(TokenError::InvalidBasisPoints.into()),
+            do_process_instruction(
+                set_ix(&fixture.mint_authority, 10_001),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // success
+        let mut accounts = set_accounts(&new_stop_loss_account);
+        do_process_instruction(
+            set_ix(&fixture.mint_authority, 1_000),
+            accounts.iter_mut().collect(),
+        )
+        .unwrap();
+        let stop_loss_account = accounts.swap_remove(0);
+        let (_, bump) = find_stop_loss_with_program_id(&fixture.program_id, &fixture.mint_key);
+        assert_eq!(
+            StopLoss::unpack(&stop_loss_account.data).unwrap(),
+            StopLoss {
+                is_initialized: true,
+                mint: fixture.mint_key,
+                oracle: oracle_key,
+                stop_price: 2_000_000_000,
+                trailing_bps: 1_000,
+                max_slippage_bps: 100,
+                asset_decimals: 6,
+                stable_decimals: 6,
+                bump,
+                ..StopLoss::default()
             }
-            TokenInstruction::ThawAccount => {
-                msg!("Instruction: FreezeAccount");
-                Self::process_toggle_freeze_account(program_id, accounts, false)
+        );
+
+        let mut asset_vault_account =
+            token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.vault_authority);
+        asset_vault_account.data[64..72].copy_from_slice(&1_000_000u64.to_le_bytes());
+        let oracle_account = |price: i64, publish_slot: u64| {
+            let mut account = SolanaAccount::new(
+                42,
+                crate::oracle::PRICE_ACCOUNT_HEADER_LEN,
+                &Pubkey::new_unique(),
+            );
+            account.data = crate::oracle::price_account_data(
+                price,
+                -8,
+                crate::oracle::PYTH_STATUS_TRADING,
+                publish_slot,
+            );
+            account
+        };
+        let trigger_accounts = |program_config: ProgramConfig,
+                                stop_loss_account: &SolanaAccount,
+                                price: i64,
+                                publish_slot: u64| {
+            vec![
+                program_config_account(&fixture.program_id, program_config),
+                stop_loss_account.clone(),
+                hedge_config_account.clone(),
+                fixture.mint_account.clone(),
+                oracle_account(price, publish_slot),
+                create_account_for_test(&Clock {
+                    slot: 100,
+                    ..Clock::default()
+                }),
+                asset_vault_account.clone(),
+                token_account(&fixture.token_program_id, &fixture.stable_mint, &fixture.vault_authority),
+                SolanaAccount::default(),
+                fixture.swap_account.clone(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+            ]
+        };
+        let trigger_ix = || {
+            trigger_conditions(
+                &fixture.program_id,
+                &fixture.mint_key,
+                &oracle_key,
+                &fixture.asset_vault,
+                &fixture.stable_vault,
+                &fixture.swap_program_id,
+                &fixture.swap_key,
+                &fixture.swap_authority,
+                &fixture.swap_state.token_a,
+                &fixture.swap_state.token_b,
+                &fixture.swap_state.pool_mint,
+                &fixture.swap_state.pool_fee_account,
+                &fixture.token_program_id,
+                None,
+                0,
+            )
+            .unwrap()
+        };
+        let program_config = ProgramConfig::default();
+
+        // above the stop: only the highest price is tracked
+        let mut accounts = trigger_accounts(program_config, &stop_loss_account, 2_500_000_000, 95);
+        do_process_instruction(trigger_ix(), accounts.iter_mut().collect()).unwrap();
+        let stop_loss_account = accounts.swap_remove(1);
+        let stop_loss = StopLoss::unpack(&stop_loss_account.data).unwrap();
+        assert_eq!(stop_loss.high_price, 2_500_000_000);
+        assert_eq!(stop_loss.effective_stop(), 2_250_000_000);
+        assert!(!stop_loss.triggered);
+
+        // above the trailing stop, swaps paused
+        let paused = ProgramConfig {
+            paused: PAUSE_SWAP,
+            ..program_config
+        };
+        let mut accounts = trigger_accounts(paused, &stop_loss_account, 2_300_000_000, 100);
+        do_process_instruction(trigger_ix(), accounts.iter_mut().collect()).unwrap();
+        assert_eq!(
+            StopLoss::unpack(&accounts[1].data).unwrap().high_price,
+            2_500_000_000
+        );
+
+        // trailing stop met, swaps paused
+        let mut accounts = trigger_accounts(paused, &stop_loss_account, 2_250_000_000, 100);
+        assert_eq!(
+            Err(TokenError::Paused.into()),
+            do_process_instruction(trigger_ix(), accounts.iter_mut().collect())
+        );
+
+        // stale price
+        let mut accounts = trigger_accounts(program_config, &stop_loss_account, 2_250_000_000, 0);
+        assert_eq!(
+            Err(TokenError::StaleOraclePrice.into()),
+            do_process_instruction(trigger_ix(), accounts.iter_mut().collect())
+        );
+
+        // oracle other than the one of the rule
+        let mut accounts = trigger_accounts(program_config, &stop_loss_account, 2_250_000_000, 100);
+        let mut ix = trigger_ix();
+        ix.accounts[4].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::InvalidOracle.into()),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // vault other than the configured one
+        let mut accounts = trigger_accounts(program_config, &stop_loss_account, 2_250_000_000, 100);
+        let mut ix = trigger_ix();
+        ix.accounts[6].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectVaultAccount.into()),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // pool other than the configured one
+        let mut accounts = trigger_accounts(program_config, &stop_loss_account, 2_250_000_000, 100);
+        let mut ix = trigger_ix();
+        ix.accounts[9].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::SwapPoolMismatch.into()),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // rule not set
+        let mut accounts = trigger_accounts(program_config, &new_stop_loss_account, 2_250_000_000, 100);
+        assert_eq!(
+            Err(TokenError::StopLossNotSet.into()),
+            do_process_instruction(trigger_ix(), accounts.iter_mut().collect())
+        );
+
+        // trailing stop met: the asset vault is sold, closing the epoch
+        stub_swaps(&fixture.swap_program_id);
+        let mut accounts = trigger_accounts(program_config, &stop_loss_account, 2_250_000_000, 100);
+        do_process_instruction(trigger_ix(), accounts.iter_mut().collect()).unwrap();
+        let stop_loss_account = accounts.swap_remove(1);
+        let stop_loss = StopLoss::unpack(&stop_loss_account.data).unwrap();
+        assert!(stop_loss.triggered);
+        assert_eq!(stop_loss.triggered_slot, 100);
+        assert_eq!(stop_loss.triggered_price, 2_250_000_000);
+        assert_eq!(stop_loss.asset_sold, 1_000_000);
+        assert_eq!(stop_loss.stable_bought, 22_275_000);
+        let triggered_hedge_config_account = accounts.swap_remove(2);
+        let hedge_config = HedgeConfig::unpack(&triggered_hedge_config_account.data).unwrap();
+        assert_eq!(hedge_config.epoch, 1);
+        assert_eq!(hedge_config.asset_scale, HEDGE_PRECISION);
+        assert_eq!(hedge_config.stable_per_base, 0);
+        assert_eq!(
+            hedge_config.previous_stable_per_base,
+            22_275_000 * HEDGE_PRECISION / 1_000_000
+        );
+
+        // already triggered
+        let mut accounts = trigger_accounts(program_config, &stop_loss_account, 2_250_000_000, 100);
+        assert_eq!(
+            Err(TokenError::StopLossTriggered.into()),
+            do_process_instruction(trigger_ix(), accounts.iter_mut().collect())
+        );
+
+        // re-armed with the record of another epoch
+        let new_hedge_epoch_account = SolanaAccount::new(
+            Rent::default().minimum_balance(HedgeEpoch::get_packed_len()),
+            HedgeEpoch::get_packed_len(),
+            &fixture.program_id,
+        );
+        let mut accounts = set_accounts(&stop_loss_account);
+        accounts[1] = triggered_hedge_config_account.clone();
+        accounts[8] = new_hedge_epoch_account.clone();
+        let mut ix = set_ix(&fixture.mint_authority, 0);
+        ix.accounts[8].pubkey =
+            find_hedge_epoch_with_program_id(&fixture.program_id, &fixture.mint_key, 1).0;
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // re-armed by the authority, keeping the record of the trigger and
+        // recording the closed epoch
+        let mut accounts = set_accounts(&stop_loss_account);
+        accounts[1] = triggered_hedge_config_account;
+        accounts[8] = new_hedge_epoch_account;
+        do_process_instruction(
+            set_ix(&fixture.mint_authority, 0),
+            accounts.iter_mut().collect(),
+        )
+        .unwrap();
+        let stop_loss = StopLoss::unpack(&accounts[0].data).unwrap();
+        assert!(!stop_loss.triggered);
+        assert_eq!(stop_loss.high_price, 0);
+        assert_eq!(stop_loss.trailing_bps, 0);
+        assert_eq!(stop_loss.asset_sold, 1_000_000);
+        assert_eq!(stop_loss.effective_stop(), 2_000_000_000);
+        let (_, bump) = find_hedge_epoch_with_program_id(&fixture.program_id, &fixture.mint_key, 0);
+        assert_eq!(
+            HedgeEpoch::unpack(&accounts[8].data).unwrap(),
+            HedgeEpoch {
+                is_initialized: true,
+                mint: fixture.mint_key,
+                epoch: 0,
+                stable_per_base: hedge_config.previous_stable_per_base,
+                bump,
             }
-            TokenInstruction::TransferChecked { amount, decimals } => {
-                msg!("Instruction: TransferChecked");
-                Self::process_transfer(program_id, accounts, amount, Some(decimals))
+        );
+    }
+
+    #[test]
+    fn test_take_profit() {
+        let fixture = HedgeFixture::new();
+        let hedge_config_account = fixture.hedge_config_account(5_000, 0);
+        let asset_entry_account = fixture.asset_entry_account(&fixture.asset_mint, AssetKind::Asset);
+        let stable_entry_account =
+            fixture.asset_entry_account(&fixture.stable_mint, AssetKind::Stablecoin);
+        let oracle_key = AssetEntry::unpack(&asset_entry_account.data).unwrap().oracle;
+        let oracle_account = |price: i64, publish_slot: u64| {
+            let mut account = SolanaAccount::new(
+                42,
+                crate::oracle::PRICE_ACCOUNT_HEADER_LEN,
+                &Pubkey::new_unique(),
+            );
+            account.data = crate::oracle::price_account_data(
+                price,
+                -8,
+                crate::oracle::PYTH_STATUS_TRADING,
+                publish_slot,
+            );
+            account
+        };
+        let clock_account = || {
+            create_account_for_test(&Clock {
+                slot: 100,
+                ..Clock::default()
+            })
+        };
+        let set_accounts = |take_profit_account: &SolanaAccount| {
+            vec![
+                take_profit_account.clone(),
+                hedge_config_account.clone(),
+                fixture.mint_account.clone(),
+                asset_entry_account.clone(),
+                stable_entry_account.clone(),
+                oracle_account(2_000_000_000, 100),
+                clock_account(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                rent_sysvar(),
+                SolanaAccount::default(),
+            ]
+        };
+        let set_ix = |authority: &Pubkey, trigger_bps| {
+            set_take_profit(
+                &fixture.program_id,
+                &fixture.mint_key,
+                &fixture.asset_mint,
+                &fixture.stable_mint,
+                &oracle_key,
+                &Pubkey::new_unique(),
+                authority,
+                &[],
+                trigger_bps,
+                2_500,
+                100,
+                true,
+            )
+            .unwrap()
+        };
+        let new_take_profit_account = SolanaAccount::new(
+            Rent::default().minimum_balance(TakeProfit::get_packed_len()),
+            TakeProfit::get_packed_len(),
+            &fixture.program_id,
+        );
+
+        // not the authority of the hedge configuration
+        let mut accounts = set_accounts(&new_take_profit_account);
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_ix(&Pubkey::new_unique(), 2_000),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // no gain to trigger on
+        let mut accounts = set_accounts(&new_take_profit_account);
+        assert_eq!(
+            Err(TokenError::InvalidBasisPoints.into()),
+            do_process_instruction(set_ix(&fixture.mint_authority, 0), accounts.iter_mut().collect())
+        );
+
+        // selling the whole asset vault
+        let mut accounts = set_accounts(&new_take_profit_account);
+        let mut ix = set_ix(&fixture.mint_authority, 2_000);
+        ix.data = TokenInstruction::SetTakeProfit {
+            trigger_bps: 2_000,
+            sell_bps: 10_000,
+            max_slippage_bps: 100,
+            distribute: true,
+        }
+        .pack();
+        assert_eq!(
+            Err(TokenError::InvalidBasisPoints.into()),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // oracle other than the one of the asset
+        let mut accounts = set_accounts(&new_take_profit_account);
+        let mut ix = set_ix(&fixture.mint_authority, 2_000);
+        ix.accounts[5].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::InvalidOracle.into()),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // success: the entry price is the current price
+        let mut accounts = set_accounts(&new_take_profit_account);
+        do_process_instruction(
+            set_ix(&fixture.mint_authority, 2_000),
+            accounts.iter_mut().collect(),
+        )
+        .unwrap();
+        let rule_account = accounts.swap_remove(0);
+        let (_, bump) = find_take_profit_with_program_id(&fixture.program_id, &fixture.mint_key);
+        let take_profit = TakeProfit::unpack(&rule_account.data).unwrap();
+        assert_eq!(
+            take_profit,
+            TakeProfit {
+                is_initialized: true,
+                mint: fixture.mint_key,
+                oracle: oracle_key,
+                trigger_bps: 2_000,
+                sell_bps: 2_500,
+                max_slippage_bps: 100,
+                distribute: true,
+                asset_decimals: 6,
+                stable_decimals: 6,
+                entry_price: 2_000_000_000,
+                entry_expo: -8,
+                bump,
+                ..TakeProfit::default()
             }
-            TokenInstruction::ApproveChecked { amount, decimals } => {
-                msg!("Instruction: ApproveChecked");
-                Self::process_approve(program_id, accounts, amount, Some(decimals))
+        );
+        assert_eq!(take_profit.target_price(), 2_400_000_000);
+
+        let mut asset_vault_account =
+            token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.vault_authority);
+        asset_vault_account.data[64..72].copy_from_slice(&1_000_000u64.to_le_bytes());
+        let stable_vault_account =
+            token_account(&fixture.token_program_id, &fixture.stable_mint, &fixture.vault_authority);
+        let harvest_accounts = |program_config: ProgramConfig,
+                                take_profit_account: &SolanaAccount,
+                                price: i64| {
+            vec![
+                program_config_account(&fixture.program_id, program_config),
+                take_profit_account.clone(),
+                hedge_config_account.clone(),
+                fixture.mint_account.clone(),
+                oracle_account(price, 100),
+                clock_account(),
+                asset_vault_account.clone(),
+                stable_vault_account.clone(),
+                SolanaAccount::default(),
+                fixture.swap_account.clone(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+            ]
+        };
+        let harvest_ix = || {
+            harvest_profit(
+                &fixture.program_id,
+                &fixture.mint_key,
+                &oracle_key,
+                &fixture.asset_vault,
+                &fixture.stable_vault,
+                &fixture.swap_program_id,
+                &fixture.swap_key,
+                &fixture.swap_authority,
+                &fixture.swap_state.token_a,
+                &fixture.swap_state.token_b,
+                &fixture.swap_state.pool_mint,
+                &fixture.swap_state.pool_fee_account,
+                &fixture.token_program_id,
+                None,
+                0,
+            )
+            .unwrap()
+        };
+        let program_config = ProgramConfig::default();
+
+        // below the target
+        let mut accounts = harvest_accounts(program_config, &rule_account, 2_399_999_999);
+        assert_eq!(
+            Err(TokenError::TakeProfitNotReached.into()),
+            do_process_instruction(harvest_ix(), accounts.iter_mut().collect())
+        );
+
+        // target reached, swaps paused
+        let paused = ProgramConfig {
+            paused: PAUSE_SWAP,
+            ..program_config
+        };
+        let mut accounts = harvest_accounts(paused, &rule_account, 2_400_000_000);
+        assert_eq!(
+            Err(TokenError::Paused.into()),
+            do_process_instruction(harvest_ix(), accounts.iter_mut().collect())
+        );
+
+        // rule not set
+        let mut accounts = harvest_accounts(program_config, &new_take_profit_account, 2_400_000_000);
+        assert_eq!(
+            Err(TokenError::TakeProfitNotSet.into()),
+            do_process_instruction(harvest_ix(), accounts.iter_mut().collect())
+        );
+
+        // target reached: the entry price moves up to the current price and
+        // the positions are left a quarter less asset and credited the
+        // stablecoin bought less the profit distributed
+        stub_swaps(&fixture.swap_program_id);
+        let mut accounts = harvest_accounts(program_config, &rule_account, 2_400_000_000);
+        do_process_instruction(harvest_ix(), accounts.iter_mut().collect()).unwrap();
+        let take_profit = TakeProfit::unpack(&accounts[1].data).unwrap();
+        assert_eq!(take_profit.entry_price, 2_400_000_000);
+        assert_eq!(take_profit.last_harvest_slot, 100);
+        assert_eq!(take_profit.target_price(), 2_880_000_000);
+        assert_eq!(token_balance(&accounts[6]), 750_000);
+        assert_eq!(token_balance(&accounts[7]), 5_940_000);
+        assert_eq!(take_profit.realized_profit, 940_000);
+        let hedge_config = HedgeConfig::unpack(&accounts[2].data).unwrap();
+        assert_eq!(hedge_config.epoch, 0);
+        assert_eq!(hedge_config.asset_scale, HEDGE_PRECISION * 3 / 4);
+        assert_eq!(
+            hedge_config.stable_per_base,
+            (5_940_000 - take_profit.reserved_rewards) as u128 * HEDGE_PRECISION / 1_000_000
+        );
+
+        // disabled rule
+        let mut accounts = set_accounts(&rule_account);
+        let mut ix = set_ix(&fixture.mint_authority, 2_000);
+        ix.data = TokenInstruction::SetTakeProfit {
+            trigger_bps: 2_000,
+            sell_bps: 0,
+            max_slippage_bps: 100,
+            distribute: true,
+        }
+        .pack();
+        do_process_instruction(ix, accounts.iter_mut().collect()).unwrap();
+        let disabled_account = accounts.swap_remove(0);
+        let mut accounts = harvest_accounts(program_config, &disabled_account, 4_000_000_000);
+        assert_eq!(
+            Err(TokenError::TakeProfitNotReached.into()),
+            do_process_instruction(harvest_ix(), accounts.iter_mut().collect())
+        );
+
+        // claims settle the rewards accrued by the balance
+        let owner_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let distributing_account = take_profit_account(
+            &fixture.program_id,
+            TakeProfit {
+                mint: fixture.mint_key,
+                reward_per_share: 2 * REWARD_PRECISION,
+                reserved_rewards: 500,
+                ..TakeProfit::default()
+            },
+        );
+        let claim_accounts = || {
+            vec![
+                distributing_account.clone(),
+                hedge_config_account.clone(),
+                fixture.mint_account.clone(),
+                initialized_account(&fixture.program_id, &fixture.mint_key, &owner_key, 100),
+                position_account(&fixture.program_id, &account_key, 100, 0),
+                stable_vault_account.clone(),
+                token_account(&fixture.token_program_id, &fixture.stable_mint, &owner_key),
+                SolanaAccount::default(),
+                SolanaAccount::new(0, 0, &fixture.token_program_id),
+                SolanaAccount::default(),
+            ]
+        };
+        let claim_ix = |owner: &Pubkey| {
+            claim_rewards(
+                &fixture.program_id,
+                &fixture.mint_key,
+                &account_key,
+                &fixture.stable_vault,
+                &Pubkey::new_unique(),
+                &fixture.token_program_id,
+                owner,
+                &[],
+            )
+            .unwrap()
+        };
+
+        // not the owner of the account
+        let mut accounts = claim_accounts();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(claim_ix(&Pubkey::new_unique()), accounts.iter_mut().collect())
+        );
+
+        // vault other than the configured one
+        let mut accounts = claim_accounts();
+        let mut ix = claim_ix(&owner_key);
+        ix.accounts[5].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectVaultAccount.into()),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // success
+        let mut accounts = claim_accounts();
+        do_process_instruction(claim_ix(&owner_key), accounts.iter_mut().collect()).unwrap();
+        let position = Position::unpack(&accounts[4].data).unwrap();
+        assert_eq!(position.rewards, 0);
+        assert_eq!(position.reward_per_share_paid, 2 * REWARD_PRECISION);
+        assert_eq!(
+            TakeProfit::unpack(&accounts[0].data).unwrap().reserved_rewards,
+            300
+        );
+    }
+
+    #[test]
+    fn test_dca() {
+        let fixture = HedgeFixture::new();
+        let portfolio_key = Pubkey::new_unique();
+        let creator_key = Pubkey::new_unique();
+        let mut portfolio_account =
+            SolanaAccount::new(42, Portfolio::get_packed_len(), &fixture.program_id);
+        Portfolio::pack(
+            Portfolio {
+                portfolio_account: portfolio_key,
+                creator_portfolio: creator_key,
+                is_initialize: 1,
+                addressAsset1: fixture.asset_mint,
+                assetToSoldIntoAsset1: fixture.stable_mint,
+                periodAsset1: 2,
+                ..Portfolio::default()
+            },
+            &mut portfolio_account.data,
+        )
+        .unwrap();
+        let (dca_config_key, bump) =
+            find_dca_config_with_program_id(&fixture.program_id, &portfolio_key, 1);
+        let (vault_authority, vault_authority_bump) =
+            find_vault_authority_with_program_id(&fixture.program_id, &dca_config_key);
+        let asset_vault_account =
+            token_account(&fixture.token_program_id, &fixture.asset_mint, &vault_authority);
+        let stable_vault_account =
+            token_account(&fixture.token_program_id, &fixture.stable_mint, &vault_authority);
+        let asset_entry_account = fixture.asset_entry_account(&fixture.asset_mint, AssetKind::Asset);
+        let oracle_key = AssetEntry::unpack(&asset_entry_account.data).unwrap().oracle;
+        let new_dca_config_account = SolanaAccount::new(
+            Rent::default().minimum_balance(DcaConfig::get_packed_len()),
+            DcaConfig::get_packed_len(),
+            &fixture.program_id,
+        );
+        let init_accounts = |asset_vault_account: &SolanaAccount| {
+            vec![
+                new_dca_config_account.clone(),
+                portfolio_account.clone(),
+                SolanaAccount::default(),
+                asset_vault_account.clone(),
+                stable_vault_account.clone(),
+                fixture.swap_account.clone(),
+                asset_entry_account.clone(),
+                fixture.asset_entry_account(&fixture.stable_mint, AssetKind::Stablecoin),
+                SolanaAccount::default(),
+                rent_sysvar(),
+                SolanaAccount::default(),
+            ]
+        };
+        let init_ix = |creator: &Pubkey, asset_index, max_slippage_bps| {
+            initialize_dca(
+                &fixture.program_id,
+                &portfolio_key,
+                &fixture.asset_mint,
+                &fixture.stable_mint,
+                &fixture.asset_vault,
+                &fixture.stable_vault,
+                &fixture.swap_key,
+                &Pubkey::new_unique(),
+                creator,
+                &[],
+                asset_index,
+                3,
+                max_slippage_bps,
+            )
+            .unwrap()
+        };
+
+        // not the creator of the portfolio
+        let mut accounts = init_accounts(&asset_vault_account);
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                init_ix(&Pubkey::new_unique(), 1, 100),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // slippage above 100%
+        let mut accounts = init_accounts(&asset_vault_account);
+        assert_eq!(
+            Err(TokenError::InvalidBasisPoints.into()),
+            do_process_instruction(
+                init_ix(&creator_key, 1, BASIS_POINTS + 1),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // asset without a schedule
+        let mut accounts = init_accounts(&asset_vault_account);
+        assert_eq!(
+            Err(TokenError::InvalidDcaSchedule.into()),
+            do_process_instruction(init_ix(&creator_key, 2, 100), accounts.iter_mut().collect())
+        );
+
+        // vault not owned by the derived authority
+        let foreign_vault_account = token_account(
+            &fixture.token_program_id,
+            &fixture.asset_mint,
+            &fixture.vault_authority,
+        );
+        let mut accounts = init_accounts(&foreign_vault_account);
+        assert_eq!(
+            Err(TokenError::IncorrectVaultOwner.into()),
+            do_process_instruction(init_ix(&creator_key, 1, 100), accounts.iter_mut().collect())
+        );
+
+        // success
+        let mut accounts = init_accounts(&asset_vault_account);
+        do_process_instruction(init_ix(&creator_key, 1, 100), accounts.iter_mut().collect())
+            .unwrap();
+        let dca_config_account = accounts.swap_remove(0);
+        assert_eq!(
+            DcaConfig::unpack(&dca_config_account.data).unwrap(),
+            DcaConfig {
+                is_initialized: true,
+                portfolio: portfolio_key,
+                asset_index: 1,
+                asset_mint: fixture.asset_mint,
+                stable_mint: fixture.stable_mint,
+                swap_program_id: fixture.swap_program_id,
+                swap_pool: fixture.swap_key,
+                asset_vault: fixture.asset_vault,
+                stable_vault: fixture.stable_vault,
+                oracle: oracle_key,
+                max_slippage_bps: 100,
+                asset_decimals: 6,
+                stable_decimals: 6,
+                period_slots: 2 * DCA_PERIOD_SLOTS,
+                tranches: 3,
+                total_usdc: 0,
+                total_asset: 0,
+                vault_authority_bump,
+                bump,
             }
-            TokenInstruction::MintToChecked { amount, decimals } => {
-                msg!("Instruction: MintToChecked");
-                Self::process_mint_to(program_id, accounts, amount, Some(decimals))
+        );
+
+        // already initialized
+        let mut accounts = init_accounts(&asset_vault_account);
+        accounts[0] = dca_config_account.clone();
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(init_ix(&creator_key, 1, 100), accounts.iter_mut().collect())
+        );
+
+        // deposits split the stablecoin into equal tranches
+        let owner_key = Pubkey::new_unique();
+        let mut accounts = vec![
+            program_config_account(&fixture.program_id, ProgramConfig::default()),
+            dca_config_account,
+            SolanaAccount::new(
+                Rent::default().minimum_balance(DcaPosition::get_packed_len()),
+                DcaPosition::get_packed_len(),
+                &fixture.program_id,
+            ),
+            SolanaAccount::default(),
+            stable_vault_account.clone(),
+            SolanaAccount::default(),
+            SolanaAccount::default(),
+            SolanaAccount::default(),
+            rent_sysvar(),
+            SolanaAccount::default(),
+        ];
+        let deposit_ix = |amount| {
+            deposit_dca(
+                &fixture.program_id,
+                &fixture.token_program_id,
+                &dca_config_key,
+                &Pubkey::new_unique(),
+                &fixture.stable_vault,
+                &Pubkey::new_unique(),
+                &owner_key,
+                amount,
+            )
+            .unwrap()
+        };
+        do_process_instruction(deposit_ix(600), accounts.iter_mut().collect()).unwrap();
+        do_process_instruction(deposit_ix(400), accounts.iter_mut().collect()).unwrap();
+        let (_, position_bump) =
+            find_dca_position_with_program_id(&fixture.program_id, &dca_config_key, &owner_key);
+        let dca_position_account = accounts.swap_remove(2);
+        let dca_config_account = accounts.swap_remove(1);
+        assert_eq!(
+            DcaPosition::unpack(&dca_position_account.data).unwrap(),
+            DcaPosition {
+                is_initialized: true,
+                dca_config: dca_config_key,
+                owner: owner_key,
+                usdc: 1_000,
+                tranche_amount: 334,
+                asset: 0,
+                next_execution_slot: 0,
+                bump: position_bump,
             }
-            TokenInstruction::BurnChecked { amount, decimals } => {
-                msg!("Instruction: BurnChecked");
-                Self::process_burn(program_id, accounts, amount, Some(decimals))
+        );
+        assert_eq!(
+            DcaConfig::unpack(&dca_config_account.data).unwrap().total_usdc,
+            1_000
+        );
+
+        // the vaults hold the deposits and the pool enough of the asset to
+        // fill the tranches at 2 stablecoin per asset
+        stub_swaps(&fixture.swap_program_id);
+        let mut funded_stable_vault_account = stable_vault_account.clone();
+        funded_stable_vault_account.data[64..72].copy_from_slice(&1_000u64.to_le_bytes());
+        let asset_reserve_account = |amount: u64| {
+            let mut account = token_account(
+                &fixture.token_program_id,
+                &fixture.asset_mint,
+                &fixture.swap_authority,
+            );
+            account.data[64..72].copy_from_slice(&amount.to_le_bytes());
+            account
+        };
+        let oracle_account = |price: i64, publish_slot: u64| {
+            let mut account = SolanaAccount::new(
+                42,
+                crate::oracle::PRICE_ACCOUNT_HEADER_LEN,
+                &Pubkey::new_unique(),
+            );
+            account.data = crate::oracle::price_account_data(
+                price,
+                -8,
+                crate::oracle::PYTH_STATUS_TRADING,
+                publish_slot,
+            );
+            account
+        };
+        let execute_accounts = |program_config: ProgramConfig,
+                                dca_config_account: &SolanaAccount,
+                                dca_position_account: &SolanaAccount,
+                                asset_reserve_balance: u64| {
+            vec![
+                program_config_account(&fixture.program_id, program_config),
+                dca_config_account.clone(),
+                dca_position_account.clone(),
+                oracle_account(200_000_000, 100),
+                create_account_for_test(&Clock {
+                    slot: 100,
+                    ..Clock::default()
+                }),
+                funded_stable_vault_account.clone(),
+                asset_vault_account.clone(),
+                SolanaAccount::default(),
+                fixture.swap_account.clone(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                asset_reserve_account(asset_reserve_balance),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+            ]
+        };
+        let execute_ix_with_oracle = |oracle: &Pubkey,
+                                      swap_source: &Pubkey,
+                                      swap_destination: &Pubkey| {
+            execute_dca(
+                &fixture.program_id,
+                &dca_config_key,
+                &owner_key,
+                oracle,
+                &fixture.stable_vault,
+                &fixture.asset_vault,
+                &fixture.swap_program_id,
+                &fixture.swap_key,
+                &fixture.swap_authority,
+                swap_source,
+                swap_destination,
+                &fixture.swap_state.pool_mint,
+                &fixture.swap_state.pool_fee_account,
+                &fixture.token_program_id,
+                None,
+                0,
+            )
+            .unwrap()
+        };
+        let execute_ix = |swap_source: &Pubkey, swap_destination: &Pubkey| {
+            execute_ix_with_oracle(&oracle_key, swap_source, swap_destination)
+        };
+        let stable_reserve = fixture.swap_state.token_b;
+        let asset_reserve = fixture.swap_state.token_a;
+
+        // swaps paused
+        let paused = ProgramConfig {
+            paused: PAUSE_SWAP,
+            ..ProgramConfig::default()
+        };
+        let mut accounts =
+            execute_accounts(paused, &dca_config_account, &dca_position_account, 1_000);
+        assert_eq!(
+            Err(TokenError::Paused.into()),
+            do_process_instruction(
+                execute_ix(&stable_reserve, &asset_reserve),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // selling the asset instead of buying it
+        let mut accounts = execute_accounts(
+            ProgramConfig::default(),
+            &dca_config_account,
+            &dca_position_account,
+            1_000,
+        );
+        assert_eq!(
+            Err(TokenError::IncorrectSwapAccount.into()),
+            do_process_instruction(
+                execute_ix(&asset_reserve, &stable_reserve),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // oracle other than the one of the asset
+        let mut accounts = execute_accounts(
+            ProgramConfig::default(),
+            &dca_config_account,
+            &dca_position_account,
+            1_000,
+        );
+        assert_eq!(
+            Err(TokenError::InvalidOracle.into()),
+            do_process_instruction(
+                execute_ix_with_oracle(&Pubkey::new_unique(), &stable_reserve, &asset_reserve),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // the 334 stablecoin of the tranche are worth 167 asset, less 1%
+        // slippage: a pool filling only 164 is rejected even though the
+        // caller asked for no minimum
+        let mut accounts = execute_accounts(
+            ProgramConfig::default(),
+            &dca_config_account,
+            &dca_position_account,
+            164,
+        );
+        assert_eq!(
+            Err(ProgramError::Custom(SWAP_EXCEEDED_SLIPPAGE)),
+            do_process_instruction(
+                execute_ix(&stable_reserve, &asset_reserve),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // success: one tranche is sold at the oracle minimum and the next one
+        // waits a period
+        let mut accounts = execute_accounts(
+            ProgramConfig::default(),
+            &dca_config_account,
+            &dca_position_account,
+            1_000,
+        );
+        do_process_instruction(
+            execute_ix(&stable_reserve, &asset_reserve),
+            accounts.iter_mut().collect(),
+        )
+        .unwrap();
+        let dca_position = DcaPosition::unpack(&accounts[2].data).unwrap();
+        assert_eq!(dca_position.usdc, 666);
+        assert_eq!(dca_position.asset, 165);
+        assert_eq!(token_balance(&accounts[6]), 165);
+        assert_eq!(dca_position.next_execution_slot, 100 + 2 * DCA_PERIOD_SLOTS);
+        assert_eq!(
+            DcaConfig::unpack(&accounts[1].data).unwrap().total_usdc,
+            666
+        );
+        let dca_position_account = accounts.swap_remove(2);
+        let dca_config_account = accounts.swap_remove(1);
+
+        // next tranche not due yet
+        let mut accounts = execute_accounts(
+            ProgramConfig::default(),
+            &dca_config_account,
+            &dca_position_account,
+            1_000,
+        );
+        assert_eq!(
+            Err(TokenError::DcaNotDue.into()),
+            do_process_instruction(
+                execute_ix(&stable_reserve, &asset_reserve),
+                accounts.iter_mut().collect()
+            )
+        );
+
+        // withdrawals are limited to the remaining balances
+        let withdraw_accounts = || {
+            vec![
+                program_config_account(&fixture.program_id, ProgramConfig::default()),
+                dca_config_account.clone(),
+                dca_position_account.clone(),
+                stable_vault_account.clone(),
+                asset_vault_account.clone(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+            ]
+        };
+        let withdraw_ix = |owner: &Pubkey, usdc| {
+            withdraw_dca(
+                &fixture.program_id,
+                &fixture.token_program_id,
+                &dca_config_key,
+                &fixture.stable_vault,
+                &fixture.asset_vault,
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                owner,
+                usdc,
+                0,
+            )
+            .unwrap()
+        };
+        let mut accounts = withdraw_accounts();
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(withdraw_ix(&owner_key, 667), accounts.iter_mut().collect())
+        );
+
+        // someone else's position
+        let mut accounts = withdraw_accounts();
+        let stranger_ix = withdraw_ix(&Pubkey::new_unique(), 666);
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(stranger_ix.clone(), accounts.iter_mut().collect())
+        );
+        let mut accounts = withdraw_accounts();
+        let mut ix = stranger_ix;
+        ix.accounts[2].pubkey = withdraw_ix(&owner_key, 0).accounts[2].pubkey;
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+
+        // success
+        let mut accounts = withdraw_accounts();
+        do_process_instruction(withdraw_ix(&owner_key, 666), accounts.iter_mut().collect())
+            .unwrap();
+        assert_eq!(DcaPosition::unpack(&accounts[2].data).unwrap().usdc, 0);
+        assert_eq!(DcaConfig::unpack(&accounts[1].data).unwrap().total_usdc, 0);
+    }
+
+    #[test]
+    fn test_cost_basis() {
+        let tracked = Position {
+            tracks_cost_basis: true,
+            ..Position::default()
+        };
+
+        // weighted average: a quarter of the balance takes a quarter of the cost
+        let mut source = tracked;
+        source.record_deposit(1_000).unwrap();
+        source.record_withdrawal(25, 100, 300).unwrap();
+        assert_eq!(source.cost_basis, 750);
+        assert_eq!(source.deposited, 1_000);
+        assert_eq!(source.withdrawn, 300);
+        assert_eq!(source.realized_pnl, 50);
+
+        // transfers move the cost without realizing it
+        let mut destination = Position::default();
+        source
+            .transfer_cost_basis(&mut destination, 50, 75, 0)
+            .unwrap();
+        assert_eq!(source.cost_basis, 250);
+        assert_eq!(source.realized_pnl, 50);
+        assert!(destination.tracks_cost_basis);
+        assert_eq!(destination.cost_basis, 500);
+        assert_eq!(destination.deposited, 0);
+
+        // an untracked balance makes the destination untracked
+        let mut untracked = Position {
+            cost_basis: 10,
+            ..Position::default()
+        };
+        untracked
+            .transfer_cost_basis(&mut destination, 5, 10, 50)
+            .unwrap();
+        assert!(!destination.tracks_cost_basis);
+
+        // pnl at 2.5 stablecoin per asset
+        let price = crate::oracle::OraclePrice {
+            price: 250_000_000,
+            expo: -8,
+            publish_slot: 100,
+        };
+        let position = Position {
+            asset: 10_000_000,
+            usdc: 1_000_000,
+            cost_basis: 20_000_000,
+            realized_pnl: -300,
+            ..tracked
+        };
+        assert_eq!(
+            position.pnl(&price, 6, 6),
+            Some(crate::state::Pnl {
+                realized: -300,
+                unrealized: 6_000_000,
+            })
+        );
+        assert_eq!(
+            Position {
+                tracks_cost_basis: false,
+                ..position
             }
-            TokenInstruction::Deposit { amount , volatility, nonce} => {
-                msg!("Instruction: Deposit");
-                Self::process_deposit(program_id , accounts , amount , volatility , nonce)
+            .pnl(&price, 6, 6),
+            None
+        );
+
+        // withdrawals are valued at the spot price of the pool
+        let fixture = HedgeFixture::new();
+        let owner_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let mut position_account = position_account(&fixture.program_id, &account_key, 100, 0);
+        Position::pack(
+            Position {
+                cost_basis: 150,
+                tracks_cost_basis: true,
+                ..Position::unpack(&position_account.data).unwrap()
+            },
+            &mut position_account.data,
+        )
+        .unwrap();
+        let mut asset_reserve_account =
+            token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.swap_authority);
+        asset_reserve_account.data[64..72].copy_from_slice(&1_000_000u64.to_le_bytes());
+        let mut stable_reserve_account =
+            token_account(&fixture.token_program_id, &fixture.stable_mint, &fixture.swap_authority);
+        stable_reserve_account.data[64..72].copy_from_slice(&2_000_000u64.to_le_bytes());
+        let accounts = || {
+            vec![
+                program_config_account(&fixture.program_id, ProgramConfig::default()),
+                initialized_account(&fixture.program_id, &fixture.mint_key, &owner_key, 100),
+                position_account.clone(),
+                SolanaAccount::default(),
+                fixture.hedge_config_account(5_000, 0),
+                fixture.swap_account.clone(),
+                asset_reserve_account.clone(),
+                stable_reserve_account.clone(),
+                SolanaAccount::default(),
+            ]
+            .into_iter()
+            .chain(fixture.withdraw_payout_accounts(100))
+            .chain(vec![
+                create_account_for_test(&Clock {
+                    slot: 100,
+                    ..Clock::default()
+                }),
+                SolanaAccount::default(),
+            ])
+            .collect::<Vec<_>>()
+        };
+        let withdraw_ix =
+            |asset_reserve: &Pubkey| fixture.withdraw_ix(&account_key, asset_reserve, &owner_key, 10);
+
+        // reserve of another pool
+        let mut withdraw_accounts = accounts();
+        assert_eq!(
+            Err(TokenError::IncorrectSwapAccount.into()),
+            do_process_instruction(
+                withdraw_ix(&Pubkey::new_unique()),
+                withdraw_accounts.iter_mut().collect()
+            )
+        );
+
+        // success
+        let mut withdraw_accounts = accounts();
+        do_process_instruction(
+            withdraw_ix(&fixture.swap_state.token_a),
+            withdraw_accounts.iter_mut().collect(),
+        )
+        .unwrap();
+        let position = Position::unpack(&withdraw_accounts[2].data).unwrap();
+        assert_eq!(position.asset, 90);
+        assert_eq!(position.cost_basis, 135);
+        assert_eq!(position.withdrawn, 20);
+        assert_eq!(position.realized_pnl, 5);
+    }
+
+    #[test]
+    fn test_withdraw_ticket() {
+        let fixture = HedgeFixture::new();
+        let program_id = fixture.program_id;
+        let admin_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let clock_account = |slot| {
+            create_account_for_test(&Clock {
+                slot,
+                ..Clock::default()
+            })
+        };
+
+        // only the admin sets the delay
+        let mut program_config_account = program_config_account(
+            &program_id,
+            ProgramConfig {
+                admin: admin_key,
+                ..ProgramConfig::default()
+            },
+        );
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_withdraw_delay(&program_id, &Pubkey::new_unique(), &[], 10, false).unwrap(),
+                vec![&mut program_config_account, &mut SolanaAccount::default()],
+            )
+        );
+        do_process_instruction(
+            set_withdraw_delay(&program_id, &admin_key, &[], 10, false).unwrap(),
+            vec![&mut program_config_account, &mut SolanaAccount::default()],
+        )
+        .unwrap();
+        let program_config = ProgramConfig::unpack(&program_config_account.data).unwrap();
+        assert_eq!(program_config.withdraw_delay_slots, 10);
+        assert!(!program_config.bypass_withdraw_delay);
+
+        let account_account = initialized_account(&program_id, &fixture.mint_key, &owner_key, 100);
+        let mut position_account = position_account(&program_id, &account_key, 100, 0);
+        Position::pack(
+            Position {
+                last_deposit_slot: 100,
+                ..Position::unpack(&position_account.data).unwrap()
+            },
+            &mut position_account.data,
+        )
+        .unwrap();
+        let mut withdraw_ticket_account = SolanaAccount::new(
+            Rent::default().minimum_balance(WithdrawTicket::get_packed_len()),
+            WithdrawTicket::get_packed_len(),
+            &program_id,
+        );
+        let withdraw_accounts = |program_config_account: &SolanaAccount,
+                                 withdraw_ticket_account: &SolanaAccount,
+                                 slot| {
+            vec![
+                program_config_account.clone(),
+                account_account.clone(),
+                position_account.clone(),
+                SolanaAccount::default(),
+                fixture.hedge_config_account(5_000, 0),
+                fixture.swap_account.clone(),
+                token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.swap_authority),
+                token_account(&fixture.token_program_id, &fixture.stable_mint, &fixture.swap_authority),
+                withdraw_ticket_account.clone(),
+            ]
+            .into_iter()
+            .chain(fixture.withdraw_payout_accounts(100))
+            .chain(vec![clock_account(slot), SolanaAccount::default()])
+            .collect::<Vec<_>>()
+        };
+        let withdraw_ix = |amount| {
+            fixture.withdraw_ix(&account_key, &fixture.swap_state.token_a, &owner_key, amount)
+        };
+
+        // same slot as the last deposit
+        let mut accounts = withdraw_accounts(&program_config_account, &withdraw_ticket_account, 100);
+        assert_eq!(
+            Err(TokenError::WithdrawInDepositSlot.into()),
+            do_process_instruction(withdraw_ix(10), accounts.iter_mut().collect())
+        );
+
+        // nothing requested
+        let mut accounts = withdraw_accounts(&program_config_account, &withdraw_ticket_account, 101);
+        assert_eq!(
+            Err(TokenError::WithdrawNotRequested.into()),
+            do_process_instruction(withdraw_ix(10), accounts.iter_mut().collect())
+        );
+
+        // request a withdrawal
+        let request_ix = |owner: &Pubkey, amount| {
+            request_withdraw(&program_id, &account_key, &Pubkey::new_unique(), owner, &[], amount)
+                .unwrap()
+        };
+        let request = |withdraw_ticket_account: &mut SolanaAccount, ix| {
+            do_process_instruction(
+                ix,
+                vec![
+                    &mut program_config_account.clone(),
+                    &mut account_account.clone(),
+                    withdraw_ticket_account,
+                    &mut clock_account(101),
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut rent_sysvar(),
+                    &mut SolanaAccount::default(),
+                ],
+            )
+        };
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            request(&mut withdraw_ticket_account, request_ix(&Pubkey::new_unique(), 10))
+        );
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            request(&mut withdraw_ticket_account, request_ix(&owner_key, 101))
+        );
+        request(&mut withdraw_ticket_account, request_ix(&owner_key, 10)).unwrap();
+        let (_, bump) = find_withdraw_ticket_with_program_id(&program_id, &account_key);
+        assert_eq!(
+            WithdrawTicket::unpack(&withdraw_ticket_account.data).unwrap(),
+            WithdrawTicket {
+                is_initialized: true,
+                account: account_key,
+                amount: 10,
+                request_slot: 101,
+                claimable_slot: 111,
+                bump,
             }
-            TokenInstruction::Withdraw { amount } => {
-                msg!("Instruction: Withdraw");
-                Self::process_withdraw(program_id , accounts , amount)
+        );
+
+        // still in its delay
+        let mut accounts = withdraw_accounts(&program_config_account, &withdraw_ticket_account, 110);
+        assert_eq!(
+            Err(TokenError::WithdrawNotClaimable.into()),
+            do_process_instruction(withdraw_ix(10), accounts.iter_mut().collect())
+        );
+
+        // more than requested
+        let mut accounts = withdraw_accounts(&program_config_account, &withdraw_ticket_account, 111);
+        assert_eq!(
+            Err(TokenError::WithdrawNotRequested.into()),
+            do_process_instruction(withdraw_ix(11), accounts.iter_mut().collect())
+        );
+
+        // success
+        let mut accounts = withdraw_accounts(&program_config_account, &withdraw_ticket_account, 111);
+        do_process_instruction(withdraw_ix(10), accounts.iter_mut().collect()).unwrap();
+        assert_eq!(Account::unpack(&accounts[1].data).unwrap().amount, 90);
+        assert_eq!(WithdrawTicket::unpack(&accounts[8].data).unwrap().amount, 0);
+
+        // the admin bypasses both rules for an emergency unwind
+        do_process_instruction(
+            set_withdraw_delay(&program_id, &admin_key, &[], 10, true).unwrap(),
+            vec![&mut program_config_account, &mut SolanaAccount::default()],
+        )
+        .unwrap();
+        let mut accounts =
+            withdraw_accounts(&program_config_account, &SolanaAccount::default(), 100);
+        do_process_instruction(withdraw_ix(10), accounts.iter_mut().collect()).unwrap();
+        assert_eq!(Account::unpack(&accounts[1].data).unwrap().amount, 90);
+    }
+
+    #[test]
+    fn test_close_account_with_position() {
+        let program_id = crate::id();
+        let owner_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let destination_key = Pubkey::new_unique();
+        let close_ix = || {
+            close_account_with_position(&program_id, &account_key, &destination_key, &owner_key, &[])
+                .unwrap()
+        };
+        let close = |account_account: &mut SolanaAccount,
+                     destination_account: &mut SolanaAccount,
+                     position_account: &mut SolanaAccount| {
+            do_process_instruction(
+                close_ix(),
+                vec![
+                    account_account,
+                    destination_account,
+                    &mut SolanaAccount::default(),
+                    position_account,
+                ],
+            )
+        };
+        let empty_position = position_account(&program_id, &account_key, 0, 0);
+
+        // hedged components left behind
+        let mut account_account =
+            initialized_account(&program_id, &Pubkey::new_unique(), &owner_key, 0);
+        let mut destination_account = SolanaAccount::default();
+        for position in [
+            Position {
+                usdc: 3,
+                ..Position::unpack(&empty_position.data).unwrap()
             },
-            TokenInstruction::InitializePortfolio {
-                metaDataUrl,
-                metaDataHash,
-                amountAsset1,
-                periodAsset1,
-                amountAsset2,
-                periodAsset2,
-                amountAsset3,
-                periodAsset3,
-                amountAsset4,
-                periodAsset4,
-                amountAsset5,
-                periodAsset5,
-                amountAsset6,
-                periodAsset6,
-                amountAsset7,
-                periodAsset7,
-                amountAsset8,
-                periodAsset8,
-                amountAsset9,
-                periodAsset9,
-                // amountAsset10,
-                // periodAsset10
-             } => {
-                msg!("Instruction: InitializePortfolio");
-                Self::process_initialize_portfolio(program_id , accounts , 
-                    metaDataUrl,
-                   metaDataHash,
-                    amountAsset1,
-                    periodAsset1,
-                    amountAsset2,
-                    periodAsset2,
-                    amountAsset3,
-                    periodAsset3,
-                    amountAsset4,
-                    periodAsset4,
-                    amountAsset5,
-                    periodAsset5,
-                    amountAsset6,
-                    periodAsset6,
-                    amountAsset7,
-                    periodAsset7,
-                    amountAsset8,
-                    periodAsset8,
-                    amountAsset9,
-                    periodAsset9,
-                    // amountAsset10,
-                    // periodAsset10
-                )
+            Position {
+                asset: 1,
+                ..Position::unpack(&empty_position.data).unwrap()
             },
-            TokenInstruction::createInitUserPortfolio {
-                delegated_amount,
-             } => {
-                msg!("Instruction: createInitUserPortfolio");
-                Self::process_create_Init_User_Portfolio(program_id , accounts ,
-                    delegated_amount,
-                )
+            Position {
+                rewards: 1,
+                ..Position::unpack(&empty_position.data).unwrap()
             },
+        ] {
+            let mut position_account = empty_position.clone();
+            Position::pack(position, &mut position_account.data).unwrap();
+            assert_eq!(
+                Err(TokenError::PositionNotEmpty.into()),
+                close(&mut account_account, &mut destination_account, &mut position_account)
+            );
         }
-    }
 
-    /// Deposit nAsset
-    pub fn process_create_Init_User_Portfolio(
-        program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        delegated_amount:u64,
-    ) -> ProgramResult {
-        let accounts_iter = &mut accounts.iter();
-        let user_portfolio_account = next_account_info(accounts_iter)?;
-        let portfolio_address = next_account_info(accounts_iter)?;
-        let owner = next_account_info(accounts_iter)?;
-        let delegate = next_account_info(accounts_iter)?;
-        msg!("create Init User Portfolio ");
-        let mut user_portfolio = UserPortfolio::unpack(&mut user_portfolio_account.data.borrow())?;
+        // an empty position is closed with the account
+        let mut position_account = empty_position.clone();
+        close(&mut account_account, &mut destination_account, &mut position_account).unwrap();
+        assert_eq!(account_account.lamports, 0);
+        assert_eq!(position_account.lamports, 0);
+        assert_eq!(
+            destination_account.lamports,
+            account_minimum_balance() + empty_position.lamports
+        );
+        assert!(position_account.data.iter().all(|byte| *byte == 0));
+
+        // the position of a hedged account cannot be left out
+        let mint_key = Pubkey::new_unique();
+        let (hedge_config_key, _) = find_hedge_config_with_program_id(&program_id, &mint_key);
+        let mut hedge_config_account = hedge_config_account(
+            &program_id,
+            HedgeConfig {
+                mint: mint_key,
+                ..HedgeConfig::default()
+            },
+        );
+        let mut account_account = initialized_account(&program_id, &mint_key, &owner_key, 0);
+        let mut ix = close_account(&program_id, &account_key, &destination_key, &owner_key, &[])
+            .unwrap();
+        ix.accounts
+            .push(AccountMeta::new_readonly(hedge_config_key, false));
+        assert_eq!(
+            Err(TokenError::PositionRequired.into()),
+            do_process_instruction(
+                ix.clone(),
+                vec![
+                    &mut account_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut hedge_config_account,
+                ],
+            )
+        );
+
+        // a hedged account holding components cannot be closed
+        let mut ix = close_ix();
+        ix.accounts
+            .push(AccountMeta::new_readonly(hedge_config_key, false));
+        let mut position_account = empty_position.clone();
+        Position::pack(
+            Position {
+                usdc: 1,
+                ..Position::unpack(&empty_position.data).unwrap()
+            },
+            &mut position_account.data,
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::PositionNotEmpty.into()),
+            do_process_instruction(
+                ix.clone(),
+                vec![
+                    &mut account_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut position_account,
+                    &mut hedge_config_account,
+                ],
+            )
+        );
 
+        // a hedged account with an empty position is closed along with it
+        let mut position_account = empty_position.clone();
+        let mut destination_account = SolanaAccount::default();
+        do_process_instruction(
+            ix,
+            vec![
+                &mut account_account,
+                &mut destination_account,
+                &mut SolanaAccount::default(),
+                &mut position_account,
+                &mut hedge_config_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(position_account.lamports, 0);
+        assert_eq!(
+            destination_account.lamports,
+            account_minimum_balance() + empty_position.lamports
+        );
 
-        user_portfolio.user_portfolio_account = *user_portfolio_account.key;
-        user_portfolio.portfolio_address = *portfolio_address.key;
-        user_portfolio.owner = *owner.key;
-        user_portfolio.delegated_amount = delegated_amount;
-     /*
-        //portfolio.delegate = COption::None;
-        portfolio.delegated_amount = 0;
-        portfolio.userAccount = *user_account.key;
-        portfolio.userPortfolioAccount = *userPortfolioAccount.key;
-        portfolio.portfolioAddress = *portfolioAddress.key;
+        // accounts of other mints are closed without their position
+        let mut account_account =
+            initialized_account(&program_id, &Pubkey::new_unique(), &owner_key, 0);
+        let mut destination_account = SolanaAccount::default();
+        do_process_instruction(
+            close_account(&program_id, &account_key, &destination_key, &owner_key, &[]).unwrap(),
+            vec![
+                &mut account_account,
+                &mut destination_account,
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(destination_account.lamports, account_minimum_balance());
 
-        UserPortfolio::pack(portfolio, &mut user_account.data.borrow_mut())?;
-*/       msg!("user portfolio account afet exec  : {:?} ",user_portfolio );
-        UserPortfolio::pack(user_portfolio, &mut user_portfolio_account.data.borrow_mut())?;
-        msg!("final create user with success") ;
-        Ok(())
+        // a native account has no position
+        let mut account_account =
+            initialized_account(&program_id, &crate::native_mint::id(), &owner_key, 42);
+        let mut account = Account::unpack(&account_account.data).unwrap();
+        account.is_native = COption::Some(account_minimum_balance());
+        Account::pack(account, &mut account_account.data).unwrap();
+        account_account.lamports += 42;
+        let mut destination_account = SolanaAccount::default();
+        close(
+            &mut account_account,
+            &mut destination_account,
+            &mut SolanaAccount::default(),
+        )
+        .unwrap();
+        assert_eq!(destination_account.lamports, account_minimum_balance() + 42);
 
+        // nor does it need one to be named
+        let mut account_account =
+            initialized_account(&program_id, &crate::native_mint::id(), &owner_key, 42);
+        Account::pack(account, &mut account_account.data).unwrap();
+        account_account.lamports += 42;
+        let mut destination_account = SolanaAccount::default();
+        do_process_instruction(
+            close_account(&program_id, &account_key, &destination_key, &owner_key, &[]).unwrap(),
+            vec![
+                &mut account_account,
+                &mut destination_account,
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(destination_account.lamports, account_minimum_balance() + 42);
     }
 
+    #[test]
+    fn test_program_config() {
+        let program_id = crate::id();
+        let admin_key = Pubkey::new_unique();
+        let guardian_key = Pubkey::new_unique();
+        let protocol_fee_owner = Pubkey::new_unique();
+        let upgrade_authority_key = Pubkey::new_unique();
+        let program_data_account = |authority: &Pubkey| {
+            let mut data = vec![3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+            data.extend_from_slice(authority.as_ref());
+            let mut account = SolanaAccount::new(42, data.len(), &bpf_loader_upgradeable::id());
+            account.data = data;
+            account
+        };
+        let initialize_ix = || {
+            initialize_program_config(
+                &program_id,
+                &admin_key,
+                &guardian_key,
+                &protocol_fee_owner,
+                &Pubkey::new_unique(),
+                &upgrade_authority_key,
+                100,
+            )
+            .unwrap()
+        };
+        let initialize_accounts = |program_data: SolanaAccount| {
+            vec![
+                SolanaAccount::new(
+                    Rent::default().minimum_balance(ProgramConfig::get_packed_len()),
+                    ProgramConfig::get_packed_len(),
+                    &program_id,
+                ),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                program_data,
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                rent_sysvar(),
+            ]
+        };
 
+        // not the upgrade authority
+        let mut accounts = initialize_accounts(program_data_account(&Pubkey::new_unique()));
+        assert_eq!(
+            Err(TokenError::IncorrectUpgradeAuthority.into()),
+            do_process_instruction(initialize_ix(), accounts.iter_mut().collect())
+        );
 
-    ///  Create init portfolio
-    pub fn process_initialize_portfolio(
-        program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        metaDataUrl : Vec<u8>,
-        metaDataHash : u16,
-        amountAsset1 : u8,
-        periodAsset1 : u8,
-        amountAsset2 : u8,
-        periodAsset2 : u8,
-        amountAsset3 : u8,
-        periodAsset3 : u8,
-        amountAsset4 : u8,
-        periodAsset4 : u8,
-        amountAsset5 : u8,
-        periodAsset5 : u8,
-        amountAsset6 : u8,
-        periodAsset6 : u8,
-        amountAsset7 : u8,
-        periodAsset7 : u8,
-        amountAsset8 : u8,
-        periodAsset8 : u8,
-        amountAsset9 : u8,
-        periodAsset9 : u8
-        //,
-        // amountAsset10 : u8,
-        // periodAsset10 : u8
-    ) -> ProgramResult {
-        let accounts_iter = &mut accounts.iter();
-        let portfolioAccount = next_account_info(accounts_iter)?;
-        let creatorPortfolio = next_account_info(accounts_iter)?;
-        let addressAsset1 = next_account_info(accounts_iter)?;
-        let assetToSoldIntoAsset1 = next_account_info(accounts_iter)?;
-        let addressAsset2 = next_account_info(accounts_iter)?;
-        let assetToSoldIntoAsset2 = next_account_info(accounts_iter)?;
-        let addressAsset3 = next_account_info(accounts_iter)?;
-        let assetToSoldIntoAsset3 = next_account_info(accounts_iter)?;
-        let addressAsset4 = next_account_info(accounts_iter)?;
-        let assetToSoldIntoAsset4 = next_account_info(accounts_iter)?;
-        let addressAsset5 = next_account_info(accounts_iter)?;
-        let assetToSoldIntoAsset5 = next_account_info(accounts_iter)?;
-        let addressAsset6 = next_account_info(accounts_iter)?;
-        let assetToSoldIntoAsset6 = next_account_info(accounts_iter)?;
-        let addressAsset7 = next_account_info(accounts_iter)?;
-        let assetToSoldIntoAsset7 = next_account_info(accounts_iter)?;
-        let addressAsset8 = next_account_info(accounts_iter)?;
-        let assetToSoldIntoAsset8 = next_account_info(accounts_iter)?;
-        let addressAsset9 = next_account_info(accounts_iter)?;
-        let assetToSoldIntoAsset9 = next_account_info(accounts_iter)?;
-        let owner = next_account_info(accounts_iter)?;
-    
-     
-       
-        msg!("initialze portfolio account : {:?} ",portfolioAccount );
-        /*for data_url in &metaDataUrl {
-            msg!("metadataURL : {:?} ",data_url );
-        }*/
-     
-       
-     //   msg!("initialze portfolio account data : {:?} ",account.data );
+        // upgrade authority did not sign
+        let mut accounts = initialize_accounts(program_data_account(&upgrade_authority_key));
+        let mut ix = initialize_ix();
+        ix.accounts[6].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
 
+        // program data of another program
+        let mut accounts = initialize_accounts(program_data_account(&upgrade_authority_key));
+        let mut ix = initialize_ix();
+        ix.accounts[5].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
 
+        // success
+        let mut accounts = initialize_accounts(program_data_account(&upgrade_authority_key));
+        do_process_instruction(initialize_ix(), accounts.iter_mut().collect()).unwrap();
+        let (_, bump) = find_program_config_with_program_id(&program_id);
+        assert_eq!(
+            ProgramConfig::unpack(&accounts[0].data).unwrap(),
+            ProgramConfig {
+                is_initialized: true,
+                admin: admin_key,
+                guardian: guardian_key,
+                paused: 0,
+                protocol_fee_bps: 100,
+                protocol_fee_owner,
+                bump,
+                withdraw_delay_slots: 0,
+                bypass_withdraw_delay: false,
+            }
+        );
+        let mut program_config_account = accounts.swap_remove(0);
 
-        let mut new_portfolio = Portfolio::unpack(&mut portfolioAccount.data.borrow())?;
+        // already initialized
+        let mut accounts = initialize_accounts(program_data_account(&upgrade_authority_key));
+        accounts[0] = program_config_account.clone();
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(initialize_ix(), accounts.iter_mut().collect())
+        );
 
-        if new_portfolio.is_initialize == 1 {
-            return Err(TokenError::AlreadyInUse.into());
+        // update by someone other than the admin
+        let new_admin_key = Pubkey::new_unique();
+        let set_ix = |admin: &Pubkey, protocol_fee_bps| {
+            set_program_config(
+                &program_id,
+                &new_admin_key,
+                &guardian_key,
+                &protocol_fee_owner,
+                admin,
+                &[],
+                protocol_fee_bps,
+            )
+            .unwrap()
+        };
+        let mut new_admin_account = SolanaAccount::default();
+        let mut new_guardian_account = SolanaAccount::default();
+        let mut new_protocol_fee_owner_account = SolanaAccount::default();
+        let mut admin_account = SolanaAccount::default();
+        macro_rules! set_accounts {
+            () => {
+                vec![
+                    &mut program_config_account,
+                    &mut new_admin_account,
+                    &mut new_guardian_account,
+                    &mut new_protocol_fee_owner_account,
+                    &mut admin_account,
+                ]
+            };
         }
-       // msg!("initialze portfolio account isinitilized : {:?} ",new_portfolio.is_initialize );
-        new_portfolio.is_initialize = 1 ;
-        new_portfolio.portfolio_account = *portfolioAccount.key;
-        new_portfolio.creator_portfolio = *creatorPortfolio.key;
-        new_portfolio.metadataUrl = metaDataUrl;
-        new_portfolio.metadataHash = metaDataHash;
-        new_portfolio.amountAsset1 = amountAsset1;
-        new_portfolio.addressAsset1 = *addressAsset1.key;
-        new_portfolio.periodAsset1 = periodAsset1;
-        new_portfolio.assetToSoldIntoAsset1 = *assetToSoldIntoAsset1.key; 
-        new_portfolio.amountAsset2 = amountAsset2;
-        new_portfolio.addressAsset2 = *addressAsset2.key;
-        new_portfolio.periodAsset2 = periodAsset2;
-        new_portfolio.assetToSoldIntoAsset2 = *assetToSoldIntoAsset2.key; 
-        new_portfolio.amountAsset3 = amountAsset3;
-        new_portfolio.addressAsset3 = *addressAsset3.key;
-        new_portfolio.periodAsset3 = periodAsset3;
-        new_portfolio.assetToSoldIntoAsset3 = *assetToSoldIntoAsset3.key; 
-        new_portfolio.amountAsset4 = amountAsset4;
-        new_portfolio.addressAsset4 = *addressAsset4.key;
-        new_portfolio.periodAsset4 = periodAsset4;
-        new_portfolio.assetToSoldIntoAsset4 = *assetToSoldIntoAsset4.key; 
-        new_portfolio.amountAsset5 = amountAsset5;
-        new_portfolio.addressAsset5 = *addressAsset5.key;
-        new_portfolio.periodAsset5 = periodAsset5;
-        new_portfolio.assetToSoldIntoAsset5 = *assetToSoldIntoAsset5.key;
-        new_portfolio.amountAsset6 = amountAsset6;
-        new_portfolio.addressAsset6 = *addressAsset6.key;
-        new_portfolio.periodAsset6 = periodAsset6;
-        new_portfolio.assetToSoldIntoAsset6 = *assetToSoldIntoAsset6.key ;
-        new_portfolio.amountAsset7 = amountAsset7;
-        new_portfolio.addressAsset7 = *addressAsset7.key;
-        new_portfolio.periodAsset7 = periodAsset7;
-        new_portfolio.assetToSoldIntoAsset7 = *assetToSoldIntoAsset7.key;
-        new_portfolio.amountAsset8 = amountAsset8;
-        new_portfolio.addressAsset8 = *addressAsset8.key;
-        new_portfolio.periodAsset8 = periodAsset8;
-        new_portfolio.assetToSoldIntoAsset8 = *assetToSoldIntoAsset8.key;
-        new_portfolio.amountAsset9 = amountAsset9;
-        new_portfolio.addressAsset9 = *addressAsset9.key;
-        new_portfolio.periodAsset9 = periodAsset9;
-        new_portfolio.assetToSoldIntoAsset9 = *assetToSoldIntoAsset9.key;
- 
-        msg!("initialze portfolio account isinitilized after  : {:?} ",new_portfolio.is_initialize );
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_ix(&guardian_key, 200),
+                set_accounts!(),
+            )
+        );
 
-        Portfolio::pack(new_portfolio, &mut portfolioAccount.data.borrow_mut())?;
-        msg!("address asset 1 {:?}  ", *addressAsset1.key ,);
-        msg!(" ******* creatorAccount portfolio_account {:?} , creator_portfolio : {:?}  ",*portfolioAccount.key , *creatorPortfolio.key );
-        msg!(" after unpack initialze portfolio account : {:?} ",portfolioAccount );
-       // msg!("after unpack initialze portfolio account date : {:?} ",account.data );
-        Ok(())
+        // fee above 100%
+        assert_eq!(
+            Err(TokenError::InvalidBasisPoints.into()),
+            do_process_instruction(
+                set_ix(&admin_key, 10_001),
+                set_accounts!(),
+            )
+        );
 
-    }
-    /// Deposit nAsset
-    pub fn process_deposit(
-        program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        amount: u64,
-        volatility: u64,
-        nonce: u8,
-    ) -> ProgramResult {
-       
-        let accounts_iter = &mut accounts.iter();
+        // update by the admin
+        do_process_instruction(
+            set_ix(&admin_key, 200),
+            set_accounts!(),
+        )
+        .unwrap();
+        let program_config = ProgramConfig::unpack(&program_config_account.data).unwrap();
+        assert_eq!(program_config.admin, new_admin_key);
+        assert_eq!(program_config.protocol_fee_bps, 200);
 
-        let swap_info = next_account_info(accounts_iter)?;
-        let owner = next_account_info(accounts_iter)?;
-        let account = next_account_info(accounts_iter)?;
-        let source_info = next_account_info(accounts_iter)?;
-        let swap_source_info = next_account_info(accounts_iter)?;
-        let swap_destination_info = next_account_info(accounts_iter)?;
-        let destination_info = next_account_info(accounts_iter)?;
-        let pool_mint_info = next_account_info(accounts_iter)?;
-        let pool_fee_account_info = next_account_info(accounts_iter)?;
-        let token_program_info = next_account_info(accounts_iter)?;
-        let host_fee_account=next_account_info(accounts_iter)?;
-	    let prog_address = next_account_info(accounts_iter)?;
-        msg!("prog_address is {}" , prog_address.key);
-       
-        let program = next_account_info(accounts_iter)?;
-        msg!("program is {}" , program.key);
- 
-        //let expected_allocated_key =Pubkey::create_program_address(&[b"Zou Zou",b"Silvester Stalone"], program_id)?;
-        let swap_bytes = swap_info.key.to_bytes();
-        let authority_signature_seeds = [&swap_bytes[..32], &[nonce]];
-        let signers = &[&authority_signature_seeds[..]];
-        msg!("swap info is {}",swap_info.key);
-        let mut buf = Vec::new();
-        let instruction:u8 = 1;
-        let amount_in:u64 = amount;
-        let minimum_amount_out:u64=0;
-
-        
-        let mut vac_accounts = Vec::new();
-        buf.push(instruction);
-        buf.extend_from_slice(&amount_in.to_le_bytes());
-        buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
-        vac_accounts.push(AccountMeta::new(*swap_info.key, true));
-        vac_accounts.push(AccountMeta::new(*owner.key, false));
-        vac_accounts.push(AccountMeta::new(*account.key, true));
-        vac_accounts.push(AccountMeta::new(*source_info.key, false));
-        vac_accounts.push(AccountMeta::new(*swap_source_info.key, false));
-        vac_accounts.push(AccountMeta::new(*swap_destination_info.key, false));
-        vac_accounts.push(AccountMeta::new(*destination_info.key, false));
-        vac_accounts.push(AccountMeta::new(*pool_mint_info.key, false));
-        vac_accounts.push(AccountMeta::new(*pool_fee_account_info.key, false));
-        vac_accounts.push(AccountMeta::new(*token_program_info.key, false));
-        vac_accounts.push(AccountMeta::new(*host_fee_account.key,false));
-        /*let ix = Instruction {
-            accounts:vac_accounts,
-            program_id: *program.key,
-            data: buf,
-       };
-       let result = invoke_signed(&ix, 
-        &[account.clone(), prog_address.clone() , program.clone()],
-        signers
-        )? ;
-      
-       msg!("result was  =  {:?}  " , result );
-       */
-       msg!("here before ");
-     /* let mut source_account = Account::unpack(&mut source_info.data.borrow())?;
-        if source_account.is_frozen() {
-            return Err(TokenError::AccountFrozen.into());
-        }
-       msg!("here after ");
-       msg!("source account is {}",  source_account.amount);*/
-/*
-       Self::validate_owner(
-            program_id,
-            &source_account.owner,
-            owner,
-            accounts_iter.as_slice(),
-        )?;
-        msg!("amount is  {}",  source_account.amount);
+        // guardian pauses withdrawals
+        let mut authority_account = SolanaAccount::default();
+        do_process_instruction(
+            set_pause(&program_id, &guardian_key, &[], PAUSE_WITHDRAW).unwrap(),
+            vec![&mut program_config_account, &mut authority_account],
+        )
+        .unwrap();
+        assert_eq!(
+            ProgramConfig::unpack(&program_config_account.data).unwrap().paused,
+            PAUSE_WITHDRAW
+        );
+
+        // guardian cannot resume them
+        assert_eq!(
+            Err(TokenError::GuardianCannotUnpause.into()),
+            do_process_instruction(
+                set_pause(&program_id, &guardian_key, &[], PAUSE_DEPOSIT).unwrap(),
+                vec![&mut program_config_account, &mut authority_account],
+            )
+        );
+
+        // stranger cannot pause
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_pause(&program_id, &Pubkey::new_unique(), &[], PAUSE_DEPOSIT).unwrap(),
+                vec![&mut program_config_account, &mut authority_account],
+            )
+        );
 
-    
-          source_account.amount = source_account
-            .amount
-            .checked_add(amount)
-            .ok_or(TokenError::Overflow)?;
+        // withdrawals are paused
+        let fixture = HedgeFixture::with_program_id(program_id);
+        let owner_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let mut account_account =
+            initialized_account(&program_id, &fixture.mint_key, &owner_key, 100);
+        let mut position = position_account(&program_id, &account_key, 100, 0);
+        let mut take_profit_account = SolanaAccount::default();
+        let mut hedge_config_account = fixture.hedge_config_account(5_000, 0);
+        let mut swap_account = fixture.swap_account.clone();
+        let mut asset_reserve_account =
+            token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.swap_authority);
+        let mut stable_reserve_account =
+            token_account(&fixture.token_program_id, &fixture.stable_mint, &fixture.swap_authority);
+        let mut withdraw_ticket_account = SolanaAccount::default();
+        let mut payout_accounts = fixture.withdraw_payout_accounts(100);
+        let mut clock_account = create_account_for_test(&Clock {
+            slot: 100,
+            ..Clock::default()
+        });
+        let mut owner_account = SolanaAccount::default();
+        let withdraw_ix = || {
+            fixture.withdraw_ix(&account_key, &fixture.swap_state.token_a, &owner_key, 10)
+        };
+        let mut accounts = vec![
+            &mut program_config_account,
+            &mut account_account,
+            &mut position,
+            &mut take_profit_account,
+            &mut hedge_config_account,
+            &mut swap_account,
+            &mut asset_reserve_account,
+            &mut stable_reserve_account,
+            &mut withdraw_ticket_account,
+        ];
+        accounts.extend(payout_accounts.iter_mut());
+        accounts.push(&mut clock_account);
+        accounts.push(&mut owner_account);
+        assert_eq!(
+            Err(TokenError::Paused.into()),
+            do_process_instruction(withdraw_ix(), accounts)
+        );
 
-        source_account.usdc = source_account
-            .usdc
-            .checked_add(400)
-            .ok_or(TokenError::Overflow)?;
+        // admin resumes them
+        do_process_instruction(
+            set_pause(&program_id, &new_admin_key, &[], 0).unwrap(),
+            vec![&mut program_config_account, &mut authority_account],
+        )
+        .unwrap();
+        let mut accounts = vec![
+            &mut program_config_account,
+            &mut account_account,
+            &mut position,
+            &mut take_profit_account,
+            &mut hedge_config_account,
+            &mut swap_account,
+            &mut asset_reserve_account,
+            &mut stable_reserve_account,
+            &mut withdraw_ticket_account,
+        ];
+        accounts.extend(payout_accounts.iter_mut());
+        accounts.push(&mut clock_account);
+        accounts.push(&mut owner_account);
+        do_process_instruction(withdraw_ix(), accounts).unwrap();
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 90);
+        assert_eq!(Position::unpack(&position.data).unwrap().asset, 90);
+    }
 
+    #[test]
+    fn test_deposit_limits() {
+        let program_id = crate::id();
+        let mint_key = Pubkey::new_unique();
+        let mint_authority = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        do_process_instruction(
+            initialize_mint(&program_id, &mint_key, &mint_authority, None, 2).unwrap(),
+            vec![&mut mint_account, &mut rent_sysvar()],
+        )
+        .unwrap();
+        let (deposit_limits_key, _) = find_deposit_limits_with_program_id(&program_id, &mint_key);
+        let set_limits_accounts = |target_account: &SolanaAccount| {
+            vec![
+                SolanaAccount::new(
+                    Rent::default().minimum_balance(DepositLimits::get_packed_len()),
+                    DepositLimits::get_packed_len(),
+                    &program_id,
+                ),
+                target_account.clone(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                rent_sysvar(),
+                SolanaAccount::default(),
+            ]
+        };
 
-        source_account.asset = source_account
-            .asset
-            .checked_add(5000)
-            .ok_or(TokenError::Overflow)?;
-*/
+        // not the mint authority
+        let mut accounts = set_limits_accounts(&mint_account);
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_deposit_limits(
+                    &program_id,
+                    &mint_key,
+                    &Pubkey::new_unique(),
+                    &Pubkey::new_unique(),
+                    &[],
+                    1_000,
+                    0,
+                    0
+                )
+                .unwrap(),
+                accounts.iter_mut().collect()
+            )
+        );
 
-     // Account::pack(source_account, &mut account.data.borrow_mut())?;
-        Ok(())
-    }
+        // success
+        let mut accounts = set_limits_accounts(&mint_account);
+        do_process_instruction(
+            set_deposit_limits(
+                &program_id,
+                &mint_key,
+                &Pubkey::new_unique(),
+                &mint_authority,
+                &[],
+                1_000,
+                0,
+                0,
+            )
+            .unwrap(),
+            accounts.iter_mut().collect(),
+        )
+        .unwrap();
+        let mut deposit_limits_account = accounts.swap_remove(0);
+        let deposit_limits = DepositLimits::unpack(&deposit_limits_account.data).unwrap();
+        assert_eq!(deposit_limits.target, mint_key);
+        assert_eq!(deposit_limits.max_supply, 1_000);
+
+        // minting past the supply cap
+        let account_key = Pubkey::new_unique();
+        let mut account_account =
+            SolanaAccount::new(account_minimum_balance(), Account::get_packed_len(), &program_id);
+        let mut owner_account = SolanaAccount::default();
+        do_process_instruction(
+            initialize_account(&program_id, &account_key, &mint_key, &mint_authority).unwrap(),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut owner_account,
+                &mut rent_sysvar(),
+            ],
+        )
+        .unwrap();
+        let mint_to_ix = |amount| {
+            let mut ix =
+                mint_to(&program_id, &mint_key, &account_key, &mint_authority, &[], amount)
+                    .unwrap();
+            ix.accounts
+                .push(AccountMeta::new_readonly(deposit_limits_key, false));
+            ix
+        };
+        do_process_instruction(
+            mint_to_ix(1_000),
+            vec![
+                &mut mint_account,
+                &mut account_account,
+                &mut owner_account,
+                &mut deposit_limits_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            Err(TokenError::SupplyCapExceeded.into()),
+            do_process_instruction(
+                mint_to_ix(1),
+                vec![
+                    &mut mint_account,
+                    &mut account_account,
+                    &mut owner_account,
+                    &mut deposit_limits_account,
+                ],
+            )
+        );
 
+        // the supply cap of a hedged mint cannot be left out
+        let (hedge_config_key, _) = find_hedge_config_with_program_id(&program_id, &mint_key);
+        let mut hedge_config = hedge_config_account(
+            &program_id,
+            HedgeConfig {
+                mint: mint_key,
+                asset_scale: HEDGE_PRECISION,
+                ..HedgeConfig::default()
+            },
+        );
+        let mut ix =
+            mint_to(&program_id, &mint_key, &account_key, &mint_authority, &[], 1).unwrap();
+        ix.accounts
+            .push(AccountMeta::new_readonly(hedge_config_key, false));
+        assert_eq!(
+            Err(TokenError::DepositLimitsRequired.into()),
+            do_process_instruction(
+                ix,
+                vec![
+                    &mut mint_account,
+                    &mut account_account,
+                    &mut owner_account,
+                    &mut hedge_config,
+                ],
+            )
+        );
 
-/*
-    pub fn process_deposit(
-        program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        amount: u64,
-        volatility: u64,
-        nonce: u8,
+        // deposit record
+        let depositor_key = Pubkey::new_unique();
+        let initialize_record_ix = || {
+            initialize_deposit_record(&program_id, &mint_key, &depositor_key, &Pubkey::new_unique())
+                .unwrap()
+        };
+        let mut record_account = SolanaAccount::new(
+            Rent::default().minimum_balance(DepositRecord::get_packed_len()),
+            DepositRecord::get_packed_len(),
+            &program_id,
+        );
+        let mut payer_account = SolanaAccount::default();
+        let mut system_account = SolanaAccount::default();
+        let mut depositor_account = SolanaAccount::default();
+        do_process_instruction(
+            initialize_record_ix(),
+            vec![
+                &mut record_account,
+                &mut mint_account,
+                &mut depositor_account,
+                &mut payer_account,
+                &mut system_account,
+                &mut rent_sysvar(),
+            ],
+        )
+        .unwrap();
+        let (_, bump) = find_deposit_record_with_program_id(&program_id, &mint_key, &depositor_key);
+        assert_eq!(
+            DepositRecord::unpack(&record_account.data).unwrap(),
+            DepositRecord {
+                is_initialized: true,
+                target: mint_key,
+                owner: depositor_key,
+                deposited: 0,
+                bump,
+            }
+        );
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(
+                initialize_record_ix(),
+                vec![
+                    &mut record_account,
+                    &mut mint_account,
+                    &mut depositor_account,
+                    &mut payer_account,
+                    &mut system_account,
+                    &mut rent_sysvar(),
+                ],
+            )
+        );
+
+        // portfolio: only its creator sets the limits
+        let portfolio_key = Pubkey::new_unique();
+        let creator_key = Pubkey::new_unique();
+        let mut portfolio_account =
+            SolanaAccount::new(42, Portfolio::get_packed_len(), &program_id);
+        Portfolio::pack(
+            Portfolio {
+                portfolio_account: portfolio_key,
+                creator_portfolio: creator_key,
+                is_initialize: 1,
+                ..Portfolio::default()
+            },
+            &mut portfolio_account.data,
+        )
+        .unwrap();
+        let mut accounts = set_limits_accounts(&portfolio_account);
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(
+                set_deposit_limits(
+                    &program_id,
+                    &portfolio_key,
+                    &Pubkey::new_unique(),
+                    &mint_authority,
+                    &[],
+                    0,
+                    0,
+                    0
+                )
+                .unwrap(),
+                accounts.iter_mut().collect()
+            )
+        );
+        do_process_instruction(
+            set_deposit_limits(
+                &program_id,
+                &portfolio_key,
+                &Pubkey::new_unique(),
+                &creator_key,
+                &[],
+                300,
+                200,
+                250,
+            )
+            .unwrap(),
+            accounts.iter_mut().collect(),
+        )
+        .unwrap();
+        let portfolio_limits_account = accounts.swap_remove(0);
 
+        // joining the portfolio counts what the user portfolio holds against
+        // its caps, not the delegated amount
+        let user_key = Pubkey::new_unique();
+        let user_portfolio_key = Pubkey::new_unique();
+        let mut asset_keys: Vec<Pubkey> = (0..9).map(|_| Pubkey::new_unique()).collect();
+        asset_keys[8] = asset_keys[0];
+        let join_ix = || {
+            create_Init_User_Portfolio(
+                &program_id,
+                &user_portfolio_key,
+                &portfolio_key,
+                &user_key,
+                &Pubkey::new_unique(),
+                &asset_keys[0],
+                &asset_keys[1],
+                &asset_keys[2],
+                &asset_keys[3],
+                &asset_keys[4],
+                &asset_keys[5],
+                &asset_keys[6],
+                &asset_keys[7],
+                &asset_keys[8],
+                &1_000,
+            )
+            .unwrap()
+        };
+        let join_accounts = |limits_account: &SolanaAccount, held: u64, deposited| {
+            let mut accounts = vec![SolanaAccount::default(); 4];
+            accounts[0] = SolanaAccount::new(42, UserPortfolio::get_packed_len(), &program_id);
+            let asset_mint = Pubkey::new_unique();
+            accounts.push(initialized_account(
+                &program_id,
+                &asset_mint,
+                &user_portfolio_key,
+                held - 20,
+            ));
+            accounts.push(initialized_account(
+                &program_id,
+                &asset_mint,
+                &user_portfolio_key,
+                20,
+            ));
+            for _ in 2..8 {
+                accounts.push(initialized_account(
+                    &program_id,
+                    &asset_mint,
+                    &user_portfolio_key,
+                    0,
+                ));
+            }
+            // the first asset account again, counted once
+            accounts.push(accounts[4].clone());
+            accounts.push(limits_account.clone());
+            accounts.push(deposit_record_account(
+                &program_id,
+                &portfolio_key,
+                &user_key,
+                deposited,
+            ));
+            accounts.push(program_config_account(&program_id, ProgramConfig::default()));
+            accounts
+        };
+        let mut accounts = join_accounts(&portfolio_limits_account, 200, 0);
+        do_process_instruction(join_ix(), accounts.iter_mut().collect()).unwrap();
+        let user_portfolio = UserPortfolio::unpack(&accounts[0].data).unwrap();
+        assert_eq!(user_portfolio.splu_asset9, asset_keys[8]);
+        assert_eq!(user_portfolio.delegated_amount, 1_000);
+        assert_eq!(DepositLimits::unpack(&accounts[13].data).unwrap().supply, 200);
+        assert_eq!(DepositRecord::unpack(&accounts[14].data).unwrap().deposited, 200);
+
+        let mut accounts = join_accounts(&portfolio_limits_account, 201, 0);
+        assert_eq!(
+            Err(TokenError::DepositCapExceeded.into()),
+            do_process_instruction(join_ix(), accounts.iter_mut().collect())
+        );
+        let mut accounts = join_accounts(&portfolio_limits_account, 151, 100);
+        assert_eq!(
+            Err(TokenError::OwnerDepositCapExceeded.into()),
+            do_process_instruction(join_ix(), accounts.iter_mut().collect())
+        );
+        let mut accounts = join_accounts(&portfolio_limits_account, 151, 0);
+        let mut deposit_limits = DepositLimits::unpack(&accounts[13].data).unwrap();
+        deposit_limits.supply = 150;
+        DepositLimits::pack(deposit_limits, &mut accounts[13].data).unwrap();
+        assert_eq!(
+            Err(TokenError::SupplyCapExceeded.into()),
+            do_process_instruction(join_ix(), accounts.iter_mut().collect())
+        );
 
+        // asset account of someone else
+        let mut accounts = join_accounts(&portfolio_limits_account, 100, 0);
+        let mut asset_account = Account::unpack(&accounts[5].data).unwrap();
+        asset_account.owner = user_key;
+        Account::pack(asset_account, &mut accounts[5].data).unwrap();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(join_ix(), accounts.iter_mut().collect())
+        );
 
-    ) -> ProgramResult {
+        // a portfolio without limits needs no deposit record
+        let mut accounts = join_accounts(&SolanaAccount::default(), 1_000, 0);
+        accounts[14] = SolanaAccount::default();
+        do_process_instruction(join_ix(), accounts.iter_mut().collect()).unwrap();
+        assert_eq!(
+            UserPortfolio::unpack(&accounts[0].data).unwrap().portfolio_address,
+            portfolio_key
+        );
 
-        let accounts_iter = &mut accounts.iter();
+        // deposits are paused
+        let mut accounts = join_accounts(&portfolio_limits_account, 100, 0);
+        accounts[15] = program_config_account(
+            &program_id,
+            ProgramConfig {
+                paused: PAUSE_DEPOSIT,
+                ..ProgramConfig::default()
+            },
+        );
+        assert_eq!(
+            Err(TokenError::Paused.into()),
+            do_process_instruction(join_ix(), accounts.iter_mut().collect())
+        );
 
-        let swap_info = next_account_info(accounts_iter)?;
-        let owner = next_account_info(accounts_iter)?;
-        let account = next_account_info(accounts_iter)?;
-        let source_info = next_account_info(accounts_iter)?;
-        let swap_source_info = next_account_info(accounts_iter)?;
-        let swap_destination_info = next_account_info(accounts_iter)?;
-        let destination_info = next_account_info(accounts_iter)?;
-        let pool_mint_info = next_account_info(accounts_iter)?;
-        let pool_fee_account_info = next_account_info(accounts_iter)?;
-        let token_program_info = next_account_info(accounts_iter)?;
-        let host_fee_account=next_account_info(accounts_iter)?;
-	    let prog_address = next_account_info(accounts_iter)?;
-        msg!("prog_address issssss {}" , prog_address.key);
-        msg!("0");
-        let program = next_account_info(accounts_iter)?;
-        msg!("program is {}" , program.key);
+        // owner did not sign
+        let mut accounts = join_accounts(&portfolio_limits_account, 100, 0);
+        let mut ix = join_ix();
+        ix.accounts[2].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
+    }
 
-       // let expected_allocated_key =Pubkey::create_program_address(&[b"Zouaoui karimaaaaaaaaaaaaaaaaaaaaaaaa",b"Silvester Stalone"], program_id)?;
+    #[test]
+    fn test_asset_registry() {
+        let fixture = HedgeFixture::new();
+        let program_id = fixture.program_id;
+        let admin_key = Pubkey::new_unique();
+        let oracle_key = Pubkey::new_unique();
+        let program_config = ProgramConfig {
+            admin: admin_key,
+            ..ProgramConfig::default()
+        };
+        let mut mint_account = SolanaAccount::new(
+            42,
+            crate::swap::TOKEN_MINT_LEN,
+            &fixture.token_program_id,
+        );
+        mint_account.data[44] = 9;
+        mint_account.data[45] = 1;
+        let register_ix = |admin: &Pubkey| {
+            register_asset(
+                &program_id,
+                &fixture.asset_mint,
+                &oracle_key,
+                &fixture.swap_key,
+                &Pubkey::new_unique(),
+                admin,
+                &[],
+                AssetKind::Asset,
+            )
+            .unwrap()
+        };
+        let register_accounts = || {
+            vec![
+                program_config_account(&program_id, program_config),
+                SolanaAccount::new(
+                    Rent::default().minimum_balance(AssetEntry::get_packed_len()),
+                    AssetEntry::get_packed_len(),
+                    &program_id,
+                ),
+                mint_account.clone(),
+                SolanaAccount::default(),
+                fixture.swap_account.clone(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                rent_sysvar(),
+                SolanaAccount::default(),
+            ]
+        };
 
-       let mut buf = Vec::new();
-       let instruction:u8 = 1;
-       let amountIn:u64 = amount;
-       let minimumAmountOut:u64=0;
+        // not the admin
+        let mut accounts = register_accounts();
+        assert_eq!(
+            Err(TokenError::OwnerMismatch.into()),
+            do_process_instruction(register_ix(&Pubkey::new_unique()), accounts.iter_mut().collect())
+        );
 
-       msg!("1");
+        // mint not traded by the pool
+        let mut accounts = register_accounts();
+        let mut ix = register_ix(&admin_key);
+        let (asset_entry_key, _) = find_asset_entry_with_program_id(&program_id, &fixture.mint_key);
+        ix.accounts[1].pubkey = asset_entry_key;
+        ix.accounts[2].pubkey = fixture.mint_key;
+        assert_eq!(
+            Err(TokenError::AssetMintMismatch.into()),
+            do_process_instruction(ix, accounts.iter_mut().collect())
+        );
 
-       let mut vacAccounts = Vec::new();
-       buf.push(instruction);
-       buf.extend_from_slice(&amountIn.to_le_bytes());
-       buf.extend_from_slice(&minimumAmountOut.to_le_bytes());
-       msg!("2");
-       vacAccounts.push(AccountMeta::new(*swap_info.key, false));
-       vacAccounts.push(AccountMeta::new(*owner.key, false));
-       vacAccounts.push(AccountMeta::new(*account.key, false));
-       vacAccounts.push(AccountMeta::new(*source_info.key, false));
-       vacAccounts.push(AccountMeta::new(*swap_source_info.key, false));
-       vacAccounts.push(AccountMeta::new(*swap_destination_info.key, false));
-       vacAccounts.push(AccountMeta::new(*destination_info.key, false));
-       vacAccounts.push(AccountMeta::new(*pool_mint_info.key, false));
-       vacAccounts.push(AccountMeta::new(*pool_fee_account_info.key, false));
-       vacAccounts.push(AccountMeta::new(*token_program_info.key, false));
-       vacAccounts.push(AccountMeta::new(*host_fee_account.key,false));
-       msg!("3");
-       let ix = Instruction {
-           accounts:vacAccounts,
-           program_id: *program.key,
-           data: buf,
-       };
-     /*  let result = invoke_signed(&ix, 
-       &[account.clone(), prog_address.clone() , program.clone()],
-       &[&[b"Mohamed zouaouii2",b"Silvester Stalone"]]
-       )?;*/
-       msg!("4 {}",account.key);
+        // mint of another token program
+        let mut accounts = register_accounts();
+        accounts[2].owner = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(register_ix(&admin_key), accounts.iter_mut().collect())
+        );
 
-       let mut source_account = Account::unpack(&mut account.data.borrow())?;
+        // success
+        let mut accounts = register_accounts();
+        do_process_instruction(register_ix(&admin_key), accounts.iter_mut().collect()).unwrap();
+        let (_, bump) = find_asset_entry_with_program_id(&program_id, &fixture.asset_mint);
+        assert_eq!(
+            AssetEntry::unpack(&accounts[1].data).unwrap(),
+            AssetEntry {
+                is_initialized: true,
+                mint: fixture.asset_mint,
+                kind: AssetKind::Asset,
+                decimals: 9,
+                oracle: oracle_key,
+                swap_program_id: fixture.swap_program_id,
+                swap_pool: fixture.swap_key,
+                bump,
+            }
+        );
 
-       msg!("5");
+        // already registered
+        let mut asset_entry_account = accounts.swap_remove(1);
+        let mut accounts = register_accounts();
+        accounts[1] = asset_entry_account.clone();
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(register_ix(&admin_key), accounts.iter_mut().collect())
+        );
+
+        // update
+        let mut program_config_account = program_config_account(&program_id, program_config);
+        let mut oracle_account = SolanaAccount::default();
+        let mut swap_account = fixture.swap_account.clone();
+        let mut admin_account = SolanaAccount::default();
+        let new_oracle_key = Pubkey::new_unique();
+        do_process_instruction(
+            update_asset(
+                &program_id,
+                &fixture.asset_mint,
+                &new_oracle_key,
+                &fixture.swap_key,
+                &admin_key,
+                &[],
+                AssetKind::Stablecoin,
+            )
+            .unwrap(),
+            vec![
+                &mut program_config_account,
+                &mut asset_entry_account,
+                &mut oracle_account,
+                &mut swap_account,
+                &mut admin_account,
+            ],
+        )
+        .unwrap();
+        let asset_entry = AssetEntry::unpack(&asset_entry_account.data).unwrap();
+        assert_eq!(asset_entry.kind, AssetKind::Stablecoin);
+        assert_eq!(asset_entry.oracle, new_oracle_key);
 
-      Self::validate_owner(
-           program_id,
-           &source_account.owner,
-           owner,
-           accounts_iter.as_slice(),
-       )?;
-       
-   
-         source_account.amount = source_account
-           .amount
-           .checked_add(amount)
-           .ok_or(TokenError::Overflow)?;
-       source_account.usdc = source_account
-           .usdc
-           .checked_add(400)
-           .ok_or(TokenError::Overflow)?;
+        // deregister
+        let mut destination_account = SolanaAccount::default();
+        let lamports = asset_entry_account.lamports;
+        do_process_instruction(
+            deregister_asset(
+                &program_id,
+                &fixture.asset_mint,
+                &Pubkey::new_unique(),
+                &admin_key,
+                &[],
+            )
+            .unwrap(),
+            vec![
+                &mut program_config_account,
+                &mut asset_entry_account,
+                &mut destination_account,
+                &mut admin_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(destination_account.lamports, lamports);
+        assert_eq!(asset_entry_account.lamports, 0);
+        assert!(!AssetEntry::unpack_unchecked(&asset_entry_account.data)
+            .unwrap()
+            .is_initialized);
+    }
 
+    #[test]
+    fn test_basis_points_of() {
+        assert_eq!(basis_points_of(1_000, 30).unwrap(), 3);
+        assert_eq!(basis_points_of(999, 1).unwrap(), 0);
+        assert_eq!(basis_points_of(u64::MAX, BASIS_POINTS).unwrap(), u64::MAX);
+    }
 
-           msg!("5");
+    #[test]
+    fn test_proportional_share() {
+        assert_eq!(proportional_share(495, 99, 990).unwrap(), 49);
+        assert_eq!(proportional_share(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert_eq!(proportional_share(10, 0, 0).unwrap(), 0);
+        assert_eq!(
+            proportional_share(u64::MAX, 2, 1),
+            Err(TokenError::Overflow.into())
+        );
+    }
 
-       source_account.asset = source_account
-           .asset
-           .checked_add(5000)
-           .ok_or(TokenError::Overflow)?;
-     Account::pack(source_account, &mut account.data.borrow_mut())?;
-       Ok(())
-   }
-*/
+    fn initialized_account(program_id: &Pubkey, mint: &Pubkey, owner: &Pubkey, amount: u64) -> SolanaAccount {
+        let mut account =
+            SolanaAccount::new(account_minimum_balance(), Account::get_packed_len(), program_id);
+        Account::pack(
+            Account {
+                mint: *mint,
+                owner: *owner,
+                amount,
+                state: AccountState::Initialized,
+                ..Account::default()
+            },
+            &mut account.data,
+        )
+        .unwrap();
+        account
+    }
 
-   /// withdraw nAsset
-   pub fn process_withdraw(
-    program_id: &Pubkey,
-    accounts: &[AccountInfo],
-    amount: u64,
-) -> ProgramResult {
-  let account_info_iter = &mut accounts.iter();
+    #[test]
+    fn test_state_account_headers() {
+        let position = Position {
+            is_initialized: true,
+            account: Pubkey::new_unique(),
+            asset: 1,
+            usdc: 2,
+            bump: 3,
+            ..Position::default()
+        };
+        let mut packed = vec![0; Position::LEN];
+        Position::pack(position, &mut packed).unwrap();
+        assert_eq!(packed[..ACCOUNT_HEADER_LEN], [AccountType::Position as u8, STATE_VERSION]);
+        assert_eq!(account_type(&packed), Ok(AccountType::Position));
+        assert_eq!(StateAccount::unpack(&packed), Ok(StateAccount::Position(position)));
+
+        // a header of another type
+        packed[0] = AccountType::HedgeConfig as u8;
+        assert_eq!(Position::unpack(&packed), Err(ProgramError::InvalidAccountData));
+
+        // an unknown version
+        packed[0] = AccountType::Position as u8;
+        packed[1] = STATE_VERSION + 1;
+        assert_eq!(account_type(&packed), Err(ProgramError::InvalidAccountData));
+        assert_eq!(Position::unpack(&packed), Err(ProgramError::InvalidAccountData));
+
+        // allocated but never initialized
+        let packed = vec![0; UserPortfolio::LEN];
+        assert_eq!(account_type(&packed), Ok(AccountType::Uninitialized));
+        assert_eq!(
+            StateAccount::unpack(&packed),
+            Err(ProgramError::UninitializedAccount)
+        );
+        assert!(UserPortfolio::unpack_unchecked(&packed).is_ok());
 
-    let account= next_account_info(account_info_iter)?;
+        // unversioned layouts are told apart by length
+        assert_eq!(
+            AccountType::from_unversioned_len(UserPortfolio::LEN - ACCOUNT_HEADER_LEN),
+            Some(AccountType::UserPortfolio)
+        );
+        assert_eq!(
+            AccountType::from_unversioned_len(Portfolio::LEN - ACCOUNT_HEADER_LEN),
+            Some(AccountType::Portfolio)
+        );
+        assert_eq!(AccountType::from_unversioned_len(Account::LEN), None);
+        assert_eq!(
+            AccountType::from_unversioned_len(Position::LEN - ACCOUNT_HEADER_LEN),
+            None
+        );
+    }
 
-    let owner = next_account_info(account_info_iter)?;
+    #[test]
+    fn test_initialize_position() {
+        let program_id = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let payer_key = Pubkey::new_unique();
+        let account_account =
+            initialized_account(&program_id, &Pubkey::new_unique(), &Pubkey::new_unique(), 0);
+        let (position_key, bump) = find_position_with_program_id(&program_id, &account_key);
+        let accounts = || {
+            vec![
+                SolanaAccount::new(
+                    Rent::default().minimum_balance(Position::get_packed_len()),
+                    Position::get_packed_len(),
+                    &program_id,
+                ),
+                account_account.clone(),
+                SolanaAccount::default(),
+                SolanaAccount::default(),
+                rent_sysvar(),
+            ]
+        };
+        let initialize_ix = || initialize_position(&program_id, &account_key, &payer_key).unwrap();
 
-    let mut source_account = Account::unpack(&account.data.borrow())?;
+        // success
+        let mut position_accounts = accounts();
+        do_process_instruction(initialize_ix(), position_accounts.iter_mut().collect()).unwrap();
+        assert_eq!(
+            Position::unpack(&position_accounts[0].data).unwrap(),
+            Position {
+                is_initialized: true,
+                account: account_key,
+                asset: 0,
+                usdc: 0,
+                bump,
+                tracks_cost_basis: true,
+                ..Position::default()
+            }
+        );
 
+        // already initialized
+        assert_eq!(
+            Err(TokenError::AlreadyInUse.into()),
+            do_process_instruction(initialize_ix(), position_accounts.iter_mut().collect())
+        );
 
-   Self::validate_owner(
-        program_id,
-        &source_account.owner,
-        owner,
-        account_info_iter.as_slice(),
-    )?;
+        // address not derived from the account
+        let mut position_accounts = accounts();
+        let mut ix = initialize_ix();
+        ix.accounts[0].pubkey = Pubkey::new_unique();
+        assert_ne!(ix.accounts[0].pubkey, position_key);
+        assert_eq!(
+            Err(ProgramError::InvalidSeeds),
+            do_process_instruction(ix, position_accounts.iter_mut().collect())
+        );
 
-    msg!("{}", amount);
-    msg!("{}" ,source_account.amount);
-     let  value :u64  =  (amount.checked_mul(100)).unwrap().checked_div(source_account.amount.into()).unwrap() ;
-    let  amount_usdc_burned  = source_account.usdc.checked_mul(value).unwrap().checked_div(100).unwrap();
-    let  amount_asset_burned = source_account.asset.checked_mul(value).unwrap().checked_div(100).unwrap();
+        // token account of another program
+        let mut position_accounts = accounts();
+        position_accounts[1].owner = Pubkey::new_unique();
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction(initialize_ix(), position_accounts.iter_mut().collect())
+        );
 
+        // not a token account
+        let mut position_accounts = accounts();
+        position_accounts[1] = SolanaAccount::new(42, Mint::get_packed_len(), &program_id);
+        assert_eq!(
+            Err(ProgramError::InvalidAccountData),
+            do_process_instruction(initialize_ix(), position_accounts.iter_mut().collect())
+        );
+    }
 
-    
-    source_account.amount = source_account
-        .amount
-        .checked_sub(amount)
-        .ok_or(TokenError::Overflow)?;
+    #[test]
+    fn test_transfer_with_positions() {
+        let program_id = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let source_key = Pubkey::new_unique();
+        let destination_key = Pubkey::new_unique();
+        let mut source_account = initialized_account(&program_id, &mint_key, &owner_key, 1_000);
+        let mut destination_account =
+            initialized_account(&program_id, &mint_key, &Pubkey::new_unique(), 0);
+        let mut source_position = position_account(&program_id, &source_key, 500, 1_000);
+        let mut destination_position = position_account(&program_id, &destination_key, 0, 0);
+        let mut owner_account = SolanaAccount::default();
+        let mut take_profit = take_profit_account(
+            &program_id,
+            TakeProfit {
+                mint: mint_key,
+                reward_per_share: 3 * REWARD_PRECISION,
+                ..TakeProfit::default()
+            },
+        );
+        let (hedge_config_key, bump) = find_hedge_config_with_program_id(&program_id, &mint_key);
+        let hedge_config = HedgeConfig {
+            is_initialized: true,
+            mint: mint_key,
+            bump,
+            asset_scale: HEDGE_PRECISION,
+            ..HedgeConfig::default()
+        };
+        let hedge_config_account = |hedge_config: HedgeConfig| {
+            let mut account = SolanaAccount::new(42, HedgeConfig::get_packed_len(), &program_id);
+            HedgeConfig::pack(hedge_config, &mut account.data).unwrap();
+            account
+        };
+        let mut hedge_config_unswapped = hedge_config_account(hedge_config);
+        let transfer_ix = |amount| {
+            transfer_with_positions(
+                &program_id,
+                &mint_key,
+                &source_key,
+                &destination_key,
+                &owner_key,
+                &[],
+                amount,
+            )
+            .unwrap()
+        };
 
-    source_account.usdc = source_account
-        .usdc
-        .checked_sub(amount_usdc_burned)
-        .ok_or(TokenError::Overflow)?;
+        // components move with the tokens, rewards are settled first
+        do_process_instruction(
+            transfer_ix(250),
+            vec![
+                &mut source_account,
+                &mut destination_account,
+                &mut owner_account,
+                &mut source_position,
+                &mut destination_position,
+                &mut take_profit,
+                &mut hedge_config_unswapped,
+            ],
+        )
+        .unwrap();
+        let position = Position::unpack(&source_position.data).unwrap();
+        assert_eq!((position.asset, position.usdc), (375, 750));
+        assert_eq!(position.rewards, 3_000);
+        assert_eq!(position.reward_per_share_paid, 3 * REWARD_PRECISION);
+        let position = Position::unpack(&destination_position.data).unwrap();
+        assert_eq!((position.asset, position.usdc), (125, 250));
+        assert_eq!(position.rewards, 0);
+        assert_eq!(position.reward_per_share_paid, 3 * REWARD_PRECISION);
 
-   
-    source_account.asset = source_account
-        .asset
-        .checked_sub(amount_asset_burned)
-        .ok_or(TokenError::Overflow)?;
+        // positions without the take-profit rule
+        let mut ix = transfer_ix(1);
+        ix.accounts.remove(5);
+        assert_eq!(
+            Err(TokenError::TakeProfitRequired.into()),
+            do_process_instruction(
+                ix,
+                vec![
+                    &mut source_account,
+                    &mut destination_account,
+                    &mut owner_account,
+                    &mut source_position,
+                    &mut destination_position,
+                    &mut hedge_config_unswapped,
+                ],
+            )
+        );
 
+        // positions cannot be left out of hedged transfers
+        let mut ix = transfer_ix(1);
+        ix.accounts.drain(3..5);
+        assert_eq!(
+            Err(TokenError::PositionRequired.into()),
+            do_process_instruction(
+                ix,
+                vec![
+                    &mut source_account,
+                    &mut destination_account,
+                    &mut owner_account,
+                    &mut take_profit,
+                    &mut hedge_config_unswapped,
+                ],
+            )
+        );
+        assert_eq!(Account::unpack(&destination_account.data).unwrap().amount, 250);
 
-  Account::pack(source_account, &mut account.data.borrow_mut())?;
-    
-    Ok(())
-}
-    /// Validates owner(s) are present
-    pub fn validate_owner(
-        program_id: &Pubkey,
-        expected_owner: &Pubkey,
-        owner_account_info: &AccountInfo,
-        signers: &[AccountInfo],
-    ) -> ProgramResult {
-        if expected_owner != owner_account_info.key {
-            return Err(TokenError::OwnerMismatch.into());
-        }
-        if program_id == owner_account_info.owner
-            && owner_account_info.data_len() == Multisig::get_packed_len()
-        {
-            let multisig = Multisig::unpack(&owner_account_info.data.borrow())?;
-            let mut num_signers = 0;
-            let mut matched = [false; MAX_SIGNERS];
-            for signer in signers.iter() {
-                for (position, key) in multisig.signers[0..multisig.n as usize].iter().enumerate() {
-                    if key == signer.key && !matched[position] {
-                        if !signer.is_signer {
-                            return Err(ProgramError::MissingRequiredSignature);
-                        }
-                        matched[position] = true;
-                        num_signers += 1;
-                    }
-                }
-            }
-            if num_signers < multisig.m {
-                return Err(ProgramError::MissingRequiredSignature);
-            }
-            return Ok(());
-        } else if !owner_account_info.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-        Ok(())
-    }
-}
+        // accounts without positions only move tokens
+        do_process_instruction(
+            transfer_ix(250),
+            vec![
+                &mut source_account,
+                &mut destination_account,
+                &mut owner_account,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut take_profit,
+                &mut hedge_config_unswapped,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&destination_account.data).unwrap().amount, 500);
+        assert_eq!(Position::unpack(&source_position.data).unwrap().asset, 375);
 
-impl PrintProgramError for TokenError {
-    fn print<E>(&self)
-    where
-        E: 'static + std::error::Error + DecodeError<E> + PrintProgramError + FromPrimitive,
-    {
-        match self {
-            TokenError::NotRentExempt => msg!("Error: Lamport balance below rent-exempt threshold"),
-            TokenError::InsufficientFunds => msg!("Error: insufficient funds"),
-            TokenError::InvalidMint => msg!("Error: Invalid Mint"),
-            TokenError::MintMismatch => msg!("Error: Account not associated with this Mint"),
-            TokenError::OwnerMismatch => msg!("Error: owner does not match"),
-            TokenError::FixedSupply => msg!("Error: the total supply of this token is fixed"),
-            TokenError::AlreadyInUse => msg!("Error: account or token already in use"),
-            TokenError::InvalidNumberOfProvidedSigners => {
-                msg!("Error: Invalid number of provided signers")
-            }
-            TokenError::InvalidNumberOfRequiredSigners => {
-                msg!("Error: Invalid number of required signers")
-            }
-            TokenError::UninitializedState => msg!("Error: State is uninitialized"),
-            TokenError::NativeNotSupported => {
-                msg!("Error: Instruction does not support native tokens")
-            }
-            TokenError::NonNativeHasBalance => {
-                msg!("Error: Non-native account can only be closed if its balance is zero")
-            }
-            TokenError::InvalidInstruction => msg!("Error: Invalid instruction"),
-            TokenError::InvalidState => msg!("Error: Invalid account state for operation"),
-            TokenError::Overflow => msg!("Error: Operation overflowed"),
-            TokenError::AuthorityTypeNotSupported => {
-                msg!("Error: Account does not support specified authority type")
-            }
-            TokenError::MintCannotFreeze => msg!("Error: This token mint cannot freeze accounts"),
-            TokenError::AccountFrozen => msg!("Error: Account is frozen"),
-            TokenError::MintDecimalsMismatch => {
-                msg!("Error: decimals different from the Mint decimals")
-            }
-        }
-    }
-}
+        // a position naming another account
+        let mut ix = transfer_ix(1);
+        ix.accounts.swap(3, 4);
+        assert_eq!(
+            Err(TokenError::PositionMismatch.into()),
+            do_process_instruction(
+                ix,
+                vec![
+                    &mut source_account,
+                    &mut destination_account,
+                    &mut owner_account,
+                    &mut source_position,
+                    &mut destination_position,
+                    &mut take_profit,
+                    &mut hedge_config_unswapped,
+                ],
+            )
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::instruction::*;
-    use solana_program::{
-        account_info::IntoAccountInfo, 
-        clock::Epoch, 
-        instruction::Instruction, 
-        sysvar::rent,
-    };
-    use solana_sdk::account::{
-        create_account_for_test, create_is_signer_account_infos, Account as SolanaAccount,
-    };
+        // source position without the destination position
+        let mut ix = transfer_ix(1);
+        ix.accounts.remove(4);
+        assert_eq!(
+            Err(TokenError::PositionRequired.into()),
+            do_process_instruction(
+                ix,
+                vec![
+                    &mut source_account,
+                    &mut destination_account,
+                    &mut owner_account,
+                    &mut source_position,
+                    &mut take_profit,
+                    &mut hedge_config_unswapped,
+                ],
+            )
+        );
 
-    fn do_process_instruction(
-        instruction: Instruction,
-        accounts: Vec<&mut SolanaAccount>,
-    ) -> ProgramResult {
-        let mut meta = instruction
-            .accounts
-            .iter()
-            .zip(accounts)
-            .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
-            .collect::<Vec<_>>();
+        // the stop-loss sold the asset vault for two stablecoins per asset:
+        // both positions are settled before the components move
+        let mut source_account = initialized_account(&program_id, &mint_key, &owner_key, 1_000);
+        let mut destination_account =
+            initialized_account(&program_id, &mint_key, &Pubkey::new_unique(), 1_000);
+        let mut source_position = position_account(&program_id, &source_key, 500, 1_000);
+        let mut destination_position = position_account(&program_id, &destination_key, 100, 0);
+        let mut hedge_config_swapped = hedge_config_account(HedgeConfig {
+            epoch: 1,
+            previous_stable_per_base: 2 * HEDGE_PRECISION,
+            ..hedge_config
+        });
+        do_process_instruction(
+            transfer_ix(500),
+            vec![
+                &mut source_account,
+                &mut destination_account,
+                &mut owner_account,
+                &mut source_position,
+                &mut destination_position,
+                &mut SolanaAccount::default(),
+                &mut hedge_config_swapped,
+            ],
+        )
+        .unwrap();
+        let position = Position::unpack(&source_position.data).unwrap();
+        assert_eq!((position.asset, position.usdc), (0, 1_000));
+        assert_eq!(position.hedge_epoch, 1);
+        let position = Position::unpack(&destination_position.data).unwrap();
+        assert_eq!((position.asset, position.usdc), (0, 1_200));
+        assert_eq!(position.hedge_epoch, 1);
 
-        let account_infos = create_is_signer_account_infos(&mut meta);
-        Processor::process(&instruction.program_id, &account_infos, &instruction.data)
-    }
+        // a hedge configuration at another address is not the mint's, the
+        // tokens move without the components
+        let mut ix = transfer_ix(100);
+        ix.accounts[6].pubkey = Pubkey::new_unique();
+        do_process_instruction(
+            ix,
+            vec![
+                &mut source_account,
+                &mut destination_account,
+                &mut owner_account,
+                &mut source_position,
+                &mut destination_position,
+                &mut SolanaAccount::default(),
+                &mut hedge_config_swapped,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&destination_account.data).unwrap().amount, 1_600);
+        assert_eq!(Position::unpack(&destination_position.data).unwrap().usdc, 1_200);
+        assert_eq!(hedge_config_key, transfer_ix(1).accounts[6].pubkey);
 
-    fn do_process_instruction_dups(
-        instruction: Instruction,
-        account_infos: Vec<AccountInfo>,
-    ) -> ProgramResult {
-        Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+        // plain transfers of other mints need no positions
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        let mut mint = Mint::unpack_unchecked(&mint_account.data).unwrap();
+        mint.is_initialized = true;
+        mint.decimals = 2;
+        Mint::pack(mint, &mut mint_account.data).unwrap();
+        do_process_instruction(
+            transfer(&program_id, &source_key, &destination_key, &owner_key, &[], 100).unwrap(),
+            vec![&mut source_account, &mut destination_account, &mut owner_account],
+        )
+        .unwrap();
+        do_process_instruction(
+            transfer_checked(
+                &program_id,
+                &source_key,
+                &mint_key,
+                &destination_key,
+                &owner_key,
+                &[],
+                100,
+                2,
+            )
+            .unwrap(),
+            vec![
+                &mut source_account,
+                &mut mint_account,
+                &mut destination_account,
+                &mut owner_account,
+            ],
+        )
+        .unwrap();
+        assert_eq!(Account::unpack(&source_account.data).unwrap().amount, 200);
+        assert_eq!(Account::unpack(&destination_account.data).unwrap().amount, 1_800);
     }
 
-    fn return_token_error_as_program_error() -> ProgramError {
-        TokenError::MintMismatch.into()
-    }
+    #[test]
+    fn test_mint_to_and_burn_with_position() {
+        let program_id = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let mut mint_account =
+            SolanaAccount::new(mint_minimum_balance(), Mint::get_packed_len(), &program_id);
+        Mint::pack(
+            Mint {
+                mint_authority: COption::Some(owner_key),
+                supply: 1_000,
+                decimals: 2,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            },
+            &mut mint_account.data,
+        )
+        .unwrap();
+        let mut account_account = initialized_account(&program_id, &mint_key, &owner_key, 1_000);
+        let mut position_account = position_account(&program_id, &account_key, 500, 1_000);
+        let mut take_profit = take_profit_account(
+            &program_id,
+            TakeProfit {
+                mint: mint_key,
+                reward_per_share: 3 * REWARD_PRECISION,
+                ..TakeProfit::default()
+            },
+        );
+        let mut hedge_config = hedge_config_account(
+            &program_id,
+            HedgeConfig {
+                mint: mint_key,
+                asset_scale: HEDGE_PRECISION,
+                ..HedgeConfig::default()
+            },
+        );
+        let (position_key, _) = find_position_with_program_id(&program_id, &account_key);
+        let (take_profit_key, _) = find_take_profit_with_program_id(&program_id, &mint_key);
+        let (hedge_config_key, _) = find_hedge_config_with_program_id(&program_id, &mint_key);
+        let (deposit_limits_key, _) = find_deposit_limits_with_program_id(&program_id, &mint_key);
+        let with_position = |mut ix: Instruction| {
+            ix.accounts.push(AccountMeta::new(position_key, false));
+            ix.accounts
+                .push(AccountMeta::new_readonly(take_profit_key, false));
+            ix.accounts
+                .push(AccountMeta::new_readonly(hedge_config_key, false));
+            ix
+        };
 
-    fn rent_sysvar() -> SolanaAccount {
-        create_account_for_test(&Rent::default())
-    }
+        // minted tokens do not earn the rewards distributed before
+        let mut ix = with_position(
+            mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 500).unwrap(),
+        );
+        ix.accounts
+            .push(AccountMeta::new_readonly(deposit_limits_key, false));
+        do_process_instruction(
+            ix,
+            vec![
+                &mut mint_account,
+                &mut account_account,
+                &mut SolanaAccount::default(),
+                &mut position_account,
+                &mut take_profit,
+                &mut hedge_config,
+                &mut SolanaAccount::default(),
+            ],
+        )
+        .unwrap();
+        let position = Position::unpack(&position_account.data).unwrap();
+        assert_eq!(position.rewards, 3_000);
+        assert_eq!(position.reward_per_share_paid, 3 * REWARD_PRECISION);
+        assert_eq!(Account::unpack(&account_account.data).unwrap().amount, 1_500);
 
-    fn mint_minimum_balance() -> u64 {
-        Rent::default().minimum_balance(Mint::get_packed_len())
-    }
+        // burned tokens keep the rewards they earned but give up their
+        // components
+        let mut take_profit_state = TakeProfit::unpack(&take_profit.data).unwrap();
+        take_profit_state.reward_per_share = 4 * REWARD_PRECISION;
+        TakeProfit::pack(take_profit_state, &mut take_profit.data).unwrap();
+        do_process_instruction(
+            with_position(
+                burn(&program_id, &account_key, &mint_key, &owner_key, &[], 300).unwrap(),
+            ),
+            vec![
+                &mut account_account,
+                &mut mint_account,
+                &mut SolanaAccount::default(),
+                &mut position_account,
+                &mut take_profit,
+                &mut hedge_config,
+            ],
+        )
+        .unwrap();
+        let position = Position::unpack(&position_account.data).unwrap();
+        assert_eq!(position.rewards, 4_500);
+        assert_eq!(position.reward_per_share_paid, 4 * REWARD_PRECISION);
+        assert_eq!((position.asset, position.usdc), (400, 800));
+        assert_eq!(Mint::unpack(&mint_account.data).unwrap().supply, 1_200);
 
-    fn account_minimum_balance() -> u64 {
-        Rent::default().minimum_balance(Account::get_packed_len())
-    }
+        // the position of a hedged account cannot be left out
+        let mut ix = mint_to(&program_id, &mint_key, &account_key, &owner_key, &[], 1).unwrap();
+        ix.accounts
+            .push(AccountMeta::new_readonly(hedge_config_key, false));
+        ix.accounts
+            .push(AccountMeta::new_readonly(deposit_limits_key, false));
+        assert_eq!(
+            Err(TokenError::PositionRequired.into()),
+            do_process_instruction(
+                ix,
+                vec![
+                    &mut mint_account,
+                    &mut account_account,
+                    &mut SolanaAccount::default(),
+                    &mut hedge_config,
+                    &mut SolanaAccount::default(),
+                ],
+            )
+        );
 
-    fn multisig_minimum_balance() -> u64 {
-        Rent::default().minimum_balance(Multisig::get_packed_len())
+        // nor can the take-profit rule
+        let mut ix = with_position(
+            burn(&program_id, &account_key, &mint_key, &owner_key, &[], 1).unwrap(),
+        );
+        ix.accounts.remove(4);
+        assert_eq!(
+            Err(TokenError::TakeProfitRequired.into()),
+            do_process_instruction(
+                ix,
+                vec![
+                    &mut account_account,
+                    &mut mint_account,
+                    &mut SolanaAccount::default(),
+                    &mut position_account,
+                    &mut hedge_config,
+                ],
+            )
+        );
     }
 
     #[test]
-    fn test_print_error() {
-        let error = return_token_error_as_program_error();
-        error.print::<TokenError>();
-    }
+    fn test_migrate_account() {
+        let program_id = Pubkey::new_unique();
+        let payer_key = Pubkey::new_unique();
 
-    #[test]
-    #[should_panic(expected = "Custom(3)")]
-    fn test_error_unwrap() {
-        Err::<(), ProgramError>(return_token_error_as_program_error()).unwrap();
-    }
+        // legacy token account
+        let account_key = Pubkey::new_unique();
+        let account = Account {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount: 1_000,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let mut legacy_account = SolanaAccount::new(
+            Rent::default().minimum_balance(LEGACY_ACCOUNT_LEN),
+            LEGACY_ACCOUNT_LEN,
+            &program_id,
+        );
+        Account::pack(account, &mut legacy_account.data[..Account::LEN]).unwrap();
+        legacy_account.data[Account::LEN..Account::LEN + 8].copy_from_slice(&400u64.to_le_bytes());
+        legacy_account.data[Account::LEN + 8..].copy_from_slice(&1_200u64.to_le_bytes());
+        let mut position = SolanaAccount::new(
+            Rent::default().minimum_balance(Position::get_packed_len()),
+            Position::get_packed_len(),
+            &program_id,
+        );
+        let migrate_ix = || migrate_account(&program_id, &account_key, &payer_key).unwrap();
+        do_process_instruction_serialized(
+            migrate_ix(),
+            vec![
+                &mut legacy_account,
+                &mut position,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut rent_sysvar(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(legacy_account.data.len(), Account::LEN);
+        assert_eq!(Account::unpack(&legacy_account.data).unwrap(), account);
+        let position = Position::unpack(&position.data).unwrap();
+        assert_eq!(position.account, account_key);
+        assert_eq!((position.asset, position.usdc), (0, 0));
 
-    #[test]
-    fn test_unique_account_sizes() {
-        assert_ne!(Mint::get_packed_len(), 0);
-        assert_ne!(Mint::get_packed_len(), Account::get_packed_len());
-        assert_ne!(Mint::get_packed_len(), Multisig::get_packed_len());
-        assert_ne!(Account::get_packed_len(), 0);
-        assert_ne!(Account::get_packed_len(), Multisig::get_packed_len());
-        assert_ne!(Multisig::get_packed_len(), 0);
+        // already canonical
+        assert_eq!(
+            Err(TokenError::NotLegacyAccount.into()),
+            do_process_instruction_serialized(
+                migrate_ix(),
+                vec![
+                    &mut legacy_account,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut rent_sysvar(),
+                ],
+            )
+        );
+
+        // legacy mint
+        let mint_key = Pubkey::new_unique();
+        let mint = Mint {
+            mint_authority: COption::Some(Pubkey::new_unique()),
+            supply: 1_000,
+            decimals: 2,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        let mut legacy_mint = SolanaAccount::new(
+            Rent::default().minimum_balance(LEGACY_MINT_LEN),
+            LEGACY_MINT_LEN,
+            &program_id,
+        );
+        Mint::pack(mint, &mut legacy_mint.data[..Mint::LEN]).unwrap();
+        legacy_mint.data[Mint::LEN..].copy_from_slice(&[7; LEGACY_MINT_LEN - Mint::LEN]);
+        assert_eq!(
+            Err(TokenError::LegacyMintHedged.into()),
+            do_process_instruction_serialized(
+                migrate_mint(&program_id, &mint_key).unwrap(),
+                vec![&mut legacy_mint],
+            )
+        );
+        legacy_mint.data[Mint::LEN..].copy_from_slice(&[0; LEGACY_MINT_LEN - Mint::LEN]);
+        do_process_instruction_serialized(
+            migrate_mint(&program_id, &mint_key).unwrap(),
+            vec![&mut legacy_mint],
+        )
+        .unwrap();
+        assert_eq!(legacy_mint.data.len(), Mint::LEN);
+        assert_eq!(Mint::unpack(&legacy_mint.data).unwrap(), mint);
+
+        // portfolio written before state accounts had a header
+        let portfolio = Portfolio {
+            is_initialize: 1,
+            portfolio_account: Pubkey::new_unique(),
+            creator_portfolio: Pubkey::new_unique(),
+            metadataUrl: vec![7; METADATA_URL_LEN],
+            amountAsset1: 100,
+            ..Portfolio::default()
+        };
+        let mut packed = vec![0; Portfolio::LEN];
+        Portfolio::pack(portfolio.clone(), &mut packed).unwrap();
+        let mut unversioned = SolanaAccount::new(
+            Rent::default().minimum_balance(Portfolio::LEN - ACCOUNT_HEADER_LEN),
+            Portfolio::LEN - ACCOUNT_HEADER_LEN,
+            &program_id,
+        );
+        unversioned
+            .data
+            .copy_from_slice(&packed[ACCOUNT_HEADER_LEN..]);
+        do_process_instruction_serialized(
+            migrate_state_account(&program_id, &portfolio.portfolio_account, &payer_key).unwrap(),
+            vec![
+                &mut unversioned,
+                &mut SolanaAccount::default(),
+                &mut SolanaAccount::default(),
+                &mut rent_sysvar(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(unversioned.data, packed);
+        assert_eq!(
+            StateAccount::unpack(&unversioned.data).unwrap(),
+            StateAccount::Portfolio(portfolio)
+        );
+
+        // a current deposit record is no legacy account
+        let mut record = deposit_record_account(&program_id, &mint_key, &account_key, 5);
+        assert_eq!(
+            Err(TokenError::NotLegacyAccount.into()),
+            do_process_instruction_serialized(
+                migrate_state_account(&program_id, &Pubkey::new_unique(), &payer_key).unwrap(),
+                vec![
+                    &mut record,
+                    &mut SolanaAccount::default(),
+                    &mut SolanaAccount::default(),
+                    &mut rent_sysvar(),
+                ],
+            )
+        );
+
+        // account of another program
+        let mut legacy_mint = SolanaAccount::new(42, LEGACY_MINT_LEN, &Pubkey::new_unique());
+        assert_eq!(
+            Err(ProgramError::IncorrectProgramId),
+            do_process_instruction_serialized(
+                migrate_mint(&program_id, &mint_key).unwrap(),
+                vec![&mut legacy_mint],
+            )
+        );
     }
 /*
     #[test]
@@ -1601,7 +9223,6 @@ mod tests {
         .unwrap();
      
           // deposit
-          let nonce: u8 = 255;
        let r = do_process_instruction(
             deposit(
                 &program_id,
@@ -1618,9 +9239,10 @@ mod tests {
                 &host_fee_account,
                 &prog_address,
                 &publickey_swap,
+                &mint_key,
+                &owner_key,
                 100,
                 20,
-                nonce,
             )
             .unwrap(),
             vec![
@@ -1637,7 +9259,8 @@ mod tests {
                 &mut account_host_fee_account,
                 &mut account_prog_address,
                 &mut  account_publickey_swap,
-               
+                &mut mint_account,
+                &mut owner_account,
             ],
 
             
@@ -1656,6 +9279,141 @@ mod tests {
 
     #[test]
     fn test_withdraw() {
+        let fixture = HedgeFixture::new();
+        stub_token_transfers(&fixture.token_program_id);
+        let owner_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let accounts = || {
+            let mut payout_accounts = fixture.withdraw_payout_accounts(200);
+            payout_accounts[1].data[64..72].copy_from_slice(&1_000u64.to_le_bytes());
+            payout_accounts[2].data[64..72].copy_from_slice(&2_000u64.to_le_bytes());
+            vec![
+                program_config_account(&fixture.program_id, ProgramConfig::default()),
+                initialized_account(&fixture.program_id, &fixture.mint_key, &owner_key, 100),
+                position_account(&fixture.program_id, &account_key, 60, 80),
+                SolanaAccount::default(),
+                fixture.hedge_config_account(5_000, 0),
+                fixture.swap_account.clone(),
+                token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.swap_authority),
+                token_account(&fixture.token_program_id, &fixture.stable_mint, &fixture.swap_authority),
+                SolanaAccount::default(),
+            ]
+            .into_iter()
+            .chain(payout_accounts)
+            .chain(vec![
+                create_account_for_test(&Clock {
+                    slot: 100,
+                    ..Clock::default()
+                }),
+                SolanaAccount::default(),
+            ])
+            .collect::<Vec<_>>()
+        };
+        let withdraw_ix =
+            |amount| fixture.withdraw_ix(&account_key, &fixture.swap_state.token_a, &owner_key, amount);
+
+        // vault of another mint
+        let mut withdraw_accounts = accounts();
+        let mut ix = withdraw_ix(25);
+        ix.accounts[11].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectVaultAccount.into()),
+            do_process_instruction(ix, withdraw_accounts.iter_mut().collect())
+        );
+
+        // vault authority of another mint
+        let mut withdraw_accounts = accounts();
+        let mut ix = withdraw_ix(25);
+        ix.accounts[18].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::InvalidVaultAuthority.into()),
+            do_process_instruction(ix, withdraw_accounts.iter_mut().collect())
+        );
+
+        // the vault cannot pay out more than it holds
+        let mut withdraw_accounts = accounts();
+        withdraw_accounts[10].data[64..72].copy_from_slice(&10u64.to_le_bytes());
+        assert_eq!(
+            Err(TokenError::InsufficientFunds.into()),
+            do_process_instruction(withdraw_ix(25), withdraw_accounts.iter_mut().collect())
+        );
+
+        // success: a quarter of the position is paid out and burned
+        let mut withdraw_accounts = accounts();
+        do_process_instruction(withdraw_ix(25), withdraw_accounts.iter_mut().collect()).unwrap();
+        assert_eq!(Account::unpack(&withdraw_accounts[1].data).unwrap().amount, 75);
+        let position = Position::unpack(&withdraw_accounts[2].data).unwrap();
+        assert_eq!(position.asset, 45);
+        assert_eq!(position.usdc, 60);
+        assert_eq!(Mint::unpack(&withdraw_accounts[9].data).unwrap().supply, 175);
+        assert_eq!(token_balance(&withdraw_accounts[10]), 985);
+        assert_eq!(token_balance(&withdraw_accounts[11]), 1_980);
+        assert_eq!(token_balance(&withdraw_accounts[12]), 15);
+        assert_eq!(token_balance(&withdraw_accounts[13]), 20);
+
+        // the mint of another account
+        let mut withdraw_accounts = accounts();
+        let mut ix = withdraw_ix(25);
+        ix.accounts[9].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::MintMismatch.into()),
+            do_process_instruction(ix, withdraw_accounts.iter_mut().collect())
+        );
+
+        // a 10% withdrawal fee on each component, half of it to the protocol
+        let fee_accounts = || {
+            let mut withdraw_accounts = accounts();
+            withdraw_accounts[0] = program_config_account(
+                &fixture.program_id,
+                ProgramConfig {
+                    protocol_fee_bps: 5_000,
+                    ..ProgramConfig::default()
+                },
+            );
+            let mut hedge_config = HedgeConfig::unpack(&withdraw_accounts[4].data).unwrap();
+            hedge_config.withdraw_fee_bps = 1_000;
+            HedgeConfig::pack(hedge_config, &mut withdraw_accounts[4].data).unwrap();
+            withdraw_accounts
+        };
+        let mut withdraw_accounts = fee_accounts();
+        do_process_instruction(withdraw_ix(50), withdraw_accounts.iter_mut().collect()).unwrap();
+        let position = Position::unpack(&withdraw_accounts[2].data).unwrap();
+        assert_eq!((position.asset, position.usdc), (30, 40));
+        assert_eq!(token_balance(&withdraw_accounts[10]), 970);
+        assert_eq!(token_balance(&withdraw_accounts[11]), 1_960);
+        assert_eq!(token_balance(&withdraw_accounts[12]), 27);
+        assert_eq!(token_balance(&withdraw_accounts[13]), 36);
+        assert_eq!(token_balance(&withdraw_accounts[14]), 2);
+        assert_eq!(token_balance(&withdraw_accounts[15]), 2);
+        assert_eq!(token_balance(&withdraw_accounts[16]), 1);
+        assert_eq!(token_balance(&withdraw_accounts[17]), 2);
+
+        // another fee account of the mint
+        let mut withdraw_accounts = fee_accounts();
+        let mut ix = withdraw_ix(50);
+        ix.accounts[14].pubkey = Pubkey::new_unique();
+        assert_eq!(
+            Err(TokenError::IncorrectFeeAccount.into()),
+            do_process_instruction(ix, withdraw_accounts.iter_mut().collect())
+        );
+
+        // stablecoin fee account of another owner
+        let mut withdraw_accounts = fee_accounts();
+        withdraw_accounts[15] =
+            token_account(&fixture.token_program_id, &fixture.stable_mint, &Pubkey::new_unique());
+        assert_eq!(
+            Err(TokenError::IncorrectFeeAccount.into()),
+            do_process_instruction(withdraw_ix(50), withdraw_accounts.iter_mut().collect())
+        );
+
+        // protocol fee account of another owner
+        let mut withdraw_accounts = fee_accounts();
+        withdraw_accounts[17] =
+            token_account(&fixture.token_program_id, &fixture.stable_mint, &Pubkey::new_unique());
+        assert_eq!(
+            Err(TokenError::IncorrectProtocolFeeAccount.into()),
+            do_process_instruction(withdraw_ix(50), withdraw_accounts.iter_mut().collect())
+        );
 /*
 
         let program_id = Pubkey::new_unique();
@@ -1740,6 +9498,127 @@ mod tests {
      }
 */
  }
+
+    #[test]
+    fn test_settle_vault_swaps() {
+        let fixture = HedgeFixture::new();
+        stub_token_transfers(&fixture.token_program_id);
+        let owner_key = Pubkey::new_unique();
+        let account_key = Pubkey::new_unique();
+        let hedge_config = HedgeConfig::unpack(&fixture.hedge_config_account(5_000, 0).data).unwrap();
+        let hedge_config_account = |hedge_config: HedgeConfig| {
+            let mut account = fixture.hedge_config_account(5_000, 0);
+            HedgeConfig::pack(hedge_config, &mut account.data).unwrap();
+            account
+        };
+        let accounts = |hedge_config: HedgeConfig| {
+            let mut payout_accounts = fixture.withdraw_payout_accounts(200);
+            payout_accounts[1].data[64..72].copy_from_slice(&1_000u64.to_le_bytes());
+            payout_accounts[2].data[64..72].copy_from_slice(&2_000u64.to_le_bytes());
+            vec![
+                program_config_account(&fixture.program_id, ProgramConfig::default()),
+                initialized_account(&fixture.program_id, &fixture.mint_key, &owner_key, 100),
+                position_account(&fixture.program_id, &account_key, 60, 80),
+                SolanaAccount::default(),
+                hedge_config_account(hedge_config),
+                fixture.swap_account.clone(),
+                token_account(&fixture.token_program_id, &fixture.asset_mint, &fixture.swap_authority),
+                token_account(&fixture.token_program_id, &fixture.stable_mint, &fixture.swap_authority),
+                SolanaAccount::default(),
+            ]
+            .into_iter()
+            .chain(payout_accounts)
+            .chain(vec![
+                create_account_for_test(&Clock {
+                    slot: 100,
+                    ..Clock::default()
+                }),
+                SolanaAccount::default(),
+            ])
+            .collect::<Vec<_>>()
+        };
+        let withdraw_ix = || fixture.withdraw_ix(&account_key, &fixture.swap_state.token_a, &owner_key, 25);
+
+        // half of the asset vault sold for one stablecoin per asset held
+        let mut withdraw_accounts = accounts(HedgeConfig {
+            asset_scale: HEDGE_PRECISION / 2,
+            stable_per_base: HEDGE_PRECISION,
+            ..hedge_config
+        });
+        do_process_instruction(withdraw_ix(), withdraw_accounts.iter_mut().collect()).unwrap();
+        let position = Position::unpack(&withdraw_accounts[2].data).unwrap();
+        assert_eq!(position.asset, 23);
+        assert_eq!(position.usdc, 105);
+        assert_eq!(position.asset_scale_paid, HEDGE_PRECISION / 2);
+        assert_eq!(position.stable_per_base_paid, HEDGE_PRECISION);
+        assert_eq!(token_balance(&withdraw_accounts[12]), 7);
+        assert_eq!(token_balance(&withdraw_accounts[13]), 35);
+
+        // the stop-loss sold the whole vault for two stablecoins per asset
+        let closed = HedgeConfig {
+            epoch: 1,
+            previous_stable_per_base: 2 * HEDGE_PRECISION,
+            ..hedge_config
+        };
+        let mut withdraw_accounts = accounts(closed);
+        do_process_instruction(withdraw_ix(), withdraw_accounts.iter_mut().collect()).unwrap();
+        let position = Position::unpack(&withdraw_accounts[2].data).unwrap();
+        assert_eq!(position.asset, 0);
+        assert_eq!(position.usdc, 150);
+        assert_eq!(position.hedge_epoch, 1);
+        assert_eq!(token_balance(&withdraw_accounts[12]), 0);
+        assert_eq!(token_balance(&withdraw_accounts[13]), 50);
+
+        // settled in an epoch closed before the last one, without its record
+        let closed_twice = HedgeConfig {
+            epoch: 2,
+            ..closed
+        };
+        let mut withdraw_accounts = accounts(closed_twice);
+        assert_eq!(
+            Err(TokenError::HedgeEpochRequired.into()),
+            do_process_instruction(withdraw_ix(), withdraw_accounts.iter_mut().collect())
+        );
+
+        // record of another epoch
+        let (hedge_epoch_key, bump) =
+            find_hedge_epoch_with_program_id(&fixture.program_id, &fixture.mint_key, 0);
+        let mut hedge_epoch_account =
+            SolanaAccount::new(42, HedgeEpoch::get_packed_len(), &fixture.program_id);
+        HedgeEpoch::pack(
+            HedgeEpoch {
+                is_initialized: true,
+                mint: fixture.mint_key,
+                epoch: 0,
+                stable_per_base: 3 * HEDGE_PRECISION,
+                bump,
+            },
+            &mut hedge_epoch_account.data,
+        )
+        .unwrap();
+        let mut ix = withdraw_ix();
+        let (other_epoch_key, _) =
+            find_hedge_epoch_with_program_id(&fixture.program_id, &fixture.mint_key, 1);
+        ix.accounts.push(AccountMeta::new_readonly(other_epoch_key, false));
+        let mut withdraw_accounts = accounts(closed_twice);
+        withdraw_accounts.push(hedge_epoch_account.clone());
+        assert_eq!(
+            Err(TokenError::HedgeEpochRequired.into()),
+            do_process_instruction(ix, withdraw_accounts.iter_mut().collect())
+        );
+
+        // success with the record of the epoch of the position
+        let mut ix = withdraw_ix();
+        ix.accounts.push(AccountMeta::new_readonly(hedge_epoch_key, false));
+        let mut withdraw_accounts = accounts(closed_twice);
+        withdraw_accounts.push(hedge_epoch_account);
+        do_process_instruction(ix, withdraw_accounts.iter_mut().collect()).unwrap();
+        let position = Position::unpack(&withdraw_accounts[2].data).unwrap();
+        assert_eq!(position.asset, 0);
+        assert_eq!(position.usdc, 195);
+        assert_eq!(position.hedge_epoch, 2);
+        assert_eq!(token_balance(&withdraw_accounts[13]), 65);
+    }
  
 
 
@@ -1751,8 +9630,6 @@ mod tests {
         mint: Pubkey::new(&[1; 32]),
         owner: Pubkey::new(&[2; 32]),
         amount: 3,
-        asset:8,
-        usdc:8,
         delegate: COption::Some(Pubkey::new(&[4; 32])),
         state: AccountState::Frozen,
         is_native: COption::Some(5),
@@ -1778,8 +9655,7 @@ mod tests {
         2, 2, 2, 2, 2, 2, 3, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4,
         4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 2, 1, 0, 0, 0, 5, 0, 0,
         0, 0, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
-        7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 8, 0, 0, 0, 0, 0, 0, 0, 8,
-         0, 0, 0, 0, 0, 0, 0
+        7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
     ];
    
     assert_eq!(packed, expect);
@@ -1838,17 +9714,17 @@ mod tests {
     let mut packed = vec![0; Portfolio::get_packed_len() + 1];
     assert_eq!(
         Err(ProgramError::InvalidAccountData),
-        Portfolio::pack(check, &mut packed)
+        Portfolio::pack(check.clone(), &mut packed)
     );
     let mut packed = vec![0; Portfolio::get_packed_len() - 1];
     assert_eq!(
         Err(ProgramError::InvalidAccountData),
-        Portfolio::pack(check, &mut packed)
+        Portfolio::pack(check.clone(), &mut packed)
     );
     msg!("ici");
     let mut packed = vec![0; Portfolio::get_packed_len()];
-    Portfolio::pack(check, &mut packed).unwrap();
-    let expect = vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+    Portfolio::pack(check.clone(), &mut packed).unwrap();
+    let expect = vec![3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
     , 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2
     , 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2
     ,97, 97, 98, 98, 99, 99, 97, 97, 98, 98, 99, 99, 97, 97, 98, 98, 99, 99
@@ -1858,7 +9734,7 @@ mod tests {
     , 97, 97, 98, 98, 99, 99, 97, 97, 98, 98, 99, 99, 97, 97, 98, 98, 99, 99
     , 97, 97, 98, 98, 99, 99, 97, 97, 98, 98, 99, 99, 97, 97, 98, 98, 99, 99
     , 97, 97, 98, 98, 99, 99, 97, 97, 98, 98, 99, 99, 97, 97, 98, 98, 99, 99
-    , 97, 97, 3,0,  4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
+    , 97, 97, 3,0, 0, 4, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
     , 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 6, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
     , 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 4
     , 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2
@@ -1937,7 +9813,26 @@ mod tests {
         let mut addressAsset9_account = SolanaAccount::new(42, Portfolio::get_packed_len(), &program_id);
         let  assetToSoldIntoAsset9 = Pubkey::new_unique();
         let mut assetToSoldIntoAsset9_account = SolanaAccount::new(42, Portfolio::get_packed_len(), &program_id);
-        let mut rent_sysvar = rent_sysvar();
+        let portfolio_owner = Pubkey::new_unique();
+        let mut portfolio_owner_account = SolanaAccount::default();
+        let swap_program_id = Pubkey::new_unique();
+        let swap_pool = Pubkey::new_unique();
+        let mut asset_entry_accounts: Vec<SolanaAccount> = [
+            addressAsset1,
+            addressAsset2,
+            addressAsset3,
+            addressAsset4,
+            addressAsset5,
+            addressAsset6,
+            addressAsset7,
+            addressAsset8,
+            addressAsset9,
+        ]
+        .iter()
+        .map(|mint| {
+            asset_entry_account(&program_id, mint, AssetKind::Asset, &swap_program_id, &swap_pool)
+        })
+        .collect();
          // addressAsset10: &Pubkey ,
         // assetToSoldIntoAsset10: &Pubkey ,
       
@@ -1970,6 +9865,7 @@ mod tests {
        do_process_instruction(
            initialize_portfolio(&program_id, &creatorAccount,
             &owner,
+            &portfolio_owner,
             &metaDataUrl,
             &metaDataHash,
             &amountAsset1,
@@ -2031,8 +9927,11 @@ mod tests {
             &mut assetToSoldIntoAsset8_account,
             &mut addressAsset9_account,
             &mut assetToSoldIntoAsset9_account,
-            &mut rent_sysvar
-        ],
+            &mut portfolio_owner_account,
+        ]
+        .into_iter()
+        .chain(asset_entry_accounts.iter_mut())
+        .collect(),
 
 
 
@@ -2043,6 +9942,104 @@ mod tests {
 
 
     
+
+    #[test]
+    fn test_create_portfolio_registry() {
+        let program_id = Pubkey::new_unique();
+        let portfolio_key = Pubkey::new_unique();
+        let creator_key = Pubkey::new_unique();
+        let owner_key = Pubkey::new_unique();
+        let swap_program_id = Pubkey::new_unique();
+        let swap_pool = Pubkey::new_unique();
+        let assets: Vec<(Pubkey, Pubkey)> = (0..9)
+            .map(|_| (Pubkey::new_unique(), Pubkey::new_unique()))
+            .collect();
+
+        // portfolio, creator, nine (mint, sold into) pairs, owner, then the
+        // nine registry entries
+        let mut accounts = vec![SolanaAccount::new(42, Portfolio::get_packed_len(), &program_id)];
+        accounts.extend((0..20).map(|_| SolanaAccount::default()));
+        accounts.extend(assets.iter().map(|(mint, _)| {
+            asset_entry_account(&program_id, mint, AssetKind::Asset, &swap_program_id, &swap_pool)
+        }));
+        let instruction = initialize_portfolio(
+            &program_id,
+            &portfolio_key,
+            &creator_key,
+            &owner_key,
+            &vec![97; 8],
+            &3,
+            &12,
+            &assets[0].0,
+            &1,
+            &assets[0].1,
+            &11,
+            &assets[1].0,
+            &1,
+            &assets[1].1,
+            &11,
+            &assets[2].0,
+            &1,
+            &assets[2].1,
+            &11,
+            &assets[3].0,
+            &1,
+            &assets[3].1,
+            &11,
+            &assets[4].0,
+            &1,
+            &assets[4].1,
+            &11,
+            &assets[5].0,
+            &1,
+            &assets[5].1,
+            &11,
+            &assets[6].0,
+            &1,
+            &assets[6].1,
+            &11,
+            &assets[7].0,
+            &1,
+            &assets[7].1,
+            &11,
+            &assets[8].0,
+            &1,
+            &assets[8].1,
+        )
+        .unwrap();
+        assert_eq!(instruction.accounts.len(), 30);
+        for (i, (mint, _)) in assets.iter().enumerate() {
+            assert_eq!(
+                instruction.accounts[21 + i].pubkey,
+                find_asset_entry_with_program_id(&program_id, mint).0
+            );
+        }
+
+        // an unregistered asset is rejected
+        let asset_entry = std::mem::take(&mut accounts[25]);
+        assert_eq!(
+            Err(TokenError::AssetNotRegistered.into()),
+            do_process_instruction(instruction.clone(), accounts.iter_mut().collect())
+        );
+        accounts[25] = asset_entry;
+
+        // the creator must sign
+        let mut unsigned = instruction.clone();
+        unsigned.accounts[1].is_signer = false;
+        assert_eq!(
+            Err(ProgramError::MissingRequiredSignature),
+            do_process_instruction(unsigned, accounts.iter_mut().collect())
+        );
+
+        // a fully registered set succeeds
+        do_process_instruction(instruction, accounts.iter_mut().collect()).unwrap();
+        let portfolio = Portfolio::unpack(&accounts[0].data).unwrap();
+        assert_eq!(portfolio.is_initialize, 1);
+        assert_eq!(portfolio.creator_portfolio, creator_key);
+        assert_eq!(portfolio.addressAsset5, assets[4].0);
+        assert_eq!(portfolio.assetToSoldIntoAsset9, assets[8].1);
+    }
+
 /*
      #[test]
     fn test_pack_unpack() {
@@ -1,6 +1,10 @@
 //! State transition types
 
-use crate::instruction::MAX_SIGNERS;
+use crate::{
+    error::TokenError,
+    instruction::{MAX_SIGNERS, METADATA_URL_LEN},
+    oracle::OraclePrice,
+};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use num_enum::TryFromPrimitive;
 use solana_program::{
@@ -10,7 +14,7 @@ use solana_program::{
     pubkey::Pubkey,
     msg
 };
-use std::convert::TryInto;
+use std::convert::TryFrom;
 
 /// Mint data.
 #[repr(C)]
@@ -28,10 +32,6 @@ pub struct Mint {
     pub is_initialized: bool,
     /// Optional authority to freeze token accounts.
     pub freeze_authority: COption<Pubkey>,
-    /// The mint id of the asset that will be hedged in the program.
-    pub mint_id_asset:COption<Pubkey>,
-    /// public key of swap .
-    pub pubkey_swap:COption<Pubkey>,
 }
 impl Sealed for Mint {}
 impl IsInitialized for Mint {
@@ -40,61 +40,1434 @@ impl IsInitialized for Mint {
     }
 }
 impl Pack for Mint {
-    const LEN: usize = 154;
+    const LEN: usize = 82;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, 154];
-        let (mint_authority, supply, decimals, is_initialized, freeze_authority,mint_id_asset, pubkey_swap) =
-            array_refs![src, 36, 8, 1, 1, 36 , 36 , 36];
+        let src = array_ref![src, 0, 82];
+        let (mint_authority, supply, decimals, is_initialized, freeze_authority) =
+            array_refs![src, 36, 8, 1, 1, 36];
         let mint_authority = unpack_coption_key(mint_authority)?;
         let supply = u64::from_le_bytes(*supply);
         let decimals = decimals[0];
         let is_initialized = match is_initialized {
             [0] => false,
             [1] => true,
-            _ => return  { 
-                Err(ProgramError::InvalidAccountData)
-            },
+            _ => return Err(ProgramError::InvalidAccountData),
         };
         let freeze_authority = unpack_coption_key(freeze_authority)?;
-        let mint_id_asset = unpack_coption_key(mint_id_asset)?;
-        let pubkey_swap = unpack_coption_key(pubkey_swap)?;
         Ok(Mint {
             mint_authority,
             supply,
             decimals,
             is_initialized,
             freeze_authority,
-            mint_id_asset,
-            pubkey_swap,
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, 154];
+        let dst = array_mut_ref![dst, 0, 82];
         let (
             mint_authority_dst,
             supply_dst,
             decimals_dst,
             is_initialized_dst,
             freeze_authority_dst,
-            mint_id_asset_dst,
-            pubkey_swap_dst,
-        ) = mut_array_refs![dst, 36, 8, 1, 1, 36,36,36];
+        ) = mut_array_refs![dst, 36, 8, 1, 1, 36];
         let &Mint {
             ref mint_authority,
             supply,
             decimals,
             is_initialized,
             ref freeze_authority,
-            ref mint_id_asset,
-            ref pubkey_swap,
         } = self;
         pack_coption_key(mint_authority, mint_authority_dst);
         *supply_dst = supply.to_le_bytes();
         decimals_dst[0] = decimals;
         is_initialized_dst[0] = is_initialized as u8;
         pack_coption_key(freeze_authority, freeze_authority_dst);
-        pack_coption_key(mint_id_asset, mint_id_asset_dst);
-        pack_coption_key(pubkey_swap, pubkey_swap_dst);
+    }
+}
+
+/// Length of the header starting the state accounts of this program that are
+/// not part of the SPL Token layout.
+pub const ACCOUNT_HEADER_LEN: usize = 2;
+/// Layout version written in the header of state accounts.
+pub const STATE_VERSION: u8 = 1;
+
+/// Type of a state account, stored in the first byte of its header.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+pub enum AccountType {
+    /// Account is allocated but not yet initialized
+    Uninitialized,
+    /// A `HedgeConfig`
+    HedgeConfig,
+    /// A `Position`
+    Position,
+    /// A `Portfolio`
+    Portfolio,
+    /// A `UserPortfolio`
+    UserPortfolio,
+    /// A `ProgramConfig`
+    ProgramConfig,
+    /// An `AssetEntry`
+    AssetEntry,
+    /// A `DepositLimits`
+    DepositLimits,
+    /// A `DepositRecord`
+    DepositRecord,
+    /// A `StopLoss`
+    StopLoss,
+    /// A `TakeProfit`
+    TakeProfit,
+    /// A `DcaConfig`
+    DcaConfig,
+    /// A `DcaPosition`
+    DcaPosition,
+    /// A `WithdrawTicket`
+    WithdrawTicket,
+    /// A `HedgeEpoch`
+    HedgeEpoch,
+}
+impl AccountType {
+    /// Returns the type of a state account written before headers were
+    /// introduced, which is only identified by its length.
+    pub fn from_unversioned_len(len: usize) -> Option<Self> {
+        match len + ACCOUNT_HEADER_LEN {
+            Portfolio::LEN => Some(AccountType::Portfolio),
+            UserPortfolio::LEN => Some(AccountType::UserPortfolio),
+            _ => None,
+        }
+    }
+}
+
+/// Reads the type of a state account from its header.
+pub fn account_type(input: &[u8]) -> Result<AccountType, ProgramError> {
+    match input.get(..ACCOUNT_HEADER_LEN) {
+        Some(&[account_type, STATE_VERSION]) => AccountType::try_from_primitive(account_type)
+            .or(Err(ProgramError::InvalidAccountData)),
+        Some(&[0, 0]) => Ok(AccountType::Uninitialized),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// A state account of any type, unpacked according to its header.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum StateAccount {
+    /// A `HedgeConfig`
+    HedgeConfig(HedgeConfig),
+    /// A `Position`
+    Position(Position),
+    /// A `Portfolio`
+    Portfolio(Portfolio),
+    /// A `UserPortfolio`
+    UserPortfolio(UserPortfolio),
+    /// A `ProgramConfig`
+    ProgramConfig(ProgramConfig),
+    /// An `AssetEntry`
+    AssetEntry(AssetEntry),
+    /// A `DepositLimits`
+    DepositLimits(DepositLimits),
+    /// A `DepositRecord`
+    DepositRecord(DepositRecord),
+    /// A `StopLoss`
+    StopLoss(StopLoss),
+    /// A `TakeProfit`
+    TakeProfit(TakeProfit),
+    /// A `DcaConfig`
+    DcaConfig(DcaConfig),
+    /// A `DcaPosition`
+    DcaPosition(DcaPosition),
+    /// A `WithdrawTicket`
+    WithdrawTicket(WithdrawTicket),
+    /// A `HedgeEpoch`
+    HedgeEpoch(HedgeEpoch),
+}
+impl StateAccount {
+    /// Unpacks a state account, dispatching on the type in its header.
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        match account_type(input)? {
+            AccountType::Uninitialized => Err(ProgramError::UninitializedAccount),
+            AccountType::HedgeConfig => HedgeConfig::unpack(input).map(StateAccount::HedgeConfig),
+            AccountType::Position => Position::unpack(input).map(StateAccount::Position),
+            AccountType::Portfolio => Portfolio::unpack(input).map(StateAccount::Portfolio),
+            AccountType::UserPortfolio => {
+                UserPortfolio::unpack(input).map(StateAccount::UserPortfolio)
+            }
+            AccountType::ProgramConfig => {
+                ProgramConfig::unpack(input).map(StateAccount::ProgramConfig)
+            }
+            AccountType::AssetEntry => AssetEntry::unpack(input).map(StateAccount::AssetEntry),
+            AccountType::DepositLimits => {
+                DepositLimits::unpack(input).map(StateAccount::DepositLimits)
+            }
+            AccountType::DepositRecord => {
+                DepositRecord::unpack(input).map(StateAccount::DepositRecord)
+            }
+            AccountType::StopLoss => StopLoss::unpack(input).map(StateAccount::StopLoss),
+            AccountType::TakeProfit => TakeProfit::unpack(input).map(StateAccount::TakeProfit),
+            AccountType::DcaConfig => DcaConfig::unpack(input).map(StateAccount::DcaConfig),
+            AccountType::DcaPosition => DcaPosition::unpack(input).map(StateAccount::DcaPosition),
+            AccountType::WithdrawTicket => {
+                WithdrawTicket::unpack(input).map(StateAccount::WithdrawTicket)
+            }
+            AccountType::HedgeEpoch => HedgeEpoch::unpack(input).map(StateAccount::HedgeEpoch),
+        }
+    }
+}
+
+/// Pause flag of deposits.
+pub const PAUSE_DEPOSIT: u32 = 1;
+/// Pause flag of withdrawals.
+pub const PAUSE_WITHDRAW: u32 = 1 << 1;
+/// Pause flag of the swaps made by the program to hedge or rebalance positions.
+pub const PAUSE_SWAP: u32 = 1 << 2;
+
+/// Program-wide configuration, stored at the address derived with
+/// `PROGRAM_CONFIG_SEED`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProgramConfig {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// Authority allowed to update the configuration, optionally a `Multisig`.
+    pub admin: Pubkey,
+    /// Authority allowed to pause operations, but not to resume them.
+    pub guardian: Pubkey,
+    /// Paused operations, a combination of the `PAUSE_*` flags.
+    pub paused: u32,
+    /// Share of the fees charged by hedged mints paid to the protocol, in
+    /// basis points.
+    pub protocol_fee_bps: u16,
+    /// Owner of the token accounts receiving the protocol fees.
+    pub protocol_fee_owner: Pubkey,
+    /// Bump seed of this account.
+    pub bump: u8,
+    /// Slots between a withdrawal request and its claim, no request being
+    /// needed if zero.
+    pub withdraw_delay_slots: u64,
+    /// Is `true` if withdrawals skip the delay and the deposit slot check,
+    /// set by the admin for emergency unwinds.
+    pub bypass_withdraw_delay: bool,
+}
+impl ProgramConfig {
+    /// Checks that none of the operations in `flags` is paused.
+    pub fn check_not_paused(&self, flags: u32) -> Result<(), ProgramError> {
+        if self.paused & flags != 0 {
+            Err(TokenError::Paused.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+impl Sealed for ProgramConfig {}
+impl IsInitialized for ProgramConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for ProgramConfig {
+    const LEN: usize = 115;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 115];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header,
+            is_initialized,
+            admin,
+            guardian,
+            paused,
+            protocol_fee_bps,
+            protocol_fee_owner,
+            bump,
+            withdraw_delay_slots,
+            bypass_withdraw_delay,
+        ) = array_refs![src, 2, 1, 32, 32, 4, 2, 32, 1, 8, 1];
+        unpack_header(header, AccountType::ProgramConfig)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let bypass_withdraw_delay = match bypass_withdraw_delay {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(ProgramConfig {
+            is_initialized,
+            admin: Pubkey::new_from_array(*admin),
+            guardian: Pubkey::new_from_array(*guardian),
+            paused: u32::from_le_bytes(*paused),
+            protocol_fee_bps: u16::from_le_bytes(*protocol_fee_bps),
+            protocol_fee_owner: Pubkey::new_from_array(*protocol_fee_owner),
+            bump: bump[0],
+            withdraw_delay_slots: u64::from_le_bytes(*withdraw_delay_slots),
+            bypass_withdraw_delay,
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 115];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header_dst,
+            is_initialized_dst,
+            admin_dst,
+            guardian_dst,
+            paused_dst,
+            protocol_fee_bps_dst,
+            protocol_fee_owner_dst,
+            bump_dst,
+            withdraw_delay_slots_dst,
+            bypass_withdraw_delay_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 32, 4, 2, 32, 1, 8, 1];
+        pack_header(AccountType::ProgramConfig, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        admin_dst.copy_from_slice(self.admin.as_ref());
+        guardian_dst.copy_from_slice(self.guardian.as_ref());
+        *paused_dst = self.paused.to_le_bytes();
+        *protocol_fee_bps_dst = self.protocol_fee_bps.to_le_bytes();
+        protocol_fee_owner_dst.copy_from_slice(self.protocol_fee_owner.as_ref());
+        bump_dst[0] = self.bump;
+        *withdraw_delay_slots_dst = self.withdraw_delay_slots.to_le_bytes();
+        bypass_withdraw_delay_dst[0] = self.bypass_withdraw_delay as u8;
+    }
+}
+
+/// Role of a mint approved in the asset registry.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+pub enum AssetKind {
+    /// An asset that hedged mints and portfolios may hold
+    Asset,
+    /// A stablecoin that hedged mints may swap into
+    Stablecoin,
+}
+impl Default for AssetKind {
+    fn default() -> Self {
+        AssetKind::Asset
+    }
+}
+
+/// Registry entry of a mint approved by the admin, stored at the address
+/// derived from the mint with `ASSET_ENTRY_SEED`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AssetEntry {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The approved mint.
+    pub mint: Pubkey,
+    /// The role of the mint.
+    pub kind: AssetKind,
+    /// Number of base 10 digits to the right of the decimal place of the mint.
+    pub decimals: u8,
+    /// The price oracle of the mint.
+    pub oracle: Pubkey,
+    /// The swap program of the preferred pool.
+    pub swap_program_id: Pubkey,
+    /// The preferred pool trading the mint.
+    pub swap_pool: Pubkey,
+    /// Bump seed of this account.
+    pub bump: u8,
+}
+impl Sealed for AssetEntry {}
+impl IsInitialized for AssetEntry {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for AssetEntry {
+    const LEN: usize = 134;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 134];
+        let (
+            header,
+            is_initialized,
+            mint,
+            kind,
+            decimals,
+            oracle,
+            swap_program_id,
+            swap_pool,
+            bump,
+        ) = array_refs![src, 2, 1, 32, 1, 1, 32, 32, 32, 1];
+        unpack_header(header, AccountType::AssetEntry)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(AssetEntry {
+            is_initialized,
+            mint: Pubkey::new_from_array(*mint),
+            kind: AssetKind::try_from_primitive(kind[0])
+                .or(Err(ProgramError::InvalidAccountData))?,
+            decimals: decimals[0],
+            oracle: Pubkey::new_from_array(*oracle),
+            swap_program_id: Pubkey::new_from_array(*swap_program_id),
+            swap_pool: Pubkey::new_from_array(*swap_pool),
+            bump: bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 134];
+        let (
+            header_dst,
+            is_initialized_dst,
+            mint_dst,
+            kind_dst,
+            decimals_dst,
+            oracle_dst,
+            swap_program_id_dst,
+            swap_pool_dst,
+            bump_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 1, 1, 32, 32, 32, 1];
+        pack_header(AccountType::AssetEntry, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        mint_dst.copy_from_slice(self.mint.as_ref());
+        kind_dst[0] = self.kind as u8;
+        decimals_dst[0] = self.decimals;
+        oracle_dst.copy_from_slice(self.oracle.as_ref());
+        swap_program_id_dst.copy_from_slice(self.swap_program_id.as_ref());
+        swap_pool_dst.copy_from_slice(self.swap_pool.as_ref());
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Caps on the deposits into a hedged mint or a portfolio, stored at the
+/// address derived from it with `DEPOSIT_LIMITS_SEED`. A cap of zero is
+/// unlimited.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DepositLimits {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The mint or portfolio the caps apply to.
+    pub target: Pubkey,
+    /// Cap on the supply of the mint, or on the total delegated to the
+    /// portfolio.
+    pub max_supply: u64,
+    /// Cap on the amount of a single deposit.
+    pub max_deposit: u64,
+    /// Cap on the cumulative amount deposited by a single owner.
+    pub max_owner_deposit: u64,
+    /// Total delegated to the portfolio. Unused for mints, whose supply is
+    /// tracked by the mint itself.
+    pub supply: u64,
+    /// Bump seed of this account.
+    pub bump: u8,
+}
+impl DepositLimits {
+    /// Checks a deposit of `amount` by an owner who already deposited
+    /// `deposited`, bringing the supply to `new_supply`, against the caps.
+    pub fn check_deposit(
+        &self,
+        amount: u64,
+        deposited: u64,
+        new_supply: u64,
+    ) -> Result<(), ProgramError> {
+        if self.max_deposit != 0 && amount > self.max_deposit {
+            return Err(TokenError::DepositCapExceeded.into());
+        }
+        self.check_supply(new_supply)?;
+        let deposited = deposited.checked_add(amount).ok_or(TokenError::Overflow)?;
+        if self.max_owner_deposit != 0 && deposited > self.max_owner_deposit {
+            return Err(TokenError::OwnerDepositCapExceeded.into());
+        }
+        Ok(())
+    }
+
+    /// Checks that `new_supply` does not exceed the supply cap.
+    pub fn check_supply(&self, new_supply: u64) -> Result<(), ProgramError> {
+        if self.max_supply != 0 && new_supply > self.max_supply {
+            Err(TokenError::SupplyCapExceeded.into())
+        } else {
+            Ok(())
+        }
+    }
+}
+impl Sealed for DepositLimits {}
+impl IsInitialized for DepositLimits {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for DepositLimits {
+    const LEN: usize = 68;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 68];
+        let (
+            header,
+            is_initialized,
+            target,
+            max_supply,
+            max_deposit,
+            max_owner_deposit,
+            supply,
+            bump,
+        ) = array_refs![src, 2, 1, 32, 8, 8, 8, 8, 1];
+        unpack_header(header, AccountType::DepositLimits)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(DepositLimits {
+            is_initialized,
+            target: Pubkey::new_from_array(*target),
+            max_supply: u64::from_le_bytes(*max_supply),
+            max_deposit: u64::from_le_bytes(*max_deposit),
+            max_owner_deposit: u64::from_le_bytes(*max_owner_deposit),
+            supply: u64::from_le_bytes(*supply),
+            bump: bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 68];
+        let (
+            header_dst,
+            is_initialized_dst,
+            target_dst,
+            max_supply_dst,
+            max_deposit_dst,
+            max_owner_deposit_dst,
+            supply_dst,
+            bump_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 8, 8, 8, 8, 1];
+        pack_header(AccountType::DepositLimits, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        target_dst.copy_from_slice(self.target.as_ref());
+        *max_supply_dst = self.max_supply.to_le_bytes();
+        *max_deposit_dst = self.max_deposit.to_le_bytes();
+        *max_owner_deposit_dst = self.max_owner_deposit.to_le_bytes();
+        *supply_dst = self.supply.to_le_bytes();
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Cumulative amount deposited by an owner into a hedged mint or a
+/// portfolio, stored at the address derived from both with
+/// `DEPOSIT_RECORD_SEED`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DepositRecord {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The mint or portfolio deposited into.
+    pub target: Pubkey,
+    /// The owner of the deposits.
+    pub owner: Pubkey,
+    /// The cumulative amount deposited, not reduced by withdrawals.
+    pub deposited: u64,
+    /// Bump seed of this account.
+    pub bump: u8,
+}
+impl Sealed for DepositRecord {}
+impl IsInitialized for DepositRecord {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for DepositRecord {
+    const LEN: usize = 76;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 76];
+        let (header, is_initialized, target, owner, deposited, bump) =
+            array_refs![src, 2, 1, 32, 32, 8, 1];
+        unpack_header(header, AccountType::DepositRecord)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(DepositRecord {
+            is_initialized,
+            target: Pubkey::new_from_array(*target),
+            owner: Pubkey::new_from_array(*owner),
+            deposited: u64::from_le_bytes(*deposited),
+            bump: bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 76];
+        let (header_dst, is_initialized_dst, target_dst, owner_dst, deposited_dst, bump_dst) =
+            mut_array_refs![dst, 2, 1, 32, 32, 8, 1];
+        pack_header(AccountType::DepositRecord, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        target_dst.copy_from_slice(self.target.as_ref());
+        owner_dst.copy_from_slice(self.owner.as_ref());
+        *deposited_dst = self.deposited.to_le_bytes();
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Stop-loss rule of a hedged mint, stored at the address derived from the
+/// mint with `STOP_LOSS_SEED`. Once the oracle price falls to the stop, the
+/// asset vault is swapped into the stablecoin and deposits are fully hedged
+/// until the authority re-arms the rule.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StopLoss {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The price oracle of the asset.
+    pub oracle: Pubkey,
+    /// Price at or below which the rule triggers, in the units of the oracle.
+    pub stop_price: u64,
+    /// Distance of the trailing stop below the highest observed price, in
+    /// basis points. Zero disables the trailing stop.
+    pub trailing_bps: u16,
+    /// Slippage tolerated on the oracle price when the rule triggers, in
+    /// basis points.
+    pub max_slippage_bps: u16,
+    /// Number of decimals of the asset.
+    pub asset_decimals: u8,
+    /// Number of decimals of the stablecoin.
+    pub stable_decimals: u8,
+    /// Highest oracle price observed since the rule was armed.
+    pub high_price: u64,
+    /// Is `true` once the rule has triggered.
+    pub triggered: bool,
+    /// The slot the rule triggered at.
+    pub triggered_slot: u64,
+    /// The oracle price the rule triggered at.
+    pub triggered_price: u64,
+    /// The asset sold when the rule triggered.
+    pub asset_sold: u64,
+    /// The stablecoin bought when the rule triggered.
+    pub stable_bought: u64,
+    /// Bump seed of this account.
+    pub bump: u8,
+}
+impl StopLoss {
+    /// Returns the price at or below which the rule triggers, accounting for
+    /// the trailing stop.
+    pub fn effective_stop(&self) -> u64 {
+        let trailing = (self.high_price as u128)
+            .saturating_mul(10_000u128.saturating_sub(self.trailing_bps as u128))
+            / 10_000;
+        if self.trailing_bps == 0 {
+            self.stop_price
+        } else {
+            self.stop_price.max(trailing as u64)
+        }
+    }
+}
+impl Sealed for StopLoss {}
+impl IsInitialized for StopLoss {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for StopLoss {
+    const LEN: usize = 123;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 123];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header,
+            is_initialized,
+            mint,
+            oracle,
+            stop_price,
+            trailing_bps,
+            max_slippage_bps,
+            asset_decimals,
+            stable_decimals,
+            high_price,
+            triggered,
+            triggered_slot,
+            triggered_price,
+            asset_sold,
+            stable_bought,
+            bump,
+        ) = array_refs![src, 2, 1, 32, 32, 8, 2, 2, 1, 1, 8, 1, 8, 8, 8, 8, 1];
+        unpack_header(header, AccountType::StopLoss)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let triggered = match triggered {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(StopLoss {
+            is_initialized,
+            mint: Pubkey::new_from_array(*mint),
+            oracle: Pubkey::new_from_array(*oracle),
+            stop_price: u64::from_le_bytes(*stop_price),
+            trailing_bps: u16::from_le_bytes(*trailing_bps),
+            max_slippage_bps: u16::from_le_bytes(*max_slippage_bps),
+            asset_decimals: asset_decimals[0],
+            stable_decimals: stable_decimals[0],
+            high_price: u64::from_le_bytes(*high_price),
+            triggered,
+            triggered_slot: u64::from_le_bytes(*triggered_slot),
+            triggered_price: u64::from_le_bytes(*triggered_price),
+            asset_sold: u64::from_le_bytes(*asset_sold),
+            stable_bought: u64::from_le_bytes(*stable_bought),
+            bump: bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 123];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header_dst,
+            is_initialized_dst,
+            mint_dst,
+            oracle_dst,
+            stop_price_dst,
+            trailing_bps_dst,
+            max_slippage_bps_dst,
+            asset_decimals_dst,
+            stable_decimals_dst,
+            high_price_dst,
+            triggered_dst,
+            triggered_slot_dst,
+            triggered_price_dst,
+            asset_sold_dst,
+            stable_bought_dst,
+            bump_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 32, 8, 2, 2, 1, 1, 8, 1, 8, 8, 8, 8, 1];
+        pack_header(AccountType::StopLoss, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        mint_dst.copy_from_slice(self.mint.as_ref());
+        oracle_dst.copy_from_slice(self.oracle.as_ref());
+        *stop_price_dst = self.stop_price.to_le_bytes();
+        *trailing_bps_dst = self.trailing_bps.to_le_bytes();
+        *max_slippage_bps_dst = self.max_slippage_bps.to_le_bytes();
+        asset_decimals_dst[0] = self.asset_decimals;
+        stable_decimals_dst[0] = self.stable_decimals;
+        *high_price_dst = self.high_price.to_le_bytes();
+        triggered_dst[0] = self.triggered as u8;
+        *triggered_slot_dst = self.triggered_slot.to_le_bytes();
+        *triggered_price_dst = self.triggered_price.to_le_bytes();
+        *asset_sold_dst = self.asset_sold.to_le_bytes();
+        *stable_bought_dst = self.stable_bought.to_le_bytes();
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Scale of the stablecoin rewards accrued per token, see
+/// `TakeProfit::reward_per_share`.
+pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+
+/// Precision of `HedgeConfig::asset_scale` and `HedgeConfig::stable_per_base`.
+pub const HEDGE_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// Take-profit rule of a hedged mint, kept next to its hedge configuration at
+/// the address derived from the mint with `TAKE_PROFIT_SEED`. Once the oracle
+/// price exceeds the entry price by `trigger_bps`, a share of the asset vault
+/// is sold into the stablecoin and the profit over the entry price is either
+/// retained in the vaults or distributed to the holders of the mint.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TakeProfit {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The price oracle of the asset.
+    pub oracle: Pubkey,
+    /// Gain of the oracle price over the entry price that triggers a harvest,
+    /// in basis points.
+    pub trigger_bps: u16,
+    /// Share of the asset vault sold by a harvest, in basis points. Zero
+    /// disables the rule.
+    pub sell_bps: u16,
+    /// Slippage tolerated on the oracle price when harvesting, in basis
+    /// points.
+    pub max_slippage_bps: u16,
+    /// Is `true` if the profit is distributed to the holders rather than
+    /// retained in the vaults.
+    pub distribute: bool,
+    /// Number of decimals of the asset.
+    pub asset_decimals: u8,
+    /// Number of decimals of the stablecoin.
+    pub stable_decimals: u8,
+    /// Oracle price the gain is measured from, reset by every harvest.
+    pub entry_price: u64,
+    /// The exponent of the entry price.
+    pub entry_expo: i32,
+    /// Stablecoin distributed per token since the rule was set, scaled by
+    /// `REWARD_PRECISION`.
+    pub reward_per_share: u128,
+    /// Stablecoin held in the stablecoin vault for rewards not yet claimed.
+    pub reserved_rewards: u64,
+    /// Cumulative profit realized by harvests.
+    pub realized_profit: u64,
+    /// The slot of the last harvest.
+    pub last_harvest_slot: u64,
+    /// Bump seed of this account.
+    pub bump: u8,
+}
+impl TakeProfit {
+    /// Returns the oracle price at or above which a harvest may run.
+    pub fn target_price(&self) -> u64 {
+        let target = (self.entry_price as u128)
+            .saturating_mul(10_000 + self.trigger_bps as u128)
+            / 10_000;
+        u64::try_from(target).unwrap_or(u64::MAX)
+    }
+}
+impl Sealed for TakeProfit {}
+impl IsInitialized for TakeProfit {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for TakeProfit {
+    const LEN: usize = 129;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 129];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header,
+            is_initialized,
+            mint,
+            oracle,
+            trigger_bps,
+            sell_bps,
+            max_slippage_bps,
+            distribute,
+            asset_decimals,
+            stable_decimals,
+            entry_price,
+            entry_expo,
+            reward_per_share,
+            reserved_rewards,
+            realized_profit,
+            last_harvest_slot,
+            bump,
+        ) = array_refs![src, 2, 1, 32, 32, 2, 2, 2, 1, 1, 1, 8, 4, 16, 8, 8, 8, 1];
+        unpack_header(header, AccountType::TakeProfit)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let distribute = match distribute {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(TakeProfit {
+            is_initialized,
+            mint: Pubkey::new_from_array(*mint),
+            oracle: Pubkey::new_from_array(*oracle),
+            trigger_bps: u16::from_le_bytes(*trigger_bps),
+            sell_bps: u16::from_le_bytes(*sell_bps),
+            max_slippage_bps: u16::from_le_bytes(*max_slippage_bps),
+            distribute,
+            asset_decimals: asset_decimals[0],
+            stable_decimals: stable_decimals[0],
+            entry_price: u64::from_le_bytes(*entry_price),
+            entry_expo: i32::from_le_bytes(*entry_expo),
+            reward_per_share: u128::from_le_bytes(*reward_per_share),
+            reserved_rewards: u64::from_le_bytes(*reserved_rewards),
+            realized_profit: u64::from_le_bytes(*realized_profit),
+            last_harvest_slot: u64::from_le_bytes(*last_harvest_slot),
+            bump: bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 129];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header_dst,
+            is_initialized_dst,
+            mint_dst,
+            oracle_dst,
+            trigger_bps_dst,
+            sell_bps_dst,
+            max_slippage_bps_dst,
+            distribute_dst,
+            asset_decimals_dst,
+            stable_decimals_dst,
+            entry_price_dst,
+            entry_expo_dst,
+            reward_per_share_dst,
+            reserved_rewards_dst,
+            realized_profit_dst,
+            last_harvest_slot_dst,
+            bump_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 32, 2, 2, 2, 1, 1, 1, 8, 4, 16, 8, 8, 8, 1];
+        pack_header(AccountType::TakeProfit, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        mint_dst.copy_from_slice(self.mint.as_ref());
+        oracle_dst.copy_from_slice(self.oracle.as_ref());
+        *trigger_bps_dst = self.trigger_bps.to_le_bytes();
+        *sell_bps_dst = self.sell_bps.to_le_bytes();
+        *max_slippage_bps_dst = self.max_slippage_bps.to_le_bytes();
+        distribute_dst[0] = self.distribute as u8;
+        asset_decimals_dst[0] = self.asset_decimals;
+        stable_decimals_dst[0] = self.stable_decimals;
+        *entry_price_dst = self.entry_price.to_le_bytes();
+        *entry_expo_dst = self.entry_expo.to_le_bytes();
+        *reward_per_share_dst = self.reward_per_share.to_le_bytes();
+        *reserved_rewards_dst = self.reserved_rewards.to_le_bytes();
+        *realized_profit_dst = self.realized_profit.to_le_bytes();
+        *last_harvest_slot_dst = self.last_harvest_slot.to_le_bytes();
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Number of slots in one period of a portfolio asset, about a day.
+pub const DCA_PERIOD_SLOTS: u64 = 216_000;
+
+/// Dollar-cost-averaging schedule of one asset of a portfolio, stored at the
+/// address derived from the portfolio and the asset index with
+/// `DCA_CONFIG_SEED`. Depositors fund it with the asset the portfolio sells
+/// into it, which is bought into the asset in equal tranches, one per
+/// `period_slots`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DcaConfig {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The portfolio defining the schedule.
+    pub portfolio: Pubkey,
+    /// Index of the asset in the portfolio, from 1.
+    pub asset_index: u8,
+    /// The asset bought.
+    pub asset_mint: Pubkey,
+    /// The stablecoin deposited and sold into the asset.
+    pub stable_mint: Pubkey,
+    /// The swap program of the pool.
+    pub swap_program_id: Pubkey,
+    /// The pool trading `asset_mint` against `stable_mint`.
+    pub swap_pool: Pubkey,
+    /// Vault holding the asset bought, owned by the vault authority.
+    pub asset_vault: Pubkey,
+    /// Vault holding the stablecoin not yet sold, owned by the vault authority.
+    pub stable_vault: Pubkey,
+    /// The price oracle of the asset.
+    pub oracle: Pubkey,
+    /// Slippage tolerated on the oracle price when buying a tranche, in
+    /// basis points.
+    pub max_slippage_bps: u16,
+    /// Number of decimals of the asset.
+    pub asset_decimals: u8,
+    /// Number of decimals of the stablecoin.
+    pub stable_decimals: u8,
+    /// Number of slots between two tranches of a depositor.
+    pub period_slots: u64,
+    /// Number of tranches a deposit is split into.
+    pub tranches: u16,
+    /// Stablecoin of all depositors not yet sold.
+    pub total_usdc: u64,
+    /// Asset bought for all depositors and not yet withdrawn.
+    pub total_asset: u64,
+    /// Bump seed of the vault authority.
+    pub vault_authority_bump: u8,
+    /// Bump seed of this account.
+    pub bump: u8,
+}
+impl Sealed for DcaConfig {}
+impl IsInitialized for DcaConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for DcaConfig {
+    const LEN: usize = 292;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 292];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header,
+            is_initialized,
+            portfolio,
+            asset_index,
+            asset_mint,
+            stable_mint,
+            swap_program_id,
+            swap_pool,
+            asset_vault,
+            stable_vault,
+            oracle,
+            max_slippage_bps,
+            asset_decimals,
+            stable_decimals,
+            period_slots,
+            tranches,
+            total_usdc,
+            total_asset,
+            vault_authority_bump,
+            bump,
+        ) = array_refs![src, 2, 1, 32, 1, 32, 32, 32, 32, 32, 32, 32, 2, 1, 1, 8, 2, 8, 8, 1, 1];
+        unpack_header(header, AccountType::DcaConfig)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(DcaConfig {
+            is_initialized,
+            portfolio: Pubkey::new_from_array(*portfolio),
+            asset_index: asset_index[0],
+            asset_mint: Pubkey::new_from_array(*asset_mint),
+            stable_mint: Pubkey::new_from_array(*stable_mint),
+            swap_program_id: Pubkey::new_from_array(*swap_program_id),
+            swap_pool: Pubkey::new_from_array(*swap_pool),
+            asset_vault: Pubkey::new_from_array(*asset_vault),
+            stable_vault: Pubkey::new_from_array(*stable_vault),
+            oracle: Pubkey::new_from_array(*oracle),
+            max_slippage_bps: u16::from_le_bytes(*max_slippage_bps),
+            asset_decimals: asset_decimals[0],
+            stable_decimals: stable_decimals[0],
+            period_slots: u64::from_le_bytes(*period_slots),
+            tranches: u16::from_le_bytes(*tranches),
+            total_usdc: u64::from_le_bytes(*total_usdc),
+            total_asset: u64::from_le_bytes(*total_asset),
+            vault_authority_bump: vault_authority_bump[0],
+            bump: bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 292];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header_dst,
+            is_initialized_dst,
+            portfolio_dst,
+            asset_index_dst,
+            asset_mint_dst,
+            stable_mint_dst,
+            swap_program_id_dst,
+            swap_pool_dst,
+            asset_vault_dst,
+            stable_vault_dst,
+            oracle_dst,
+            max_slippage_bps_dst,
+            asset_decimals_dst,
+            stable_decimals_dst,
+            period_slots_dst,
+            tranches_dst,
+            total_usdc_dst,
+            total_asset_dst,
+            vault_authority_bump_dst,
+            bump_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 1, 32, 32, 32, 32, 32, 32, 32, 2, 1, 1, 8, 2, 8, 8, 1, 1];
+        pack_header(AccountType::DcaConfig, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        portfolio_dst.copy_from_slice(self.portfolio.as_ref());
+        asset_index_dst[0] = self.asset_index;
+        asset_mint_dst.copy_from_slice(self.asset_mint.as_ref());
+        stable_mint_dst.copy_from_slice(self.stable_mint.as_ref());
+        swap_program_id_dst.copy_from_slice(self.swap_program_id.as_ref());
+        swap_pool_dst.copy_from_slice(self.swap_pool.as_ref());
+        asset_vault_dst.copy_from_slice(self.asset_vault.as_ref());
+        stable_vault_dst.copy_from_slice(self.stable_vault.as_ref());
+        oracle_dst.copy_from_slice(self.oracle.as_ref());
+        *max_slippage_bps_dst = self.max_slippage_bps.to_le_bytes();
+        asset_decimals_dst[0] = self.asset_decimals;
+        stable_decimals_dst[0] = self.stable_decimals;
+        *period_slots_dst = self.period_slots.to_le_bytes();
+        *tranches_dst = self.tranches.to_le_bytes();
+        *total_usdc_dst = self.total_usdc.to_le_bytes();
+        *total_asset_dst = self.total_asset.to_le_bytes();
+        vault_authority_bump_dst[0] = self.vault_authority_bump;
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Deposit of one owner into a dollar-cost-averaging schedule, stored at the
+/// address derived from the `DcaConfig` and the owner with `DCA_POSITION_SEED`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DcaPosition {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The dollar-cost-averaging configuration.
+    pub dca_config: Pubkey,
+    /// The depositor.
+    pub owner: Pubkey,
+    /// Stablecoin deposited and not yet sold.
+    pub usdc: u64,
+    /// Stablecoin sold by each tranche.
+    pub tranche_amount: u64,
+    /// Asset bought and not yet withdrawn.
+    pub asset: u64,
+    /// The slot from which the next tranche may be bought.
+    pub next_execution_slot: u64,
+    /// Bump seed of this account.
+    pub bump: u8,
+}
+impl Sealed for DcaPosition {}
+impl IsInitialized for DcaPosition {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for DcaPosition {
+    const LEN: usize = 100;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 100];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (header, is_initialized, dca_config, owner, usdc, tranche_amount, asset, next_execution_slot, bump) =
+            array_refs![src, 2, 1, 32, 32, 8, 8, 8, 8, 1];
+        unpack_header(header, AccountType::DcaPosition)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(DcaPosition {
+            is_initialized,
+            dca_config: Pubkey::new_from_array(*dca_config),
+            owner: Pubkey::new_from_array(*owner),
+            usdc: u64::from_le_bytes(*usdc),
+            tranche_amount: u64::from_le_bytes(*tranche_amount),
+            asset: u64::from_le_bytes(*asset),
+            next_execution_slot: u64::from_le_bytes(*next_execution_slot),
+            bump: bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 100];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header_dst,
+            is_initialized_dst,
+            dca_config_dst,
+            owner_dst,
+            usdc_dst,
+            tranche_amount_dst,
+            asset_dst,
+            next_execution_slot_dst,
+            bump_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 32, 8, 8, 8, 8, 1];
+        pack_header(AccountType::DcaPosition, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        dca_config_dst.copy_from_slice(self.dca_config.as_ref());
+        owner_dst.copy_from_slice(self.owner.as_ref());
+        *usdc_dst = self.usdc.to_le_bytes();
+        *tranche_amount_dst = self.tranche_amount.to_le_bytes();
+        *asset_dst = self.asset.to_le_bytes();
+        *next_execution_slot_dst = self.next_execution_slot.to_le_bytes();
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Pending withdrawal request of a token account, stored at the address
+/// derived from the account with `WITHDRAW_TICKET_SEED`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WithdrawTicket {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The token account the withdrawal is requested from.
+    pub account: Pubkey,
+    /// The amount that remains to be claimed.
+    pub amount: u64,
+    /// The slot the withdrawal was requested in.
+    pub request_slot: u64,
+    /// The slot from which the withdrawal may be claimed.
+    pub claimable_slot: u64,
+    /// Bump seed of this account.
+    pub bump: u8,
+}
+impl Sealed for WithdrawTicket {}
+impl IsInitialized for WithdrawTicket {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for WithdrawTicket {
+    const LEN: usize = 60;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 60];
+        let (header, is_initialized, account, amount, request_slot, claimable_slot, bump) =
+            array_refs![src, 2, 1, 32, 8, 8, 8, 1];
+        unpack_header(header, AccountType::WithdrawTicket)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(WithdrawTicket {
+            is_initialized,
+            account: Pubkey::new_from_array(*account),
+            amount: u64::from_le_bytes(*amount),
+            request_slot: u64::from_le_bytes(*request_slot),
+            claimable_slot: u64::from_le_bytes(*claimable_slot),
+            bump: bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 60];
+        let (
+            header_dst,
+            is_initialized_dst,
+            account_dst,
+            amount_dst,
+            request_slot_dst,
+            claimable_slot_dst,
+            bump_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 8, 8, 8, 1];
+        pack_header(AccountType::WithdrawTicket, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        account_dst.copy_from_slice(self.account.as_ref());
+        *amount_dst = self.amount.to_le_bytes();
+        *request_slot_dst = self.request_slot.to_le_bytes();
+        *claimable_slot_dst = self.claimable_slot.to_le_bytes();
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Stablecoin credited over an epoch of a hedged mint that a stop-loss
+/// closed, kept at the address derived from the mint and the epoch with
+/// `HEDGE_EPOCH_SEED` for the positions last settled in that epoch.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HedgeEpoch {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// The closed epoch.
+    pub epoch: u32,
+    /// `HedgeConfig::stable_per_base` when the epoch closed.
+    pub stable_per_base: u128,
+    /// Bump seed of this account.
+    pub bump: u8,
+}
+impl Sealed for HedgeEpoch {}
+impl IsInitialized for HedgeEpoch {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for HedgeEpoch {
+    const LEN: usize = 56;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 56];
+        let (header, is_initialized, mint, epoch, stable_per_base, bump) =
+            array_refs![src, 2, 1, 32, 4, 16, 1];
+        unpack_header(header, AccountType::HedgeEpoch)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(HedgeEpoch {
+            is_initialized,
+            mint: Pubkey::new_from_array(*mint),
+            epoch: u32::from_le_bytes(*epoch),
+            stable_per_base: u128::from_le_bytes(*stable_per_base),
+            bump: bump[0],
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 56];
+        let (header_dst, is_initialized_dst, mint_dst, epoch_dst, stable_per_base_dst, bump_dst) =
+            mut_array_refs![dst, 2, 1, 32, 4, 16, 1];
+        pack_header(AccountType::HedgeEpoch, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        mint_dst.copy_from_slice(self.mint.as_ref());
+        *epoch_dst = self.epoch.to_le_bytes();
+        *stable_per_base_dst = self.stable_per_base.to_le_bytes();
+        bump_dst[0] = self.bump;
+    }
+}
+
+/// Hedge configuration of a mint, stored at the address derived from the mint
+/// with `HEDGE_CONFIG_SEED`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HedgeConfig {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The hedged mint.
+    pub mint: Pubkey,
+    /// Authority allowed to update the configuration.
+    pub authority: Pubkey,
+    /// The asset deposited against the hedged mint.
+    pub asset_mint: Pubkey,
+    /// The stablecoin the hedged share of deposits is swapped into.
+    pub stable_mint: Pubkey,
+    /// The swap program of the pool.
+    pub swap_program_id: Pubkey,
+    /// The pool trading `asset_mint` against `stable_mint`.
+    pub swap_pool: Pubkey,
+    /// Vault holding the asset, owned by the vault authority.
+    pub asset_vault: Pubkey,
+    /// Vault holding the stablecoin, owned by the vault authority.
+    pub stable_vault: Pubkey,
+    /// Asset token account receiving the fees.
+    pub fee_account: Pubkey,
+    /// Share of each deposit swapped into the stablecoin, in basis points.
+    pub target_ratio_bps: u16,
+    /// Fee charged on deposits, in basis points.
+    pub deposit_fee_bps: u16,
+    /// Fee charged on withdrawals, in basis points.
+    pub withdraw_fee_bps: u16,
+    /// Bump seed of the vault authority.
+    pub vault_authority_bump: u8,
+    /// Bump seed of this account.
+    pub bump: u8,
+    /// Asset left per unit of base by the swaps out of the asset vault in
+    /// the current epoch, scaled by `HEDGE_PRECISION`. Positions hold
+    /// `asset * HEDGE_PRECISION / asset_scale` units of base.
+    pub asset_scale: u128,
+    /// Stablecoin credited per unit of base by the swaps out of the asset
+    /// vault in the current epoch, scaled by `HEDGE_PRECISION`.
+    pub stable_per_base: u128,
+    /// Number of times a stop-loss sold the whole asset vault.
+    pub epoch: u32,
+    /// `stable_per_base` when the previous epoch closed.
+    pub previous_stable_per_base: u128,
+}
+impl HedgeConfig {
+    /// Records a swap of `asset_sold` out of the asset vault holding
+    /// `vault_asset`, crediting `stable_credited` of the stablecoin bought to
+    /// the positions. Selling the whole vault closes the epoch.
+    pub fn record_vault_swap(
+        &mut self,
+        vault_asset: u64,
+        asset_sold: u64,
+        stable_credited: u64,
+    ) -> Result<(), ProgramError> {
+        if vault_asset == 0 {
+            return Ok(());
+        }
+        let stable_per_base = (stable_credited as u128)
+            .checked_mul(self.asset_scale)
+            .map(|credited| credited / vault_asset as u128)
+            .and_then(|credited| self.stable_per_base.checked_add(credited))
+            .ok_or(TokenError::Overflow)?;
+        let asset_left = vault_asset
+            .checked_sub(asset_sold)
+            .ok_or(TokenError::Overflow)?;
+        if asset_left == 0 {
+            self.epoch = self.epoch.checked_add(1).ok_or(TokenError::Overflow)?;
+            self.previous_stable_per_base = stable_per_base;
+            self.asset_scale = HEDGE_PRECISION;
+            self.stable_per_base = 0;
+        } else {
+            self.asset_scale = self
+                .asset_scale
+                .checked_mul(asset_left as u128)
+                .map(|scale| scale / vault_asset as u128)
+                .filter(|scale| *scale > 0)
+                .ok_or(TokenError::Overflow)?;
+            self.stable_per_base = stable_per_base;
+        }
+        Ok(())
+    }
+}
+impl Sealed for HedgeConfig {}
+impl IsInitialized for HedgeConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for HedgeConfig {
+    const LEN: usize = 351;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 351];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header,
+            is_initialized,
+            mint,
+            authority,
+            asset_mint,
+            stable_mint,
+            swap_program_id,
+            swap_pool,
+            asset_vault,
+            stable_vault,
+            fee_account,
+            target_ratio_bps,
+            deposit_fee_bps,
+            withdraw_fee_bps,
+            vault_authority_bump,
+            bump,
+            asset_scale,
+            stable_per_base,
+            epoch,
+            previous_stable_per_base,
+        ) = array_refs![src, 2, 1, 32, 32, 32, 32, 32, 32, 32, 32, 32, 2, 2, 2, 1, 1, 16, 16, 4, 16];
+        unpack_header(header, AccountType::HedgeConfig)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(HedgeConfig {
+            is_initialized,
+            mint: Pubkey::new_from_array(*mint),
+            authority: Pubkey::new_from_array(*authority),
+            asset_mint: Pubkey::new_from_array(*asset_mint),
+            stable_mint: Pubkey::new_from_array(*stable_mint),
+            swap_program_id: Pubkey::new_from_array(*swap_program_id),
+            swap_pool: Pubkey::new_from_array(*swap_pool),
+            asset_vault: Pubkey::new_from_array(*asset_vault),
+            stable_vault: Pubkey::new_from_array(*stable_vault),
+            fee_account: Pubkey::new_from_array(*fee_account),
+            target_ratio_bps: u16::from_le_bytes(*target_ratio_bps),
+            deposit_fee_bps: u16::from_le_bytes(*deposit_fee_bps),
+            withdraw_fee_bps: u16::from_le_bytes(*withdraw_fee_bps),
+            vault_authority_bump: vault_authority_bump[0],
+            bump: bump[0],
+            asset_scale: u128::from_le_bytes(*asset_scale),
+            stable_per_base: u128::from_le_bytes(*stable_per_base),
+            epoch: u32::from_le_bytes(*epoch),
+            previous_stable_per_base: u128::from_le_bytes(*previous_stable_per_base),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 351];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header_dst,
+            is_initialized_dst,
+            mint_dst,
+            authority_dst,
+            asset_mint_dst,
+            stable_mint_dst,
+            swap_program_id_dst,
+            swap_pool_dst,
+            asset_vault_dst,
+            stable_vault_dst,
+            fee_account_dst,
+            target_ratio_bps_dst,
+            deposit_fee_bps_dst,
+            withdraw_fee_bps_dst,
+            vault_authority_bump_dst,
+            bump_dst,
+            asset_scale_dst,
+            stable_per_base_dst,
+            epoch_dst,
+            previous_stable_per_base_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 32, 32, 32, 32, 32, 32, 32, 32, 2, 2, 2, 1, 1, 16, 16, 4, 16];
+        pack_header(AccountType::HedgeConfig, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        mint_dst.copy_from_slice(self.mint.as_ref());
+        authority_dst.copy_from_slice(self.authority.as_ref());
+        asset_mint_dst.copy_from_slice(self.asset_mint.as_ref());
+        stable_mint_dst.copy_from_slice(self.stable_mint.as_ref());
+        swap_program_id_dst.copy_from_slice(self.swap_program_id.as_ref());
+        swap_pool_dst.copy_from_slice(self.swap_pool.as_ref());
+        asset_vault_dst.copy_from_slice(self.asset_vault.as_ref());
+        stable_vault_dst.copy_from_slice(self.stable_vault.as_ref());
+        fee_account_dst.copy_from_slice(self.fee_account.as_ref());
+        *target_ratio_bps_dst = self.target_ratio_bps.to_le_bytes();
+        *deposit_fee_bps_dst = self.deposit_fee_bps.to_le_bytes();
+        *withdraw_fee_bps_dst = self.withdraw_fee_bps.to_le_bytes();
+        vault_authority_bump_dst[0] = self.vault_authority_bump;
+        bump_dst[0] = self.bump;
+        *asset_scale_dst = self.asset_scale.to_le_bytes();
+        *stable_per_base_dst = self.stable_per_base.to_le_bytes();
+        *epoch_dst = self.epoch.to_le_bytes();
+        *previous_stable_per_base_dst = self.previous_stable_per_base.to_le_bytes();
     }
 }
 
@@ -121,10 +1494,6 @@ pub struct Account {
     pub delegated_amount: u64,
     /// Optional authority to close the account.
     pub close_authority: COption<Pubkey>,
-     /// the amount of token asset 
-    pub asset: u64,
-    /// the amount of token usdc
-    pub usdc: u64,
 }
 impl Account {
     /// Checks if account is frozen
@@ -160,11 +1529,11 @@ impl IsInitialized for Account {
 
 */
 impl Pack for Account {
-    const LEN: usize = 181;
+    const LEN: usize = 165;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, 181];
-        let (mint, owner, amount, delegate, state, is_native, delegated_amount, close_authority,asset,usdc) =
-            array_refs![src, 32, 32, 8, 36, 1, 12, 8, 36 , 8 , 8];
+        let src = array_ref![src, 0, 165];
+        let (mint, owner, amount, delegate, state, is_native, delegated_amount, close_authority) =
+            array_refs![src, 32, 32, 8, 36, 1, 12, 8, 36];
         Ok(Account {
             mint: Pubkey::new_from_array(*mint),
             owner: Pubkey::new_from_array(*owner),
@@ -175,12 +1544,10 @@ impl Pack for Account {
             is_native: unpack_coption_u64(is_native)?,
             delegated_amount: u64::from_le_bytes(*delegated_amount),
             close_authority: unpack_coption_key(close_authority)?,
-            asset: u64::from_le_bytes(*asset),
-            usdc: u64::from_le_bytes(*usdc),
         })
     }
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, 181];
+        let dst = array_mut_ref![dst, 0, 165];
         let (
             mint_dst,
             owner_dst,
@@ -190,9 +1557,7 @@ impl Pack for Account {
             is_native_dst,
             delegated_amount_dst,
             close_authority_dst,
-            asset_dst,
-            usdc_dst,
-        ) = mut_array_refs![dst, 32, 32, 8, 36, 1, 12, 8, 36,8,8];
+        ) = mut_array_refs![dst, 32, 32, 8, 36, 1, 12, 8, 36];
         let &Account {
             ref mint,
             ref owner,
@@ -202,8 +1567,6 @@ impl Pack for Account {
             ref is_native,
             delegated_amount,
             ref close_authority,
-             asset,
-             usdc,
         } = self;
         mint_dst.copy_from_slice(mint.as_ref());
         owner_dst.copy_from_slice(owner.as_ref());
@@ -213,9 +1576,6 @@ impl Pack for Account {
         pack_coption_u64(is_native, is_native_dst);
         *delegated_amount_dst = delegated_amount.to_le_bytes();
         pack_coption_key(close_authority, close_authority_dst);
-        *asset_dst = asset.to_le_bytes();
-        *usdc_dst = usdc.to_le_bytes();
-        
     }
 }
 
@@ -239,6 +1599,310 @@ impl Default for AccountState {
     }
 }
 
+/// Size of a mint created before the hedge fields moved to `HedgeConfig`.
+pub const LEGACY_MINT_LEN: usize = 154;
+/// Size of a token account created before the hedge fields moved to `Position`.
+pub const LEGACY_ACCOUNT_LEN: usize = 181;
+
+/// Hedged components backing the balance of a token account, stored at the
+/// address derived from the token account with `POSITION_SEED`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// The token account this position belongs to.
+    pub account: Pubkey,
+    /// The amount of asset backing the account balance.
+    pub asset: u64,
+    /// The amount of stablecoin backing the account balance.
+    pub usdc: u64,
+    /// Bump seed of this account.
+    pub bump: u8,
+    /// `TakeProfit::reward_per_share` of the mint when the rewards were last
+    /// settled.
+    pub reward_per_share_paid: u128,
+    /// Stablecoin rewards settled and not yet claimed.
+    pub rewards: u64,
+    /// Is `true` if the cost basis covers the whole balance, which is only
+    /// the case for positions that were empty when tracking started.
+    pub tracks_cost_basis: bool,
+    /// Stablecoin-equivalent cost of the current balance, on a weighted
+    /// average basis.
+    pub cost_basis: u64,
+    /// Total stablecoin-equivalent value deposited.
+    pub deposited: u64,
+    /// Total stablecoin-equivalent value withdrawn.
+    pub withdrawn: u64,
+    /// Profit or loss realized by withdrawals, in stablecoin.
+    pub realized_pnl: i64,
+    /// The slot of the last deposit into the account, in which it cannot be
+    /// withdrawn from.
+    pub last_deposit_slot: u64,
+    /// `HedgeConfig::asset_scale` when the vault swaps were last settled,
+    /// zero for positions migrated before it was tracked.
+    pub asset_scale_paid: u128,
+    /// `HedgeConfig::stable_per_base` when the vault swaps were last settled.
+    pub stable_per_base_paid: u128,
+    /// `HedgeConfig::epoch` when the vault swaps were last settled.
+    pub hedge_epoch: u32,
+}
+impl Position {
+    /// Starts tracking the cost basis if the position is empty.
+    pub fn start_cost_basis(&mut self, balance: u64) {
+        if balance == 0 {
+            self.tracks_cost_basis = true;
+            self.cost_basis = 0;
+            self.deposited = 0;
+            self.withdrawn = 0;
+            self.realized_pnl = 0;
+        }
+    }
+
+    /// Adds a deposit worth `value` to the cost basis.
+    pub fn record_deposit(&mut self, value: u64) -> Result<(), ProgramError> {
+        self.cost_basis = self.cost_basis.checked_add(value).ok_or(TokenError::Overflow)?;
+        self.deposited = self.deposited.checked_add(value).ok_or(TokenError::Overflow)?;
+        Ok(())
+    }
+
+    /// Removes the cost of `amount` out of `balance` and realizes its
+    /// difference with the `value` withdrawn.
+    pub fn record_withdrawal(&mut self, amount: u64, balance: u64, value: u64) -> Result<(), ProgramError> {
+        let cost = self.remove_cost(amount, balance)?;
+        let pnl = i64::try_from(value as i128 - cost as i128).map_err(|_| TokenError::Overflow)?;
+        self.realized_pnl = self.realized_pnl.checked_add(pnl).ok_or(TokenError::Overflow)?;
+        self.withdrawn = self.withdrawn.checked_add(value).ok_or(TokenError::Overflow)?;
+        Ok(())
+    }
+
+    /// Moves the cost of `amount` out of `balance` to `destination`, which
+    /// holds `destination_balance`.
+    pub fn transfer_cost_basis(
+        &mut self,
+        destination: &mut Position,
+        amount: u64,
+        balance: u64,
+        destination_balance: u64,
+    ) -> Result<(), ProgramError> {
+        let cost = self.remove_cost(amount, balance)?;
+        let tracks_cost_basis = self.tracks_cost_basis
+            && (destination.tracks_cost_basis || destination_balance == 0);
+        destination.start_cost_basis(destination_balance);
+        destination.tracks_cost_basis = tracks_cost_basis;
+        destination.cost_basis = destination
+            .cost_basis
+            .checked_add(cost)
+            .ok_or(TokenError::Overflow)?;
+        Ok(())
+    }
+
+    /// Removes and returns the cost of `amount` out of `balance`.
+    fn remove_cost(&mut self, amount: u64, balance: u64) -> Result<u64, ProgramError> {
+        let cost = if amount >= balance {
+            self.cost_basis
+        } else {
+            u64::try_from(self.cost_basis as u128 * amount as u128 / balance as u128)
+                .map_err(|_| TokenError::Overflow)?
+        };
+        self.cost_basis -= cost;
+        Ok(cost)
+    }
+
+    /// Returns the realized and unrealized profit or loss of the position in
+    /// stablecoin, the asset being valued at `price`, or `None` if the cost
+    /// basis is not tracked.
+    pub fn pnl(&self, price: &OraclePrice, asset_decimals: u8, stable_decimals: u8) -> Option<Pnl> {
+        if !self.tracks_cost_basis {
+            return None;
+        }
+        let value = price
+            .quote(self.asset, asset_decimals, stable_decimals)?
+            .checked_add(self.usdc)?;
+        Some(Pnl {
+            realized: self.realized_pnl,
+            unrealized: i64::try_from(value as i128 - self.cost_basis as i128).ok()?,
+        })
+    }
+
+    /// Returns `true` if a stop-loss closed the epoch after the one the vault
+    /// swaps were last settled in, which leaves the position to settle against
+    /// the `HedgeEpoch` of that epoch.
+    pub fn needs_hedge_epoch(&self, hedge_config: &HedgeConfig) -> bool {
+        self.asset > 0 && self.hedge_epoch.saturating_add(1) < hedge_config.epoch
+    }
+
+    /// Rescales the asset and credits the stablecoin of the swaps out of the
+    /// asset vault since the last settlement. `closed_stable_per_base` is the
+    /// `HedgeEpoch` of the last settled epoch when `needs_hedge_epoch`.
+    pub fn settle_vault_swaps(
+        &mut self,
+        hedge_config: &HedgeConfig,
+        closed_stable_per_base: Option<u128>,
+    ) -> Result<(), ProgramError> {
+        if self.asset > 0 {
+            // positions migrated before the scale was tracked were last
+            // settled at the start of the first epoch
+            let asset_scale_paid = match self.asset_scale_paid {
+                0 => HEDGE_PRECISION,
+                asset_scale_paid => asset_scale_paid,
+            };
+            let (asset, stable_per_base) = if self.hedge_epoch == hedge_config.epoch {
+                let asset = (self.asset as u128)
+                    .checked_mul(hedge_config.asset_scale)
+                    .map(|asset| asset / asset_scale_paid)
+                    .and_then(|asset| u64::try_from(asset).ok())
+                    .ok_or(TokenError::Overflow)?;
+                (asset, hedge_config.stable_per_base)
+            } else if self.hedge_epoch.saturating_add(1) == hedge_config.epoch {
+                (0, hedge_config.previous_stable_per_base)
+            } else {
+                (
+                    0,
+                    closed_stable_per_base.ok_or(TokenError::HedgeEpochRequired)?,
+                )
+            };
+            let credited = stable_per_base
+                .checked_sub(self.stable_per_base_paid)
+                .and_then(|per_base| per_base.checked_mul(self.asset as u128))
+                .map(|credited| credited / asset_scale_paid)
+                .and_then(|credited| u64::try_from(credited).ok())
+                .ok_or(TokenError::Overflow)?;
+            self.asset = asset;
+            self.usdc = self.usdc.checked_add(credited).ok_or(TokenError::Overflow)?;
+        }
+        self.asset_scale_paid = hedge_config.asset_scale;
+        self.stable_per_base_paid = hedge_config.stable_per_base;
+        self.hedge_epoch = hedge_config.epoch;
+        Ok(())
+    }
+
+    /// Credits the rewards accrued by `balance` since the last settlement,
+    /// up to `reward_per_share`.
+    pub fn settle_rewards(&mut self, balance: u64, reward_per_share: u128) -> Result<(), ProgramError> {
+        let accrued = reward_per_share
+            .checked_sub(self.reward_per_share_paid)
+            .and_then(|per_share| per_share.checked_mul(balance as u128))
+            .map(|accrued| accrued / REWARD_PRECISION)
+            .and_then(|accrued| u64::try_from(accrued).ok())
+            .ok_or(TokenError::Overflow)?;
+        self.rewards = self.rewards.checked_add(accrued).ok_or(TokenError::Overflow)?;
+        self.reward_per_share_paid = reward_per_share;
+        Ok(())
+    }
+}
+
+impl Sealed for Position {}
+impl IsInitialized for Position {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for Position {
+    const LEN: usize = 153;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 153];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header,
+            is_initialized,
+            account,
+            asset,
+            usdc,
+            bump,
+            reward_per_share_paid,
+            rewards,
+            tracks_cost_basis,
+            cost_basis,
+            deposited,
+            withdrawn,
+            realized_pnl,
+            last_deposit_slot,
+            asset_scale_paid,
+            stable_per_base_paid,
+            hedge_epoch,
+        ) = array_refs![src, 2, 1, 32, 8, 8, 1, 16, 8, 1, 8, 8, 8, 8, 8, 16, 16, 4];
+        unpack_header(header, AccountType::Position)?;
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let tracks_cost_basis = match tracks_cost_basis {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        Ok(Position {
+            is_initialized,
+            account: Pubkey::new_from_array(*account),
+            asset: u64::from_le_bytes(*asset),
+            usdc: u64::from_le_bytes(*usdc),
+            bump: bump[0],
+            reward_per_share_paid: u128::from_le_bytes(*reward_per_share_paid),
+            rewards: u64::from_le_bytes(*rewards),
+            tracks_cost_basis,
+            cost_basis: u64::from_le_bytes(*cost_basis),
+            deposited: u64::from_le_bytes(*deposited),
+            withdrawn: u64::from_le_bytes(*withdrawn),
+            realized_pnl: i64::from_le_bytes(*realized_pnl),
+            last_deposit_slot: u64::from_le_bytes(*last_deposit_slot),
+            asset_scale_paid: u128::from_le_bytes(*asset_scale_paid),
+            stable_per_base_paid: u128::from_le_bytes(*stable_per_base_paid),
+            hedge_epoch: u32::from_le_bytes(*hedge_epoch),
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 153];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            header_dst,
+            is_initialized_dst,
+            account_dst,
+            asset_dst,
+            usdc_dst,
+            bump_dst,
+            reward_per_share_paid_dst,
+            rewards_dst,
+            tracks_cost_basis_dst,
+            cost_basis_dst,
+            deposited_dst,
+            withdrawn_dst,
+            realized_pnl_dst,
+            last_deposit_slot_dst,
+            asset_scale_paid_dst,
+            stable_per_base_paid_dst,
+            hedge_epoch_dst,
+        ) = mut_array_refs![dst, 2, 1, 32, 8, 8, 1, 16, 8, 1, 8, 8, 8, 8, 8, 16, 16, 4];
+        pack_header(AccountType::Position, header_dst);
+        is_initialized_dst[0] = self.is_initialized as u8;
+        account_dst.copy_from_slice(self.account.as_ref());
+        *asset_dst = self.asset.to_le_bytes();
+        *usdc_dst = self.usdc.to_le_bytes();
+        bump_dst[0] = self.bump;
+        *reward_per_share_paid_dst = self.reward_per_share_paid.to_le_bytes();
+        *rewards_dst = self.rewards.to_le_bytes();
+        tracks_cost_basis_dst[0] = self.tracks_cost_basis as u8;
+        *cost_basis_dst = self.cost_basis.to_le_bytes();
+        *deposited_dst = self.deposited.to_le_bytes();
+        *withdrawn_dst = self.withdrawn.to_le_bytes();
+        *realized_pnl_dst = self.realized_pnl.to_le_bytes();
+        *last_deposit_slot_dst = self.last_deposit_slot.to_le_bytes();
+        *asset_scale_paid_dst = self.asset_scale_paid.to_le_bytes();
+        *stable_per_base_paid_dst = self.stable_per_base_paid.to_le_bytes();
+        *hedge_epoch_dst = self.hedge_epoch.to_le_bytes();
+    }
+}
+
+/// Profit or loss of a position, in stablecoin base units.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pnl {
+    /// Profit or loss realized by withdrawals.
+    pub realized: i64,
+    /// Profit or loss of the current balance against its cost basis.
+    pub unrealized: i64,
+}
+
 /// Account data.
 #[repr(C)]
 #[derive(Clone, /*Copy,*/ Debug, Default, PartialEq)]
@@ -335,10 +1999,25 @@ pub struct Portfolio {
     // pub assetToSoldIntoAsset10: Pubkey,
 }
 
-fn convert<T, const N: usize>(v: Vec<T>) -> [T; N] {
-    v.try_into()
-        .unwrap_or_else(|v: Vec<T>| panic!("Expected a Vec of length {} but it was {}", N, v.len()))
+impl Portfolio {
+    /// Returns the asset at `index`, from 1, with the asset sold into it and
+    /// its period, or `None` if the index is out of range.
+    pub fn asset_schedule(&self, index: u8) -> Option<(Pubkey, Pubkey, u8)> {
+        match index {
+            1 => Some((self.addressAsset1, self.assetToSoldIntoAsset1, self.periodAsset1)),
+            2 => Some((self.addressAsset2, self.assetToSoldIntoAsset2, self.periodAsset2)),
+            3 => Some((self.addressAsset3, self.assetToSoldIntoAsset3, self.periodAsset3)),
+            4 => Some((self.addressAsset4, self.assetToSoldIntoAsset4, self.periodAsset4)),
+            5 => Some((self.addressAsset5, self.assetToSoldIntoAsset5, self.periodAsset5)),
+            6 => Some((self.addressAsset6, self.assetToSoldIntoAsset6, self.periodAsset6)),
+            7 => Some((self.addressAsset7, self.assetToSoldIntoAsset7, self.periodAsset7)),
+            8 => Some((self.addressAsset8, self.assetToSoldIntoAsset8, self.periodAsset8)),
+            9 => Some((self.addressAsset9, self.assetToSoldIntoAsset9, self.periodAsset9)),
+            _ => None,
+        }
+    }
 }
+
 impl Sealed for Portfolio {}
 impl IsInitialized for Portfolio {
     fn is_initialized(&self) -> bool {
@@ -350,10 +2029,12 @@ impl IsInitialized for Portfolio {
 
 
 impl Pack for Portfolio {
-    const LEN: usize = 789;
+    const LEN: usize = 791;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
    
-        let src = array_ref![src, 0, 789];
+        let src = array_ref![src, 0, 791];
+        let (header, src) = array_refs![src, 2, 789];
+        unpack_header(header, AccountType::Portfolio)?;
     
         let (portfolio_account,creator_portfolio , metadataUrl, metadataHash, is_initialize, amountAsset1, addressAsset1, periodAsset1,
             assetToSoldIntoAsset1, amountAsset2, addressAsset2, periodAsset2,assetToSoldIntoAsset2, amountAsset3, 
@@ -428,7 +2109,9 @@ impl Pack for Portfolio {
 
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, 789];
+        let dst = array_mut_ref![dst, 0, 791];
+        let (header_dst, dst) = mut_array_refs![dst, 2, 789];
+        pack_header(AccountType::Portfolio, header_dst);
         let (
             portfolio_account_dst,
             creator_portfolio_dst,
@@ -530,10 +2213,9 @@ impl Pack for Portfolio {
         portfolio_account_dst.copy_from_slice(portfolio_account.as_ref());
         //Pubkey(creatorAccount,creator_Account_dst);
         creator_portfolio_dst.copy_from_slice(creator_portfolio.as_ref());
-        //*metadata_URL_dst = convert(metadataURL);
-        *metadata_URL_dst = convert(metadataUrl.to_vec());
-        // *metadata_URL_dst = metadataURL.borrow();
-        *metadata_URL_dst= array_ref!( metadataUrl, 0, 128).clone();/*****/
+        let url_len = metadataUrl.len().min(METADATA_URL_LEN);
+        *metadata_URL_dst = [0; METADATA_URL_LEN];
+        metadata_URL_dst[..url_len].copy_from_slice(&metadataUrl[..url_len]);
         *metadata_HASH_dst = metadataHash.to_le_bytes();
         *is_initialize_dst = is_initialize.to_le_bytes();
      
@@ -650,9 +2332,11 @@ impl IsInitialized for UserPortfolio {
 }
 }
 impl Pack for UserPortfolio {
-    const LEN: usize = 424;
+    const LEN: usize = 426;
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = array_ref![src, 0, 424];
+        let src = array_ref![src, 0, 426];
+        let (header, src) = array_refs![src, 2, 424];
+        unpack_header(header, AccountType::UserPortfolio)?;
         let (user_portfolio_account,owner, portfolio_address,delegate, delegated_amount, splu_asset1,  splu_asset2,
             splu_asset3, splu_asset4,  splu_asset5, splu_asset6,  splu_asset7, splu_asset8, splu_asset9) =
             array_refs![src,32,32, 32, 32, 8, 32, 32 , 32, 32 , 32, 32 , 32, 32 , 32 ];
@@ -680,7 +2364,9 @@ impl Pack for UserPortfolio {
 
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = array_mut_ref![dst, 0, 424];
+        let dst = array_mut_ref![dst, 0, 426];
+        let (header_dst, dst) = mut_array_refs![dst, 2, 424];
+        pack_header(AccountType::UserPortfolio, header_dst);
         let (
             user_portfolio_account_dst,
             owner_dst,
@@ -794,6 +2480,19 @@ impl Pack for Multisig {
 }
 
 // Helpers
+fn pack_header(account_type: AccountType, dst: &mut [u8; ACCOUNT_HEADER_LEN]) {
+    *dst = [account_type as u8, STATE_VERSION];
+}
+fn unpack_header(
+    src: &[u8; ACCOUNT_HEADER_LEN],
+    expected_type: AccountType,
+) -> Result<(), ProgramError> {
+    match account_type(src)? {
+        AccountType::Uninitialized => Ok(()),
+        account_type if account_type == expected_type => Ok(()),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
 fn pack_coption_key(src: &COption<Pubkey>, dst: &mut [u8; 36]) {
     let (tag, body) = mut_array_refs![dst, 4, 32];
     match src {
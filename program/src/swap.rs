@@ -0,0 +1,897 @@
+//! Token swap pool state, as laid out by the SPL Token Swap program
+
+use crate::error::TokenError;
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+use std::convert::TryFrom;
+
+/// Version byte written in front of `SwapV1` pools.
+pub const SWAP_VERSION_1: u8 = 1;
+
+/// Length of a canonical SPL Token account, used to read the mint of pool
+/// reserves and vaults.
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+
+/// Length of a canonical SPL Token mint, used to read the decimals of
+/// registered mints.
+pub const TOKEN_MINT_LEN: usize = 82;
+
+/// Tag of the `Swap` instruction of the swap program.
+pub const SWAP_INSTRUCTION_TAG: u8 = 1;
+
+/// Curve type of constant product pools.
+pub const CURVE_CONSTANT_PRODUCT: u8 = 0;
+
+/// Curve type of pools trading at a fixed price of token B in token A.
+pub const CURVE_CONSTANT_PRICE: u8 = 1;
+
+/// Curve type of stable swap pools.
+pub const CURVE_STABLE: u8 = 2;
+
+/// Curve type of constant product pools adding an offset to token B.
+pub const CURVE_OFFSET: u8 = 3;
+
+/// Newton iterations allowed to the stable swap invariant.
+const STABLE_ITERATIONS: usize = 32;
+
+/// Fees charged by a token swap pool.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Fees {
+    /// Trade fee numerator.
+    pub trade_fee_numerator: u64,
+    /// Trade fee denominator.
+    pub trade_fee_denominator: u64,
+    /// Owner trade fee numerator.
+    pub owner_trade_fee_numerator: u64,
+    /// Owner trade fee denominator.
+    pub owner_trade_fee_denominator: u64,
+    /// Owner withdraw fee numerator.
+    pub owner_withdraw_fee_numerator: u64,
+    /// Owner withdraw fee denominator.
+    pub owner_withdraw_fee_denominator: u64,
+    /// Host fee numerator.
+    pub host_fee_numerator: u64,
+    /// Host fee denominator.
+    pub host_fee_denominator: u64,
+}
+
+/// Swap pool data.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapState {
+    /// Layout version of the pool.
+    pub version: u8,
+    /// Is `true` if this structure has been initialized
+    pub is_initialized: bool,
+    /// Nonce used to derive the pool authority from the pool address.
+    pub nonce: u8,
+    /// Token program used by the pool reserves.
+    pub token_program_id: Pubkey,
+    /// Reserve account holding token A.
+    pub token_a: Pubkey,
+    /// Reserve account holding token B.
+    pub token_b: Pubkey,
+    /// Pool token mint.
+    pub pool_mint: Pubkey,
+    /// Mint of token A.
+    pub token_a_mint: Pubkey,
+    /// Mint of token B.
+    pub token_b_mint: Pubkey,
+    /// Pool token account receiving owner fees.
+    pub pool_fee_account: Pubkey,
+    /// Fees charged by the pool.
+    pub fees: Fees,
+    /// Curve type of the pool.
+    pub curve_type: u8,
+    /// Raw curve parameters.
+    pub curve_parameters: [u8; 32],
+}
+impl SwapState {
+    /// Returns the reserve account holding `mint`, if the pool trades it.
+    pub fn reserve_for_mint(&self, mint: &Pubkey) -> Option<Pubkey> {
+        if *mint == self.token_a_mint {
+            Some(self.token_a)
+        } else if *mint == self.token_b_mint {
+            Some(self.token_b)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the mint held by the `reserve` account, if it is one of the pool reserves.
+    pub fn mint_for_reserve(&self, reserve: &Pubkey) -> Option<Pubkey> {
+        if *reserve == self.token_a {
+            Some(self.token_a_mint)
+        } else if *reserve == self.token_b {
+            Some(self.token_b_mint)
+        } else {
+            None
+        }
+    }
+
+    /// Derives the pool authority from the pool address and its nonce.
+    pub fn authority(&self, swap_program_id: &Pubkey, swap: &Pubkey) -> Result<Pubkey, ProgramError> {
+        Pubkey::create_program_address(&[swap.as_ref(), &[self.nonce]], swap_program_id)
+            .map_err(|_| ProgramError::InvalidSeeds)
+    }
+}
+impl Sealed for SwapState {}
+impl IsInitialized for SwapState {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+impl Pack for SwapState {
+    const LEN: usize = 324;
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = array_ref![src, 0, 324];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            version,
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            curve_type,
+            curve_parameters,
+        ) = array_refs![src, 1, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 1, 32];
+        if version[0] != SWAP_VERSION_1 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let is_initialized = match is_initialized {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidAccountData),
+        };
+        let mut fee_values = [0u64; 8];
+        for (src, dst) in fees.chunks(8).zip(fee_values.iter_mut()) {
+            *dst = u64::from_le_bytes(*array_ref![src, 0, 8]);
+        }
+        Ok(SwapState {
+            version: version[0],
+            is_initialized,
+            nonce: nonce[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            fees: Fees {
+                trade_fee_numerator: fee_values[0],
+                trade_fee_denominator: fee_values[1],
+                owner_trade_fee_numerator: fee_values[2],
+                owner_trade_fee_denominator: fee_values[3],
+                owner_withdraw_fee_numerator: fee_values[4],
+                owner_withdraw_fee_denominator: fee_values[5],
+                host_fee_numerator: fee_values[6],
+                host_fee_denominator: fee_values[7],
+            },
+            curve_type: curve_type[0],
+            curve_parameters: *curve_parameters,
+        })
+    }
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = array_mut_ref![dst, 0, 324];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            version_dst,
+            is_initialized_dst,
+            nonce_dst,
+            token_program_id_dst,
+            token_a_dst,
+            token_b_dst,
+            pool_mint_dst,
+            token_a_mint_dst,
+            token_b_mint_dst,
+            pool_fee_account_dst,
+            fees_dst,
+            curve_type_dst,
+            curve_parameters_dst,
+        ) = mut_array_refs![dst, 1, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 1, 32];
+        version_dst[0] = self.version;
+        is_initialized_dst[0] = self.is_initialized as u8;
+        nonce_dst[0] = self.nonce;
+        token_program_id_dst.copy_from_slice(self.token_program_id.as_ref());
+        token_a_dst.copy_from_slice(self.token_a.as_ref());
+        token_b_dst.copy_from_slice(self.token_b.as_ref());
+        pool_mint_dst.copy_from_slice(self.pool_mint.as_ref());
+        token_a_mint_dst.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint_dst.copy_from_slice(self.token_b_mint.as_ref());
+        pool_fee_account_dst.copy_from_slice(self.pool_fee_account.as_ref());
+        let fees = &self.fees;
+        let fee_values = [
+            fees.trade_fee_numerator,
+            fees.trade_fee_denominator,
+            fees.owner_trade_fee_numerator,
+            fees.owner_trade_fee_denominator,
+            fees.owner_withdraw_fee_numerator,
+            fees.owner_withdraw_fee_denominator,
+            fees.host_fee_numerator,
+            fees.host_fee_denominator,
+        ];
+        for (dst, value) in fees_dst.chunks_mut(8).zip(fee_values.iter()) {
+            dst.copy_from_slice(&value.to_le_bytes());
+        }
+        curve_type_dst[0] = self.curve_type;
+        curve_parameters_dst.copy_from_slice(&self.curve_parameters);
+    }
+}
+
+/// Expected outcome of a swap through a pool.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SwapQuote {
+    /// Amount of the source token paid into the pool.
+    pub amount_in: u64,
+    /// Share of `amount_in` kept by the pool as the trade fee.
+    pub trade_fee: u64,
+    /// Share of `amount_in` paid to the pool owner.
+    pub owner_fee: u64,
+    /// Amount of the destination token received.
+    pub amount_out: u64,
+    /// Output lost to the depth of the pool against its spot price, fees
+    /// aside, in basis points.
+    pub price_impact_bps: u64,
+}
+
+impl SwapState {
+    /// Quotes a swap of `amount_in` of `source_mint` against reserves holding
+    /// `source_reserve` and `destination_reserve`, reproducing the fees and
+    /// the curve of the swap program. Amounts are rounded down like the pool
+    /// does.
+    pub fn quote(
+        &self,
+        source_mint: &Pubkey,
+        amount_in: u64,
+        source_reserve: u64,
+        destination_reserve: u64,
+    ) -> Result<SwapQuote, ProgramError> {
+        let source_is_a = if *source_mint == self.token_a_mint {
+            true
+        } else if *source_mint == self.token_b_mint {
+            false
+        } else {
+            return Err(TokenError::IncorrectSwapAccount.into());
+        };
+        let trade_fee = pool_fee(
+            amount_in,
+            self.fees.trade_fee_numerator,
+            self.fees.trade_fee_denominator,
+        )
+        .ok_or(TokenError::Overflow)?;
+        let owner_fee = pool_fee(
+            amount_in,
+            self.fees.owner_trade_fee_numerator,
+            self.fees.owner_trade_fee_denominator,
+        )
+        .ok_or(TokenError::Overflow)?;
+        let amount_less_fees = amount_in
+            .checked_sub(trade_fee)
+            .and_then(|amount| amount.checked_sub(owner_fee))
+            .ok_or(TokenError::Overflow)?;
+
+        let amount_out = self.swap_without_fees(
+            source_is_a,
+            amount_less_fees,
+            source_reserve,
+            destination_reserve,
+        )?;
+        let spot_amount_out = self.spot_amount_out(
+            source_is_a,
+            amount_less_fees,
+            source_reserve,
+            destination_reserve,
+        )?;
+        let price_impact_bps = if spot_amount_out > u128::from(amount_out) {
+            (spot_amount_out - u128::from(amount_out)) * 10_000 / spot_amount_out
+        } else {
+            0
+        };
+
+        Ok(SwapQuote {
+            amount_in,
+            trade_fee,
+            owner_fee,
+            amount_out,
+            price_impact_bps: price_impact_bps as u64,
+        })
+    }
+
+    /// Returns the first 8 bytes of the curve parameters: the price of token B
+    /// of constant price pools, the amplification of stable pools and the
+    /// token B offset of offset pools.
+    fn curve_parameter(&self) -> u64 {
+        u64::from_le_bytes(*array_ref![self.curve_parameters, 0, 8])
+    }
+
+    fn swap_without_fees(
+        &self,
+        source_is_a: bool,
+        amount: u64,
+        source_reserve: u64,
+        destination_reserve: u64,
+    ) -> Result<u64, ProgramError> {
+        let (amount, source_reserve, destination_reserve) = (
+            u128::from(amount),
+            u128::from(source_reserve),
+            u128::from(destination_reserve),
+        );
+        let amount_out = match self.curve_type {
+            CURVE_CONSTANT_PRODUCT => {
+                constant_product_swap(amount, source_reserve, destination_reserve)
+            }
+            CURVE_CONSTANT_PRICE => {
+                let token_b_price = u128::from(self.curve_parameter());
+                if source_is_a {
+                    amount.checked_div(token_b_price)
+                } else {
+                    amount.checked_mul(token_b_price)
+                }
+            }
+            CURVE_STABLE => stable_swap(
+                u128::from(self.curve_parameter()),
+                amount,
+                source_reserve,
+                destination_reserve,
+            ),
+            CURVE_OFFSET => {
+                let offset = u128::from(self.curve_parameter());
+                if source_is_a {
+                    constant_product_swap(amount, source_reserve, destination_reserve + offset)
+                } else {
+                    constant_product_swap(amount, source_reserve + offset, destination_reserve)
+                }
+            }
+            _ => return Err(ProgramError::InvalidAccountData),
+        }
+        .ok_or(TokenError::Overflow)?;
+        // An offset pool cannot pay out more than it holds.
+        let amount_out = amount_out.min(destination_reserve);
+        u64::try_from(amount_out).map_err(|_| TokenError::Overflow.into())
+    }
+
+    /// Output of `amount` at the spot price of the pool.
+    fn spot_amount_out(
+        &self,
+        source_is_a: bool,
+        amount: u64,
+        source_reserve: u64,
+        destination_reserve: u64,
+    ) -> Result<u128, ProgramError> {
+        let (source_reserve, destination_reserve) = match self.curve_type {
+            CURVE_CONSTANT_PRODUCT => (source_reserve, destination_reserve),
+            CURVE_OFFSET if source_is_a => (
+                source_reserve,
+                destination_reserve.saturating_add(self.curve_parameter()),
+            ),
+            CURVE_OFFSET => (
+                source_reserve.saturating_add(self.curve_parameter()),
+                destination_reserve,
+            ),
+            CURVE_STABLE => {
+                // The stable curve has no closed form spot price, probe it
+                // with a trade small against the reserves.
+                let probe = (source_reserve / 1_000_000).max(1);
+                let probe_out = self.swap_without_fees(
+                    source_is_a,
+                    probe,
+                    source_reserve,
+                    destination_reserve,
+                )?;
+                return Ok(u128::from(amount) * u128::from(probe_out) / u128::from(probe));
+            }
+            _ => {
+                return self
+                    .swap_without_fees(source_is_a, amount, source_reserve, destination_reserve)
+                    .map(u128::from)
+            }
+        };
+        if source_reserve == 0 {
+            return Ok(0);
+        }
+        Ok(u128::from(amount) * u128::from(destination_reserve) / u128::from(source_reserve))
+    }
+}
+
+/// Fee of `numerator / denominator` on `amount`, at least one token when the
+/// fee is set, like the swap program charges it.
+fn pool_fee(amount: u64, numerator: u64, denominator: u64) -> Option<u64> {
+    if numerator == 0 || amount == 0 {
+        return Some(0);
+    }
+    let fee = u128::from(amount)
+        .checked_mul(u128::from(numerator))?
+        .checked_div(u128::from(denominator))?;
+    u64::try_from(fee.max(1)).ok()
+}
+
+fn constant_product_swap(
+    amount: u128,
+    source_reserve: u128,
+    destination_reserve: u128,
+) -> Option<u128> {
+    destination_reserve
+        .checked_mul(amount)?
+        .checked_div(source_reserve.checked_add(amount)?)
+}
+
+/// Stable swap of two tokens with amplification `amp`: solves the invariant
+/// of the reserves, then the destination reserve matching the new source
+/// reserve. The cubes of the invariant are taken in 256 bits so reserves up
+/// to `u64::MAX` do not overflow.
+fn stable_swap(
+    amp: u128,
+    amount: u128,
+    source_reserve: u128,
+    destination_reserve: u128,
+) -> Option<u128> {
+    let leverage = amp.checked_mul(2)?;
+    if leverage == 0 || source_reserve == 0 || destination_reserve == 0 {
+        return None;
+    }
+
+    let sum = source_reserve.checked_add(destination_reserve)?;
+    let mut d = sum;
+    for _ in 0..STABLE_ITERATIONS {
+        let d_product = mul_div(
+            mul_div(d, d, source_reserve.checked_mul(2)?)?,
+            d,
+            destination_reserve.checked_mul(2)?,
+        )?;
+        let previous = d;
+        d = mul_div(
+            leverage
+                .checked_mul(sum)?
+                .checked_add(d_product.checked_mul(2)?)?,
+            d,
+            leverage
+                .checked_sub(1)?
+                .checked_mul(d)?
+                .checked_add(d_product.checked_mul(3)?)?,
+        )?;
+        if d.max(previous) - d.min(previous) <= 1 {
+            break;
+        }
+    }
+
+    let new_source_reserve = source_reserve.checked_add(amount)?;
+    let c = mul_div(
+        mul_div(d, d, new_source_reserve.checked_mul(2)?)?,
+        d,
+        leverage.checked_mul(2)?,
+    )?;
+    let b = new_source_reserve.checked_add(d.checked_div(leverage)?)?;
+    let mut y = d;
+    for _ in 0..STABLE_ITERATIONS {
+        let previous = y;
+        y = U256::mul(y, y)
+            .checked_add(c)?
+            .checked_div(y.checked_mul(2)?.checked_add(b)?.checked_sub(d)?)?;
+        if y.max(previous) - y.min(previous) <= 1 {
+            break;
+        }
+    }
+    Some(destination_reserve.saturating_sub(y))
+}
+
+/// `a * b / c` rounded down, with the product taken in 256 bits.
+fn mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+    U256::mul(a, b).checked_div(c)
+}
+
+/// Unsigned 256-bit integer, just wide enough for the products of the stable
+/// swap invariant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct U256 {
+    hi: u128,
+    lo: u128,
+}
+
+impl U256 {
+    /// Full product of `a` and `b`.
+    fn mul(a: u128, b: u128) -> Self {
+        const MASK: u128 = u64::MAX as u128;
+        let (a_hi, a_lo) = (a >> 64, a & MASK);
+        let (b_hi, b_lo) = (b >> 64, b & MASK);
+        let (cross_a, cross_b) = (a_hi * b_lo, a_lo * b_hi);
+        let (lo, carry_a) = (a_lo * b_lo).overflowing_add(cross_a << 64);
+        let (lo, carry_b) = lo.overflowing_add(cross_b << 64);
+        let hi =
+            a_hi * b_hi + (cross_a >> 64) + (cross_b >> 64) + carry_a as u128 + carry_b as u128;
+        U256 { hi, lo }
+    }
+
+    fn checked_add(self, other: u128) -> Option<Self> {
+        let (lo, carry) = self.lo.overflowing_add(other);
+        Some(U256 {
+            hi: self.hi.checked_add(carry as u128)?,
+            lo,
+        })
+    }
+
+    /// Quotient by `divisor`, `None` when it is zero or the quotient does
+    /// not fit 128 bits.
+    fn checked_div(self, divisor: u128) -> Option<u128> {
+        if divisor == 0 || self.hi >= divisor {
+            return None;
+        }
+        // Long division, the remainder staying below the divisor.
+        let (mut remainder, mut quotient) = (self.hi, 0u128);
+        for bit in (0..128).rev() {
+            let carry = remainder >> 127;
+            remainder = (remainder << 1) | ((self.lo >> bit) & 1);
+            quotient <<= 1;
+            if carry == 1 || remainder >= divisor {
+                remainder = remainder.wrapping_sub(divisor);
+                quotient |= 1;
+            }
+        }
+        Some(quotient)
+    }
+}
+
+/// Creates a `Swap` instruction for the swap program.
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    swap_program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    host_fee_pubkey: Option<&Pubkey>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let mut data = Vec::with_capacity(17);
+    data.push(SWAP_INSTRUCTION_TAG);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*swap_source_pubkey, false),
+        AccountMeta::new(*swap_destination_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+    }
+
+    Instruction {
+        program_id: *swap_program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Leading fields of an SPL Token account.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TokenAccountHeader {
+    /// The mint of the account.
+    pub mint: Pubkey,
+    /// The owner of the account.
+    pub owner: Pubkey,
+    /// The amount of tokens held by the account.
+    pub amount: u64,
+}
+
+/// Reads the leading fields of an SPL Token account owned by `token_program_id`.
+pub fn unpack_token_account(
+    token_program_id: &Pubkey,
+    owner: &Pubkey,
+    data: &[u8],
+) -> Result<TokenAccountHeader, ProgramError> {
+    if owner != token_program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if data.len() != TOKEN_ACCOUNT_LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let (mint, owner, amount) = array_refs![array_ref![data, 0, 72], 32, 32, 8];
+    Ok(TokenAccountHeader {
+        mint: Pubkey::new_from_array(*mint),
+        owner: Pubkey::new_from_array(*owner),
+        amount: u64::from_le_bytes(*amount),
+    })
+}
+
+/// Reads the decimals of an initialized SPL Token mint owned by `token_program_id`.
+pub fn unpack_token_mint_decimals(
+    token_program_id: &Pubkey,
+    owner: &Pubkey,
+    data: &[u8],
+) -> Result<u8, ProgramError> {
+    if owner != token_program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if data.len() != TOKEN_MINT_LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let (_, decimals, is_initialized) = array_refs![array_ref![data, 0, 46], 44, 1, 1];
+    if *is_initialized != [1] {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(decimals[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_unpack() {
+        let check = SwapState {
+            version: SWAP_VERSION_1,
+            is_initialized: true,
+            nonce: 254,
+            token_program_id: Pubkey::new_from_array([1; 32]),
+            token_a: Pubkey::new_from_array([2; 32]),
+            token_b: Pubkey::new_from_array([3; 32]),
+            pool_mint: Pubkey::new_from_array([4; 32]),
+            token_a_mint: Pubkey::new_from_array([5; 32]),
+            token_b_mint: Pubkey::new_from_array([6; 32]),
+            pool_fee_account: Pubkey::new_from_array([7; 32]),
+            fees: Fees {
+                trade_fee_numerator: 25,
+                trade_fee_denominator: 10_000,
+                owner_trade_fee_numerator: 5,
+                owner_trade_fee_denominator: 10_000,
+                owner_withdraw_fee_numerator: 0,
+                owner_withdraw_fee_denominator: 0,
+                host_fee_numerator: 20,
+                host_fee_denominator: 100,
+            },
+            curve_type: 0,
+            curve_parameters: [0; 32],
+        };
+        let mut packed = vec![0; SwapState::get_packed_len()];
+        SwapState::pack(check, &mut packed).unwrap();
+        assert_eq!(packed[0], SWAP_VERSION_1);
+        assert_eq!(packed[2], 254);
+        assert_eq!(SwapState::unpack(&packed).unwrap(), check);
+
+        packed[0] = 2;
+        assert_eq!(
+            SwapState::unpack(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_reserve_lookup() {
+        let state = SwapState {
+            token_a: Pubkey::new_from_array([2; 32]),
+            token_b: Pubkey::new_from_array([3; 32]),
+            token_a_mint: Pubkey::new_from_array([5; 32]),
+            token_b_mint: Pubkey::new_from_array([6; 32]),
+            ..SwapState::default()
+        };
+        assert_eq!(
+            state.reserve_for_mint(&Pubkey::new_from_array([6; 32])),
+            Some(Pubkey::new_from_array([3; 32]))
+        );
+        assert_eq!(state.reserve_for_mint(&Pubkey::new_from_array([9; 32])), None);
+        assert_eq!(
+            state.mint_for_reserve(&Pubkey::new_from_array([2; 32])),
+            Some(Pubkey::new_from_array([5; 32]))
+        );
+        assert_eq!(state.mint_for_reserve(&Pubkey::new_from_array([5; 32])), None);
+    }
+
+    #[test]
+    fn test_swap_instruction() {
+        let keys: Vec<Pubkey> = (0..12).map(|_| Pubkey::new_unique()).collect();
+        let ix = swap(
+            &keys[0], &keys[1], &keys[2], &keys[3], &keys[4], &keys[5], &keys[6], &keys[7],
+            &keys[8], &keys[9], &keys[10], Some(&keys[11]), 100, 90,
+        );
+        assert_eq!(ix.program_id, keys[0]);
+        assert_eq!(ix.data[0], SWAP_INSTRUCTION_TAG);
+        assert_eq!(ix.data[1..9], 100u64.to_le_bytes());
+        assert_eq!(ix.data[9..17], 90u64.to_le_bytes());
+        assert_eq!(ix.accounts.len(), 11);
+        assert!(ix.accounts[2].is_signer);
+        assert_eq!(ix.accounts[9].pubkey, keys[1]);
+        assert_eq!(ix.accounts[10].pubkey, keys[11]);
+    }
+
+    #[test]
+    fn test_unpack_token_account() {
+        let token_program_id = Pubkey::new_unique();
+        let mut data = vec![0; TOKEN_ACCOUNT_LEN];
+        data[..32].copy_from_slice(&[1; 32]);
+        data[32..64].copy_from_slice(&[2; 32]);
+        data[64..72].copy_from_slice(&42u64.to_le_bytes());
+        assert_eq!(
+            unpack_token_account(&token_program_id, &token_program_id, &data).unwrap(),
+            TokenAccountHeader {
+                mint: Pubkey::new_from_array([1; 32]),
+                owner: Pubkey::new_from_array([2; 32]),
+                amount: 42,
+            }
+        );
+        assert_eq!(
+            unpack_token_account(&token_program_id, &Pubkey::new_unique(), &data),
+            Err(ProgramError::IncorrectProgramId)
+        );
+        assert_eq!(
+            unpack_token_account(&token_program_id, &token_program_id, &data[..82]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    fn quote_pool(curve_type: u8, curve_parameter: u64) -> SwapState {
+        let mut curve_parameters = [0; 32];
+        curve_parameters[..8].copy_from_slice(&curve_parameter.to_le_bytes());
+        SwapState {
+            token_a_mint: Pubkey::new_from_array([5; 32]),
+            token_b_mint: Pubkey::new_from_array([6; 32]),
+            fees: Fees {
+                trade_fee_numerator: 25,
+                trade_fee_denominator: 10_000,
+                owner_trade_fee_numerator: 5,
+                owner_trade_fee_denominator: 10_000,
+                ..Fees::default()
+            },
+            curve_type,
+            curve_parameters,
+            ..SwapState::default()
+        }
+    }
+
+    #[test]
+    fn test_quote_constant_product() {
+        let pool = quote_pool(CURVE_CONSTANT_PRODUCT, 0);
+        let token_a = Pubkey::new_from_array([5; 32]);
+        let quote = pool
+            .quote(&token_a, 1_000_000, 1_000_000_000, 2_000_000_000)
+            .unwrap();
+        assert_eq!(quote.trade_fee, 2_500);
+        assert_eq!(quote.owner_fee, 500);
+        // 2e9 * 997_000 / (1e9 + 997_000)
+        assert_eq!(quote.amount_out, 1_992_013);
+        assert_eq!(quote.price_impact_bps, 9);
+
+        let quote = pool
+            .quote(&token_a, 500_000_000, 1_000_000_000, 2_000_000_000)
+            .unwrap();
+        assert_eq!(quote.price_impact_bps, 3_326);
+
+        // fees round up to one token
+        let quote = pool.quote(&token_a, 10, 1_000, 1_000).unwrap();
+        assert_eq!((quote.trade_fee, quote.owner_fee), (1, 1));
+
+        assert_eq!(
+            pool.quote(&Pubkey::new_from_array([7; 32]), 10, 1_000, 1_000),
+            Err(TokenError::IncorrectSwapAccount.into())
+        );
+        assert_eq!(
+            quote_pool(9, 0).quote(&token_a, 10, 1_000, 1_000),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_quote_constant_price_and_offset() {
+        let token_a = Pubkey::new_from_array([5; 32]);
+        let token_b = Pubkey::new_from_array([6; 32]);
+        let pool = quote_pool(CURVE_CONSTANT_PRICE, 4);
+        let quote = pool.quote(&token_a, 10_000, 0, 1_000_000).unwrap();
+        assert_eq!(quote.amount_out, 2_492);
+        assert_eq!(quote.price_impact_bps, 0);
+        let quote = pool.quote(&token_b, 10_000, 1_000_000, 1_000_000).unwrap();
+        assert_eq!(quote.amount_out, 39_880);
+
+        // the offset counts as token B liquidity, but is never paid out
+        let pool = quote_pool(CURVE_OFFSET, 1_000_000);
+        let quote = pool.quote(&token_a, 1_000_000, 1_000_000, 0).unwrap();
+        assert_eq!(quote.amount_out, 0);
+        let quote = pool.quote(&token_b, 100_000, 0, 1_000_000).unwrap();
+        assert_eq!(quote.amount_out, 90_661);
+    }
+
+    #[test]
+    fn test_quote_stable() {
+        let token_a = Pubkey::new_from_array([5; 32]);
+        let pool = quote_pool(CURVE_STABLE, 100);
+        let quote = pool
+            .quote(&token_a, 1_000_000, 1_000_000_000, 1_000_000_000)
+            .unwrap();
+        // a balanced stable pool trades close to one for one
+        assert!(quote.amount_out < 997_000);
+        assert!(quote.amount_out > 996_900);
+        assert_eq!(quote.price_impact_bps, 0);
+
+        // far less impact than a constant product pool of the same depth
+        let stable = pool
+            .quote(&token_a, 200_000_000, 1_000_000_000, 1_000_000_000)
+            .unwrap();
+        let constant_product = quote_pool(CURVE_CONSTANT_PRODUCT, 0)
+            .quote(&token_a, 200_000_000, 1_000_000_000, 1_000_000_000)
+            .unwrap();
+        assert!(stable.amount_out > constant_product.amount_out);
+        assert!(stable.price_impact_bps < 100);
+        assert!(constant_product.price_impact_bps > 1_000);
+
+        assert_eq!(
+            quote_pool(CURVE_STABLE, 0).quote(&token_a, 10, 1_000, 1_000),
+            Err(TokenError::Overflow.into())
+        );
+    }
+
+    #[test]
+    fn test_quote_stable_large_reserves() {
+        let token_a = Pubkey::new_from_array([5; 32]);
+        let pool = quote_pool(CURVE_STABLE, 100);
+        // the cubes of the invariant of reserves this deep exceed 128 bits
+        let reserve = u64::MAX / 2;
+        let quote = pool
+            .quote(&token_a, 1_000_000_000_000_000, reserve, reserve)
+            .unwrap();
+        assert!(quote.amount_out < 997_000_000_000_000);
+        assert!(quote.amount_out > 996_900_000_000_000);
+        assert_eq!(quote.price_impact_bps, 0);
+
+        let quote = pool.quote(&token_a, u64::MAX / 4, reserve, reserve).unwrap();
+        assert!(quote.amount_out < u64::MAX / 4);
+        assert!(quote.amount_out > u64::MAX / 5);
+    }
+
+    #[test]
+    fn test_u256() {
+        let product = U256::mul(u128::MAX, u128::MAX);
+        assert_eq!(
+            product,
+            U256 {
+                hi: u128::MAX - 1,
+                lo: 1,
+            }
+        );
+        assert_eq!(product.checked_div(u128::MAX), Some(u128::MAX));
+        assert_eq!(product.checked_div(u128::MAX - 1), None);
+        assert_eq!(U256::mul(u128::MAX, 3).checked_div(6), Some(u128::MAX / 2));
+        assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 90), Some(1 << 110));
+        assert_eq!(mul_div(7, 3, 2), Some(10));
+        assert_eq!(mul_div(7, 3, 0), None);
+        assert_eq!(
+            product.checked_add(u128::MAX),
+            Some(U256 {
+                hi: u128::MAX,
+                lo: 0,
+            })
+        );
+        assert_eq!(
+            U256 {
+                hi: u128::MAX,
+                lo: u128::MAX,
+            }
+            .checked_add(1),
+            None
+        );
+    }
+}
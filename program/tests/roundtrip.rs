@@ -0,0 +1,299 @@
+//! Round-trip properties of instruction and state serialization
+
+use proptest::prelude::*;
+use solana_program::{program_option::COption, program_pack::Pack, pubkey::Pubkey};
+use spl_token::{
+    instruction::{AuthorityType, TokenInstruction, METADATA_URL_LEN},
+    state::{
+        Account, AssetEntry, AssetKind, DcaConfig, DcaPosition, DepositLimits, DepositRecord,
+        HedgeConfig, HedgeEpoch, Mint, Multisig, Portfolio, Position, ProgramConfig, StopLoss,
+        TakeProfit, UserPortfolio, WithdrawTicket,
+    },
+};
+
+fn pubkey() -> impl Strategy<Value = Pubkey> {
+    any::<[u8; 32]>().prop_map(Pubkey::new_from_array)
+}
+
+fn optional_pubkey() -> impl Strategy<Value = COption<Pubkey>> {
+    prop::option::of(pubkey()).prop_map(|key| match key {
+        Some(key) => COption::Some(key),
+        None => COption::None,
+    })
+}
+
+fn authority_type() -> impl Strategy<Value = AuthorityType> {
+    prop_oneof![
+        Just(AuthorityType::MintTokens),
+        Just(AuthorityType::FreezeAccount),
+        Just(AuthorityType::AccountOwner),
+        Just(AuthorityType::CloseAccount),
+    ]
+}
+
+fn asset_kind() -> impl Strategy<Value = AssetKind> {
+    prop_oneof![Just(AssetKind::Asset), Just(AssetKind::Stablecoin)]
+}
+
+fn initialize_portfolio() -> impl Strategy<Value = TokenInstruction> {
+    (
+        prop::collection::vec(any::<u8>(), METADATA_URL_LEN),
+        any::<u16>(),
+        any::<[u8; 18]>(),
+    )
+        .prop_map(|(url, hash, a)| TokenInstruction::InitializePortfolio {
+            metaDataUrl: url,
+            metaDataHash: hash,
+            amountAsset1: a[0],
+            periodAsset1: a[1],
+            amountAsset2: a[2],
+            periodAsset2: a[3],
+            amountAsset3: a[4],
+            periodAsset3: a[5],
+            amountAsset4: a[6],
+            periodAsset4: a[7],
+            amountAsset5: a[8],
+            periodAsset5: a[9],
+            amountAsset6: a[10],
+            periodAsset6: a[11],
+            amountAsset7: a[12],
+            periodAsset7: a[13],
+            amountAsset8: a[14],
+            periodAsset8: a[15],
+            amountAsset9: a[16],
+            periodAsset9: a[17],
+        })
+}
+
+fn token_instruction() -> impl Strategy<Value = TokenInstruction> {
+    prop_oneof![
+        (any::<u8>(), pubkey(), optional_pubkey()).prop_map(
+            |(decimals, mint_authority, freeze_authority)| TokenInstruction::InitializeMint {
+                decimals,
+                mint_authority,
+                freeze_authority,
+            }
+        ),
+        Just(TokenInstruction::InitializeAccount),
+        any::<u8>().prop_map(|m| TokenInstruction::InitializeMultisig { m }),
+        any::<u64>().prop_map(|amount| TokenInstruction::Transfer { amount }),
+        any::<u64>().prop_map(|amount| TokenInstruction::Approve { amount }),
+        Just(TokenInstruction::Revoke),
+        (authority_type(), optional_pubkey()).prop_map(|(authority_type, new_authority)| {
+            TokenInstruction::SetAuthority {
+                authority_type,
+                new_authority,
+            }
+        }),
+        any::<u64>().prop_map(|amount| TokenInstruction::MintTo { amount }),
+        any::<u64>().prop_map(|amount| TokenInstruction::Burn { amount }),
+        Just(TokenInstruction::CloseAccount),
+        Just(TokenInstruction::FreezeAccount),
+        Just(TokenInstruction::ThawAccount),
+        (any::<u64>(), any::<u8>())
+            .prop_map(|(amount, decimals)| TokenInstruction::TransferChecked { amount, decimals }),
+        (any::<u64>(), any::<u8>())
+            .prop_map(|(amount, decimals)| TokenInstruction::ApproveChecked { amount, decimals }),
+        (any::<u64>(), any::<u8>())
+            .prop_map(|(amount, decimals)| TokenInstruction::MintToChecked { amount, decimals }),
+        (any::<u64>(), any::<u8>())
+            .prop_map(|(amount, decimals)| TokenInstruction::BurnChecked { amount, decimals }),
+        pubkey().prop_map(|owner| TokenInstruction::InitializeAccount2 { owner }),
+        (any::<u64>(), any::<u64>()).prop_map(|(amount, minimum_amount_out)| {
+            TokenInstruction::Deposit {
+                amount,
+                minimum_amount_out,
+            }
+        }),
+        any::<u64>().prop_map(|amount| TokenInstruction::Withdraw { amount }),
+        initialize_portfolio(),
+        any::<u64>().prop_map(|delegated_amount| TokenInstruction::createInitUserPortfolio {
+            delegated_amount
+        }),
+        any::<u64>().prop_map(|amount| TokenInstruction::ApproveUserPortfolio { amount }),
+        (any::<u16>(), any::<u16>(), any::<u16>()).prop_map(
+            |(target_ratio_bps, deposit_fee_bps, withdraw_fee_bps)| {
+                TokenInstruction::InitializeHedgedMint {
+                    target_ratio_bps,
+                    deposit_fee_bps,
+                    withdraw_fee_bps,
+                }
+            }
+        ),
+        Just(TokenInstruction::InitializePosition),
+        Just(TokenInstruction::MigrateAccount),
+        any::<u16>().prop_map(|protocol_fee_bps| TokenInstruction::InitializeProgramConfig {
+            protocol_fee_bps
+        }),
+        any::<u16>().prop_map(|protocol_fee_bps| TokenInstruction::SetProgramConfig {
+            protocol_fee_bps
+        }),
+        any::<u32>().prop_map(|paused| TokenInstruction::SetPause { paused }),
+        asset_kind().prop_map(|kind| TokenInstruction::RegisterAsset { kind }),
+        asset_kind().prop_map(|kind| TokenInstruction::UpdateAsset { kind }),
+        Just(TokenInstruction::DeregisterAsset),
+        (any::<u64>(), any::<u64>(), any::<u64>()).prop_map(
+            |(max_supply, max_deposit, max_owner_deposit)| TokenInstruction::SetDepositLimits {
+                max_supply,
+                max_deposit,
+                max_owner_deposit,
+            }
+        ),
+        Just(TokenInstruction::InitializeDepositRecord),
+        (any::<u64>(), any::<u16>(), any::<u16>()).prop_map(
+            |(stop_price, trailing_bps, max_slippage_bps)| TokenInstruction::SetStopLoss {
+                stop_price,
+                trailing_bps,
+                max_slippage_bps,
+            }
+        ),
+        any::<u64>().prop_map(|minimum_amount_out| TokenInstruction::TriggerConditions {
+            minimum_amount_out
+        }),
+        (any::<u16>(), any::<u16>(), any::<u16>(), any::<bool>()).prop_map(
+            |(trigger_bps, sell_bps, max_slippage_bps, distribute)| {
+                TokenInstruction::SetTakeProfit {
+                    trigger_bps,
+                    sell_bps,
+                    max_slippage_bps,
+                    distribute,
+                }
+            }
+        ),
+        any::<u64>().prop_map(|minimum_amount_out| TokenInstruction::HarvestProfit {
+            minimum_amount_out
+        }),
+        Just(TokenInstruction::ClaimRewards),
+        (any::<u8>(), any::<u16>(), any::<u16>()).prop_map(
+            |(asset_index, tranches, max_slippage_bps)| TokenInstruction::InitializeDca {
+                asset_index,
+                tranches,
+                max_slippage_bps,
+            }
+        ),
+        any::<u64>().prop_map(|amount| TokenInstruction::DepositDca { amount }),
+        any::<u64>().prop_map(|minimum_amount_out| TokenInstruction::ExecuteDca {
+            minimum_amount_out
+        }),
+        (any::<u64>(), any::<u64>())
+            .prop_map(|(usdc, asset)| TokenInstruction::WithdrawDca { usdc, asset }),
+        any::<u64>().prop_map(|amount| TokenInstruction::RequestWithdraw { amount }),
+        (any::<u64>(), any::<bool>()).prop_map(|(delay_slots, bypass)| {
+            TokenInstruction::SetWithdrawDelay {
+                delay_slots,
+                bypass,
+            }
+        }),
+    ]
+}
+
+/// Any state that unpacks from raw bytes must pack back into something that
+/// unpacks to the same value.
+fn check_state_roundtrip<T: Pack + Clone + PartialEq + std::fmt::Debug>(data: &[u8]) {
+    if let Ok(state) = T::unpack_unchecked(data) {
+        let mut packed = vec![0u8; T::LEN];
+        T::pack(state.clone(), &mut packed).unwrap();
+        assert_eq!(T::unpack_unchecked(&packed).unwrap(), state);
+    }
+}
+
+proptest! {
+    #[test]
+    fn instruction_roundtrip(instruction in token_instruction()) {
+        let packed = instruction.pack();
+        prop_assert_eq!(TokenInstruction::unpack(&packed), Ok(instruction));
+    }
+
+    #[test]
+    fn instruction_unpack_arbitrary_bytes(data in prop::collection::vec(any::<u8>(), 0..256)) {
+        if let Ok(instruction) = TokenInstruction::unpack(&data) {
+            let packed = instruction.pack();
+            prop_assert_eq!(TokenInstruction::unpack(&packed), Ok(instruction));
+        }
+    }
+
+    #[test]
+    fn mint_roundtrip(data in prop::collection::vec(any::<u8>(), Mint::LEN)) {
+        check_state_roundtrip::<Mint>(&data);
+    }
+
+    #[test]
+    fn account_roundtrip(data in prop::collection::vec(any::<u8>(), Account::LEN)) {
+        check_state_roundtrip::<Account>(&data);
+    }
+
+    #[test]
+    fn multisig_roundtrip(data in prop::collection::vec(any::<u8>(), Multisig::LEN)) {
+        check_state_roundtrip::<Multisig>(&data);
+    }
+
+    #[test]
+    fn hedge_config_roundtrip(data in prop::collection::vec(any::<u8>(), HedgeConfig::LEN)) {
+        check_state_roundtrip::<HedgeConfig>(&data);
+    }
+
+    #[test]
+    fn hedge_epoch_roundtrip(data in prop::collection::vec(any::<u8>(), HedgeEpoch::LEN)) {
+        check_state_roundtrip::<HedgeEpoch>(&data);
+    }
+
+    #[test]
+    fn position_roundtrip(data in prop::collection::vec(any::<u8>(), Position::LEN)) {
+        check_state_roundtrip::<Position>(&data);
+    }
+
+    #[test]
+    fn program_config_roundtrip(data in prop::collection::vec(any::<u8>(), ProgramConfig::LEN)) {
+        check_state_roundtrip::<ProgramConfig>(&data);
+    }
+
+    #[test]
+    fn asset_entry_roundtrip(data in prop::collection::vec(any::<u8>(), AssetEntry::LEN)) {
+        check_state_roundtrip::<AssetEntry>(&data);
+    }
+
+    #[test]
+    fn deposit_limits_roundtrip(data in prop::collection::vec(any::<u8>(), DepositLimits::LEN)) {
+        check_state_roundtrip::<DepositLimits>(&data);
+    }
+
+    #[test]
+    fn deposit_record_roundtrip(data in prop::collection::vec(any::<u8>(), DepositRecord::LEN)) {
+        check_state_roundtrip::<DepositRecord>(&data);
+    }
+
+    #[test]
+    fn stop_loss_roundtrip(data in prop::collection::vec(any::<u8>(), StopLoss::LEN)) {
+        check_state_roundtrip::<StopLoss>(&data);
+    }
+
+    #[test]
+    fn take_profit_roundtrip(data in prop::collection::vec(any::<u8>(), TakeProfit::LEN)) {
+        check_state_roundtrip::<TakeProfit>(&data);
+    }
+
+    #[test]
+    fn dca_config_roundtrip(data in prop::collection::vec(any::<u8>(), DcaConfig::LEN)) {
+        check_state_roundtrip::<DcaConfig>(&data);
+    }
+
+    #[test]
+    fn dca_position_roundtrip(data in prop::collection::vec(any::<u8>(), DcaPosition::LEN)) {
+        check_state_roundtrip::<DcaPosition>(&data);
+    }
+
+    #[test]
+    fn withdraw_ticket_roundtrip(data in prop::collection::vec(any::<u8>(), WithdrawTicket::LEN)) {
+        check_state_roundtrip::<WithdrawTicket>(&data);
+    }
+
+    #[test]
+    fn portfolio_roundtrip(data in prop::collection::vec(any::<u8>(), Portfolio::LEN)) {
+        check_state_roundtrip::<Portfolio>(&data);
+    }
+
+    #[test]
+    fn user_portfolio_roundtrip(data in prop::collection::vec(any::<u8>(), UserPortfolio::LEN)) {
+        check_state_roundtrip::<UserPortfolio>(&data);
+    }
+}